@@ -0,0 +1,533 @@
+//! Tamper-evident audit log.
+//!
+//! Security-relevant events — client connections, privileged commands, config changes and
+//! service restarts — are appended to a JSON-lines file. Each record carries the SHA-256 of
+//! the record before it, so editing, removing or reordering records breaks the chain, which
+//! queries and the `audit` health component report. Records cut off the end of the file can't
+//! be detected from the file alone; keep a copy of the reported `head` hash elsewhere (e.g. in
+//! a monitoring job) to catch that.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use shq_auth::token_matches;
+use shq_config::changed_sections;
+use shq_protocol::ComponentHealth;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+/// `prev_hash` of the first record
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Records returned by a query that doesn't set `limit`
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+
+/// Most records a single query returns
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// `audit` config section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Record events (on by default)
+    pub enabled: bool,
+    /// Log file (default `audit.jsonl` next to the config file); read at startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Reading the log over the service's API
+    pub query: AuditQueryConfig,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            query: AuditQueryConfig::default(),
+        }
+    }
+}
+
+/// `audit.query` config section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditQueryConfig {
+    /// Allow clients to read the audit log (off by default)
+    pub enabled: bool,
+    /// Token clients must present (none = any client may read the log)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl AuditQueryConfig {
+    /// Check whether a client presenting `token` may read the audit log
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        if !self.enabled {
+            bail!("Audit log queries are disabled");
+        }
+
+        match &self.token {
+            Some(expected) if !token_matches(expected, token) => bail!("Invalid audit token"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What an audit record is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// Service started or stopped
+    Service,
+    /// Client connected or disconnected
+    Connection,
+    /// Privileged command (door movement, CNC settings, display power, alarms, config import)
+    Command,
+    /// Config file changed
+    Config,
+    /// Hardware state change (door state, alarms, faults)
+    State,
+}
+
+impl AuditKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::Service => "service",
+            AuditKind::Connection => "connection",
+            AuditKind::Command => "command",
+            AuditKind::Config => "config",
+            AuditKind::State => "state",
+        }
+    }
+}
+
+impl FromStr for AuditKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "service" => Ok(AuditKind::Service),
+            "connection" => Ok(AuditKind::Connection),
+            "command" => Ok(AuditKind::Command),
+            "config" => Ok(AuditKind::Config),
+            "state" => Ok(AuditKind::State),
+            _ => bail!(
+                "Unknown audit kind '{}' (service, connection, command, config, state)",
+                s
+            ),
+        }
+    }
+}
+
+/// An event to record; the log adds sequence number, time and hashes
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    kind: AuditKind,
+    action: String,
+    client: Option<String>,
+    detail: Option<Value>,
+    error: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditKind, action: impl Into<String>) -> Self {
+        Self {
+            kind,
+            action: action.into(),
+            client: None,
+            detail: None,
+            error: None,
+        }
+    }
+
+    /// Who caused the event: a client address, or e.g. `schedule` for internal triggers
+    pub fn client(mut self, client: impl Display) -> Self {
+        self.client = Some(client.to_string());
+        self
+    }
+
+    /// Command arguments or other context; never put secrets here
+    pub fn detail(mut self, detail: Value) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Mark the event as refused or failed
+    pub fn failed(mut self, error: impl Display) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position in the log, starting at 1
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub service: String,
+    pub kind: AuditKind,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Value>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// `hash` of the previous record ([`GENESIS_HASH`] for the first)
+    pub prev_hash: String,
+    /// SHA-256 (hex) of this record serialized with an empty `hash`
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> String {
+        let unsigned = AuditRecord {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&unsigned).unwrap_or_default();
+        format!("{:x}", Sha256::digest(&json))
+    }
+}
+
+/// Which records to return
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    /// Only records after this sequence number, oldest first (default: the newest records)
+    pub after_seq: Option<u64>,
+    /// Only records written at or after this time (ms since the Unix epoch)
+    pub since_ms: Option<u64>,
+    /// Most records to return (default 100, at most 1000)
+    pub limit: Option<usize>,
+    /// Only records of these kinds (all kinds if empty)
+    pub kinds: Vec<AuditKind>,
+}
+
+/// Result of checking the whole hash chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStatus {
+    /// Every record is intact and in order
+    pub valid: bool,
+    /// Records in the log
+    pub records: u64,
+    /// Hash of the last record; compare with a copy kept elsewhere to detect truncation
+    pub head: String,
+    /// First problem found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Records matching a query, with the state of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub records: Vec<AuditRecord>,
+    pub chain: ChainStatus,
+}
+
+struct Writer {
+    path: PathBuf,
+    file: Option<File>,
+    seq: u64,
+    head: String,
+    /// The existing file ended mid-line, so the next record starts with a newline
+    needs_newline: bool,
+    /// Problem found in the existing chain when the log was opened
+    chain_error: Option<String>,
+    /// Last failure to open or write the file
+    write_error: Option<String>,
+}
+
+impl Writer {
+    fn new(path: PathBuf) -> Self {
+        let mut writer = Self {
+            path,
+            file: None,
+            seq: 0,
+            head: GENESIS_HASH.to_string(),
+            needs_newline: false,
+            chain_error: None,
+            write_error: None,
+        };
+        if let Err(e) = writer.open() {
+            tracing::error!("Audit log unavailable: {:#}", e);
+            writer.write_error = Some(format!("{:#}", e));
+        }
+        writer
+    }
+
+    /// Pick up the chain where the existing file ends and open it for appending
+    fn open(&mut self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+
+        if self.path.exists() {
+            let contents = std::fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read {:?}", self.path))?;
+            let (records, chain) = verify(&contents);
+            if let Some(error) = &chain.error {
+                tracing::error!(
+                    "Audit log {:?} has been tampered with: {}",
+                    self.path,
+                    error
+                );
+            }
+            self.seq = records.last().map_or(0, |r| r.seq);
+            self.head = chain.head;
+            self.chain_error = chain.error;
+            self.needs_newline = !contents.is_empty() && !contents.ends_with('\n');
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {:?}", self.path))?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn append(&mut self, record: &AuditRecord) -> Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let Some(file) = self.file.as_mut() else {
+            bail!("Audit log is not open");
+        };
+
+        let mut line = String::new();
+        if self.needs_newline {
+            line.push('\n');
+        }
+        line.push_str(&serde_json::to_string(record)?);
+        line.push('\n');
+
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        self.needs_newline = false;
+        Ok(())
+    }
+}
+
+/// Append-only, hash-chained audit log; clones share the file
+#[derive(Clone)]
+pub struct AuditLog {
+    service: &'static str,
+    writer: Option<Arc<Mutex<Writer>>>,
+}
+
+impl AuditLog {
+    /// Open the log selected by `config`, or `default_path` if it doesn't set one
+    ///
+    /// Never fails: if the file can't be opened the problem is logged, reported by
+    /// [`AuditLog::health`] and opening is retried on the next record.
+    pub fn open(service: &'static str, config: &AuditConfig, default_path: PathBuf) -> Self {
+        let writer = config.enabled.then(|| {
+            let path = config.path.clone().unwrap_or(default_path);
+            tracing::info!("Audit log: {:?}", path);
+            Arc::new(Mutex::new(Writer::new(path)))
+        });
+        Self { service, writer }
+    }
+
+    /// Append an event; failures are logged, never returned, so auditing can't block commands
+    pub fn record(&self, event: AuditEvent) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let mut writer = writer.lock().unwrap();
+
+        let mut record = AuditRecord {
+            seq: writer.seq + 1,
+            timestamp_ms: now_ms(),
+            service: self.service.to_string(),
+            kind: event.kind,
+            action: event.action,
+            client: event.client,
+            detail: event.detail,
+            success: event.error.is_none(),
+            error: event.error,
+            prev_hash: writer.head.clone(),
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+
+        match writer.append(&record) {
+            Ok(()) => {
+                tracing::debug!(
+                    "Audit {}: {} {} by {}",
+                    record.seq,
+                    record.kind.as_str(),
+                    record.action,
+                    record.client.as_deref().unwrap_or("local")
+                );
+                writer.seq = record.seq;
+                writer.head = record.hash;
+                writer.write_error = None;
+            }
+            Err(e) => {
+                tracing::error!("Failed to write audit record '{}': {:#}", record.action, e);
+                writer.write_error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Records matching `query`, after verifying the whole chain
+    pub fn query(&self, query: &AuditQuery) -> Result<AuditPage> {
+        let Some(writer) = &self.writer else {
+            bail!("Audit log is disabled");
+        };
+
+        // Hold the lock so a record being written isn't read half-finished
+        let writer = writer.lock().unwrap();
+        let contents = read_log(&writer.path)?;
+        drop(writer);
+
+        let (records, chain) = verify(&contents);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .min(MAX_QUERY_LIMIT);
+        let matching = records
+            .into_iter()
+            .filter(|r| query.after_seq.is_none_or(|after| r.seq > after))
+            .filter(|r| query.since_ms.is_none_or(|since| r.timestamp_ms >= since))
+            .filter(|r| query.kinds.is_empty() || query.kinds.contains(&r.kind));
+
+        let records = match query.after_seq {
+            Some(_) => matching.take(limit).collect(),
+            None => {
+                let all: Vec<AuditRecord> = matching.collect();
+                let skip = all.len().saturating_sub(limit);
+                all.into_iter().skip(skip).collect()
+            }
+        };
+        Ok(AuditPage { records, chain })
+    }
+
+    /// `audit` health component (`None` when auditing is disabled)
+    ///
+    /// Degraded if records can't be written or the chain was broken when the log was opened.
+    pub fn health(&self) -> Option<ComponentHealth> {
+        let writer = self.writer.as_ref()?.lock().unwrap();
+        let health = if let Some(error) = &writer.write_error {
+            ComponentHealth::degraded("audit", format!("Cannot write audit log: {}", error))
+        } else if let Some(error) = &writer.chain_error {
+            ComponentHealth::degraded("audit", format!("Audit chain broken: {}", error))
+        } else {
+            ComponentHealth::ok("audit")
+        };
+        Some(health)
+    }
+
+    /// Record a `config_changed` event naming the top-level sections that differ whenever a
+    /// new config is published on `rx` (API updates, imports and hand edits alike)
+    pub fn watch_config<T>(&self, mut rx: watch::Receiver<T>)
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        if self.writer.is_none() {
+            return;
+        }
+
+        let audit = self.clone();
+        tokio::spawn(async move {
+            let mut current = serde_json::to_value(&*rx.borrow_and_update()).unwrap_or_default();
+            while rx.changed().await.is_ok() {
+                let new = serde_json::to_value(&*rx.borrow_and_update()).unwrap_or_default();
+                let sections = changed_sections(&current, &new);
+                if !sections.is_empty() {
+                    audit.record(
+                        AuditEvent::new(AuditKind::Config, "config_changed")
+                            .detail(json!({ "sections": sections })),
+                    );
+                }
+                current = new;
+            }
+        });
+    }
+}
+
+fn read_log(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    }
+}
+
+/// Parse every record and check sequence numbers and hashes, reporting the first problem
+///
+/// Checking carries on from each readable record, so one bad line is reported once rather
+/// than breaking everything after it.
+fn verify(contents: &str) -> (Vec<AuditRecord>, ChainStatus) {
+    let mut records: Vec<AuditRecord> = Vec::new();
+    let mut error = None;
+    let mut head = GENESIS_HASH.to_string();
+    let mut last_seq = 0;
+
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: AuditRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => {
+                error.get_or_insert_with(|| format!("line {}: unreadable record ({})", i + 1, e));
+                continue;
+            }
+        };
+
+        let problem = if record.seq != last_seq + 1 {
+            Some(format!(
+                "expected record {}, found {}",
+                last_seq + 1,
+                record.seq
+            ))
+        } else if record.prev_hash != head {
+            Some(format!(
+                "record {} does not follow the previous record",
+                record.seq
+            ))
+        } else if record.hash != record.compute_hash() {
+            Some(format!("record {} has been modified", record.seq))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            error.get_or_insert(format!("line {}: {}", i + 1, problem));
+        }
+
+        last_seq = record.seq;
+        head = record.hash.clone();
+        records.push(record);
+    }
+
+    let chain = ChainStatus {
+        valid: error.is_none(),
+        records: records.len() as u64,
+        head,
+        error,
+    };
+    (records, chain)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,165 @@
+//! Token authentication with roles for service APIs.
+//!
+//! Clients present a token when they connect; the `auth` config section maps each token to a
+//! name and a [`Role`]. Services check the role a request needs against the client's
+//! [`Identity`] before running it, so e.g. a dashboard can watch status with a `read` token
+//! while only `control` tokens may move hardware or change settings.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// What a client may do; each role includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Status, health and other queries
+    Read,
+    /// Commands that move hardware or change settings
+    Control,
+    /// Raw access for diagnostics, e.g. a hardware controller's console; never granted
+    /// without a token or an explicit anonymous role
+    Advanced,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Read => "read",
+            Role::Control => "control",
+            Role::Advanced => "advanced",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `auth` config section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Require clients to present a token (off by default: every client has `control`)
+    pub enabled: bool,
+    /// Role of clients that connect without a token (none = they are rejected)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymous: Option<Role>,
+    /// Accepted tokens
+    pub tokens: Vec<ApiToken>,
+}
+
+/// A client token and the role it grants
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Who holds the token (e.g. "hall-dashboard"); recorded in the audit log
+    pub name: String,
+    pub token: String,
+    pub role: Role,
+}
+
+impl AuthConfig {
+    /// Check names and tokens are set and unique; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut tokens = HashSet::new();
+        for token in &self.tokens {
+            if token.name.trim().is_empty() {
+                bail!("auth.tokens: every token needs a name");
+            }
+            if token.token.is_empty() {
+                bail!("auth.tokens '{}': token must not be empty", token.name);
+            }
+            if !names.insert(token.name.as_str()) {
+                bail!("auth.tokens: duplicate name '{}'", token.name);
+            }
+            if !tokens.insert(token.token.as_str()) {
+                bail!("auth.tokens '{}': token is used more than once", token.name);
+            }
+        }
+        if self.enabled && self.tokens.is_empty() && self.anonymous.is_none() {
+            bail!(
+                "auth is enabled without tokens or an anonymous role, so no client could connect"
+            );
+        }
+        Ok(())
+    }
+
+    /// Identify a client by the token it presented
+    ///
+    /// With auth disabled everyone gets `control`. An unknown token is always refused, even
+    /// when anonymous clients are allowed, so a mistyped token doesn't silently lose access.
+    pub fn authenticate(&self, token: Option<&str>) -> Result<Identity> {
+        if !self.enabled {
+            return Ok(Identity::unrestricted());
+        }
+
+        match token {
+            Some(presented) => self
+                .tokens
+                .iter()
+                .find(|t| constant_time_eq(t.token.as_bytes(), presented.as_bytes()))
+                .map(|t| Identity {
+                    name: Some(t.name.clone()),
+                    role: t.role,
+                })
+                .ok_or_else(|| anyhow::anyhow!("Invalid token")),
+            None => match self.anonymous {
+                Some(role) => Ok(Identity { name: None, role }),
+                None => bail!("Token required"),
+            },
+        }
+    }
+}
+
+/// Who a client is and what it may do
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    /// Name of the client's token (`None` for anonymous clients or with auth disabled)
+    pub name: Option<String>,
+    pub role: Role,
+}
+
+impl Identity {
+    /// Identity of every client while auth is disabled
+    pub fn unrestricted() -> Self {
+        Self {
+            name: None,
+            role: Role::Control,
+        }
+    }
+
+    /// Check the client has at least `role`
+    pub fn require(&self, role: Role) -> Result<()> {
+        if self.role < role {
+            bail!("Requires the {} role (client has {})", role, self.role);
+        }
+        Ok(())
+    }
+
+    /// Label for logs and audit records: `<name>@<addr>`, or just `addr` without a token
+    pub fn label(&self, addr: impl fmt::Display) -> String {
+        match &self.name {
+            Some(name) => format!("{}@{}", name, addr),
+            None => addr.to_string(),
+        }
+    }
+}
+
+/// Whether `presented` is the single `expected` token (log stream, backup, audit queries, ...)
+///
+/// Compared in constant time, as client tokens are.
+pub fn token_matches(expected: &str, presented: Option<&str>) -> bool {
+    presented.is_some_and(|presented| constant_time_eq(expected.as_bytes(), presented.as_bytes()))
+}
+
+/// Compare without returning early, so response times don't reveal how much of a token matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,124 @@
+//! Config export and restore.
+//!
+//! A [`ConfigArchive`] holds the full effective configuration of one or more services, keyed
+//! by name (the service name, or the peer name when a hub collects its peers). Services export
+//! their own entry and restore from whichever entry is theirs, so reflashing a device only
+//! needs the archive. Archives contain secrets (AWS keys, MQTT passwords, tokens); exports and
+//! imports are off unless the service's `backup` section enables them.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shq_auth::token_matches;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Archive layout written by this build
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// `backup` config section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Allow clients to export and import the config (off by default)
+    pub enabled: bool,
+    /// Token clients must present (none = any client may export and import)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl BackupConfig {
+    /// Check whether a client presenting `token` may export or import the config
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        if !self.enabled {
+            bail!("Config backup is disabled");
+        }
+
+        match &self.token {
+            Some(expected) if !token_matches(expected, token) => bail!("Invalid backup token"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Configs of one or more services
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigArchive {
+    pub format: u32,
+    /// Milliseconds since the Unix epoch
+    pub created_ms: u64,
+    pub entries: BTreeMap<String, ConfigEntry>,
+}
+
+/// One service's configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    /// Service that wrote the entry (`dosa`, `nyx`, `overwatch`)
+    pub service: String,
+    /// Version of the service that wrote it
+    pub version: String,
+    /// The config file's contents, including `config_version`
+    pub config: Value,
+}
+
+impl Default for ConfigArchive {
+    fn default() -> Self {
+        Self {
+            format: ARCHIVE_FORMAT,
+            created_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigArchive {
+    /// An archive holding a single entry, named after its service
+    pub fn single(entry: ConfigEntry) -> Self {
+        let mut archive = Self::default();
+        archive.entries.insert(entry.service.clone(), entry);
+        archive
+    }
+
+    /// The entry `service` should restore: `name` if given, otherwise its only entry
+    pub fn entry_for(&self, service: &str, name: Option<&str>) -> Result<&ConfigEntry> {
+        if self.format > ARCHIVE_FORMAT {
+            bail!(
+                "Archive format {} is newer than this build supports ({})",
+                self.format,
+                ARCHIVE_FORMAT
+            );
+        }
+
+        let entry = match name {
+            Some(name) => self
+                .entries
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("Archive has no entry '{}'", name))?,
+            None => {
+                let mut matching = self.entries.values().filter(|e| e.service == service);
+                match (matching.next(), matching.next()) {
+                    (Some(entry), None) => entry,
+                    (None, _) => bail!("Archive has no {} entry", service),
+                    (Some(_), Some(_)) => {
+                        bail!(
+                            "Archive has several {} entries; choose one by name",
+                            service
+                        )
+                    }
+                }
+            }
+        };
+
+        if entry.service != service {
+            bail!(
+                "Archive entry is a {} config, not {}",
+                entry.service,
+                service
+            );
+        }
+        Ok(entry)
+    }
+}
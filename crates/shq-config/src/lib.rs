@@ -0,0 +1,447 @@
+//! Shared configuration management for SHQ services.
+//!
+//! Handles XDG path resolution, YAML/JSON (de)serialisation, writing a default config on
+//! first run, validation, schema migrations (see [`migrate`]), export/restore (see
+//! [`backup`]), and hot reload with change notifications.
+
+pub mod backup;
+pub mod migrate;
+
+pub use backup::{BackupConfig, ConfigArchive, ConfigEntry};
+pub use migrate::Migration;
+
+use anyhow::{bail, Context, Result};
+use directories::ProjectDirs;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+
+/// A service configuration that can be managed by [`ConfigManager`]
+///
+/// The struct needs a `config_version: u32` field (serialized first by convention) so files
+/// record the schema version they were written with.
+pub trait Configuration:
+    Serialize + DeserializeOwned + Default + Clone + Send + Sync + 'static
+{
+    /// Schema version written by this build; bump it and add a migration whenever settings
+    /// are renamed or removed
+    const VERSION: u32 = 1;
+
+    /// Upgrade steps for older files: `migrations()[n]` turns a version `n` document into
+    /// version `n + 1`. Versions without a step are upgraded unchanged.
+    fn migrations() -> Vec<Migration> {
+        Vec::new()
+    }
+
+    /// Validate the configuration. Invalid configs are never applied or saved.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// On-disk config format, detected from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// Detect format from a path - `.json` is JSON, anything else is YAML
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+
+    // YAML enums with data are written as single-key maps (`move: {percent: 50}`), the same
+    // shape as in JSON, rather than serde_yaml's default `!move` tags
+    fn parse<T: DeserializeOwned>(&self, contents: &str) -> Result<T> {
+        match self {
+            Format::Yaml => Ok(serde_yaml::with::singleton_map_recursive::deserialize(
+                serde_yaml::Deserializer::from_str(contents),
+            )?),
+            Format::Json => Ok(serde_json::from_str(contents)?),
+        }
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        match self {
+            Format::Yaml => {
+                let mut out = Vec::new();
+                serde_yaml::with::singleton_map_recursive::serialize(
+                    value,
+                    &mut serde_yaml::Serializer::new(&mut out),
+                )?;
+                Ok(String::from_utf8(out)?)
+            }
+            Format::Json => Ok(serde_json::to_string_pretty(value)?),
+        }
+    }
+}
+
+/// Get the XDG-compliant config path for an application: ~/.config/<app>/<file_name>
+pub fn xdg_config_path(app_name: &str, file_name: &str) -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("", "", app_name)
+        .context("Failed to determine config directory")?;
+
+    Ok(proj_dirs.config_dir().join(file_name))
+}
+
+/// Configuration manager for persistent storage
+///
+/// The current config lives in a watch channel, so every clone of the manager and every
+/// subscriber sees the same value. Changes made through [`ConfigManager::update`] or picked
+/// up from disk by [`ConfigManager::watch`] are published to subscribers.
+pub struct ConfigManager<T: Configuration> {
+    path: PathBuf,
+    format: Format,
+    tx: Arc<watch::Sender<T>>,
+    last_modified: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl<T: Configuration> Clone for ConfigManager<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            format: self.format,
+            tx: self.tx.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
+impl<T: Configuration> ConfigManager<T> {
+    /// Load config from the XDG config directory (~/.config/<app>/<file_name>)
+    pub async fn load_xdg(app_name: &str, file_name: &str) -> Result<Self> {
+        Self::load(xdg_config_path(app_name, file_name)?).await
+    }
+
+    /// Load config from disk, or create a default config if it doesn't exist
+    pub async fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let format = Format::from_path(&path);
+
+        // Ensure config directory exists
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .await
+                .context("Failed to create config directory")?;
+        }
+
+        let config = if path.exists() {
+            let (config, migrated_from) = Self::read(&path, format).await?;
+            config
+                .validate()
+                .with_context(|| format!("Invalid configuration in {:?}", path))?;
+
+            if let Some(from) = migrated_from {
+                Self::save_migrated(&path, format, from, &config).await?;
+            }
+
+            tracing::info!("Loaded configuration from {:?}", path);
+            config
+        } else {
+            tracing::info!("Config file not found, creating default at {:?}", path);
+            let config = Self::stamp_version(T::default())?;
+
+            let contents = format
+                .serialize(&config)
+                .context("Failed to serialize default config")?;
+            fs::write(&path, contents)
+                .await
+                .context("Failed to write default config")?;
+
+            config
+        };
+
+        let (tx, _) = watch::channel(config);
+        let manager = Self {
+            path,
+            format,
+            tx: Arc::new(tx),
+            last_modified: Arc::new(Mutex::new(None)),
+        };
+        manager.record_modified().await;
+
+        Ok(manager)
+    }
+
+    /// Read and parse a config file, upgrading it if it has an older schema version
+    ///
+    /// Returns the config and, if it was migrated, the version it was migrated from.
+    async fn read(path: &Path, format: Format) -> Result<(T, Option<u32>)> {
+        let contents = fs::read_to_string(path)
+            .await
+            .context("Failed to read config file")?;
+
+        let mut doc: Value = format
+            .parse(&contents)
+            .context("Failed to parse config file")?;
+        let version = migrate::version_of(&doc);
+
+        let (config, migrated_from): (T, _) = if version < T::VERSION {
+            migrate::upgrade::<T>(&mut doc, version).context("Failed to migrate config file")?;
+            // Parsed from text rather than the document so errors keep line numbers
+            let upgraded = format.serialize(&doc)?;
+            let config = format
+                .parse(&upgraded)
+                .context("Failed to parse migrated config file")?;
+            (config, Some(version))
+        } else {
+            if version > T::VERSION {
+                tracing::warn!(
+                    "{:?} is config version {}, newer than this build's {}; unknown settings will be lost if it is saved",
+                    path,
+                    version,
+                    T::VERSION
+                );
+            }
+            let config = format
+                .parse(&contents)
+                .context("Failed to parse config file")?;
+            (config, None)
+        };
+
+        for field in migrate::unknown_fields(&doc, &serde_json::to_value(&config)?) {
+            tracing::warn!("Ignoring unknown setting {} in {:?}", field, path);
+        }
+
+        Ok((config, migrated_from))
+    }
+
+    /// Keep the pre-migration file as `<file>.v<N>.bak` and write the upgraded config
+    async fn save_migrated(path: &Path, format: Format, from: u32, config: &T) -> Result<()> {
+        let backup = with_suffix(path, &format!(".v{}.bak", from));
+
+        fs::copy(path, &backup)
+            .await
+            .with_context(|| format!("Failed to back up config to {:?}", backup))?;
+        let contents = format
+            .serialize(config)
+            .context("Failed to serialize migrated config")?;
+        fs::write(path, contents)
+            .await
+            .context("Failed to write migrated config")?;
+
+        tracing::warn!(
+            "Upgraded {:?} from config version {} to {}; the previous file is saved as {:?}",
+            path,
+            from,
+            T::VERSION,
+            backup
+        );
+        Ok(())
+    }
+
+    /// Set `config_version` on a config that didn't come from a file
+    fn stamp_version(config: T) -> Result<T> {
+        let mut doc = serde_json::to_value(&config)?;
+        if let Some(object) = doc.as_object_mut() {
+            object.insert(migrate::VERSION_FIELD.to_string(), Value::from(T::VERSION));
+        }
+        Ok(serde_json::from_value(doc)?)
+    }
+
+    /// Remember the file's modification time so our own writes don't trigger a reload
+    async fn record_modified(&self) {
+        let modified = fs::metadata(&self.path)
+            .await
+            .and_then(|meta| meta.modified())
+            .ok();
+        *self.last_modified.lock().unwrap() = modified;
+    }
+
+    /// Path of the backing config file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get a copy of the current configuration
+    pub fn get(&self) -> T {
+        self.tx.borrow().clone()
+    }
+
+    /// Short fingerprint of the current configuration (16 hex digits)
+    ///
+    /// Stable across runs and builds, so crash reports from different runs can be compared.
+    pub fn hash(&self) -> String {
+        // Via `Value` so map keys are sorted whatever the config's map types are
+        let json = serde_json::to_value(&*self.tx.borrow())
+            .and_then(|value| serde_json::to_vec(&value))
+            .unwrap_or_default();
+        // FNV-1a; std's hasher isn't guaranteed stable between Rust releases
+        let hash = json.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// Subscribe to configuration changes
+    pub fn subscribe(&self) -> watch::Receiver<T> {
+        self.tx.subscribe()
+    }
+
+    /// Modify, validate and persist the configuration, then notify subscribers
+    pub async fn update<F>(&self, modify: F) -> Result<()>
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut config = self.get();
+        modify(&mut config);
+        config.validate()?;
+
+        self.save(config).await?;
+        tracing::debug!("Saved configuration to {:?}", self.path);
+        Ok(())
+    }
+
+    /// Write `config` to disk and publish it
+    async fn save(&self, config: T) -> Result<()> {
+        let contents = self
+            .format
+            .serialize(&config)
+            .context("Failed to serialize config")?;
+        fs::write(&self.path, contents)
+            .await
+            .context("Failed to write config file")?;
+        self.record_modified().await;
+
+        self.tx.send_replace(config);
+        Ok(())
+    }
+
+    /// The current config as an archive entry for `service`
+    pub fn export(&self, service: &str, version: &str) -> Result<ConfigEntry> {
+        Ok(ConfigEntry {
+            service: service.to_string(),
+            version: version.to_string(),
+            config: serde_json::to_value(&*self.tx.borrow())?,
+        })
+    }
+
+    /// Replace the config with an exported one, then notify subscribers
+    ///
+    /// Entries from older builds are migrated first. Nothing changes unless the entry is valid;
+    /// the file being replaced is kept as `<file>.bak`.
+    pub async fn import(&self, entry: &ConfigEntry) -> Result<()> {
+        let mut doc = entry.config.clone();
+        let version = migrate::version_of(&doc);
+        if version > T::VERSION {
+            bail!(
+                "Archived config is version {}, newer than this build's {}",
+                version,
+                T::VERSION
+            );
+        }
+        if version < T::VERSION {
+            migrate::upgrade::<T>(&mut doc, version)
+                .context("Failed to migrate archived config")?;
+        }
+
+        let config: T =
+            serde_json::from_value(doc.clone()).context("Failed to parse archived config")?;
+        config.validate().context("Invalid archived config")?;
+        for field in migrate::unknown_fields(&doc, &serde_json::to_value(&config)?) {
+            tracing::warn!("Ignoring unknown setting {} in archived config", field);
+        }
+
+        let backup = with_suffix(&self.path, ".bak");
+        fs::copy(&self.path, &backup)
+            .await
+            .with_context(|| format!("Failed to back up config to {:?}", backup))?;
+        self.save(config).await?;
+
+        tracing::warn!(
+            "Restored configuration from a {} {} archive; the previous file is saved as {:?}",
+            entry.service,
+            entry.version,
+            backup
+        );
+        Ok(())
+    }
+
+    /// Reload the config if the file changed on disk since it was last read or written
+    ///
+    /// Returns true if a new config was applied. A file that fails to parse or validate is
+    /// reported once and ignored until it changes again.
+    pub async fn reload_if_changed(&self) -> Result<bool> {
+        let modified = fs::metadata(&self.path)
+            .await
+            .and_then(|meta| meta.modified())
+            .context("Failed to stat config file")?;
+
+        {
+            let mut last = self.last_modified.lock().unwrap();
+            if *last == Some(modified) {
+                return Ok(false);
+            }
+            *last = Some(modified);
+        }
+
+        let (config, migrated_from) = Self::read(&self.path, self.format).await?;
+        config.validate()?;
+
+        if let Some(from) = migrated_from {
+            Self::save_migrated(&self.path, self.format, from, &config).await?;
+            self.record_modified().await;
+        }
+
+        self.tx.send_replace(config);
+        tracing::info!("Reloaded configuration from {:?}", self.path);
+        Ok(true)
+    }
+
+    /// Start a background task that polls the config file and hot-reloads changes
+    pub fn watch(&self, poll_interval: Duration) -> JoinHandle<()> {
+        let manager = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = interval(poll_interval);
+
+            loop {
+                ticker.tick().await;
+
+                if let Err(e) = manager.reload_if_changed().await {
+                    tracing::warn!(
+                        "Ignoring config change in {:?}: {:#}",
+                        manager.path,
+                        e
+                    );
+                }
+            }
+        })
+    }
+}
+
+/// Top-level keys whose values differ between two serialized configs
+pub fn changed_sections(old: &Value, new: &Value) -> Vec<String> {
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Vec::new();
+    };
+
+    let mut sections: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    sections.sort();
+    sections.dedup();
+    sections
+}
+
+/// `path` with `suffix` appended to the file name (`config.yaml` → `config.yaml.bak`)
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
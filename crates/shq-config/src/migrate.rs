@@ -0,0 +1,156 @@
+//! Config schema versions and migrations.
+//!
+//! Every config file carries a `config_version`. Files written by an older build are upgraded
+//! one version at a time by the app's [`Migration`]s before being parsed, and the original
+//! file is kept as `<file>.v<N>.bak`. Settings the current build doesn't know about are
+//! logged instead of being dropped silently.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::Configuration;
+
+/// Field holding the schema version in every config file
+pub const VERSION_FIELD: &str = "config_version";
+
+/// Upgrades a config document from one schema version to the next
+///
+/// Migrations work on the raw document (YAML is converted to the equivalent JSON value), so
+/// they can see fields the current config structs no longer have.
+pub type Migration = fn(&mut Value) -> Result<()>;
+
+/// Schema version of a raw config document (0 if it predates versioning)
+pub(crate) fn version_of(doc: &Value) -> u32 {
+    doc.get(VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .map(|v| v as u32)
+        .unwrap_or(0)
+}
+
+/// Run the migrations from `from` up to `T::VERSION` and stamp the new version
+pub(crate) fn upgrade<T: Configuration>(doc: &mut Value, from: u32) -> Result<()> {
+    let migrations = T::migrations();
+    for version in from..T::VERSION {
+        if let Some(migration) = migrations.get(version as usize) {
+            migration(doc)?;
+        }
+        tracing::info!(
+            "Migrated config from version {} to {}",
+            version,
+            version + 1
+        );
+    }
+
+    if let Some(object) = doc.as_object_mut() {
+        object.insert(VERSION_FIELD.to_string(), Value::from(T::VERSION));
+    }
+    Ok(())
+}
+
+/// Dotted paths (`door.limit_offset`, `schedule.jobs[0].cron`) present in `raw` but not in
+/// `parsed`, i.e. settings that were ignored when the file was loaded
+pub(crate) fn unknown_fields(raw: &Value, parsed: &Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    collect_unknown(raw, parsed, "", &mut unknown);
+    unknown
+}
+
+fn collect_unknown(raw: &Value, parsed: &Value, prefix: &str, unknown: &mut Vec<String>) {
+    match (raw, parsed) {
+        (Value::Object(raw), Value::Object(parsed)) => {
+            for (key, value) in raw {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match parsed.get(key) {
+                    Some(parsed) => collect_unknown(value, parsed, &path, unknown),
+                    // Explicit nulls are how optional sections are left empty
+                    None if !value.is_null() => unknown.push(path),
+                    None => {}
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(parsed)) => {
+            for (i, (raw, parsed)) in raw.iter().zip(parsed).enumerate() {
+                collect_unknown(raw, parsed, &format!("{}[{}]", prefix, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Move the value at `from` to `to` (dotted paths), creating parent sections as needed
+///
+/// Returns false if there was nothing at `from`. An existing value at `to` is kept.
+pub fn rename(doc: &mut Value, from: &str, to: &str) -> bool {
+    let Some(value) = take(doc, from) else {
+        return false;
+    };
+
+    let (parent, key) = split(to);
+    let Some(parent) = section_mut(doc, parent, true) else {
+        return false;
+    };
+    if parent.contains_key(key) {
+        tracing::warn!(
+            "Config migration: dropped {} ({}), {} is already set",
+            from,
+            value,
+            to
+        );
+    } else {
+        tracing::info!("Config migration: {} renamed to {}", from, to);
+        parent.insert(key.to_string(), value);
+    }
+    true
+}
+
+/// Remove the setting at `path`, returning its value so the migration can act on it
+pub fn remove(doc: &mut Value, path: &str) -> Option<Value> {
+    let value = take(doc, path)?;
+    tracing::warn!("Config migration: removed {} ({})", path, value);
+    Some(value)
+}
+
+/// Rename an enum value: replace the string `from` at `path` with `to`
+pub fn rename_value(doc: &mut Value, path: &str, from: &str, to: &str) -> bool {
+    let (parent, key) = split(path);
+    let Some(value) = section_mut(doc, parent, false).and_then(|s| s.get_mut(key)) else {
+        return false;
+    };
+    if value.as_str() != Some(from) {
+        return false;
+    }
+
+    tracing::info!("Config migration: {} changed from {} to {}", path, from, to);
+    *value = Value::from(to);
+    true
+}
+
+fn take(doc: &mut Value, path: &str) -> Option<Value> {
+    let (parent, key) = split(path);
+    section_mut(doc, parent, false)?.remove(key)
+}
+
+/// Split `a.b.c` into (`a.b`, `c`)
+fn split(path: &str) -> (&str, &str) {
+    path.rsplit_once('.').unwrap_or(("", path))
+}
+
+/// The object at dotted `path` (the root for ""), optionally creating missing sections
+fn section_mut<'a>(
+    doc: &'a mut Value,
+    path: &str,
+    create: bool,
+) -> Option<&'a mut Map<String, Value>> {
+    let mut section = doc.as_object_mut()?;
+    for key in path.split('.').filter(|k| !k.is_empty()) {
+        if create && !section.contains_key(key) {
+            section.insert(key.to_string(), Value::Object(Map::new()));
+        }
+        section = section.get_mut(key)?.as_object_mut()?;
+    }
+    Some(section)
+}
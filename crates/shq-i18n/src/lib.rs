@@ -0,0 +1,249 @@
+//! Localized user-facing strings.
+//!
+//! Each service ships its own catalogs (`<app>/locales/<locale>.yaml`, compiled in) and picks
+//! one with the `i18n.locale` setting. Files in `i18n.dir` extend or override the built-in
+//! catalogs, or add locales the build doesn't ship. Keys missing from the selected locale
+//! fall back to English, then to the key itself, so a partial translation never breaks a
+//! reply.
+//!
+//! Catalogs are YAML maps, nested sections flattened to dotted keys (`error.open_failed`).
+//! Values may use `{name}` placeholders, filled from the arguments at lookup.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Locale used for keys the selected locale doesn't translate
+pub const FALLBACK_LOCALE: &str = "en";
+
+/// Catalogs compiled into a service: `(locale, YAML)` pairs, English first
+pub type BuiltinLocales = &'static [(&'static str, &'static str)];
+
+/// `i18n` config section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct I18nConfig {
+    /// Locale for client errors and announcements (`en`, `de`, ...)
+    pub locale: String,
+    /// Directory of `<locale>.yaml` files extending the built-in catalogs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        Self {
+            locale: FALLBACK_LOCALE.to_string(),
+            dir: None,
+        }
+    }
+}
+
+impl I18nConfig {
+    /// Check the locale name; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        let valid = !self.locale.is_empty()
+            && self
+                .locale
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            bail!(
+                "i18n.locale must be a locale name like \"en\" or \"de-AT\", got '{}'",
+                self.locale
+            );
+        }
+        Ok(())
+    }
+}
+
+struct Catalogs {
+    locale: String,
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+/// Shared translator; clones see the same catalogs
+#[derive(Clone)]
+pub struct Translator {
+    builtin: BuiltinLocales,
+    inner: Arc<RwLock<Catalogs>>,
+}
+
+impl Translator {
+    /// Load the catalogs selected by `config`
+    ///
+    /// Falls back to the built-in English catalog (with a warning) if a catalog file is invalid
+    /// or the locale has neither a built-in catalog nor a file in `config.dir`.
+    pub fn new(builtin: BuiltinLocales, config: &I18nConfig) -> Self {
+        let catalogs = load(builtin, config).unwrap_or_else(|e| {
+            tracing::warn!("Using English messages: {:#}", e);
+            load(builtin, &I18nConfig::default()).expect("built-in English catalog is valid")
+        });
+
+        Self {
+            builtin,
+            inner: Arc::new(RwLock::new(catalogs)),
+        }
+    }
+
+    /// Apply a reloaded config; the current catalogs stay in place if the new ones fail to load
+    pub fn update(&self, config: &I18nConfig) -> Result<()> {
+        let catalogs = load(self.builtin, config)?;
+        *self.inner.write().unwrap() = catalogs;
+        Ok(())
+    }
+
+    /// The selected locale
+    pub fn locale(&self) -> String {
+        self.inner.read().unwrap().locale.clone()
+    }
+
+    /// Look up `key` and fill in its `{name}` placeholders from `args`
+    pub fn t(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let catalogs = self.inner.read().unwrap();
+        match catalogs
+            .messages
+            .get(key)
+            .or_else(|| catalogs.fallback.get(key))
+        {
+            Some(template) => fill(template, args.iter().map(|(k, v)| (*k, v.to_string()))),
+            None => {
+                tracing::debug!("No translation for '{}'", key);
+                key.to_string()
+            }
+        }
+    }
+
+    /// Like [`Translator::t`] with string arguments, but `None` for unknown keys
+    ///
+    /// For user-defined templates (e.g. overwatch announcements), where an unknown key is an
+    /// error rather than something to show.
+    pub fn template(&self, key: &str, args: &BTreeMap<String, String>) -> Option<String> {
+        let catalogs = self.inner.read().unwrap();
+        let template = catalogs
+            .messages
+            .get(key)
+            .or_else(|| catalogs.fallback.get(key))?;
+        Some(fill(
+            template,
+            args.iter().map(|(k, v)| (k.as_str(), v.clone())),
+        ))
+    }
+}
+
+/// Fill `{name}` placeholders in text that didn't come from a catalog, e.g. announcements
+/// written into a service's config, the same way [`Translator::template`] does
+pub fn fill_template(template: &str, args: &BTreeMap<String, String>) -> String {
+    fill(template, args.iter().map(|(k, v)| (k.as_str(), v.clone())))
+}
+
+fn load(builtin: BuiltinLocales, config: &I18nConfig) -> Result<Catalogs> {
+    let fallback = catalog(builtin, FALLBACK_LOCALE, config.dir.as_deref())?.unwrap_or_default();
+    let messages = if config.locale == FALLBACK_LOCALE {
+        HashMap::new()
+    } else {
+        catalog(builtin, &config.locale, config.dir.as_deref())?
+            .with_context(|| format!("No translations for locale '{}'", config.locale))?
+    };
+
+    tracing::info!("Using locale '{}'", config.locale);
+    Ok(Catalogs {
+        locale: config.locale.clone(),
+        messages,
+        fallback,
+    })
+}
+
+/// The built-in catalog for `locale` with `<dir>/<locale>.yaml` merged over it, or `None` if
+/// there is neither
+fn catalog(
+    builtin: BuiltinLocales,
+    locale: &str,
+    dir: Option<&Path>,
+) -> Result<Option<HashMap<String, String>>> {
+    let mut messages = None;
+
+    if let Some((_, yaml)) = builtin.iter().find(|(name, _)| *name == locale) {
+        let parsed = parse(yaml)
+            .with_context(|| format!("Invalid built-in catalog for locale '{}'", locale))?;
+        messages = Some(parsed);
+    }
+
+    if let Some(dir) = dir {
+        let path = dir.join(format!("{}.yaml", locale));
+        if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {:?}", path))?;
+            let parsed = parse(&contents).with_context(|| format!("Invalid catalog {:?}", path))?;
+            messages.get_or_insert_with(HashMap::new).extend(parsed);
+        }
+    }
+
+    Ok(messages)
+}
+
+fn parse(yaml: &str) -> Result<HashMap<String, String>> {
+    let value: Value = serde_yaml::from_str(yaml)?;
+    let mut messages = HashMap::new();
+    flatten(&value, "", &mut messages)?;
+    Ok(messages)
+}
+
+fn flatten(value: &Value, prefix: &str, messages: &mut HashMap<String, String>) -> Result<()> {
+    match value {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                let key = key
+                    .as_str()
+                    .with_context(|| format!("Non-string key under '{}'", prefix))?;
+                let path = if prefix.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(value, &path, messages)?;
+            }
+        }
+        Value::String(text) => {
+            messages.insert(prefix.to_string(), text.clone());
+        }
+        Value::Null if prefix.is_empty() => {}
+        _ => bail!("'{}' must be a string or a section", prefix),
+    }
+    Ok(())
+}
+
+/// Replace `{name}` placeholders; unknown ones are left as they are
+///
+/// Single pass, so argument values (often error text) are never expanded themselves.
+fn fill<'a>(template: &str, args: impl Iterator<Item = (&'a str, String)>) -> String {
+    let args: HashMap<&str, String> = args.collect();
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after
+            .find('}')
+            .and_then(|end| Some((args.get(&after[..end])?, end)))
+        {
+            Some((value, end)) => {
+                text.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+
+    text.push_str(rest);
+    text
+}
@@ -0,0 +1,35 @@
+//! Correlation IDs for client commands.
+//!
+//! Servers run each WebSocket message, HTTP request or RPC inside a [`command_span`], so every
+//! line logged while handling it (including by background tasks that carry the span) shares
+//! one ID and can be picked out of a log store such as Loki.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tracing::Span;
+
+/// Span for one client command, with a fresh `correlation_id`
+///
+/// The span's `client` field starts empty; servers that know who sent the command record it.
+/// The span is always enabled (see `ALWAYS_ON_DIRECTIVES`), whatever the filter says about the
+/// crate that creates it.
+pub fn command_span(command: &str) -> Span {
+    tracing::info_span!(
+        target: "shq_logging::command",
+        "command",
+        correlation_id = %correlation_id(),
+        command,
+        client = tracing::field::Empty,
+    )
+}
+
+/// 16 hex digits counting up from a random start, so IDs don't repeat across restarts
+fn correlation_id() -> String {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let seed = *SEED.get_or_init(|| RandomState::new().build_hasher().finish());
+    format!("{:016x}", seed.wrapping_add(NEXT.fetch_add(1, Ordering::Relaxed)))
+}
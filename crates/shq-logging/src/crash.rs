@@ -0,0 +1,142 @@
+//! Panic capture and crash reports.
+//!
+//! The panic hook logs the panic with a backtrace and writes a JSON crash report (version,
+//! config hash, recent log lines) next to the service's config. The report survives the
+//! restart, so the next run can show the last crash in its health reply.
+
+use crate::stream::{LogLine, LogStream};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use shq_protocol::CrashInfo;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Log lines kept in a crash report
+const RECENT_LINES: usize = 100;
+
+/// Everything known about a panic, as written to the crash report file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub service: String,
+    pub version: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub thread: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub config_hash: String,
+    pub backtrace: String,
+    /// Log output leading up to the panic
+    pub recent_logs: Vec<LogLine>,
+}
+
+impl CrashReport {
+    /// Read a crash report written by an earlier run
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read crash report {:?}", path))?;
+        serde_json::from_str(&contents).context("Failed to parse crash report")
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write crash report {:?}", path))
+    }
+
+    /// Summary for health replies
+    pub fn info(&self) -> CrashInfo {
+        CrashInfo {
+            timestamp_ms: self.timestamp_ms,
+            version: self.version.clone(),
+            message: self.message.clone(),
+            location: self.location.clone(),
+            config_hash: self.config_hash.clone(),
+        }
+    }
+}
+
+/// Replace the default panic hook; see [`crate::LogHandle::install_panic_hook`]
+pub(crate) fn install(
+    service: &'static str,
+    version: &'static str,
+    path: PathBuf,
+    config_hash: Box<dyn Fn() -> String + Send + Sync>,
+    stream: LogStream,
+    last_crash: Arc<Mutex<Option<CrashInfo>>>,
+) {
+    match CrashReport::load(&path) {
+        Ok(report) => {
+            tracing::warn!(
+                "Last crash at {} (version {}): {}",
+                report.location.as_deref().unwrap_or("unknown location"),
+                report.version,
+                report.message
+            );
+            *last_crash.lock().unwrap() = Some(report.info());
+        }
+        Err(_) if !path.exists() => {}
+        Err(e) => tracing::warn!("Ignoring crash report: {:#}", e),
+    }
+
+    std::panic::set_hook(Box::new(move |info| {
+        // Taken before logging the panic so the report shows what led up to it
+        let recent_logs = stream.recent(RECENT_LINES);
+        let backtrace = Backtrace::force_capture();
+        let thread = std::thread::current()
+            .name()
+            .unwrap_or("<unnamed>")
+            .to_string();
+        let message = panic_message(info);
+        let location = info.location().map(|l| l.to_string());
+
+        tracing::error!(
+            "Thread '{}' panicked at {}: {}\n{}",
+            thread,
+            location.as_deref().unwrap_or("unknown location"),
+            message,
+            backtrace
+        );
+
+        let report = CrashReport {
+            service: service.to_string(),
+            version: version.to_string(),
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            thread,
+            message,
+            location,
+            config_hash: config_hash(),
+            backtrace: backtrace.to_string(),
+            recent_logs,
+        };
+
+        match report.save(&path) {
+            Ok(()) => tracing::error!("Crash report written to {:?}", path),
+            Err(e) => tracing::error!("{:#}", e),
+        }
+
+        // A panicking task doesn't always take the process down; keep health up to date
+        if let Ok(mut last) = last_crash.try_lock() {
+            *last = Some(report.info());
+        }
+    }));
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
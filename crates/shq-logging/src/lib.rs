@@ -0,0 +1,322 @@
+//! Shared logging setup for SHQ services.
+//!
+//! Always logs to stderr (for journald), as plain text or JSON (`--log-format json`). Client
+//! commands run in a span with a correlation ID (see [`command_span`]). Optional file logging
+//! with size/age based rotation can be switched on once the service config has been loaded,
+//! and recent output can be streamed to remote clients (see [`stream`]). Panics are logged
+//! and written to a crash report (see [`crash`]).
+
+mod command;
+mod crash;
+mod stream;
+
+pub use command::command_span;
+pub use crash::CrashReport;
+pub use stream::{LogLine, LogStreamConfig};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use shq_protocol::CrashInfo;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use stream::LogStream;
+use tokio::sync::broadcast;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Shared-crate output that is logged whatever the filter says: panics and crash reports,
+/// config problems (migrations, ignored settings, rejected reloads) and the command span that
+/// carries correlation IDs
+const ALWAYS_ON_DIRECTIVES: &[&str] = &[
+    "shq_logging::crash=info",
+    "shq_config=warn",
+    "shq_logging::command=info",
+];
+
+/// Log output format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Plain,
+    Json,
+}
+
+impl LogFormat {
+    /// The `--log-format plain|json` command-line option (plain if it isn't given)
+    pub fn from_args() -> Result<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        match args.iter().position(|arg| arg == "--log-format") {
+            Some(i) => match args.get(i + 1) {
+                Some(value) => value.parse(),
+                None => bail!("--log-format needs a value (plain or json)"),
+            },
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "json" => Ok(Self::Json),
+            other => bail!("Unknown log format '{}' (expected plain or json)", other),
+        }
+    }
+}
+
+/// Logging configuration section, shared by all services
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Optional log file (stderr logging is always on)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileLogConfig>,
+    /// Remote log streaming over the service's API
+    pub stream: LogStreamConfig,
+}
+
+/// File logging configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileLogConfig {
+    /// Path of the active log file; rotated files get a `.1`, `.2`, ... suffix
+    pub path: PathBuf,
+    /// Rotate once the file exceeds this size in megabytes (0 = no size limit)
+    pub max_size_mb: u64,
+    /// Rotate once the file is older than this many hours (0 = no age limit)
+    pub max_age_hours: u64,
+    /// Number of rotated files to keep
+    pub max_files: usize,
+    /// Output format
+    pub format: LogFormat,
+}
+
+impl Default for FileLogConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("service.log"),
+            max_size_mb: 10,
+            max_age_hours: 24,
+            max_files: 5,
+            format: LogFormat::Plain,
+        }
+    }
+}
+
+/// Handle returned by [`init`] for reconfiguring file logging at runtime
+#[derive(Clone)]
+pub struct LogHandle {
+    file_layer: reload::Handle<Option<BoxedLayer>, Registry>,
+    stream: LogStream,
+    last_crash: Arc<Mutex<Option<CrashInfo>>>,
+}
+
+/// Initialize tracing with stderr output in `format`
+///
+/// `default_filter` is used when `RUST_LOG` is not set (e.g. "dosa=info").
+pub fn init(default_filter: &str, format: LogFormat) -> LogHandle {
+    let (file_layer, handle) = reload::Layer::new(None::<BoxedLayer>);
+    let stream = LogStream::new();
+
+    let filter = ALWAYS_ON_DIRECTIVES.iter().fold(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| default_filter.into()),
+        |filter, directive| filter.add_directive(directive.parse().expect("valid directive")),
+    );
+
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(stream.clone())
+        .with(filter)
+        .with((format == LogFormat::Plain).then(fmt::layer))
+        .with((format == LogFormat::Json).then(|| fmt::layer().json()))
+        .init();
+
+    LogHandle {
+        file_layer: handle,
+        stream,
+        last_crash: Arc::default(),
+    }
+}
+
+impl LogHandle {
+    /// Apply a logging config, enabling, changing or disabling file output
+    pub fn apply(&self, config: &LoggingConfig) -> Result<()> {
+        let layer = match &config.file {
+            Some(file_config) => {
+                let writer = RotatingWriter::open(file_config)?;
+                let layer = fmt::layer().with_ansi(false).with_writer(writer);
+                let layer: BoxedLayer = match file_config.format {
+                    LogFormat::Plain => Box::new(layer),
+                    LogFormat::Json => Box::new(layer.json()),
+                };
+                tracing::info!("Logging to file {:?}", file_config.path);
+                Some(layer)
+            }
+            None => None,
+        };
+
+        self.file_layer
+            .reload(layer)
+            .context("Failed to reconfigure file logging")
+    }
+
+    /// Subscribe to live log output, starting with up to `backlog` recent lines
+    ///
+    /// Callers are responsible for checking [`LogStreamConfig::authorize`] first.
+    pub fn subscribe(&self, backlog: usize) -> (Vec<LogLine>, broadcast::Receiver<LogLine>) {
+        self.stream.subscribe(backlog)
+    }
+
+    /// Log panics with a backtrace and write a crash report to `path`
+    ///
+    /// Call once the config is loaded; apps keep the report next to the config file as
+    /// `crash-report.json`. `config_hash` is called when a panic happens. A report left by a
+    /// previous run is logged and available from [`LogHandle::last_crash`].
+    pub fn install_panic_hook(
+        &self,
+        service: &'static str,
+        version: &'static str,
+        path: PathBuf,
+        config_hash: impl Fn() -> String + Send + Sync + 'static,
+    ) {
+        crash::install(
+            service,
+            version,
+            path,
+            Box::new(config_hash),
+            self.stream.clone(),
+            self.last_crash.clone(),
+        );
+    }
+
+    /// The most recent panic recorded in the crash report, if any
+    pub fn last_crash(&self) -> Option<CrashInfo> {
+        self.last_crash.lock().unwrap().clone()
+    }
+}
+
+/// Log file writer that rotates by size and age
+#[derive(Clone)]
+struct RotatingWriter {
+    inner: Arc<Mutex<RotatingFile>>,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_age: Option<Duration>,
+    max_files: usize,
+    file: File,
+    size: u64,
+    opened_at: SystemTime,
+}
+
+impl RotatingWriter {
+    fn open(config: &FileLogConfig) -> Result<Self> {
+        if let Some(parent) = config.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).context("Failed to create log directory")?;
+        }
+
+        let file = Self::open_file(&config.path)?;
+        let meta = file.metadata()?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(RotatingFile {
+                path: config.path.clone(),
+                max_bytes: config.max_size_mb * 1024 * 1024,
+                max_age: (config.max_age_hours > 0)
+                    .then(|| Duration::from_secs(config.max_age_hours * 3600)),
+                max_files: config.max_files,
+                file,
+                size: meta.len(),
+                opened_at: meta.created().unwrap_or_else(|_| SystemTime::now()),
+            })),
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {:?}", path))
+    }
+}
+
+impl RotatingFile {
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        let too_big = self.max_bytes > 0 && self.size + incoming as u64 > self.max_bytes;
+        let too_old = self.max_age.is_some_and(|max_age| {
+            self.opened_at
+                .elapsed()
+                .map(|age| age >= max_age)
+                .unwrap_or(false)
+        });
+
+        self.size > 0 && (too_big || too_old)
+    }
+
+    /// Shift log.N-1 -> log.N ... log -> log.1, dropping anything beyond max_files
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+
+        if self.max_files == 0 {
+            let _ = fs::remove_file(&self.path);
+        } else {
+            let _ = fs::remove_file(rotated(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let _ = fs::rename(rotated(n), rotated(n + 1));
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.opened_at = SystemTime::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file = self.inner.lock().unwrap();
+
+        if file.needs_rotation(buf.len()) {
+            if let Err(e) = file.rotate() {
+                // Keep writing to the current file rather than losing logs
+                eprintln!("Failed to rotate log file {:?}: {}", file.path, e);
+            }
+        }
+
+        let written = file.file.write(buf)?;
+        file.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.lock().unwrap().file.flush()
+    }
+}
+
+impl<'a> fmt::MakeWriter<'a> for RotatingWriter {
+    type Writer = RotatingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
@@ -0,0 +1,153 @@
+//! Live log streaming for remote debugging.
+//!
+//! Every event that passes the global filter is kept in a small backlog and broadcast to
+//! subscribers, so WebSocket/gRPC servers can tail a device's logs without SSH access.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use shq_auth::token_matches;
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Number of recent lines kept for new subscribers
+const BACKLOG_LINES: usize = 500;
+
+/// Capacity of the live channel; slow subscribers skip lines beyond this
+const CHANNEL_CAPACITY: usize = 1000;
+
+/// A single captured log event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub level: String,
+    pub target: String,
+    /// Event message followed by any extra fields (`key=value`)
+    pub message: String,
+}
+
+/// Remote log streaming configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogStreamConfig {
+    /// Allow clients to subscribe to live logs (off by default)
+    pub enabled: bool,
+    /// Token clients must present to subscribe (none = any client may subscribe)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl LogStreamConfig {
+    /// Check whether a client presenting `token` may subscribe to logs
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        if !self.enabled {
+            bail!("Log streaming is disabled");
+        }
+
+        match &self.token {
+            Some(expected) if !token_matches(expected, token) => bail!("Invalid log stream token"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tracing layer that captures events into the backlog and live channel
+#[derive(Clone)]
+pub(crate) struct LogStream {
+    tx: broadcast::Sender<LogLine>,
+    backlog: Arc<Mutex<VecDeque<LogLine>>>,
+}
+
+impl LogStream {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        Self {
+            tx,
+            backlog: Arc::new(Mutex::new(VecDeque::with_capacity(BACKLOG_LINES))),
+        }
+    }
+
+    /// Get up to `lines` recent lines plus a receiver for everything after them
+    pub(crate) fn subscribe(&self, lines: usize) -> (Vec<LogLine>, broadcast::Receiver<LogLine>) {
+        // Subscribe while holding the backlog lock so no line is missed or duplicated
+        let backlog = self.backlog.lock().unwrap();
+        let rx = self.tx.subscribe();
+        let skip = backlog.len().saturating_sub(lines);
+
+        (backlog.iter().skip(skip).cloned().collect(), rx)
+    }
+
+    /// Up to `lines` recent lines, or none if the backlog is locked (e.g. a panic mid-event)
+    pub(crate) fn recent(&self, lines: usize) -> Vec<LogLine> {
+        match self.backlog.try_lock() {
+            Ok(backlog) => {
+                let skip = backlog.len().saturating_sub(lines);
+                backlog.iter().skip(skip).cloned().collect()
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn push(&self, line: LogLine) {
+        let mut backlog = self.backlog.lock().unwrap();
+        if backlog.len() == BACKLOG_LINES {
+            backlog.pop_front();
+        }
+        backlog.push_back(line.clone());
+
+        // No subscribers is the normal case
+        let _ = self.tx.send(line);
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogStream {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        self.push(LogLine {
+            timestamp_ms,
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.message + &visitor.fields,
+        });
+    }
+}
+
+/// Collects an event's message and remaining fields as text
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! mDNS / DNS-SD advertisement for SHQ services.
+//!
+//! Each service announces itself as `_<service>._tcp.local` (e.g. `_dosa._tcp`) with its port
+//! and a few TXT records (version, protocol revision), so wall panels and peer services can
+//! find it without a hardcoded address. The responder runs on its own thread; announcements
+//! follow the host's interfaces and addresses as they change.
+
+use anyhow::Result;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long `stop()` waits for the goodbye announcement to go out
+const GOODBYE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longest DNS label, and so the longest instance name
+const MAX_NAME_LEN: usize = 63;
+
+/// `mdns` config section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MdnsConfig {
+    pub enabled: bool,
+    /// Instance name shown when browsing (default: the host name)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name: None,
+        }
+    }
+}
+
+impl MdnsConfig {
+    /// Check the instance name; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() || name.len() > MAX_NAME_LEN || name.contains('.') {
+                anyhow::bail!("mdns.name must be 1-{} bytes without dots, got {:?}", MAX_NAME_LEN, name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A running advertisement; call [`Advertisement::stop`] on shutdown so browsers drop it at once
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertisement {
+    /// Advertise `_<service>._tcp` on `port` with `txt` records
+    ///
+    /// `None` if `config` disables it or the responder can't start (logged; the service runs on
+    /// without it, reachable by address as before).
+    pub fn start(config: &MdnsConfig, service: &str, port: u16, txt: &[(&str, &str)]) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        match Self::register(config, service, port, txt) {
+            Ok(advertisement) => {
+                tracing::info!("Advertising {} via mDNS", advertisement.fullname);
+                Some(advertisement)
+            }
+            Err(e) => {
+                tracing::warn!("mDNS advertisement disabled: {:#}", e);
+                None
+            }
+        }
+    }
+
+    fn register(config: &MdnsConfig, service: &str, port: u16, txt: &[(&str, &str)]) -> Result<Self> {
+        let host = host_name();
+        let name = config.name.clone().unwrap_or_else(|| host.clone());
+        let service_type = format!("_{}._tcp.local.", service);
+        let info = ServiceInfo::new(&service_type, &name, &format!("{}.local.", host), "", port, txt)?
+            .enable_addr_auto();
+        let fullname = info.get_fullname().to_string();
+
+        let daemon = ServiceDaemon::new()?;
+        daemon.register(info)?;
+        Ok(Self { daemon, fullname })
+    }
+
+    /// Withdraw the advertisement and stop the responder; blocks up to a second for the goodbye
+    pub fn stop(self) {
+        match self.daemon.unregister(&self.fullname) {
+            Ok(done) => {
+                let _ = done.recv_timeout(GOODBYE_TIMEOUT);
+            }
+            Err(e) => tracing::debug!("mDNS unregister failed: {}", e),
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
+
+/// The machine's host name, without any domain
+fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .ok()
+        .and_then(|name| name.trim().split('.').next().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "shq".to_string())
+}
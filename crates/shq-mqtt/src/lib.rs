@@ -0,0 +1,329 @@
+//! Home Assistant MQTT integration shared by SHQ services.
+//!
+//! One connection config format, one topic layout and one availability topic per device:
+//!
+//! - availability: `<base_topic>/<node_id>/availability` (`online`/`offline`, retained, LWT)
+//! - entity topics: `<base_topic>/<node_id>/<object_id>/...` (e.g. `.../set`, `.../state`)
+//! - discovery: `<discovery_prefix>/<component>/<node_id>/<object_id>/config` (retained)
+//!
+//! Services describe their entities with the [`Entity`] builders, call [`MqttClient::connect`]
+//! and handle the [`MqttCommand`]s it produces.
+
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
+
+/// Delay between reconnect attempts after the broker connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// MQTT connection settings, identical for every service's `mqtt` config section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker host name or address
+    pub host: String,
+    /// Broker port
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Unique device name (e.g. "front-door"); defaults to the service name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    /// Prefix for state/command/availability topics
+    pub base_topic: String,
+    /// Home Assistant discovery prefix
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            node_id: None,
+            base_topic: "shq".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// A Home Assistant entity announced through MQTT discovery
+///
+/// Topic fields use HA's `~` abbreviation, which expands to the entity's topic root
+/// (`<base_topic>/<node_id>/<object_id>`).
+#[derive(Debug, Clone)]
+pub struct Entity {
+    component: &'static str,
+    object_id: String,
+    config: Map<String, Value>,
+}
+
+impl Entity {
+    fn new(component: &'static str, object_id: &str, name: &str) -> Self {
+        let mut config = Map::new();
+        config.insert("name".to_string(), json!(name));
+
+        Self {
+            component,
+            object_id: sanitize(object_id),
+            config,
+        }
+    }
+
+    /// Cover: `OPEN`/`CLOSE`/`STOP` on `~/set`, state (`open`, `closed`, `opening`, ...) on `~/state`
+    pub fn cover(object_id: &str, name: &str) -> Self {
+        Self::new("cover", object_id, name)
+            .with("command_topic", "~/set")
+            .with("state_topic", "~/state")
+    }
+
+    /// Light: `ON`/`OFF` on `~/set`, brightness 0-255 on `~/brightness/set`
+    pub fn light(object_id: &str, name: &str) -> Self {
+        Self::new("light", object_id, name)
+            .with("command_topic", "~/set")
+            .with("state_topic", "~/state")
+            .with("brightness_command_topic", "~/brightness/set")
+            .with("brightness_state_topic", "~/brightness")
+            .with("brightness_scale", 255)
+    }
+
+    /// Switch: `ON`/`OFF` on `~/set`, state on `~/state`
+    pub fn switch(object_id: &str, name: &str) -> Self {
+        Self::new("switch", object_id, name)
+            .with("command_topic", "~/set")
+            .with("state_topic", "~/state")
+    }
+
+    /// Notify: message text published to `~/set`
+    pub fn notify(object_id: &str, name: &str) -> Self {
+        Self::new("notify", object_id, name).with("command_topic", "~/set")
+    }
+
+    /// Siren: JSON commands (`{"state": "ON", "tone": ...}`) on `~/set`, `ON`/`OFF` on `~/state`
+    pub fn siren(object_id: &str, name: &str) -> Self {
+        Self::new("siren", object_id, name)
+            .with("command_topic", "~/set")
+            .with("state_topic", "~/state")
+    }
+
+    /// Set or override a discovery config field
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.config.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn object_id(&self) -> &str {
+        &self.object_id
+    }
+}
+
+/// A message received on one of a service's entity topics
+#[derive(Debug, Clone)]
+pub struct MqttCommand {
+    /// Entity the message was addressed to
+    pub object_id: String,
+    /// Topic below the entity root, e.g. `set` or `brightness/set`
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Device identity shown in Home Assistant
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// Service name, used as the HA model (e.g. "dosa")
+    pub service: String,
+    pub version: String,
+}
+
+/// Handle for publishing entity state
+#[derive(Clone)]
+pub struct MqttClient {
+    client: AsyncClient,
+    topic_root: Arc<str>,
+}
+
+impl MqttClient {
+    /// Connect to the broker and announce `entities`
+    ///
+    /// Runs in the background: availability, discovery and command subscriptions are
+    /// re-sent after every (re)connect. Commands for the entities arrive on the returned
+    /// channel.
+    pub fn connect(
+        config: &MqttConfig,
+        device: DeviceInfo,
+        entities: Vec<Entity>,
+    ) -> (Self, mpsc::Receiver<MqttCommand>) {
+        let node_id = sanitize(config.node_id.as_deref().unwrap_or(&device.service));
+        let topic_root: Arc<str> = format!("{}/{}", config.base_topic, node_id).into();
+        let availability_topic = format!("{}/availability", topic_root);
+
+        let mut options = MqttOptions::new(
+            format!("shq-{}-{}", device.service, node_id),
+            &config.host,
+            config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+        }
+
+        // Room for availability + discovery + subscriptions queued on every connect
+        let (client, event_loop) = AsyncClient::new(options, 64 + entities.len() * 2);
+        let (command_tx, command_rx) = mpsc::channel(32);
+
+        let device_config = json!({
+            "identifiers": [format!("shq_{}", node_id)],
+            "name": node_id,
+            "manufacturer": "SHQ",
+            "model": device.service,
+            "sw_version": device.version,
+        });
+
+        let announcements: Vec<(String, String)> = entities
+            .iter()
+            .map(|entity| {
+                let mut payload = entity.config.clone();
+                payload.insert(
+                    "~".to_string(),
+                    json!(format!("{}/{}", topic_root, entity.object_id)),
+                );
+                payload.insert(
+                    "unique_id".to_string(),
+                    json!(format!("{}_{}", node_id, entity.object_id)),
+                );
+                payload.insert("availability_topic".to_string(), json!(availability_topic));
+                payload.insert("device".to_string(), device_config.clone());
+
+                let topic = format!(
+                    "{}/{}/{}/{}/config",
+                    config.discovery_prefix, entity.component, node_id, entity.object_id
+                );
+                (topic, Value::Object(payload).to_string())
+            })
+            .collect();
+
+        tracing::info!(
+            "Connecting to MQTT broker {}:{} as {}",
+            config.host,
+            config.port,
+            node_id
+        );
+
+        tokio::spawn(run_event_loop(
+            event_loop,
+            client.clone(),
+            topic_root.clone(),
+            availability_topic,
+            announcements,
+            command_tx,
+        ));
+
+        (Self { client, topic_root }, command_rx)
+    }
+
+    /// Publish a retained state value to `<entity root>/<topic>`
+    pub fn publish(&self, object_id: &str, topic: &str, payload: impl Into<Vec<u8>>) {
+        let topic = format!("{}/{}/{}", self.topic_root, object_id, topic);
+        if let Err(e) = self
+            .client
+            .try_publish(&topic, QoS::AtLeastOnce, true, payload)
+        {
+            tracing::warn!("Failed to publish MQTT state to {}: {}", topic, e);
+        }
+    }
+}
+
+/// Drive the MQTT connection, re-announcing after every connect and forwarding commands
+async fn run_event_loop(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    topic_root: Arc<str>,
+    availability_topic: String,
+    announcements: Vec<(String, String)>,
+    command_tx: mpsc::Sender<MqttCommand>,
+) {
+    let command_prefix = format!("{}/", topic_root);
+
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("Connected to MQTT broker");
+
+                let result = client
+                    .try_publish(&availability_topic, QoS::AtLeastOnce, true, "online")
+                    .and_then(|_| {
+                        announcements.iter().try_for_each(|(topic, payload)| {
+                            client.try_publish(topic, QoS::AtLeastOnce, true, payload.as_bytes())
+                        })
+                    })
+                    .and_then(|_| {
+                        client.try_subscribe(format!("{}+/set", command_prefix), QoS::AtLeastOnce)
+                    })
+                    .and_then(|_| {
+                        client.try_subscribe(format!("{}+/+/set", command_prefix), QoS::AtLeastOnce)
+                    });
+
+                if let Err(e) = result {
+                    tracing::error!("Failed to announce MQTT entities: {}", e);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let Some((object_id, topic)) = publish
+                    .topic
+                    .strip_prefix(&command_prefix)
+                    .and_then(|rest| rest.split_once('/'))
+                else {
+                    continue;
+                };
+
+                let command = MqttCommand {
+                    object_id: object_id.to_string(),
+                    topic: topic.to_string(),
+                    payload: String::from_utf8_lossy(&publish.payload).into_owned(),
+                };
+                tracing::debug!("MQTT command: {:?}", command);
+
+                if command_tx.send(command).await.is_err() {
+                    tracing::info!("MQTT command receiver dropped, disconnecting");
+                    let _ = client.try_disconnect();
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(
+                    "MQTT connection error: {} (retrying in {:?})",
+                    e,
+                    RECONNECT_DELAY
+                );
+                sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Restrict an ID to the characters Home Assistant accepts in discovery topics
+fn sanitize(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,262 @@
+//! Wire types shared by the SHQ service protocols.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Metadata wrapped around every message a service sends
+///
+/// The payload is flattened, so enveloped messages keep their `type` tag at the top level and
+/// clients that ignore the envelope fields keep working. `id` echoes the request ID of the
+/// message being replied to, unchanged (clients may add a string or number `id` to any
+/// request); broadcasts have none.
+/// `seq` increases with every message a service sends, so events can be ordered even when
+/// timestamps collide.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    /// Send time in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Sending service (`dosa`, `nyx`, `overwatch`)
+    pub source: String,
+    pub seq: u64,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(id: Option<Value>, source: &str, seq: u64, payload: T) -> Self {
+        Self {
+            id,
+            timestamp: now_ms(),
+            source: source.to_string(),
+            seq,
+            payload,
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Reply to a `hello`/`GetInfo` handshake
+///
+/// `protocol` is bumped whenever a service's message set changes incompatibly;
+/// `capabilities` lists optional features so clients can adapt to older deployments.
+/// Services that predate the handshake reject `hello`, which clients should treat as
+/// protocol 0 with no capability list.
+///
+/// Clients can also list the optional features they would like to use; the reply then splits
+/// them into `accepted` and `unsupported` (see [`ServiceInfo::negotiate`]). Both are absent
+/// when the client didn't ask, or when the service predates negotiation, in which case
+/// clients fall back to checking `capabilities`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceInfo {
+    pub service: String,
+    pub version: String,
+    pub protocol: u32,
+    pub capabilities: Vec<String>,
+    /// Requested features this build supports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted: Option<Vec<String>>,
+    /// Requested features this build doesn't support; the client should do without them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unsupported: Option<Vec<String>>,
+}
+
+impl ServiceInfo {
+    pub fn new(service: &str, version: &str, protocol: u32, capabilities: &[&str]) -> Self {
+        Self {
+            service: service.to_string(),
+            version: version.to_string(),
+            protocol,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            accepted: None,
+            unsupported: None,
+        }
+    }
+
+    /// Answer a client's feature request, keeping its order and dropping duplicates
+    pub fn negotiate(mut self, requested: &[String]) -> Self {
+        let mut accepted = Vec::new();
+        let mut unsupported = Vec::new();
+
+        for feature in requested {
+            if accepted.contains(feature) || unsupported.contains(feature) {
+                continue;
+            }
+            if self.supports(feature) {
+                accepted.push(feature.clone());
+            } else {
+                unsupported.push(feature.clone());
+            }
+        }
+
+        self.accepted = Some(accepted);
+        self.unsupported = Some(unsupported);
+        self
+    }
+
+    /// Check whether the service advertises a capability
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// Health of a component or a whole service, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// Working with reduced functionality (e.g. door not homed, touch device missing)
+    Degraded,
+    /// Not working; needs attention
+    Failed,
+}
+
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Ok => "ok",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Health of one part of a service (hardware link, external dependency, ...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    /// Why the component isn't `ok`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl ComponentHealth {
+    pub fn ok(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Ok,
+            reason: None,
+        }
+    }
+
+    pub fn degraded(name: &str, reason: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Degraded,
+            reason: Some(reason.into()),
+        }
+    }
+
+    pub fn failed(name: &str, reason: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Failed,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// The most recent panic, from the crash report the service wrote at the time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashInfo {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Service version that panicked
+    pub version: String,
+    pub message: String,
+    /// `file:line:column` of the panic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Hash of the config that was running, to spot config-related crashes
+    pub config_hash: String,
+}
+
+/// Reply to a `get_health`/`GetHealth` request
+///
+/// `status` is the worst of the component statuses, so monitoring can show one badge per
+/// device and expand to the components for detail. `last_crash` is informational and doesn't
+/// affect `status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub service: String,
+    pub status: HealthStatus,
+    pub components: Vec<ComponentHealth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_crash: Option<CrashInfo>,
+}
+
+impl Health {
+    pub fn new(service: &str, components: Vec<ComponentHealth>) -> Self {
+        let status = components
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(HealthStatus::Ok);
+
+        Self {
+            service: service.to_string(),
+            status,
+            components,
+            last_crash: None,
+        }
+    }
+
+    /// Attach the last crash, if there was one
+    pub fn with_last_crash(mut self, crash: Option<CrashInfo>) -> Self {
+        self.last_crash = crash;
+        self
+    }
+}
+
+/// Outcome of one action in a scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneActionResult {
+    /// `local` or the name of the peer the action was sent to
+    pub target: String,
+    /// Short action name (e.g. `close`, `sleep`, `announce`)
+    pub action: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reply to a `run_scene`/`RunScene` request
+///
+/// Every action is attempted even if an earlier one failed; `success` is true only if all
+/// of them succeeded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneReport {
+    pub scene: String,
+    pub success: bool,
+    pub results: Vec<SceneActionResult>,
+}
+
+impl SceneReport {
+    pub fn new(scene: &str, results: Vec<SceneActionResult>) -> Self {
+        Self {
+            scene: scene.to_string(),
+            success: results.iter().all(|r| r.success),
+            results,
+        }
+    }
+}
+
+/// Reply to a request refused by the rate limiter
+///
+/// Serializes as an ordinary `error` message with an extra `retry_after_ms` hint, so
+/// clients that only understand `{"type": "error", "message": ...}` still handle it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "error")]
+pub struct RateLimitError {
+    pub message: String,
+    pub retry_after_ms: u64,
+}
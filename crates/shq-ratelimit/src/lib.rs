@@ -0,0 +1,154 @@
+//! Per-client request rate limiting.
+//!
+//! A token bucket per client IP: each request takes a token, buckets hold up to `burst`
+//! tokens and refill at `rate` per second. Requests that find an empty bucket are refused
+//! with a [`RateLimited`] error saying how long until the next token. The WebSocket servers
+//! apply it through `shq-ws`; overwatch wraps it in a tonic interceptor.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets kept before idle ones are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// `rate_limit` config section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Sustained requests per second per client
+    pub rate: f64,
+    /// Requests a client may make in a quick burst before the rate applies
+    pub burst: u32,
+    /// Clients that are never limited (e.g. the Home Assistant host)
+    pub exempt: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate: 10.0,
+            burst: 20,
+            exempt: Vec::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Check the limits; call from the app's `validate()`
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.rate.is_finite() || self.rate <= 0.0 {
+            anyhow::bail!("rate_limit.rate must be greater than 0, got {}", self.rate);
+        }
+        if self.burst == 0 {
+            anyhow::bail!("rate_limit.burst must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+/// A request refused because the client used up its burst
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimited {
+    /// Wait before the next request will be accepted
+    pub retry_after: Duration,
+}
+
+impl RateLimited {
+    /// `retry_after` rounded up to whole milliseconds
+    pub fn retry_after_ms(&self) -> u64 {
+        self.retry_after.as_nanos().div_ceil(1_000_000) as u64
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Rate limit exceeded, retry after {}ms",
+            self.retry_after_ms()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Shared limiter; clones use the same buckets
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    config: RateLimitConfig,
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                config,
+                buckets: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Apply a reloaded config; existing buckets keep their tokens, capped at the new burst
+    pub fn update(&self, config: RateLimitConfig) {
+        let mut inner = self.inner.lock().unwrap();
+        let burst = config.burst as f64;
+        for bucket in inner.buckets.values_mut() {
+            bucket.tokens = bucket.tokens.min(burst);
+        }
+        inner.config = config;
+    }
+
+    /// Take a token for one request from `client`
+    pub fn check(&self, client: IpAddr) -> Result<(), RateLimited> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.config.enabled || inner.config.exempt.contains(&client) {
+            return Ok(());
+        }
+
+        let rate = inner.config.rate;
+        let burst = inner.config.burst as f64;
+        let now = Instant::now();
+
+        if inner.buckets.len() >= PRUNE_THRESHOLD {
+            // A bucket that has had time to refill completely is the same as a new one
+            let full_after = Duration::from_secs_f64(burst / rate);
+            inner
+                .buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated) < full_after);
+        }
+
+        let bucket = inner.buckets.entry(client).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(RateLimited {
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / rate),
+            })
+        }
+    }
+}
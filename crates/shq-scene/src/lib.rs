@@ -0,0 +1,304 @@
+//! Scenes: named bundles of actions spanning SHQ services.
+//!
+//! A service embeds a [`SceneConfig`] with its own action type for local actions (the same
+//! type its schedule uses) and a list of WebSocket peers — other dosa/nyx instances — that
+//! remote actions are sent to. [`SceneConfig::run`] performs a scene's actions in order and
+//! reports each result; a failing action doesn't stop the ones after it.
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shq_protocol::{SceneActionResult, SceneReport};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Longest a single action may take before it is reported as failed
+const ACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `id` given to [`Peer::request`] messages; each request has its own connection
+const REQUEST_ID: &str = "peer-request";
+
+/// `scene` config section: peers plus the scenes themselves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneConfig<A> {
+    /// Services remote actions can be sent to, by name
+    #[serde(default = "BTreeMap::new")]
+    pub peers: BTreeMap<String, Peer>,
+    #[serde(default = "Vec::new")]
+    pub scenes: Vec<Scene<A>>,
+}
+
+impl<A> Default for SceneConfig<A> {
+    fn default() -> Self {
+        Self {
+            peers: BTreeMap::new(),
+            scenes: Vec::new(),
+        }
+    }
+}
+
+impl<A> SceneConfig<A> {
+    /// Check peer URLs, scene IDs and peer references; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        for (name, peer) in &self.peers {
+            if !peer.url.starts_with("ws://") && !peer.url.starts_with("wss://") {
+                anyhow::bail!(
+                    "scene peer '{}': url must start with ws:// or wss://, got '{}'",
+                    name,
+                    peer.url
+                );
+            }
+        }
+
+        let mut ids = HashSet::new();
+        for scene in &self.scenes {
+            if scene.id.is_empty() {
+                anyhow::bail!("scene IDs must not be empty");
+            }
+            if !ids.insert(scene.id.as_str()) {
+                anyhow::bail!("Duplicate scene ID '{}'", scene.id);
+            }
+            for action in &scene.actions {
+                if let SceneAction::Remote { peer, message } = action {
+                    if !self.peers.contains_key(peer) {
+                        anyhow::bail!("scene '{}': unknown peer '{}'", scene.id, peer);
+                    }
+                    if message.get("type").and_then(Value::as_str).is_none() {
+                        anyhow::bail!(
+                            "scene '{}': message for peer '{}' needs a \"type\"",
+                            scene.id,
+                            peer
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Local actions of every scene, for app-specific validation
+    pub fn local_actions(&self) -> impl Iterator<Item = (&str, &A)> {
+        self.scenes.iter().flat_map(|scene| {
+            scene.actions.iter().filter_map(move |action| match action {
+                SceneAction::Local(action) => Some((scene.id.as_str(), action)),
+                SceneAction::Remote { .. } => None,
+            })
+        })
+    }
+}
+
+impl<A: Serialize> SceneConfig<A> {
+    /// Run scene `id`, performing local actions through `local`
+    ///
+    /// Errors only if the scene doesn't exist; failed actions are recorded in the report.
+    pub async fn run<L: LocalActions<A>>(&self, id: &str, local: &L) -> Result<SceneReport> {
+        let scene = self
+            .scenes
+            .iter()
+            .find(|scene| scene.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown scene '{}'", id))?;
+
+        tracing::info!("Running scene '{}'", scene.display_name());
+
+        let mut results = Vec::with_capacity(scene.actions.len());
+        for action in &scene.actions {
+            let (target, name, result) = match action {
+                SceneAction::Local(action) => (
+                    "local".to_string(),
+                    local_action_name(action),
+                    with_timeout(local.run(action)).await,
+                ),
+                SceneAction::Remote { peer, message } => {
+                    let name = message
+                        .get("type")
+                        .and_then(Value::as_str)
+                        .unwrap_or("message")
+                        .to_string();
+                    let url = &self.peers[peer].url;
+                    (
+                        peer.clone(),
+                        name,
+                        with_timeout(send_remote(url, message)).await,
+                    )
+                }
+            };
+
+            if let Err(e) = &result {
+                tracing::warn!(
+                    "Scene '{}': {} on {} failed: {:#}",
+                    scene.id,
+                    name,
+                    target,
+                    e
+                );
+            }
+
+            results.push(SceneActionResult {
+                target,
+                action: name,
+                success: result.is_ok(),
+                error: result.err().map(|e| format!("{:#}", e)),
+            });
+        }
+
+        Ok(SceneReport::new(&scene.id, results))
+    }
+}
+
+/// Another SHQ service reachable over WebSocket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Peer {
+    /// e.g. `ws://door.local:8766`, or `wss://` for a peer with TLS
+    pub url: String,
+    /// The peer's `backup.token`, presented when a hub exports or restores its config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_token: Option<String>,
+}
+
+impl Peer {
+    /// Send one command and return the peer's reply to it
+    ///
+    /// The reply is matched on the message `id`, so broadcasts arriving first are skipped.
+    /// `error` replies are returned as errors.
+    pub async fn request(&self, message: &Value) -> Result<Value> {
+        let mut message = message.clone();
+        if let Some(object) = message.as_object_mut() {
+            object.insert("id".to_string(), Value::from(REQUEST_ID));
+        }
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(&self.url)
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.url))?;
+        ws.send(Message::Text(message.to_string())).await?;
+
+        let result = loop {
+            let Some(frame) = ws.next().await else {
+                break Err(anyhow::anyhow!("Connection closed before a reply"));
+            };
+            let Message::Text(text) = frame? else {
+                continue;
+            };
+
+            let reply: Value = serde_json::from_str(&text)?;
+            match reply.get("type").and_then(Value::as_str) {
+                Some("server_shutting_down") => {
+                    break Err(anyhow::anyhow!("Peer is shutting down"))
+                }
+                _ if reply.get("id").and_then(Value::as_str) != Some(REQUEST_ID) => {}
+                Some("error") => {
+                    let message = reply
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown error");
+                    break Err(anyhow::anyhow!("{}", message));
+                }
+                _ => break Ok(reply),
+            }
+        };
+
+        let _ = ws.close(None).await;
+        result
+    }
+}
+
+/// A named bundle of actions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene<A> {
+    /// Unique name, used to trigger the scene
+    pub id: String,
+    /// Friendly name for logs and UIs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub actions: Vec<SceneAction<A>>,
+}
+
+impl<A> Scene<A> {
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// One step of a scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SceneAction<A> {
+    /// Service-specific action performed by the service running the scene
+    Local(A),
+    /// WebSocket command (e.g. `{"type": "close"}`) sent to a peer
+    Remote { peer: String, message: Value },
+}
+
+/// Performs a service's own scene actions
+pub trait LocalActions<A>: Sync {
+    /// Perform `action`, returning once it has finished
+    fn run(&self, action: &A) -> impl Future<Output = Result<()>> + Send;
+}
+
+async fn with_timeout(action: impl Future<Output = Result<()>>) -> Result<()> {
+    timeout(ACTION_TIMEOUT, action).await.unwrap_or_else(|_| {
+        Err(anyhow::anyhow!(
+            "Timed out after {}s",
+            ACTION_TIMEOUT.as_secs()
+        ))
+    })
+}
+
+/// Name of a unit variant (`"close"`) or the key of a struct variant (`{"move": {...}}`)
+fn local_action_name<A: Serialize>(action: &A) -> String {
+    match serde_json::to_value(action) {
+        Ok(Value::String(name)) => name,
+        Ok(Value::Object(map)) if map.len() == 1 => map.keys().next().cloned().unwrap_or_default(),
+        _ => "local".to_string(),
+    }
+}
+
+/// Send one command to a peer and wait for its `response`/`error` reply
+///
+/// Status and metrics broadcasts that arrive first are skipped. Peers acknowledge long
+/// operations (door moves) when they start, so success means the command was accepted.
+async fn send_remote(url: &str, message: &Value) -> Result<()> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .with_context(|| format!("Failed to connect to {}", url))?;
+
+    ws.send(Message::Text(message.to_string())).await?;
+
+    let result = loop {
+        let Some(frame) = ws.next().await else {
+            break Err(anyhow::anyhow!("Connection closed before a reply"));
+        };
+        let Message::Text(text) = frame? else {
+            continue;
+        };
+
+        let reply: Value = serde_json::from_str(&text)?;
+        match reply.get("type").and_then(Value::as_str) {
+            Some("response") | Some("scene_result") => {
+                if reply
+                    .get("success")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false)
+                {
+                    break Ok(());
+                }
+                break Err(anyhow::anyhow!("Peer reported failure"));
+            }
+            Some("error") => {
+                let message = reply
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error");
+                break Err(anyhow::anyhow!("{}", message));
+            }
+            Some("server_shutting_down") => break Err(anyhow::anyhow!("Peer is shutting down")),
+            _ => {}
+        }
+    };
+
+    let _ = ws.close(None).await;
+    result
+}
@@ -0,0 +1,411 @@
+//! Cron-style job scheduling shared by SHQ services.
+//!
+//! Services embed a [`SchedulerConfig`] with their own action type (door commands, display
+//! power, alarms, ...) and call [`Scheduler::start`]; due jobs arrive as [`Trigger`]s on the
+//! returned channel. Jobs recur on a cron expression or run once at a fixed time. The last
+//! run of every job is persisted so runs missed while the service was down (or the host was
+//! asleep) can be caught up according to [`MissedRunPolicy`].
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+/// Longest sleep between checks, so wall-clock jumps (NTP sync, suspend) are noticed quickly
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How late a run may fire before it counts as missed
+const LATE_TOLERANCE: chrono::Duration = chrono::Duration::seconds(90);
+
+/// `schedule` config section: a time zone plus a list of jobs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerConfig<A> {
+    /// IANA time zone for cron expressions (e.g. "Australia/Sydney"); system local time if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default = "Vec::new")]
+    pub jobs: Vec<Job<A>>,
+}
+
+impl<A> Default for SchedulerConfig<A> {
+    fn default() -> Self {
+        Self {
+            timezone: None,
+            jobs: Vec::new(),
+        }
+    }
+}
+
+impl<A> SchedulerConfig<A> {
+    /// Check time zones, cron expressions and job IDs; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        Zone::parse(self.timezone.as_deref())?;
+
+        let mut ids = HashSet::new();
+        for job in &self.jobs {
+            if job.id.is_empty() {
+                anyhow::bail!("schedule job IDs must not be empty");
+            }
+            if !ids.insert(job.id.as_str()) {
+                anyhow::bail!("Duplicate schedule job ID '{}'", job.id);
+            }
+            When::parse(job).with_context(|| format!("schedule job '{}'", job.id))?;
+            Zone::parse(job.timezone.as_deref())
+                .with_context(|| format!("schedule job '{}'", job.id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Next time `job` is due after now, in its own or the schedule's time zone; `None` if the
+    /// job is disabled, invalid or never due again
+    pub fn next_run(&self, job: &Job<A>) -> Option<DateTime<Utc>> {
+        if !job.enabled {
+            return None;
+        }
+        let when = When::parse(job).ok()?;
+        let zone = match Zone::parse(job.timezone.as_deref()).ok()? {
+            Some(zone) => zone,
+            None => Zone::parse(self.timezone.as_deref()).ok()?.unwrap_or(Zone::Local),
+        };
+        when.next_after(zone, Utc::now())
+    }
+}
+
+/// A scheduled action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job<A> {
+    /// Unique name, used in logs and to track the last run
+    pub id: String,
+    /// `minute hour day-of-month month day-of-week` (e.g. `30 7 * * Mon-Fri`); a leading
+    /// seconds field (6-7 fields) is also accepted. Empty for a one-shot job.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub cron: String,
+    /// Run once at this time instead of on `cron` (e.g. `2026-10-18T07:00:00+11:00`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<DateTime<FixedOffset>>,
+    /// Overrides the schedule-wide time zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub missed: MissedRunPolicy,
+    /// Service-specific action to perform
+    pub action: A,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// What to do about runs that should have happened while the service was down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedRunPolicy {
+    /// Drop missed runs and wait for the next one
+    #[default]
+    Skip,
+    /// Run once on startup (however many runs were missed), then resume the schedule
+    RunOnce,
+}
+
+/// A job that is due
+#[derive(Debug, Clone)]
+pub struct Trigger<A> {
+    pub job_id: String,
+    pub action: A,
+    /// When the run was scheduled for
+    pub scheduled: DateTime<Utc>,
+    /// Catch-up run for a time that passed while the service wasn't running
+    pub missed: bool,
+}
+
+/// Handle for a running scheduler
+pub struct Scheduler<A> {
+    config_tx: watch::Sender<SchedulerConfig<A>>,
+}
+
+impl<A> Scheduler<A>
+where
+    A: Clone + Send + Sync + 'static,
+{
+    /// Start scheduling `config`'s jobs
+    ///
+    /// Last-run times are kept in `state_path` (JSON) if given. Due jobs arrive on the returned
+    /// channel; dropping the receiver stops the scheduler.
+    pub fn start(
+        config: SchedulerConfig<A>,
+        state_path: Option<PathBuf>,
+    ) -> (Self, mpsc::Receiver<Trigger<A>>) {
+        let (config_tx, config_rx) = watch::channel(config);
+        let (trigger_tx, trigger_rx) = mpsc::channel(16);
+
+        tokio::spawn(run(config_rx, RunState::load(state_path), trigger_tx));
+
+        (Self { config_tx }, trigger_rx)
+    }
+
+    /// Replace the schedule (e.g. after a config reload)
+    pub fn update(&self, config: SchedulerConfig<A>) {
+        self.config_tx.send_replace(config);
+    }
+}
+
+/// Time zone cron expressions are evaluated in
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    fn parse(name: Option<&str>) -> Result<Option<Self>> {
+        match name {
+            None => Ok(None),
+            Some(name) => name
+                .parse::<Tz>()
+                .map(|tz| Some(Zone::Named(tz)))
+                .map_err(|_| anyhow::anyhow!("Unknown time zone '{}'", name)),
+        }
+    }
+
+    /// Next fire time of `schedule` strictly after `after`
+    fn next_after(&self, schedule: &Schedule, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        fn next<Z: TimeZone>(
+            schedule: &Schedule,
+            zone: &Z,
+            after: DateTime<Utc>,
+        ) -> Option<DateTime<Utc>> {
+            schedule
+                .after(&after.with_timezone(zone))
+                .next()
+                .map(|t| t.with_timezone(&Utc))
+        }
+
+        match self {
+            Zone::Local => next(schedule, &Local, after),
+            Zone::Named(tz) => next(schedule, tz, after),
+        }
+    }
+}
+
+/// When a job runs: on a cron schedule, or once
+#[derive(Debug, Clone)]
+enum When {
+    Cron(Box<Schedule>),
+    At(DateTime<Utc>),
+}
+
+impl When {
+    /// A job's `cron` or `at`, whichever it has (exactly one is required)
+    fn parse<A>(job: &Job<A>) -> Result<Self> {
+        match (job.cron.is_empty(), job.at) {
+            (false, None) => Ok(When::Cron(Box::new(parse_cron(&job.cron)?))),
+            (true, Some(at)) => Ok(When::At(at.with_timezone(&Utc))),
+            (false, Some(_)) => anyhow::bail!("Give either cron or at, not both"),
+            (true, None) => anyhow::bail!("Needs either cron or at"),
+        }
+    }
+
+    /// Next run strictly after `after`
+    fn next_after(&self, zone: Zone, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            When::Cron(schedule) => zone.next_after(schedule, after),
+            When::At(at) => Some(*at).filter(|at| *at > after),
+        }
+    }
+}
+
+/// Parse a 5-field cron expression (or the `cron` crate's native 6-7 field form with seconds)
+fn parse_cron(expression: &str) -> Result<Schedule> {
+    let fields = expression.split_whitespace().count();
+    let expression = if fields == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+
+    Schedule::from_str(&expression)
+        .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", expression, e))
+}
+
+/// Last-run times, persisted so missed runs can be detected across restarts
+struct RunState {
+    path: Option<PathBuf>,
+    last_runs: HashMap<String, DateTime<Utc>>,
+}
+
+impl RunState {
+    fn load(path: Option<PathBuf>) -> Self {
+        let last_runs = path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                std::fs::read_to_string(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Ok(serde_json::from_str(&json)?))
+                    .map_err(|e| {
+                        tracing::warn!("Ignoring unreadable schedule state {:?}: {}", path, e)
+                    })
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        Self { path, last_runs }
+    }
+
+    fn record(&mut self, job_id: &str, run: DateTime<Utc>) {
+        self.last_runs.insert(job_id.to_string(), run);
+
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        // Write-then-rename so a crash can't leave a truncated file
+        let result = serde_json::to_string_pretty(&self.last_runs)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let tmp = path.with_extension("tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to save schedule state to {:?}: {}", path, e);
+        }
+    }
+}
+
+struct ActiveJob<A> {
+    id: String,
+    when: When,
+    zone: Zone,
+    missed: MissedRunPolicy,
+    action: A,
+    next: Option<DateTime<Utc>>,
+}
+
+/// Build the active job list from config, starting each job after its last recorded run
+fn activate<A: Clone>(config: &SchedulerConfig<A>, state: &RunState) -> Vec<ActiveJob<A>> {
+    let default_zone = match Zone::parse(config.timezone.as_deref()) {
+        Ok(zone) => zone.unwrap_or(Zone::Local),
+        Err(e) => {
+            tracing::error!("{}, using local time", e);
+            Zone::Local
+        }
+    };
+
+    let now = Utc::now();
+
+    config
+        .jobs
+        .iter()
+        .filter(|job| job.enabled)
+        .filter_map(|job| {
+            let when = When::parse(job)
+                .map_err(|e| tracing::error!("Schedule job '{}' disabled: {}", job.id, e))
+                .ok()?;
+            let zone = match Zone::parse(job.timezone.as_deref()) {
+                Ok(zone) => zone.unwrap_or(default_zone),
+                Err(e) => {
+                    tracing::error!("Schedule job '{}' disabled: {}", job.id, e);
+                    return None;
+                }
+            };
+
+            // A one-shot that has never run is due even if its time passed while the service was
+            // down, so the missed-run policy decides whether it still fires
+            let after = match (state.last_runs.get(&job.id), &when) {
+                (Some(last_run), _) => *last_run,
+                (None, When::At(_)) => DateTime::<Utc>::MIN_UTC,
+                (None, When::Cron(_)) => now,
+            };
+            let next = when.next_after(zone, after);
+            match next {
+                Some(next) => tracing::info!("Schedule job '{}' next runs at {}", job.id, next),
+                None => tracing::warn!("Schedule job '{}' has no upcoming runs", job.id),
+            }
+
+            Some(ActiveJob {
+                id: job.id.clone(),
+                when,
+                zone,
+                missed: job.missed,
+                action: job.action.clone(),
+                next,
+            })
+        })
+        .collect()
+}
+
+async fn run<A: Clone + Send + Sync + 'static>(
+    mut config_rx: watch::Receiver<SchedulerConfig<A>>,
+    mut state: RunState,
+    trigger_tx: mpsc::Sender<Trigger<A>>,
+) {
+    loop {
+        let mut jobs = activate(&config_rx.borrow_and_update(), &state);
+
+        loop {
+            let now = Utc::now();
+            let delay = jobs
+                .iter()
+                .filter_map(|job| job.next)
+                .min()
+                .map(|next| (next - now).to_std().unwrap_or(Duration::ZERO))
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+
+            tokio::select! {
+                _ = sleep(delay) => {}
+                changed = config_rx.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    tracing::info!("Schedule changed, reloading jobs");
+                    break;
+                }
+            }
+
+            let now = Utc::now();
+            for job in &mut jobs {
+                let Some(due) = job.next.filter(|due| *due <= now) else {
+                    continue;
+                };
+
+                let missed = now - due > LATE_TOLERANCE;
+                if missed && job.missed == MissedRunPolicy::Skip {
+                    tracing::info!(
+                        "Skipping missed run of schedule job '{}' (due {})",
+                        job.id,
+                        due
+                    );
+                } else {
+                    tracing::info!("Running schedule job '{}'", job.id);
+                    let trigger = Trigger {
+                        job_id: job.id.clone(),
+                        action: job.action.clone(),
+                        scheduled: due,
+                        missed,
+                    };
+                    if trigger_tx.send(trigger).await.is_err() {
+                        tracing::debug!("Schedule trigger receiver dropped, stopping scheduler");
+                        return;
+                    }
+                    state.record(&job.id, due);
+                }
+
+                // However many runs were missed, resume from now
+                job.next = job.when.next_after(job.zone, now);
+            }
+        }
+    }
+}
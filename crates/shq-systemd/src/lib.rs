@@ -0,0 +1,171 @@
+//! systemd integration for SHQ services.
+//!
+//! Readiness/stopping notifications, shutdown signal handling and a watchdog that only
+//! pings systemd while every registered core loop is still making progress. All calls are
+//! no-ops when the service isn't started by systemd, so binaries behave the same when run
+//! by hand.
+
+use sd_notify::NotifyState;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+
+/// Tell systemd the service has finished starting up (`Type=notify`)
+pub fn notify_ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+/// Tell systemd the service is shutting down
+pub fn notify_stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+/// Set the free-form status shown by `systemctl status`
+pub fn notify_status(status: &str) {
+    notify(&[NotifyState::Status(status)]);
+}
+
+/// Wait for SIGTERM (`systemctl stop`) or SIGINT (Ctrl+C)
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Unable to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Unable to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
+
+fn notify(state: &[NotifyState]) {
+    // Fails harmlessly with no NOTIFY_SOCKET; only a broken socket is worth logging
+    if let Err(e) = sd_notify::notify(false, state) {
+        tracing::debug!("sd_notify failed: {}", e);
+    }
+}
+
+/// Liveness marker for a single core loop
+///
+/// The loop calls [`Heartbeat::beat`] every iteration. If any heartbeat goes quiet for
+/// longer than the watchdog timeout, pings stop and systemd restarts the service.
+#[derive(Clone)]
+pub struct Heartbeat {
+    name: Arc<str>,
+    last_beat: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+    /// Record that the loop is alive
+    pub fn beat(&self) {
+        *self.last_beat.lock().unwrap() = Instant::now();
+    }
+
+    fn since_last_beat(&self) -> Duration {
+        self.last_beat.lock().unwrap().elapsed()
+    }
+}
+
+/// systemd watchdog driven by core-loop heartbeats (`WatchdogSec=`)
+#[derive(Clone)]
+pub struct Watchdog {
+    timeout: Option<Duration>,
+    heartbeats: Arc<Mutex<Vec<Heartbeat>>>,
+}
+
+impl Watchdog {
+    /// Create a watchdog using the timeout systemd passed via `WATCHDOG_USEC`
+    pub fn new() -> Self {
+        let mut usec = 0;
+        let timeout =
+            sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec));
+
+        Self {
+            timeout,
+            heartbeats: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Register a core loop that must keep beating for the service to be considered alive
+    pub fn heartbeat(&self, name: &str) -> Heartbeat {
+        let heartbeat = Heartbeat {
+            name: name.into(),
+            last_beat: Arc::new(Mutex::new(Instant::now())),
+        };
+        self.heartbeats.lock().unwrap().push(heartbeat.clone());
+        heartbeat
+    }
+
+    /// Start pinging systemd at half the watchdog timeout
+    ///
+    /// Returns `None` if the watchdog isn't enabled for this service.
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        let Some(timeout) = self.timeout else {
+            tracing::debug!("systemd watchdog not enabled");
+            return None;
+        };
+
+        tracing::info!("systemd watchdog enabled ({:?} timeout)", timeout);
+        let heartbeats = self.heartbeats.clone();
+
+        Some(tokio::spawn(async move {
+            let mut ticker = interval(timeout / 2);
+            let mut withholding = false;
+
+            loop {
+                ticker.tick().await;
+
+                let stalled = heartbeats
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|hb| (hb.name.clone(), hb.since_last_beat()))
+                    .find(|(_, silent_for)| *silent_for > timeout);
+
+                match stalled {
+                    None => {
+                        if withholding {
+                            tracing::info!(
+                                "All core loops responsive again, resuming watchdog pings"
+                            );
+                            withholding = false;
+                        }
+                        notify(&[NotifyState::Watchdog]);
+                    }
+                    Some((name, silent_for)) => {
+                        if !withholding {
+                            tracing::error!(
+                                "Core loop '{}' silent for {:?}, withholding watchdog ping",
+                                name,
+                                silent_for
+                            );
+                            withholding = true;
+                        }
+                    }
+                }
+            }
+        }))
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,734 @@
+//! WebSocket server framework shared by SHQ services.
+//!
+//! Provides the accept loop, client registry, topic-based broadcasts, ping/pong with dead
+//! client reaping, an authorization hook, optional TLS, optional per-IP rate limiting and
+//! graceful shutdown. Services implement [`Handler`] for their message protocol and use a
+//! [`Broadcaster`] to push updates to subscribed clients. Each message is handled in a
+//! [`shq_logging::command_span`], so its log lines share a correlation ID. Every outgoing
+//! message is wrapped in a [`shq_protocol::Envelope`]; replies echo the `id` of the request
+//! they answer. Servers that allow it ([`Server::msgpack`]) talk MessagePack in binary frames
+//! to clients that ask for the `msgpack` subprotocol.
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shq_protocol::{Envelope, RateLimitError};
+use shq_ratelimit::{RateLimited, RateLimiter};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep_until, timeout, Duration, Instant, MissedTickBehavior};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::{accept_hdr_async, tungstenite::Message};
+use tracing::Instrument;
+
+pub use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+pub type ClientId = usize;
+
+/// How long connections get to finish in-flight requests once shutdown starts
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client gets to complete the TLS handshake
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Per-client queue depth; slow clients skip the oldest messages beyond this
+const CLIENT_QUEUE: usize = 100;
+
+/// `Sec-WebSocket-Protocol` a client offers to talk MessagePack instead of JSON
+const MSGPACK_PROTOCOL: &str = "msgpack";
+
+/// `websocket.tls` config: serve `wss://` with this certificate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, server certificate first
+    pub cert: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key: PathBuf,
+}
+
+impl TlsConfig {
+    /// Load the certificate and key
+    fn acceptor(&self) -> Result<TlsAcceptor> {
+        let certs = rustls_pemfile::certs(&mut read_pem(&self.cert)?.as_slice())
+            .collect::<std::result::Result<Vec<CertificateDer>, _>>()
+            .with_context(|| format!("Invalid certificate in {:?}", self.cert))?;
+        if certs.is_empty() {
+            anyhow::bail!("No certificate found in {:?}", self.cert);
+        }
+        let key: PrivateKeyDer = rustls_pemfile::private_key(&mut read_pem(&self.key)?.as_slice())
+            .with_context(|| format!("Invalid private key in {:?}", self.key))?
+            .with_context(|| format!("No private key found in {:?}", self.key))?;
+
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .context("Certificate and key don't match")?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// `websocket.keep_alive` config: server pings, and how long a client gets to answer
+///
+/// A client that sends nothing (not even a pong) within `timeout_secs` of a ping is dropped, so
+/// half-open connections from crashed clients don't linger in the registry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepAliveConfig {
+    /// Seconds between pings
+    pub ping_interval_secs: u64,
+    /// Seconds to wait for the answer to a ping
+    pub timeout_secs: u64,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval_secs: 30,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl KeepAliveConfig {
+    /// Check the intervals; call from the app's `validate()`
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=3600).contains(&self.ping_interval_secs) {
+            anyhow::bail!(
+                "websocket.keep_alive.ping_interval_secs must be 1-3600, got {}",
+                self.ping_interval_secs
+            );
+        }
+        if !(1..=3600).contains(&self.timeout_secs) {
+            anyhow::bail!(
+                "websocket.keep_alive.timeout_secs must be 1-3600, got {}",
+                self.timeout_secs
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A service's WebSocket protocol
+pub trait Handler: Send + Sync + 'static {
+    /// Server-to-client message type
+    type Message: Serialize + Send;
+
+    /// Per-connection state, created by [`Handler::authorize`]
+    type State: Send + 'static;
+
+    /// Topics every new client is subscribed to
+    fn default_topics(&self) -> &'static [&'static str];
+
+    /// Inspect the HTTP upgrade request (headers, path, query) and create the client's state
+    ///
+    /// Returning an error rejects the connection with 401 Unauthorized.
+    fn authorize(&self, request: &Request) -> Result<Self::State, String>;
+
+    /// Messages to send as soon as a client connects (e.g. current status)
+    fn on_connect(
+        &self,
+        _client: &mut Client<Self::State>,
+    ) -> impl Future<Output = Vec<Self::Message>> + Send {
+        async { Vec::new() }
+    }
+
+    /// Handle a text message from a client, returning the reply
+    fn on_message(
+        &self,
+        client: &mut Client<Self::State>,
+        text: &str,
+    ) -> impl Future<Output = Self::Message> + Send;
+
+    /// Message sent to every client when the server shuts down, before the connection closes
+    fn shutdown_message(&self) -> Option<Self::Message> {
+        None
+    }
+
+    /// Text of the error sent for a request refused by the rate limiter (e.g. translated)
+    fn rate_limited_message(&self, limited: &RateLimited) -> String {
+        limited.to_string()
+    }
+
+    /// Called after a client disconnects
+    fn on_disconnect(&self, _client: &mut Client<Self::State>) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+struct ClientEntry {
+    tx: broadcast::Sender<Arc<str>>,
+    topics: HashSet<String>,
+}
+
+/// Registry of connected clients, used to push messages to topic subscribers
+#[derive(Clone)]
+pub struct Broadcaster {
+    clients: Arc<Mutex<HashMap<ClientId, ClientEntry>>>,
+    next_client_id: Arc<AtomicUsize>,
+    /// Service name stamped on every envelope
+    source: &'static str,
+    /// Envelope sequence number, shared by replies and broadcasts
+    next_seq: Arc<AtomicU64>,
+}
+
+impl Broadcaster {
+    pub fn new(source: &'static str) -> Self {
+        Self {
+            clients: Arc::default(),
+            next_client_id: Arc::default(),
+            source,
+            next_seq: Arc::default(),
+        }
+    }
+
+    /// Send a message to every client subscribed to `topic`
+    pub async fn broadcast<M: Serialize>(&self, topic: &str, message: &M) -> Result<()> {
+        let json: Arc<str> = self.encode(None, message)?.into();
+        let clients = self.clients.lock().await;
+
+        for (client_id, client) in clients.iter() {
+            if client.topics.contains(topic) {
+                if let Err(e) = client.tx.send(json.clone()) {
+                    tracing::debug!("Failed to broadcast to client {}: {}", client_id, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check whether any client is subscribed to `topic`
+    pub async fn has_subscribers(&self, topic: &str) -> bool {
+        self.clients
+            .lock()
+            .await
+            .values()
+            .any(|client| client.topics.contains(topic))
+    }
+
+    /// Number of connected clients
+    pub async fn client_count(&self) -> usize {
+        self.clients.lock().await.len()
+    }
+
+    /// Serialize `message` in an envelope, replying to request `id` if given
+    fn encode<M: Serialize>(&self, id: Option<Value>, message: &M) -> Result<String> {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        Ok(serde_json::to_string(&Envelope::new(
+            id,
+            self.source,
+            seq,
+            message,
+        ))?)
+    }
+
+    async fn register(&self, topics: &[&str]) -> (ClientId, broadcast::Receiver<Arc<str>>) {
+        let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = broadcast::channel(CLIENT_QUEUE);
+
+        let entry = ClientEntry {
+            tx,
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+        };
+        self.clients.lock().await.insert(client_id, entry);
+
+        (client_id, rx)
+    }
+
+    async fn unregister(&self, client_id: ClientId) {
+        self.clients.lock().await.remove(&client_id);
+    }
+}
+
+/// A connected client, passed to every [`Handler`] callback
+pub struct Client<S> {
+    pub id: ClientId,
+    pub addr: SocketAddr,
+    /// Handler-defined per-connection state
+    pub state: S,
+    broadcaster: Broadcaster,
+}
+
+impl<S> Client<S> {
+    /// Start receiving broadcasts on `topic`
+    pub async fn subscribe(&self, topic: &str) {
+        if let Some(client) = self.broadcaster.clients.lock().await.get_mut(&self.id) {
+            client.topics.insert(topic.to_string());
+        }
+    }
+
+    /// Stop receiving broadcasts on `topic`
+    pub async fn unsubscribe(&self, topic: &str) {
+        if let Some(client) = self.broadcaster.clients.lock().await.get_mut(&self.id) {
+            client.topics.remove(topic);
+        }
+    }
+
+    /// Whether this client receives broadcasts on `topic`
+    pub async fn is_subscribed(&self, topic: &str) -> bool {
+        self.broadcaster
+            .clients
+            .lock()
+            .await
+            .get(&self.id)
+            .is_some_and(|client| client.topics.contains(topic))
+    }
+
+    /// Queue a message for this client only
+    pub async fn send<M: Serialize>(&self, message: &M) -> Result<()> {
+        self.handle().send(message).await
+    }
+
+    /// Cloneable handle for sending to this client from a background task
+    pub fn handle(&self) -> ClientHandle {
+        ClientHandle {
+            id: self.id,
+            broadcaster: self.broadcaster.clone(),
+        }
+    }
+}
+
+/// Sends messages to one client from outside its handler callbacks; see [`Client::handle`]
+#[derive(Clone)]
+pub struct ClientHandle {
+    pub id: ClientId,
+    broadcaster: Broadcaster,
+}
+
+impl ClientHandle {
+    /// Queue a message for the client; dropped silently once it has disconnected
+    pub async fn send<M: Serialize>(&self, message: &M) -> Result<()> {
+        let json: Arc<str> = self.broadcaster.encode(None, message)?.into();
+        if let Some(client) = self.broadcaster.clients.lock().await.get(&self.id) {
+            let _ = client.tx.send(json);
+        }
+        Ok(())
+    }
+}
+
+/// WebSocket server bound to a listening socket
+pub struct Server<H: Handler> {
+    listener: TcpListener,
+    handler: Arc<H>,
+    broadcaster: Broadcaster,
+    tls: Option<TlsAcceptor>,
+    settings: ConnectionSettings,
+}
+
+/// How each connection is served
+#[derive(Clone)]
+struct ConnectionSettings {
+    rate_limiter: Option<RateLimiter>,
+    keep_alive: KeepAliveConfig,
+    /// Clients may ask for MessagePack frames
+    msgpack: bool,
+}
+
+/// Wire format of a connection, chosen during the handshake
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    /// JSON in text frames
+    Json,
+    /// MessagePack in binary frames, with the same structure as the JSON
+    MessagePack,
+}
+
+impl Encoding {
+    /// Frame carrying `json` (an encoded message) in this encoding
+    fn frame(self, json: &str) -> Result<Message> {
+        Ok(match self {
+            Encoding::Json => Message::Text(json.to_string()),
+            Encoding::MessagePack => {
+                let value: Value = serde_json::from_str(json)?;
+                Message::Binary(rmp_serde::to_vec(&value)?)
+            }
+        })
+    }
+}
+
+impl<H: Handler> Server<H> {
+    /// Bind the listening socket
+    pub async fn bind(addr: SocketAddr, handler: H, broadcaster: Broadcaster) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!("WebSocket server listening on {}", addr);
+
+        Ok(Self {
+            listener,
+            handler: Arc::new(handler),
+            broadcaster,
+            tls: None,
+            settings: ConnectionSettings {
+                rate_limiter: None,
+                keep_alive: KeepAliveConfig::default(),
+                msgpack: false,
+            },
+        })
+    }
+
+    /// Serve `wss://` only, with the certificate and key from `config`
+    ///
+    /// Fails if the files can't be read or don't match, so a bad setup is reported at startup.
+    /// Clients that don't complete a TLS handshake are dropped.
+    pub fn tls(mut self, config: &TlsConfig) -> Result<Self> {
+        self.tls = Some(config.acceptor()?);
+        tracing::info!("WebSocket TLS enabled with certificate {:?}", config.cert);
+        Ok(self)
+    }
+
+    /// Ping clients and drop unresponsive ones as `config` says (default: ping every 30s,
+    /// drop after 10s without an answer)
+    pub fn keep_alive(mut self, config: &KeepAliveConfig) -> Self {
+        self.settings.keep_alive = config.clone();
+        self
+    }
+
+    /// Limit how fast each client IP may send requests
+    ///
+    /// Requests over the limit get a [`RateLimitError`] reply and never reach the handler.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.settings.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Let clients that offer the `msgpack` subprotocol (`Sec-WebSocket-Protocol: msgpack`)
+    /// send and receive MessagePack in binary frames instead of JSON text
+    ///
+    /// Messages keep the structure they have in JSON, envelope included. Other clients are
+    /// unaffected.
+    pub fn msgpack(mut self) -> Self {
+        self.settings.msgpack = true;
+        self
+    }
+
+    /// Accept connections until `shutdown` completes, then close every connection
+    ///
+    /// On shutdown the listener is closed, clients finish the request they're handling, receive
+    /// [`Handler::shutdown_message`] and a close frame. Connections still open after a grace
+    /// period are dropped.
+    pub async fn run(self, shutdown: impl Future<Output = ()> + Send) -> Result<()> {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, peer_addr)) => {
+                        let handler = self.handler.clone();
+                        let broadcaster = self.broadcaster.clone();
+                        let settings = self.settings.clone();
+                        let shutdown_rx = shutdown_rx.clone();
+                        let tls = self.tls.clone();
+                        connections.spawn(async move {
+                            let result = match tls {
+                                Some(tls) => match timeout(TLS_HANDSHAKE_TIMEOUT, tls.accept(stream)).await {
+                                    Ok(Ok(stream)) => {
+                                        handle_connection(handler, broadcaster, settings, stream, peer_addr, shutdown_rx)
+                                            .await
+                                    }
+                                    Ok(Err(e)) => Err(anyhow::Error::new(e).context("TLS handshake failed")),
+                                    Err(_) => Err(anyhow::anyhow!("TLS handshake timed out")),
+                                },
+                                None => {
+                                    handle_connection(handler, broadcaster, settings, stream, peer_addr, shutdown_rx)
+                                        .await
+                                }
+                            };
+                            if let Err(e) = result {
+                                tracing::error!("Connection error from {}: {:#}", peer_addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        tracing::error!("Accept error: {}", e);
+                    }
+                },
+                // Reap finished connection tasks
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = &mut shutdown => break,
+            }
+        }
+
+        // Stop accepting before telling clients to go away
+        drop(self.listener);
+        tracing::info!(
+            "WebSocket server shutting down, closing {} connection(s)",
+            connections.len()
+        );
+        let _ = shutdown_tx.send(true);
+
+        let drained = timeout(SHUTDOWN_TIMEOUT, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+
+        if drained.is_err() {
+            tracing::warn!(
+                "{} connection(s) did not close within {:?}, dropping",
+                connections.len(),
+                SHUTDOWN_TIMEOUT
+            );
+            connections.shutdown().await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle a new client connection
+// The handshake callback's error type is dictated by tungstenite
+#[allow(clippy::result_large_err)]
+async fn handle_connection<H: Handler, S: AsyncRead + AsyncWrite + Unpin>(
+    handler: Arc<H>,
+    broadcaster: Broadcaster,
+    settings: ConnectionSettings,
+    stream: S,
+    peer_addr: SocketAddr,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    tracing::info!("New connection from {}", peer_addr);
+
+    let mut state = None;
+    let mut encoding = Encoding::Json;
+    let ws_stream = accept_hdr_async(
+        stream,
+        |request: &Request, mut response: Response| match handler.authorize(request) {
+            Ok(s) => {
+                state = Some(s);
+                if settings.msgpack && offers_protocol(request, MSGPACK_PROTOCOL) {
+                    encoding = Encoding::MessagePack;
+                    response
+                        .headers_mut()
+                        .insert("sec-websocket-protocol", MSGPACK_PROTOCOL.parse().expect("valid header"));
+                }
+                Ok(response)
+            }
+            Err(reason) => {
+                tracing::warn!("Rejected connection from {}: {}", peer_addr, reason);
+                let mut error = ErrorResponse::new(Some(reason));
+                *error.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(error)
+            }
+        },
+    )
+    .await?;
+
+    let Some(state) = state else {
+        return Ok(());
+    };
+
+    let (mut write, mut read) = ws_stream.split();
+
+    // Register client
+    let (client_id, mut rx) = broadcaster.register(handler.default_topics()).await;
+    tracing::info!("Client {} registered from {} ({:?})", client_id, peer_addr, encoding);
+
+    let mut client = Client {
+        id: client_id,
+        addr: peer_addr,
+        state,
+        broadcaster: broadcaster.clone(),
+    };
+
+    let ConnectionSettings { rate_limiter, keep_alive, .. } = settings;
+    let mut ping = interval(Duration::from_secs(keep_alive.ping_interval_secs));
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ping.tick().await;
+    let pong_timeout = Duration::from_secs(keep_alive.timeout_secs);
+    // Set by a ping, cleared by anything the client sends
+    let mut pong_deadline: Option<Instant> = None;
+
+    let result = async {
+        for message in handler.on_connect(&mut client).await {
+            let json = broadcaster.encode(None, &message)?;
+            write.send(encoding.frame(&json)?).await?;
+        }
+
+        loop {
+            tokio::select! {
+                // Handle incoming messages from client
+                msg = read.next() => {
+                    if let Some(Ok(_)) = msg {
+                        pong_deadline = None;
+                    }
+                    // MessagePack requests are handled as the equivalent JSON
+                    let msg = match msg {
+                        Some(Ok(Message::Binary(data))) if encoding == Encoding::MessagePack => {
+                            match rmp_serde::from_slice::<Value>(&data) {
+                                Ok(request) => Some(Ok(Message::Text(request.to_string()))),
+                                Err(e) => {
+                                    tracing::warn!("Invalid MessagePack from client {}: {}", client_id, e);
+                                    continue;
+                                }
+                            }
+                        }
+                        msg => msg,
+                    };
+                    match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            let request: Option<Value> = serde_json::from_str(&text).ok();
+                            let id = request.as_ref().and_then(request_id);
+                            let limited = rate_limiter
+                                .as_ref()
+                                .and_then(|limiter| limiter.check(peer_addr.ip()).err());
+                            let response_json = match limited {
+                                Some(limited) => {
+                                    tracing::debug!("Rate limited client {}: {}", client_id, limited);
+                                    let error = RateLimitError {
+                                        message: handler.rate_limited_message(&limited),
+                                        retry_after_ms: limited.retry_after_ms(),
+                                    };
+                                    broadcaster.encode(id, &error)?
+                                }
+                                None => {
+                                    let command = request
+                                        .as_ref()
+                                        .and_then(|request| request.get("type")?.as_str())
+                                        .unwrap_or("unknown");
+                                    let span = shq_logging::command_span(command);
+                                    span.record("client", client_id);
+                                    let response = handler
+                                        .on_message(&mut client, &text)
+                                        .instrument(span)
+                                        .await;
+                                    broadcaster.encode(id, &response)?
+                                }
+                            };
+                            write.send(encoding.frame(&response_json)?).await?;
+                        }
+                        Some(Ok(Message::Ping(data))) => {
+                            write.send(Message::Pong(data)).await?;
+                        }
+                        Some(Ok(Message::Close(_))) | None => {
+                            tracing::info!("Client {} disconnected", client_id);
+                            break;
+                        }
+                        Some(Err(e)) => {
+                            tracing::error!("WebSocket error from client {}: {}", client_id, e);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                // Handle broadcast messages to this client
+                Ok(broadcast_msg) = rx.recv() => {
+                    if let Err(e) = write.send(encoding.frame(&broadcast_msg)?).await {
+                        tracing::error!("Failed to send broadcast to client {}: {}", client_id, e);
+                        break;
+                    }
+                }
+                // Server is shutting down; any in-flight request has already been answered
+                Ok(()) = shutdown.changed() => {
+                    if let Some(message) = handler.shutdown_message() {
+                        let json = broadcaster.encode(None, &message)?;
+                        write.send(encoding.frame(&json)?).await?;
+                    }
+                    write.send(Message::Close(None)).await?;
+                    tracing::info!("Closed client {} for shutdown", client_id);
+                    break;
+                }
+                // Keep idle connections (and NAT mappings) alive
+                _ = ping.tick() => {
+                    write.send(Message::Ping(Vec::new())).await?;
+                    pong_deadline.get_or_insert(Instant::now() + pong_timeout);
+                }
+                // Nothing back since the ping: a crashed client or a half-open connection
+                _ = sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                    tracing::warn!("Client {} didn't answer a ping within {:?}, dropping", client_id, pong_timeout);
+                    break;
+                }
+            }
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    // Unregister client
+    handler.on_disconnect(&mut client).await;
+    broadcaster.unregister(client_id).await;
+    tracing::info!("Client {} unregistered", client_id);
+
+    result
+}
+
+/// Token a client presented when connecting: an `Authorization: Bearer <token>` header, or a
+/// `token` query parameter for browsers, which can't set headers on WebSocket requests
+pub fn request_token(request: &Request) -> Option<String> {
+    let header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = header {
+        return Some(token.trim().to_string());
+    }
+
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(percent_decode)
+}
+
+/// Whether the client listed `protocol` in its `Sec-WebSocket-Protocol` header
+fn offers_protocol(request: &Request, protocol: &str) -> bool {
+    request
+        .headers()
+        .get_all("sec-websocket-protocol")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|offered| offered.trim() == protocol)
+}
+
+/// Decode `%XX` escapes (and `+` as space) in a query value
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The `id` of a client request, if it has one; strings and numbers are echoed unchanged
+fn request_id(request: &Value) -> Option<Value> {
+    request
+        .get("id")
+        .filter(|id| id.is_string() || id.is_number())
+        .cloned()
+}
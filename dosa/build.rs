@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Client for overwatch's Verbalise, compiled from overwatch's own proto
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_server(false)
+        .compile(&["../overwatch/proto/voice.proto"], &["../overwatch/proto"])?;
+    Ok(())
+}
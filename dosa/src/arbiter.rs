@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::messages::STOP_PRIORITY;
+
+/// How long a claim holds after its command was accepted, even if the door hasn't started
+/// moving yet (commands run in the background)
+const CLAIM_GRACE: Duration = Duration::from_secs(1);
+
+/// Which client's motion command a door is carrying out
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Claim {
+    /// Client label (`<token name>@<addr>`, or the address without auth)
+    pub client: String,
+    pub command: &'static str,
+    pub priority: u8,
+    /// When the command was accepted, in ms since the epoch
+    pub since_ms: u64,
+    #[serde(skip)]
+    accepted: Instant,
+}
+
+/// Decides between motion commands from different clients, per door
+///
+/// A door belongs to the client whose command it is carrying out until it stops moving.
+/// Meanwhile another client's command needs a higher priority (`stop` always has one); the
+/// same client can always change its mind, e.g. reverse its own close.
+#[derive(Default)]
+pub struct Arbiter {
+    claims: Mutex<HashMap<String, Claim>>,
+}
+
+impl Arbiter {
+    /// Claim `door` for `client`'s command, or return the claim that holds it
+    ///
+    /// `moving` is whether the door is in a moving state right now.
+    pub fn claim(&self, door: &str, moving: bool, client: &str, command: &'static str, priority: u8) -> Result<Claim, Claim> {
+        let mut claims = self.claims.lock().unwrap();
+        if let Some(held) = claims.get(door) {
+            let live = moving || held.accepted.elapsed() < CLAIM_GRACE;
+            if live && held.client != client && priority <= held.priority && priority != STOP_PRIORITY {
+                return Err(held.clone());
+            }
+        }
+
+        let since_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let claim = Claim {
+            client: client.to_string(),
+            command,
+            priority,
+            since_ms,
+            accepted: Instant::now(),
+        };
+        claims.insert(door.to_string(), claim.clone());
+        Ok(claim)
+    }
+}
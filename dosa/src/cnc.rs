@@ -1,41 +1,328 @@
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
 use tokio_serial::SerialPortBuilderExt;
 
 use crate::config::CncConnection;
 
+/// Read timeout until the door config sets one (`door.timeouts.read_ms`)
+const DEFAULT_READ_TIMEOUT_MS: u64 = 1000;
+
+/// Longest wait for a TCP controller to accept the connection, so an unreachable one fails
+/// over rather than hanging
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Latest status report from the controller and when it arrived
+type Report = Option<(Instant, String)>;
+
+/// Lines received for a command, ending with its `ok`/`error:` reply
+type Reply = Result<Vec<String>>;
+
+/// Longest wait for the reply to a `raw_command()`, long enough for a `$$` dump over serial
+const RAW_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which way a console line went
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleDirection {
+    Sent,
+    Received,
+}
+
+/// A command line sent to the controller or a line it sent back, for `subscribe_console()`
+///
+/// Status reports and real-time bytes are left out, so a console shows the conversation
+/// rather than the position monitor's polling.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsoleLine {
+    pub direction: ConsoleDirection,
+    pub line: String,
+}
+
+/// Controller output that isn't a reply to a command
+#[derive(Debug, Clone)]
+pub enum CncEvent {
+    /// `ALARM:<code>`, e.g. a limit switch hit while idle
+    Alarm(String),
+}
+
 /// CNC controller client for grblHAL
 pub struct CncController {
     connection: Arc<Mutex<CncConnectionType>>,
+    /// Status reports routed here by the reader task, whether pushed or asked for with `?`
+    reports: watch::Receiver<Report>,
+    /// Kept to hand out receivers with `subscribe_events()`
+    events: broadcast::Receiver<CncEvent>,
+    /// Kept to hand out receivers with `subscribe_console()`
+    console: broadcast::Receiver<ConsoleLine>,
+    /// grblHAL's automatic report interval in ms ($481); 0 while we poll with `?`
+    report_interval_ms: AtomicU64,
+    /// How long to wait for a command reply or status report (`door.timeouts.read_ms`)
+    read_timeout_ms: AtomicU64,
+    /// The connection this controller was opened on (absent for the fault-state dummy)
+    endpoint: Option<CncConnection>,
 }
 
 enum CncConnectionType {
-    Tcp(BufReader<TcpStream>),
-    Serial(BufReader<tokio_serial::SerialStream>),
+    Connected(CncLink),
     Dummy, // For fault state when CNC is not connected
 }
 
+/// An open connection, driven by a writer task (fed through `requests`) and a reader task
+/// that routes each line: status reports, command replies or events
+struct CncLink {
+    requests: mpsc::UnboundedSender<Outgoing>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+/// Where a link's reader task sends what the controller says
+struct LinkOutput {
+    reports: watch::Receiver<Report>,
+    events: broadcast::Receiver<CncEvent>,
+    console: broadcast::Receiver<ConsoleLine>,
+}
+
+/// Something for the writer task to send
+enum Outgoing {
+    /// Command lines; `done` gets their replies once `count` of them are answered
+    Lines {
+        data: String,
+        count: usize,
+        error: &'static str,
+        done: oneshot::Sender<Reply>,
+    },
+    /// A real-time byte (`?`, `!`, Ctrl-X...), which grblHAL doesn't answer with `ok`
+    Realtime {
+        byte: u8,
+        done: oneshot::Sender<Result<()>>,
+    },
+}
+
+/// Command lines written and waiting for their replies, oldest first
+///
+/// grblHAL answers lines in order, so each `ok`/`error:` belongs to the front entry.
+#[derive(Default)]
+struct PendingCommands {
+    commands: VecDeque<PendingCommand>,
+    /// The reader task has stopped; nothing will be answered any more
+    closed: bool,
+}
+
+struct PendingCommand {
+    lines: Vec<String>,
+    remaining: usize,
+    done: oneshot::Sender<Reply>,
+}
+
+type Pending = Arc<std::sync::Mutex<PendingCommands>>;
+
+impl CncLink {
+    /// Split `stream` and start its reader and writer tasks
+    fn open<S>(stream: S) -> (Self, LinkOutput)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read_half, write_half) = tokio::io::split(stream);
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        let (reports_tx, reports) = watch::channel(None);
+        let (events_tx, events) = broadcast::channel(32);
+        let (console_tx, console) = broadcast::channel(64);
+        let pending = Pending::default();
+
+        let reader = tokio::spawn(read_lines(read_half, pending.clone(), reports_tx, events_tx, console_tx.clone()));
+        let writer = tokio::spawn(write_requests(write_half, requests_rx, pending, console_tx));
+
+        let link = Self {
+            requests,
+            reader,
+            writer,
+        };
+        (link, LinkOutput { reports, events, console })
+    }
+}
+
+impl Drop for CncLink {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.writer.abort();
+    }
+}
+
+/// Writer task: send requests in the order they were made, registering command lines as
+/// pending just before they go out so replies can be matched to them
+async fn write_requests(
+    mut writer: impl AsyncWrite + Unpin,
+    mut requests: mpsc::UnboundedReceiver<Outgoing>,
+    pending: Pending,
+    console: broadcast::Sender<ConsoleLine>,
+) {
+    while let Some(request) = requests.recv().await {
+        match request {
+            Outgoing::Lines { data, count, error, done } => {
+                {
+                    let mut queue = pending.lock().unwrap();
+                    if queue.closed {
+                        let _ = done.send(Err(anyhow::anyhow!("CNC connection closed")));
+                        continue;
+                    }
+                    queue.commands.push_back(PendingCommand {
+                        lines: Vec::new(),
+                        remaining: count,
+                        done,
+                    });
+                }
+
+                if let Err(e) = write_bytes(&mut writer, data.as_bytes()).await {
+                    // Only the writer adds entries, so ours is still the last one
+                    if let Some(command) = pending.lock().unwrap().commands.pop_back() {
+                        let _ = command.done.send(Err(e.context(error)));
+                    }
+                    continue;
+                }
+                for line in data.lines().filter(|line| !line.is_empty()) {
+                    let _ = console.send(ConsoleLine {
+                        direction: ConsoleDirection::Sent,
+                        line: line.to_string(),
+                    });
+                }
+            }
+            Outgoing::Realtime { byte, done } => {
+                let result = write_bytes(&mut writer, &[byte]).await;
+                let _ = done.send(result.context("Failed to send realtime command to CNC"));
+            }
+        }
+    }
+}
+
+async fn write_bytes(writer: &mut (impl AsyncWrite + Unpin), bytes: &[u8]) -> Result<()> {
+    writer.write_all(bytes).await?;
+    writer.flush().await.context("Failed to flush command to CNC")
+}
+
+/// Reader task: route every line the controller sends until the connection closes, then
+/// fail whatever is still waiting for a reply
+async fn read_lines(
+    stream: impl AsyncRead + Unpin,
+    pending: Pending,
+    reports: watch::Sender<Report>,
+    events: broadcast::Sender<CncEvent>,
+    console: broadcast::Sender<ConsoleLine>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("Failed to read from CNC: {}", e);
+                break;
+            }
+        }
+
+        let line = line.trim();
+        if !line.is_empty() {
+            route_line(line, &pending, &reports, &events);
+            if !is_status_report(line) {
+                let _ = console.send(ConsoleLine {
+                    direction: ConsoleDirection::Received,
+                    line: line.to_string(),
+                });
+            }
+        }
+    }
+
+    // Dropping the senders tells everyone waiting that the connection closed
+    let mut queue = pending.lock().unwrap();
+    queue.closed = true;
+    queue.commands.clear();
+    tracing::debug!("CNC reader stopped");
+}
+
+/// Send one line where it belongs: status reports to `reports`, alarms to `events`,
+/// messages and boot banners to the log, anything else (settings lines, `ok`, `error:`) to
+/// the oldest pending command
+fn route_line(line: &str, pending: &Pending, reports: &watch::Sender<Report>, events: &broadcast::Sender<CncEvent>) {
+    if is_status_report(line) {
+        tracing::trace!("CNC status report: {}", line);
+        reports.send_replace(Some((Instant::now(), line.to_string())));
+    } else if let Some(alarm_code) = line.strip_prefix("ALARM:") {
+        match CncController::alarm_description(alarm_code) {
+            Some(description) => tracing::error!("CNC ALARM triggered: Code {} ({})", alarm_code, description),
+            None => tracing::error!("CNC ALARM triggered: Code {}", alarm_code),
+        }
+        let _ = events.send(CncEvent::Alarm(alarm_code.to_string()));
+    } else if let Some(msg) = line.strip_prefix("[MSG:").and_then(|msg| msg.strip_suffix(']')) {
+        tracing::info!("CNC message: {}", msg);
+    } else if line.starts_with("GrblHAL") || line.starts_with("Grbl") {
+        // A reset discards the commands grblHAL hadn't answered yet
+        tracing::debug!("CNC boot message: {}", line);
+        let mut queue = pending.lock().unwrap();
+        for command in queue.commands.drain(..) {
+            let _ = command.done.send(Err(anyhow::anyhow!("CNC was reset before replying")));
+        }
+    } else {
+        let mut queue = pending.lock().unwrap();
+        let Some(command) = queue.commands.front_mut() else {
+            tracing::warn!("Unexpected CNC response line: {}", line);
+            return;
+        };
+        tracing::debug!("CNC response line: {}", line);
+        command.lines.push(line.to_string());
+        if line == "ok" || line.starts_with("error:") {
+            command.remaining = command.remaining.saturating_sub(1);
+            if command.remaining == 0 {
+                if let Some(command) = queue.commands.pop_front() {
+                    // The caller may have timed out and gone; the reply is still consumed
+                    let _ = command.done.send(Ok(command.lines));
+                }
+            }
+        }
+    }
+}
+
+fn is_status_report(line: &str) -> bool {
+    line.starts_with('<') && line.ends_with('>')
+}
+
 impl CncController {
     /// Create a dummy CNC controller for fault state
     pub fn dummy() -> Self {
+        let (_, reports) = watch::channel(None);
+        let (_, events) = broadcast::channel(1);
+        let (_, console) = broadcast::channel(1);
         Self {
             connection: Arc::new(Mutex::new(CncConnectionType::Dummy)),
+            reports,
+            events,
+            console,
+            report_interval_ms: AtomicU64::new(0),
+            read_timeout_ms: AtomicU64::new(DEFAULT_READ_TIMEOUT_MS),
+            endpoint: None,
         }
     }
 
     /// Create a new CNC controller connection
     pub async fn new(config: &CncConnection) -> Result<Self> {
-        let connection = match config {
+        let (link, LinkOutput { reports, events, console }) = match config {
             CncConnection::Tcp { host, port } => {
                 tracing::info!("Connecting to CNC controller at {}:{}", host, port);
-                let stream = TcpStream::connect(format!("{}:{}", host, port))
-                    .await
-                    .context("Failed to connect to CNC controller via TCP")?;
-                let reader = BufReader::new(stream);
-                CncConnectionType::Tcp(reader)
+                let connect = TcpStream::connect(format!("{}:{}", host, port));
+                let stream = match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+                    Ok(connected) => connected.context("Failed to connect to CNC controller via TCP")?,
+                    Err(_) => anyhow::bail!("Timed out connecting to CNC controller via TCP"),
+                };
+                CncLink::open(stream)
             }
             CncConnection::Serial { port, baud_rate } => {
                 tracing::info!(
@@ -46,183 +333,158 @@ impl CncController {
                 let serial = tokio_serial::new(port, *baud_rate)
                     .open_native_async()
                     .context("Failed to open serial port")?;
-                let reader = BufReader::new(serial);
-                CncConnectionType::Serial(reader)
+                CncLink::open(serial)
             }
         };
 
         let controller = Self {
-            connection: Arc::new(Mutex::new(connection)),
+            connection: Arc::new(Mutex::new(CncConnectionType::Connected(link))),
+            reports,
+            events,
+            console,
+            report_interval_ms: AtomicU64::new(0),
+            read_timeout_ms: AtomicU64::new(DEFAULT_READ_TIMEOUT_MS),
+            endpoint: Some(config.clone()),
         };
 
         // Small delay to let connection stabilize
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
 
         Ok(controller)
     }
 
-    /// Query all grblHAL settings ($$)
-    /// Returns a map of setting names to values (e.g., "$120" -> "1000.000")
-    /// Settings are sorted numerically by the number after the $ sign
-    pub async fn query_settings(&self) -> Result<indexmap::IndexMap<String, String>> {
-        let mut conn = self.connection.lock().await;
+    /// Connect to the first of `endpoints` that can be opened, starting at index `first` and
+    /// wrapping around, so a failed connection fails over to the next one
+    pub async fn connect(endpoints: &[CncConnection], first: usize) -> Result<Self> {
+        let mut failures = Vec::new();
+        for offset in 0..endpoints.len() {
+            let endpoint = &endpoints[(first + offset) % endpoints.len()];
+            match Self::new(endpoint).await {
+                Ok(cnc) => return Ok(cnc),
+                Err(e) if endpoints.len() == 1 => return Err(e),
+                Err(e) => {
+                    tracing::warn!("CNC connection {} failed: {:#}", endpoint, e);
+                    failures.push(format!("{}: {:#}", endpoint, e));
+                }
+            }
+        }
+        anyhow::bail!("No CNC connection could be opened ({})", failures.join("; "))
+    }
 
-        let cmd = "$$\n";
-        tracing::debug!("Sending CNC command: $$");
+    /// The connection in use, `None` in fault state
+    pub fn endpoint(&self) -> Option<&CncConnection> {
+        self.endpoint.as_ref()
+    }
 
-        match &mut *conn {
-            CncConnectionType::Tcp(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(cmd.as_bytes())
-                    .await
-                    .context("Failed to send settings query command to CNC")?;
-
-                stream
-                    .flush()
-                    .await
-                    .context("Failed to flush command to CNC")?;
-
-                // Read all lines until we get "ok" with timeout
-                // Use Vec to collect, then sort numerically
-                let mut settings_vec = Vec::new();
-                let mut lines_read = 0;
-                const MAX_LINES: usize = 200; // Safety limit
-                const READ_TIMEOUT_MS: u64 = 2000; // 2 second timeout per line
-
-                loop {
-                    let mut line = String::new();
-                    let read_result = tokio::time::timeout(
-                        tokio::time::Duration::from_millis(READ_TIMEOUT_MS),
-                        reader.read_line(&mut line)
-                    ).await;
-
-                    match read_result {
-                        Ok(Ok(0)) => {
-                            anyhow::bail!("Connection closed while reading settings (read {} lines)", lines_read);
-                        }
-                        Ok(Ok(_)) => {
-                            let trimmed = line.trim();
-                            tracing::trace!("Settings line {}: {}", lines_read, trimmed);
-
-                            if trimmed == "ok" {
-                                tracing::debug!("Received 'ok', settings complete ({} lines)", lines_read);
-                                break;
-                            }
-
-                            // Parse setting line: $120=1000.000
-                            if let Some(eq_pos) = trimmed.find('=') {
-                                let setting_name = trimmed[..eq_pos].to_string();
-                                let setting_value = trimmed[eq_pos + 1..].to_string();
-                                settings_vec.push((setting_name, setting_value));
-                                lines_read += 1;
-                            }
-
-                            if lines_read >= MAX_LINES {
-                                anyhow::bail!("Too many lines reading settings (safety limit)");
-                            }
-                        }
-                        Ok(Err(e)) => {
-                            return Err(e).context(format!("Failed to read settings from CNC (after {} lines)", lines_read));
-                        }
-                        Err(_) => {
-                            anyhow::bail!("Timeout reading settings from CNC (after {} lines)", lines_read);
-                        }
-                    }
-                }
+    /// Events the controller reports on its own, in order
+    /// The stream ends when the connection closes.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<CncEvent> {
+        self.events.resubscribe()
+    }
 
-                // Sort numerically by extracting the number from "$XXX"
-                settings_vec.sort_by(|a, b| {
-                    let num_a = a.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
-                    let num_b = b.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
-                    num_a.cmp(&num_b)
-                });
+    /// Command lines sent and lines received from now on, like a serial terminal would show
+    /// The stream ends when the connection closes.
+    pub fn subscribe_console(&self) -> broadcast::Receiver<ConsoleLine> {
+        self.console.resubscribe()
+    }
 
-                // Convert to IndexMap to preserve insertion order
-                let settings: indexmap::IndexMap<String, String> = settings_vec.into_iter().collect();
+    /// Have grblHAL push a status report every `interval_ms` ($481, 100-1000)
+    ///
+    /// `latest_status()` then returns the latest pushed report instead of sending `?`,
+    /// falling back to a query if the reports stop coming.
+    pub async fn enable_status_reports(&self, interval_ms: u64) -> Result<()> {
+        self.set_setting("$481", &interval_ms.to_string()).await?;
+        self.report_interval_ms.store(interval_ms, Ordering::Relaxed);
+        Ok(())
+    }
 
-                tracing::debug!("CNC settings response: {} settings", settings.len());
-                Ok(settings)
-            }
-            CncConnectionType::Serial(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(cmd.as_bytes())
-                    .await
-                    .context("Failed to send settings query command to CNC")?;
-
-                stream
-                    .flush()
-                    .await
-                    .context("Failed to flush command to CNC")?;
-
-                // Read all lines until we get "ok" with timeout
-                // Use Vec to collect, then sort numerically
-                let mut settings_vec = Vec::new();
-                let mut lines_read = 0;
-                const MAX_LINES: usize = 200;
-                const READ_TIMEOUT_MS: u64 = 2000;
-
-                loop {
-                    let mut line = String::new();
-                    let read_result = tokio::time::timeout(
-                        tokio::time::Duration::from_millis(READ_TIMEOUT_MS),
-                        reader.read_line(&mut line)
-                    ).await;
-
-                    match read_result {
-                        Ok(Ok(0)) => {
-                            anyhow::bail!("Connection closed while reading settings (read {} lines)", lines_read);
-                        }
-                        Ok(Ok(_)) => {
-                            let trimmed = line.trim();
-                            tracing::trace!("Settings line {}: {}", lines_read, trimmed);
-
-                            if trimmed == "ok" {
-                                tracing::debug!("Received 'ok', settings complete ({} lines)", lines_read);
-                                break;
-                            }
-
-                            // Parse setting line: $120=1000.000
-                            if let Some(eq_pos) = trimmed.find('=') {
-                                let setting_name = trimmed[..eq_pos].to_string();
-                                let setting_value = trimmed[eq_pos + 1..].to_string();
-                                settings_vec.push((setting_name, setting_value));
-                                lines_read += 1;
-                            }
-
-                            if lines_read >= MAX_LINES {
-                                anyhow::bail!("Too many lines reading settings (safety limit)");
-                            }
-                        }
-                        Ok(Err(e)) => {
-                            return Err(e).context(format!("Failed to read settings from CNC (after {} lines)", lines_read));
-                        }
-                        Err(_) => {
-                            anyhow::bail!("Timeout reading settings from CNC (after {} lines)", lines_read);
-                        }
-                    }
-                }
+    /// Wait up to `timeout_ms` for command replies and status reports from now on
+    pub fn set_read_timeout(&self, timeout_ms: u64) {
+        self.read_timeout_ms.store(timeout_ms, Ordering::Relaxed);
+    }
 
-                // Sort numerically by extracting the number from "$XXX"
-                settings_vec.sort_by(|a, b| {
-                    let num_a = a.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
-                    let num_b = b.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
-                    num_a.cmp(&num_b)
-                });
+    fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms.load(Ordering::Relaxed))
+    }
 
-                // Convert to IndexMap to preserve insertion order
-                let settings: indexmap::IndexMap<String, String> = settings_vec.into_iter().collect();
+    /// Whether grblHAL pushes status reports on this connection
+    pub fn pushes_reports(&self) -> bool {
+        self.report_interval_ms.load(Ordering::Relaxed) > 0
+    }
 
-                tracing::debug!("CNC settings response: {} settings", settings.len());
-                Ok(settings)
-            }
-            CncConnectionType::Dummy => {
-                anyhow::bail!("System is in fault state - CNC not connected")
-            }
+    /// Wait until the next status report arrives, for at most `timeout`
+    pub async fn wait_for_report(&self, timeout: Duration) {
+        let mut reports = self.reports.clone();
+        reports.borrow_and_update();
+        let _ = tokio::time::timeout(timeout, reports.changed()).await;
+    }
+
+    /// Hand `request` to the writer task
+    async fn submit(&self, request: Outgoing) -> Result<()> {
+        let conn = self.connection.lock().await;
+        match &*conn {
+            CncConnectionType::Connected(link) => link
+                .requests
+                .send(request)
+                .map_err(|_| anyhow::anyhow!("CNC connection closed")),
+            CncConnectionType::Dummy => anyhow::bail!("System is in fault state - CNC not connected"),
+        }
+    }
+
+    /// Send command lines (newline-terminated) and return a receiver for their replies
+    async fn submit_lines(&self, data: String, count: usize, error: &'static str) -> Result<oneshot::Receiver<Reply>> {
+        let (done, reply) = oneshot::channel();
+        self.submit(Outgoing::Lines { data, count, error, done }).await?;
+        Ok(reply)
+    }
+
+    /// Wait up to `timeout` for the replies to submitted lines
+    async fn wait_for_reply(reply: oneshot::Receiver<Reply>, timeout: Duration) -> Reply {
+        match tokio::time::timeout(timeout, reply).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => anyhow::bail!("CNC connection closed"),
+            Err(_) => anyhow::bail!("Timeout reading from CNC"),
         }
     }
 
+    /// Query all grblHAL settings ($$)
+    /// Returns a map of setting names to values (e.g., "$120" -> "1000.000")
+    /// Settings are sorted numerically by the number after the $ sign
+    pub async fn query_settings(&self) -> Result<indexmap::IndexMap<String, String>> {
+        const READ_TIMEOUT_MS: u64 = 5000;
+
+        tracing::debug!("Sending CNC command: $$");
+        let reply = self
+            .submit_lines("$$\n".to_string(), 1, "Failed to send settings query command to CNC")
+            .await?;
+        let lines = Self::wait_for_reply(reply, Duration::from_millis(READ_TIMEOUT_MS))
+            .await
+            .context("Failed to read settings from CNC")?;
+
+        // Collect setting lines ($120=1000.000), then sort numerically
+        let mut settings_vec: Vec<(String, String)> = lines
+            .iter()
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        if let Some(error) = lines.iter().find(|line| line.starts_with("error:")) {
+            anyhow::bail!("CNC error: {}", Self::describe_error(error));
+        }
+
+        // Sort numerically by extracting the number from "$XXX"
+        settings_vec.sort_by(|a, b| {
+            let num_a = a.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
+            let num_b = b.0.trim_start_matches('$').parse::<u32>().unwrap_or(0);
+            num_a.cmp(&num_b)
+        });
+
+        // Convert to IndexMap to preserve insertion order
+        let settings: indexmap::IndexMap<String, String> = settings_vec.into_iter().collect();
+
+        tracing::debug!("CNC settings response: {} settings", settings.len());
+        Ok(settings)
+    }
+
     /// Get a specific CNC setting by name (e.g., "$120")
     /// Returns the value as a string
     pub async fn get_setting(&self, setting_name: &str) -> Result<String> {
@@ -248,350 +510,232 @@ impl CncController {
         Ok(())
     }
 
-    /// Helper to read all available lines from the CNC controller
-    /// Reads lines until timeout (default 50ms), logging and discarding [MSG:...] lines
-    async fn read_all_response_lines(
-        reader: &mut tokio::io::BufReader<impl tokio::io::AsyncRead + Unpin>,
-        timeout_ms: Option<u64>,
-    ) -> Result<Vec<String>> {
-        let mut lines = Vec::new();
-        let mut response = String::new();
-        let timeout = timeout_ms.unwrap_or(50);
-
-        // Read first line (should always be present)
-        let read_result = tokio::time::timeout(
-            tokio::time::Duration::from_millis(timeout),
-            reader.read_line(&mut response)
-        ).await;
-
-        match read_result {
-            Ok(Ok(_)) => {
-                let line = response.trim().to_string();
-                if !line.is_empty() {
-                    lines.push(line);
-                }
-            }
-            Ok(Err(e)) => return Err(e).context("Failed to read from CNC"),
-            Err(_) => return Err(anyhow::anyhow!("Timeout reading from CNC")),
-        }
-
-        // Continue reading additional lines with same timeout
-        // This consumes any trailing messages like [MSG:...] or status responses
-        loop {
-            response.clear();
-            let read_result = tokio::time::timeout(
-                tokio::time::Duration::from_millis(timeout),
-                reader.read_line(&mut response)
-            ).await;
-
-            match read_result {
-                Ok(Ok(0)) => break, // EOF
-                Ok(Ok(_)) => {
-                    let line = response.trim().to_string();
-                    if !line.is_empty() {
-                        lines.push(line);
-                    }
-                }
-                Ok(Err(_)) => break, // Read error, stop
-                Err(_) => break,     // Timeout, no more data available
-            }
-        }
-
-        Ok(lines)
-    }
-
     /// Send a command to the CNC controller and wait for response
     ///
     /// # Arguments
     /// * `command` - The command to send
-    /// * `expect_status_response` - If true, will read a second line if first response is "ok"
-    /// * `timeout_ms` - Timeout in milliseconds for reading response (default: 1000ms)
-    pub async fn send_command_with_options(
-        &self,
-        command: &str,
-        expect_status_response: bool,
-        timeout_ms: u64,
-    ) -> Result<String> {
-        let mut conn = self.connection.lock().await;
-
-        let cmd = format!("{}\n", command.trim());
+    /// * `timeout_ms` - Timeout in milliseconds for the reply (default: the read timeout)
+    pub async fn send_command_with_options(&self, command: &str, timeout_ms: u64) -> Result<String> {
         tracing::debug!("Sending CNC command: {}", command);
+        let cmd = format!("{}\n", command.trim());
+        let reply = self.submit_lines(cmd, 1, "Failed to send command to CNC").await?;
+        let lines = Self::wait_for_reply(reply, Duration::from_millis(timeout_ms)).await?;
 
-        match &mut *conn {
-            CncConnectionType::Tcp(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(cmd.as_bytes())
-                    .await
-                    .context("Failed to send command to CNC")?;
-
-                // Read all response lines (uses timeout_ms for first line, then defaults to 50ms)
-                let lines = Self::read_all_response_lines(reader, Some(timeout_ms)).await?;
+        // Process the lines
+        self.process_response_lines(lines)
+    }
 
-                // Process the lines
-                self.process_response_lines(lines, expect_status_response)
-            }
-            CncConnectionType::Serial(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(cmd.as_bytes())
-                    .await
-                    .context("Failed to send command to CNC")?;
-
-                // Read all response lines (uses timeout_ms for first line, then defaults to 50ms)
-                let lines = Self::read_all_response_lines(reader, Some(timeout_ms)).await?;
-
-                // Process the lines
-                self.process_response_lines(lines, expect_status_response)
-            }
-            CncConnectionType::Dummy => {
-                anyhow::bail!("System is in fault state - CNC not connected")
-            }
-        }
+    /// Send `command` as typed and return every line of its reply, `error:` included
+    ///
+    /// For diagnostics: nothing checks what the command does, so a move or setting change
+    /// sent this way bypasses the door's own bookkeeping.
+    pub async fn raw_command(&self, command: &str) -> Result<Vec<String>> {
+        tracing::debug!("Sending raw CNC command: {}", command);
+        let cmd = format!("{}\n", command.trim());
+        let reply = self.submit_lines(cmd, 1, "Failed to send command to CNC").await?;
+        Self::wait_for_reply(reply, RAW_COMMAND_TIMEOUT).await
     }
 
-    /// Process response lines from CNC, filtering MSG lines and extracting the appropriate response
-    fn process_response_lines(&self, lines: Vec<String>, expect_status_response: bool) -> Result<String> {
-        let mut first_response = None;
-        let mut status_response = None;
-
-        for line in lines {
-            tracing::debug!("CNC response line: {}", line);
-
-            if line.starts_with("[MSG:") && line.ends_with("]") {
-                // Log informational messages and discard
-                let msg = &line[5..line.len()-1]; // Extract message content
-                tracing::info!("CNC message: {}", msg);
-            } else if line.starts_with("GrblHAL") || line.starts_with("Grbl") {
-                // Boot/reset message from controller - log and discard
-                tracing::debug!("CNC boot message: {}", line);
-            } else if line.starts_with("ALARM:") {
-                // Alarm notification from controller (can occur asynchronously)
-                let alarm_code = line.strip_prefix("ALARM:").unwrap_or("unknown");
-                tracing::error!("CNC ALARM triggered: Code {}", alarm_code);
-            } else if line.starts_with("<") && line.ends_with(">") {
-                // Status response
-                status_response = Some(line);
-            } else if line == "ok" || line.starts_with("error:") {
-                // Command response
-                first_response = Some(line);
-            } else {
-                // Unexpected line
-                tracing::warn!("Unexpected CNC response line: {}", line);
-            }
-        }
+    /// Process the reply lines to a command, extracting the `ok`/`error:` response
+    fn process_response_lines(&self, lines: Vec<String>) -> Result<String> {
+        let response = lines
+            .into_iter()
+            .rfind(|line| line == "ok" || line.starts_with("error:"))
+            .context("No valid response from CNC")?;
 
-        // Return appropriate response
-        if expect_status_response {
-            if let Some(status) = status_response {
-                Ok(status)
-            } else {
-                anyhow::bail!("Expected status response but none found")
-            }
-        } else if let Some(response) = first_response {
-            if response.starts_with("error:") {
-                anyhow::bail!("CNC error: {}", response)
-            } else {
-                Ok(response)
-            }
-        } else {
-            anyhow::bail!("No valid response from CNC")
+        if response.starts_with("error:") {
+            anyhow::bail!("CNC error: {}", Self::describe_error(&response))
         }
+        Ok(response)
     }
 
     /// Send a command to the CNC controller and wait for response (convenience wrapper)
     pub async fn send_command(&self, command: &str) -> Result<String> {
-        self.send_command_with_options(command, false, 1000).await
+        self.send_command_with_options(command, self.read_timeout_ms.load(Ordering::Relaxed))
+            .await
     }
 
     /// Send a real-time command (single byte, no newline)
+    ///
+    /// Goes out straight away, even while earlier commands are waiting for replies.
     pub async fn send_realtime_command(&self, command: u8) -> Result<()> {
-        let mut conn = self.connection.lock().await;
-
         tracing::debug!("Sending CNC realtime command: 0x{:02X}", command);
 
-        match &mut *conn {
-            CncConnectionType::Tcp(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(&[command])
-                    .await
-                    .context("Failed to send realtime command to CNC")?;
-            }
-            CncConnectionType::Serial(reader) => {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(&[command])
-                    .await
-                    .context("Failed to send realtime command to CNC")?;
-            }
-            CncConnectionType::Dummy => {
-                anyhow::bail!("System is in fault state - CNC not connected")
-            }
-        }
-
-        Ok(())
+        let (done, written) = oneshot::channel();
+        self.submit(Outgoing::Realtime { byte: command, done }).await?;
+        written.await.map_err(|_| anyhow::anyhow!("CNC connection closed"))?
     }
 
     /// Home the specified axis
     ///
     /// Homing is special: grblHAL enters Home mode immediately, then completes the
     /// two-stage homing cycle (fast seek + slow approach), which can take 30+ seconds.
-    /// We handle the entire sequence here instead of returning immediately.
-    pub async fn home_axis(&self, axis: &str) -> Result<String> {
+    /// We handle the entire sequence here instead of returning immediately, for at most
+    /// `timeout` once it has started. A cycle that runs out of time (e.g. a broken limit
+    /// switch) is aborted with a soft reset, which leaves grblHAL in alarm 6.
+    pub async fn home_axis(&self, axis: &str, timeout: Duration) -> Result<String> {
         let command = format!("$H{}", axis);
 
         tracing::debug!("Sending CNC homing command: {}", &command);
 
-        let mut conn = self.connection.lock().await;
+        // Status reports during homing arrive separately from the "ok"
+        let mut reports = self.reports.clone();
+        reports.borrow_and_update();
+
         let cmd = format!("{}\n", command.trim());
+        let mut reply = self.submit_lines(cmd, 1, "Failed to send homing command to CNC").await?;
 
-        // Send the homing command
-        match &mut *conn {
-            CncConnectionType::Tcp(reader) => {
-                // Send homing command
-                {
-                    let stream = reader.get_mut();
-                    stream.write_all(cmd.as_bytes()).await
-                        .context("Failed to send homing command to CNC")?;
-                    stream.flush().await
-                        .context("Failed to flush homing command to CNC")?;
+        // Homing should report its start (the Home state) within 2 seconds, then we wait
+        // for "ok", an alarm or the timeout
+        let start_time = Instant::now();
+        let mut started = false;
+
+        loop {
+            let timeout_duration = if started { timeout } else { Duration::from_secs(2) };
+            let remaining_time = timeout_duration.saturating_sub(start_time.elapsed());
+            if remaining_time.is_zero() {
+                if let Err(e) = self.soft_reset().await {
+                    tracing::error!("Failed to abort the homing cycle: {}", e);
                 }
+                if started {
+                    return Err(anyhow::anyhow!("Homing timeout after {} seconds", timeout.as_secs()));
+                }
+                return Err(anyhow::anyhow!("Timeout waiting for homing to start"));
+            }
 
-                // Read immediate status response
-                let mut line = String::new();
-                tokio::time::timeout(
-                    tokio::time::Duration::from_secs(2),
-                    reader.read_line(&mut line)
-                ).await
-                    .context("Timeout waiting for homing to start")??;
-
-                tracing::debug!("Homing started: {}", line.trim());
-
-                // Wait for grblHAL to send status update when homing completes
-                // Keep reading lines until we see Idle state or timeout
-                let start_time = tokio::time::Instant::now();
-                let timeout_duration = tokio::time::Duration::from_secs(60);
-
-                loop {
-                    let remaining_time = timeout_duration.saturating_sub(start_time.elapsed());
-                    if remaining_time.is_zero() {
-                        return Err(anyhow::anyhow!("Homing timeout after 60 seconds"));
-                    }
+            tokio::select! {
+                result = tokio::time::timeout(remaining_time, &mut reply) => {
+                    let Ok(result) = result else { continue };
+                    let lines = result.map_err(|_| anyhow::anyhow!("CNC connection closed"))??;
+                    tracing::debug!("Homing response: {:?}", lines);
 
-                    line.clear();
-                    match tokio::time::timeout(remaining_time, reader.read_line(&mut line)).await {
-                        Ok(Ok(_)) => {
-                            let response = line.trim();
-                            tracing::debug!("Homing response: {}", response);
-
-                            // Check for completion
-                            if response == "ok" {
-                                tracing::info!("Homing completed after {:.1}s", start_time.elapsed().as_secs_f32());
-                                return Ok("ok".to_string());
-                            }
-
-                            // Check for alarm in status responses
-                            if let Ok(state) = Self::parse_state(response) {
-                                if state.starts_with("Alarm") {
-                                    return Err(anyhow::anyhow!("Homing failed: {}", state));
-                                }
-                            }
-
-                            // Ignore blank MSG lines and status updates, keep waiting
-                        }
-                        Ok(Err(e)) => {
-                            return Err(anyhow::anyhow!("Error reading during homing: {}", e));
-                        }
-                        Err(_) => {
-                            return Err(anyhow::anyhow!("Homing timeout - no response from controller"));
-                        }
+                    if let Some(error) = lines.iter().find(|line| line.starts_with("error:")) {
+                        return Err(anyhow::anyhow!("Homing failed: {}", Self::describe_error(error)));
                     }
+                    tracing::info!("Homing completed after {:.1}s", start_time.elapsed().as_secs_f32());
+                    return Ok("ok".to_string());
                 }
-            }
-            CncConnectionType::Serial(reader) => {
-                // Send homing command
-                {
-                    let stream = reader.get_mut();
-                    stream.write_all(cmd.as_bytes()).await
-                        .context("Failed to send homing command to CNC")?;
-                    stream.flush().await
-                        .context("Failed to flush homing command to CNC")?;
-                }
-
-                // Read immediate status response
-                let mut line = String::new();
-                tokio::time::timeout(
-                    tokio::time::Duration::from_secs(2),
-                    reader.read_line(&mut line)
-                ).await
-                    .context("Timeout waiting for homing to start")??;
-
-                tracing::debug!("Homing started: {}", line.trim());
-
-                // Wait for grblHAL to send status update when homing completes
-                // Keep reading lines until we see Idle state or timeout
-                let start_time = tokio::time::Instant::now();
-                let timeout_duration = tokio::time::Duration::from_secs(60);
-
-                loop {
-                    let remaining_time = timeout_duration.saturating_sub(start_time.elapsed());
-                    if remaining_time.is_zero() {
-                        return Err(anyhow::anyhow!("Homing timeout after 60 seconds"));
+                changed = reports.changed() => {
+                    if changed.is_err() {
+                        anyhow::bail!("CNC connection closed");
                     }
-
-                    line.clear();
-                    match tokio::time::timeout(remaining_time, reader.read_line(&mut line)).await {
-                        Ok(Ok(_)) => {
-                            let response = line.trim();
-                            tracing::debug!("Homing response: {}", response);
-
-                            // Check for completion
-                            if response == "ok" {
-                                tracing::info!("Homing completed after {:.1}s", start_time.elapsed().as_secs_f32());
-                                return Ok("ok".to_string());
-                            }
-
-                            // Check for alarm in status responses
-                            if let Ok(state) = Self::parse_state(response) {
-                                if state.starts_with("Alarm") {
-                                    return Err(anyhow::anyhow!("Homing failed: {}", state));
-                                }
-                            }
-
-                            // Ignore blank MSG lines and status updates, keep waiting
-                        }
-                        Ok(Err(e)) => {
-                            return Err(anyhow::anyhow!("Error reading during homing: {}", e));
-                        }
-                        Err(_) => {
-                            return Err(anyhow::anyhow!("Homing timeout - no response from controller"));
+                    let report = reports
+                        .borrow_and_update()
+                        .as_ref()
+                        .map(|(_, report)| report.clone())
+                        .unwrap_or_default();
+                    tracing::debug!("Homing response: {}", report);
+                    started = true;
+
+                    // Check for alarm in status reports
+                    if let Ok(state) = Self::parse_state(&report) {
+                        if state.starts_with("Alarm") {
+                            return Err(anyhow::anyhow!("Homing failed: {}", state));
                         }
                     }
                 }
             }
-            CncConnectionType::Dummy => {
-                Err(anyhow::anyhow!("System is in fault state - CNC not connected"))
-            }
         }
     }
 
-    /// Move to absolute position with feed rate
-    pub async fn move_absolute(&self, axis: &str, position: f64, feed_rate: f64) -> Result<String> {
-        let command = format!("G90 G1 {}{}F{}", axis, position, feed_rate);
-        self.send_command(&command).await
+    /// Queue absolute moves `(position, feed_rate)` one after another
+    /// grblHAL's planner blends the segments, so the axes change speed without stopping
+    ///
+    /// `axes` are `(axis, factor)` pairs: each axis moves to `position * factor`, so
+    /// synchronized axes (factor 1 or -1) travel the same distance at the same speed.
+    pub async fn move_segments(&self, axes: &[(String, f64)], segments: &[(f64, f64)]) -> Result<String> {
+        let commands: Vec<String> = segments
+            .iter()
+            .map(|&(position, feed_rate)| {
+                format!("G90 G1 {}F{}", Self::axis_words(axes, position), Self::path_feed(axes, feed_rate))
+            })
+            .collect();
+        self.send_commands(&commands).await
+    }
+
+    /// Axis words moving each of `axes` to `value * factor`, e.g. `X100 Y-100`
+    fn axis_words(axes: &[(String, f64)], value: f64) -> String {
+        axes.iter()
+            .map(|(axis, factor)| format!("{}{}", axis, value * factor))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Feed rate along the combined path that moves each of `axes` (equal distances) at `feed_rate`
+    fn path_feed(axes: &[(String, f64)], feed_rate: f64) -> f64 {
+        let feed = feed_rate * (axes.len() as f64).sqrt();
+        (feed * 1000.0).round() / 1000.0
+    }
+
+    /// Send several commands in one write and wait for a response to each
+    ///
+    /// One write keeps other commands from landing between them, so queued moves reach the
+    /// planner before the earlier ones finish.
+    pub async fn send_commands(&self, commands: &[String]) -> Result<String> {
+        let batch: String = commands.iter().map(|command| format!("{}\n", command.trim())).collect();
+        tracing::debug!("Sending CNC commands: {:?}", commands);
+
+        let reply = self.submit_lines(batch, commands.len(), "Failed to send commands to CNC").await?;
+        let timeout = self.read_timeout() * commands.len().max(1) as u32;
+        let lines = Self::wait_for_reply(reply, timeout).await?;
+
+        // Report the first failed command rather than the last response
+        if let Some(error) = lines.iter().find(|line| line.starts_with("error:")) {
+            anyhow::bail!("CNC error: {}", Self::describe_error(error));
+        }
+        self.process_response_lines(lines)
     }
 
     /// Jog axis by a relative distance at specified feed rate
     /// Uses the $J jog command which enables real-time feed override and rapid stop
-    pub async fn jog(&self, axis: &str, distance: f64, feed_rate: f64) -> Result<String> {
-        let command = format!("$J=G21G91{}{}F{}", axis, distance, feed_rate);
+    /// `axes` are `(axis, factor)` pairs as for `move_segments`
+    pub async fn jog(&self, axes: &[(String, f64)], distance: f64, feed_rate: f64) -> Result<String> {
+        let command = format!(
+            "$J=G21G91{}F{}",
+            Self::axis_words(axes, distance).replace(' ', ""),
+            Self::path_feed(axes, feed_rate)
+        );
         self.send_command(&command).await
     }
 
-    /// Get current position (send ? status query)
+    /// Latest status report: the last pushed one (`enable_status_reports`) if it is no older
+    /// than two report intervals, otherwise the reply to a `?` query
+    ///
+    /// For following the door's position; after a command, use `get_status()` so the report
+    /// can't predate it.
+    pub async fn latest_status(&self) -> Result<String> {
+        let interval_ms = self.report_interval_ms.load(Ordering::Relaxed);
+        if interval_ms > 0 {
+            if let Some((received, report)) = &*self.reports.borrow() {
+                if received.elapsed() < Duration::from_millis(interval_ms * 2) {
+                    return Ok(report.clone());
+                }
+            }
+        }
+        self.get_status().await
+    }
+
+    /// Get current status (send ? status query and wait for the report)
     pub async fn get_status(&self) -> Result<String> {
-        self.send_command_with_options("?", true, 1000).await
+        let mut reports = self.reports.clone();
+
+        // Only a report arriving after the query counts
+        let sent = Instant::now();
+        self.send_realtime_command(b'?').await?;
+
+        let deadline = sent + self.read_timeout();
+        loop {
+            if let Some((received, report)) = &*reports.borrow_and_update() {
+                if *received >= sent {
+                    return Ok(report.clone());
+                }
+            }
+            match tokio::time::timeout_at(deadline, reports.changed()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) => anyhow::bail!("CNC connection closed"),
+                Err(_) => anyhow::bail!("Timeout reading from CNC"),
+            }
+        }
     }
 
     /// Send feed hold command (0x21 = '!')
@@ -604,6 +748,22 @@ impl CncController {
         self.send_realtime_command(0x21).await
     }
 
+    /// Send cycle start command (0x7E = '~')
+    ///
+    /// Resumes motion after a feed hold, or after the safety door once it is closed (`Door:0`).
+    /// grblHAL also accepts 0x81 for this; `~` works with every grbl.
+    pub async fn cycle_start(&self) -> Result<()> {
+        self.send_realtime_command(0x7E).await
+    }
+
+    /// Send jog cancel command (0x85)
+    ///
+    /// Decelerates and ends a running `$J=` jog, dropping any queued jogs; ordinary `G1`
+    /// moves are left alone.
+    pub async fn jog_cancel(&self) -> Result<()> {
+        self.send_realtime_command(0x85).await
+    }
+
     /// Send queue flush command (0x19 = Ctrl-Y)
     ///
     /// Gracefully clears the command queue without triggering an alarm state.
@@ -613,6 +773,15 @@ impl CncController {
         self.send_realtime_command(0x19).await
     }
 
+    /// Send soft reset command (0x18 = Ctrl-X)
+    ///
+    /// Stops everything at once, including a homing cycle (which feed hold can't pause), and
+    /// discards commands waiting for replies. grblHAL loses its position and comes back in
+    /// alarm if the reset interrupted motion.
+    pub async fn soft_reset(&self) -> Result<()> {
+        self.send_realtime_command(0x18).await
+    }
+
     /// Parse position from status response
     /// Status format: <Idle|MPos:0.000,0.000,0.000|...>
     pub fn parse_position(status: &str, axis: &str) -> Result<f64> {
@@ -629,17 +798,7 @@ impl CncController {
 
         let coords = &status[coords_start..coords_end];
         let parts: Vec<&str> = coords.split(',').collect();
-
-        // Map axis to index: X=0, Y=1, Z=2, A=3, B=4, C=5
-        let index = match axis.to_uppercase().as_str() {
-            "X" => 0,
-            "Y" => 1,
-            "Z" => 2,
-            "A" => 3,
-            "B" => 4,
-            "C" => 5,
-            _ => anyhow::bail!("Invalid axis: {} (supported: X, Y, Z, A, B, C)", axis),
-        };
+        let index = Self::axis_index(axis)?;
 
         if index < parts.len() {
             parts[index]
@@ -650,6 +809,59 @@ impl CncController {
         }
     }
 
+    /// Raw value of a named status report field, e.g. "PD" for field "Pn" in <Idle|...|Pn:PD>
+    pub fn parse_field<'a>(status: &'a str, field: &str) -> Option<&'a str> {
+        let prefix = format!("{}:", field);
+        status
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .split('|')
+            .find_map(|part| part.strip_prefix(prefix.as_str()))
+    }
+
+    /// Current feed rate in mm/min, from the `FS:feed,spindle` field (or `F:feed` without a
+    /// spindle)
+    pub fn parse_feed_rate(status: &str) -> Option<f64> {
+        let field = Self::parse_field(status, "FS").or_else(|| Self::parse_field(status, "F"))?;
+        field.split(',').next()?.trim().parse().ok()
+    }
+
+    /// Parse an axis value from a named status report field
+    /// Status format: <Run|MPos:...|Ld:35,0,0> for field "Ld"; a field with a single value
+    /// applies to every axis
+    pub fn parse_axis_field(status: &str, field: &str, axis: &str) -> Result<f64> {
+        let values = Self::parse_field(status, field).with_context(|| format!("{} not found in status", field))?;
+
+        let parts: Vec<&str> = values.split(',').collect();
+        let value = if parts.len() == 1 {
+            parts[0]
+        } else {
+            let index = Self::axis_index(axis)?;
+            parts
+                .get(index)
+                .with_context(|| format!("No {} value for axis {}", field, axis))?
+        };
+
+        value
+            .trim()
+            .parse::<f64>()
+            .with_context(|| format!("Failed to parse {} value", field))
+    }
+
+    /// Map axis to its index in multi-axis reports: X=0, Y=1, Z=2, A=3, B=4, C=5
+    fn axis_index(axis: &str) -> Result<usize> {
+        match axis.to_uppercase().as_str() {
+            "X" => Ok(0),
+            "Y" => Ok(1),
+            "Z" => Ok(2),
+            "A" => Ok(3),
+            "B" => Ok(4),
+            "C" => Ok(5),
+            _ => anyhow::bail!("Invalid axis: {} (supported: X, Y, Z, A, B, C)", axis),
+        }
+    }
+
     /// Parse state from status response
     /// Status format: <Idle|...> or <Run|...> etc.
     pub fn parse_state(status: &str) -> Result<String> {
@@ -684,6 +896,18 @@ impl CncController {
         (false, None)
     }
 
+    /// Parse the safety door state from a status response, if the door stopped the machine
+    /// Status format: <Door:1|...>; 0 = closed and ready to resume, 1 = still ajar,
+    /// 2 = opened and parking, 3 = closed and restoring from the park position
+    pub fn parse_safety_door(status: &str) -> Option<u32> {
+        let state = Self::parse_state(status).ok()?;
+        let code = state.strip_prefix("Door")?;
+        match code.strip_prefix(':') {
+            Some(code) => code.parse().ok(),
+            None => code.is_empty().then_some(0),
+        }
+    }
+
     /// Check if an error is a connection/communication error (should trigger reconnect)
     /// vs a grblHAL command error (should not trigger reconnect)
     ///
@@ -715,8 +939,98 @@ impl CncController {
         drop(conn);
 
         // Give the OS time to release the resource (especially important for serial)
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
 
         tracing::debug!("CNC connection closed");
     }
 }
+
+/// grblHAL alarm codes (`ALARM:<code>`, `<Alarm:<code>|...>`)
+const ALARM_CODES: &[(u32, &str)] = &[
+    (1, "Hard limit triggered, position lost"),
+    (2, "Soft limit: target beyond machine travel"),
+    (3, "Reset while in motion, position lost"),
+    (4, "Probe fail: probe not in the expected initial state"),
+    (5, "Probe fail: probe did not make contact"),
+    (6, "Homing fail: reset during homing cycle"),
+    (7, "Homing fail: safety door opened during homing cycle"),
+    (8, "Homing fail: pull-off did not clear the limit switch"),
+    (9, "Homing fail: limit switch not found within search distance"),
+    (10, "Emergency stop asserted"),
+    (11, "Homing required"),
+    (12, "Limit switch engaged"),
+    (13, "Probe protection triggered"),
+    (14, "Spindle at speed timeout"),
+    (15, "Homing fail: second limit switch not found (auto-squaring)"),
+    (16, "Power-on self test failed"),
+    (17, "Motor fault"),
+    (18, "Homing fail: bad configuration"),
+];
+
+/// grblHAL error codes (`error:<code>` replies)
+const ERROR_CODES: &[(u32, &str)] = &[
+    (1, "G-code word is missing its letter"),
+    (2, "G-code word value is missing or not a valid number"),
+    (3, "'$' system command not recognised or not supported"),
+    (4, "Negative value for a positive-only setting"),
+    (5, "Homing is not enabled in settings"),
+    (6, "Step pulse time must be at least 3 microseconds"),
+    (7, "Settings read failed, defaults restored"),
+    (8, "'$' command only allowed while idle"),
+    (9, "G-code locked out during alarm or jog"),
+    (10, "Soft limits need homing enabled"),
+    (11, "Line too long"),
+    (12, "Setting would exceed the maximum step rate"),
+    (13, "Safety door opened"),
+    (14, "Startup line or build info too long"),
+    (15, "Jog target beyond machine travel"),
+    (16, "Invalid jog command"),
+    (17, "Laser mode requires PWM output"),
+    (18, "Reset asserted"),
+    (19, "Value must be positive"),
+    (20, "Unsupported or invalid G-code command"),
+    (21, "More than one command from the same modal group"),
+    (22, "Feed rate not set"),
+    (23, "Command requires an integer value"),
+    (24, "More than one command needing axis words"),
+    (25, "Repeated G-code word"),
+    (26, "No axis words for a command that needs them"),
+    (27, "Invalid line number"),
+    (28, "Command is missing a required value word"),
+    (29, "Work coordinate system not supported"),
+    (30, "G53 only allowed with G0 and G1"),
+    (31, "Axis words given with no command that uses them"),
+    (32, "Arc needs at least one in-plane axis word"),
+    (33, "Invalid motion target"),
+    (34, "Invalid arc radius"),
+    (35, "Arc needs at least one in-plane offset word"),
+    (36, "Unused value words in block"),
+    (37, "Tool length offset not on the configured axis"),
+    (38, "Tool number out of range"),
+    (39, "Value out of range"),
+];
+
+fn describe(table: &[(u32, &'static str)], code: &str) -> Option<&'static str> {
+    let code: u32 = code.trim().parse().ok()?;
+    table.iter().find(|(c, _)| *c == code).map(|(_, text)| *text)
+}
+
+impl CncController {
+    /// Human-readable meaning of a grblHAL alarm code (`None` for codes we don't know)
+    pub fn alarm_description(code: &str) -> Option<&'static str> {
+        describe(ALARM_CODES, code)
+    }
+
+    /// Human-readable meaning of a grblHAL error code (`None` for codes we don't know)
+    pub fn error_description(code: &str) -> Option<&'static str> {
+        describe(ERROR_CODES, code)
+    }
+
+    /// `error:<code>` reply with its meaning appended, e.g. `error:9 (G-code locked out during alarm or jog)`
+    fn describe_error(response: &str) -> String {
+        match response.strip_prefix("error:").and_then(Self::error_description) {
+            Some(description) => format!("{} ({})", response, description),
+            None => response.to_string(),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A door's grblHAL settings as dumped by `backup_cnc_settings`, for `restore_cnc_settings`
+/// to push back onto a replacement controller
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CncBackup {
+    /// Every `$$` setting, sorted numerically by setting number
+    pub settings: IndexMap<String, String>,
+    /// When the backup was taken, in ms since the epoch
+    pub saved_ms: u64,
+}
+
+impl CncBackup {
+    pub fn new(settings: IndexMap<String, String>) -> Self {
+        let saved_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        Self { settings, saved_ms }
+    }
+}
+
+/// Backups by door id
+type CncBackups = BTreeMap<String, CncBackup>;
+
+/// Store `backup` as `door`'s, keeping the other doors' backups in the file
+pub async fn save(path: &Path, door: &str, backup: &CncBackup) -> Result<()> {
+    let mut backups = read(path).await?;
+    backups.insert(door.to_string(), backup.clone());
+
+    let json = serde_json::to_string_pretty(&backups)?;
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json)
+        .await
+        .with_context(|| format!("Failed to write {:?}", tmp))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// `door`'s last backup, if it has one
+pub async fn load(path: &Path, door: &str) -> Result<Option<CncBackup>> {
+    Ok(read(path).await?.remove(door))
+}
+
+async fn read(path: &Path) -> Result<CncBackups> {
+    let text = match tokio::fs::read_to_string(path).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(CncBackups::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    serde_json::from_str(&text).with_context(|| format!("Invalid CNC settings backup {:?}", path))
+}
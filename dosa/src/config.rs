@@ -1,17 +1,38 @@
-use anyhow::{Context, Result};
-use directories::ProjectDirs;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shq_audit::AuditConfig;
+use shq_auth::AuthConfig;
+use shq_config::{migrate, BackupConfig, ConfigEntry, Configuration, Migration};
+use shq_i18n::I18nConfig;
+use shq_logging::LoggingConfig;
+use shq_mdns::MdnsConfig;
+use shq_mqtt::MqttConfig;
+use shq_ratelimit::RateLimitConfig;
+use shq_scene::SceneConfig;
+use shq_scheduler::{Job, SchedulerConfig};
+use shq_ws::{KeepAliveConfig, TlsConfig};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
 use std::path::PathBuf;
-use tokio::fs;
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+use crate::messages::DoorState;
 
 /// WebSocket server configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct WebSocketConfig {
     /// Host address to bind to (e.g., "0.0.0.0" for all interfaces)
     pub host: String,
     /// Port to listen on
     pub port: u16,
+    /// Serve `wss://` with this certificate and key (plain `ws://` if absent); read at startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Server pings, and how long a client gets to answer before it's dropped; read at startup
+    pub keep_alive: KeepAliveConfig,
 }
 
 impl Default for WebSocketConfig {
@@ -19,18 +40,206 @@ impl Default for WebSocketConfig {
         Self {
             host: "0.0.0.0".to_string(),
             port: 8766,
+            tls: None,
+            keep_alive: KeepAliveConfig::default(),
         }
     }
 }
 
-/// CNC connection type
+/// Plain HTTP API server configuration (`http`)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Host address to bind to
+    pub host: String,
+    /// Port to listen on (separate from the WebSocket port)
+    pub port: u16,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8767,
+        }
+    }
+}
+
+/// A door event webhooks and overwatch announcements can be told about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoorEvent {
+    /// The door started opening
+    Opening,
+    /// The door started closing
+    Closing,
+    /// The door reached its open position
+    Opened,
+    /// The door reached its closed position
+    Closed,
+    /// Obstruction detection stopped the door
+    Obstructed,
+    /// The controller went into alarm
+    Alarm,
+    /// The controller connection was lost
+    Fault,
+}
+
+impl DoorEvent {
+    /// What entering `state` from `last` tells a listener, if anything
+    pub fn between(last: &DoorState, state: &DoorState) -> Option<Self> {
+        if last == state {
+            return None;
+        }
+        match state {
+            DoorState::Opening => Some(Self::Opening),
+            DoorState::Closing => Some(Self::Closing),
+            DoorState::Open => Some(Self::Opened),
+            // A closed position check ends where it started
+            DoorState::Closed if *last != DoorState::Verifying => Some(Self::Closed),
+            DoorState::Obstructed => Some(Self::Obstructed),
+            DoorState::Alarm => Some(Self::Alarm),
+            DoorState::Fault => Some(Self::Fault),
+            _ => None,
+        }
+    }
+}
+
+/// An HTTP endpoint that gets a JSON POST for door events (`webhooks`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Where to POST (http:// or https://)
+    pub url: String,
+    /// Events to send; empty sends all of them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<DoorEvent>,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Further attempts after a failed POST (0-10), waiting 1s, 2s, 4s... between them
+    pub retries: u32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: Vec::new(),
+            headers: BTreeMap::new(),
+            retries: 3,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, event: DoorEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Spoken announcements through an overwatch service (`overwatch`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverwatchConfig {
+    /// overwatch's gRPC address, e.g. `http://overwatch.local:50051`
+    pub url: String,
+    /// Voice, volume (0.0-2.0) and tone for every announcement; overwatch's defaults if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification_tone_id: Option<String>,
+    /// overwatch output to speak on (e.g. `garage`); every output if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// What to say for each event; events not listed aren't announced
+    pub announcements: BTreeMap<DoorEvent, Announcement>,
+}
+
+/// One event's announcement: fixed `text`, or an `announce.<template>` from overwatch's
+/// locale files
+///
+/// Both get `{door}` (the door id) and `{position}` (percent open); `args` add to or replace them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    /// Override the section's voice, volume, tone and zone for this event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_tone_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+}
+
+/// Flood protection for motion commands, on top of `rate_limit` (`command_limit`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandLimitConfig {
+    /// Limit motion commands per client IP to `rate` per second after a `burst`
+    pub enabled: bool,
+    pub rate: f64,
+    pub burst: u32,
+    /// Clients that are never limited
+    pub exempt: Vec<IpAddr>,
+    /// A motion command identical to the door's last one within this many ms is acknowledged
+    /// but not run again (0 = off)
+    pub debounce_ms: u64,
+}
+
+impl Default for CommandLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rate: 2.0,
+            burst: 10,
+            exempt: Vec::new(),
+            debounce_ms: 500,
+        }
+    }
+}
+
+impl CommandLimitConfig {
+    /// The rate part, for a `shq_ratelimit::RateLimiter`
+    pub fn rate_limit(&self) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: self.enabled,
+            rate: self.rate,
+            burst: self.burst,
+            exempt: self.exempt.clone(),
+        }
+    }
+}
+
+/// Id of the door configured under `door`; doors under `doors` are addressed by their key
+pub const PRIMARY_DOOR: &str = "main";
+
+/// CNC connection type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum CncConnection {
     Tcp { host: String, port: u16 },
     Serial { port: String, baud_rate: u32 },
 }
 
+impl std::fmt::Display for CncConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp { host, port } => write!(f, "{}:{}", host, port),
+            Self::Serial { port, .. } => write!(f, "{}", port),
+        }
+    }
+}
+
 impl Default for CncConnection {
     fn default() -> Self {
         Self::Tcp {
@@ -40,8 +249,22 @@ impl Default for CncConnection {
     }
 }
 
+/// Whether a door restarts from the position saved at shutdown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestorePosition {
+    /// Always start unhomed
+    Off,
+    /// Only if the controller still reports the machine position it had at shutdown
+    #[default]
+    Verify,
+    /// Whatever the controller reports, like `zero` at the saved position (clears an alarm,
+    /// e.g. after the controller was power cycled); the door must not have been moved by hand
+    Trust,
+}
+
 /// Door configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct DoorConfig {
     /// Distance to open the door in millimeters
@@ -65,8 +288,86 @@ pub struct DoorConfig {
     /// Default: false
     pub auto_home: bool,
 
+    /// Pick up from the position saved at the last shutdown instead of needing homing
+    pub restore_position: RestorePosition,
+
+    /// Close the door this many seconds after it finishes opening, unless a client sends
+    /// `hold_open` (0 = never)
+    pub auto_close_after_secs: u64,
+
     /// CNC controller connection
+    /// Doors with identical connections (and fallbacks) share it
     pub cnc_connection: CncConnection,
+
+    /// Backup controllers, tried in order when the active connection fails
+    /// Default: none
+    pub cnc_fallbacks: Vec<CncConnection>,
+
+    /// Retry the CNC connection this often while in Fault state, in seconds (0 = only when a
+    /// command is sent)
+    pub reconnect_interval_secs: u64,
+
+    /// Poll the controller with `?` this often, in ms (50-1000), while the door moves or the
+    /// controller is busy
+    pub poll_interval_ms: u64,
+
+    /// Poll this often, in ms, while the door is at rest (at least `poll_interval_ms`, at most
+    /// 10000); a command that starts a move switches back to `poll_interval_ms` at once
+    pub idle_poll_interval_ms: u64,
+
+    /// Have grblHAL push a status report this often, in ms ($481, 100-1000), instead of
+    /// polling with `?` (0 = poll); applied when the connection opens
+    pub status_report_interval_ms: u64,
+
+    /// Further axes driven in lockstep with `cnc_axis` (e.g. the second leaf of a double door)
+    /// They move the same distance, each in its own open direction
+    pub sync_axes: Vec<SyncAxis>,
+
+    /// Stop and back off when the motor load spikes during a move
+    pub obstruction: ObstructionConfig,
+
+    /// Beam or PIR sensor across the doorway that stops and reopens a closing door
+    pub safety_sensor: SafetySensorConfig,
+
+    /// Emergency stop button that halts the controller and latches the `estop` state
+    pub estop: EStopConfig,
+
+    /// Soft start/soft stop for open, close and move (off by default: one constant-speed move)
+    pub motion: MotionProfile,
+
+    /// Probe for the limit switch after each close to catch missed steps or a slipping belt
+    pub close_verify: CloseVerifyConfig,
+
+    /// How long to wait for the controller, for long rails or slow homing feeds
+    pub timeouts: TimeoutsConfig,
+
+    /// Named partial-open positions in percent (e.g. "pet": 15.0), used by `open_preset`
+    pub presets: BTreeMap<String, f64>,
+
+    /// grblHAL settings this door needs (e.g. "$110": 6000), written on every connection when
+    /// the controller's differ, so a firmware reset doesn't leave it misconfigured
+    #[serde(deserialize_with = "setting_values")]
+    pub cnc_settings: BTreeMap<String, String>,
+}
+
+/// Read `cnc_settings` values written as YAML numbers or strings
+fn setting_values<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values = BTreeMap::<String, Value>::deserialize(deserializer)?;
+    values
+        .into_iter()
+        .map(|(setting, value)| match value {
+            Value::String(value) => Ok((setting, value)),
+            Value::Number(value) => Ok((setting, value.to_string())),
+            Value::Bool(value) => Ok((setting, if value { "1" } else { "0" }.to_string())),
+            other => Err(serde::de::Error::custom(format!(
+                "cnc_settings {} must be a number or string, got {}",
+                setting, other
+            ))),
+        })
+        .collect()
 }
 
 impl Default for DoorConfig {
@@ -78,117 +379,739 @@ impl Default for DoorConfig {
             cnc_axis: "X".to_string(),
             open_direction: "right".to_string(),
             auto_home: false,
+            restore_position: RestorePosition::default(),
+            auto_close_after_secs: 0,
             cnc_connection: CncConnection::default(),
+            cnc_fallbacks: Vec::new(),
+            reconnect_interval_secs: 10,
+            poll_interval_ms: 200,
+            idle_poll_interval_ms: 2000,
+            status_report_interval_ms: 0,
+            sync_axes: Vec::new(),
+            obstruction: ObstructionConfig::default(),
+            safety_sensor: SafetySensorConfig::default(),
+            estop: EStopConfig::default(),
+            motion: MotionProfile::default(),
+            close_verify: CloseVerifyConfig::default(),
+            timeouts: TimeoutsConfig::default(),
+            presets: BTreeMap::new(),
+            cnc_settings: BTreeMap::new(),
         }
     }
 }
 
-/// Application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl DoorConfig {
+    /// `cnc_connection` followed by the `cnc_fallbacks`, in the order they're tried
+    pub fn cnc_endpoints(&self) -> Vec<CncConnection> {
+        std::iter::once(self.cnc_connection.clone())
+            .chain(self.cnc_fallbacks.iter().cloned())
+            .collect()
+    }
+
+    /// `(axis, factor)` for the main axis and each sync axis: a sync axis moves to the main
+    /// axis' position times its factor (-1 when it opens the other way)
+    pub fn axes(&self) -> Vec<(String, f64)> {
+        let main_left = self.open_direction.eq_ignore_ascii_case("left");
+        let mut axes = vec![(self.cnc_axis.clone(), 1.0)];
+        for sync in &self.sync_axes {
+            let same_way = sync.open_direction.eq_ignore_ascii_case("left") == main_left;
+            axes.push((sync.axis.clone(), if same_way { 1.0 } else { -1.0 }));
+        }
+        axes
+    }
+}
+
+/// Axis moved together with the door's main axis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncAxis {
+    /// CNC axis (X, Y, Z, A, B, or C), other than the door's `cnc_axis`
+    pub axis: String,
+
+    /// Direction this axis moves when opening: "left" or "right"
+    pub open_direction: String,
+}
+
+/// Obstruction detection from the motor load grblHAL reports while the door is moving
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
-pub struct Config {
-    pub door: DoorConfig,
-    pub websocket: WebSocketConfig,
+pub struct ObstructionConfig {
+    /// Watch the motor load while opening and closing
+    /// Default: false
+    pub enabled: bool,
+
+    /// Status report field carrying the load, e.g. "Ld" for `<Run|MPos:...|Ld:35,0,0>`
+    /// The door axis' value is used, or the only value if the field has just one
+    pub field: String,
+
+    /// Load above which the door is treated as obstructed
+    pub threshold: f64,
+
+    /// Consecutive status polls (`poll_interval_ms` apart) over the threshold before stopping
+    pub samples: u32,
+
+    /// Distance in mm to back away from the obstruction after stopping (0 = stop only)
+    pub back_off: f64,
 }
 
-impl Default for Config {
+impl Default for ObstructionConfig {
     fn default() -> Self {
         Self {
-            door: DoorConfig::default(),
-            websocket: WebSocketConfig::default(),
+            enabled: false,
+            field: "Ld".to_string(),
+            threshold: 80.0,
+            samples: 2,
+            back_off: 50.0,
         }
     }
 }
 
-/// Configuration manager for persistent storage
-pub struct ConfigManager {
-    config_path: PathBuf,
-    config: Config,
+/// Check of the closed position against the limit switch after every close
+///
+/// Homing leaves the door the homing pull-off (`$27`) away from the switch, so the switch
+/// should close exactly that far beyond the closed position. A door that lost steps finds it
+/// somewhere else.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloseVerifyConfig {
+    /// Probe after each close
+    /// Default: false
+    pub enabled: bool,
+
+    /// Probing speed in mm/min
+    pub feed_rate: f64,
+
+    /// How far past the expected switch position to search, in mm
+    pub search_distance: f64,
+
+    /// Difference from the expected switch position, in mm, that counts as drift
+    pub tolerance: f64,
 }
 
-impl ConfigManager {
-    /// Create a new configuration manager and load config from disk
-    pub async fn new() -> Result<Self> {
-        let config_path = Self::get_config_path()?;
+impl Default for CloseVerifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            feed_rate: 300.0,
+            search_distance: 5.0,
+            tolerance: 1.0,
+        }
+    }
+}
+
+/// How long dosa waits for the controller before giving up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    /// Reply to a command or status query, in ms
+    /// Default: 1000
+    pub read_ms: u64,
 
-        // Ensure config directory exists
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)
-                .await
-                .context("Failed to create config directory")?;
+    /// Status report confirming the position after a stop, in ms
+    /// Default: 3000
+    pub status_ms: u64,
+
+    /// Homing cycle from its start to the `ok`, per axis, in seconds
+    /// Default: 60
+    pub homing_secs: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            read_ms: 1000,
+            status_ms: 3000,
+            homing_secs: 60,
         }
+    }
+}
 
-        // Load or create default config
-        let config = Self::load_config(&config_path).await?;
+/// Safety sensor (light beam or PIR) watched by the position monitor
+///
+/// While it is triggered the door won't start closing, a closing door is feed-held and opened
+/// again, and the auto-close countdown waits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetySensorConfig {
+    /// Watch the sensor
+    /// Default: false
+    pub enabled: bool,
 
-        Ok(Self {
-            config_path,
-            config,
-        })
+    /// Where the sensor is wired
+    pub input: SensorInput,
+
+    /// Treat an inactive input as triggered, e.g. for a normally-closed beam
+    pub invert: bool,
+}
+
+impl Default for SafetySensorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            input: SensorInput::Grbl { pin: "P".to_string() },
+            invert: false,
+        }
     }
+}
 
-    /// Get the XDG-compliant config path: ~/.config/dosa/config.yaml
-    fn get_config_path() -> Result<PathBuf> {
-        let proj_dirs = ProjectDirs::from("", "", "dosa")
-            .context("Failed to determine config directory")?;
+/// Emergency stop button or switch, watched by the position monitor
+///
+/// When it trips, the controller is reset straight away (no deceleration, like grblHAL's own
+/// E-stop) and the door stays in the `estop` state, refusing every motion command, until a
+/// client sends `reset_estop` with the input released. An input that can't be read trips it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EStopConfig {
+    /// Watch the E-stop
+    /// Default: false
+    pub enabled: bool,
 
-        Ok(proj_dirs.config_dir().join("config.yaml"))
+    /// Where the E-stop is wired (default: grblHAL's E-stop pin, `E` in `Pn:`)
+    pub input: SensorInput,
+
+    /// Treat an inactive input as tripped, e.g. for a normally-closed button
+    pub invert: bool,
+}
+
+impl Default for EStopConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            input: SensorInput::Grbl { pin: "E".to_string() },
+            invert: false,
+        }
     }
+}
 
-    /// Load config from disk, or create default if it doesn't exist
-    async fn load_config(path: &PathBuf) -> Result<Config> {
-        if path.exists() {
-            let contents = fs::read_to_string(path)
-                .await
-                .context("Failed to read config file")?;
+/// Input a safety sensor or E-stop is read from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum SensorInput {
+    /// grblHAL input pin, active while its letter is in the status report's `Pn:` field
+    /// (e.g. "P" for the probe input)
+    Grbl { pin: String },
+    /// File holding "0" or "1", e.g. a sysfs GPIO value like `/sys/class/gpio/gpio17/value`
+    Gpio { path: PathBuf },
+}
 
-            let config: Config = serde_yaml::from_str(&contents)
-                .context("Failed to parse config file")?;
+/// Speed ramping for door moves, sent to grblHAL as several `G1` segments that its planner
+/// runs back to back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionProfile {
+    /// Distance in mm at the start of a move over which the speed steps up from `creep_speed`
+    /// to the full open/close speed (0 = full speed straight away)
+    pub ramp_distance: f64,
 
-            tracing::info!("Loaded configuration from {:?}", path);
-            Ok(config)
-        } else {
-            tracing::info!("Config file not found, creating default at {:?}", path);
-            let config = Config::default();
+    /// Distance in mm before fully open or fully closed covered at `creep_speed`, so the door
+    /// doesn't hit the end of travel at full speed (0 = off)
+    pub creep_distance: f64,
 
-            // Save default config
-            let yaml = serde_yaml::to_string(&config)
-                .context("Failed to serialize default config")?;
-            fs::write(path, yaml)
-                .await
-                .context("Failed to write default config")?;
+    /// Feed rate in mm/min at the start of the ramp and for the final creep
+    pub creep_speed: f64,
+}
 
-            Ok(config)
+impl Default for MotionProfile {
+    fn default() -> Self {
+        Self {
+            ramp_distance: 0.0,
+            creep_distance: 0.0,
+            creep_speed: 600.0,
         }
     }
+}
 
-    /// Save config to disk
-    async fn save(&self) -> Result<()> {
-        let yaml = serde_yaml::to_string(&self.config)
-            .context("Failed to serialize config")?;
+/// Door command run by a schedule job or scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoorAction {
+    Open,
+    Close,
+    /// Move to a percentage (0 = closed, 100 = fully open)
+    Move { percent: f64 },
+    Home,
+}
 
-        fs::write(&self.config_path, yaml)
-            .await
-            .context("Failed to write config file")?;
+/// Application configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Schema version, maintained by `shq-config` migrations
+    pub config_version: u32,
+    /// Primary door (id "main"); the one schedules, scenes and MQTT drive
+    pub door: DoorConfig,
+    /// Further independent doors by id, each with its own axis, travel and direction
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub doors: BTreeMap<String, DoorConfig>,
+    pub websocket: WebSocketConfig,
+    /// Client tokens and their roles for the WebSocket API (off by default)
+    pub auth: AuthConfig,
+    pub logging: LoggingConfig,
+    /// Scheduled door commands
+    pub schedule: SchedulerConfig<DoorAction>,
+    /// Named bundles of door and peer commands
+    pub scene: SceneConfig<DoorAction>,
+    /// Per-client request limits for the WebSocket API
+    pub rate_limit: RateLimitConfig,
+    /// Per-client limit and debounce for motion commands (WebSocket and HTTP)
+    pub command_limit: CommandLimitConfig,
+    /// Remote config export/import
+    pub backup: BackupConfig,
+    /// Language of messages sent to clients
+    pub i18n: I18nConfig,
+    /// Audit log of connections, privileged commands and config changes
+    pub audit: AuditConfig,
+    /// Home Assistant MQTT discovery (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    /// `_dosa._tcp` advertisement for panels and peer services (on by default)
+    pub mdns: MdnsConfig,
+    /// REST endpoints for scripts and curl (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    /// HTTP endpoints told about door events (opened, closed, alarms...)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    /// overwatch service that speaks door events (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overwatch: Option<OverwatchConfig>,
+}
+
+impl Configuration for Config {
+    const VERSION: u32 = 1;
+
+    fn migrations() -> Vec<Migration> {
+        vec![drop_limit_offset]
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_door("door", &self.door)?;
+        for (id, door) in &self.doors {
+            if id.is_empty() || id == PRIMARY_DOOR || id.contains(char::is_whitespace) {
+                anyhow::bail!("Invalid door id {:?} (must be non-empty, without spaces, not \"{}\")", id, PRIMARY_DOOR);
+            }
+            validate_door(&format!("doors.{}", id), door)?;
+        }
+
+        self.schedule.validate()?;
+        for job in &self.schedule.jobs {
+            if let DoorAction::Move { percent } = job.action {
+                if !(0.0..=100.0).contains(&percent) {
+                    anyhow::bail!("schedule job '{}': move percent must be 0-100, got {}", job.id, percent);
+                }
+            }
+        }
+
+        if let Some(http) = &self.http {
+            if http.port == self.websocket.port {
+                anyhow::bail!("http.port must differ from websocket.port ({})", http.port);
+            }
+        }
+
+        for (i, webhook) in self.webhooks.iter().enumerate() {
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                anyhow::bail!("webhooks[{}].url must start with http:// or https://, got {:?}", i, webhook.url);
+            }
+            if webhook.retries > 10 {
+                anyhow::bail!("webhooks[{}].retries must be 0-10, got {}", i, webhook.retries);
+            }
+            if webhook.headers.keys().any(|name| name.trim().is_empty()) {
+                anyhow::bail!("webhooks[{}].headers: header names must not be empty", i);
+            }
+        }
+
+        if let Some(overwatch) = &self.overwatch {
+            if !overwatch.url.starts_with("http://") && !overwatch.url.starts_with("https://") {
+                anyhow::bail!("overwatch.url must start with http:// or https://, got {:?}", overwatch.url);
+            }
+            validate_volume("overwatch.volume", overwatch.volume)?;
+            for (event, announcement) in &overwatch.announcements {
+                let name = format!("{:?}", event).to_lowercase();
+                if announcement.text.is_some() == announcement.template.is_some() {
+                    anyhow::bail!("overwatch.announcements.{}: needs either text or template", name);
+                }
+                validate_volume(&format!("overwatch.announcements.{}.volume", name), announcement.volume)?;
+            }
+        }
+
+        self.rate_limit.validate()?;
+        if !self.command_limit.rate.is_finite() || self.command_limit.rate <= 0.0 || self.command_limit.burst == 0 {
+            anyhow::bail!("command_limit.rate must be greater than 0 and command_limit.burst at least 1");
+        }
+        self.auth.validate()?;
+        self.i18n.validate()?;
+        self.mdns.validate()?;
+        self.websocket.keep_alive.validate()?;
+
+        self.scene.validate()?;
+        for (scene, action) in self.scene.local_actions() {
+            if let DoorAction::Move { percent } = action {
+                if !(0.0..=100.0).contains(percent) {
+                    anyhow::bail!("scene '{}': move percent must be 0-100, got {}", scene, percent);
+                }
+            }
+        }
 
-        tracing::debug!("Saved configuration to {:?}", self.config_path);
         Ok(())
     }
+}
+
+/// Check one door's settings; `path` prefixes the field names in errors
+fn validate_door(path: &str, door: &DoorConfig) -> Result<()> {
+    const AXES: [&str; 6] = ["X", "Y", "Z", "A", "B", "C"];
+    const DIRECTIONS: [&str; 2] = ["left", "right"];
+
+    if door.open_distance <= 0.0 {
+        anyhow::bail!("{}.open_distance must be greater than 0, got {}", path, door.open_distance);
+    }
+    if door.open_speed <= 0.0 || door.close_speed <= 0.0 {
+        anyhow::bail!("{}.open_speed and {}.close_speed must be greater than 0", path, path);
+    }
+    if !AXES.contains(&door.cnc_axis.to_uppercase().as_str()) {
+        anyhow::bail!("Invalid {}.cnc_axis: {} (supported: X, Y, Z, A, B, C)", path, door.cnc_axis);
+    }
+    if !DIRECTIONS.contains(&door.open_direction.to_lowercase().as_str()) {
+        anyhow::bail!("Invalid {}.open_direction: {} (must be \"left\" or \"right\")", path, door.open_direction);
+    }
+    let endpoints = door.cnc_endpoints();
+    for (i, endpoint) in endpoints.iter().enumerate() {
+        if endpoints[..i].contains(endpoint) {
+            anyhow::bail!("{}.cnc_fallbacks lists {} twice (or repeats cnc_connection)", path, endpoint);
+        }
+    }
+    if !(50..=1000).contains(&door.poll_interval_ms) {
+        anyhow::bail!("{}.poll_interval_ms must be 50-1000, got {}", path, door.poll_interval_ms);
+    }
+    if !(door.poll_interval_ms..=10000).contains(&door.idle_poll_interval_ms) {
+        anyhow::bail!(
+            "{}.idle_poll_interval_ms must be {}-10000 (poll_interval_ms or more), got {}",
+            path,
+            door.poll_interval_ms,
+            door.idle_poll_interval_ms
+        );
+    }
+    if door.status_report_interval_ms != 0 && !(100..=1000).contains(&door.status_report_interval_ms) {
+        anyhow::bail!(
+            "{}.status_report_interval_ms must be 0 or 100-1000, got {}",
+            path,
+            door.status_report_interval_ms
+        );
+    }
+    let mut axes = vec![door.cnc_axis.to_uppercase()];
+    for sync in &door.sync_axes {
+        let axis = sync.axis.to_uppercase();
+        if !AXES.contains(&axis.as_str()) || axes.contains(&axis) {
+            anyhow::bail!("Invalid {}.sync_axes axis: {} (must be a different axis X-C)", path, sync.axis);
+        }
+        if !DIRECTIONS.contains(&sync.open_direction.to_lowercase().as_str()) {
+            anyhow::bail!("Invalid {}.sync_axes open_direction for {}: {}", path, sync.axis, sync.open_direction);
+        }
+        axes.push(axis);
+    }
+    for (name, percent) in &door.presets {
+        if name.trim().is_empty() {
+            anyhow::bail!("{}.presets names must not be empty", path);
+        }
+        if !(0.0..=100.0).contains(percent) {
+            anyhow::bail!("{}.presets '{}' must be 0-100, got {}", path, name, percent);
+        }
+    }
+    for (setting, value) in &door.cnc_settings {
+        if !is_cnc_setting_name(setting) {
+            anyhow::bail!("Invalid {}.cnc_settings name: {:?} (must be $ and a number, e.g. $110)", path, setting);
+        }
+        if !is_cnc_setting_value(value) {
+            anyhow::bail!("Invalid {}.cnc_settings {} value: {:?}", path, setting, value);
+        }
+        if setting == "$481" && door.status_report_interval_ms != 0 {
+            anyhow::bail!("{}.cnc_settings must not set $481 when status_report_interval_ms is set", path);
+        }
+    }
+    let motion = &door.motion;
+    let distances = [("ramp_distance", motion.ramp_distance), ("creep_distance", motion.creep_distance)];
+    for (name, distance) in distances {
+        if !distance.is_finite() || distance < 0.0 {
+            anyhow::bail!("{}.motion.{} must be 0 or more, got {}", path, name, distance);
+        }
+    }
+    if !motion.creep_speed.is_finite() || motion.creep_speed <= 0.0 {
+        anyhow::bail!("{}.motion.creep_speed must be positive, got {}", path, motion.creep_speed);
+    }
+    if door.safety_sensor.enabled {
+        validate_input(&format!("{}.safety_sensor", path), &door.safety_sensor.input)?;
+    }
+    if door.estop.enabled {
+        validate_input(&format!("{}.estop", path), &door.estop.input)?;
+    }
+    let obstruction = &door.obstruction;
+    if obstruction.enabled {
+        if obstruction.field.is_empty() || obstruction.field.contains(['|', ':', ',']) {
+            anyhow::bail!("Invalid {}.obstruction.field: {:?}", path, obstruction.field);
+        }
+        if !obstruction.threshold.is_finite() {
+            anyhow::bail!("{}.obstruction.threshold must be a number", path);
+        }
+        if obstruction.samples == 0 {
+            anyhow::bail!("{}.obstruction.samples must be at least 1", path);
+        }
+        if !obstruction.back_off.is_finite() || obstruction.back_off < 0.0 {
+            anyhow::bail!("{}.obstruction.back_off must be 0 or more, got {}", path, obstruction.back_off);
+        }
+    }
+    let close_verify = &door.close_verify;
+    if close_verify.enabled {
+        let values = [
+            ("feed_rate", close_verify.feed_rate),
+            ("search_distance", close_verify.search_distance),
+            ("tolerance", close_verify.tolerance),
+        ];
+        for (name, value) in values {
+            if !value.is_finite() || value <= 0.0 {
+                anyhow::bail!("{}.close_verify.{} must be positive, got {}", path, name, value);
+            }
+        }
+    }
+    let timeouts = &door.timeouts;
+    if !(100..=60000).contains(&timeouts.read_ms) {
+        anyhow::bail!("{}.timeouts.read_ms must be 100-60000, got {}", path, timeouts.read_ms);
+    }
+    if !(timeouts.read_ms..=60000).contains(&timeouts.status_ms) {
+        anyhow::bail!(
+            "{}.timeouts.status_ms must be {}-60000 (read_ms or more), got {}",
+            path,
+            timeouts.read_ms,
+            timeouts.status_ms
+        );
+    }
+    if !(1..=3600).contains(&timeouts.homing_secs) {
+        anyhow::bail!("{}.timeouts.homing_secs must be 1-3600, got {}", path, timeouts.homing_secs);
+    }
+    Ok(())
+}
+
+/// Check the `input` of the safety sensor or E-stop at `path`
+/// Whether `setting` names a grblHAL setting: `$` and a number, e.g. `$110`
+pub fn is_cnc_setting_name(setting: &str) -> bool {
+    let number = setting.strip_prefix('$').unwrap_or_default();
+    !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether `value` can be written as `$N=value` in one line
+pub fn is_cnc_setting_value(value: &str) -> bool {
+    !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
+fn validate_input(path: &str, input: &SensorInput) -> Result<()> {
+    match input {
+        SensorInput::Grbl { pin } => {
+            if pin.len() != 1 || !pin.chars().all(|c| c.is_ascii_alphabetic()) {
+                anyhow::bail!("Invalid {}.input pin: {:?} (must be one Pn letter)", path, pin);
+            }
+        }
+        SensorInput::Gpio { path: file } => {
+            if file.as_os_str().is_empty() {
+                anyhow::bail!("{}.input path must not be empty", path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_volume(path: &str, volume: Option<f32>) -> Result<()> {
+    match volume {
+        Some(volume) if !(0.0..=2.0).contains(&volume) => {
+            anyhow::bail!("{} must be 0.0-2.0, got {}", path, volume)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 0 → 1: `door.limit_offset` was replaced by the controller's homing pull-off (`$27`)
+fn drop_limit_offset(doc: &mut Value) -> Result<()> {
+    if let Some(offset) = migrate::remove(doc, "door.limit_offset") {
+        tracing::warn!(
+            "door.limit_offset is no longer used; set the homing pull-off on the controller instead ($27={})",
+            offset
+        );
+    }
+    Ok(())
+}
+
+/// Configuration manager for persistent storage in ~/.config/dosa/config.yaml
+#[derive(Clone)]
+pub struct ConfigManager {
+    inner: shq_config::ConfigManager<Config>,
+}
+
+impl ConfigManager {
+    /// Create a new configuration manager and load config from disk
+    pub async fn new() -> Result<Self> {
+        let inner = shq_config::ConfigManager::load_xdg("dosa", "config.yaml").await?;
+        Ok(Self { inner })
+    }
 
     /// Get the current door configuration
     pub fn get_door_config(&self) -> DoorConfig {
-        self.config.door.clone()
+        self.inner.get().door
     }
 
-    /// Set and persist door configuration
-    pub async fn set_door_config(&mut self, config: DoorConfig) -> Result<()> {
-        self.config.door = config;
-        self.save().await?;
-        Ok(())
+    /// Get the configuration of the doors under `doors`, by id
+    pub fn get_doors_config(&self) -> BTreeMap<String, DoorConfig> {
+        self.inner.get().doors
+    }
+
+    /// Set and persist the configuration of door `id` (`PRIMARY_DOOR` or a key of `doors`)
+    pub async fn set_door_config(&self, id: &str, config: DoorConfig) -> Result<()> {
+        if id != PRIMARY_DOOR && !self.inner.get().doors.contains_key(id) {
+            anyhow::bail!("Unknown door '{}'", id);
+        }
+        self.inner
+            .update(|c| match c.doors.get_mut(id) {
+                Some(door) => *door = config,
+                None => c.door = config,
+            })
+            .await
     }
 
     /// Get the WebSocket configuration
     pub fn get_websocket_config(&self) -> WebSocketConfig {
-        self.config.websocket.clone()
+        self.inner.get().websocket
+    }
+
+    /// Get the logging configuration
+    pub fn get_logging_config(&self) -> LoggingConfig {
+        self.inner.get().logging
+    }
+
+    /// Get the WebSocket client tokens and roles
+    pub fn get_auth_config(&self) -> AuthConfig {
+        self.inner.get().auth
+    }
+
+    /// Get the WebSocket rate limits
+    pub fn get_rate_limit_config(&self) -> RateLimitConfig {
+        self.inner.get().rate_limit
+    }
+
+    /// Get the motion command limit and debounce
+    pub fn get_command_limit_config(&self) -> CommandLimitConfig {
+        self.inner.get().command_limit
+    }
+
+    /// Get the door schedule
+    pub fn get_schedule_config(&self) -> SchedulerConfig<DoorAction> {
+        self.inner.get().schedule
+    }
+
+    /// Add and persist a schedule job (rejected if its ID is taken or it doesn't validate)
+    pub async fn add_schedule_job(&self, job: Job<DoorAction>) -> Result<()> {
+        self.inner.update(|c| c.schedule.jobs.push(job)).await
+    }
+
+    /// Remove and persist a schedule job
+    pub async fn remove_schedule_job(&self, id: &str) -> Result<()> {
+        if !self.inner.get().schedule.jobs.iter().any(|job| job.id == id) {
+            anyhow::bail!("Unknown schedule job '{}'", id);
+        }
+        self.inner.update(|c| c.schedule.jobs.retain(|job| job.id != id)).await
+    }
+
+    /// Get the configured scenes
+    pub fn get_scene_config(&self) -> SceneConfig<DoorAction> {
+        self.inner.get().scene
+    }
+
+    /// Get the locale settings
+    pub fn get_i18n_config(&self) -> I18nConfig {
+        self.inner.get().i18n
+    }
+
+    /// Get the config export/import settings
+    pub fn get_backup_config(&self) -> BackupConfig {
+        self.inner.get().backup
+    }
+
+    /// Get the MQTT settings (`None` if MQTT is disabled)
+    pub fn get_mqtt_config(&self) -> Option<MqttConfig> {
+        self.inner.get().mqtt
+    }
+
+    /// Get the mDNS advertisement settings
+    pub fn get_mdns_config(&self) -> MdnsConfig {
+        self.inner.get().mdns
+    }
+
+    /// Get the HTTP API settings, if the API is enabled
+    pub fn get_http_config(&self) -> Option<HttpConfig> {
+        self.inner.get().http
+    }
+
+    /// Get the audit log settings
+    pub fn get_audit_config(&self) -> AuditConfig {
+        self.inner.get().audit
+    }
+
+    /// Get the webhooks told about door events
+    pub fn get_webhooks_config(&self) -> Vec<WebhookConfig> {
+        self.inner.get().webhooks
+    }
+
+    /// Get the overwatch service that announces door events, if any
+    pub fn get_overwatch_config(&self) -> Option<OverwatchConfig> {
+        self.inner.get().overwatch
+    }
+
+    /// The full config as an archive entry
+    pub fn export(&self) -> Result<ConfigEntry> {
+        self.inner.export("dosa", env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Replace the config with an archived one (door settings must be re-applied by the caller)
+    pub async fn import(&self, entry: &ConfigEntry) -> Result<()> {
+        self.inner.import(entry).await
+    }
+
+    /// Fingerprint of the current config, recorded in crash reports
+    pub fn hash(&self) -> String {
+        self.inner.hash()
+    }
+
+    /// Where the panic hook writes crash reports (next to the config file)
+    pub fn crash_report_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("crash-report.json")
+    }
+
+    /// Default audit log location (next to the config file)
+    pub fn audit_log_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("audit.jsonl")
+    }
+
+    /// Subscribe to config changes (API updates, imports and hand edits)
+    pub fn subscribe(&self) -> watch::Receiver<Config> {
+        self.inner.subscribe()
+    }
+
+    /// Start polling the config file for hand edits
+    pub fn watch(&self) {
+        self.inner.watch(Duration::from_secs(2));
+    }
+
+    /// Where schedule last-run times are kept (next to the config file)
+    pub fn schedule_state_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("schedule-state.json")
+    }
+
+    /// Where `backup_cnc_settings` keeps each door's grblHAL settings (next to the config file)
+    pub fn cnc_backup_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("cnc-settings.json")
+    }
+
+    /// Door positions saved at shutdown (next to the config file)
+    pub fn door_positions_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("door-positions.json")
+    }
+
+    /// Where each door last came to rest, kept up to date while running (next to the config file)
+    pub fn last_positions_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("last-positions.json")
     }
 }
@@ -0,0 +1,330 @@
+//! `dosa ctl`: command-line client for a running dosa, for shell scripts and systemd units.
+//!
+//! Speaks the WebSocket API like any other client. Output is human-readable unless `--json` is
+//! given; the exit status is 0 on success, 1 if the command failed and 2 on bad usage.
+
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::config::PRIMARY_DOOR;
+
+/// Where dosa listens unless `--url` or `DOSA_URL` says otherwise
+const DEFAULT_URL: &str = "ws://127.0.0.1:8766";
+
+/// Longest wait for dosa to accept the connection or reply to a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest `--wait` waits for the door to come to rest, unless `--timeout` is given
+const DEFAULT_WAIT_SECS: u64 = 120;
+
+/// `--wait` gives up if the door hasn't started moving by then (e.g. it isn't homed)
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often `--wait` checks the door
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const USAGE: &str = "\
+Usage: dosa ctl [options] <command>
+
+Commands:
+  status            Show the door's state and position
+  open              Open the door
+  close             Close the door
+  stop              Stop the door
+  move <percent>    Move the door to a position (0 = closed, 100 = open)
+  settings          Show the door's settings
+
+Options:
+  --url <url>       dosa's WebSocket address (default: $DOSA_URL, or ws://127.0.0.1:8766)
+  --token <token>   Auth token (default: $DOSA_TOKEN)
+  --door <id>       Door to address (default: the primary door)
+  --wait            After open, close or move, wait for the door to get there
+  --timeout <secs>  Longest --wait waits (default: 120)
+  --json            Print dosa's replies as JSON
+";
+
+/// A door command from the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Status,
+    Open,
+    Close,
+    Stop,
+    Move(f64),
+    Settings,
+}
+
+impl Command {
+    fn message(self) -> Value {
+        match self {
+            Self::Status => json!({"type": "status"}),
+            Self::Open => json!({"type": "open"}),
+            Self::Close => json!({"type": "close"}),
+            Self::Stop => json!({"type": "stop"}),
+            Self::Move(percent) => json!({"type": "move", "percent": percent}),
+            Self::Settings => json!({"type": "get_config"}),
+        }
+    }
+
+    /// Whether `door` (a `status` reply's `door`) is where this command sends it
+    fn reached(self, door: &Value) -> bool {
+        let state = door["state"].as_str().unwrap_or_default();
+        match self {
+            Self::Open => state == "open",
+            Self::Close => state == "closed",
+            Self::Move(percent) => {
+                let position = door["position_percent"].as_f64().unwrap_or(f64::NAN);
+                matches!(state, "open" | "closed" | "intermediate") && (position - percent).abs() < 0.5
+            }
+            Self::Status | Self::Stop | Self::Settings => true,
+        }
+    }
+}
+
+struct Options {
+    url: String,
+    token: Option<String>,
+    door: Option<String>,
+    json: bool,
+    /// How long to wait for the door to get there, with `--wait`
+    wait: Option<Duration>,
+    command: Command,
+}
+
+/// Run `dosa ctl` with the arguments after `ctl`, returning the exit status
+pub async fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", USAGE);
+        return 0;
+    }
+
+    let options = match parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("dosa ctl: {:#}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    match execute(&options).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("dosa ctl: {:#}", e);
+            1
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Options> {
+    let mut url = std::env::var("DOSA_URL").unwrap_or_else(|_| DEFAULT_URL.to_string());
+    let mut token = std::env::var("DOSA_TOKEN").ok().filter(|token| !token.is_empty());
+    let mut door = None;
+    let mut json = false;
+    let mut wait = false;
+    let mut wait_secs = DEFAULT_WAIT_SECS;
+    let mut words = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().with_context(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--url" => url = value()?,
+            "--token" => token = Some(value()?),
+            "--door" => door = Some(value()?),
+            "--timeout" => {
+                wait_secs = value()?
+                    .parse()
+                    .context("--timeout needs a number of seconds")?
+            }
+            "--wait" => wait = true,
+            "--json" => json = true,
+            option if option.starts_with("--") => bail!("Unknown option {}", option),
+            word => words.push(word),
+        }
+    }
+
+    let command = match words.as_slice() {
+        ["status"] => Command::Status,
+        ["open"] => Command::Open,
+        ["close"] => Command::Close,
+        ["stop"] => Command::Stop,
+        ["move", percent] => percent
+            .parse()
+            .ok()
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .map(Command::Move)
+            .with_context(|| format!("Invalid position '{}' (0 to 100)", percent))?,
+        ["settings"] => Command::Settings,
+        [] => bail!("No command given"),
+        words => bail!("Unknown command '{}'", words.join(" ")),
+    };
+
+    Ok(Options {
+        url,
+        token,
+        door,
+        json,
+        wait: wait.then(|| Duration::from_secs(wait_secs)),
+        command,
+    })
+}
+
+async fn execute(options: &Options) -> Result<()> {
+    let mut connection = Connection::open(&options.url, options.token.as_deref()).await?;
+    let reply = connection.request(options.message(options.command)).await?;
+
+    match options.command {
+        Command::Status if !options.json => println!("{}", describe_status(&reply["door"])),
+        Command::Settings if !options.json => print!("{}", serde_yaml::to_string(&reply["config"])?),
+        _ if options.json => println!("{}", reply),
+        _ => println!("{}: accepted", reply["command"].as_str().unwrap_or_default()),
+    }
+
+    if let (Some(limit), Command::Open | Command::Close | Command::Move(_)) = (options.wait, options.command) {
+        let door = wait_until_reached(&mut connection, options, limit).await?;
+        if options.json {
+            println!("{}", json!({"type": "status", "door": door}));
+        } else {
+            println!("{}", describe_status(&door));
+        }
+    }
+    Ok(())
+}
+
+/// Poll the door until it's where `options.command` sends it, failing if it stops elsewhere
+async fn wait_until_reached(connection: &mut Connection, options: &Options, limit: Duration) -> Result<Value> {
+    let started = Instant::now();
+    let mut moved = false;
+
+    loop {
+        let door = connection.request(options.message(Command::Status)).await?["door"].clone();
+        if options.command.reached(&door) {
+            return Ok(door);
+        }
+
+        match door["state"].as_str().unwrap_or_default() {
+            "opening" | "closing" | "halting" => moved = true,
+            "alarm" | "fault" | "estop" | "obstructed" | "safety_door" => {
+                bail!("Door stopped: {}", describe_status(&door))
+            }
+            _ if moved => bail!("Door stopped short: {}", describe_status(&door)),
+            _ if started.elapsed() > START_TIMEOUT => {
+                bail!("Door didn't start moving (check dosa's log): {}", describe_status(&door))
+            }
+            _ => {}
+        }
+        if started.elapsed() > limit {
+            bail!("Door didn't get there within {}s: {}", limit.as_secs(), describe_status(&door));
+        }
+        sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+impl Options {
+    /// `command`'s message, addressed to `--door`
+    fn message(&self, command: Command) -> Value {
+        let mut message = command.message();
+        if let Some(door) = &self.door {
+            message["door"] = json!(door);
+        }
+        message
+    }
+}
+
+/// One line for a `status` reply's `door`, plus a line for each alarm, fault or hold
+fn describe_status(door: &Value) -> String {
+    let mut text = format!(
+        "{}: {}, {:.1}% ({:.1} mm)",
+        door["id"].as_str().unwrap_or(PRIMARY_DOOR),
+        door["state"].as_str().unwrap_or("unknown"),
+        door["position_percent"].as_f64().unwrap_or_default(),
+        door["position_mm"].as_f64().unwrap_or_default()
+    );
+    if let Some(eta) = door["eta_secs"].as_f64() {
+        text.push_str(&format!(", {:.0}s to go", eta));
+    }
+    if let Some(alarm) = door["alarm_code"].as_str() {
+        let description = door["alarm_description"].as_str().unwrap_or_default();
+        text.push_str(&format!("\n  alarm {}: {}", alarm, description));
+    }
+    if let Some(fault) = door["fault_message"].as_str() {
+        text.push_str(&format!("\n  fault: {}", fault));
+    }
+    if let Some(hold) = door["hold"].as_str() {
+        text.push_str(&format!("\n  held open ({})", hold));
+    }
+    if let Some(secs) = door["auto_close_in_secs"].as_u64() {
+        text.push_str(&format!("\n  closing automatically in {}s", secs));
+    }
+    text
+}
+
+/// WebSocket connection to dosa
+struct Connection {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Connection {
+    async fn open(url: &str, token: Option<&str>) -> Result<Self> {
+        let mut request = url
+            .into_client_request()
+            .with_context(|| format!("Invalid URL {}", url))?;
+        if let Some(token) = token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid token")?;
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+
+        let (ws, _) = match timeout(REPLY_TIMEOUT, tokio_tungstenite::connect_async(request)).await {
+            Ok(connected) => connected.with_context(|| format!("Failed to connect to {}", url))?,
+            Err(_) => bail!("Timed out connecting to {}", url),
+        };
+        Ok(Self { ws, next_id: 1 })
+    }
+
+    /// Send `message` and return the reply to it, or its `message` as an error if dosa
+    /// refused it (`error`, `busy`, a failed `response`)
+    ///
+    /// Broadcasts that arrive in the meantime are skipped.
+    async fn request(&mut self, mut message: Value) -> Result<Value> {
+        let id = format!("ctl-{}", self.next_id);
+        self.next_id += 1;
+        message["id"] = json!(id);
+        self.ws.send(Message::Text(message.to_string())).await?;
+
+        let reply = timeout(REPLY_TIMEOUT, self.reply_to(&id))
+            .await
+            .context("Timed out waiting for a reply")??;
+        let accepted = match reply["type"].as_str() {
+            Some("status") => true,
+            Some("response") => reply["success"] == true,
+            _ => false,
+        };
+        if !accepted {
+            bail!("{}", reply["message"].as_str().unwrap_or("dosa refused the command"));
+        }
+        Ok(reply)
+    }
+
+    async fn reply_to(&mut self, id: &str) -> Result<Value> {
+        loop {
+            let frame = self.ws.next().await.context("dosa closed the connection")??;
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            let message: Value = serde_json::from_str(&text)?;
+            if message["type"] == "server_shutting_down" {
+                bail!("dosa is shutting down");
+            }
+            if message["id"] == id {
+                return Ok(message);
+            }
+        }
+    }
+}
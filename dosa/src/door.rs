@@ -1,14 +1,25 @@
 use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use shq_protocol::ComponentHealth;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tokio::sync::{broadcast, Mutex, RwLock};
-use tokio::time::{interval, Duration};
-
-use crate::cnc::CncController;
-use crate::config::DoorConfig;
-use crate::messages::{DoorState, DoorStatus};
+use tokio::time::{interval, Duration, Instant};
+
+use crate::cnc::{CncController, CncEvent, ConsoleLine};
+use crate::config::{
+    is_cnc_setting_name, is_cnc_setting_value, DoorAction, DoorConfig, MotionProfile, RestorePosition, SensorInput,
+    PRIMARY_DOOR,
+};
+use crate::messages::{CncSettingDiff, DoorState, DoorStatus, HoldState, PositionDrift};
+use crate::resume::SavedPosition;
+use crate::sensor;
+use crate::stats::{Cycle, CycleLog, CycleStats};
 
 /// Door controller that manages door state and CNC movements
 pub struct DoorController {
+    id: String, // PRIMARY_DOOR or the key under `doors`
     cnc: Arc<RwLock<Arc<CncController>>>,
     config: Arc<RwLock<DoorConfig>>,
     status: Arc<Mutex<DoorStatus>>,
@@ -17,10 +28,115 @@ pub struct DoorController {
     stop_requested: Arc<Mutex<bool>>,
     auto_home_done: Arc<Mutex<bool>>, // Tracks if auto-home has been performed
     discard_next_poll: Arc<Mutex<bool>>, // Flag to discard next status poll (set when state is updated manually)
+    hold_open: Arc<Mutex<Option<Hold>>>, // Suppresses auto-close (and scheduled closes) while set
+    estop_latched: Arc<Mutex<bool>>, // Set when the E-stop trips, cleared by reset_estop()
+    move_span: Arc<Mutex<(f64, f64)>>, // Start and target (mm) of the latest open/close/move, for progress
+    interrupted: Arc<Mutex<Option<DoorState>>>, // State the safety door interrupted, restored on resume
+    cycles: Arc<Mutex<CycleLog>>, // Recent open/close timings, for get_stats
+    last_rest: Arc<Mutex<Option<SavedPosition>>>, // Where the door last came to rest, for trust_position
     status_tx: broadcast::Sender<DoorStatus>, // Broadcasts status changes
 }
 
+/// Why the door is being kept open (`hold_open`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hold {
+    /// Auto-close is off until the door next closes
+    UntilClosed,
+    /// Held open until this instant
+    Until(Instant),
+    /// Held open until `release_hold`
+    Indefinite,
+}
+
+/// Speed steps in a soft-start ramp
+const RAMP_STEPS: u32 = 4;
+
 impl DoorController {
+    /// Split a move into `(position, feed_rate)` segments following the motion profile
+    ///
+    /// The ramp climbs from `creep_speed` to `speed` in equal steps; the creep only applies
+    /// when `to` is the end of travel (fully open or closed). Both shrink to fit short moves.
+    fn motion_segments(
+        profile: &MotionProfile,
+        from: f64,
+        to: f64,
+        speed: f64,
+        end_of_travel: bool,
+    ) -> Vec<(f64, f64)> {
+        let distance = (to - from).abs();
+        let direction = (to - from).signum();
+        let creep_speed = profile.creep_speed.min(speed);
+        let creep = if end_of_travel { profile.creep_distance.min(distance) } else { 0.0 };
+        let ramp = profile.ramp_distance.min(distance - creep);
+
+        // grblHAL doesn't need more precision than a micron
+        let round = |position: f64| (position * 1000.0).round() / 1000.0;
+
+        let mut segments = Vec::new();
+        if ramp > 0.0 {
+            for step in 0..RAMP_STEPS {
+                let feed = creep_speed + (speed - creep_speed) * f64::from(step) / f64::from(RAMP_STEPS);
+                let position = from + direction * ramp * f64::from(step + 1) / f64::from(RAMP_STEPS);
+                segments.push((round(position), feed));
+            }
+        }
+        if distance - ramp - creep > 0.0 {
+            segments.push((round(to - direction * creep), speed));
+        }
+        if creep > 0.0 || segments.is_empty() {
+            segments.push((to, if creep > 0.0 { creep_speed } else { speed }));
+        }
+        segments
+    }
+
+    /// `G92` command zeroing the door's main and sync axes
+    fn zero_command(config: &DoorConfig) -> String {
+        let words: Vec<String> = config.axes().iter().map(|(axis, _)| format!("{}0", axis)).collect();
+        format!("G92 {}", words.join(" "))
+    }
+
+    /// Send a move to `target` (mm) at `speed`, split into segments by `door.motion`
+    async fn send_move(&self, target: f64, speed: f64, end_of_travel: bool, operation_name: &str) -> Result<()> {
+        let from = self.status.lock().await.position_mm;
+        *self.move_span.lock().await = (from, target);
+        let config = self.config.read().await;
+        let axes = config.axes();
+        let segments = Self::motion_segments(&config.motion, from, target, speed, end_of_travel);
+        drop(config);
+
+        if segments.len() > 1 {
+            tracing::debug!("{} segments: {:?}", operation_name, segments);
+        }
+
+        // Send move commands with automatic reconnection on connection errors
+        let cnc = self.cnc.clone();
+        self.execute_with_reconnect(
+            move || {
+                let cnc = cnc.clone();
+                let axes = axes.clone();
+                let segments = segments.clone();
+                async move {
+                    let cnc_read = cnc.read().await;
+                    cnc_read.move_segments(&axes, &segments).await
+                }
+            },
+            operation_name,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Percent done (0-100) and mm left of a move from `from` to `to`, now at `position`
+    fn move_progress(from: f64, to: f64, position: f64) -> (f64, f64) {
+        let total = (to - from).abs();
+        let remaining = (to - position).abs().min(total);
+        if total == 0.0 {
+            return (100.0, 0.0);
+        }
+        ((total - remaining) / total * 100.0, remaining)
+    }
+
     /// Calculate position as percentage (0-100), capped at bounds
     fn calculate_position_percent(position_mm: f64, open_distance: f64) -> f64 {
         let abs_open = open_distance.abs();
@@ -29,35 +145,313 @@ impl DoorController {
         }
 
         let percent = (position_mm.abs() / abs_open) * 100.0;
-        percent.max(0.0).min(100.0)
+        percent.clamp(0.0, 100.0)
+    }
+
+    /// Create a new door controller, homed already if `saved` can be restored
+    pub async fn new(id: &str, cnc: CncController, config: DoorConfig, saved: Option<&SavedPosition>) -> Result<Self> {
+        cnc.set_read_timeout(config.timeouts.read_ms);
+        Self::push_cnc_settings(id, &cnc, &config).await;
+        Self::start_status_reports(&cnc, &config).await;
+        let home = match saved {
+            Some(saved) => Self::restore_home(id, &cnc, &config, saved).await,
+            None => None,
+        };
+        let endpoint = cnc.endpoint().map(ToString::to_string);
+        Ok(Self::with_cnc(id, Arc::new(RwLock::new(Arc::new(cnc))), endpoint, config, home))
+    }
+
+    /// Home machine position for a door restarting at `saved`, per `door.restore_position`
+    ///
+    /// The controller's work offset is set again (`G92`), like `zero` does, so moves go where
+    /// they would have before the restart. `None` leaves the door needing homing.
+    async fn restore_home(id: &str, cnc: &CncController, config: &DoorConfig, saved: &SavedPosition) -> Option<f64> {
+        if config.restore_position == RestorePosition::Off {
+            return None;
+        }
+
+        let trust = config.restore_position == RestorePosition::Trust;
+        match Self::adopt_position(cnc, config, saved, trust).await {
+            Ok(home) => {
+                tracing::info!("Door '{}' restored at {:.3} mm from the last shutdown", id, saved.position_mm);
+                Some(home)
+            }
+            Err(e) => {
+                tracing::warn!("Door '{}' not restored from the last shutdown, needs homing: {:#}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Set the controller's work offset (`G92`) so the door is at `saved`, returning the home
+    /// machine position
+    ///
+    /// Unless `trust`, the controller must still report the machine position saved and not be
+    /// in alarm; with `trust` an alarm is unlocked (`$X`) and the position taken as it is.
+    async fn adopt_position(cnc: &CncController, config: &DoorConfig, saved: &SavedPosition, trust: bool) -> Result<f64> {
+        let mut status_str = cnc.get_status().await?;
+        if CncController::parse_alarm(&status_str).0 {
+            if !trust {
+                anyhow::bail!("the controller is in alarm");
+            }
+            cnc.send_command("$X").await?;
+            status_str = cnc.get_status().await?;
+        }
+
+        let mpos = CncController::parse_position(&status_str, &config.cnc_axis)?;
+        if (mpos - saved.machine_mm).abs() >= 0.1 {
+            if !trust {
+                anyhow::bail!(
+                    "the controller reports MPos {:.3} instead of {:.3} (reset or moved since)",
+                    mpos,
+                    saved.machine_mm
+                );
+            }
+            tracing::info!(
+                "Controller reports MPos {:.3} instead of {:.3} (reset since), trusting the saved position",
+                mpos,
+                saved.machine_mm
+            );
+        }
+
+        let words: Vec<String> = config
+            .axes()
+            .iter()
+            .map(|(axis, factor)| format!("{}{}", axis, saved.position_mm * factor))
+            .collect();
+        cnc.send_command(&format!("G92 {}", words.join(" "))).await?;
+        Ok(mpos - saved.position_mm)
+    }
+
+    /// Record where the door last came to rest (`resume::track`), offered by `trust_position`
+    pub async fn set_last_rest(&self, rest: Option<SavedPosition>) {
+        *self.last_rest.lock().await = rest;
+    }
+
+    /// Take the door's last rest position as where it is, without homing
+    ///
+    /// For a door a power cut left unhomed: the controller is unlocked if in alarm and its work
+    /// offset set, as `door.restore_position: trust` does at startup. Only valid if nobody has
+    /// moved the door since; home it when in doubt.
+    pub async fn trust_position(&self) -> Result<()> {
+        let Some(rest) = self.last_rest.lock().await.clone() else {
+            return Err(anyhow::anyhow!("No last known position to trust. Home the door instead."));
+        };
+        {
+            let status = self.status.lock().await;
+            if *self.is_homed.lock().await {
+                return Err(anyhow::anyhow!("Door is already homed"));
+            }
+            if !matches!(status.state, DoorState::Pending | DoorState::Alarm) {
+                return Err(anyhow::anyhow!("Door can't take its last position while {:?}", status.state));
+            }
+        }
+
+        let config = self.config.read().await.clone();
+        let cnc = self.cnc.read().await.clone();
+        let home = Self::adopt_position(&cnc, &config, &rest, true).await?;
+        *self.home_position.lock().await = home;
+        *self.is_homed.lock().await = true;
+
+        let updated_status = {
+            let mut discard = self.discard_next_poll.lock().await;
+            *discard = true;
+            drop(discard);
+
+            let target_open = if config.open_direction.to_lowercase() == "left" {
+                -config.open_distance
+            } else {
+                config.open_distance
+            };
+            let mut status = self.status.lock().await;
+            status.position_mm = rest.position_mm;
+            status.position_percent = Self::calculate_position_percent(rest.position_mm, config.open_distance);
+            status.state = if rest.position_mm.abs() < 0.1 {
+                DoorState::Closed
+            } else if (rest.position_mm - target_open).abs() < 0.1 {
+                DoorState::Open
+            } else {
+                DoorState::Intermediate
+            };
+            set_alarm(&mut status, None);
+            status.position_drift = None;
+            status.trust_position_mm = None;
+            status.clone()
+        };
+        let _ = self.status_tx.send(updated_status);
+
+        tracing::warn!(
+            "Door '{}' trusted at its last position, {:.3} mm, without homing",
+            self.id,
+            rest.position_mm
+        );
+        Ok(())
+    }
+
+    /// Where the door is, for restoring on the next start; `None` unless homed and at rest
+    pub async fn saved_position(&self) -> Option<SavedPosition> {
+        if !*self.is_homed.lock().await {
+            return None;
+        }
+        let status = self.status.lock().await;
+        if !matches!(
+            status.state,
+            DoorState::Closed | DoorState::Open | DoorState::Intermediate | DoorState::Obstructed
+        ) {
+            return None;
+        }
+        let home = *self.home_position.lock().await;
+        Some(SavedPosition::new(status.position_mm, home + status.position_mm))
+    }
+
+    /// Switch a new connection to pushed status reports when `status_report_interval_ms` is set
+    /// Controllers without $481 keep being polled
+    async fn start_status_reports(cnc: &CncController, config: &DoorConfig) {
+        let interval_ms = config.status_report_interval_ms;
+        if interval_ms == 0 {
+            return;
+        }
+        match cnc.enable_status_reports(interval_ms).await {
+            Ok(()) => tracing::info!("grblHAL pushes status reports every {} ms", interval_ms),
+            Err(e) => tracing::warn!("Can't enable grblHAL status reports, polling instead: {:#}", e),
+        }
+    }
+
+    /// `door.cnc_settings` entries the controller doesn't match
+    ///
+    /// Numbers compare by value, since grblHAL reports `$110=6000.000` for a configured `6000`.
+    async fn cnc_settings_diff(cnc: &CncController, config: &DoorConfig) -> Result<Vec<CncSettingDiff>> {
+        if config.cnc_settings.is_empty() {
+            return Ok(Vec::new());
+        }
+        let current = cnc.query_settings().await?;
+        Ok(Self::settings_diff(&current, &config.cnc_settings))
+    }
+
+    /// Entries of `desired` that `current` doesn't match, in `desired`'s order
+    fn settings_diff<'a>(
+        current: &IndexMap<String, String>,
+        desired: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Vec<CncSettingDiff> {
+        let matches = |have: &str, want: &str| match (have.parse::<f64>(), want.parse::<f64>()) {
+            (Ok(have), Ok(want)) => (have - want).abs() < 1e-6,
+            _ => have == want,
+        };
+
+        desired
+            .into_iter()
+            .filter(|(setting, desired)| !current.get(*setting).is_some_and(|have| matches(have, desired)))
+            .map(|(setting, desired)| CncSettingDiff {
+                setting: setting.clone(),
+                desired: desired.clone(),
+                current: current.get(setting).cloned(),
+            })
+            .collect()
+    }
+
+    /// Write the `door.cnc_settings` the controller doesn't match to a new connection
+    ///
+    /// Only differing settings are written, as each write goes to the controller's EEPROM.
+    /// Failures are logged rather than refusing the connection.
+    async fn push_cnc_settings(id: &str, cnc: &CncController, config: &DoorConfig) {
+        let diffs = match Self::cnc_settings_diff(cnc, config).await {
+            Ok(diffs) => diffs,
+            Err(e) => {
+                tracing::warn!("Can't check door '{}' CNC settings: {:#}", id, e);
+                return;
+            }
+        };
+        for diff in diffs {
+            let current = diff.current.as_deref().unwrap_or("unset");
+            match cnc.set_setting(&diff.setting, &diff.desired).await {
+                Ok(()) => tracing::info!(
+                    "Door '{}' CNC setting {} changed from {} to {}",
+                    id,
+                    diff.setting,
+                    current,
+                    diff.desired
+                ),
+                Err(e) => tracing::warn!("Can't set door '{}' CNC setting {}: {:#}", id, diff.setting, e),
+            }
+        }
+    }
+
+    /// Create another door driven through this door's CNC connection (e.g. a second axis)
+    ///
+    /// Both doors see a reconnect by either; a stop or alarm clear halts the whole controller.
+    pub async fn new_shared(&self, id: &str, config: DoorConfig, saved: Option<&SavedPosition>) -> Self {
+        let cnc = self.cnc.read().await.clone();
+        cnc.set_read_timeout(config.timeouts.read_ms);
+        Self::push_cnc_settings(id, &cnc, &config).await;
+        let home = match saved {
+            Some(saved) => Self::restore_home(id, &cnc, &config, saved).await,
+            None => None,
+        };
+        let endpoint = cnc.endpoint().map(ToString::to_string);
+        Self::with_cnc(id, self.cnc.clone(), endpoint, config, home)
     }
 
-    /// Create a new door controller
-    pub async fn new(cnc: CncController, config: DoorConfig) -> Result<Self> {
+    /// `home` is the home machine position of a door that starts out homed; `endpoint` is the
+    /// connection `cnc` holds
+    fn with_cnc(
+        id: &str,
+        cnc: Arc<RwLock<Arc<CncController>>>,
+        endpoint: Option<String>,
+        config: DoorConfig,
+        home: Option<f64>,
+    ) -> Self {
         let (status_tx, _) = broadcast::channel(100);
 
         let controller = Self {
-            cnc: Arc::new(RwLock::new(Arc::new(cnc))),
+            id: id.to_string(),
+            cnc,
             config: Arc::new(RwLock::new(config)),
             status: Arc::new(Mutex::new(DoorStatus {
+                id: id.to_string(),
                 state: DoorState::Pending,
                 position_mm: 0.0,
                 position_percent: 0.0,
                 fault_message: None,
                 alarm_code: None,
+                alarm_description: None,
+                auto_close_in_secs: None,
+                safety_sensor: None,
+                progress_percent: None,
+                feed_rate: None,
+                eta_secs: None,
+                hold: None,
+                hold_remaining_secs: None,
+                position_drift: None,
+                safety_door_ready: None,
+                cnc_endpoint: endpoint,
+                interlock: None,
+                trust_position_mm: None,
             })),
-            is_homed: Arc::new(Mutex::new(false)),
-            home_position: Arc::new(Mutex::new(0.0)),
+            is_homed: Arc::new(Mutex::new(home.is_some())),
+            home_position: Arc::new(Mutex::new(home.unwrap_or(0.0))),
             stop_requested: Arc::new(Mutex::new(false)),
             auto_home_done: Arc::new(Mutex::new(false)),
             discard_next_poll: Arc::new(Mutex::new(false)),
+            hold_open: Arc::new(Mutex::new(None)),
+            estop_latched: Arc::new(Mutex::new(false)),
+            move_span: Arc::new(Mutex::new((0.0, 0.0))),
+            interrupted: Arc::new(Mutex::new(None)),
+            cycles: Arc::new(Mutex::new(CycleLog::default())),
+            last_rest: Arc::new(Mutex::new(None)),
             status_tx,
         };
 
         // Start background position monitoring
         controller.start_position_monitor();
+        controller.start_reconnect_loop();
+        controller.start_event_listener();
 
-        Ok(controller)
+        controller
+    }
+
+    /// Door id (`PRIMARY_DOOR` or the key under `doors`)
+    pub fn id(&self) -> &str {
+        &self.id
     }
 
     /// Subscribe to status updates
@@ -66,30 +460,53 @@ impl DoorController {
     }
 
     /// Create a door controller in fault state (when initialization fails)
-    pub fn new_fault(error: String, config: DoorConfig) -> Self {
+    pub fn new_fault(id: &str, error: String, config: DoorConfig) -> Self {
         let (status_tx, _) = broadcast::channel(100);
 
         let controller = Self {
+            id: id.to_string(),
             cnc: Arc::new(RwLock::new(Arc::new(CncController::dummy()))),
             config: Arc::new(RwLock::new(config)),
             status: Arc::new(Mutex::new(DoorStatus {
+                id: id.to_string(),
                 state: DoorState::Fault,
                 position_mm: 0.0,
                 position_percent: 0.0,
                 fault_message: Some(error),
                 alarm_code: None,
+                alarm_description: None,
+                auto_close_in_secs: None,
+                safety_sensor: None,
+                progress_percent: None,
+                feed_rate: None,
+                eta_secs: None,
+                hold: None,
+                hold_remaining_secs: None,
+                position_drift: None,
+                safety_door_ready: None,
+                cnc_endpoint: None,
+                interlock: None,
+                trust_position_mm: None,
             })),
             is_homed: Arc::new(Mutex::new(false)),
             home_position: Arc::new(Mutex::new(0.0)),
             stop_requested: Arc::new(Mutex::new(false)),
             auto_home_done: Arc::new(Mutex::new(false)),
             discard_next_poll: Arc::new(Mutex::new(false)),
+            hold_open: Arc::new(Mutex::new(None)),
+            estop_latched: Arc::new(Mutex::new(false)),
+            move_span: Arc::new(Mutex::new((0.0, 0.0))),
+            interrupted: Arc::new(Mutex::new(None)),
+            cycles: Arc::new(Mutex::new(CycleLog::default())),
+            last_rest: Arc::new(Mutex::new(None)),
             status_tx,
         };
 
         // Start position monitor - it will skip monitoring while in fault state
         // but will automatically activate when reconnect() clears the fault
         controller.start_position_monitor();
+        controller.start_reconnect_loop();
+        controller.start_event_listener();
 
         controller
     }
@@ -99,6 +516,7 @@ impl DoorController {
         let mut status = self.status.lock().await;
         status.state = DoorState::Fault;
         status.fault_message = Some(error.clone());
+        status.cnc_endpoint = None;
         tracing::error!("System entered fault state: {}", error);
     }
 
@@ -114,17 +532,97 @@ impl DoorController {
         *cfg = config;
         drop(cfg);
 
-        // Clear fault state and reset homing
+        self.clear_fault().await;
+        tracing::info!("System reconnected successfully - fault state cleared");
+        Ok(())
+    }
+
+    /// Leave Fault state for Pending; the door needs homing again
+    async fn clear_fault(&self) {
+        let endpoint = self.cnc.read().await.endpoint().map(ToString::to_string);
         let mut status = self.status.lock().await;
         status.state = DoorState::Pending;
         status.fault_message = None;
+        status.cnc_endpoint = endpoint;
         drop(status);
 
         let mut is_homed = self.is_homed.lock().await;
         *is_homed = false; // Reset homed state on reconnect
+    }
 
-        tracing::info!("System reconnected successfully - fault state cleared");
-        Ok(())
+    /// Start background task that retries the CNC connection every `reconnect_interval_secs`
+    /// while in Fault state, so the door recovers without a command having to fail first
+    fn start_reconnect_loop(&self) {
+        let door = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // Re-read each round so config changes apply; 0 disables retries
+                let interval = door.config.read().await.reconnect_interval_secs;
+                tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+                if interval == 0 || door.status.lock().await.state != DoorState::Fault {
+                    continue;
+                }
+
+                // A door sharing this connection may have restored it already
+                let restored = door.cnc.read().await.get_status().await.is_ok();
+                let result = if restored {
+                    door.clear_fault().await;
+                    Ok(())
+                } else {
+                    door.try_reconnect().await
+                };
+
+                match result {
+                    Ok(()) => {
+                        tracing::info!("CNC connection recovered - door is pending");
+                        let _ = door.status_tx.send(door.get_status().await);
+                    }
+                    Err(e) => tracing::debug!("CNC reconnect attempt failed: {:#}", e),
+                }
+            }
+        });
+    }
+
+    /// Start background task that acts on events the controller sends unasked, so an
+    /// `ALARM:` line between status reports shows up straight away
+    fn start_event_listener(&self) {
+        let door = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // Follow the current connection; a reconnect replaces it
+                let mut events = door.cnc.read().await.subscribe_events();
+
+                loop {
+                    match events.recv().await {
+                        Ok(CncEvent::Alarm(code)) => door.handle_cnc_alarm(code).await,
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+
+                // The connection is gone; look again once a reconnect may have replaced it
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    /// Go to Alarm on an `ALARM:` line without waiting for the status report to say so
+    async fn handle_cnc_alarm(&self, code: String) {
+        let mut st = self.status.lock().await;
+
+        // home() reports a failed homing itself, and a faulted door's state is about the connection
+        if matches!(st.state, DoorState::Homing | DoorState::Fault | DoorState::Alarm) {
+            return;
+        }
+
+        tracing::warn!("CNC Alarm detected{}", describe_alarm(Some(&code)));
+        st.state = DoorState::Alarm;
+        set_alarm(&mut st, Some(code));
+        let updated_status = st.clone();
+        drop(st);
+        let _ = self.status_tx.send(updated_status);
     }
 
     /// Attempt to reconnect to CNC controller (called on-demand when commands fail)
@@ -140,10 +638,22 @@ impl DoorController {
         // Get current config
         let config = self.config.read().await.clone();
 
-        // Try to create new CNC connection
-        let cnc = CncController::new(&config.cnc_connection)
+        // Try the connection after the one that failed first, then the others in turn
+        let endpoints = config.cnc_endpoints();
+        let failed = self.cnc.read().await.endpoint().cloned();
+        let first = match failed.and_then(|failed| endpoints.iter().position(|endpoint| *endpoint == failed)) {
+            Some(failed) => failed + 1,
+            None => 0,
+        };
+        let cnc = CncController::connect(&endpoints, first)
             .await
             .context("Failed to create new CNC connection")?;
+        if let Some(endpoint) = cnc.endpoint().filter(|endpoint| **endpoint != config.cnc_connection) {
+            tracing::warn!("Door '{}' failed over to CNC connection {}", self.id, endpoint);
+        }
+        cnc.set_read_timeout(config.timeouts.read_ms);
+        Self::push_cnc_settings(&self.id, &cnc, &config).await;
+        Self::start_status_reports(&cnc, &config).await;
 
         // Reconnect
         self.reconnect(cnc, config).await?;
@@ -221,14 +731,50 @@ impl DoorController {
         let discard_next_poll = self.discard_next_poll.clone();
         let status_tx = self.status_tx.clone();
         let auto_home_done = self.auto_home_done.clone();
+        let hold_open = self.hold_open.clone();
+        let move_span = self.move_span.clone();
+        let interrupted = self.interrupted.clone();
+        let last_rest = self.last_rest.clone();
         let door_controller = self.clone();
 
         tokio::spawn(async move {
-            let mut ticker = interval(Duration::from_millis(200));
+            let mut status_rx = status_tx.subscribe();
+            let mut last_poll = Instant::now();
+            let mut controller_busy = true; // Poll fast until a report says the controller is at rest
             let mut last_broadcast_status: Option<DoorStatus> = None;
+            let mut overload_polls = 0u32; // Consecutive polls with the motor load over the obstruction threshold
+            let mut load_field_warned = false;
+            let mut auto_close_at: Option<Instant> = None; // When the open door closes itself
+            let mut sensor_was_triggered = false;
 
             loop {
-                ticker.tick().await;
+                // Follow pushed status reports as they arrive, otherwise poll: every
+                // poll_interval_ms while anything moves, idle_poll_interval_ms at rest
+                let current = cnc.read().await.clone();
+                if current.pushes_reports() {
+                    current.wait_for_report(Duration::from_millis(200)).await;
+                } else {
+                    let (poll, idle_poll) = {
+                        let cfg = config.read().await;
+                        (cfg.poll_interval_ms, cfg.idle_poll_interval_ms)
+                    };
+                    // Skip broadcasts already seen, so only a move started from here on ends the wait
+                    while let Ok(_) | Err(TryRecvError::Lagged(_)) = status_rx.try_recv() {}
+                    let active = controller_busy || auto_close_at.is_some() || is_active(&status.lock().await.state);
+                    if active {
+                        tokio::time::sleep_until(last_poll + Duration::from_millis(poll)).await;
+                    } else {
+                        let idle_until = last_poll + Duration::from_millis(idle_poll);
+                        let _ = tokio::time::timeout_at(idle_until, wait_for_move(&mut status_rx)).await;
+                    }
+                    last_poll = Instant::now();
+                }
+                drop(current);
+
+                // A GPIO E-stop is read in every state, even while homing
+                if door_controller.watch_estop(None).await {
+                    continue;
+                }
 
                 // Skip polling during Homing (controller doesn't respond), Fault (no connection),
                 // and Halting (stop() function is actively polling)
@@ -240,6 +786,11 @@ impl DoorController {
                             // Don't poll during homing - controller doesn't respond to status queries
                             continue;
                         }
+                        DoorState::Calibrating | DoorState::Verifying => {
+                            // calibrate() and verify_closed() follow the position and watch the
+                            // limit switch themselves
+                            continue;
+                        }
                         DoorState::Fault => {
                             // Don't poll when in fault state (no valid CNC connection)
                             continue;
@@ -257,7 +808,11 @@ impl DoorController {
 
                 // Query CNC status
                 let cnc_read = cnc.read().await;
-                if let Ok(status_str) = cnc_read.get_status().await {
+                if let Ok(status_str) = cnc_read.latest_status().await {
+                    // Anything but a controller at rest (a jog, a feed hold) keeps polling fast
+                    controller_busy = CncController::parse_state(&status_str)
+                        .map_or(true, |state| !(state == "Idle" || state == "Sleep" || state.starts_with("Alarm")));
+
                     // Check discard flag first - if set, skip this poll iteration
                     let mut discard = discard_next_poll.lock().await;
                     if *discard {
@@ -268,14 +823,24 @@ impl DoorController {
                     }
                     drop(discard);
 
+                    // A grblHAL pin E-stop is read from the report just polled
+                    if door_controller.watch_estop(Some(&status_str)).await {
+                        continue;
+                    }
+
                     let cfg = config.read().await;
+                    let sensor = if cfg.safety_sensor.enabled {
+                        Some(sensor::read(&cfg.safety_sensor.input, cfg.safety_sensor.invert, &status_str).await)
+                    } else {
+                        None
+                    };
                     let homed = *is_homed.lock().await;
                     let mut st = status.lock().await;
 
                     // Re-check state after receiving response to avoid race conditions
-                    // If state changed to Homing/Fault/Halting while we were waiting for CNC response, skip processing
+                    // If state changed to Homing/Calibrating/Fault/Halting while we were waiting for CNC response, skip processing
                     match st.state {
-                        DoorState::Homing => {
+                        DoorState::Homing | DoorState::Calibrating | DoorState::Verifying => {
                             drop(st);
                             drop(cfg);
                             continue;
@@ -293,17 +858,23 @@ impl DoorController {
                         _ => {}
                     }
 
+                    // A door sharing the connection may have failed over to another controller
+                    st.cnc_endpoint = cnc_read.endpoint().map(ToString::to_string);
+
+                    // Offer a door that lost its homing (e.g. to a power cut) its last rest position
+                    st.trust_position_mm = match st.state {
+                        DoorState::Pending | DoorState::Alarm if !homed => {
+                            last_rest.lock().await.as_ref().map(|rest| rest.position_mm)
+                        }
+                        _ => None,
+                    };
+
                     // Check for alarm state
                     let (is_alarm, alarm_code) = CncController::parse_alarm(&status_str);
 
                     // Log alarm state changes
                     if is_alarm && st.state != DoorState::Alarm {
-                        let alarm_msg = if let Some(code) = &alarm_code {
-                            format!("CNC Alarm detected: Code {}", code)
-                        } else {
-                            "CNC Alarm detected".to_string()
-                        };
-                        tracing::warn!("{}", alarm_msg);
+                        tracing::warn!("CNC Alarm detected{}", describe_alarm(alarm_code.as_deref()));
                     } else if !is_alarm && st.state == DoorState::Alarm {
                         tracing::info!("CNC Alarm cleared");
                     }
@@ -311,12 +882,12 @@ impl DoorController {
                     // If alarm detected, transition to Alarm state
                     if is_alarm {
                         st.state = DoorState::Alarm;
-                        st.alarm_code = alarm_code;
+                        set_alarm(&mut st, alarm_code);
                         continue;
                     }
 
                     // Clear alarm code if no alarm
-                    st.alarm_code = None;
+                    set_alarm(&mut st, None);
 
                     // Parse position (convert to relative by default)
                     // Note: We can't call self.parse_position() from the spawned task,
@@ -335,11 +906,14 @@ impl DoorController {
                     }
 
                     // Update state based on CNC state
+                    let mut verify_close = false; // Probe for the limit switch now the door has closed
                     if let Ok(cnc_state) = CncController::parse_state(&status_str) {
                         match cnc_state.as_str() {
                             "Idle" => {
                                 // Movement complete - determine final state based on position
-                                if homed {
+                                if st.state == DoorState::Obstructed {
+                                    // Stays obstructed (also after backing off) until the next command
+                                } else if homed {
                                     let pos = st.position_mm;
                                     let prev_state = st.state.clone();
 
@@ -355,6 +929,7 @@ impl DoorController {
                                         st.state = DoorState::Closed;
                                         if prev_state == DoorState::Closing {
                                             tracing::info!("Door is in closed position");
+                                            verify_close = cfg.close_verify.enabled;
                                         }
                                     }
                                     // Check if at open position (within 0.1mm for floating point precision)
@@ -371,19 +946,133 @@ impl DoorController {
                                             tracing::info!("Door stopped at intermediate position: {} mm", pos);
                                         }
                                     }
-                                } else {
+                                } else if st.state != DoorState::Manual {
                                     st.state = DoorState::Pending;
                                 }
                             }
+                            "Run" if st.state == DoorState::SafetyDoor => {
+                                // The move the safety door interrupted carries on
+                                st.state = interrupted.lock().await.take().unwrap_or(DoorState::Intermediate);
+                            }
                             "Run" => {
                                 // Keep current state (Opening/Closing/Homing)
                             }
                             "Home" => {
-                                st.state = DoorState::Homing;
+                                // home() sets Homing itself; on a shared connection this may be
+                                // the other door homing
+                            }
+                            door if door.starts_with("Door") && st.state != DoorState::SafetyDoor => {
+                                // grblHAL's safety door input stopped the machine; it holds the
+                                // move until the door is closed and a cycle start resumes it
+                                tracing::warn!("Safety door opened while {:?}", st.state);
+                                *interrupted.lock().await = Some(st.state.clone());
+                                st.state = DoorState::SafetyDoor;
                             }
                             _ => {}
                         }
                     }
+                    st.safety_door_ready = (st.state == DoorState::SafetyDoor)
+                        .then(|| CncController::parse_safety_door(&status_str) == Some(0));
+
+                    // Progress and time left while the door is moving
+                    if matches!(st.state, DoorState::Opening | DoorState::Closing) {
+                        let (from, to) = *move_span.lock().await;
+                        let feed_rate = CncController::parse_feed_rate(&status_str);
+                        let (progress, remaining) = Self::move_progress(from, to, st.position_mm);
+                        st.progress_percent = Some((progress * 10.0).round() / 10.0);
+                        st.feed_rate = feed_rate;
+                        st.eta_secs = feed_rate
+                            .filter(|feed| *feed > 0.0)
+                            .map(|feed| (remaining / feed * 600.0).round() / 10.0);
+                    } else {
+                        st.progress_percent = None;
+                        st.feed_rate = None;
+                        st.eta_secs = None;
+                    }
+
+                    // Stop and reopen a closing door when the safety sensor trips
+                    let sensor_triggered = sensor.as_ref().is_some_and(|sensor| sensor.triggered);
+                    if sensor_triggered != sensor_was_triggered {
+                        match sensor.as_ref().and_then(|sensor| sensor.error.as_ref()) {
+                            Some(e) => tracing::warn!("Safety sensor can't be read, treating as triggered: {}", e),
+                            None if sensor_triggered => tracing::info!("Safety sensor triggered"),
+                            None => tracing::info!("Safety sensor cleared"),
+                        }
+                        sensor_was_triggered = sensor_triggered;
+                    }
+                    st.safety_sensor = sensor;
+                    let mut safety_tripped = false;
+                    if sensor_triggered && st.state == DoorState::Closing {
+                        safety_tripped = true;
+                        // Halting keeps new commands and the monitor out until the stop is done
+                        st.state = DoorState::Halting;
+                    }
+
+                    // Check motor load for obstructions while the door is moving
+                    let mut obstructed = None;
+                    let obstruction = &cfg.obstruction;
+                    if obstruction.enabled && matches!(st.state, DoorState::Opening | DoorState::Closing) {
+                        match CncController::parse_axis_field(&status_str, &obstruction.field, &cfg.cnc_axis) {
+                            Ok(load) if load > obstruction.threshold => {
+                                overload_polls += 1;
+                                if overload_polls >= obstruction.samples {
+                                    overload_polls = 0;
+                                    obstructed = Some((st.state.clone(), load));
+                                    // Halting keeps new commands and the monitor out until the stop is done
+                                    st.state = DoorState::Halting;
+                                }
+                            }
+                            Ok(_) => overload_polls = 0,
+                            Err(e) => {
+                                if !load_field_warned {
+                                    tracing::warn!("Obstruction detection can't read the motor load: {}", e);
+                                    load_field_warned = true;
+                                }
+                            }
+                        }
+                    } else {
+                        overload_polls = 0;
+                    }
+
+                    // Count down to auto-close while the door sits open
+                    let mut auto_close = false;
+                    let mut hold = hold_open.lock().await;
+                    match *hold {
+                        Some(Hold::UntilClosed) if matches!(st.state, DoorState::Closing | DoorState::Closed) => {
+                            *hold = None;
+                        }
+                        Some(Hold::Until(until)) if until <= Instant::now() => {
+                            tracing::info!("Hold-open expired");
+                            *hold = None;
+                        }
+                        _ => {}
+                    }
+                    (st.hold, st.hold_remaining_secs) = match *hold {
+                        None => (None, None),
+                        Some(Hold::UntilClosed) => (Some(HoldState::UntilClosed), None),
+                        Some(Hold::Until(until)) => {
+                            let remaining = until.saturating_duration_since(Instant::now());
+                            (Some(HoldState::Timed), Some(remaining.as_secs_f64().ceil() as u64))
+                        }
+                        Some(Hold::Indefinite) => (Some(HoldState::Indefinite), None),
+                    };
+                    // The countdown starts over once the safety sensor clears
+                    if st.state == DoorState::Open && cfg.auto_close_after_secs > 0 && hold.is_none() && !sensor_triggered {
+                        let deadline = *auto_close_at
+                            .get_or_insert_with(|| Instant::now() + Duration::from_secs(cfg.auto_close_after_secs));
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero() {
+                            auto_close_at = None;
+                            st.auto_close_in_secs = None;
+                            auto_close = true;
+                        } else {
+                            st.auto_close_in_secs = Some(remaining.as_secs_f64().ceil() as u64);
+                        }
+                    } else {
+                        auto_close_at = None;
+                        st.auto_close_in_secs = None;
+                    }
+                    drop(hold);
 
                     // Broadcast status if it changed
                     let current_status = st.clone();
@@ -400,6 +1089,37 @@ impl DoorController {
                         last_broadcast_status = Some(current_status.clone());
                     }
 
+                    if auto_close {
+                        tracing::info!("Auto-closing door after {} seconds open", cfg.auto_close_after_secs);
+                        let controller = door_controller.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = controller.close().await {
+                                tracing::error!("Auto-close failed: {}", e);
+                            }
+                        });
+                    }
+
+                    if verify_close {
+                        let controller = door_controller.clone();
+                        tokio::spawn(async move {
+                            controller.verify_closed().await;
+                        });
+                    }
+
+                    if safety_tripped {
+                        let controller = door_controller.clone();
+                        tokio::spawn(async move {
+                            controller.handle_safety_trip().await;
+                        });
+                    }
+
+                    if let Some((direction, load)) = obstructed {
+                        let controller = door_controller.clone();
+                        tokio::spawn(async move {
+                            controller.handle_obstruction(direction, load).await;
+                        });
+                    }
+
                     // Check for auto-home on first Pending state
                     if current_status.state == DoorState::Pending {
                         let mut auto_home_flag = auto_home_done.lock().await;
@@ -431,12 +1151,127 @@ impl DoorController {
         self.status.lock().await.clone()
     }
 
+    /// Recent open/close timings and their baselines
+    pub async fn cycle_stats(&self) -> CycleStats {
+        self.cycles.lock().await.stats()
+    }
+
+    /// Add a finished open/close to the timings (`stats`)
+    pub async fn record_cycle(&self, cycle: Cycle) {
+        self.cycles.lock().await.record(&self.id, cycle);
+    }
+
+    /// Component health derived from the cached door state
+    pub async fn health(&self) -> Vec<ComponentHealth> {
+        let status = self.status.lock().await.clone();
+
+        let cnc = match status.state {
+            DoorState::Fault => ComponentHealth::failed(
+                "cnc",
+                status.fault_message.unwrap_or_else(|| "Controller fault".to_string()),
+            ),
+            DoorState::Alarm => ComponentHealth::failed(
+                "cnc",
+                format!(
+                    "Controller in alarm{}",
+                    describe_alarm(status.alarm_code.as_deref())
+                ),
+            ),
+            DoorState::EStop => ComponentHealth::failed(
+                "cnc",
+                "Emergency stop tripped; release it and send reset_estop",
+            ),
+            DoorState::Obstructed => ComponentHealth::degraded(
+                "cnc",
+                "Door stopped by an obstruction; send open, close or move to resume",
+            ),
+            DoorState::Manual => ComponentHealth::degraded(
+                "cnc",
+                "Motor disabled for manual operation; send home or zero to resume",
+            ),
+            DoorState::SafetyDoor => ComponentHealth::degraded(
+                "cnc",
+                if status.safety_door_ready == Some(true) {
+                    "Stopped by the safety door; send resume to continue"
+                } else {
+                    "Stopped by the safety door; close it, then send resume"
+                },
+            ),
+            _ => ComponentHealth::ok("cnc"),
+        };
+
+        let homing = if !*self.is_homed.lock().await {
+            ComponentHealth::degraded("homing", "Door not homed; send home or zero before moving")
+        } else if let Some(drift) = &status.position_drift {
+            let reason = match drift.drift_mm {
+                Some(drift_mm) => format!("Closed position is off by {:.3} mm; send home", drift_mm),
+                None => "Limit switch not found when checking the closed position; send home".to_string(),
+            };
+            ComponentHealth::degraded("homing", reason)
+        } else {
+            ComponentHealth::ok("homing")
+        };
+
+        let mut components = vec![cnc, homing];
+        if let Some(sensor) = status.safety_sensor {
+            components.push(match sensor.error {
+                Some(e) => ComponentHealth::failed("safety_sensor", e),
+                None => ComponentHealth::ok("safety_sensor"),
+            });
+        }
+        components
+    }
+
     /// Get raw status directly from CNC controller
     pub async fn get_raw_status(&self) -> Result<String> {
         let cnc = self.cnc.read().await;
         cnc.get_status().await
     }
 
+    /// Send a command line straight to the controller and return every line of its reply
+    ///
+    /// For diagnostics (`raw_command`): grblHAL `error:` replies are returned, not turned into
+    /// errors. One line at a time; homing goes through `home()`, which tracks the cycle.
+    pub async fn raw_command(&self, command: &str) -> Result<Vec<String>> {
+        let command = command.trim();
+        if command.is_empty() || command.chars().any(|c| c.is_control()) {
+            return Err(anyhow::anyhow!("Send a single command line"));
+        }
+        let upper = command.to_uppercase();
+        if upper
+            .strip_prefix("$H")
+            .is_some_and(|axes| axes.chars().all(|axis| "XYZABC".contains(axis)))
+        {
+            return Err(anyhow::anyhow!("Use home to run a homing cycle"));
+        }
+        if self.status.lock().await.state == DoorState::Homing {
+            return Err(anyhow::anyhow!("Door is homing; the controller doesn't answer until it's done"));
+        }
+
+        tracing::info!("Raw CNC command: {}", command);
+        self.cnc.read().await.raw_command(command).await
+    }
+
+    /// Whether both doors drive the same CNC connection (`new_shared()`)
+    pub fn shares_connection(&self, other: &DoorController) -> bool {
+        Arc::ptr_eq(&self.cnc, &other.cnc)
+    }
+
+    /// What goes to and comes from the current CNC connection, from now on
+    /// The stream ends when the connection closes; a reconnect starts a new one.
+    pub async fn subscribe_console(&self) -> broadcast::Receiver<ConsoleLine> {
+        self.cnc.read().await.subscribe_console()
+    }
+
+    /// Position straight from the controller as `(mm, percent)`, fresher than the cached
+    /// status the position monitor keeps
+    pub async fn live_position(&self) -> Result<(f64, f64)> {
+        let status_str = self.cnc.read().await.latest_status().await?;
+        let position = self.parse_position(&status_str, true).await?;
+        let open_distance = self.config.read().await.open_distance;
+        Ok((position, Self::calculate_position_percent(position, open_distance)))
+    }
+
     /// Parse position from status string and optionally convert to relative position
     ///
     /// # Arguments
@@ -463,132 +1298,9 @@ impl DoorController {
         }
     }
 
-    /// Query CNC controller and update status, then return current status
-    pub async fn query_and_get_status(&self) -> Result<DoorStatus> {
-        // Query CNC controller with automatic reconnection on connection errors
-        // Wrap the entire operation in a timeout to avoid blocking WebSocket
-        let query_operation = async {
-            let cnc = self.cnc.clone();
-            let status_str = self
-                .execute_with_reconnect(
-                    move || {
-                        let cnc = cnc.clone();
-                        async move {
-                            let cnc_read = cnc.read().await;
-                            cnc_read.get_status().await
-                        }
-                    },
-                    "Status query",
-                )
-                .await?;
-
-            // Parse and update status
-            self.parse_and_update_status(&status_str).await
-        };
-
-        match tokio::time::timeout(Duration::from_secs(3), query_operation).await {
-            Ok(Ok(status)) => Ok(status),
-            Ok(Err(e)) => {
-                // Error occurred during query/reconnection
-                tracing::error!("Status query failed: {}", e);
-                let mut st = self.status.lock().await;
-                st.state = DoorState::Fault;
-                st.fault_message = Some(format!("Connection lost: {}", e));
-                Ok(st.clone())
-            }
-            Err(_) => {
-                // Timeout
-                tracing::error!("Status query timed out after 3 seconds");
-                let mut st = self.status.lock().await;
-                st.state = DoorState::Fault;
-                st.fault_message = Some("Status query timed out".to_string());
-                Ok(st.clone())
-            }
-        }
-    }
-
-    /// Parse status string and update internal status
-    async fn parse_and_update_status(&self, status_str: &str) -> Result<DoorStatus> {
-        let homed = *self.is_homed.lock().await;
-        let mut st = self.status.lock().await;
-
-        // Check for alarm state
-        let (is_alarm, alarm_code) = CncController::parse_alarm(&status_str);
-        if is_alarm && st.state != DoorState::Alarm {
-            tracing::warn!("CNC Alarm detected: Code {:?}", alarm_code);
-        } else if !is_alarm && st.state == DoorState::Alarm {
-            tracing::info!("CNC Alarm cleared");
-        }
-
-        // If alarm, set state and return
-        if is_alarm {
-            st.state = DoorState::Alarm;
-            st.alarm_code = alarm_code;
-            return Ok(st.clone());
-        }
-
-        // Clear alarm code if no alarm
-        st.alarm_code = None;
-
-        // Clear fault state if we were in fault and now successfully connected
-        if st.state == DoorState::Fault {
-            st.fault_message = None;
-            tracing::info!("Connection recovered, clearing fault state");
-        }
-
-        // Parse position (convert to relative by default)
-        drop(st); // Release lock before calling parse_position
-        let position = self.parse_position(&status_str, true).await.unwrap_or(0.0);
-
-        // Get config for state logic
-        let cfg = self.config.read().await;
-
-        let mut st = self.status.lock().await;
-        st.position_mm = position;
-        st.position_percent = Self::calculate_position_percent(position, cfg.open_distance);
-
-        // Update state based on CNC state and position
-        if let Ok(cnc_state) = CncController::parse_state(&status_str) {
-            match cnc_state.as_str() {
-                "Idle" => {
-                    if homed {
-                        let pos = st.position_mm;
-
-                        // Calculate target open position based on direction
-                        let target_open_pos = if cfg.open_direction.to_lowercase() == "left" {
-                            -cfg.open_distance
-                        } else {
-                            cfg.open_distance
-                        };
-
-                        // Check if at closed position (within 0.1mm for floating point precision)
-                        if pos.abs() < 0.1 {
-                            st.state = DoorState::Closed;
-                        }
-                        // Check if at open position (within 0.1mm for floating point precision)
-                        else if (pos - target_open_pos).abs() < 0.1 {
-                            st.state = DoorState::Open;
-                        }
-                        // Otherwise door is at an intermediate position
-                        else {
-                            st.state = DoorState::Intermediate;
-                        }
-                    } else {
-                        st.state = DoorState::Pending;
-                    }
-                }
-                "Home" => {
-                    st.state = DoorState::Homing;
-                }
-                _ => {}
-            }
-        }
-
-        Ok(st.clone())
-    }
-
     /// Update configuration
     pub async fn update_config(&self, config: DoorConfig) {
+        self.cnc.read().await.set_read_timeout(config.timeouts.read_ms);
         let mut cfg = self.config.write().await;
         *cfg = config;
     }
@@ -607,6 +1319,9 @@ impl DoorController {
             if status.state == DoorState::Homing {
                 return Ok(());
             }
+            if status.state == DoorState::Calibrating {
+                return Err(anyhow::anyhow!("Door is calibrating; send stop to end calibration first"));
+            }
         }
 
         // Always clear alarm before homing (soft reset + $X)
@@ -631,24 +1346,47 @@ impl DoorController {
         // Broadcast homing state to clients (position monitor won't broadcast during homing)
         let _ = self.status_tx.send(homing_status);
 
-        tracing::info!("Homing door on {} axis", config.cnc_axis);
+        // Sync axes home one after another, after the main axis
+        let axes: Vec<String> = config.axes().into_iter().map(|(axis, _)| axis).collect();
+        tracing::info!("Homing door on {} axis", axes.join("/"));
 
         // Send home command with automatic reconnection on connection errors
         // Note: home_axis() waits for homing to complete internally
-        let axis = config.cnc_axis.clone();
+        let home_axes = axes.clone();
+        let homing_timeout = Duration::from_secs(config.timeouts.homing_secs);
         let cnc = self.cnc.clone();
-        self.execute_with_reconnect(
-            move || {
-                let cnc = cnc.clone();
-                let axis = axis.clone();
-                async move {
-                    let cnc_read = cnc.read().await;
-                    cnc_read.home_axis(&axis).await
+        let homed = self
+            .execute_with_reconnect(
+                move || {
+                    let cnc = cnc.clone();
+                    let axes = home_axes.clone();
+                    async move {
+                        let cnc_read = cnc.read().await;
+                        let mut response = String::new();
+                        for axis in &axes {
+                            response = cnc_read.home_axis(axis, homing_timeout).await?;
+                        }
+                        Ok(response)
+                    }
+                },
+                "Home command",
+            )
+            .await;
+        if let Err(e) = homed {
+            // The monitor doesn't poll while Homing, so hand the door back to it (a timed out
+            // cycle may still be running); the door has moved and needs homing again
+            *self.is_homed.lock().await = false;
+            let pending = {
+                let mut status = self.status.lock().await;
+                if status.state != DoorState::Homing {
+                    return Err(e);
                 }
-            },
-            "Home command",
-        )
-        .await?;
+                status.state = DoorState::Pending;
+                status.clone()
+            };
+            let _ = self.status_tx.send(pending);
+            return Err(e);
+        }
 
         // grblHAL automatically backs off from the limit switch after homing
         // Configure the pulloff distance with grblHAL setting $27 (homing pulloff in mm)
@@ -656,7 +1394,7 @@ impl DoorController {
         tracing::info!("Homing complete, grblHAL pulloff handled by controller");
 
         // Reset position to zero (this is now our closed position)
-        let reset_cmd = format!("G92 {}0", config.cnc_axis);
+        let reset_cmd = Self::zero_command(&config);
         let cnc = self.cnc.clone();
         self.execute_with_reconnect(
             move || {
@@ -696,6 +1434,7 @@ impl DoorController {
             let mut status = self.status.lock().await;
             status.position_mm = 0.0;
             status.position_percent = 0.0;
+            status.position_drift = None;
             status.state = DoorState::Closed;
             status.clone()
         };
@@ -717,9 +1456,7 @@ impl DoorController {
         tracing::info!("Zeroing door at current position");
 
         // Reset position to zero (set current position as home)
-        let config = self.config.read().await;
-        let reset_cmd = format!("G92 {}0", config.cnc_axis);
-        drop(config);
+        let reset_cmd = Self::zero_command(&*self.config.read().await);
 
         // Send reset command with automatic reconnection on connection errors
         let cnc = self.cnc.clone();
@@ -757,23 +1494,440 @@ impl DoorController {
             *is_homed = true;
         }
 
-        let updated_status = {
+        let updated_status = {
+            let mut status = self.status.lock().await;
+            status.position_mm = 0.0;
+            status.position_percent = 0.0;
+            status.position_drift = None;
+            status.state = DoorState::Closed;
+            status.clone()
+        };
+
+        // Broadcast status update to all clients
+        let _ = self.status_tx.send(updated_status);
+
+        tracing::info!("Zero operation complete - current position set as home (closed)");
+        Ok(())
+    }
+
+    /// Measure the door's travel and make it the new `open_distance`
+    ///
+    /// Homes the door, then jogs toward open at `feed_rate` (default `motion.creep_speed`)
+    /// until the limit switch on the door's axis shows up in the status report's `Pn` field,
+    /// or until `stop` is sent. A limit switch hit backs the door off by the homing pull-off
+    /// (`$27`), so the switch is clear again once the door is open. `max_distance` defaults to
+    /// the axis' max travel setting (`$130` for X, `$131` for Y, ...).
+    ///
+    /// The door ends up `Open` at the measured distance, which is returned; saving it to the
+    /// config file is up to the caller.
+    pub async fn calibrate(&self, feed_rate: Option<f64>, max_distance: Option<f64>) -> Result<f64> {
+        // Calibration ends with the door fully open
+        Self::check_interlock(&*self.status.lock().await)?;
+        self.home().await?;
+
+        let config = self.config.read().await.clone();
+        let feed_rate = feed_rate.unwrap_or(config.motion.creep_speed);
+        let max_distance = match max_distance {
+            Some(distance) => distance,
+            None => self.max_travel(&config.cnc_axis).await?,
+        };
+        if feed_rate <= 0.0 || max_distance <= 0.0 {
+            return Err(anyhow::anyhow!("Calibration feed rate and distance must be positive"));
+        }
+
+        // Homing may have been skipped (already homing) or stopped; only calibrate a closed door
+        let calibrating_status = {
+            let mut status = self.status.lock().await;
+            if status.state != DoorState::Closed {
+                return Err(anyhow::anyhow!("Door must be closed to calibrate (state: {:?})", status.state));
+            }
+            status.state = DoorState::Calibrating;
+            status.clone()
+        };
+        let _ = self.status_tx.send(calibrating_status);
+
+        tracing::info!(
+            "Calibrating: jogging up to {} mm toward open at {} mm/min until the limit switch",
+            max_distance,
+            feed_rate
+        );
+
+        match self.measure_travel(&config, feed_rate, max_distance).await {
+            Ok(distance) => {
+                self.config.write().await.open_distance = distance;
+                let updated_status = {
+                    let mut status = self.status.lock().await;
+                    status.position_percent = 100.0;
+                    status.state = DoorState::Open;
+                    status.clone()
+                };
+                let _ = self.status_tx.send(updated_status);
+
+                tracing::info!("Calibration complete: open_distance is {:.3} mm", distance);
+                Ok(distance)
+            }
+            Err(e) => {
+                // An alarm or fault already set its own state
+                let failed_status = {
+                    let mut status = self.status.lock().await;
+                    if matches!(status.state, DoorState::Calibrating | DoorState::Intermediate) {
+                        status.state = DoorState::Intermediate;
+                    }
+                    status.clone()
+                };
+                let _ = self.status_tx.send(failed_status);
+                Err(e)
+            }
+        }
+    }
+
+    /// Jog toward open until the limit switch or a stop, and return the distance from home
+    async fn measure_travel(&self, config: &DoorConfig, feed_rate: f64, max_distance: f64) -> Result<f64> {
+        let axes = config.axes();
+        let limit_pin = config.cnc_axis.to_ascii_uppercase();
+        let direction = if config.open_direction.eq_ignore_ascii_case("left") { -1.0 } else { 1.0 };
+
+        let cnc = self.cnc.clone();
+        let jog_axes = axes.clone();
+        self.execute_with_reconnect(
+            move || {
+                let cnc = cnc.clone();
+                let axes = jog_axes.clone();
+                async move {
+                    let cnc_read = cnc.read().await;
+                    cnc_read.jog(&axes, direction * max_distance, feed_rate).await
+                }
+            },
+            "Calibration jog",
+        )
+        .await?;
+
+        // Leave room for the jog to get going before an Idle report means it's over
+        let started = Instant::now();
+        let mut ticker = interval(Duration::from_millis(100));
+        let limit_hit = loop {
+            ticker.tick().await;
+
+            if self.status.lock().await.state != DoorState::Calibrating {
+                break false;
+            }
+
+            let status_str = self.cnc.read().await.latest_status().await?;
+            if let Ok(position) = self.parse_position(&status_str, true).await {
+                let updated_status = {
+                    let mut status = self.status.lock().await;
+                    status.position_mm = position;
+                    status.clone()
+                };
+                let _ = self.status_tx.send(updated_status);
+            }
+
+            if CncController::parse_field(&status_str, "Pn").is_some_and(|pins| pins.contains(limit_pin.as_str())) {
+                break true;
+            }
+            if started.elapsed() > Duration::from_millis(500)
+                && CncController::parse_state(&status_str).is_ok_and(|state| state == "Idle")
+            {
+                return Err(anyhow::anyhow!("No limit switch found within {} mm", max_distance));
+            }
+        };
+
+        if limit_hit {
+            tracing::info!("Calibration: limit switch reached, backing off");
+            let cnc = self.cnc.read().await;
+            cnc.jog_cancel().await?;
+            self.wait_for_idle(&cnc).await?;
+
+            // Back off like homing does, so the switch isn't held at the open position
+            let pulloff = cnc.get_setting("$27").await.ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+            if pulloff > 0.0 {
+                cnc.jog(&axes, -direction * pulloff, feed_rate).await?;
+                self.wait_for_idle(&cnc).await?;
+            }
+        } else {
+            // stop() sent while calibrating; it leaves the door where it halted
+            self.wait_until_stopped().await;
+            let state = self.status.lock().await.state.clone();
+            if state != DoorState::Intermediate {
+                return Err(anyhow::anyhow!("Calibration interrupted (state: {:?})", state));
+            }
+            tracing::info!("Calibration: stopped by request");
+        }
+
+        let status_str = self.cnc.read().await.get_status().await?;
+        let position = self.parse_position(&status_str, true).await?;
+        {
+            let mut status = self.status.lock().await;
+            status.position_mm = position;
+            // Back to Calibrating so the monitor leaves the door alone until it's Open
+            status.state = DoorState::Calibrating;
+        }
+
+        let distance = position.abs();
+        if distance < 1.0 {
+            return Err(anyhow::anyhow!("Measured travel of {:.3} mm is too short", distance));
+        }
+        Ok(distance)
+    }
+
+    /// Max travel setting for `axis` (`$130` for X, `$131` for Y, ...)
+    async fn max_travel(&self, axis: &str) -> Result<f64> {
+        let index = ["X", "Y", "Z", "A", "B", "C"]
+            .iter()
+            .position(|a| a.eq_ignore_ascii_case(axis))
+            .with_context(|| format!("Unknown axis {}", axis))?;
+        let setting = format!("${}", 130 + index);
+        let value = self.cnc.read().await.get_setting(&setting).await?;
+        value
+            .parse::<f64>()
+            .with_context(|| format!("Invalid max travel {}={}; pass max_distance instead", setting, value))
+    }
+
+    /// Check the closed position against the limit switch (`door.close_verify`)
+    ///
+    /// Runs after a close finishes. The door creeps toward the switch, which should close the
+    /// homing pull-off (`$27`) beyond the closed position, and comes back; a switch further
+    /// out or nearer than `tolerance` sets `position_drift`. Any command that changes the
+    /// state in the meantime (open, stop, home) abandons the check.
+    async fn verify_closed(&self) {
+        let config = self.config.read().await.close_verify.clone();
+        {
+            let mut status = self.status.lock().await;
+            if status.state != DoorState::Closed {
+                return;
+            }
+            status.state = DoorState::Verifying;
+            let _ = self.status_tx.send(status.clone());
+        }
+
+        tracing::info!("Checking the closed position against the limit switch");
+        let result = self.probe_closed().await;
+
+        let mut status = self.status.lock().await;
+        if status.state != DoorState::Verifying {
+            tracing::info!("Closed position check interrupted (state: {:?})", status.state);
+            return;
+        }
+        match result {
+            Ok(drift_mm) => {
+                match drift_mm {
+                    Some(drift_mm) if drift_mm.abs() <= config.tolerance => {
+                        tracing::info!("Closed position checked: switch within {:.3} mm", drift_mm.abs());
+                        status.position_drift = None;
+                    }
+                    Some(drift_mm) => {
+                        tracing::warn!(
+                            "Position drift: the door is {:.3} mm off its closed position (tolerance {} mm); home it",
+                            drift_mm,
+                            config.tolerance
+                        );
+                        status.position_drift = Some(PositionDrift {
+                            drift_mm: Some((drift_mm * 1000.0).round() / 1000.0),
+                            tolerance_mm: config.tolerance,
+                        });
+                    }
+                    None => {
+                        tracing::warn!(
+                            "Position drift: no limit switch within {} mm of where it should be; home the door",
+                            config.search_distance
+                        );
+                        status.position_drift = Some(PositionDrift {
+                            drift_mm: None,
+                            tolerance_mm: config.tolerance,
+                        });
+                    }
+                }
+                status.state = DoorState::Closed;
+            }
+            Err(e) => {
+                tracing::error!("Closed position check failed: {}", e);
+                // The monitor settles the state from the position on its next poll
+                status.state = DoorState::Intermediate;
+            }
+        }
+        let _ = self.status_tx.send(status.clone());
+    }
+
+    /// Jog toward the limit switch and back; how far short of truly closed the door is (mm), or
+    /// `None` if the switch didn't close within `search_distance` of where it should
+    async fn probe_closed(&self) -> Result<Option<f64>> {
+        let config = self.config.read().await.clone();
+        let verify = &config.close_verify;
+        let axes = config.axes();
+        let limit_pin = config.cnc_axis.to_ascii_uppercase();
+        let direction = if config.open_direction.eq_ignore_ascii_case("left") { -1.0 } else { 1.0 };
+
+        let cnc = self.cnc.read().await.clone();
+        let pulloff = cnc.get_setting("$27").await.ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.0);
+        cnc.jog(&axes, -direction * (pulloff + verify.search_distance), verify.feed_rate)
+            .await?;
+
+        // Leave room for the jog to get going before an Idle report means it's over
+        let started = Instant::now();
+        let mut ticker = interval(Duration::from_millis(50));
+        let limit_hit = loop {
+            ticker.tick().await;
+            if self.status.lock().await.state != DoorState::Verifying {
+                return Err(anyhow::anyhow!("Interrupted"));
+            }
+
+            let status_str = cnc.latest_status().await?;
+            if CncController::parse_field(&status_str, "Pn").is_some_and(|pins| pins.contains(limit_pin.as_str())) {
+                break true;
+            }
+            if started.elapsed() > Duration::from_millis(500)
+                && CncController::parse_state(&status_str).is_ok_and(|state| state == "Idle")
+            {
+                break false;
+            }
+        };
+        if limit_hit {
+            cnc.jog_cancel().await?;
+        }
+        self.wait_for_idle(&cnc).await?;
+
+        // Distance travelled toward the switch, then back to the closed position
+        let position = self.parse_position(&cnc.get_status().await?, true).await?;
+        cnc.jog(&axes, -position, verify.feed_rate).await?;
+        self.wait_for_idle(&cnc).await?;
+
+        let travelled = -position * direction;
+        Ok(limit_hit.then_some(travelled - pulloff))
+    }
+
+    /// Poll the controller until it reports Idle (up to 5s)
+    async fn wait_for_idle(&self, cnc: &CncController) -> Result<()> {
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let status_str = cnc.get_status().await?;
+            if CncController::parse_state(&status_str).is_ok_and(|state| state == "Idle") {
+                return Ok(());
+            }
+        }
+        Err(anyhow::anyhow!("Timed out waiting for the controller to stop"))
+    }
+
+    /// Disable the motor so the door can be pushed by hand
+    ///
+    /// Sends grblHAL's `$SLP`, which de-energises the steppers until the next soft reset. The
+    /// position is lost, so the door stays `Manual` until `home` or `zero` (both reset the
+    /// controller first); motorised commands are refused meanwhile.
+    pub async fn manual_mode(&self) -> Result<()> {
+        {
+            let status = self.status.lock().await;
+            match status.state {
+                DoorState::Manual => return Ok(()),
+                DoorState::Opening
+                | DoorState::Closing
+                | DoorState::Homing
+                | DoorState::Halting
+                | DoorState::Calibrating
+                | DoorState::Verifying => {
+                    return Err(anyhow::anyhow!("Cannot enter manual mode while moving (state: {:?})", status.state));
+                }
+                DoorState::Fault => {
+                    return Err(anyhow::anyhow!(
+                        "System is in fault state: {}",
+                        status.fault_message.as_ref().unwrap_or(&"Unknown error".to_string())
+                    ));
+                }
+                DoorState::EStop => {
+                    return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+                }
+                DoorState::SafetyDoor => {
+                    return Err(anyhow::anyhow!("Safety door stopped the door. Close it and send resume first."));
+                }
+                _ => {}
+            }
+        }
+
+        tracing::info!("Disabling the motor for manual operation ($SLP)");
+        let cnc = self.cnc.clone();
+        self.execute_with_reconnect(
+            move || {
+                let cnc = cnc.clone();
+                async move {
+                    let cnc_read = cnc.read().await;
+                    cnc_read.send_command("$SLP").await
+                }
+            },
+            "Manual mode",
+        )
+        .await?;
+
+        // Nothing tracks the door while it's pushed around, so the position can't be trusted
+        *self.is_homed.lock().await = false;
+        *self.hold_open.lock().await = None;
+
+        let updated_status = {
+            let mut discard = self.discard_next_poll.lock().await;
+            *discard = true;
+            drop(discard);
+
+            let mut status = self.status.lock().await;
+            status.state = DoorState::Manual;
+            status.position_mm = 0.0;
+            status.position_percent = 0.0;
+            status.auto_close_in_secs = None;
+            status.clone()
+        };
+        let _ = self.status_tx.send(updated_status);
+
+        tracing::info!("Manual mode: push the door by hand, then home or zero it");
+        Ok(())
+    }
+
+    /// Carry on after grblHAL's safety door input stopped the machine
+    ///
+    /// grblHAL holds the interrupted move until the safety door is closed (`Door:0`), then a
+    /// cycle start resumes it; the door goes back to the state it was in. After a `stop` there
+    /// is nothing left to resume, and the controller just returns to Idle.
+    pub async fn resume(&self) -> Result<()> {
+        if self.status.lock().await.state != DoorState::SafetyDoor {
+            return Err(anyhow::anyhow!("Nothing to resume; the safety door hasn't stopped the door"));
+        }
+
+        let cnc = self.cnc.clone();
+        self.execute_with_reconnect(
+            move || {
+                let cnc = cnc.clone();
+                async move {
+                    let cnc_read = cnc.read().await;
+                    let status_str = cnc_read.get_status().await?;
+                    match CncController::parse_safety_door(&status_str) {
+                        Some(0) | None => cnc_read.cycle_start().await,
+                        Some(_) => Err(anyhow::anyhow!("Safety door is still open. Close it first.")),
+                    }
+                }
+            },
+            "Resume",
+        )
+        .await?;
+        tracing::info!("Resuming after the safety door closed");
+
+        let resumed = {
+            let mut discard = self.discard_next_poll.lock().await;
+            *discard = true;
+            drop(discard);
+
             let mut status = self.status.lock().await;
-            status.position_mm = 0.0;
-            status.position_percent = 0.0;
-            status.state = DoorState::Closed;
+            if status.state != DoorState::SafetyDoor {
+                return Ok(());
+            }
+            status.state = self.interrupted.lock().await.take().unwrap_or(DoorState::Intermediate);
+            status.safety_door_ready = None;
             status.clone()
         };
-
-        // Broadcast status update to all clients
-        let _ = self.status_tx.send(updated_status);
-
-        tracing::info!("Zero operation complete - current position set as home (closed)");
+        let _ = self.status_tx.send(resumed);
         Ok(())
     }
 
     /// Clear alarm state
     pub async fn clear_alarm(&self) -> Result<()> {
+        if *self.estop_latched.lock().await {
+            return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+        }
+
         let current_state = {
             let status = self.status.lock().await;
             status.state.clone()
@@ -837,7 +1991,7 @@ impl DoorController {
                         tracing::warn!("Alarm still present after clear attempt: {:?}", alarm_code);
                         let mut st = self.status.lock().await;
                         st.state = DoorState::Alarm;
-                        st.alarm_code = alarm_code;
+                        set_alarm(&mut st, alarm_code);
                         let updated_status = st.clone();
                         drop(st);
                         let _ = self.status_tx.send(updated_status);
@@ -854,7 +2008,7 @@ impl DoorController {
                         {
                             let mut st = self.status.lock().await;
                             st.state = DoorState::Pending;
-                            st.alarm_code = None;
+                            set_alarm(&mut st, None);
                             st.position_mm = 0.0;
                             st.position_percent = 0.0;
                             let updated_status = st.clone();
@@ -880,8 +2034,19 @@ impl DoorController {
 
     /// Open the door
     pub async fn open(&self) -> Result<()> {
+        self.open_door(true).await
+    }
+
+    /// Open the door, ignoring the interlock unless `interlock` is set
+    ///
+    /// Safety reactions (reopening for a tripped safety sensor) skip the interlock: it keeps
+    /// clients from opening the door, not the door from clearing someone in its way.
+    async fn open_door(&self, interlock: bool) -> Result<()> {
         {
             let status = self.status.lock().await;
+            if interlock {
+                Self::check_interlock(&status)?;
+            }
 
             // Check if homed
             let is_homed = *self.is_homed.lock().await;
@@ -893,7 +2058,7 @@ impl DoorController {
 
             // Only allow opening when door is Closed, Closing, or Intermediate
             match status.state {
-                DoorState::Closed | DoorState::Intermediate => {
+                DoorState::Closed | DoorState::Intermediate | DoorState::Obstructed => {
                     // Allow operation to proceed
                 }
                 DoorState::Closing => {
@@ -902,6 +2067,12 @@ impl DoorController {
                     tracing::info!("Door is closing, stopping and reversing to open");
                     self.stop().await?;
                 }
+                DoorState::Verifying => {
+                    // Checking the closed position - abandon the check and open
+                    drop(status);
+                    tracing::info!("Door is checking its closed position, stopping to open");
+                    self.stop().await?;
+                }
                 DoorState::Open => {
                     return Err(anyhow::anyhow!("Door is already open"));
                 }
@@ -914,6 +2085,9 @@ impl DoorController {
                 DoorState::Halting => {
                     return Err(anyhow::anyhow!("Door is currently halting. Wait for halt to complete."));
                 }
+                DoorState::Calibrating => {
+                    return Err(anyhow::anyhow!("Door is calibrating. Send stop to end calibration."));
+                }
                 DoorState::Pending => {
                     return Err(anyhow::anyhow!("Door is in pending state. Home the door first."));
                 }
@@ -924,12 +2098,19 @@ impl DoorController {
                     ));
                 }
                 DoorState::Alarm => {
-                    let alarm_msg = if let Some(code) = &status.alarm_code {
-                        format!("CNC is in alarm state (Code {}). Use clear_alarm command first.", code)
-                    } else {
-                        "CNC is in alarm state. Use clear_alarm command first.".to_string()
-                    };
-                    return Err(anyhow::anyhow!(alarm_msg));
+                    return Err(anyhow::anyhow!(
+                        "CNC is in alarm state{}. Use clear_alarm command first.",
+                        describe_alarm(status.alarm_code.as_deref())
+                    ));
+                }
+                DoorState::EStop => {
+                    return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+                }
+                DoorState::Manual => {
+                    return Err(anyhow::anyhow!("Door is in manual mode. Home or zero it first."));
+                }
+                DoorState::SafetyDoor => {
+                    return Err(anyhow::anyhow!("Safety door stopped the door. Close it and send resume first."));
                 }
             }
         }
@@ -937,7 +2118,6 @@ impl DoorController {
         let config = self.config.read().await;
         let open_distance = config.open_distance;
         let open_speed = config.open_speed;
-        let axis = config.cnc_axis.clone();
 
         // Calculate target position based on direction
         let target_position = if config.open_direction.to_lowercase() == "left" {
@@ -948,21 +2128,7 @@ impl DoorController {
         drop(config);
 
         tracing::info!("Opening door to {} mm at {} mm/min", target_position, open_speed);
-
-        // Send move command with automatic reconnection on connection errors
-        let cnc = self.cnc.clone();
-        self.execute_with_reconnect(
-            move || {
-                let cnc = cnc.clone();
-                let axis = axis.clone();
-                async move {
-                    let cnc_read = cnc.read().await;
-                    cnc_read.move_absolute(&axis, target_position, open_speed).await
-                }
-            },
-            "Open command",
-        )
-        .await?;
+        self.send_move(target_position, open_speed, true, "Open command").await?;
 
         // Set state to opening AFTER sending command to avoid race condition
         {
@@ -972,6 +2138,9 @@ impl DoorController {
 
             let mut status = self.status.lock().await;
             status.state = DoorState::Opening;
+
+            // Broadcast now: a short move can finish before the monitor sees it running
+            let _ = self.status_tx.send(status.clone());
         }
 
         Ok(())
@@ -990,9 +2159,13 @@ impl DoorController {
                 ));
             }
 
+            if status.safety_sensor.as_ref().is_some_and(|sensor| sensor.triggered) {
+                return Err(anyhow::anyhow!("Safety sensor is triggered, not closing the door"));
+            }
+
             // Only allow closing when door is Open, Opening, or Intermediate
             match status.state {
-                DoorState::Open | DoorState::Intermediate => {
+                DoorState::Open | DoorState::Intermediate | DoorState::Obstructed => {
                     // Allow operation to proceed
                 }
                 DoorState::Opening => {
@@ -1001,7 +2174,7 @@ impl DoorController {
                     tracing::info!("Door is opening, stopping and reversing to close");
                     self.stop().await?;
                 }
-                DoorState::Closed => {
+                DoorState::Closed | DoorState::Verifying => {
                     return Err(anyhow::anyhow!("Door is already closed"));
                 }
                 DoorState::Closing => {
@@ -1013,6 +2186,9 @@ impl DoorController {
                 DoorState::Halting => {
                     return Err(anyhow::anyhow!("Door is currently halting. Wait for halt to complete."));
                 }
+                DoorState::Calibrating => {
+                    return Err(anyhow::anyhow!("Door is calibrating. Send stop to end calibration."));
+                }
                 DoorState::Pending => {
                     return Err(anyhow::anyhow!("Door is in pending state. Home the door first."));
                 }
@@ -1023,37 +2199,27 @@ impl DoorController {
                     ));
                 }
                 DoorState::Alarm => {
-                    let alarm_msg = if let Some(code) = &status.alarm_code {
-                        format!("CNC is in alarm state (Code {}). Use clear_alarm command first.", code)
-                    } else {
-                        "CNC is in alarm state. Use clear_alarm command first.".to_string()
-                    };
-                    return Err(anyhow::anyhow!(alarm_msg));
+                    return Err(anyhow::anyhow!(
+                        "CNC is in alarm state{}. Use clear_alarm command first.",
+                        describe_alarm(status.alarm_code.as_deref())
+                    ));
+                }
+                DoorState::EStop => {
+                    return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+                }
+                DoorState::Manual => {
+                    return Err(anyhow::anyhow!("Door is in manual mode. Home or zero it first."));
+                }
+                DoorState::SafetyDoor => {
+                    return Err(anyhow::anyhow!("Safety door stopped the door. Close it and send resume first."));
                 }
             }
         }
 
-        let config = self.config.read().await;
-        let close_speed = config.close_speed;
-        let axis = config.cnc_axis.clone();
-        drop(config);
+        let close_speed = self.config.read().await.close_speed;
 
         tracing::info!("Closing door to 0 mm at {} mm/min", close_speed);
-
-        // Send move command to home position (0mm) with automatic reconnection on connection errors
-        let cnc = self.cnc.clone();
-        self.execute_with_reconnect(
-            move || {
-                let cnc = cnc.clone();
-                let axis = axis.clone();
-                async move {
-                    let cnc_read = cnc.read().await;
-                    cnc_read.move_absolute(&axis, 0.0, close_speed).await
-                }
-            },
-            "Close command",
-        )
-        .await?;
+        self.send_move(0.0, close_speed, true, "Close command").await?;
 
         // Set state to closing AFTER sending command to avoid race condition
         {
@@ -1063,19 +2229,133 @@ impl DoorController {
 
             let mut status = self.status.lock().await;
             status.state = DoorState::Closing;
+
+            // Broadcast now: a short move can finish before the monitor sees it running
+            let _ = self.status_tx.send(status.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Keep the door open, cancelling the auto-close countdown
+    ///
+    /// `Hold::UntilClosed` needs the door open or opening already; the other holds replace any
+    /// current one and leave opening the door to the caller (see [`DoorController::needs_opening`]).
+    pub async fn hold_open(&self, hold: Hold) -> Result<()> {
+        let mut status = self.status.lock().await;
+        let open = matches!(status.state, DoorState::Open | DoorState::Opening);
+        if hold == Hold::UntilClosed && !open {
+            return Err(anyhow::anyhow!("Door is not open (state: {:?})", status.state));
+        }
+        if !open {
+            Self::check_interlock(&status)?;
+        }
+
+        *self.hold_open.lock().await = Some(hold);
+        status.auto_close_in_secs = None;
+        match hold {
+            Hold::UntilClosed => tracing::info!("Holding door open"),
+            Hold::Until(until) => tracing::info!(
+                "Holding door open for {} seconds",
+                until.saturating_duration_since(Instant::now()).as_secs_f64().round()
+            ),
+            Hold::Indefinite => tracing::info!("Holding door open until released"),
+        }
+        Ok(())
+    }
+
+    /// End the current hold; the auto-close countdown starts again
+    pub async fn release_hold(&self) -> Result<()> {
+        if self.hold_open.lock().await.take().is_none() {
+            return Err(anyhow::anyhow!("Door is not being held open"));
+        }
+        tracing::info!("Released hold-open");
+        Ok(())
+    }
+
+    /// The `hold_open` in force, if any
+    pub async fn hold(&self) -> Option<Hold> {
+        *self.hold_open.lock().await
+    }
+
+    /// Whether the door has to be opened for a hold, i.e. it's neither open nor on its way
+    pub async fn needs_opening(&self) -> bool {
+        !matches!(self.status.lock().await.state, DoorState::Open | DoorState::Opening)
+    }
+
+    /// Keep the door from opening until [`DoorController::clear_interlock`]
+    ///
+    /// Open, opening moves and jogs, calibration and holds that open the door are refused while
+    /// set; closing still works. A door already opening is stopped. Setting it again replaces
+    /// the reason.
+    pub async fn set_interlock(&self, reason: &str) -> Result<()> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(anyhow::anyhow!("Interlock reason must not be empty"));
         }
 
+        let (opening, updated_status) = {
+            let mut status = self.status.lock().await;
+            if status.interlock.as_deref() == Some(reason) {
+                return Ok(());
+            }
+            status.interlock = Some(reason.to_string());
+            (status.state == DoorState::Opening, status.clone())
+        };
+        tracing::warn!("Door interlocked: {}", reason);
+        let _ = self.status_tx.send(updated_status);
+
+        if opening {
+            tracing::info!("Door is opening, stopping for the interlock");
+            if let Err(e) = self.stop().await {
+                tracing::error!("Failed to stop door for the interlock: {}", e);
+            }
+        }
         Ok(())
     }
 
+    /// Let the door open again; nothing happens if no interlock is set
+    pub async fn clear_interlock(&self) {
+        let updated_status = {
+            let mut status = self.status.lock().await;
+            let Some(reason) = status.interlock.take() else {
+                return;
+            };
+            tracing::info!("Door interlock cleared (was: {})", reason);
+            status.clone()
+        };
+        let _ = self.status_tx.send(updated_status);
+    }
+
+    /// Refuse a command that would open the door while an interlock is set
+    fn check_interlock(status: &DoorStatus) -> Result<()> {
+        match &status.interlock {
+            Some(reason) => Err(anyhow::anyhow!("Door is interlocked ({}); send clear_interlock first", reason)),
+            None => Ok(()),
+        }
+    }
+
     /// Jog the door by a relative distance in mm
     pub async fn jog(&self, distance: f64, feed_rate: Option<f64>) -> Result<()> {
+        self.jog_door(distance, feed_rate, true).await
+    }
+
+    /// Jog the door, ignoring the interlock unless `interlock` is set (see [`Self::open_door`])
+    async fn jog_door(&self, distance: f64, feed_rate: Option<f64>, interlock: bool) -> Result<()> {
         {
             let status = self.status.lock().await;
+            if interlock && distance > 0.0 {
+                Self::check_interlock(&status)?;
+            }
 
             // Check state - don't allow jogging during certain states
             match status.state {
-                DoorState::Opening | DoorState::Closing | DoorState::Homing | DoorState::Halting => {
+                DoorState::Opening
+                | DoorState::Closing
+                | DoorState::Homing
+                | DoorState::Halting
+                | DoorState::Calibrating
+                | DoorState::Verifying => {
                     return Err(anyhow::anyhow!("Cannot jog while door is moving (state: {:?})", status.state));
                 }
                 DoorState::Fault => {
@@ -1085,19 +2365,26 @@ impl DoorController {
                     ));
                 }
                 DoorState::Alarm => {
-                    let alarm_msg = if let Some(code) = &status.alarm_code {
-                        format!("CNC is in alarm state (Code {}). Use clear_alarm command first.", code)
-                    } else {
-                        "CNC is in alarm state. Use clear_alarm command first.".to_string()
-                    };
-                    return Err(anyhow::anyhow!(alarm_msg));
+                    return Err(anyhow::anyhow!(
+                        "CNC is in alarm state{}. Use clear_alarm command first.",
+                        describe_alarm(status.alarm_code.as_deref())
+                    ));
+                }
+                DoorState::EStop => {
+                    return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+                }
+                DoorState::Manual => {
+                    return Err(anyhow::anyhow!("Door is in manual mode. Home or zero it first."));
+                }
+                DoorState::SafetyDoor => {
+                    return Err(anyhow::anyhow!("Safety door stopped the door. Close it and send resume first."));
                 }
                 _ => {} // Allow jogging in any non-moving state (including when not homed)
             }
         }
 
         let config = self.config.read().await;
-        let axis = config.cnc_axis.clone();
+        let axes = config.axes();
 
         // Use provided feed rate or default to open_speed
         let jog_feed_rate = feed_rate.unwrap_or(config.open_speed);
@@ -1117,10 +2404,10 @@ impl DoorController {
         self.execute_with_reconnect(
             move || {
                 let cnc = cnc.clone();
-                let axis = axis.clone();
+                let axes = axes.clone();
                 async move {
                     let cnc_read = cnc.read().await;
-                    cnc_read.jog(&axis, jog_distance, jog_feed_rate).await
+                    cnc_read.jog(&axes, jog_distance, jog_feed_rate).await
                 }
             },
             "Jog command",
@@ -1133,12 +2420,15 @@ impl DoorController {
     /// Move to a specific percentage (0-100)
     pub async fn move_to_percent(&self, percent: f64) -> Result<()> {
         // Validate percentage
-        if percent < 0.0 || percent > 100.0 {
+        if !(0.0..=100.0).contains(&percent) {
             return Err(anyhow::anyhow!("Percentage must be between 0 and 100, got {}", percent));
         }
 
         {
             let status = self.status.lock().await;
+            if percent > status.position_percent {
+                Self::check_interlock(&status)?;
+            }
 
             // Check if homed
             let is_homed = *self.is_homed.lock().await;
@@ -1148,7 +2438,12 @@ impl DoorController {
 
             // Check if already moving - if so, ignore this command
             match status.state {
-                DoorState::Opening | DoorState::Closing | DoorState::Homing | DoorState::Halting => {
+                DoorState::Opening
+                | DoorState::Closing
+                | DoorState::Homing
+                | DoorState::Halting
+                | DoorState::Calibrating
+                | DoorState::Verifying => {
                     return Err(anyhow::anyhow!("Door is already moving (state: {:?}). Wait for current operation to complete.", status.state));
                 }
                 DoorState::Fault => {
@@ -1158,12 +2453,16 @@ impl DoorController {
                     ));
                 }
                 DoorState::Alarm => {
-                    let alarm_msg = if let Some(code) = &status.alarm_code {
-                        format!("CNC is in alarm state (Code {}). Use clear_alarm command first.", code)
-                    } else {
-                        "CNC is in alarm state. Use clear_alarm command first.".to_string()
-                    };
-                    return Err(anyhow::anyhow!(alarm_msg));
+                    return Err(anyhow::anyhow!(
+                        "CNC is in alarm state{}. Use clear_alarm command first.",
+                        describe_alarm(status.alarm_code.as_deref())
+                    ));
+                }
+                DoorState::EStop => {
+                    return Err(anyhow::anyhow!("Emergency stop is active. Release it and send reset_estop first."));
+                }
+                DoorState::SafetyDoor => {
+                    return Err(anyhow::anyhow!("Safety door stopped the door. Close it and send resume first."));
                 }
                 _ => {} // Closed, Open, Intermediate, Pending - allow movement
             }
@@ -1172,7 +2471,6 @@ impl DoorController {
         let config = self.config.read().await;
         let open_speed = config.open_speed;
         let close_speed = config.close_speed;
-        let axis = config.cnc_axis.clone();
 
         // Calculate target position
         let target_position = if config.open_direction.to_lowercase() == "left" {
@@ -1180,31 +2478,27 @@ impl DoorController {
         } else {
             config.open_distance * percent / 100.0
         };
+        drop(config);
 
         // Get current position to determine direction
-        let current_pos = self.status.lock().await.position_mm;
+        let (current_pos, sensor_triggered) = {
+            let status = self.status.lock().await;
+            (status.position_mm, status.safety_sensor.as_ref().is_some_and(|sensor| sensor.triggered))
+        };
 
         // Determine if opening or closing
         let moving_toward_open = target_position.abs() > current_pos.abs();
+        if !moving_toward_open && sensor_triggered {
+            return Err(anyhow::anyhow!("Safety sensor is triggered, not closing the door"));
+        }
         let speed = if moving_toward_open { open_speed } else { close_speed };
         let new_state = if moving_toward_open { DoorState::Opening } else { DoorState::Closing };
 
         tracing::info!("Moving to {}% (position {} mm) at {} mm/min", percent, target_position, speed);
 
-        // Send move command
-        let cnc = self.cnc.clone();
-        self.execute_with_reconnect(
-            move || {
-                let cnc = cnc.clone();
-                let axis = axis.clone();
-                async move {
-                    let cnc_read = cnc.read().await;
-                    cnc_read.move_absolute(&axis, target_position, speed).await
-                }
-            },
-            "Move to percent",
-        )
-        .await?;
+        // Only moves to fully open or closed finish with the creep
+        let end_of_travel = percent == 0.0 || percent == 100.0;
+        self.send_move(target_position, speed, end_of_travel, "Move to percent").await?;
 
         // Set state AFTER sending command to avoid race condition
         {
@@ -1214,6 +2508,9 @@ impl DoorController {
 
             let mut status = self.status.lock().await;
             status.state = new_state;
+
+            // Broadcast now: a short move can finish before the monitor sees it running
+            let _ = self.status_tx.send(status.clone());
         }
 
         Ok(())
@@ -1222,15 +2519,31 @@ impl DoorController {
     /// Stop mid-movement.
     ///
     /// This method safely decelerates the door to a stop using feed hold,
-    /// then flushes the command queue to clear any pending actions.
+    /// then flushes the command queue to clear any pending actions. A homing cycle, which
+    /// feed hold can't pause, is aborted with a soft reset instead.
     ///
     /// Blocking call.
     pub async fn stop(&self) -> Result<()> {
+        // The E-stop already reset the controller
+        if *self.estop_latched.lock().await {
+            return Ok(());
+        }
+
+        // The reset fails the `$H` still waiting for its `ok`, so home() hands the door back as
+        // pending, and grblHAL reports alarm 6 until it is cleared or homed again
+        if self.status.lock().await.state == DoorState::Homing {
+            tracing::warn!("Stop requested while homing - aborting the cycle with a soft reset");
+            return self.cnc.read().await.soft_reset().await;
+        }
+
         // Set stop flag
         let mut stop_flag = self.stop_requested.lock().await;
         *stop_flag = true;
         drop(stop_flag);
 
+        // A move the safety door interrupted is abandoned; resume then only clears the door state
+        *self.interrupted.lock().await = None;
+
         // Set state to halting AND discard next monitor poll to prevent competition
         {
             let mut discard = self.discard_next_poll.lock().await;
@@ -1309,6 +2622,11 @@ impl DoorController {
                         tracing::info!("Motor already in Idle state");
                         break;
                     }
+                    // The safety door already stopped it
+                    else if state.starts_with("Door") {
+                        tracing::info!("Motor stopped by the safety door ({})", state);
+                        break;
+                    }
                     // Hold:1 means still stopping, continue polling
                     else if state == "Hold:1" {
                         tracing::debug!("Motor still decelerating (Hold:1)");
@@ -1333,7 +2651,8 @@ impl DoorController {
             cnc.get_status().await
         };
 
-        match tokio::time::timeout(Duration::from_secs(3), status_query).await {
+        let status_timeout = Duration::from_millis(self.config.read().await.timeouts.status_ms);
+        match tokio::time::timeout(status_timeout, status_query).await {
             Ok(Ok(status_str)) => {
                 let config = self.config.read().await;
                 let homed = *self.is_homed.lock().await;
@@ -1410,39 +2729,179 @@ impl DoorController {
         Ok(())
     }
 
-    /// Wait for CNC to reach idle state
-    /// Uses longer polling intervals to avoid flooding the serial buffer during
-    /// operations like homing where the controller doesn't respond to queries
-    async fn wait_for_idle(&self) -> Result<()> {
-        let mut attempts = 0;
-        const MAX_ATTEMPTS: u32 = 60; // 60 seconds max wait
-        const POLL_INTERVAL_MS: u64 = 1000; // Poll every 1 second
+    /// Feed-hold a closing door whose safety sensor tripped, then open it again
+    async fn handle_safety_trip(&self) {
+        tracing::warn!("Safety sensor triggered while closing, stopping and reopening door");
 
-        loop {
-            tokio::time::sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
-            attempts += 1;
+        if let Err(e) = self.stop().await {
+            tracing::error!("Failed to stop door after safety sensor trip: {}", e);
+            return;
+        }
+        if let Err(e) = self.open_door(false).await {
+            tracing::error!("Failed to reopen door after safety sensor trip: {}", e);
+        }
+    }
 
-            if attempts > MAX_ATTEMPTS {
-                return Err(anyhow::anyhow!("Timeout waiting for CNC to reach idle"));
+    /// Check `door.estop` for the position monitor; true while the E-stop is latched
+    ///
+    /// `report` is `None` for the check at the top of each pass, which reads a GPIO input, and
+    /// the polled status report for the check that reads a grblHAL pin. While latched the
+    /// state is held at `EStop`, whatever a command that was running set on its way out.
+    async fn watch_estop(&self, report: Option<&str>) -> bool {
+        let mut latched = self.estop_latched.lock().await;
+        if !*latched {
+            let estop = self.config.read().await.estop.clone();
+            let polled = matches!(estop.input, SensorInput::Grbl { .. });
+            if !estop.enabled || polled != report.is_some() {
+                return false;
+            }
+            let reading = sensor::read(&estop.input, estop.invert, report.unwrap_or_default()).await;
+            if !reading.triggered {
+                return false;
             }
+            match reading.error {
+                Some(e) => tracing::error!("E-stop can't be read, stopping the controller: {}", e),
+                None => tracing::error!("E-stop tripped, stopping the controller"),
+            }
+            *latched = true;
 
-            let cnc = self.cnc.read().await;
-            if let Ok(status_str) = cnc.get_status().await {
-                if let Ok(state) = CncController::parse_state(&status_str) {
-                    if state == "Idle" {
-                        return Ok(());
-                    }
-                    // Log current state if not idle
-                    tracing::debug!("Waiting for idle, current state: {}", state);
+            // Reset straight away, without waiting for the monitor's hold on the connection
+            let cnc = self.cnc.clone();
+            tokio::spawn(async move {
+                if let Err(e) = cnc.read().await.send_realtime_command(0x18).await {
+                    tracing::error!("Failed to reset the controller for the E-stop: {}", e);
+                }
+            });
+        }
+
+        let mut status = self.status.lock().await;
+        if status.state != DoorState::EStop {
+            status.state = DoorState::EStop;
+            status.auto_close_in_secs = None;
+            let _ = self.status_tx.send(status.clone());
+        }
+        true
+    }
+
+    /// Release a latched E-stop once its input is no longer active
+    ///
+    /// The door picks up from whatever the controller reports: a reset during a move leaves
+    /// grblHAL in alarm, so the door needs `clear_alarm` or `home` next.
+    pub async fn reset_estop(&self) -> Result<()> {
+        let mut latched = self.estop_latched.lock().await;
+        if !*latched {
+            return Err(anyhow::anyhow!("Emergency stop is not active"));
+        }
+
+        let estop = self.config.read().await.estop.clone();
+        if estop.enabled {
+            let report = match estop.input {
+                SensorInput::Grbl { .. } => self.cnc.read().await.get_status().await?,
+                SensorInput::Gpio { .. } => String::new(),
+            };
+            let reading = sensor::read(&estop.input, estop.invert, &report).await;
+            if let Some(e) = reading.error {
+                return Err(anyhow::anyhow!("E-stop can't be read: {}", e));
+            }
+            if reading.triggered {
+                return Err(anyhow::anyhow!("E-stop is still active; release it first"));
+            }
+        }
+
+        *latched = false;
+        tracing::info!("E-stop reset");
+        let homed = *self.is_homed.lock().await;
+        let updated_status = {
+            let mut status = self.status.lock().await;
+            // The position monitor settles the real state on its next poll
+            status.state = if homed { DoorState::Intermediate } else { DoorState::Pending };
+            status.clone()
+        };
+        let _ = self.status_tx.send(updated_status);
+        Ok(())
+    }
+
+    /// Stop a door the position monitor found obstructed, then back it away from the obstruction
+    ///
+    /// The door halts like `stop()`, enters `Obstructed` and jogs `obstruction.back_off` mm in
+    /// the opposite direction (never past the closed or open position). It stays `Obstructed`
+    /// until the next open/close/move command.
+    async fn handle_obstruction(&self, direction: DoorState, load: f64) {
+        let config = self.config.read().await.clone();
+        tracing::warn!(
+            "Obstruction detected while {:?}: motor load {} over threshold {}, stopping door",
+            direction,
+            load,
+            config.obstruction.threshold
+        );
+
+        if let Err(e) = self.stop().await {
+            tracing::error!("Failed to stop obstructed door: {}", e);
+            return;
+        }
+
+        let (position, status) = {
+            let mut status = self.status.lock().await;
+            status.state = DoorState::Obstructed;
+            (status.position_mm.abs(), status.clone())
+        };
+        let _ = self.status_tx.send(status);
+
+        // Positive jogs move toward open
+        let (distance, speed) = if direction == DoorState::Closing {
+            let room = (config.open_distance - position).max(0.0);
+            (config.obstruction.back_off.min(room), config.open_speed)
+        } else {
+            (-config.obstruction.back_off.min(position), config.close_speed)
+        };
+
+        if distance.abs() >= 0.1 {
+            tracing::info!("Backing off {} mm from obstruction", distance.abs());
+            if let Err(e) = self.jog_door(distance, Some(speed), false).await {
+                tracing::error!("Failed to back off from obstruction: {}", e);
+            }
+        }
+    }
+
+    /// Bring the door to rest before the process exits
+    ///
+    /// A door in motion is halted with `stop()` (feed hold, wait for Hold:0, queue flush) so
+    /// grblHAL isn't left executing a move nobody is tracking. Homing can't be interrupted
+    /// safely, so it (and a halt already in progress) is given time to finish. The CNC
+    /// connection is closed afterwards.
+    pub async fn shutdown(&self) {
+        const MAX_WAIT: Duration = Duration::from_secs(30);
+
+        let state = self.status.lock().await.state.clone();
+        if matches!(
+            state,
+            DoorState::Opening | DoorState::Closing | DoorState::Calibrating | DoorState::Verifying
+        ) {
+            tracing::info!("Door is moving ({:?}), halting before shutdown", state);
+            if let Err(e) = self.stop().await {
+                tracing::error!("Failed to halt door during shutdown: {}", e);
+            }
+        }
+
+        let wait = async {
+            loop {
+                let state = self.status.lock().await.state.clone();
+                if !matches!(state, DoorState::Homing | DoorState::Halting) {
+                    break;
                 }
+                tokio::time::sleep(Duration::from_millis(100)).await;
             }
-            // If query times out or fails, just continue waiting
-            // (grblHAL doesn't respond to status queries during some operations like homing)
+        };
+
+        if tokio::time::timeout(MAX_WAIT, wait).await.is_err() {
+            tracing::warn!("Door still busy after {:?}, shutting down anyway", MAX_WAIT);
         }
+
+        self.cnc.read().await.close().await;
     }
 
     /// Query all CNC settings
-    pub async fn query_cnc_settings(&self) -> Result<indexmap::IndexMap<String, String>> {
+    pub async fn query_cnc_settings(&self) -> Result<IndexMap<String, String>> {
         let cnc = self.cnc.read().await;
         cnc.query_settings().await
     }
@@ -1458,11 +2917,148 @@ impl DoorController {
         let cnc = self.cnc.read().await;
         cnc.set_setting(setting_name, value).await
     }
+
+    /// Compare the controller's settings with `door.cnc_settings`
+    pub async fn diff_cnc_settings(&self) -> Result<Vec<CncSettingDiff>> {
+        let config = self.config.read().await.clone();
+        let cnc = self.cnc.read().await.clone();
+        Self::cnc_settings_diff(&cnc, &config).await
+    }
+
+    /// Write a `backup_cnc_settings` dump back to the controller, e.g. after swapping it
+    ///
+    /// Only settings that differ are written, in order, stopping at the first the controller
+    /// refuses; `dry_run` only compares. Returns the settings that differed.
+    pub async fn restore_cnc_settings(
+        &self,
+        settings: &IndexMap<String, String>,
+        dry_run: bool,
+    ) -> Result<Vec<CncSettingDiff>> {
+        if let Some((setting, value)) = settings
+            .iter()
+            .find(|(setting, value)| !is_cnc_setting_name(setting) || !is_cnc_setting_value(value))
+        {
+            return Err(anyhow::anyhow!("Invalid CNC setting {:?}={:?}", setting, value));
+        }
+        let state = self.status.lock().await.state.clone();
+        let moving = matches!(
+            state,
+            DoorState::Opening
+                | DoorState::Closing
+                | DoorState::Halting
+                | DoorState::Homing
+                | DoorState::Calibrating
+                | DoorState::Verifying
+        );
+        if moving && !dry_run {
+            return Err(anyhow::anyhow!("Cannot restore CNC settings while moving (state: {:?})", state));
+        }
+
+        let cnc = self.cnc.read().await.clone();
+        let differences = Self::settings_diff(&cnc.query_settings().await?, settings);
+        if dry_run {
+            return Ok(differences);
+        }
+        for (written, diff) in differences.iter().enumerate() {
+            cnc.set_setting(&diff.setting, &diff.desired).await.with_context(|| {
+                format!(
+                    "Failed to set {}={} ({} of {} settings written)",
+                    diff.setting,
+                    diff.desired,
+                    written,
+                    differences.len()
+                )
+            })?;
+            tracing::info!(
+                "Door '{}' CNC setting {} restored from {} to {}",
+                self.id,
+                diff.setting,
+                diff.current.as_deref().unwrap_or("unset"),
+                diff.desired
+            );
+        }
+        Ok(differences)
+    }
+
+    /// Run a scheduled or scene door command, returning once the door has finished moving
+    pub async fn run_action(&self, action: &DoorAction) -> Result<()> {
+        match action {
+            DoorAction::Open => self.open().await?,
+            DoorAction::Close => self.close().await?,
+            DoorAction::Move { percent } => self.move_to_percent(*percent).await?,
+            DoorAction::Home => return self.home().await,
+        }
+        self.wait_until_stopped().await;
+        Ok(())
+    }
+
+    /// Wait until the door is no longer opening, closing or halting
+    async fn wait_until_stopped(&self) {
+        let mut status_rx = self.subscribe_status();
+        while matches!(
+            self.status.lock().await.state,
+            DoorState::Opening | DoorState::Closing | DoorState::Halting
+        ) {
+            // The monitor broadcasts the end of the move; look again at least every second
+            let _ = tokio::time::timeout(Duration::from_secs(1), status_rx.recv()).await;
+        }
+    }
+}
+
+impl shq_scene::LocalActions<DoorAction> for DoorController {
+    async fn run(&self, action: &DoorAction) -> Result<()> {
+        self.run_action(action).await
+    }
+}
+
+/// Whether the door is in a state that moves it, and so wants fast polling
+fn is_active(state: &DoorState) -> bool {
+    matches!(
+        state,
+        DoorState::Opening
+            | DoorState::Closing
+            | DoorState::Homing
+            | DoorState::Halting
+            | DoorState::Calibrating
+            | DoorState::Verifying
+    )
+}
+
+/// Wait for a status broadcast showing the door on the move
+async fn wait_for_move(status_rx: &mut broadcast::Receiver<DoorStatus>) {
+    loop {
+        match status_rx.recv().await {
+            Ok(update) if is_active(&update.state) => return,
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Set or clear the alarm code, with its description
+fn set_alarm(status: &mut DoorStatus, code: Option<String>) {
+    status.alarm_description = code
+        .as_deref()
+        .and_then(CncController::alarm_description)
+        .map(str::to_string);
+    status.alarm_code = code;
+}
+
+/// " (Code 9: Homing fail: ...)" for messages about an alarm; empty if the controller gave no code
+fn describe_alarm(code: Option<&str>) -> String {
+    match code {
+        Some(code) => match CncController::alarm_description(code) {
+            Some(description) => format!(" (Code {}: {})", code, description),
+            None => format!(" (Code {})", code),
+        },
+        None => String::new(),
+    }
 }
 
 impl Clone for DoorController {
     fn clone(&self) -> Self {
         Self {
+            id: self.id.clone(),
             cnc: self.cnc.clone(),
             config: self.config.clone(),
             status: self.status.clone(),
@@ -1471,7 +3067,45 @@ impl Clone for DoorController {
             stop_requested: self.stop_requested.clone(),
             auto_home_done: self.auto_home_done.clone(),
             discard_next_poll: self.discard_next_poll.clone(),
+            hold_open: self.hold_open.clone(),
+            estop_latched: self.estop_latched.clone(),
+            move_span: self.move_span.clone(),
+            interrupted: self.interrupted.clone(),
+            cycles: self.cycles.clone(),
+            last_rest: self.last_rest.clone(),
             status_tx: self.status_tx.clone(),
         }
     }
 }
+
+/// Every door dosa drives: the primary door plus the independent ones under `doors`
+#[derive(Clone)]
+pub struct Doors {
+    primary: DoorController,
+    others: BTreeMap<String, DoorController>,
+}
+
+impl Doors {
+    pub fn new(primary: DoorController, others: Vec<DoorController>) -> Self {
+        let others = others.into_iter().map(|door| (door.id.clone(), door)).collect();
+        Self { primary, others }
+    }
+
+    /// The door configured under `door`, driven by schedules, scenes and MQTT
+    pub fn primary(&self) -> &DoorController {
+        &self.primary
+    }
+
+    /// Door `id`, or the primary door if no id is given
+    pub fn get(&self, id: Option<&str>) -> Option<&DoorController> {
+        match id {
+            None | Some(PRIMARY_DOOR) => Some(&self.primary),
+            Some(id) => self.others.get(id),
+        }
+    }
+
+    /// All doors, primary first
+    pub fn iter(&self) -> impl Iterator<Item = &DoorController> {
+        std::iter::once(&self.primary).chain(self.others.values())
+    }
+}
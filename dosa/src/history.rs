@@ -0,0 +1,54 @@
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::door::DoorController;
+use crate::messages::{DoorState, DoorStatus};
+
+/// Record the door's state transitions (including alarms and faults) in the audit log
+///
+/// Together with the door commands already audited this is the history `get_history` returns.
+/// The state at startup is recorded without a `from`.
+pub fn start(door: DoorController, audit: AuditLog) {
+    tokio::spawn(async move {
+        let mut updates = door.subscribe_status();
+        let mut last = None;
+        record_change(&audit, &door.get_status().await, &mut last);
+
+        loop {
+            match updates.recv().await {
+                Ok(status) => record_change(&audit, &status, &mut last),
+                Err(RecvError::Lagged(_)) => record_change(&audit, &door.get_status().await, &mut last),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Record a `state_changed` event if the state differs from the last one recorded
+fn record_change(audit: &AuditLog, status: &DoorStatus, last: &mut Option<DoorState>) {
+    if last.as_ref() == Some(&status.state) {
+        return;
+    }
+
+    let mut detail = json!({
+        "door": status.id,
+        "to": status.state,
+        "position_percent": (status.position_percent * 10.0).round() / 10.0,
+    });
+    if let Some(from) = last {
+        detail["from"] = json!(from);
+    }
+    if let Some(code) = &status.alarm_code {
+        detail["alarm_code"] = json!(code);
+    }
+    if let Some(description) = &status.alarm_description {
+        detail["alarm_description"] = json!(description);
+    }
+    if let Some(message) = &status.fault_message {
+        detail["fault_message"] = json!(message);
+    }
+
+    audit.record(AuditEvent::new(AuditKind::State, "state_changed").detail(detail));
+    *last = Some(status.state.clone());
+}
@@ -0,0 +1,353 @@
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use shq_audit::AuditLog;
+use shq_auth::Identity;
+use shq_i18n::Translator;
+use shq_ratelimit::RateLimiter;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::Instrument;
+
+use crate::config::ConfigManager;
+use crate::door::{DoorController, Doors};
+use crate::messages::{ClientMessage, ServerMessage};
+use crate::websocket::MotionGate;
+
+/// Everything the HTTP endpoints share with the WebSocket API
+pub struct HttpApi {
+    pub doors: Doors,
+    pub config_manager: ConfigManager,
+    pub tr: Translator,
+    pub audit: AuditLog,
+    /// Same gate as the WebSocket handler, so HTTP and WebSocket clients can't fight over a door
+    /// or get around the command limit by switching API
+    pub motion_gate: Arc<MotionGate>,
+    pub rate_limiter: RateLimiter,
+}
+
+/// Query parameters every endpoint accepts
+#[derive(Debug, Default, Deserialize)]
+struct Params {
+    /// Door to address (the primary door if absent)
+    door: Option<String>,
+    /// Auth token, for clients that can't set an `Authorization` header
+    token: Option<String>,
+}
+
+/// Body of `POST /move`
+#[derive(Debug, Deserialize)]
+struct MoveBody {
+    percent: f64,
+}
+
+/// Body of `POST /interlock`
+#[derive(Debug, Deserialize)]
+struct InterlockBody {
+    reason: String,
+}
+
+/// REST endpoints mapping onto the door commands, on their own port
+pub struct HttpServer {
+    listener: TcpListener,
+    router: Router,
+}
+
+impl HttpServer {
+    /// Bind the listener; requests are served once [`HttpServer::run`] is called
+    pub async fn bind(addr: SocketAddr, api: HttpApi) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind HTTP API to {}", addr))?;
+        tracing::info!("HTTP API listening on http://{}", addr);
+
+        let router = Router::new()
+            .route("/status", get(status))
+            .route("/open", post(open))
+            .route("/close", post(close))
+            .route("/move", post(move_to))
+            .route("/stop", post(stop))
+            .route("/interlock", post(set_interlock).delete(clear_interlock))
+            .with_state(Arc::new(api));
+        Ok(Self { listener, router })
+    }
+
+    /// Serve requests until `shutdown` completes
+    pub async fn run(self, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
+        axum::serve(
+            self.listener,
+            self.router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+        Ok(())
+    }
+}
+
+async fn status(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, ClientMessage::Status).await
+}
+
+async fn open(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, ClientMessage::Open).await
+}
+
+async fn close(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, ClientMessage::Close).await
+}
+
+async fn stop(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, ClientMessage::Stop).await
+}
+
+/// `POST /move` with `{"percent": 50}`; the body is read as JSON whatever its content type, so
+/// `curl -d` works as is
+async fn move_to(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match serde_json::from_slice::<MoveBody>(&body) {
+        Ok(MoveBody { percent }) => {
+            api.handle(addr, &headers, params, ClientMessage::Move { percent })
+                .await
+        }
+        Err(e) => api
+            .error(StatusCode::BAD_REQUEST, "error.invalid_command", &[("error", &e)])
+            .into_response(),
+    }
+}
+
+/// `POST /interlock` with `{"reason": "high wind"}`, e.g. from a weather station's webhook;
+/// read as JSON whatever its content type, like `POST /move`
+async fn set_interlock(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match serde_json::from_slice::<InterlockBody>(&body) {
+        Ok(InterlockBody { reason }) => {
+            api.handle(addr, &headers, params, ClientMessage::SetInterlock { reason })
+                .await
+        }
+        Err(e) => api
+            .error(StatusCode::BAD_REQUEST, "error.invalid_command", &[("error", &e)])
+            .into_response(),
+    }
+}
+
+async fn clear_interlock(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, ClientMessage::ClearInterlock).await
+}
+
+impl HttpApi {
+    /// Handle one request in its own command span, so its log lines share a correlation ID
+    async fn handle(&self, addr: SocketAddr, headers: &HeaderMap, params: Params, message: ClientMessage) -> Response {
+        let span = shq_logging::command_span(command_name(&message));
+        span.record("client", tracing::field::display(addr));
+        self.check_and_run(addr, headers, params, message).instrument(span).await
+    }
+
+    /// Run one request through the same checks as a WebSocket command: rate limit, token, role,
+    /// door and arbitration, with privileged commands recorded in the audit log
+    async fn check_and_run(
+        &self,
+        addr: SocketAddr,
+        headers: &HeaderMap,
+        params: Params,
+        message: ClientMessage,
+    ) -> Response {
+        if let Err(limited) = self.rate_limiter.check(addr.ip()) {
+            let mut response = self
+                .error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "error.rate_limited",
+                    &[("retry_after_ms", &limited.retry_after_ms())],
+                )
+                .into_response();
+            let retry_after = limited.retry_after_ms().div_ceil(1000).to_string();
+            if let Ok(value) = retry_after.parse() {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+            return response;
+        }
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string())
+            .or(params.token);
+        let identity = match self.config_manager.get_auth_config().authenticate(token.as_deref()) {
+            Ok(identity) => identity,
+            Err(e) => {
+                tracing::warn!("Refused HTTP request from {}: {}", addr, e);
+                let message = ServerMessage::Error { message: e.to_string() };
+                return (StatusCode::UNAUTHORIZED, Json(message)).into_response();
+            }
+        };
+
+        let Some(event) = message.audit_event(params.door.as_deref()) else {
+            return self.run(&identity, addr, params.door, message).await.into_response();
+        };
+        let event = event.client(identity.label(addr.ip()));
+        let (status, Json(reply)) = self.run(&identity, addr, params.door, message).await;
+        self.audit.record(match &reply {
+            ServerMessage::Error { message }
+            | ServerMessage::Busy { message, .. }
+            | ServerMessage::CommandRateLimited { message, .. } => event.failed(message),
+            _ => event,
+        });
+        (status, Json(reply)).into_response()
+    }
+
+    /// Check the role, find the door and claim it, then start the command
+    ///
+    /// Every request is a new connection, so clients are told apart by token and IP address
+    /// only; a script can then reverse its own command with its next request.
+    async fn run(
+        &self,
+        identity: &Identity,
+        addr: SocketAddr,
+        door: Option<String>,
+        message: ClientMessage,
+    ) -> (StatusCode, Json<ServerMessage>) {
+        let role = message.required_role();
+        if identity.require(role).is_err() {
+            tracing::warn!(
+                "Refused HTTP command from {}: requires the {} role",
+                identity.label(addr.ip()),
+                role
+            );
+            return self.error(StatusCode::FORBIDDEN, "error.forbidden", &[("role", &role)]);
+        }
+
+        let Some(controller) = self.doors.get(door.as_deref()) else {
+            return self.error(
+                StatusCode::NOT_FOUND,
+                "error.unknown_door",
+                &[("door", &door.unwrap_or_default())],
+            );
+        };
+        let label = identity.label(addr.ip());
+        match self.motion_gate.admit(controller, label, addr.ip(), &message).await {
+            None => {}
+            Some(reply @ (ServerMessage::Busy { .. } | ServerMessage::Error { .. })) => {
+                return (StatusCode::CONFLICT, Json(reply));
+            }
+            Some(reply @ ServerMessage::CommandRateLimited { .. }) => {
+                return (StatusCode::TOO_MANY_REQUESTS, Json(reply));
+            }
+            Some(reply) => return (StatusCode::ACCEPTED, Json(reply)),
+        }
+
+        let command = command_name(&message);
+        let done = ServerMessage::Response {
+            success: true,
+            command: command.to_string(),
+            config: None,
+        };
+        match message {
+            ClientMessage::Status => {
+                let status = ServerMessage::Status {
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    door: controller.get_status().await,
+                };
+                return (StatusCode::OK, Json(status));
+            }
+            ClientMessage::SetInterlock { reason } => {
+                return match controller.set_interlock(&reason).await {
+                    Ok(()) => (StatusCode::OK, Json(done)),
+                    Err(e) => self.error(StatusCode::BAD_REQUEST, "error.interlock_failed", &[("error", &e)]),
+                };
+            }
+            ClientMessage::ClearInterlock => {
+                controller.clear_interlock().await;
+                return (StatusCode::OK, Json(done));
+            }
+            _ => {}
+        }
+
+        // Like the WebSocket commands, motion runs in the background and the reply comes at once
+        let door = controller.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = run_motion(&door, message).await {
+                    tracing::error!("HTTP {} failed: {}", command, e);
+                }
+            }
+            .in_current_span(),
+        );
+
+        (StatusCode::ACCEPTED, Json(done))
+    }
+
+    /// An `error` reply with a translated message
+    fn error(&self, status: StatusCode, key: &str, args: &[(&str, &dyn Display)]) -> (StatusCode, Json<ServerMessage>) {
+        let message = ServerMessage::Error {
+            message: self.tr.t(key, args),
+        };
+        (status, Json(message))
+    }
+}
+
+/// Name of a command with an HTTP route, as in WebSocket `response` messages
+fn command_name(message: &ClientMessage) -> &'static str {
+    match message {
+        ClientMessage::Status => "status",
+        ClientMessage::Open => "open",
+        ClientMessage::Close => "close",
+        ClientMessage::Move { .. } => "move",
+        ClientMessage::Stop => "stop",
+        ClientMessage::SetInterlock { .. } => "set_interlock",
+        ClientMessage::ClearInterlock => "clear_interlock",
+        _ => unreachable!("no HTTP route for this command"),
+    }
+}
+
+async fn run_motion(door: &DoorController, message: ClientMessage) -> Result<()> {
+    match message {
+        ClientMessage::Open => door.open().await,
+        ClientMessage::Close => door.close().await,
+        ClientMessage::Move { percent } => door.move_to_percent(percent).await,
+        ClientMessage::Stop => door.stop().await,
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,71 @@
+use shq_ratelimit::{RateLimited, RateLimiter};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{CommandLimitConfig, ConfigManager};
+use crate::messages::ClientMessage;
+
+/// Why a motion command isn't run
+pub enum Limited {
+    /// Identical to the door's last command within the debounce window; acknowledged anyway
+    Debounced,
+    /// The client sent too many motion commands
+    RateLimited(RateLimited),
+}
+
+/// Keeps a stuck panel button from flooding the controller (`command_limit`)
+///
+/// Repeats of the door's last motion command less than `debounce_ms` apart are dropped,
+/// whichever client sent them; past that, each client IP gets its own token bucket for motion
+/// commands.
+pub struct CommandLimiter {
+    config_manager: ConfigManager,
+    limiter: RateLimiter,
+    /// Config the limiter was last given, so edits apply without a restart
+    applied: Mutex<CommandLimitConfig>,
+    /// Debounce key (if any) and time of each door's last accepted motion command
+    last: Mutex<HashMap<String, (Option<String>, Instant)>>,
+}
+
+impl CommandLimiter {
+    pub fn new(config_manager: ConfigManager) -> Self {
+        let config = config_manager.get_command_limit_config();
+        Self {
+            limiter: RateLimiter::new(config.rate_limit()),
+            applied: Mutex::new(config),
+            config_manager,
+            last: Mutex::default(),
+        }
+    }
+
+    /// Check a motion command from `client` for `door`; `stop` always passes
+    pub fn check(&self, client: IpAddr, door: &str, message: &ClientMessage) -> Result<(), Limited> {
+        let config = self.config_manager.get_command_limit_config();
+        {
+            let mut applied = self.applied.lock().unwrap();
+            if *applied != config {
+                self.limiter.update(config.rate_limit());
+                *applied = config.clone();
+            }
+        }
+
+        // Any other command in between (a stop, a jog) ends the debounce, so open, stop, open
+        // still opens twice
+        let key = message.debounce_key();
+        let mut last = self.last.lock().unwrap();
+        // Each repeat restarts the window, so a button that keeps sending never gets through
+        if let (Some(key), Some((previous, at))) = (&key, last.get_mut(door)) {
+            if previous.as_ref() == Some(key) && at.elapsed() < Duration::from_millis(config.debounce_ms) {
+                *at = Instant::now();
+                return Err(Limited::Debounced);
+            }
+        }
+        if !matches!(message, ClientMessage::Stop) {
+            self.limiter.check(client).map_err(Limited::RateLimited)?;
+        }
+        last.insert(door.to_string(), (key, Instant::now()));
+        Ok(())
+    }
+}
@@ -1,21 +1,44 @@
+mod arbiter;
 mod cnc;
+mod cnc_backup;
 mod config;
+mod ctl;
 mod door;
+mod history;
+mod limit;
+mod http;
 mod messages;
+mod mqtt;
+mod overwatch;
+mod resume;
+mod schedule;
+mod sensor;
+mod stats;
+mod webhook;
 mod websocket;
 
 use anyhow::Result;
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_i18n::Translator;
+use shq_logging::LogFormat;
+use shq_mdns::Advertisement;
+use shq_ratelimit::RateLimiter;
+use shq_ws::Broadcaster;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::signal;
+use tokio::sync::oneshot;
 
 use cnc::CncController;
-use config::ConfigManager;
-use door::DoorController;
-use websocket::WebSocketServer;
+use config::{ConfigManager, PRIMARY_DOOR};
+use door::{DoorController, Doors};
+use http::{HttpApi, HttpServer};
+use messages::{DoorState, LOCALES, PROTOCOL_VERSION};
+use resume::SavedPositions;
+use websocket::{MotionGate, WebSocketHandler};
 
 /// Initialize the door controller using existing config manager
-async fn initialize_door(config_manager: &ConfigManager) -> Result<DoorController> {
+async fn initialize_door(config_manager: &ConfigManager, saved: &SavedPositions) -> Result<DoorController> {
     let door_config = config_manager.get_door_config();
 
     tracing::info!("Door configuration:");
@@ -27,34 +50,96 @@ async fn initialize_door(config_manager: &ConfigManager) -> Result<DoorControlle
     tracing::info!("  (Homing pulloff configured via grblHAL $27)");
 
     // Initialize CNC controller
-    let cnc = CncController::new(&door_config.cnc_connection).await?;
+    let cnc = CncController::connect(&door_config.cnc_endpoints(), 0).await?;
     tracing::info!("Connected to CNC controller");
 
     // Initialize door controller
-    let door = DoorController::new(cnc, door_config).await?;
+    let door = DoorController::new(PRIMARY_DOOR, cnc, door_config, saved.get(PRIMARY_DOOR)).await?;
     tracing::info!("Door controller initialized");
 
     Ok(door)
 }
 
+/// Initialize the doors under `doors`; doors on the primary door's connection share it
+async fn initialize_other_doors(
+    config_manager: &ConfigManager,
+    primary: &DoorController,
+    saved: &SavedPositions,
+) -> Vec<DoorController> {
+    let primary_endpoints = config_manager.get_door_config().cnc_endpoints();
+    let mut doors = Vec::new();
+
+    for (id, door_config) in config_manager.get_doors_config() {
+        tracing::info!(
+            "Door '{}': {} axis, {} mm, opens {}",
+            id,
+            door_config.cnc_axis,
+            door_config.open_distance,
+            door_config.open_direction
+        );
+
+        if door_config.cnc_endpoints() == primary_endpoints {
+            let door = if primary.get_status().await.state == DoorState::Fault {
+                let error = "The primary door's CNC connection failed".to_string();
+                DoorController::new_fault(&id, error, door_config)
+            } else {
+                primary.new_shared(&id, door_config, saved.get(&id)).await
+            };
+            doors.push(door);
+            continue;
+        }
+
+        let door = match CncController::connect(&door_config.cnc_endpoints(), 0).await {
+            Ok(cnc) => DoorController::new(&id, cnc, door_config.clone(), saved.get(&id)).await,
+            Err(e) => Err(e),
+        };
+        doors.push(door.unwrap_or_else(|e| {
+            tracing::error!("Door '{}' initialization failed: {:?}", id, e);
+            DoorController::new_fault(&id, format!("{:?}", e), door_config)
+        }));
+    }
+
+    doors
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "dosa=info".into()),
-        )
-        .init();
+    // `dosa ctl ...` is a client for a running dosa rather than the service itself
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|arg| arg == "ctl") {
+        std::process::exit(ctl::run(&args[2..]).await);
+    }
+
+    // Initialize tracing (file logging is enabled once config is loaded)
+    let log_handle = shq_logging::init("dosa=info", LogFormat::from_args()?);
 
     tracing::info!("Starting DOSA (Door Opening Sensor Automation) v{}", env!("CARGO_PKG_VERSION"));
 
     // Load configuration
     let config_manager = ConfigManager::new().await?;
     let ws_config = config_manager.get_websocket_config();
+    let mdns_config = config_manager.get_mdns_config();
+
+    if let Err(e) = log_handle.apply(&config_manager.get_logging_config()) {
+        tracing::warn!("File logging disabled: {:#}", e);
+    }
+
+    let crash_config = config_manager.clone();
+    log_handle.install_panic_hook(
+        "dosa",
+        env!("CARGO_PKG_VERSION"),
+        config_manager.crash_report_path(),
+        move || crash_config.hash(),
+    );
+
+    // Security audit trail: connections, privileged commands, config and door state changes
+    let audit = AuditLog::open("dosa", &config_manager.get_audit_config(), config_manager.audit_log_path());
+    audit.record(
+        AuditEvent::new(AuditKind::Service, "started").detail(json!({ "version": env!("CARGO_PKG_VERSION") })),
+    );
+    audit.watch_config(config_manager.subscribe());
 
     // Parse command-line arguments (can override config values)
-    let args: Vec<String> = std::env::args().collect();
     let host = args
         .iter()
         .position(|arg| arg == "--host")
@@ -71,8 +156,12 @@ async fn main() -> Result<()> {
 
     let addr: SocketAddr = format!("{}:{}", host, port).parse()?;
 
+    // Positions saved by the last shutdown, so doors left at rest needn't home again
+    let positions_path = config_manager.door_positions_path();
+    let saved = resume::take(&positions_path).await;
+
     // Try to initialize the door - if any error occurs, continue in fault state
-    let door = match initialize_door(&config_manager).await {
+    let door = match initialize_door(&config_manager, &saved).await {
         Ok(door) => {
             tracing::info!("System initialized successfully");
             door
@@ -81,34 +170,175 @@ async fn main() -> Result<()> {
             tracing::error!("System initialization failed: {:?}", e);
             tracing::warn!("Starting in FAULT state - WebSocket API available for status");
             let door_config = config_manager.get_door_config();
-            DoorController::new_fault(format!("{:?}", e), door_config)
+            DoorController::new_fault(PRIMARY_DOOR, format!("{:?}", e), door_config)
         }
     };
+    let doors = Doors::new(door.clone(), initialize_other_doors(&config_manager, &door, &saved).await);
+
+    // The status broadcaster polls the door every second, so it doubles as the watchdog heartbeat
+    let watchdog = shq_systemd::Watchdog::new();
+    let heartbeat = watchdog.heartbeat("status broadcaster");
+
+    // Start background broadcasts
+    let broadcaster = Broadcaster::new("dosa");
+    websocket::start_status_broadcaster(door.clone(), broadcaster.clone(), heartbeat);
+    for other in doors.iter().skip(1) {
+        let heartbeat = watchdog.heartbeat(&format!("{} status broadcaster", other.id()));
+        websocket::start_status_broadcaster(other.clone(), broadcaster.clone(), heartbeat);
+    }
+    websocket::start_log_forwarder(log_handle.clone(), broadcaster.clone());
+    // One console per connection, named after the first door on it
+    for (i, door) in doors.iter().enumerate() {
+        if !doors.iter().take(i).any(|earlier| earlier.shares_connection(door)) {
+            websocket::start_console_forwarder(door.clone(), broadcaster.clone());
+        }
+    }
+
+    // Door state changes for `get_history`
+    for door in doors.iter() {
+        history::start(door.clone(), audit.clone());
+    }
+
+    // Last rest positions, offered by `trust_position` after a power cut
+    let last_positions_path = config_manager.last_positions_path();
+    resume::track(&doors, last_positions_path.clone(), resume::load(&last_positions_path).await);
+
+    // Open/close timings for `get_stats`
+    for door in doors.iter() {
+        stats::start(door.clone());
+    }
+
+    // Door events for the configured webhooks
+    webhook::start(&doors, config_manager.clone());
+
+    // Door events spoken by overwatch
+    overwatch::start(&doors, config_manager.clone());
+
+    // Scheduled door commands
+    schedule::start(&config_manager, door.clone(), audit.clone());
+
+    // Home Assistant cover (optional)
+    if let Some(mqtt_config) = config_manager.get_mqtt_config() {
+        mqtt::start(&mqtt_config, door.clone(), audit.clone());
+    }
 
     // Create and start WebSocket server
-    let server = Arc::new(WebSocketServer::new(addr, door.clone(), config_manager));
+    let rate_limiter = RateLimiter::new(config_manager.get_rate_limit_config());
+    let translator = Translator::new(LOCALES, &config_manager.get_i18n_config());
+    let motion_gate = Arc::new(MotionGate::new(config_manager.clone(), broadcaster.clone(), translator.clone()));
+
+    // Plain HTTP API (optional), sharing auth, limits and arbitration with the WebSocket API
+    let http_config = config_manager.get_http_config();
+    let http_server = match &http_config {
+        Some(http_config) => {
+            let http_addr: SocketAddr = format!("{}:{}", http_config.host, http_config.port).parse()?;
+            let api = HttpApi {
+                doors: doors.clone(),
+                config_manager: config_manager.clone(),
+                tr: translator.clone(),
+                audit: audit.clone(),
+                motion_gate: motion_gate.clone(),
+                rate_limiter: rate_limiter.clone(),
+            };
+            Some(HttpServer::bind(http_addr, api).await?)
+        }
+        None => None,
+    };
+
+    // Apply hand edits to the config file (and tell clients) while running
+    config_manager.watch();
+    websocket::start_config_reloader(
+        &config_manager,
+        doors.clone(),
+        broadcaster.clone(),
+        rate_limiter.clone(),
+        translator.clone(),
+        log_handle.clone(),
+    );
 
-    // Spawn server task
-    let server_clone = server.clone();
+    let handler = WebSocketHandler::new(
+        doors.clone(),
+        config_manager,
+        log_handle,
+        translator,
+        audit.clone(),
+        motion_gate,
+    );
+    let mut server = shq_ws::Server::bind(addr, handler, broadcaster)
+        .await?
+        .rate_limit(rate_limiter)
+        .keep_alive(&ws_config.keep_alive)
+        .msgpack();
+    if let Some(tls) = &ws_config.tls {
+        server = server.tls(tls)?;
+    }
+    shq_systemd::notify_ready();
+
+    // Announce _dosa._tcp so panels and peers can find the door without its address
+    let protocol = PROTOCOL_VERSION.to_string();
+    let http_port = http_config.map(|http| http.port.to_string());
+    let mut txt = vec![
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("protocol", protocol.as_str()),
+        ("tls", if ws_config.tls.is_some() { "true" } else { "false" }),
+    ];
+    if let Some(http_port) = &http_port {
+        txt.push(("http_port", http_port));
+    }
+    let mdns = Advertisement::start(&mdns_config, "dosa", port, &txt);
+
+    // Spawn server task; it stops accepting and closes connections once shutdown starts
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
     let server_handle = tokio::spawn(async move {
-        if let Err(e) = server_clone.start().await {
+        let shutdown = async {
+            let _ = shutdown_rx.await;
+        };
+        if let Err(e) = server.run(shutdown).await {
             tracing::error!("WebSocket server error: {}", e);
         }
     });
+    let (http_shutdown_tx, http_shutdown_rx) = oneshot::channel::<()>();
+    let http_handle = http_server.map(|http_server| {
+        tokio::spawn(async move {
+            let shutdown = async {
+                let _ = http_shutdown_rx.await;
+            };
+            if let Err(e) = http_server.run(shutdown).await {
+                tracing::error!("HTTP API error: {}", e);
+            }
+        })
+    });
+    watchdog.start();
+
+    // Wait for SIGTERM/SIGINT
+    shq_systemd::shutdown_signal().await;
 
-    // Wait for shutdown signal
-    match signal::ctrl_c().await {
-        Ok(()) => {
-            tracing::info!("Received shutdown signal");
+    // Cleanup: disconnect clients first so no new commands arrive, then bring the doors to rest
+    tracing::info!("Shutting down...");
+    shq_systemd::notify_stopping();
+    if let Some(mdns) = mdns {
+        mdns.stop();
+    }
+    let _ = shutdown_tx.send(());
+    let _ = http_shutdown_tx.send(());
+    let _ = server_handle.await;
+    if let Some(http_handle) = http_handle {
+        let _ = http_handle.await;
+    }
+    let mut positions = SavedPositions::new();
+    for door in doors.iter() {
+        door.shutdown().await;
+        if let Some(position) = door.saved_position().await {
+            positions.insert(door.id().to_string(), position);
         }
-        Err(err) => {
-            tracing::error!("Unable to listen for shutdown signal: {}", err);
+    }
+    if !positions.is_empty() {
+        match resume::save(&positions_path, &positions).await {
+            Ok(()) => tracing::info!("Saved {} door position(s) for the next start", positions.len()),
+            Err(e) => tracing::error!("Failed to save door positions: {:#}", e),
         }
     }
-
-    // Cleanup
-    tracing::info!("Shutting down...");
-    server_handle.abort();
+    audit.record(AuditEvent::new(AuditKind::Service, "stopped"));
 
     tracing::info!("Shutdown complete");
     Ok(())
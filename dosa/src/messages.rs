@@ -1,6 +1,73 @@
 use serde::{Deserialize, Serialize, Serializer};
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditPage, AuditRecord};
+use shq_auth::Role;
+use shq_config::ConfigArchive;
+use shq_i18n::BuiltinLocales;
+use shq_logging::LogLine;
+use shq_protocol::{Health, SceneReport, ServiceInfo};
+use shq_scheduler::Job;
 
-use crate::config::DoorConfig;
+use crate::arbiter::Claim;
+use crate::cnc::ConsoleLine;
+use crate::config::{DoorAction, DoorConfig, MotionProfile, ObstructionConfig, SafetySensorConfig, TimeoutsConfig};
+use crate::stats::CycleStats;
+
+/// Priority of `stop`, above every other motion command
+pub const STOP_PRIORITY: u8 = u8::MAX;
+
+/// WebSocket protocol revision, reported in the `hello` handshake
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features advertised in the `hello` handshake
+pub const CAPABILITIES: &[&str] = &[
+    "move",
+    "jog",
+    "home",
+    "zero",
+    "calibrate",
+    "clear_alarm",
+    "raw_status",
+    "config",
+    "cnc_settings",
+    "cnc_backup",
+    "logs",
+    "position_stream",
+    "arbitration",
+    "health",
+    "scenes",
+    "envelope",
+    "rate_limit",
+    "command_limit",
+    "hold",
+    "manual_mode",
+    "safety_door",
+    "interlock",
+    "config_backup",
+    "config_reload",
+    "i18n",
+    "audit",
+    "schedules",
+    "presets",
+    "auto_close",
+    "doors",
+    "safety_sensor",
+    "estop",
+    "auth",
+    "history",
+    "stats",
+    "subscriptions",
+    "msgpack",
+    "trust_position",
+    "raw_command",
+    "console",
+];
+
+/// Built-in catalogs for client-facing messages (`i18n.locale`)
+pub const LOCALES: BuiltinLocales = &[
+    ("en", include_str!("../locales/en.yaml")),
+    ("de", include_str!("../locales/de.yaml")),
+];
 
 /// Serialize f64 with 3 decimal places to avoid floating point rounding issues
 fn round_to_3dp<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
@@ -14,6 +81,14 @@ where
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
+    /// Version and capability handshake
+    #[serde(alias = "get_info")]
+    Hello {
+        /// Optional client name, for logging
+        client: Option<String>,
+        /// Optional features the client wants to use; the reply says which are supported
+        features: Option<Vec<String>>,
+    },
     /// Open the door
     Open,
     /// Close the door
@@ -22,6 +97,19 @@ pub enum ClientMessage {
     Move {
         percent: f64,
     },
+    /// Move to a named position from `door.presets`
+    OpenPreset {
+        name: String,
+    },
+    /// Add or change a preset (percent 0-100)
+    SetPreset {
+        name: String,
+        percent: f64,
+    },
+    /// Remove a preset
+    RemovePreset {
+        name: String,
+    },
     /// Jog the CNC axis by a specific distance
     Jog {
         distance: f64,
@@ -31,12 +119,37 @@ pub enum ClientMessage {
     Home,
     /// Zero the door (set current position as home without homing sequence)
     Zero,
+    /// Take the position the door last came to rest at as where it is (`trust_position_mm` in
+    /// its status), instead of homing after a power cut
+    TrustPosition,
+    /// Home, then jog toward open until the limit switch (or `stop`) and save the travel as
+    /// `open_distance`
+    Calibrate {
+        feed_rate: Option<f64>,
+        max_distance: Option<f64>,
+    },
     /// Clear CNC alarm state
     ClearAlarm,
+    /// Release a tripped E-stop once its input is no longer active
+    ResetEstop,
     /// Get current door position and state
     Status,
+    /// Get component health (CNC link, homing)
+    GetHealth,
+    /// Get recent open/close timings
+    GetStats,
     /// Get raw status directly from CNC controller
     RawStatus,
+    /// Send a command line straight to the CNC controller and get every reply line back
+    RawCommand {
+        command: String,
+    },
+    /// Stream the CNC console (command lines sent, lines received) to this client
+    SubscribeConsole,
+    /// Stop streaming the CNC console
+    UnsubscribeConsole,
+    /// Get the safety sensor settings and latest reading
+    GetSafetySensor,
     /// Set door configuration
     SetConfig {
         open_distance: Option<f64>,
@@ -45,11 +158,38 @@ pub enum ClientMessage {
         cnc_axis: Option<String>,
         open_direction: Option<String>,
         auto_home: Option<bool>,
+        auto_close_after_secs: Option<u64>,
+        obstruction: Option<ObstructionConfig>,
+        safety_sensor: Option<SafetySensorConfig>,
+        motion: Option<MotionProfile>,
+        timeouts: Option<TimeoutsConfig>,
     },
     /// Get door configuration
     GetConfig,
     /// Emergency stop
     Stop,
+    /// Keep the door open: without arguments, cancel the auto-close countdown until the door next
+    /// closes; with `duration_secs` or `indefinite`, also open it and hold it until
+    /// `release_hold` (or the time runs out), closing or not
+    HoldOpen {
+        #[serde(default)]
+        duration_secs: Option<u64>,
+        #[serde(default)]
+        indefinite: bool,
+    },
+    /// End a hold, restarting the auto-close countdown
+    ReleaseHold,
+    /// Keep the door from opening (e.g. a weather station's high-wind alarm) until
+    /// `clear_interlock`; closing still works
+    SetInterlock {
+        reason: String,
+    },
+    /// Let the door open again
+    ClearInterlock,
+    /// Disable the motor so the door can be pushed by hand; `home` or `zero` ends it
+    ManualMode,
+    /// Carry on with the move the safety door interrupted, once it is closed
+    Resume,
     /// Query all CNC settings
     GetCncSettings,
     /// Get a specific CNC setting
@@ -61,6 +201,87 @@ pub enum ClientMessage {
         setting: String,
         value: String,
     },
+    /// Compare the controller's settings with `door.cnc_settings`
+    DiffCncSettings,
+    /// Dump all the controller's settings, keeping a copy next to the config for
+    /// `restore_cnc_settings`
+    BackupCncSettings,
+    /// Write settings back to the controller after a swap, only those that differ;
+    /// `settings` defaults to the door's last backup and `dry_run` only compares
+    RestoreCncSettings {
+        #[serde(default)]
+        settings: Option<indexmap::IndexMap<String, String>>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Run a configured scene and report each action's result
+    RunScene {
+        scene: String,
+    },
+    /// List schedule jobs with their next run
+    ListSchedules,
+    /// Add a schedule job (persisted to the config file)
+    AddSchedule {
+        job: Job<DoorAction>,
+    },
+    /// Remove a schedule job by ID (`id` is the envelope's request ID)
+    RemoveSchedule {
+        job_id: String,
+    },
+    /// Stream live log output to this client (requires `logging.stream.enabled`)
+    SubscribeLogs {
+        token: Option<String>,
+        /// Number of recent lines to send first (default 100)
+        lines: Option<usize>,
+    },
+    /// Stop streaming log output
+    UnsubscribeLogs,
+    /// Stream `position` messages to this client every `interval_ms` (default 100) while a door
+    /// is opening or closing
+    SubscribePosition {
+        interval_ms: Option<u64>,
+    },
+    /// Stop streaming positions
+    UnsubscribePosition,
+    /// Choose which broadcasts this client gets; categories left out stay as they are
+    SetSubscriptions {
+        /// `status` updates other than position ticks (state, alarms, holds, ...)
+        status: Option<bool>,
+        /// `status` updates that only move the door along (position, progress, speed)
+        position: Option<bool>,
+        /// `config_changed`
+        config: Option<bool>,
+        /// `command_accepted`
+        events: Option<bool>,
+    },
+    /// Dump the full config as an archive (requires `backup.enabled`)
+    ExportConfig {
+        token: Option<String>,
+    },
+    /// Replace the config from an archive (requires `backup.enabled`)
+    ImportConfig {
+        token: Option<String>,
+        archive: ConfigArchive,
+        /// Entry to restore when the archive holds several dosa configs
+        entry: Option<String>,
+    },
+    /// Read the audit log (requires `audit.query.enabled`)
+    GetAuditLog {
+        token: Option<String>,
+        /// Only records after this sequence number, oldest first (default: the newest)
+        after_seq: Option<u64>,
+        /// Most records to return (default 100, at most 1000)
+        limit: Option<usize>,
+        kind: Option<AuditKind>,
+    },
+    /// Door commands and state changes from the audit log (requires `audit.query.enabled`)
+    GetHistory {
+        token: Option<String>,
+        /// Only events at or after this time (ms since the epoch)
+        since: Option<u64>,
+        /// Most events to return, newest kept (default 100, at most 1000)
+        limit: Option<usize>,
+    },
     /// No operation (keep-alive)
     Noop,
 }
@@ -69,6 +290,16 @@ pub enum ClientMessage {
 #[derive(Debug, Serialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerMessage {
+    /// Handshake reply: service, version, protocol revision and capabilities
+    Hello(ServiceInfo),
+    /// Component health report
+    Health(Health),
+    /// Recent open/close timings, sent in reply to `get_stats`
+    Stats {
+        door: String,
+        #[serde(flatten)]
+        stats: CycleStats,
+    },
     /// Door status update
     Status {
         version: String,
@@ -90,14 +321,334 @@ pub enum ServerMessage {
         setting: String,
         value: String,
     },
+    /// `door.cnc_settings` entries the controller doesn't match, sent in reply to
+    /// `diff_cnc_settings` (empty when it matches)
+    CncSettingsDiff {
+        door: String,
+        differences: Vec<CncSettingDiff>,
+    },
+    /// Every controller setting, sent in reply to `backup_cnc_settings`
+    CncSettingsBackup {
+        door: String,
+        settings: indexmap::IndexMap<String, String>,
+        /// When the backup was taken (ms since the epoch)
+        saved_ms: u64,
+    },
+    /// Settings that differed from the controller's, sent in reply to `restore_cnc_settings`
+    /// (written unless `dry_run`)
+    CncSettingsRestore {
+        door: String,
+        dry_run: bool,
+        differences: Vec<CncSettingDiff>,
+    },
     /// Raw status response from CNC controller
     RawStatus {
         raw: String,
     },
+    /// Everything the controller replied to a `raw_command`, ending with its `ok`/`error:`
+    RawResponse {
+        door: String,
+        command: String,
+        lines: Vec<String>,
+    },
+    /// Safety sensor settings and latest reading, sent in reply to `get_safety_sensor`
+    SafetySensor {
+        door: String,
+        config: SafetySensorConfig,
+        /// Absent while the sensor is disabled or before the first reading
+        #[serde(skip_serializing_if = "Option::is_none")]
+        state: Option<SensorStatus>,
+    },
+    /// Per-action results of a `run_scene`
+    SceneResult(SceneReport),
+    /// Schedule jobs, sent in reply to `list_schedules`
+    Schedules {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timezone: Option<String>,
+        jobs: Vec<ScheduleEntry>,
+    },
+    /// Broadcast categories this client gets, sent in reply to `set_subscriptions`
+    Subscriptions {
+        status: bool,
+        position: bool,
+        config: bool,
+        events: bool,
+    },
+    /// Recent log lines, sent in reply to `subscribe_logs`
+    Logs {
+        lines: Vec<LogLine>,
+    },
+    /// Live log line for subscribed clients
+    Log(LogLine),
+    /// A line sent to or received from a door's CNC controller, for `subscribe_console` clients
+    Console {
+        door: String,
+        #[serde(flatten)]
+        line: ConsoleLine,
+    },
+    /// Live position of a moving door, sent to clients that sent `subscribe_position`
+    Position {
+        door: String,
+        state: DoorState,
+        position_mm: f64,
+        position_percent: f64,
+    },
+    /// Full config, sent in reply to `export_config`
+    ConfigArchive {
+        archive: ConfigArchive,
+    },
+    /// Audit records and hash chain state, sent in reply to `get_audit_log`
+    AuditLog(AuditPage),
+    /// Commands and state changes, oldest first, sent in reply to `get_history`
+    History {
+        records: Vec<AuditRecord>,
+    },
+    /// Broadcast when the config changes (hand edits, `set_config`, imports), naming the
+    /// top-level sections that differ
+    ConfigChanged {
+        sections: Vec<String>,
+    },
+    /// Sent to every client right before the server closes connections on shutdown
+    ServerShuttingDown,
     /// Error message
     Error {
         message: String,
     },
+    /// Motion command refused by `command_limit`; an `error` with the same retry hint as
+    /// `rate_limit` refusals
+    #[serde(rename = "error")]
+    CommandRateLimited {
+        message: String,
+        retry_after_ms: u64,
+    },
+    /// Another client's command holds the door (see `arbiter`); sent instead of running
+    /// `command`
+    Busy {
+        door: String,
+        command: String,
+        held_by: Claim,
+        message: String,
+    },
+    /// A motion command was accepted, broadcast to every client so panels can show who moved
+    /// the door
+    CommandAccepted {
+        door: String,
+        command: String,
+        client: String,
+    },
+}
+
+/// Door a request is for: its optional top-level `door` field, next to the envelope `id`
+#[derive(Debug, Default, Deserialize)]
+pub struct DoorTarget {
+    /// Door id; the primary door if absent
+    pub door: Option<String>,
+}
+
+impl ClientMessage {
+    /// Role a client needs to send this message (`auth`); anything not listed as a query
+    /// needs `control`, so new commands are restricted until they're deliberately opened up
+    pub fn required_role(&self) -> Role {
+        match self {
+            ClientMessage::Hello { .. }
+            | ClientMessage::Status
+            | ClientMessage::GetHealth
+            | ClientMessage::GetStats
+            | ClientMessage::RawStatus
+            | ClientMessage::GetSafetySensor
+            | ClientMessage::GetConfig
+            | ClientMessage::GetCncSettings
+            | ClientMessage::GetCncSetting { .. }
+            | ClientMessage::DiffCncSettings
+            | ClientMessage::ListSchedules
+            | ClientMessage::SubscribeLogs { .. }
+            | ClientMessage::UnsubscribeLogs
+            | ClientMessage::UnsubscribeConsole
+            | ClientMessage::SubscribePosition { .. }
+            | ClientMessage::UnsubscribePosition
+            | ClientMessage::SetSubscriptions { .. }
+            | ClientMessage::GetAuditLog { .. }
+            | ClientMessage::GetHistory { .. }
+            | ClientMessage::Noop => Role::Read,
+            ClientMessage::RawCommand { .. } | ClientMessage::SubscribeConsole => Role::Advanced,
+            _ => Role::Control,
+        }
+    }
+
+    /// Name and priority of commands that move the door (`arbiter`), `None` for others
+    ///
+    /// A command only takes a busy door from another client with a higher priority; `stop`
+    /// always does.
+    pub fn motion_priority(&self) -> Option<(&'static str, u8)> {
+        match self {
+            ClientMessage::Open => Some(("open", 1)),
+            ClientMessage::Close => Some(("close", 1)),
+            ClientMessage::Move { .. } => Some(("move", 1)),
+            ClientMessage::OpenPreset { .. } => Some(("open_preset", 1)),
+            ClientMessage::Jog { .. } => Some(("jog", 1)),
+            ClientMessage::Home => Some(("home", 2)),
+            ClientMessage::Zero => Some(("zero", 2)),
+            ClientMessage::TrustPosition => Some(("trust_position", 2)),
+            ClientMessage::ManualMode => Some(("manual_mode", 2)),
+            ClientMessage::Resume => Some(("resume", 1)),
+            ClientMessage::Calibrate { .. } => Some(("calibrate", 2)),
+            ClientMessage::Stop => Some(("stop", STOP_PRIORITY)),
+            // Only the timed and indefinite forms move the door
+            ClientMessage::HoldOpen {
+                duration_secs,
+                indefinite,
+            } if duration_secs.is_some() || *indefinite => Some(("hold_open", 1)),
+            _ => None,
+        }
+    }
+
+    /// Whether this command moves the door toward open from `position_percent`, which an
+    /// interlock refuses
+    ///
+    /// Presets and holds are checked by the door itself, which knows their target.
+    pub fn opens_door(&self, position_percent: f64) -> bool {
+        match self {
+            ClientMessage::Open | ClientMessage::Calibrate { .. } => true,
+            ClientMessage::Move { percent } => *percent > position_percent,
+            ClientMessage::Jog { distance, .. } => *distance > 0.0,
+            _ => false,
+        }
+    }
+
+    /// What makes a repeat of this command identical (`command_limit.debounce_ms`); `None` for
+    /// commands that are never debounced (jogs add up, and stop must always get through)
+    pub fn debounce_key(&self) -> Option<String> {
+        match self {
+            ClientMessage::Open => Some("open".to_string()),
+            ClientMessage::Close => Some("close".to_string()),
+            ClientMessage::Move { percent } => Some(format!("move {}", percent)),
+            ClientMessage::OpenPreset { name } => Some(format!("open_preset {}", name)),
+            _ => None,
+        }
+    }
+
+    /// Audit event for privileged commands (`None` for read-only ones); tokens are left out
+    ///
+    /// `door` is added to the detail when the request named a door.
+    pub fn audit_event(&self, door: Option<&str>) -> Option<AuditEvent> {
+        let (action, detail) = match self {
+            ClientMessage::Open => ("open", None),
+            ClientMessage::Close => ("close", None),
+            ClientMessage::Move { percent } => ("move", Some(json!({ "percent": percent }))),
+            ClientMessage::OpenPreset { name } => ("open_preset", Some(json!({ "name": name }))),
+            ClientMessage::SetPreset { name, percent } => {
+                ("set_preset", Some(json!({ "name": name, "percent": percent })))
+            }
+            ClientMessage::RemovePreset { name } => ("remove_preset", Some(json!({ "name": name }))),
+            ClientMessage::Jog { distance, feed_rate } => {
+                ("jog", Some(json!({ "distance": distance, "feed_rate": feed_rate })))
+            }
+            ClientMessage::Home => ("home", None),
+            ClientMessage::Zero => ("zero", None),
+            ClientMessage::TrustPosition => ("trust_position", None),
+            ClientMessage::Calibrate { feed_rate, max_distance } => {
+                ("calibrate", Some(json!({ "feed_rate": feed_rate, "max_distance": max_distance })))
+            }
+            ClientMessage::ClearAlarm => ("clear_alarm", None),
+            ClientMessage::ResetEstop => ("reset_estop", None),
+            ClientMessage::Stop => ("stop", None),
+            ClientMessage::HoldOpen {
+                duration_secs: None,
+                indefinite: false,
+            } => ("hold_open", None),
+            ClientMessage::HoldOpen {
+                duration_secs,
+                indefinite,
+            } => ("hold_open", Some(json!({ "duration_secs": duration_secs, "indefinite": indefinite }))),
+            ClientMessage::ReleaseHold => ("release_hold", None),
+            ClientMessage::SetInterlock { reason } => ("set_interlock", Some(json!({ "reason": reason }))),
+            ClientMessage::ClearInterlock => ("clear_interlock", None),
+            ClientMessage::ManualMode => ("manual_mode", None),
+            ClientMessage::Resume => ("resume", None),
+            ClientMessage::SetConfig {
+                open_distance,
+                open_speed,
+                close_speed,
+                cnc_axis,
+                open_direction,
+                auto_home,
+                auto_close_after_secs,
+                obstruction,
+                safety_sensor,
+                motion,
+                timeouts,
+            } => {
+                let detail = json!({
+                    "open_distance": open_distance,
+                    "open_speed": open_speed,
+                    "close_speed": close_speed,
+                    "cnc_axis": cnc_axis,
+                    "open_direction": open_direction,
+                    "auto_home": auto_home,
+                    "auto_close_after_secs": auto_close_after_secs,
+                    "obstruction": obstruction,
+                    "safety_sensor": safety_sensor,
+                    "motion": motion,
+                    "timeouts": timeouts,
+                });
+                ("set_config", Some(detail))
+            }
+            ClientMessage::SetCncSetting { setting, value } => {
+                ("set_cnc_setting", Some(json!({ "setting": setting, "value": value })))
+            }
+            ClientMessage::BackupCncSettings => ("backup_cnc_settings", None),
+            ClientMessage::RestoreCncSettings { settings, dry_run } => (
+                "restore_cnc_settings",
+                Some(json!({ "from_backup": settings.is_none(), "dry_run": dry_run })),
+            ),
+            ClientMessage::RunScene { scene } => ("run_scene", Some(json!({ "scene": scene }))),
+            ClientMessage::AddSchedule { job } => ("add_schedule", Some(json!(job))),
+            ClientMessage::RemoveSchedule { job_id } => ("remove_schedule", Some(json!({ "job_id": job_id }))),
+            ClientMessage::SubscribeLogs { .. } => ("subscribe_logs", None),
+            ClientMessage::RawCommand { command } => ("raw_command", Some(json!({ "command": command }))),
+            ClientMessage::SubscribeConsole => ("subscribe_console", None),
+            ClientMessage::ExportConfig { .. } => ("export_config", None),
+            ClientMessage::ImportConfig { entry, .. } => ("import_config", Some(json!({ "entry": entry }))),
+            ClientMessage::GetAuditLog { .. } => ("get_audit_log", None),
+            ClientMessage::GetHistory { .. } => ("get_history", None),
+            _ => return None,
+        };
+
+        let detail = match (detail, door) {
+            (Some(mut detail), Some(door)) => {
+                detail["door"] = json!(door);
+                Some(detail)
+            }
+            (None, Some(door)) => Some(json!({ "door": door })),
+            (detail, None) => detail,
+        };
+
+        let event = AuditEvent::new(AuditKind::Command, action);
+        Some(match detail {
+            Some(detail) => event.detail(detail),
+            None => event,
+        })
+    }
+}
+
+/// A schedule job and when it next runs
+#[derive(Debug, Serialize, Clone)]
+pub struct ScheduleEntry {
+    #[serde(flatten)]
+    pub job: Job<DoorAction>,
+    /// Next run (ms since the epoch); absent for disabled jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run_ms: Option<i64>,
+}
+
+/// A `door.cnc_settings` entry the controller doesn't match
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CncSettingDiff {
+    pub setting: String,
+    pub desired: String,
+    /// What the controller reports (absent if it doesn't have the setting)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
 }
 
 /// Door state
@@ -120,15 +671,31 @@ pub enum DoorState {
     Closing,
     /// Door is homing
     Homing,
+    /// Door is measuring its travel (`calibrate`)
+    Calibrating,
+    /// Door is probing for the limit switch after closing (`door.close_verify`)
+    Verifying,
     /// CNC controller is in alarm state
     Alarm,
     /// System is in fault state (connection error)
     Fault,
+    /// Door was stopped because the motor load spiked (see `door.obstruction`)
+    Obstructed,
+    /// The E-stop tripped (see `door.estop`); nothing moves until `reset_estop`
+    #[serde(rename = "estop")]
+    EStop,
+    /// Motor disabled (`manual_mode`) so the door can be pushed by hand; the position is
+    /// unknown until `home` or `zero`
+    Manual,
+    /// grblHAL's safety door input stopped the machine; `resume` carries on once it's closed
+    SafetyDoor,
 }
 
 /// Door position information
 #[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct DoorStatus {
+    /// Door id ("main" for the primary door)
+    pub id: String,
     /// Current door state
     pub state: DoorState,
     /// Current position in millimeters relative to home (0 = closed/home position)
@@ -145,4 +712,82 @@ pub struct DoorStatus {
     /// Alarm code if in alarm state
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alarm_code: Option<String>,
+    /// What the alarm code means (absent for codes dosa doesn't know)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alarm_description: Option<String>,
+    /// Seconds until the open door closes itself (`door.auto_close_after_secs`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_close_in_secs: Option<u64>,
+    /// Latest safety sensor reading (absent if `door.safety_sensor` is disabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_sensor: Option<SensorStatus>,
+    /// How much of the current open, close or move is done, 0-100 (only while opening or
+    /// closing)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_percent: Option<f64>,
+    /// Feed rate in mm/min from the status report's `FS:` field (only while opening or closing)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_rate: Option<f64>,
+    /// Estimated seconds until the move ends, from the distance left at the current feed rate
+    /// (absent while the rate is 0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<f64>,
+    /// Why the door is being kept open (absent when it isn't)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold: Option<HoldState>,
+    /// Seconds left on a timed hold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_remaining_secs: Option<u64>,
+    /// The last closed-position check found the limit switch out of place (until the next
+    /// clean check, `home` or `zero`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_drift: Option<PositionDrift>,
+    /// While `safety_door`: whether the safety door is closed again, so `resume` can carry on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_door_ready: Option<bool>,
+    /// The CNC connection in use, as `host:port` or the serial port (absent in fault state);
+    /// one of `door.cnc_fallbacks` after a failover
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnc_endpoint: Option<String>,
+    /// Why the door may not open (`set_interlock`, e.g. a weather station's high-wind alarm);
+    /// absent when it may
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interlock: Option<String>,
+    /// Where the door last came to rest, offered to a door that lost its homing (e.g. to a
+    /// power cut) so `trust_position` can skip homing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trust_position_mm: Option<f64>,
+}
+
+/// Closed-position check that failed (`door.close_verify`)
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PositionDrift {
+    /// How far short of truly closed the door is, in mm (negative: past it); absent if the
+    /// switch wasn't found within `search_distance`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drift_mm: Option<f64>,
+    /// The configured `tolerance`
+    pub tolerance_mm: f64,
+}
+
+/// Kind of hold keeping the door open
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldState {
+    /// Plain `hold_open`: auto-close is off until the door next closes
+    UntilClosed,
+    /// `hold_open` with `duration_secs`
+    Timed,
+    /// `hold_open` with `indefinite`
+    Indefinite,
+}
+
+/// Safety sensor reading
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SensorStatus {
+    /// Beam broken or presence detected; also true while the sensor can't be read
+    pub triggered: bool,
+    /// Why the sensor couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
@@ -0,0 +1,127 @@
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_mqtt::{DeviceInfo, Entity, MqttClient, MqttCommand, MqttConfig};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::door::DoorController;
+use crate::messages::{DoorState, DoorStatus};
+
+const DOOR: &str = "door";
+
+/// Expose the door to Home Assistant as a cover with position support
+///
+/// `OPEN`/`CLOSE`/`STOP` and position (0-100) commands go to the same `DoorController`
+/// methods as the WebSocket API and are recorded in the audit log with client "mqtt".
+pub fn start(config: &MqttConfig, door: DoorController, audit: AuditLog) {
+    let entities = vec![Entity::cover(DOOR, "Door")
+        .with("device_class", "door")
+        .with("position_topic", "~/position")
+        .with("set_position_topic", "~/position/set")
+        .with("position_open", 100)
+        .with("position_closed", 0)];
+
+    let device = DeviceInfo {
+        service: "dosa".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let (client, mut commands) = MqttClient::connect(config, device, entities);
+
+    // Publish door state changes
+    let state_door = door.clone();
+    tokio::spawn(async move {
+        let mut updates = state_door.subscribe_status();
+        let mut last = None;
+        publish_status(&client, &state_door.get_status().await, &mut last);
+
+        loop {
+            match updates.recv().await {
+                Ok(status) => publish_status(&client, &status, &mut last),
+                Err(RecvError::Lagged(_)) => {
+                    publish_status(&client, &state_door.get_status().await, &mut last)
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(command) = commands.recv().await {
+            if command.object_id != DOOR {
+                tracing::debug!("Ignoring MQTT command for unknown entity '{}'", command.object_id);
+                continue;
+            }
+
+            // Door commands can take a while (stop waits for the door to halt)
+            let door = door.clone();
+            let audit = audit.clone();
+            tokio::spawn(async move {
+                run_command(&door, &audit, command).await;
+            });
+        }
+    });
+}
+
+/// Publish cover state and position if either changed since the last publish
+fn publish_status(client: &MqttClient, status: &DoorStatus, last: &mut Option<(&'static str, u8)>) {
+    let state = match status.state {
+        DoorState::Open => "open",
+        DoorState::Closed | DoorState::Verifying => "closed",
+        DoorState::Opening | DoorState::Calibrating => "opening",
+        DoorState::Closing => "closing",
+        DoorState::Intermediate | DoorState::Halting | DoorState::Obstructed | DoorState::SafetyDoor => "stopped",
+        // HA treats "None" as unknown state
+        DoorState::Pending
+        | DoorState::Homing
+        | DoorState::Alarm
+        | DoorState::Fault
+        | DoorState::EStop
+        | DoorState::Manual => "None",
+    };
+    let position = status.position_percent.round().clamp(0.0, 100.0) as u8;
+
+    if *last == Some((state, position)) {
+        return;
+    }
+    client.publish(DOOR, "state", state);
+    client.publish(DOOR, "position", position.to_string());
+    *last = Some((state, position));
+}
+
+/// Run a cover command from Home Assistant
+async fn run_command(door: &DoorController, audit: &AuditLog, command: MqttCommand) {
+    let payload = command.payload.trim();
+
+    let (event, result) = match command.topic.as_str() {
+        "set" => match payload.to_ascii_uppercase().as_str() {
+            "OPEN" => (AuditEvent::new(AuditKind::Command, "open"), door.open().await),
+            "CLOSE" => (AuditEvent::new(AuditKind::Command, "close"), door.close().await),
+            "STOP" => (AuditEvent::new(AuditKind::Command, "stop"), door.stop().await),
+            other => {
+                tracing::warn!("Ignoring unknown MQTT cover command '{}'", other);
+                return;
+            }
+        },
+        "position/set" => {
+            let Ok(percent) = payload.parse::<f64>() else {
+                tracing::warn!("Ignoring invalid MQTT cover position '{}'", payload);
+                return;
+            };
+            let event = AuditEvent::new(AuditKind::Command, "move").detail(json!({ "percent": percent }));
+            (event, door.move_to_percent(percent).await)
+        }
+        other => {
+            tracing::debug!("Ignoring MQTT cover topic '{}'", other);
+            return;
+        }
+    };
+
+    let event = event.client("mqtt");
+    match result {
+        Ok(()) => audit.record(event),
+        Err(e) => {
+            tracing::error!("MQTT cover command failed: {}", e);
+            audit.record(event.failed(&e));
+        }
+    }
+}
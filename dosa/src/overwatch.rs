@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tonic::transport::Endpoint;
+
+use crate::config::{Announcement, ConfigManager, DoorEvent, OverwatchConfig};
+use crate::door::Doors;
+use crate::messages::DoorStatus;
+
+mod voice {
+    tonic::include_proto!("voice");
+}
+
+use voice::voice_service_client::VoiceServiceClient;
+use voice::VerbaliseRequest;
+
+/// Longest to wait for overwatch to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest one announcement may take; Verbalise answers once the speech has played
+const VERBALISE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Have overwatch speak each door's events as configured under `overwatch`
+///
+/// The section is read when an event happens, so edits apply straight away. Announcements are
+/// spoken one at a time in the order the events happened; one that fails is logged and dropped,
+/// since a late "door opening" helps nobody.
+pub fn start(doors: &Doors, config_manager: ConfigManager) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<VerbaliseRequest>();
+
+    for door in doors.iter() {
+        let door = door.clone();
+        let tx = tx.clone();
+        let config_manager = config_manager.clone();
+        tokio::spawn(async move {
+            let mut updates = door.subscribe_status();
+            let mut last = door.get_status().await.state;
+
+            loop {
+                let status = match updates.recv().await {
+                    Ok(status) => status,
+                    Err(RecvError::Lagged(_)) => door.get_status().await,
+                    Err(RecvError::Closed) => break,
+                };
+                let event = DoorEvent::between(&last, &status.state);
+                last = status.state.clone();
+
+                let (Some(event), Some(config)) = (event, config_manager.get_overwatch_config()) else {
+                    continue;
+                };
+                if let Some(announcement) = config.announcements.get(&event) {
+                    let _ = tx.send(request(&config, announcement, &status));
+                }
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        while let Some(request) = rx.recv().await {
+            // Whoever queued it saw a config; the URL may have gone since
+            let Some(config) = config_manager.get_overwatch_config() else {
+                continue;
+            };
+            if let Err(e) = verbalise(&config.url, request).await {
+                tracing::warn!("overwatch announcement failed: {}", e);
+            }
+        }
+    });
+}
+
+fn request(config: &OverwatchConfig, announcement: &Announcement, status: &DoorStatus) -> VerbaliseRequest {
+    let mut args = BTreeMap::from([
+        ("door".to_string(), status.id.clone()),
+        ("position".to_string(), format!("{:.0}", status.position_percent)),
+    ]);
+    args.extend(announcement.args.clone());
+
+    // overwatch fills its own templates; fixed text is filled here
+    let (text, template, args) = match (&announcement.text, &announcement.template) {
+        (Some(text), _) => (shq_i18n::fill_template(text, &args), None, BTreeMap::new()),
+        (None, template) => (String::new(), template.clone(), args),
+    };
+
+    VerbaliseRequest {
+        text,
+        notification_tone_id: announcement
+            .notification_tone_id
+            .clone()
+            .or_else(|| config.notification_tone_id.clone()),
+        voice_id: announcement.voice_id.clone().or_else(|| config.voice_id.clone()),
+        volume: announcement.volume.or(config.volume),
+        template,
+        args: args.into_iter().collect(),
+        priority: None,
+        zone: announcement.zone.clone().or_else(|| config.zone.clone()),
+    }
+}
+
+async fn verbalise(url: &str, request: VerbaliseRequest) -> anyhow::Result<()> {
+    let what = request.template.clone().unwrap_or_else(|| request.text.clone());
+    let channel = Endpoint::from_shared(url.to_string())?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(VERBALISE_TIMEOUT)
+        .connect()
+        .await?;
+    let response = VoiceServiceClient::new(channel)
+        .verbalise(request)
+        .await
+        .map_err(|status| anyhow::anyhow!("{}", status.message()))?
+        .into_inner();
+    if !response.success {
+        anyhow::bail!("{}", response.message);
+    }
+    tracing::debug!("overwatch announced {:?}", what);
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+
+use crate::door::{DoorController, Doors};
+use crate::messages::DoorState;
+
+/// Where a door was left at shutdown, so the next start can skip homing (`door.restore_position`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPosition {
+    /// Position relative to home in mm
+    pub position_mm: f64,
+    /// Machine position (MPos) of the door's main axis
+    pub machine_mm: f64,
+    /// When the position was saved, in ms since the epoch
+    pub saved_ms: u64,
+}
+
+impl SavedPosition {
+    pub fn new(position_mm: f64, machine_mm: f64) -> Self {
+        let saved_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            position_mm,
+            machine_mm,
+            saved_ms,
+        }
+    }
+}
+
+/// Saved positions by door id
+pub type SavedPositions = BTreeMap<String, SavedPosition>;
+
+/// Write the positions of the doors that were at rest, replacing any earlier file
+pub async fn save(path: &Path, positions: &SavedPositions) -> Result<()> {
+    let json = serde_json::to_string_pretty(positions)?;
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json)
+        .await
+        .with_context(|| format!("Failed to write {:?}", tmp))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// Read and delete the saved positions
+///
+/// A position is only good for the start right after the shutdown that saved it; after a
+/// crash the file would be stale, so it never survives being read.
+pub async fn take(path: &Path) -> SavedPositions {
+    let positions = load(path).await;
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("Can't remove saved door positions {:?}: {}", path, e);
+        }
+    }
+    positions
+}
+
+/// Read saved positions, leaving the file in place
+pub async fn load(path: &Path) -> SavedPositions {
+    let text = match tokio::fs::read_to_string(path).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return SavedPositions::new(),
+        Err(e) => {
+            tracing::warn!("Can't read saved door positions {:?}: {}", path, e);
+            return SavedPositions::new();
+        }
+    };
+
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring saved door positions {:?}: {}", path, e);
+        SavedPositions::new()
+    })
+}
+
+/// Keep `path` up to date with where each door last came to rest, starting from `known`
+///
+/// Unlike the shutdown file this survives a power cut, so a door left unhomed by one can be
+/// offered its last position (`trust_position`) instead of homing. A door pushed by hand
+/// (`manual_mode`) loses its entry.
+pub fn track(doors: &Doors, path: PathBuf, known: SavedPositions) {
+    let known = Arc::new(Mutex::new(known));
+    for door in doors.iter() {
+        let door = door.clone();
+        let path = path.clone();
+        let known = known.clone();
+        tokio::spawn(async move {
+            door.set_last_rest(known.lock().await.get(door.id()).cloned()).await;
+            let mut updates = door.subscribe_status();
+            loop {
+                match updates.recv().await {
+                    Ok(status) if status.state == DoorState::Manual => forget(&door, &path, &known).await,
+                    Ok(_) | Err(RecvError::Lagged(_)) => record(&door, &path, &known).await,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+/// Save the door's position if it has come to rest somewhere new
+async fn record(door: &DoorController, path: &Path, known: &Mutex<SavedPositions>) {
+    let Some(position) = door.saved_position().await else {
+        return;
+    };
+    let mut known = known.lock().await;
+    if known
+        .get(door.id())
+        .is_some_and(|last| {
+            (last.position_mm - position.position_mm).abs() < 0.1 && (last.machine_mm - position.machine_mm).abs() < 0.1
+        })
+    {
+        return;
+    }
+
+    known.insert(door.id().to_string(), position.clone());
+    door.set_last_rest(Some(position)).await;
+    if let Err(e) = save(path, &known).await {
+        tracing::warn!("Failed to save the last door positions: {:#}", e);
+    }
+}
+
+async fn forget(door: &DoorController, path: &Path, known: &Mutex<SavedPositions>) {
+    let mut known = known.lock().await;
+    if known.remove(door.id()).is_none() {
+        return;
+    }
+
+    door.set_last_rest(None).await;
+    if let Err(e) = save(path, &known).await {
+        tracing::warn!("Failed to save the last door positions: {:#}", e);
+    }
+}
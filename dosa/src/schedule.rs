@@ -0,0 +1,72 @@
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_scheduler::Scheduler;
+
+use crate::config::{ConfigManager, DoorAction};
+use crate::door::{DoorController, Hold};
+use crate::messages::DoorState;
+
+/// Run scheduled door commands
+///
+/// Commands run one at a time; a job that comes due while the door is still moving waits
+/// for the previous command to finish. Jobs are skipped while the controller is in fault or
+/// alarm, and all but opens are skipped while a timed or indefinite `hold_open` keeps the door
+/// open. Schedule changes (`add_schedule`/`remove_schedule`, imports) apply straight away.
+/// Each run is recorded in the audit log.
+pub fn start(config_manager: &ConfigManager, door: DoorController, audit: AuditLog) {
+    let (scheduler, mut triggers) = Scheduler::start(
+        config_manager.get_schedule_config(),
+        Some(config_manager.schedule_state_path()),
+    );
+
+    // Follow the schedule section of the config; the scheduler lives as long as this task
+    let mut config_rx = config_manager.subscribe();
+    tokio::spawn(async move {
+        let mut current = config_rx.borrow_and_update().schedule.clone();
+        while config_rx.changed().await.is_ok() {
+            let schedule = config_rx.borrow_and_update().schedule.clone();
+            if schedule != current {
+                scheduler.update(schedule.clone());
+                current = schedule;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(trigger) = triggers.recv().await {
+            if trigger.missed {
+                tracing::info!(
+                    "Catching up missed schedule job '{}' (was due {})",
+                    trigger.job_id,
+                    trigger.scheduled
+                );
+            }
+
+            let event = AuditEvent::new(AuditKind::Command, "schedule")
+                .client("schedule")
+                .detail(json!({ "job": trigger.job_id, "action": trigger.action }));
+
+            let state = door.get_status().await.state;
+            if matches!(state, DoorState::Fault | DoorState::Alarm) {
+                tracing::warn!("Skipping schedule job '{}': door is in {:?} state", trigger.job_id, state);
+                audit.record(event.failed(format!("Skipped: door is in {:?} state", state)));
+                continue;
+            }
+
+            let held = matches!(door.hold().await, Some(Hold::Until(_) | Hold::Indefinite));
+            if held && trigger.action != DoorAction::Open {
+                tracing::info!("Skipping schedule job '{}': door is held open", trigger.job_id);
+                audit.record(event.failed("Skipped: door is held open"));
+                continue;
+            }
+
+            match door.run_action(&trigger.action).await {
+                Ok(()) => audit.record(event),
+                Err(e) => {
+                    tracing::error!("Schedule job '{}' failed: {}", trigger.job_id, e);
+                    audit.record(event.failed(&e));
+                }
+            }
+        }
+    });
+}
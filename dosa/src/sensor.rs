@@ -0,0 +1,42 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use crate::cnc::CncController;
+use crate::config::SensorInput;
+use crate::messages::SensorStatus;
+
+/// Read the safety sensor or E-stop wired to `input`; `status_report` is the latest `?` reply
+/// from the controller, and `invert` flips the reading for normally-closed wiring
+///
+/// An input that can't be read counts as triggered, so a broken sensor keeps the door from
+/// closing rather than letting it close blind.
+pub async fn read(input: &SensorInput, invert: bool, status_report: &str) -> SensorStatus {
+    let active = match input {
+        SensorInput::Grbl { pin } => Ok(CncController::parse_field(status_report, "Pn")
+            .is_some_and(|pins| pins.contains(pin.to_ascii_uppercase().as_str()))),
+        SensorInput::Gpio { path } => read_gpio(path).await,
+    };
+
+    match active {
+        Ok(active) => SensorStatus {
+            triggered: active != invert,
+            error: None,
+        },
+        Err(e) => SensorStatus {
+            triggered: true,
+            error: Some(format!("{:#}", e)),
+        },
+    }
+}
+
+/// Read a GPIO value file ("0" or "1")
+async fn read_gpio(path: &Path) -> Result<bool> {
+    let value = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    match value.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        other => bail!("Unexpected value {:?} in {:?}", other, path),
+    }
+}
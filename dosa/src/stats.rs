@@ -0,0 +1,200 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::door::DoorController;
+use crate::messages::{DoorState, DoorStatus};
+
+/// Cycles kept per door for `get_stats`
+const RECENT_CYCLES: usize = 20;
+
+/// Earlier cycles with the same speed and travel needed before a baseline is taken
+const MIN_BASELINE_CYCLES: usize = 3;
+
+/// A cycle this many times slower than its baseline is logged as a warning
+const SLOW_CYCLE_FACTOR: f64 = 1.5;
+
+/// Which way a cycle moved the door
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleDirection {
+    Open,
+    Close,
+}
+
+/// A full open (closed to open) or close (open to closed), timed from the command to the
+/// door coming to rest
+#[derive(Debug, Clone, Serialize)]
+pub struct Cycle {
+    pub direction: CycleDirection,
+    /// When the cycle started, in ms since the epoch
+    pub started_ms: u64,
+    pub duration_secs: f64,
+    /// Highest feed rate (mm/min) the controller reported during the cycle
+    pub max_feed_rate: Option<f64>,
+    /// Configured speed (mm/min) and travel (mm); only cycles with the same ones are compared
+    pub speed: f64,
+    pub distance_mm: f64,
+    /// Took more than `SLOW_CYCLE_FACTOR` times the baseline, e.g. from mechanical binding
+    pub slow: bool,
+}
+
+/// Recent cycles and the baselines they're judged against, sent in reply to `get_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleStats {
+    /// Oldest first
+    pub cycles: Vec<Cycle>,
+    /// Median duration of recent opens at the latest open's speed and travel
+    pub open_baseline_secs: Option<f64>,
+    /// Median duration of recent closes at the latest close's speed and travel
+    pub close_baseline_secs: Option<f64>,
+}
+
+/// A door's recent cycles
+#[derive(Debug, Default)]
+pub struct CycleLog {
+    cycles: VecDeque<Cycle>,
+}
+
+impl CycleLog {
+    /// Add a finished cycle, warning if it was much slower than the baseline
+    pub fn record(&mut self, door: &str, mut cycle: Cycle) {
+        if let Some(baseline) = self.baseline(cycle.direction, cycle.speed, cycle.distance_mm) {
+            if cycle.duration_secs > baseline * SLOW_CYCLE_FACTOR {
+                cycle.slow = true;
+                let action = match cycle.direction {
+                    CycleDirection::Open => "open",
+                    CycleDirection::Close => "close",
+                };
+                tracing::warn!(
+                    "Door '{}' took {:.1}s to {}, {:.1}s is usual; check it for binding",
+                    door,
+                    cycle.duration_secs,
+                    action,
+                    baseline
+                );
+            }
+        }
+
+        self.cycles.push_back(cycle);
+        if self.cycles.len() > RECENT_CYCLES {
+            self.cycles.pop_front();
+        }
+    }
+
+    /// Median duration of the recent cycles in `direction` at `speed` over `distance_mm`
+    fn baseline(&self, direction: CycleDirection, speed: f64, distance_mm: f64) -> Option<f64> {
+        let mut durations: Vec<f64> = self
+            .cycles
+            .iter()
+            .filter(|cycle| cycle.direction == direction && cycle.speed == speed && cycle.distance_mm == distance_mm)
+            .map(|cycle| cycle.duration_secs)
+            .collect();
+        if durations.len() < MIN_BASELINE_CYCLES {
+            return None;
+        }
+
+        durations.sort_by(f64::total_cmp);
+        let middle = durations.len() / 2;
+        Some(if durations.len().is_multiple_of(2) {
+            (durations[middle - 1] + durations[middle]) / 2.0
+        } else {
+            durations[middle]
+        })
+    }
+
+    pub fn stats(&self) -> CycleStats {
+        let baseline = |direction| {
+            let latest = self.cycles.iter().rev().find(|cycle| cycle.direction == direction)?;
+            self.baseline(direction, latest.speed, latest.distance_mm)
+        };
+        CycleStats {
+            cycles: self.cycles.iter().cloned().collect(),
+            open_baseline_secs: baseline(CycleDirection::Open),
+            close_baseline_secs: baseline(CycleDirection::Close),
+        }
+    }
+}
+
+/// A cycle under way
+struct Running {
+    direction: CycleDirection,
+    started: Instant,
+    started_ms: u64,
+    max_feed_rate: Option<f64>,
+    speed: f64,
+    distance_mm: f64,
+}
+
+/// Time the door's open and close cycles for `get_stats`
+///
+/// Only moves from one end of travel to the other count; a cycle that stops short, reverses
+/// or is interrupted (safety door, obstruction) is dropped.
+pub fn start(door: DoorController) {
+    tokio::spawn(async move {
+        let mut updates = door.subscribe_status();
+        let mut last = door.get_status().await.state;
+        let mut running = None;
+
+        loop {
+            match updates.recv().await {
+                Ok(status) => track(&door, &status, &mut last, &mut running).await,
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn track(door: &DoorController, status: &DoorStatus, last: &mut DoorState, running: &mut Option<Running>) {
+    if status.state == *last {
+        if let (Some(cycle), Some(feed_rate)) = (running.as_mut(), status.feed_rate) {
+            cycle.max_feed_rate = Some(cycle.max_feed_rate.map_or(feed_rate, |max| max.max(feed_rate)));
+        }
+        return;
+    }
+
+    let finished = match (running.take(), &status.state) {
+        (Some(cycle), DoorState::Open) if cycle.direction == CycleDirection::Open => Some(cycle),
+        (Some(cycle), DoorState::Closed) if cycle.direction == CycleDirection::Close => Some(cycle),
+        _ => None,
+    };
+    if let Some(cycle) = finished {
+        let duration = cycle.started.elapsed().as_secs_f64();
+        door.record_cycle(Cycle {
+            direction: cycle.direction,
+            started_ms: cycle.started_ms,
+            duration_secs: (duration * 100.0).round() / 100.0,
+            max_feed_rate: cycle.max_feed_rate,
+            speed: cycle.speed,
+            distance_mm: cycle.distance_mm,
+            slow: false,
+        })
+        .await;
+    }
+
+    let direction = match (&*last, &status.state) {
+        (DoorState::Closed, DoorState::Opening) => Some(CycleDirection::Open),
+        (DoorState::Open, DoorState::Closing) => Some(CycleDirection::Close),
+        _ => None,
+    };
+    if let Some(direction) = direction {
+        let config = door.get_config().await;
+        *running = Some(Running {
+            direction,
+            started: Instant::now(),
+            started_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            max_feed_rate: status.feed_rate,
+            speed: match direction {
+                CycleDirection::Open => config.open_speed,
+                CycleDirection::Close => config.close_speed,
+            },
+            distance_mm: config.open_distance,
+        });
+    }
+    *last = status.state.clone();
+}
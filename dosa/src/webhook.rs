@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Duration;
+
+use crate::config::{ConfigManager, DoorEvent, WebhookConfig};
+use crate::door::Doors;
+use crate::messages::{DoorState, DoorStatus};
+
+/// Longest a webhook endpoint gets to answer one POST
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait before the first retry; doubled for each one after it, up to `MAX_BACKOFF`
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// JSON body POSTed to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub event: DoorEvent,
+    pub door: String,
+    pub state: DoorState,
+    pub position_mm: f64,
+    pub position_percent: f64,
+    /// When the door changed state, in ms since the epoch
+    pub timestamp_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alarm_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alarm_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault_message: Option<String>,
+}
+
+/// POST each door's events to the configured `webhooks`
+///
+/// The list is read when an event happens, so hand edits apply straight away. Each delivery
+/// runs on its own and is retried with backoff, so a slow endpoint doesn't hold up the next
+/// event (deliveries may arrive out of order; `timestamp_ms` tells).
+pub fn start(doors: &Doors, config_manager: ConfigManager) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Webhooks disabled, can't create the HTTP client: {}", e);
+            return;
+        }
+    };
+
+    for door in doors.iter() {
+        let door = door.clone();
+        let client = client.clone();
+        let config_manager = config_manager.clone();
+        tokio::spawn(async move {
+            let mut updates = door.subscribe_status();
+            let mut last = door.get_status().await.state;
+
+            loop {
+                let status = match updates.recv().await {
+                    Ok(status) => status,
+                    Err(RecvError::Lagged(_)) => door.get_status().await,
+                    Err(RecvError::Closed) => break,
+                };
+                if let Some(event) = DoorEvent::between(&last, &status.state) {
+                    notify(&client, &config_manager.get_webhooks_config(), event, &status);
+                }
+                last = status.state;
+            }
+        });
+    }
+}
+
+fn notify(client: &reqwest::Client, webhooks: &[WebhookConfig], event: DoorEvent, status: &DoorStatus) {
+    let payload = WebhookPayload {
+        event,
+        door: status.id.clone(),
+        state: status.state.clone(),
+        position_mm: status.position_mm,
+        position_percent: status.position_percent,
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0),
+        alarm_code: status.alarm_code.clone(),
+        alarm_description: status.alarm_description.clone(),
+        fault_message: status.fault_message.clone(),
+    };
+
+    for webhook in webhooks.iter().filter(|webhook| webhook.wants(event)) {
+        tokio::spawn(deliver(client.clone(), webhook.clone(), payload.clone()));
+    }
+}
+
+/// POST `payload`, retrying up to `webhook.retries` times until the endpoint answers 2xx
+async fn deliver(client: reqwest::Client, webhook: WebhookConfig, payload: WebhookPayload) {
+    let mut backoff = FIRST_BACKOFF;
+    for attempt in 0..=webhook.retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+
+        let mut request = client.post(&webhook.url).json(&payload);
+        for (name, value) in &webhook.headers {
+            request = request.header(name, value);
+        }
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                tracing::debug!("Webhook {} got {:?} for door '{}'", webhook.url, payload.event, payload.door);
+                return;
+            }
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+
+        if attempt < webhook.retries {
+            tracing::info!("Webhook {} failed ({}), retrying in {:?}", webhook.url, error, backoff);
+        } else {
+            tracing::warn!(
+                "Webhook {} failed ({}); gave up on {:?} for door '{}'",
+                webhook.url,
+                error,
+                payload.event,
+                payload.door
+            );
+        }
+    }
+}
@@ -1,251 +1,237 @@
 use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
+use shq_audit::{AuditEvent, AuditKind, AuditLog, AuditQuery};
+use shq_auth::Identity;
+use shq_config::{changed_sections, ConfigArchive};
+use shq_i18n::Translator;
+use shq_logging::LogHandle;
+use shq_protocol::{Health, ServiceInfo};
+use shq_ratelimit::{RateLimited, RateLimiter};
+use shq_systemd::Heartbeat;
+use shq_ws::{request_token, Broadcaster, Client, ClientHandle, ClientId, Handler, Request};
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, Mutex};
-use tokio::time::{interval, Duration};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
-
-use crate::config::ConfigManager;
-use crate::door::DoorController;
-use crate::messages::{ClientMessage, DoorStatus, ServerMessage};
-
-type ClientId = usize;
-
-/// WebSocket server for door control
-pub struct WebSocketServer {
-    addr: SocketAddr,
-    door: DoorController,
-    config_manager: Arc<Mutex<ConfigManager>>,
-    clients: Arc<Mutex<HashMap<ClientId, broadcast::Sender<String>>>>,
-    next_client_id: Arc<Mutex<ClientId>>,
-}
-
-impl WebSocketServer {
-    /// Create a new WebSocket server
-    pub fn new(addr: SocketAddr, door: DoorController, config_manager: ConfigManager) -> Self {
-        Self {
-            addr,
-            door,
-            config_manager: Arc::new(Mutex::new(config_manager)),
-            clients: Arc::new(Mutex::new(HashMap::new())),
-            next_client_id: Arc::new(Mutex::new(0)),
-        }
-    }
-
-    /// Start the WebSocket server
-    pub async fn start(self: Arc<Self>) -> Result<()> {
-        let listener = TcpListener::bind(self.addr).await?;
-        tracing::info!("WebSocket server listening on {}", self.addr);
-
-        // Start periodic status broadcast
-        self.start_status_broadcaster();
-
-        loop {
-            match listener.accept().await {
-                Ok((stream, peer_addr)) => {
-                    let server = self.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = server.handle_connection(stream, peer_addr).await {
-                            tracing::error!("Connection error from {}: {}", peer_addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    tracing::error!("Accept error: {}", e);
-                }
-            }
-        }
-    }
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
+use tracing::Instrument;
 
-    /// Start background task to broadcast status updates
-    fn start_status_broadcaster(&self) {
-        let door = self.door.clone();
-        let clients = self.clients.clone();
-        let mut status_rx = door.subscribe_status();
+use crate::arbiter::Arbiter;
+use crate::cnc_backup::{self, CncBackup};
+use crate::limit::{CommandLimiter, Limited};
+use crate::config::{ConfigManager, PRIMARY_DOOR};
+use crate::door::{DoorController, Doors, Hold};
+use crate::messages::{
+    ClientMessage, DoorState, DoorStatus, DoorTarget, ScheduleEntry, ServerMessage, CAPABILITIES, PROTOCOL_VERSION,
+};
 
-        tokio::spawn(async move {
-            let mut ticker = interval(Duration::from_secs(1));
-            let mut last_broadcast_status: Option<DoorStatus> = None;
+/// Broadcast topics every client starts on, each of which `set_subscriptions` can turn off:
+/// door status updates, status updates that only move the door along, `config_changed` and
+/// `command_accepted`
+const STATUS_TOPIC: &str = "status";
+const POSITION_TOPIC: &str = "position";
+const CONFIG_TOPIC: &str = "config";
+const EVENTS_TOPIC: &str = "events";
 
-            // Unified broadcaster: event-driven with fallback polling
-            loop {
-                tokio::select! {
-                    // Priority 1: Event-driven updates from position monitor (immediate)
-                    result = status_rx.recv() => {
-                        match result {
-                            Ok(status) => {
-                                // Only broadcast if status actually changed
-                                let should_broadcast = match &last_broadcast_status {
-                                    None => true,
-                                    Some(prev) => prev != &status,
-                                };
+/// Broadcast topic for live log lines (clients that sent `subscribe_logs`)
+const LOGS_TOPIC: &str = "logs";
 
-                                if should_broadcast {
-                                    let message = ServerMessage::Status {
-                                        version: env!("CARGO_PKG_VERSION").to_string(),
-                                        door: status.clone(),
-                                    };
-
-                                    if let Ok(json) = serde_json::to_string(&message) {
-                                        let clients_lock = clients.lock().await;
-                                        for (client_id, tx) in clients_lock.iter() {
-                                            if let Err(e) = tx.send(json.clone()) {
-                                                tracing::debug!("Failed to broadcast to client {}: {}", client_id, e);
-                                            }
-                                        }
-                                    }
-
-                                    last_broadcast_status = Some(status);
-                                }
-                            }
-                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
-                                tracing::warn!("Status broadcaster lagged, skipped {} messages", skipped);
-                            }
-                            Err(broadcast::error::RecvError::Closed) => {
-                                tracing::error!("Status channel closed, stopping broadcaster");
-                                break;
-                            }
-                        }
-                    }
+/// Broadcast topic for every door's CNC console (clients that sent `subscribe_console`)
+const CONSOLE_TOPIC: &str = "console";
 
-                    // Priority 2: Fallback polling for non-movement state changes (every 1 second)
-                    _ = ticker.tick() => {
-                        let status = door.get_status().await;
+/// Recent log lines sent to a new log subscriber unless it asks for a different amount
+const DEFAULT_LOG_BACKLOG: usize = 100;
 
-                        // Only broadcast if status has changed since last broadcast
-                        let should_broadcast = match &last_broadcast_status {
-                            None => true,
-                            Some(prev) => prev != &status,
-                        };
+/// Position stream rate unless the subscriber asks for a different one, and its limits
+const DEFAULT_POSITION_INTERVAL_MS: u64 = 100;
+const MIN_POSITION_INTERVAL_MS: u64 = 50;
+const MAX_POSITION_INTERVAL_MS: u64 = 1000;
 
-                        if should_broadcast {
-                            let message = ServerMessage::Status {
-                                version: env!("CARGO_PKG_VERSION").to_string(),
-                                door: status.clone(),
-                            };
+/// WebSocket protocol handler for door control
+pub struct WebSocketHandler {
+    doors: Doors,
+    config_manager: ConfigManager,
+    log_handle: LogHandle,
+    /// Client-facing message catalog
+    tr: Translator,
+    audit: AuditLog,
+    /// Position stream task of each client that sent `subscribe_position`
+    position_streams: Mutex<HashMap<ClientId, JoinHandle<()>>>,
+    /// Debounce, command rate limit and arbitration of motion commands (shared with the HTTP API)
+    motion_gate: Arc<MotionGate>,
+}
 
-                            if let Ok(json) = serde_json::to_string(&message) {
-                                let clients_lock = clients.lock().await;
-                                for (client_id, tx) in clients_lock.iter() {
-                                    if let Err(e) = tx.send(json.clone()) {
-                                        tracing::debug!("Failed to broadcast to client {}: {}", client_id, e);
-                                    }
-                                }
-                            }
+impl Handler for WebSocketHandler {
+    type Message = ServerMessage;
+    type State = Identity;
 
-                            last_broadcast_status = Some(status);
-                        }
-                    }
-                }
-            }
-        });
+    fn default_topics(&self) -> &'static [&'static str] {
+        &[STATUS_TOPIC, POSITION_TOPIC, CONFIG_TOPIC, EVENTS_TOPIC]
     }
 
-    /// Handle a new client connection
-    async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) -> Result<()> {
-        tracing::info!("New connection from {}", peer_addr);
-
-        let ws_stream = accept_async(stream).await?;
-        let (mut write, mut read) = ws_stream.split();
+    fn authorize(&self, request: &Request) -> Result<Identity, String> {
+        self.config_manager
+            .get_auth_config()
+            .authenticate(request_token(request).as_deref())
+            .map_err(|e| e.to_string())
+    }
 
-        // Register client
-        let client_id = self.register_client().await;
-        tracing::info!("Client {} registered from {}", client_id, peer_addr);
+    async fn on_connect(&self, client: &mut Client<Identity>) -> Vec<ServerMessage> {
+        self.audit
+            .record(AuditEvent::new(AuditKind::Connection, "connect").client(client.state.label(client.addr)));
 
-        // Send initial status
-        if let Ok(status_msg) = self.collect_status().await {
-            let msg = serde_json::to_string(&status_msg)?;
-            let _ = write.send(Message::Text(msg)).await;
+        // Send initial status of every door
+        let mut statuses = Vec::new();
+        for door in self.doors.iter() {
+            statuses.push(Self::collect_status(door).await);
         }
+        statuses
+    }
 
-        // Get broadcast receiver for this client
-        let mut rx = {
-            let clients = self.clients.lock().await;
-            clients.get(&client_id).unwrap().subscribe()
-        };
+    fn shutdown_message(&self) -> Option<ServerMessage> {
+        Some(ServerMessage::ServerShuttingDown)
+    }
 
-        loop {
-            tokio::select! {
-                // Handle incoming messages from client
-                msg = read.next() => {
-                    match msg {
-                        Some(Ok(Message::Text(text))) => {
-                            let response = match self.handle_message(&text).await {
-                                Ok(resp) => resp,
-                                Err(e) => {
-                                    // Send error response for invalid messages
-                                    tracing::warn!("Invalid message from client {}: {}", client_id, e);
-                                    ServerMessage::Error {
-                                        message: format!("Invalid command: {}", e),
-                                    }
-                                }
-                            };
+    fn rate_limited_message(&self, limited: &RateLimited) -> String {
+        self.tr.t("error.rate_limited", &[("retry_after_ms", &limited.retry_after_ms())])
+    }
 
-                            let response_json = serde_json::to_string(&response)?;
-                            write.send(Message::Text(response_json)).await?;
-                        }
-                        Some(Ok(Message::Close(_))) | None => {
-                            tracing::info!("Client {} disconnected", client_id);
-                            break;
-                        }
-                        Some(Err(e)) => {
-                            tracing::error!("WebSocket error from client {}: {}", client_id, e);
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-                // Handle broadcast messages to this client
-                Ok(broadcast_msg) = rx.recv() => {
-                    if let Err(e) = write.send(Message::Text(broadcast_msg)).await {
-                        tracing::error!("Failed to send broadcast to client {}: {}", client_id, e);
-                        break;
-                    }
+    async fn on_message(&self, client: &mut Client<Identity>, text: &str) -> ServerMessage {
+        match self.handle_message(client, text).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                // Send error response for invalid messages
+                tracing::warn!("Invalid message from client {}: {}", client.id, e);
+                ServerMessage::Error {
+                    message: self.tr.t("error.invalid_command", &[("error", &e)]),
                 }
             }
         }
+    }
 
-        // Unregister client
-        self.unregister_client(client_id).await;
-        tracing::info!("Client {} unregistered", client_id);
+    async fn on_disconnect(&self, client: &mut Client<Identity>) {
+        self.stop_position_stream(client.id);
+        self.audit
+            .record(AuditEvent::new(AuditKind::Connection, "disconnect").client(client.state.label(client.addr)));
+    }
+}
 
-        Ok(())
+impl WebSocketHandler {
+    /// Create a new WebSocket handler
+    pub fn new(
+        doors: Doors,
+        config_manager: ConfigManager,
+        log_handle: LogHandle,
+        tr: Translator,
+        audit: AuditLog,
+        motion_gate: Arc<MotionGate>,
+    ) -> Self {
+        Self {
+            doors,
+            config_manager,
+            log_handle,
+            tr,
+            audit,
+            position_streams: Mutex::default(),
+            motion_gate,
+        }
     }
 
-    /// Register a new client
-    async fn register_client(&self) -> ClientId {
-        let mut next_id = self.next_client_id.lock().await;
-        let client_id = *next_id;
-        *next_id += 1;
+    /// Stop the client's position stream, if it has one
+    fn stop_position_stream(&self, client_id: ClientId) {
+        if let Some(task) = self.position_streams.lock().unwrap().remove(&client_id) {
+            task.abort();
+        }
+    }
 
-        let (tx, _) = broadcast::channel(100);
-        self.clients.lock().await.insert(client_id, tx);
+    /// Handle a client message, recording privileged commands and their outcome in the audit log
+    async fn handle_message(&self, client: &Client<Identity>, text: &str) -> Result<ServerMessage> {
+        let message: ClientMessage = serde_json::from_str(text)?;
+        let target: DoorTarget = serde_json::from_str(text)?;
+        let Some(event) = message.audit_event(target.door.as_deref()) else {
+            return self.authorize_and_run(client, message, target).await;
+        };
 
-        client_id
+        let event = event.client(client.state.label(client.addr));
+        let result = self.authorize_and_run(client, message, target).await;
+        self.audit.record(match &result {
+            Ok(ServerMessage::Error { message })
+            | Ok(ServerMessage::Busy { message, .. })
+            | Ok(ServerMessage::CommandRateLimited { message, .. }) => event.failed(message),
+            Ok(_) => event,
+            Err(e) => event.failed(e),
+        });
+        result
     }
 
-    /// Unregister a client
-    async fn unregister_client(&self, client_id: ClientId) {
-        self.clients.lock().await.remove(&client_id);
+    /// Refuse commands the client's role doesn't allow, then run the command
+    async fn authorize_and_run(
+        &self,
+        client: &Client<Identity>,
+        message: ClientMessage,
+        target: DoorTarget,
+    ) -> Result<ServerMessage> {
+        let role = message.required_role();
+        if client.state.require(role).is_err() {
+            tracing::warn!("Refused command from {}: requires the {} role", client.state.label(client.addr), role);
+            return Ok(ServerMessage::Error {
+                message: self.tr.t("error.forbidden", &[("role", &role)]),
+            });
+        }
+        self.run_for_door(client, message, target).await
     }
 
-    /// Handle a client message
-    async fn handle_message(&self, text: &str) -> Result<ServerMessage> {
-        let message: ClientMessage = serde_json::from_str(text)?;
+    /// Run a parsed client command on the door it names
+    async fn run_for_door(
+        &self,
+        client: &Client<Identity>,
+        message: ClientMessage,
+        target: DoorTarget,
+    ) -> Result<ServerMessage> {
+        let Some(door) = self.doors.get(target.door.as_deref()) else {
+            return Ok(ServerMessage::Error {
+                message: self.tr.t("error.unknown_door", &[("door", &target.door.unwrap_or_default())]),
+            });
+        };
+        let label = client.state.label(client.addr);
+        if let Some(reply) = self.motion_gate.admit(door, label, client.addr.ip(), &message).await {
+            return Ok(reply);
+        }
+        self.run_command(client, message, door).await
+    }
 
+    /// Run a parsed client command; door commands go to `door`
+    async fn run_command(
+        &self,
+        client: &Client<Identity>,
+        message: ClientMessage,
+        door: &DoorController,
+    ) -> Result<ServerMessage> {
         match message {
+            ClientMessage::Hello { client: name, features } => {
+                tracing::info!("Hello from client {:?}", name.as_deref().unwrap_or("unknown"));
+                let info = ServiceInfo::new("dosa", env!("CARGO_PKG_VERSION"), PROTOCOL_VERSION, CAPABILITIES);
+                let info = match features {
+                    Some(features) => info.negotiate(&features),
+                    None => info,
+                };
+                if let Some(unsupported) = info.unsupported.as_ref().filter(|u| !u.is_empty()) {
+                    tracing::debug!("Client {} requested unsupported features: {:?}", client.id, unsupported);
+                }
+                Ok(ServerMessage::Hello(info))
+            }
             ClientMessage::Open => {
                 // Spawn open in background to avoid blocking WebSocket
                 // This allows the client to continue receiving status broadcasts during movement
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.open().await {
-                        tracing::error!("Open failed: {}", e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.open().await {
+                            tracing::error!("Open failed: {}", e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -257,12 +243,15 @@ impl WebSocketServer {
             ClientMessage::Close => {
                 // Spawn close in background to avoid blocking WebSocket
                 // This allows the client to continue receiving status broadcasts during movement
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.close().await {
-                        tracing::error!("Close failed: {}", e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.close().await {
+                            tracing::error!("Close failed: {}", e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -274,12 +263,15 @@ impl WebSocketServer {
             ClientMessage::Move { percent } => {
                 // Spawn move in background to avoid blocking WebSocket
                 // This allows the client to continue receiving status broadcasts during movement
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.move_to_percent(percent).await {
-                        tracing::error!("Move to {}% failed: {}", percent, e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.move_to_percent(percent).await {
+                            tracing::error!("Move to {}% failed: {}", percent, e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -288,15 +280,78 @@ impl WebSocketServer {
                     config: None,
                 })
             }
+            ClientMessage::OpenPreset { name } => {
+                let Some(percent) = door.get_config().await.presets.get(&name).copied() else {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.unknown_preset", &[("name", &name)]),
+                    });
+                };
+
+                // Same as move: run in the background so status broadcasts keep flowing
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        tracing::info!("Moving to preset '{}' ({}%)", name, percent);
+                        if let Err(e) = door.move_to_percent(percent).await {
+                            tracing::error!("Move to preset '{}' failed: {}", name, e);
+                        }
+                    }
+                    .in_current_span(),
+                );
+
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "open_preset".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::SetPreset { name, percent } => {
+                let mut config = door.get_config().await;
+                config.presets.insert(name, percent);
+
+                // Persist first so invalid values are rejected before reaching the door
+                if let Err(e) = self.config_manager.set_door_config(door.id(), config.clone()).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.preset_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+                door.update_config(config).await;
+
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "set_preset".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::RemovePreset { name } => {
+                let mut config = door.get_config().await;
+                if config.presets.remove(&name).is_none() {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.unknown_preset", &[("name", &name)]),
+                    });
+                }
+
+                self.config_manager.set_door_config(door.id(), config.clone()).await?;
+                door.update_config(config).await;
+
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "remove_preset".to_string(),
+                    config: None,
+                })
+            }
             ClientMessage::Jog { distance, feed_rate } => {
                 // Spawn jog in background to avoid blocking WebSocket
                 // This allows the client to continue receiving status broadcasts during movement
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.jog(distance, feed_rate).await {
-                        tracing::error!("Jog {} mm failed: {}", distance, e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.jog(distance, feed_rate).await {
+                            tracing::error!("Jog {} mm failed: {}", distance, e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -307,12 +362,15 @@ impl WebSocketServer {
             }
             ClientMessage::Home => {
                 // Spawn homing in background to avoid blocking WebSocket
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.home().await {
-                        tracing::error!("Homing failed: {}", e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.home().await {
+                            tracing::error!("Homing failed: {}", e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -322,9 +380,9 @@ impl WebSocketServer {
                 })
             }
             ClientMessage::Zero => {
-                if let Err(e) = self.door.zero().await {
+                if let Err(e) = door.zero().await {
                     return Ok(ServerMessage::Error {
-                        message: format!("Failed to zero door: {}", e),
+                        message: self.tr.t("error.zero_failed", &[("error", &e)]),
                     });
                 }
                 Ok(ServerMessage::Response {
@@ -333,10 +391,71 @@ impl WebSocketServer {
                     config: None,
                 })
             }
+            ClientMessage::TrustPosition => {
+                if let Err(e) = door.trust_position().await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.trust_position_failed", &[("error", &e)]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "trust_position".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::Calibrate { feed_rate, max_distance } => {
+                // Spawn calibration in background so a stop from this client can end it
+                let door = door.clone();
+                let config_manager = self.config_manager.clone();
+                tokio::spawn(
+                    async move {
+                        match door.calibrate(feed_rate, max_distance).await {
+                            Ok(distance) => {
+                                if let Err(e) = config_manager.set_door_config(door.id(), door.get_config().await).await {
+                                    tracing::error!("Failed to save calibrated open_distance {:.3} mm: {:#}", distance, e);
+                                }
+                            }
+                            Err(e) => tracing::error!("Calibration failed: {}", e),
+                        }
+                    }
+                    .in_current_span(),
+                );
+
+                // Return immediately so client can receive status broadcasts
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "calibrate".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::ManualMode => {
+                if let Err(e) = door.manual_mode().await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.manual_mode_failed", &[("error", &e)]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "manual_mode".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::Resume => {
+                if let Err(e) = door.resume().await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.resume_failed", &[("error", &e)]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "resume".to_string(),
+                    config: None,
+                })
+            }
             ClientMessage::ClearAlarm => {
-                if let Err(e) = self.door.clear_alarm().await {
+                if let Err(e) = door.clear_alarm().await {
                     return Ok(ServerMessage::Error {
-                        message: format!("Failed to clear alarm: {}", e),
+                        message: self.tr.t("error.clear_alarm_failed", &[("error", &e)]),
                     });
                 }
                 Ok(ServerMessage::Response {
@@ -345,15 +464,30 @@ impl WebSocketServer {
                     config: None,
                 })
             }
+            ClientMessage::ResetEstop => {
+                if let Err(e) = door.reset_estop().await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.reset_estop_failed", &[("error", &e)]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "reset_estop".to_string(),
+                    config: None,
+                })
+            }
             ClientMessage::Stop => {
                 // Spawn stop in background to avoid blocking WebSocket
                 // This allows the client to continue receiving status broadcasts during the stop sequence
-                let door = self.door.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = door.stop().await {
-                        tracing::error!("Stop failed: {}", e);
+                let door = door.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(e) = door.stop().await {
+                            tracing::error!("Stop failed: {}", e);
+                        }
                     }
-                });
+                    .in_current_span(),
+                );
 
                 // Return immediately so client can receive status broadcasts
                 Ok(ServerMessage::Response {
@@ -362,9 +496,93 @@ impl WebSocketServer {
                     config: None,
                 })
             }
+            ClientMessage::HoldOpen {
+                duration_secs,
+                indefinite,
+            } => {
+                let hold = match (duration_secs, indefinite) {
+                    (None, false) => Hold::UntilClosed,
+                    (None, true) => Hold::Indefinite,
+                    (Some(secs), false) if secs > 0 => Hold::Until(Instant::now() + Duration::from_secs(secs)),
+                    _ => {
+                        return Ok(ServerMessage::Error {
+                            message: self.tr.t("error.invalid_hold", &[]),
+                        });
+                    }
+                };
+                door.hold_open(hold).await?;
+
+                // A timed or indefinite hold opens the door first; open runs in the background
+                if hold != Hold::UntilClosed && door.needs_opening().await {
+                    let door = door.clone();
+                    tokio::spawn(
+                        async move {
+                            if let Err(e) = door.open().await {
+                                tracing::error!("Open for hold failed: {}", e);
+                            }
+                        }
+                        .in_current_span(),
+                    );
+                }
+
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "hold_open".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::ReleaseHold => {
+                door.release_hold().await?;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "release_hold".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::SetInterlock { reason } => {
+                if let Err(e) = door.set_interlock(&reason).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.interlock_failed", &[("error", &e)]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "set_interlock".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::ClearInterlock => {
+                door.clear_interlock().await;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "clear_interlock".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::GetHealth => {
+                // Other doors' components are prefixed with their id, e.g. "side.cnc"
+                let mut components = Vec::new();
+                for door in self.doors.iter() {
+                    let primary = door.id() == self.doors.primary().id();
+                    components.extend(door.health().await.into_iter().map(|mut component| {
+                        if !primary {
+                            component.name = format!("{}.{}", door.id(), component.name);
+                        }
+                        component
+                    }));
+                }
+                components.extend(self.audit.health());
+                let health = Health::new("dosa", components).with_last_crash(self.log_handle.last_crash());
+                Ok(ServerMessage::Health(health))
+            }
+            ClientMessage::GetStats => Ok(ServerMessage::Stats {
+                door: door.id().to_string(),
+                stats: door.cycle_stats().await,
+            }),
+
             ClientMessage::Status => {
                 // Return cached status (updated in real-time by position monitor and event broadcasts)
-                let status = self.door.get_status().await;
+                let status = door.get_status().await;
                 Ok(ServerMessage::Status {
                     version: env!("CARGO_PKG_VERSION").to_string(),
                     door: status,
@@ -372,13 +590,44 @@ impl WebSocketServer {
             }
             ClientMessage::RawStatus => {
                 // Query CNC controller directly and return raw response
-                match self.door.get_raw_status().await {
+                match door.get_raw_status().await {
                     Ok(raw) => Ok(ServerMessage::RawStatus { raw }),
                     Err(e) => Ok(ServerMessage::Error {
-                        message: format!("Failed to get raw status: {}", e),
+                        message: self.tr.t("error.raw_status_failed", &[("error", &e)]),
                     }),
                 }
             }
+            ClientMessage::RawCommand { command } => match door.raw_command(&command).await {
+                Ok(lines) => Ok(ServerMessage::RawResponse {
+                    door: door.id().to_string(),
+                    command,
+                    lines,
+                }),
+                Err(e) => Ok(ServerMessage::Error {
+                    message: self.tr.t("error.raw_command_failed", &[("error", &e)]),
+                }),
+            },
+            ClientMessage::SubscribeConsole => {
+                client.subscribe(CONSOLE_TOPIC).await;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "subscribe_console".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::UnsubscribeConsole => {
+                client.unsubscribe(CONSOLE_TOPIC).await;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "unsubscribe_console".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::GetSafetySensor => Ok(ServerMessage::SafetySensor {
+                door: door.id().to_string(),
+                config: door.get_config().await.safety_sensor,
+                state: door.get_status().await.safety_sensor,
+            }),
             ClientMessage::SetConfig {
                 open_distance,
                 open_speed,
@@ -386,8 +635,13 @@ impl WebSocketServer {
                 cnc_axis,
                 open_direction,
                 auto_home,
+                auto_close_after_secs,
+                obstruction,
+                safety_sensor,
+                motion,
+                timeouts,
             } => {
-                let mut config = self.door.get_config().await;
+                let mut config = door.get_config().await;
 
                 if let Some(dist) = open_distance {
                     config.open_distance = dist;
@@ -407,13 +661,25 @@ impl WebSocketServer {
                 if let Some(auto) = auto_home {
                     config.auto_home = auto;
                 }
+                if let Some(secs) = auto_close_after_secs {
+                    config.auto_close_after_secs = secs;
+                }
+                if let Some(obstruction) = obstruction {
+                    config.obstruction = obstruction;
+                }
+                if let Some(sensor) = safety_sensor {
+                    config.safety_sensor = sensor;
+                }
+                if let Some(motion) = motion {
+                    config.motion = motion;
+                }
+                if let Some(timeouts) = timeouts {
+                    config.timeouts = timeouts;
+                }
 
-                self.door.update_config(config.clone()).await;
-                self.config_manager
-                    .lock()
-                    .await
-                    .set_door_config(config)
-                    .await?;
+                // Persist first so invalid values are rejected before reaching the door
+                self.config_manager.set_door_config(door.id(), config.clone()).await?;
+                door.update_config(config).await;
 
                 Ok(ServerMessage::Response {
                     success: true,
@@ -422,7 +688,7 @@ impl WebSocketServer {
                 })
             }
             ClientMessage::GetConfig => {
-                let config = self.door.get_config().await;
+                let config = door.get_config().await;
                 Ok(ServerMessage::Response {
                     success: true,
                     command: "get_config".to_string(),
@@ -430,30 +696,309 @@ impl WebSocketServer {
                 })
             }
             ClientMessage::GetCncSettings => {
-                match self.door.query_cnc_settings().await {
+                match door.query_cnc_settings().await {
                     Ok(settings) => Ok(ServerMessage::CncSettings { settings }),
                     Err(e) => Ok(ServerMessage::Error {
-                        message: format!("Failed to query CNC settings: {}", e),
+                        message: self.tr.t("error.cnc_settings_failed", &[("error", &e)]),
                     }),
                 }
             }
             ClientMessage::GetCncSetting { setting } => {
-                match self.door.get_cnc_setting(&setting).await {
+                match door.get_cnc_setting(&setting).await {
                     Ok(value) => Ok(ServerMessage::CncSetting { setting, value }),
                     Err(e) => Ok(ServerMessage::Error {
-                        message: format!("Failed to get CNC setting {}: {}", setting, e),
+                        message: self.tr.t(
+                            "error.cnc_setting_get_failed",
+                            &[("setting", &setting), ("error", &e)],
+                        ),
                     }),
                 }
             }
             ClientMessage::SetCncSetting { setting, value } => {
-                match self.door.set_cnc_setting(&setting, &value).await {
+                match door.set_cnc_setting(&setting, &value).await {
                     Ok(()) => Ok(ServerMessage::Response {
                         success: true,
                         command: "set_cnc_setting".to_string(),
                         config: None,
                     }),
                     Err(e) => Ok(ServerMessage::Error {
-                        message: format!("Failed to set CNC setting {}={}: {}", setting, value, e),
+                        message: self.tr.t(
+                            "error.cnc_setting_set_failed",
+                            &[("setting", &setting), ("value", &value), ("error", &e)],
+                        ),
+                    }),
+                }
+            }
+            ClientMessage::DiffCncSettings => match door.diff_cnc_settings().await {
+                Ok(differences) => Ok(ServerMessage::CncSettingsDiff {
+                    door: door.id().to_string(),
+                    differences,
+                }),
+                Err(e) => Ok(ServerMessage::Error {
+                    message: self.tr.t("error.cnc_settings_failed", &[("error", &e)]),
+                }),
+            },
+            ClientMessage::BackupCncSettings => {
+                let settings = match door.query_cnc_settings().await {
+                    Ok(settings) => settings,
+                    Err(e) => {
+                        return Ok(ServerMessage::Error {
+                            message: self.tr.t("error.cnc_settings_failed", &[("error", &e)]),
+                        })
+                    }
+                };
+                let backup = CncBackup::new(settings);
+                let path = self.config_manager.cnc_backup_path();
+                if let Err(e) = cnc_backup::save(&path, door.id(), &backup).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.cnc_backup_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+                tracing::info!("Backed up {} CNC settings of door '{}'", backup.settings.len(), door.id());
+                Ok(ServerMessage::CncSettingsBackup {
+                    door: door.id().to_string(),
+                    settings: backup.settings,
+                    saved_ms: backup.saved_ms,
+                })
+            }
+            ClientMessage::RestoreCncSettings { settings, dry_run } => {
+                let settings = match settings {
+                    Some(settings) => settings,
+                    None => match cnc_backup::load(&self.config_manager.cnc_backup_path(), door.id()).await {
+                        Ok(Some(backup)) => backup.settings,
+                        Ok(None) => {
+                            return Ok(ServerMessage::Error {
+                                message: self.tr.t("error.no_cnc_backup", &[("door", &door.id())]),
+                            })
+                        }
+                        Err(e) => {
+                            return Ok(ServerMessage::Error {
+                                message: self.tr.t("error.cnc_restore_failed", &[("error", &format!("{:#}", e))]),
+                            })
+                        }
+                    },
+                };
+                match door.restore_cnc_settings(&settings, dry_run).await {
+                    Ok(differences) => Ok(ServerMessage::CncSettingsRestore {
+                        door: door.id().to_string(),
+                        dry_run,
+                        differences,
+                    }),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.cnc_restore_failed", &[("error", &format!("{:#}", e))]),
+                    }),
+                }
+            }
+            ClientMessage::RunScene { scene } => {
+                // Local door commands run to completion, so this reply comes once the door stops
+                let config = self.config_manager.get_scene_config();
+                match config.run(&scene, self.doors.primary()).await {
+                    Ok(report) => Ok(ServerMessage::SceneResult(report)),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.scene_failed", &[("error", &e)]),
+                    }),
+                }
+            }
+            ClientMessage::ListSchedules => {
+                let schedule = self.config_manager.get_schedule_config();
+                let jobs = schedule
+                    .jobs
+                    .iter()
+                    .map(|job| ScheduleEntry {
+                        job: job.clone(),
+                        next_run_ms: schedule.next_run(job).map(|t| t.timestamp_millis()),
+                    })
+                    .collect();
+                Ok(ServerMessage::Schedules {
+                    timezone: schedule.timezone,
+                    jobs,
+                })
+            }
+            ClientMessage::AddSchedule { job } => {
+                if let Err(e) = self.config_manager.add_schedule_job(job).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.schedule_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "add_schedule".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::RemoveSchedule { job_id } => {
+                if let Err(e) = self.config_manager.remove_schedule_job(&job_id).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.schedule_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "remove_schedule".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::SubscribeLogs { token, lines } => {
+                let stream_config = self.config_manager.get_logging_config().stream;
+                if let Err(e) = stream_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.logs_refused", &[("error", &e)]),
+                    });
+                }
+
+                let (backlog, _) = self
+                    .log_handle
+                    .subscribe(lines.unwrap_or(DEFAULT_LOG_BACKLOG));
+                client.subscribe(LOGS_TOPIC).await;
+                Ok(ServerMessage::Logs { lines: backlog })
+            }
+            ClientMessage::UnsubscribeLogs => {
+                client.unsubscribe(LOGS_TOPIC).await;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "unsubscribe_logs".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::SubscribePosition { interval_ms } => {
+                let interval_ms = interval_ms.unwrap_or(DEFAULT_POSITION_INTERVAL_MS);
+                if !(MIN_POSITION_INTERVAL_MS..=MAX_POSITION_INTERVAL_MS).contains(&interval_ms) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t(
+                            "error.position_interval",
+                            &[("min", &MIN_POSITION_INTERVAL_MS), ("max", &MAX_POSITION_INTERVAL_MS)],
+                        ),
+                    });
+                }
+
+                // A new subscription replaces the old one, so the rate can be changed
+                let task = start_position_stream(
+                    self.doors.clone(),
+                    client.handle(),
+                    Duration::from_millis(interval_ms),
+                );
+                if let Some(old) = self.position_streams.lock().unwrap().insert(client.id, task) {
+                    old.abort();
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "subscribe_position".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::UnsubscribePosition => {
+                self.stop_position_stream(client.id);
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "unsubscribe_position".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::SetSubscriptions { status, position, config, events } => {
+                let topics = [
+                    (STATUS_TOPIC, status),
+                    (POSITION_TOPIC, position),
+                    (CONFIG_TOPIC, config),
+                    (EVENTS_TOPIC, events),
+                ];
+                for (topic, subscribe) in topics {
+                    match subscribe {
+                        Some(true) => client.subscribe(topic).await,
+                        Some(false) => client.unsubscribe(topic).await,
+                        None => {}
+                    }
+                }
+                Ok(ServerMessage::Subscriptions {
+                    status: client.is_subscribed(STATUS_TOPIC).await,
+                    position: client.is_subscribed(POSITION_TOPIC).await,
+                    config: client.is_subscribed(CONFIG_TOPIC).await,
+                    events: client.is_subscribed(EVENTS_TOPIC).await,
+                })
+            }
+            ClientMessage::ExportConfig { token } => {
+                let backup_config = self.config_manager.get_backup_config();
+                if let Err(e) = backup_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.export_refused", &[("error", &e)]),
+                    });
+                }
+
+                let entry = self.config_manager.export()?;
+                Ok(ServerMessage::ConfigArchive {
+                    archive: ConfigArchive::single(entry),
+                })
+            }
+            ClientMessage::ImportConfig { token, archive, entry } => {
+                let backup_config = self.config_manager.get_backup_config();
+                if let Err(e) = backup_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.import_refused", &[("error", &e)]),
+                    });
+                }
+
+                let result = match archive.entry_for("dosa", entry.as_deref()) {
+                    Ok(entry) => self.config_manager.import(entry).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.import_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+
+                // Door settings apply before the reply (the config reloader takes care of limits,
+                // locale and logging); connection and added or removed door changes on restart
+                let doors = self.config_manager.get_doors_config();
+                for door in self.doors.iter() {
+                    let config = match doors.get(door.id()) {
+                        Some(config) => config.clone(),
+                        None => self.config_manager.get_door_config(),
+                    };
+                    door.update_config(config).await;
+                }
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "import_config".to_string(),
+                    config: None,
+                })
+            }
+            ClientMessage::GetAuditLog { token, after_seq, limit, kind } => {
+                let audit_config = self.config_manager.get_audit_config();
+                if let Err(e) = audit_config.query.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_refused", &[("error", &e)]),
+                    });
+                }
+
+                match self.audit.query(&AuditQuery {
+                    after_seq,
+                    limit,
+                    kinds: kind.into_iter().collect(),
+                    ..Default::default()
+                }) {
+                    Ok(page) => Ok(ServerMessage::AuditLog(page)),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_failed", &[("error", &format!("{:#}", e))]),
+                    }),
+                }
+            }
+            ClientMessage::GetHistory { token, since, limit } => {
+                let audit_config = self.config_manager.get_audit_config();
+                if let Err(e) = audit_config.query.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_refused", &[("error", &e)]),
+                    });
+                }
+
+                match self.audit.query(&AuditQuery {
+                    since_ms: since,
+                    limit,
+                    kinds: vec![AuditKind::Command, AuditKind::State],
+                    ..Default::default()
+                }) {
+                    Ok(page) => Ok(ServerMessage::History { records: page.records }),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_failed", &[("error", &format!("{:#}", e))]),
                     }),
                 }
             }
@@ -465,25 +1010,368 @@ impl WebSocketServer {
         }
     }
 
-    /// Collect and return current status
-    async fn collect_status(&self) -> Result<ServerMessage> {
-        let status = self.door.get_status().await;
+    /// Collect and return the current status of `door`
+    async fn collect_status(door: &DoorController) -> ServerMessage {
+        let status = door.get_status().await;
 
-        Ok(ServerMessage::Status {
+        ServerMessage::Status {
             version: env!("CARGO_PKG_VERSION").to_string(),
             door: status,
-        })
+        }
     }
 }
 
-impl Clone for WebSocketServer {
-    fn clone(&self) -> Self {
+/// Decides whether motion commands run, for both the WebSocket and the HTTP API: repeats are
+/// debounced and clients rate limited (`command_limit`), then the door is claimed (`arbiter`)
+pub struct MotionGate {
+    arbiter: Arbiter,
+    limiter: CommandLimiter,
+    /// For announcing accepted motion commands to every WebSocket client
+    broadcaster: Broadcaster,
+    tr: Translator,
+}
+
+impl MotionGate {
+    pub fn new(config_manager: ConfigManager, broadcaster: Broadcaster, tr: Translator) -> Self {
         Self {
-            addr: self.addr,
-            door: self.door.clone(),
-            config_manager: self.config_manager.clone(),
-            clients: self.clients.clone(),
-            next_client_id: self.next_client_id.clone(),
+            arbiter: Arbiter::default(),
+            limiter: CommandLimiter::new(config_manager),
+            broadcaster,
+            tr,
+        }
+    }
+
+    /// The reply to send instead of running `message` on `door`, or `None` to run it
+    ///
+    /// Only motion commands are checked. `client` is the arbiter's label for the sender, `ip`
+    /// its address for the rate limit. A debounced repeat gets the command's usual `response`.
+    pub async fn admit(
+        &self,
+        door: &DoorController,
+        client: String,
+        ip: IpAddr,
+        message: &ClientMessage,
+    ) -> Option<ServerMessage> {
+        let (command, priority) = message.motion_priority()?;
+
+        // Refused here rather than failing after the command was accepted
+        let status = door.get_status().await;
+        if let Some(reason) = status.interlock.filter(|_| message.opens_door(status.position_percent)) {
+            tracing::info!("Refused {} from {}: door '{}' is interlocked ({})", command, client, door.id(), reason);
+            return Some(ServerMessage::Error {
+                message: self.tr.t("error.interlocked", &[("reason", &reason)]),
+            });
+        }
+
+        match self.limiter.check(ip, door.id(), message) {
+            Ok(()) => {}
+            Err(Limited::Debounced) => {
+                tracing::debug!("Ignoring repeated {} for door '{}' from {}", command, door.id(), client);
+                return Some(ServerMessage::Response {
+                    success: true,
+                    command: command.to_string(),
+                    config: None,
+                });
+            }
+            Err(Limited::RateLimited(limited)) => {
+                tracing::warn!("Refused {} from {}: too many motion commands", command, client);
+                return Some(ServerMessage::CommandRateLimited {
+                    message: self.tr.t("error.rate_limited", &[("retry_after_ms", &limited.retry_after_ms())]),
+                    retry_after_ms: limited.retry_after_ms(),
+                });
+            }
+        }
+
+        let moving = matches!(
+            door.get_status().await.state,
+            DoorState::Opening | DoorState::Closing | DoorState::Homing | DoorState::Calibrating | DoorState::Halting
+        );
+        match self.arbiter.claim(door.id(), moving, &client, command, priority) {
+            Ok(_) => {
+                let accepted = ServerMessage::CommandAccepted {
+                    door: door.id().to_string(),
+                    command: command.to_string(),
+                    client,
+                };
+                if let Err(e) = self.broadcaster.broadcast(EVENTS_TOPIC, &accepted).await {
+                    tracing::error!("Failed to broadcast accepted command: {}", e);
+                }
+                None
+            }
+            Err(held_by) => {
+                tracing::info!(
+                    "Refused {} from {}: door '{}' is busy with {} from {}",
+                    command,
+                    client,
+                    door.id(),
+                    held_by.command,
+                    held_by.client
+                );
+                Some(ServerMessage::Busy {
+                    door: door.id().to_string(),
+                    command: command.to_string(),
+                    message: self.tr.t("error.busy", &[("command", &held_by.command), ("client", &held_by.client)]),
+                    held_by,
+                })
+            }
         }
     }
 }
+
+/// Start a task that applies config changes to the running service and tells clients about them
+///
+/// Covers hand edits picked up by [`ConfigManager::watch`] as well as API updates and imports.
+/// Door settings, rate limits, locale and logging apply at once; a door's `cnc_connection`,
+/// added or removed doors and the `websocket` section (host, port, TLS, keep-alive) wait for a
+/// restart.
+pub fn start_config_reloader(
+    config_manager: &ConfigManager,
+    doors: Doors,
+    broadcaster: Broadcaster,
+    rate_limiter: RateLimiter,
+    translator: Translator,
+    log_handle: LogHandle,
+) {
+    let mut config_rx = config_manager.subscribe();
+
+    tokio::spawn(async move {
+        let mut current = config_rx.borrow_and_update().clone();
+        while config_rx.changed().await.is_ok() {
+            let config = config_rx.borrow_and_update().clone();
+            let sections = changed_sections(
+                &serde_json::to_value(&current).unwrap_or_default(),
+                &serde_json::to_value(&config).unwrap_or_default(),
+            );
+            if sections.is_empty() {
+                continue;
+            }
+
+            for door in doors.iter() {
+                let new_config = match door.id() {
+                    PRIMARY_DOOR => Some(config.door.clone()),
+                    id => config.doors.get(id).cloned(),
+                };
+                let Some(mut new_config) = new_config else {
+                    tracing::warn!("Door '{}' was removed from the config; it stays until a restart", door.id());
+                    continue;
+                };
+
+                let running = door.get_config().await;
+                if new_config.cnc_connection != running.cnc_connection {
+                    tracing::warn!("Door '{}': cnc_connection changes apply on restart", door.id());
+                    new_config.cnc_connection = running.cnc_connection.clone();
+                }
+                if new_config != running {
+                    door.update_config(new_config).await;
+                    tracing::info!("Applied new configuration to door '{}'", door.id());
+                }
+            }
+            for id in config.doors.keys().filter(|id| doors.get(Some(id.as_str())).is_none()) {
+                tracing::warn!("Door '{}' was added to the config; it starts on restart", id);
+            }
+            if config.websocket != current.websocket {
+                tracing::warn!("WebSocket settings change on restart");
+            }
+
+            if config.rate_limit != current.rate_limit {
+                rate_limiter.update(config.rate_limit.clone());
+            }
+            if config.i18n != current.i18n {
+                if let Err(e) = translator.update(&config.i18n) {
+                    tracing::warn!("Failed to apply i18n config: {:#}", e);
+                }
+            }
+            if config.logging != current.logging {
+                if let Err(e) = log_handle.apply(&config.logging) {
+                    tracing::warn!("Failed to apply logging config: {:#}", e);
+                }
+            }
+
+            let message = ServerMessage::ConfigChanged { sections };
+            if let Err(e) = broadcaster.broadcast(CONFIG_TOPIC, &message).await {
+                tracing::error!("Failed to broadcast config change: {}", e);
+            }
+            current = config;
+        }
+    });
+}
+
+/// Start background task to broadcast status updates, beating `heartbeat` on every poll
+pub fn start_status_broadcaster(door: DoorController, broadcaster: Broadcaster, heartbeat: Heartbeat) {
+    let mut status_rx = door.subscribe_status();
+
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(1));
+        let mut last_broadcast_status: Option<DoorStatus> = None;
+
+        // Unified broadcaster: event-driven with fallback polling
+        loop {
+            tokio::select! {
+                // Priority 1: Event-driven updates from position monitor (immediate)
+                result = status_rx.recv() => {
+                    match result {
+                        Ok(status) => {
+                            // Only broadcast if status actually changed
+                            let should_broadcast = match &last_broadcast_status {
+                                None => true,
+                                Some(prev) => prev != &status,
+                            };
+
+                            if should_broadcast {
+                                let message = ServerMessage::Status {
+                                    version: env!("CARGO_PKG_VERSION").to_string(),
+                                    door: status.clone(),
+                                };
+
+                                let topic = status_topic(last_broadcast_status.as_ref(), &status);
+                                if let Err(e) = broadcaster.broadcast(topic, &message).await {
+                                    tracing::error!("Failed to broadcast status: {}", e);
+                                }
+
+                                last_broadcast_status = Some(status);
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            tracing::warn!("Status broadcaster lagged, skipped {} messages", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            tracing::error!("Status channel closed, stopping broadcaster");
+                            break;
+                        }
+                    }
+                }
+
+                // Priority 2: Fallback polling for non-movement state changes (every 1 second)
+                _ = ticker.tick() => {
+                    let status = door.get_status().await;
+
+                    // Reaching here means the door state lock isn't wedged
+                    heartbeat.beat();
+
+                    // Only broadcast if status has changed since last broadcast
+                    let should_broadcast = match &last_broadcast_status {
+                        None => true,
+                        Some(prev) => prev != &status,
+                    };
+
+                    if should_broadcast {
+                        let message = ServerMessage::Status {
+                            version: env!("CARGO_PKG_VERSION").to_string(),
+                            door: status.clone(),
+                        };
+
+                        let topic = status_topic(last_broadcast_status.as_ref(), &status);
+                        if let Err(e) = broadcaster.broadcast(topic, &message).await {
+                            tracing::error!("Failed to broadcast status: {}", e);
+                        }
+
+                        last_broadcast_status = Some(status);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Topic of a status update: `position` if it only moves the door along from `previous`
+fn status_topic(previous: Option<&DoorStatus>, status: &DoorStatus) -> &'static str {
+    let Some(previous) = previous else {
+        return STATUS_TOPIC;
+    };
+    let unmoved = DoorStatus {
+        position_mm: previous.position_mm,
+        position_percent: previous.position_percent,
+        progress_percent: previous.progress_percent,
+        feed_rate: previous.feed_rate,
+        eta_secs: previous.eta_secs,
+        ..status.clone()
+    };
+    if unmoved == *previous {
+        POSITION_TOPIC
+    } else {
+        STATUS_TOPIC
+    }
+}
+
+/// Start a task that sends `client` the position of each door that's moving, every `every`
+fn start_position_stream(doors: Doors, client: ClientHandle, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            for door in doors.iter() {
+                let status = door.get_status().await;
+                if !matches!(status.state, DoorState::Opening | DoorState::Closing | DoorState::Halting) {
+                    continue;
+                }
+
+                // The cached status only moves at the position monitor's pace
+                let (position_mm, position_percent) = door
+                    .live_position()
+                    .await
+                    .unwrap_or((status.position_mm, status.position_percent));
+                let message = ServerMessage::Position {
+                    door: door.id().to_string(),
+                    state: status.state,
+                    position_mm,
+                    position_percent,
+                };
+                if let Err(e) = client.send(&message).await {
+                    tracing::error!("Failed to send position to client {}: {}", client.id, e);
+                }
+            }
+        }
+    })
+}
+
+/// Start background task to forward log output to clients subscribed to logs
+pub fn start_log_forwarder(log_handle: LogHandle, broadcaster: Broadcaster) {
+    let (_, mut log_rx) = log_handle.subscribe(0);
+
+    tokio::spawn(async move {
+        loop {
+            match log_rx.recv().await {
+                Ok(line) => {
+                    if broadcaster.has_subscribers(LOGS_TOPIC).await {
+                        let _ = broadcaster.broadcast(LOGS_TOPIC, &ServerMessage::Log(line)).await;
+                    }
+                }
+                // Dropped lines are acceptable for a debugging stream
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Start background task to forward a door's CNC console to clients subscribed to it
+pub fn start_console_forwarder(door: DoorController, broadcaster: Broadcaster) {
+    tokio::spawn(async move {
+        loop {
+            // Follow the current connection; a reconnect replaces it
+            let mut console = door.subscribe_console().await;
+
+            loop {
+                match console.recv().await {
+                    Ok(line) => {
+                        if broadcaster.has_subscribers(CONSOLE_TOPIC).await {
+                            let message = ServerMessage::Console {
+                                door: door.id().to_string(),
+                                line,
+                            };
+                            let _ = broadcaster.broadcast(CONSOLE_TOPIC, &message).await;
+                        }
+                    }
+                    // Dropped lines are acceptable for a debugging stream
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+
+            // The connection is gone; look again once a reconnect may have replaced it
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Only the overwatch tests need the gRPC client, and with it protoc
+    #[cfg(feature = "overwatch")]
+    tonic_build::configure()
+        .build_server(false)
+        .compile(&["../overwatch/proto/voice.proto"], &["../overwatch/proto"])?;
+    Ok(())
+}
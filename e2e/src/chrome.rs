@@ -0,0 +1,274 @@
+//! Stand-in for Chromium's remote debugging port, so nyx's CDP calls can be tested.
+
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Base64 image data every `Page.captureScreenshot` returns (a PNG signature)
+pub const SCREENSHOT: &str = "iVBORw0KGgo=";
+
+/// An open tab
+#[derive(Debug, Clone)]
+pub struct MockTab {
+    pub id: String,
+    pub url: String,
+}
+
+#[derive(Default)]
+struct State {
+    tabs: Vec<MockTab>,
+    next_tab: u32,
+    /// Tab ids in the order `/json/activate` brought them to the front
+    activated: Vec<String>,
+    commands: Vec<Value>,
+    connections: usize,
+    /// Debugger WebSocket tasks, aborted by `restart`
+    sockets: Vec<JoinHandle<()>>,
+    /// Event queues of the open debugger WebSockets, fed by `emit`
+    events: Vec<mpsc::UnboundedSender<String>>,
+}
+
+/// Chrome with one tab to start with; stops listening when dropped
+///
+/// `/json/list` lists the tabs and `/json/new`, `/json/close/<id>` and `/json/activate/<id>`
+/// change them. Each tab's debugger WebSocket records every CDP command (with the tab's id
+/// as `tab`) and answers it with an empty result, [`SCREENSHOT`] for a screenshot, or the
+/// expression itself for `Runtime.evaluate` (an exception if it starts with `throw`);
+/// `Page.navigate` changes the tab's URL. [`MockChrome::emit`] sends an event down every
+/// open debugger WebSocket.
+pub struct MockChrome {
+    port: u16,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockChrome {
+    pub async fn start(url: &str) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let state = Arc::new(Mutex::new(State::default()));
+        add_tab(&mut state.lock().unwrap(), url);
+
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = task_state.clone();
+                let socket_state = state.clone();
+                let socket = tokio::spawn(async move {
+                    let _ = serve(stream, port, socket_state).await;
+                });
+                state.lock().unwrap().sockets.push(socket);
+            }
+        });
+
+        Ok(Self { port, state, task })
+    }
+
+    /// Address for nyx's `NYX_CDP_ADDR`
+    pub fn addr(&self) -> String {
+        format!("127.0.0.1:{}", self.port)
+    }
+
+    /// The first tab's URL
+    pub fn url(&self) -> String {
+        self.tabs().first().map(|tab| tab.url.clone()).unwrap_or_default()
+    }
+
+    pub fn tabs(&self) -> Vec<MockTab> {
+        self.state.lock().unwrap().tabs.clone()
+    }
+
+    /// Tab ids brought to the front, oldest first
+    pub fn activated(&self) -> Vec<String> {
+        self.state.lock().unwrap().activated.clone()
+    }
+
+    /// Every CDP command so far (`{ tab, id, method, params }`)
+    pub fn commands(&self) -> Vec<Value> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// Debugger WebSocket connections opened so far
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    /// Send CDP event `method` with `params` to every open debugger WebSocket
+    pub fn emit(&self, method: &str, params: Value) {
+        let event = json!({ "method": method, "params": params }).to_string();
+        self.state
+            .lock()
+            .unwrap()
+            .events
+            .retain(|events| events.send(event.clone()).is_ok());
+    }
+
+    /// Drop every open connection, as a Chrome restart would (the tabs stay)
+    pub fn restart(&self) {
+        for socket in self.state.lock().unwrap().sockets.drain(..) {
+            socket.abort();
+        }
+    }
+
+    /// Wait until a command matches `predicate`
+    pub async fn wait_for(&self, wait: Duration, predicate: impl Fn(&Value) -> bool) -> Result<Value> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(command) = self.commands().into_iter().find(|command| predicate(command)) {
+                return Ok(command);
+            }
+            if Instant::now() > deadline {
+                anyhow::bail!("No matching CDP command within {:?}; got {:?}", wait, self.commands());
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for MockChrome {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.restart();
+    }
+}
+
+fn add_tab(state: &mut State, url: &str) -> MockTab {
+    state.next_tab += 1;
+    let tab = MockTab {
+        id: format!("TAB{}", state.next_tab),
+        url: url.to_string(),
+    };
+    state.tabs.push(tab.clone());
+    tab
+}
+
+fn target(tab: &MockTab, port: u16) -> Value {
+    json!({
+        "id": tab.id,
+        "type": "page",
+        "title": "",
+        "url": tab.url,
+        "webSocketDebuggerUrl": format!("ws://127.0.0.1:{}/devtools/page/{}", port, tab.id),
+    })
+}
+
+/// Answer one `/json` request, or take the connection over as a tab's debugger WebSocket
+async fn serve(mut stream: TcpStream, port: u16, state: Arc<Mutex<State>>) -> Result<()> {
+    let mut request_line = String::new();
+    {
+        // Peek, so the WebSocket handshake still sees the whole request
+        let mut start = [0u8; 256];
+        let read = stream.peek(&mut start).await?;
+        let mut reader = BufReader::new(&start[..read]);
+        reader.read_line(&mut request_line).await?;
+    }
+    let mut words = request_line.split_whitespace();
+    let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+
+    if let Some(tab) = path.strip_prefix("/devtools/page/") {
+        return debug(stream, tab.to_string(), state).await;
+    }
+
+    let (status, body) = {
+        let mut state = state.lock().unwrap();
+        if path == "/json" || path == "/json/list" {
+            (200, json!(state.tabs.iter().map(|tab| target(tab, port)).collect::<Vec<_>>()))
+        } else if let Some(url) = path.strip_prefix("/json/new?").filter(|_| method == "PUT") {
+            let tab = add_tab(&mut state, url);
+            (200, target(&tab, port))
+        } else if let Some(id) = path.strip_prefix("/json/close/") {
+            let before = state.tabs.len();
+            state.tabs.retain(|tab| tab.id != id);
+            if state.tabs.len() < before {
+                (200, json!("Target is closing"))
+            } else {
+                (404, json!(format!("No such target id: {}", id)))
+            }
+        } else if let Some(id) = path.strip_prefix("/json/activate/") {
+            if state.tabs.iter().any(|tab| tab.id == id) {
+                state.activated.push(id.to_string());
+                (200, json!("Target activated"))
+            } else {
+                (404, json!(format!("No such target id: {}", id)))
+            }
+        } else {
+            (404, json!("Unknown request"))
+        }
+    };
+
+    // Drain the request before answering
+    let mut request = vec![0u8; 4096];
+    let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut request).await;
+
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        if status == 200 { "OK" } else { "Not Found" },
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Record and answer the CDP commands sent to `tab`
+async fn debug(stream: TcpStream, tab: String, state: Arc<Mutex<State>>) -> Result<()> {
+    let mut ws = tokio_tungstenite::accept_async(stream).await?;
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    {
+        let mut state = state.lock().unwrap();
+        state.connections += 1;
+        state.events.push(events_tx);
+    }
+
+    loop {
+        let message = tokio::select! {
+            message = ws.next() => message,
+            Some(event) = events.recv() => {
+                ws.send(Message::Text(event)).await?;
+                continue;
+            }
+        };
+        let Some(Ok(message)) = message else {
+            break;
+        };
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let mut command: Value = serde_json::from_str(&text)?;
+        let expression = command["params"]["expression"].as_str().unwrap_or_default();
+        let result = if command["method"] == "Page.captureScreenshot" {
+            json!({ "data": SCREENSHOT })
+        } else if command["method"] == "Runtime.evaluate" && expression.starts_with("throw") {
+            json!({ "result": {}, "exceptionDetails": { "text": "Uncaught", "exception": { "description": expression } } })
+        } else if command["method"] == "Runtime.evaluate" {
+            json!({ "result": { "type": "string", "value": expression } })
+        } else {
+            json!({})
+        };
+        let reply = json!({ "id": command["id"], "result": result });
+        {
+            let mut state = state.lock().unwrap();
+            if command["method"] == "Page.navigate" {
+                if let (Some(url), Some(open)) = (
+                    command["params"]["url"].as_str(),
+                    state.tabs.iter_mut().find(|open| open.id == tab),
+                ) {
+                    open.url = url.to_string();
+                }
+            }
+            command["tab"] = json!(tab);
+            state.commands.push(command);
+        }
+        ws.send(Message::Text(reply.to_string())).await?;
+    }
+    Ok(())
+}
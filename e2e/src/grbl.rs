@@ -0,0 +1,603 @@
+//! Simulated grblHAL controller on a local TCP port, for dosa to drive.
+//!
+//! Implements the slice of the protocol dosa uses: `?` status reports, `$H`/`$H<axis>`
+//! homing (a soft reset aborts it), `G1`/`$J=` moves with real-time interpolation at the requested feed rate (shown
+//! in `FS:`; `G1` lines sent during a move are queued like grblHAL's planner buffer and run
+//! back to back),
+//! `$$`/`$N=value` settings (`$481` pushes a status report every that many ms), `G92`
+//! work offsets (reports stay in `MPos`), `$X` unlock and the feed hold (`!`), queue flush
+//! (Ctrl-Y) and soft reset (Ctrl-X) real-time bytes. Only the X, Y and Z axes exist.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{Duration, Instant};
+
+const FEED_HOLD: u8 = b'!';
+const QUEUE_FLUSH: u8 = 0x19;
+const SOFT_RESET: u8 = 0x18;
+const JOG_CANCEL: u8 = 0x85;
+const CYCLE_START: u8 = b'~';
+/// grblHAL's top-bit alternative to `~`
+const CYCLE_START_ALT: u8 = 0x81;
+
+/// How long `$H` takes before reporting `ok`, unless `set_homing_time` changes it
+const HOMING_TIME: Duration = Duration::from_millis(300);
+
+const AXES: [char; 3] = ['X', 'Y', 'Z'];
+
+/// Mock grblHAL server; stops listening and drops its connections when dropped
+pub struct MockGrbl {
+    addr: SocketAddr,
+    machine: Arc<Mutex<Machine>>,
+    /// Unsolicited lines (`ALARM:`) for every connection
+    lines: broadcast::Sender<String>,
+    task: JoinHandle<()>,
+}
+
+impl MockGrbl {
+    /// Listen on a free localhost port
+    pub async fn start() -> Result<Self> {
+        Self::start_on(0).await
+    }
+
+    /// Listen on `port`, e.g. to bring back a controller after dropping the previous mock
+    pub async fn start_on(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let addr = listener.local_addr()?;
+        let machine = Arc::new(Mutex::new(Machine::default()));
+
+        let (lines, _) = broadcast::channel(16);
+
+        let accept_machine = machine.clone();
+        let accept_lines = lines.clone();
+        let task = tokio::spawn(async move {
+            // Aborted along with this task, closing every connection
+            let mut connections = JoinSet::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.spawn(serve(stream, accept_machine.clone(), accept_lines.subscribe()));
+            }
+        });
+
+        Ok(Self {
+            addr,
+            machine,
+            lines,
+            task,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Current machine position of `axis`
+    pub fn position(&self, axis: char) -> f64 {
+        let mut machine = self.machine.lock().unwrap();
+        machine.update();
+        machine.position[axis_index(axis).expect("unknown axis")]
+    }
+
+    /// Put the controller into alarm `code`, stopping any motion (e.g. a limit switch hit),
+    /// and announce it with an `ALARM:<code>` line
+    pub fn trigger_alarm(&self, code: u32) {
+        {
+            let mut machine = self.machine.lock().unwrap();
+            machine.update();
+            machine.motion = None;
+            machine.alarm = Some(code);
+        }
+        let _ = self.lines.send(format!("ALARM:{}", code));
+    }
+
+    /// Add (or replace) a field in every status report, e.g. a motor load `Ld:95,0,0`
+    pub fn set_report_field(&self, name: &str, value: &str) {
+        let mut machine = self.machine.lock().unwrap();
+        machine
+            .report_fields
+            .insert(name.to_string(), value.to_string());
+    }
+
+    /// Drop a field added with `set_report_field`
+    pub fn remove_report_field(&self, name: &str) {
+        self.machine.lock().unwrap().report_fields.remove(name);
+    }
+
+    /// Open the safety door input: motion stops where it is and waits, reported as `Door:1`
+    pub fn open_safety_door(&self) {
+        let mut machine = self.machine.lock().unwrap();
+        machine.update();
+        machine.suspended = machine
+            .motion
+            .take()
+            .map(|motion| (motion.to, motion.feed));
+        machine.safety_door = Some(1);
+    }
+
+    /// Close the safety door input (`Door:0`); a cycle start then resumes the stopped motion
+    pub fn close_safety_door(&self) {
+        let mut machine = self.machine.lock().unwrap();
+        if machine.safety_door.is_some() {
+            machine.safety_door = Some(0);
+        }
+    }
+
+    /// Make each homing cycle take `time` instead of the default 300ms
+    pub fn set_homing_time(&self, time: Duration) {
+        self.machine.lock().unwrap().homing_time = time;
+    }
+
+    /// How many `?` status queries were received
+    pub fn status_queries(&self) -> usize {
+        self.machine.lock().unwrap().status_queries
+    }
+
+    /// Every line command received so far, oldest first (real-time bytes excluded)
+    pub fn commands(&self) -> Vec<String> {
+        self.machine.lock().unwrap().commands.clone()
+    }
+}
+
+impl Drop for MockGrbl {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Machine {
+    position: [f64; 3],
+    motion: Option<Motion>,
+    /// `G1` targets and feed rates waiting behind `motion`
+    planned: VecDeque<([f64; 3], f64)>,
+    /// Motion paused by a feed hold
+    held: bool,
+    alarm: Option<u32>,
+    /// Steppers disabled by `$SLP` until a soft reset
+    asleep: bool,
+    /// Safety door state (`Door:<n>`) while the input has stopped the machine
+    safety_door: Option<u32>,
+    /// Target and feed of the move the safety door stopped, resumed by a cycle start
+    suspended: Option<([f64; 3], f64)>,
+    /// `G92` offset added to `G1` targets
+    offset: [f64; 3],
+    settings: BTreeMap<u32, String>,
+    /// Extra status report fields, after `MPos` and `FS`
+    report_fields: BTreeMap<String, String>,
+    commands: Vec<String>,
+    status_queries: usize,
+    homing_time: Duration,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        let settings = [
+            (22, "1"),         // homing enable
+            (27, "3.000"),     // homing pulloff
+            (110, "6000.000"), // X max rate
+            (120, "1000.000"), // X acceleration
+            (130, "2000.000"), // X max travel
+        ]
+        .into_iter()
+        .map(|(n, v)| (n, v.to_string()))
+        .collect();
+
+        Self {
+            position: [0.0; 3],
+            motion: None,
+            planned: VecDeque::new(),
+            held: false,
+            alarm: None,
+            asleep: false,
+            safety_door: None,
+            suspended: None,
+            offset: [0.0; 3],
+            settings,
+            report_fields: BTreeMap::new(),
+            commands: Vec::new(),
+            status_queries: 0,
+            homing_time: HOMING_TIME,
+        }
+    }
+}
+
+struct Motion {
+    from: [f64; 3],
+    to: [f64; 3],
+    feed: f64,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Machine {
+    /// Advance in-progress and queued moves to the current time
+    fn update(&mut self) {
+        while let Some(motion) = &self.motion {
+            let progress = if motion.duration.is_zero() {
+                1.0
+            } else {
+                (motion.started.elapsed().as_secs_f64() / motion.duration.as_secs_f64()).min(1.0)
+            };
+            for i in 0..3 {
+                self.position[i] = motion.from[i] + (motion.to[i] - motion.from[i]) * progress;
+            }
+            if progress < 1.0 {
+                return;
+            }
+
+            // The next queued block starts when this one finished, not when we looked
+            let finished = motion.started + motion.duration;
+            self.motion = None;
+            if let Some((target, feed)) = self.planned.pop_front() {
+                self.begin_move(target, feed, finished);
+            }
+        }
+    }
+
+    fn status_report(&mut self) -> String {
+        self.update();
+        let state = match (self.alarm, self.safety_door, self.held, &self.motion) {
+            _ if self.asleep => "Sleep".to_string(),
+            (Some(code), _, _, _) => format!("Alarm:{}", code),
+            (None, Some(door), _, _) => format!("Door:{}", door),
+            (None, None, true, _) => "Hold:0".to_string(),
+            (None, None, false, Some(_)) => "Run".to_string(),
+            (None, None, false, None) => "Idle".to_string(),
+        };
+        let [x, y, z] = self.position;
+        let feed = match (&self.motion, self.held) {
+            (Some(motion), false) => motion.feed,
+            _ => 0.0,
+        };
+        let fields: String = self
+            .report_fields
+            .iter()
+            .map(|(name, value)| format!("|{}:{}", name, value))
+            .collect();
+        format!(
+            "<{}|MPos:{:.3},{:.3},{:.3}|FS:{},0{}>",
+            state, x, y, z, feed, fields
+        )
+    }
+
+    /// Automatic status report interval (`$481`), if set
+    fn report_interval(&self) -> Option<Duration> {
+        let ms: u64 = self.settings.get(&481)?.parse().ok()?;
+        (ms > 0).then(|| Duration::from_millis(ms))
+    }
+
+    /// Start a move to `target` at `feed` mm/min, dropping any queued moves
+    fn start_move(&mut self, target: [f64; 3], feed: f64) {
+        self.update();
+        self.planned.clear();
+        self.begin_move(target, feed, Instant::now());
+    }
+
+    /// Run a `G1` to `target` after the current and queued moves
+    fn queue_move(&mut self, target: [f64; 3], feed: f64) {
+        self.update();
+        if self.motion.is_some() {
+            self.planned.push_back((target, feed));
+        } else {
+            self.begin_move(target, feed, Instant::now());
+        }
+    }
+
+    /// Where the machine will be once the current and queued moves finish
+    fn planned_position(&self) -> [f64; 3] {
+        self.planned
+            .back()
+            .map(|(target, _)| *target)
+            .or(self.motion.as_ref().map(|motion| motion.to))
+            .unwrap_or(self.position)
+    }
+
+    fn begin_move(&mut self, target: [f64; 3], feed: f64, started: Instant) {
+        let distance = (0..3)
+            .map(|i| (target[i] - self.position[i]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        let minutes = if feed > 0.0 { distance / feed } else { 0.0 };
+        self.motion = Some(Motion {
+            from: self.position,
+            to: target,
+            feed,
+            started,
+            duration: Duration::from_secs_f64(minutes * 60.0),
+        });
+    }
+
+    /// Stop where the machine is now, discarding queued moves
+    fn halt(&mut self) {
+        self.update();
+        self.motion = None;
+        self.planned.clear();
+        self.suspended = None;
+    }
+}
+
+fn axis_index(axis: char) -> Option<usize> {
+    AXES.iter().position(|a| *a == axis.to_ascii_uppercase())
+}
+
+/// Axis words and feed rate of a G-code line, e.g. `G90 G1 X100 F6000`
+fn parse_words(line: &str) -> (Vec<(usize, f64)>, Option<f64>) {
+    let mut axes = Vec::new();
+    let mut feed = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, letter)) = chars.next() {
+        if !letter.is_ascii_alphabetic() {
+            continue;
+        }
+        let mut end = start + 1;
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_ascii_digit() || c == '.' || c == '-' {
+                end = i + 1;
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let Ok(value) = line[start + 1..end].parse::<f64>() else {
+            continue;
+        };
+        match letter.to_ascii_uppercase() {
+            'F' => feed = Some(value),
+            other => {
+                if let Some(index) = axis_index(other) {
+                    axes.push((index, value));
+                }
+            }
+        }
+    }
+
+    (axes, feed)
+}
+
+/// What woke `serve` up: bytes from dosa, or a line to send unasked
+enum Wake {
+    Read(std::io::Result<usize>),
+    Push(String),
+}
+
+async fn serve(mut stream: TcpStream, machine: Arc<Mutex<Machine>>, mut unsolicited: broadcast::Receiver<String>) {
+    let mut line = Vec::new();
+    let mut buf = [0u8; 256];
+    let mut last_report = Instant::now();
+
+    loop {
+        // Push a status report whenever `$481` says one is due
+        let interval = machine.lock().unwrap().report_interval();
+        let due = last_report + interval.unwrap_or_default();
+        let wake = tokio::select! {
+            read = stream.read(&mut buf) => Wake::Read(read),
+            _ = tokio::time::sleep_until(due), if interval.is_some() => {
+                last_report = Instant::now();
+                Wake::Push(machine.lock().unwrap().status_report())
+            }
+            Ok(line) = unsolicited.recv() => Wake::Push(line),
+        };
+        let n = match wake {
+            Wake::Read(Ok(0) | Err(_)) => return,
+            Wake::Read(Ok(n)) => n,
+            Wake::Push(line) => {
+                if stream.write_all(format!("{}\r\n", line).as_bytes()).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        for &byte in &buf[..n] {
+            let reply = match byte {
+                b'?' => {
+                    let mut m = machine.lock().unwrap();
+                    m.status_queries += 1;
+                    Some(m.status_report())
+                }
+                FEED_HOLD => {
+                    let mut m = machine.lock().unwrap();
+                    if m.motion.is_some() {
+                        m.halt();
+                        m.held = true;
+                    }
+                    None
+                }
+                CYCLE_START | CYCLE_START_ALT => {
+                    let mut m = machine.lock().unwrap();
+                    if m.safety_door == Some(0) {
+                        m.safety_door = None;
+                        if let Some((target, feed)) = m.suspended.take() {
+                            m.begin_move(target, feed, Instant::now());
+                        }
+                    }
+                    None
+                }
+                QUEUE_FLUSH | JOG_CANCEL => {
+                    let mut m = machine.lock().unwrap();
+                    m.halt();
+                    m.held = false;
+                    None
+                }
+                SOFT_RESET => {
+                    let mut m = machine.lock().unwrap();
+                    m.halt();
+                    m.held = false;
+                    m.asleep = false;
+                    Some("GrblHAL 1.1f ['$' or '$HELP' for help]".to_string())
+                }
+                b'\n' => {
+                    let text = String::from_utf8_lossy(&line).trim().to_string();
+                    line.clear();
+                    if text.is_empty() {
+                        None
+                    } else if text.starts_with("$H") {
+                        if home(&mut stream, &machine, &text).await.is_err() {
+                            return;
+                        }
+                        None
+                    } else {
+                        Some(execute(&machine, &text))
+                    }
+                }
+                b'\r' => None,
+                other => {
+                    line.push(other);
+                    None
+                }
+            };
+
+            if let Some(reply) = reply {
+                if stream
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Run a homing cycle: report `Home` straight away, then `ok` once it finishes
+///
+/// `?` is answered with a `Home` report meanwhile, and a soft reset aborts the cycle with
+/// alarm 6 like grblHAL's; other input is dropped.
+async fn home(stream: &mut TcpStream, machine: &Arc<Mutex<Machine>>, command: &str) -> Result<()> {
+    let (report, homing_time) = {
+        let mut m = machine.lock().unwrap();
+        m.commands.push(command.to_string());
+        m.halt();
+        m.held = false;
+        let [x, y, z] = m.position;
+        (format!("<Home|MPos:{:.3},{:.3},{:.3}|FS:0,0>\r\n", x, y, z), m.homing_time)
+    };
+    stream.write_all(report.as_bytes()).await?;
+
+    let done = tokio::time::sleep(homing_time);
+    tokio::pin!(done);
+    let mut buf = [0u8; 256];
+    loop {
+        let n = tokio::select! {
+            _ = &mut done => break,
+            read = stream.read(&mut buf) => read?,
+        };
+        if n == 0 {
+            anyhow::bail!("connection closed while homing");
+        }
+        if buf[..n].contains(&SOFT_RESET) {
+            machine.lock().unwrap().alarm = Some(6);
+            stream
+                .write_all(b"ALARM:6\r\nGrblHAL 1.1f ['$' or '$HELP' for help]\r\n")
+                .await?;
+            return Ok(());
+        }
+        for _ in buf[..n].iter().filter(|&&byte| byte == b'?') {
+            stream.write_all(report.as_bytes()).await?;
+        }
+    }
+
+    {
+        let mut m = machine.lock().unwrap();
+        let axes = command.trim_start_matches("$H");
+        for (i, axis) in AXES.iter().enumerate() {
+            if axes.is_empty() || axes.contains(*axis) {
+                m.position[i] = 0.0;
+            }
+        }
+        m.alarm = None;
+    }
+    stream.write_all(b"ok\r\n").await?;
+    Ok(())
+}
+
+/// Handle a line command, returning the full reply (may span several lines)
+fn execute(machine: &Arc<Mutex<Machine>>, command: &str) -> String {
+    let mut m = machine.lock().unwrap();
+    m.commands.push(command.to_string());
+
+    if command == "$X" {
+        m.alarm = None;
+        return "[MSG:Caution: Unlocked]\r\nok".to_string();
+    }
+    if command == "$$" {
+        let mut reply: Vec<String> = m
+            .settings
+            .iter()
+            .map(|(n, v)| format!("${}={}", n, v))
+            .collect();
+        reply.push("ok".to_string());
+        return reply.join("\r\n");
+    }
+    if let Some((setting, value)) = command
+        .strip_prefix('$')
+        .and_then(|rest| rest.split_once('='))
+        .filter(|(setting, _)| *setting != "J")
+    {
+        return match setting.parse::<u32>() {
+            Ok(n) => {
+                m.settings.insert(n, value.to_string());
+                "ok".to_string()
+            }
+            Err(_) => "error:3".to_string(),
+        };
+    }
+
+    if command == "$SLP" {
+        m.halt();
+        m.held = false;
+        m.asleep = true;
+        return "ok".to_string();
+    }
+
+    // G-code is locked out while in alarm or asleep
+    if m.alarm.is_some() || m.asleep {
+        return "error:9".to_string();
+    }
+
+    if let Some(jog) = command.strip_prefix("$J=") {
+        let (axes, feed) = parse_words(jog);
+        let Some(feed) = feed else {
+            return "error:22".to_string();
+        };
+        m.update();
+        let mut target = m.position;
+        for (i, distance) in axes {
+            target[i] += distance;
+        }
+        m.held = false;
+        m.start_move(target, feed);
+        return "ok".to_string();
+    }
+
+    let upper = command.to_ascii_uppercase();
+    if upper.starts_with("G92") {
+        let (axes, _) = parse_words(command);
+        m.update();
+        let current = m.planned_position();
+        for (i, position) in axes {
+            m.offset[i] = current[i] - position;
+        }
+        return "ok".to_string();
+    }
+    if upper.contains("G1") || upper.contains("G0") {
+        let (axes, feed) = parse_words(command);
+        m.update();
+        let mut target = m.planned_position();
+        for (i, position) in axes {
+            target[i] = position + m.offset[i];
+        }
+        m.held = false;
+        m.queue_move(target, feed.unwrap_or(1000.0));
+        return "ok".to_string();
+    }
+
+    // G21, M-codes and anything else are accepted without effect
+    "ok".to_string()
+}
@@ -0,0 +1,170 @@
+//! Bare HTTP/1.1 client for the dosa and nyx HTTP APIs, and a receiver for dosa's webhooks.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
+
+use crate::ws::REPLY_TIMEOUT;
+
+/// Send one request to `127.0.0.1:port` and return the status code and JSON body
+///
+/// `token` goes in an `Authorization: Bearer` header; `body` is sent without a content type,
+/// the way `curl -d` would send it.
+pub async fn http_request(
+    port: u16,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+    body: Option<&Value>,
+) -> Result<(u16, Value)> {
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        port,
+        body.len()
+    );
+    if let Some(token) = token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    request.push_str(&body);
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Failed to connect to HTTP port {}", port))?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    timeout(REPLY_TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .with_context(|| format!("No HTTP reply to {} {}", method, path))??;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .with_context(|| format!("Malformed HTTP reply: {:?}", response))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .with_context(|| format!("No status code in {:?}", head))?;
+    let json = serde_json::from_str(body).with_context(|| format!("Reply body isn't JSON: {:?}", body))?;
+    Ok((status, json))
+}
+
+/// A request a [`WebhookServer`] received
+#[derive(Debug, Clone)]
+pub struct WebhookRequest {
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+    pub body: Value,
+    /// The status it was answered with
+    pub status: u16,
+}
+
+/// Local endpoint standing in for a webhook receiver; stops listening when dropped
+///
+/// Records every POST and answers 200, except the first `fail_first` requests, which get
+/// a 500 so retries can be tested.
+pub struct WebhookServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<WebhookRequest>>>,
+    arrived: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl WebhookServer {
+    pub async fn start(fail_first: usize) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let arrived = Arc::new(Notify::new());
+
+        let task_requests = requests.clone();
+        let task_arrived = arrived.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let requests = task_requests.clone();
+                let arrived = task_arrived.clone();
+                tokio::spawn(async move {
+                    let failing = requests.lock().unwrap().len() < fail_first;
+                    let status = if failing { 500 } else { 200 };
+                    if let Ok((headers, body)) = receive(stream, status).await {
+                        requests.lock().unwrap().push(WebhookRequest { headers, body, status });
+                        arrived.notify_waiters();
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            port,
+            requests,
+            arrived,
+            task,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}/hook", self.port)
+    }
+
+    /// Every request so far, failed ones included
+    pub fn requests(&self) -> Vec<WebhookRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Wait for an accepted request whose body matches `predicate`
+    pub async fn wait_for(&self, wait: Duration, predicate: impl Fn(&Value) -> bool) -> Result<WebhookRequest> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let notified = self.arrived.notified();
+            let found = self
+                .requests()
+                .into_iter()
+                .find(|request| request.status == 200 && predicate(&request.body));
+            if let Some(request) = found {
+                return Ok(request);
+            }
+            if timeout(deadline.saturating_duration_since(Instant::now()), notified).await.is_err() {
+                anyhow::bail!("No matching webhook within {:?}; got {:?}", wait, self.requests());
+            }
+        }
+    }
+}
+
+impl Drop for WebhookServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Read one request's headers and JSON body, answer `status` and close the connection
+async fn receive(stream: TcpStream, status: u16) -> Result<(HashMap<String, String>, Value)> {
+    let mut reader = BufReader::new(stream);
+    let mut headers = HashMap::new();
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let Some((name, value)) = line.trim_end().split_once(':') else {
+            break;
+        };
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let length = headers.get("content-length").and_then(|len| len.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    let reply = format!("HTTP/1.1 {} Webhook\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    reader.get_mut().write_all(reply.as_bytes()).await?;
+    Ok((headers, serde_json::from_slice(&body)?))
+}
@@ -0,0 +1,34 @@
+//! End-to-end test harness for the SHQ services.
+//!
+//! Tests in `tests/` build the real dosa, nyx and overwatch binaries, start them in scratch
+//! directories on free ports and talk to them over their public APIs. Hardware is faked:
+//! dosa drives [`MockGrbl`], nyx gets a backlight directory of plain files, no touch
+//! device and, when a test needs one, a [`MockChrome`], and overwatch runs with the stub
+//! TTS backend.
+
+pub mod chrome;
+pub mod grbl;
+pub mod http;
+pub mod service;
+pub mod ws;
+
+pub use chrome::MockChrome;
+pub use grbl::MockGrbl;
+pub use http::{http_request, WebhookServer};
+pub use service::{free_port, Service};
+pub use ws::WsClient;
+
+use std::path::PathBuf;
+
+/// Path of a file under `fixtures/` (e.g. `tls/server.pem`)
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+/// Generated overwatch gRPC client
+#[cfg(feature = "overwatch")]
+pub mod voice {
+    tonic::include_proto!("voice");
+}
@@ -0,0 +1,308 @@
+//! Building and running the service binaries in scratch directories.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Mutex;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::chrome::MockChrome;
+use crate::grbl::MockGrbl;
+
+/// How long a service gets to start listening
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Binaries already built by this test process, by app name
+static BUILT: Mutex<Option<HashMap<&'static str, PathBuf>>> = Mutex::new(None);
+
+/// A running service; killed when dropped
+pub struct Service {
+    app: &'static str,
+    child: Child,
+    dir: TempDir,
+    port: u16,
+    env: Vec<(String, PathBuf)>,
+}
+
+impl Service {
+    /// Start dosa talking to `grbl`; `extra_yaml` is appended to the config (top-level sections)
+    ///
+    /// Host and port come from the command line, so `extra_yaml` may add its own `websocket`
+    /// section (e.g. for TLS).
+    pub async fn dosa(grbl: &MockGrbl, extra_yaml: &str) -> Result<Self> {
+        Self::dosa_with_env(grbl, extra_yaml, &[]).await
+    }
+
+    /// Start dosa as [`Service::dosa`] does, with extra environment variables
+    /// (e.g. `SSL_CERT_FILE` to trust the test CA)
+    pub async fn dosa_with_env(
+        grbl: &MockGrbl,
+        extra_yaml: &str,
+        env: &[(&str, PathBuf)],
+    ) -> Result<Self> {
+        let dir = TempDir::new()?;
+        let port = free_port()?;
+
+        let config = format!(
+            "mdns:\n  enabled: false\ndoor:\n  open_distance: 100.0\n  open_speed: 60000.0\n  close_speed: 60000.0\n  \
+             cnc_axis: \"X\"\n  open_direction: \"right\"\n  auto_home: false\n  \
+             cnc_connection:\n    type: tcp\n    host: \"127.0.0.1\"\n    port: {}\n{extra_yaml}",
+            grbl.port()
+        );
+        write_file(&dir.path().join("config/dosa/config.yaml"), &config)?;
+
+        Self::start("dosa", dir, port, env).await
+    }
+
+    /// Start nyx against a fake backlight (see [`Service::backlight`]) and a touch device
+    /// that doesn't exist; `config` is merged into the default config
+    pub async fn nyx(config: serde_json::Value) -> Result<Self> {
+        Self::nyx_with_displays(config, &["fake"]).await
+    }
+
+    /// Start nyx with one fake backlight per name in `displays`
+    pub async fn nyx_with_displays(config: serde_json::Value, displays: &[&str]) -> Result<Self> {
+        Self::start_nyx(config, displays, None).await
+    }
+
+    /// Start nyx with its CDP calls going to `chrome` instead of a real browser
+    pub async fn nyx_with_chrome(config: serde_json::Value, chrome: &MockChrome) -> Result<Self> {
+        Self::start_nyx(config, &["fake"], Some(chrome)).await
+    }
+
+    async fn start_nyx(config: serde_json::Value, displays: &[&str], chrome: Option<&MockChrome>) -> Result<Self> {
+        let dir = TempDir::new()?;
+        let port = free_port()?;
+
+        for display in displays {
+            let backlight = dir.path().join("backlight").join(display);
+            write_file(&backlight.join("max_brightness"), "100\n")?;
+            write_file(&backlight.join("brightness"), "0\n")?;
+        }
+
+        let mut full = serde_json::json!({
+            "websocket": { "host": "127.0.0.1", "port": port },
+            "mdns": { "enabled": false },
+        });
+        if let (Some(full), Some(extra)) = (full.as_object_mut(), config.as_object()) {
+            full.extend(extra.clone());
+        }
+        write_file(
+            &dir.path().join("config/shqd/config.json"),
+            &serde_json::to_string_pretty(&full)?,
+        )?;
+
+        let mut env = vec![
+            ("NYX_BACKLIGHT_DIR", dir.path().join("backlight")),
+            ("NYX_TOUCH_DEVICE", dir.path().join("no-touch-device")),
+        ];
+        if let Some(chrome) = chrome {
+            env.push(("NYX_CDP_ADDR", PathBuf::from(chrome.addr())));
+        }
+        Self::start("nyx", dir, port, &env).await
+    }
+
+    /// Start overwatch with the stub TTS backend; `extra_yaml` is appended to the config
+    pub async fn overwatch(extra_yaml: &str) -> Result<Self> {
+        let dir = TempDir::new()?;
+        let port = free_port()?;
+
+        let config =
+            format!("server_address: \"127.0.0.1:{port}\"\ntts_backend: stub\n{extra_yaml}");
+        let config_path = dir.path().join("config.yaml");
+        write_file(&config_path, &config)?;
+
+        Self::start("overwatch", dir, port, &[("CONFIG_PATH", config_path)]).await
+    }
+
+    async fn start(
+        app: &'static str,
+        dir: TempDir,
+        port: u16,
+        env: &[(&str, PathBuf)],
+    ) -> Result<Self> {
+        let env: Vec<(String, PathBuf)> = env.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        let child = spawn(app, dir.path(), port, &env)?;
+
+        let mut service = Self {
+            app,
+            child,
+            dir,
+            port,
+            env,
+        };
+        service.wait_until_listening(app).await?;
+        Ok(service)
+    }
+
+    /// Stop the service with SIGTERM, as systemd does, and wait for it to exit
+    pub async fn terminate(&mut self) -> Result<()> {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .context("Failed to run kill")?;
+        anyhow::ensure!(status.success(), "kill failed: {}", status);
+
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while self.child.try_wait()?.is_none() {
+            if Instant::now() > deadline {
+                anyhow::bail!("{} didn't exit after SIGTERM", self.app);
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+
+    /// Start a terminated service again on the same config directory and port
+    pub async fn start_again(&mut self) -> Result<()> {
+        self.child = spawn(self.app, self.dir.path(), self.port, &self.env)?;
+        self.wait_until_listening(self.app).await
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// A file in the service's config directory (`config.json.v0.bak`, `crash-report.json`, ...)
+    pub fn config_file(&self, name: &str) -> PathBuf {
+        let dir = match self.app {
+            "dosa" => "config/dosa",
+            "nyx" => "config/shqd",
+            _ => "",
+        };
+        self.dir.path().join(dir).join(name)
+    }
+
+    /// Run `dosa ctl <args>` or `nyx ctl <args>` against this service and wait for it to exit
+    pub async fn ctl(&self, args: &[&str]) -> Result<Output> {
+        let url = format!("ws://127.0.0.1:{}", self.port);
+        tokio::process::Command::new(binary(self.app)?)
+            .arg("ctl")
+            .args(["--url", &url])
+            .args(args)
+            .env_remove("DOSA_TOKEN")
+            .env_remove("NYX_TOKEN")
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {} ctl", self.app))
+    }
+
+    /// Raw value in nyx's fake backlight `brightness` file (`max_brightness` is 100)
+    pub fn backlight(&self) -> Result<u32> {
+        self.backlight_of("fake")
+    }
+
+    /// Raw value in the `brightness` file of the fake backlight named `display`
+    pub fn backlight_of(&self, display: &str) -> Result<u32> {
+        let path = self.dir.path().join("backlight").join(display).join("brightness");
+        Ok(std::fs::read_to_string(path)?.trim().parse()?)
+    }
+
+    /// Change nyx's fake backlight behind its back, as another program would
+    pub fn set_backlight(&self, raw: u32) -> Result<()> {
+        let path = self.dir.path().join("backlight").join("fake").join("brightness");
+        Ok(std::fs::write(path, format!("{}\n", raw))?)
+    }
+
+    async fn wait_until_listening(&mut self, app: &str) -> Result<()> {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                anyhow::bail!("{} exited during startup: {}", app, status);
+            }
+            if TcpStream::connect(("127.0.0.1", self.port)).await.is_ok() {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                anyhow::bail!("{} didn't start listening on port {}", app, self.port);
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Run `app` in `dir`, listening on `port`
+fn spawn(app: &'static str, dir: &Path, port: u16, env: &[(String, PathBuf)]) -> Result<Child> {
+    let binary = binary(app)?;
+
+    // dosa and nyx take --port; overwatch reads its address from the config
+    Command::new(&binary)
+        .args(["--host", "127.0.0.1", "--port", &port.to_string()])
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env(
+            "RUST_LOG",
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "warn".to_string()),
+        )
+        .envs(env.iter().map(|(k, v)| (k, v.as_os_str())))
+        .stdin(Stdio::null())
+        .stdout(log_output())
+        .stderr(log_output())
+        .spawn()
+        .with_context(|| format!("Failed to start {:?}", binary))
+}
+
+/// Path to `app`'s debug binary, building it on first use
+///
+/// `SHQ_E2E_<APP>_BIN` (e.g. `SHQ_E2E_DOSA_BIN`) points at a prebuilt binary instead.
+fn binary(app: &'static str) -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(format!("SHQ_E2E_{}_BIN", app.to_uppercase())) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let mut built = BUILT.lock().unwrap();
+    let built = built.get_or_insert_with(HashMap::new);
+    if let Some(path) = built.get(app) {
+        return Ok(path.clone());
+    }
+
+    let app_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(app);
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args(["build", "--quiet", "--manifest-path"])
+        .arg(app_dir.join("Cargo.toml"))
+        .env_remove("CARGO_TARGET_DIR")
+        .status()
+        .with_context(|| format!("Failed to run cargo build for {}", app))?;
+    if !status.success() {
+        anyhow::bail!("cargo build for {} failed: {}", app, status);
+    }
+
+    let path = app_dir.join("target/debug").join(app);
+    built.insert(app, path.clone());
+    Ok(path)
+}
+
+/// Service logs go to the test's stderr with `SHQ_E2E_LOG=1`, otherwise nowhere
+fn log_output() -> Stdio {
+    if std::env::var_os("SHQ_E2E_LOG").is_some() {
+        Stdio::inherit()
+    } else {
+        Stdio::null()
+    }
+}
+
+/// A localhost port nothing is listening on right now (e.g. for a second listener in the config)
+pub fn free_port() -> Result<u16> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {:?}", path))
+}
@@ -0,0 +1,140 @@
+//! JSON (or MessagePack) WebSocket client for the dosa and nyx APIs.
+
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+
+/// How long to wait for a reply before failing the test
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct WsClient {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+    /// Talking MessagePack in binary frames rather than JSON text
+    msgpack: bool,
+}
+
+impl WsClient {
+    pub async fn connect(port: u16) -> Result<Self> {
+        Self::connect_url(&format!("ws://127.0.0.1:{}", port)).await
+    }
+
+    /// Connect to `url` as-is (e.g. with a `?token=` query)
+    pub async fn connect_url(url: &str) -> Result<Self> {
+        let (stream, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .with_context(|| format!("Failed to connect to {}", url))?;
+        Ok(Self { stream, next_id: 1, msgpack: false })
+    }
+
+    /// Connect presenting `token` in an `Authorization: Bearer` header
+    pub async fn connect_with_token(port: u16, token: &str) -> Result<Self> {
+        let url = format!("ws://127.0.0.1:{}", port);
+        let mut request = url.as_str().into_client_request()?;
+        request
+            .headers_mut()
+            .insert("authorization", format!("Bearer {}", token).parse()?);
+        let (stream, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .with_context(|| format!("Failed to connect to {}", url))?;
+        Ok(Self { stream, next_id: 1, msgpack: false })
+    }
+
+    /// Connect asking for the `msgpack` subprotocol, failing if the server doesn't agree
+    pub async fn connect_msgpack(port: u16) -> Result<Self> {
+        let url = format!("ws://127.0.0.1:{}", port);
+        let mut request = url.as_str().into_client_request()?;
+        request
+            .headers_mut()
+            .insert("sec-websocket-protocol", "msgpack".parse()?);
+        let (stream, response) = tokio_tungstenite::connect_async(request)
+            .await
+            .with_context(|| format!("Failed to connect to {}", url))?;
+        anyhow::ensure!(
+            response.headers().get("sec-websocket-protocol").is_some_and(|p| p == "msgpack"),
+            "Server didn't accept msgpack"
+        );
+        Ok(Self { stream, next_id: 1, msgpack: true })
+    }
+
+    /// Connect over `wss://`, trusting only the CA certificate in `ca` (PEM)
+    pub async fn connect_tls(port: u16, ca: &Path) -> Result<Self> {
+        let mut roots = rustls::RootCertStore::empty();
+        let pem = std::fs::read(ca).with_context(|| format!("Failed to read {:?}", ca))?;
+        for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+            roots.add(cert?)?;
+        }
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let url = format!("wss://localhost:{}", port);
+        let (stream, _) = tokio_tungstenite::connect_async_tls_with_config(
+            url.as_str(),
+            None,
+            false,
+            Some(Connector::Rustls(Arc::new(config))),
+        )
+        .await
+        .with_context(|| format!("Failed to connect to {}", url))?;
+        Ok(Self { stream, next_id: 1, msgpack: false })
+    }
+
+    /// Send `message` with a fresh `id` and return the reply carrying that `id`
+    ///
+    /// Broadcasts that arrive in the meantime are skipped.
+    pub async fn request(&mut self, mut message: Value) -> Result<Value> {
+        let id = format!("e2e-{}", self.next_id);
+        self.next_id += 1;
+        message["id"] = json!(id);
+
+        self.send(&message).await?;
+        self.wait_for(REPLY_TIMEOUT, |reply| reply["id"] == id)
+            .await
+            .with_context(|| format!("No reply to {}", message))
+    }
+
+    /// Send `message` as-is
+    pub async fn send(&mut self, message: &Value) -> Result<()> {
+        let frame = if self.msgpack {
+            Message::Binary(rmp_serde::to_vec(message)?)
+        } else {
+            Message::Text(message.to_string())
+        };
+        self.stream.send(frame).await?;
+        Ok(())
+    }
+
+    /// First incoming message matching `predicate`, within `limit`
+    pub async fn wait_for(
+        &mut self,
+        limit: Duration,
+        predicate: impl Fn(&Value) -> bool,
+    ) -> Result<Value> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = timeout(remaining, self.stream.next())
+                .await
+                .context("Timed out waiting for a message")?
+                .context("Connection closed")??;
+
+            let message: Value = match frame {
+                Message::Text(text) if !self.msgpack => serde_json::from_str(&text)?,
+                Message::Binary(data) if self.msgpack => rmp_serde::from_slice(&data)?,
+                Message::Text(_) | Message::Binary(_) => anyhow::bail!("Frame in the wrong encoding"),
+                _ => continue,
+            };
+            if predicate(&message) {
+                return Ok(message);
+            }
+        }
+    }
+}
@@ -0,0 +1,2076 @@
+//! dosa over WebSocket, driving the mock grblHAL controller.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use shq_e2e::{fixture, free_port, http_request, MockGrbl, Service, WebhookServer, WsClient};
+use tokio::time::Duration;
+
+/// Longest a door state change may take to show up in a status broadcast
+const MOVE_TIMEOUT: Duration = Duration::from_secs(15);
+
+const SCENES: &str = "
+scene:
+  scenes:
+    - id: open-up
+      actions:
+        - local: open
+";
+
+async fn start() -> Result<(MockGrbl, Service, WsClient)> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, SCENES).await?;
+    let client = WsClient::connect(dosa.port()).await?;
+    Ok((grbl, dosa, client))
+}
+
+/// Wait for a status broadcast with the door in `state`
+async fn wait_for_state(client: &mut WsClient, state: &str) -> Result<Value> {
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["state"] == state
+        })
+        .await
+}
+
+/// Wait for a status broadcast with door `id` in `state`
+async fn wait_for_door_state(client: &mut WsClient, id: &str, state: &str) -> Result<Value> {
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status"
+                && message["door"]["id"] == id
+                && message["door"]["state"] == state
+        })
+        .await
+}
+
+async fn home(client: &mut WsClient) -> Result<()> {
+    let reply = client.request(json!({"type": "home"})).await?;
+    assert_eq!(reply["type"], "response");
+    assert_eq!(reply["success"], true);
+    wait_for_state(client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn hello_reply_carries_envelope() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+
+    let reply = client
+        .request(json!({"type": "hello", "client": "e2e"}))
+        .await?;
+    assert_eq!(reply["type"], "hello");
+    assert_eq!(reply["service"], "dosa");
+    assert_eq!(reply["source"], "dosa");
+    assert!(reply["seq"].is_u64());
+    assert!(reply["timestamp"].as_u64().unwrap_or(0) > 0);
+
+    let capabilities = reply["capabilities"].as_array().expect("capabilities");
+    assert!(capabilities.contains(&json!("envelope")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn msgpack_clients_get_binary_frames() -> Result<()> {
+    let (_grbl, dosa, mut client) = start().await?;
+    let mut compact = WsClient::connect_msgpack(dosa.port()).await?;
+
+    let reply = compact.request(json!({"type": "hello"})).await?;
+    assert_eq!(reply["type"], "hello", "{}", reply);
+    assert_eq!(reply["source"], "dosa");
+    let capabilities = reply["capabilities"].as_array().expect("capabilities");
+    assert!(capabilities.contains(&json!("msgpack")));
+
+    // Broadcasts reach both kinds of client, each in its own encoding
+    home(&mut client).await?;
+    compact.request(json!({"type": "subscribe_position"})).await?;
+    client.request(json!({"type": "open"})).await?;
+    compact
+        .wait_for(MOVE_TIMEOUT, |message| message["type"] == "status" && message["door"]["state"] == "open")
+        .await?;
+    wait_for_state(&mut client, "open").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn responses_and_errors_echo_the_request_id() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+
+    // Several commands in flight; each reply names its request, status broadcasts none
+    client.send(&json!({"type": "nonsense", "id": 7})).await?;
+    client.send(&json!({"type": "home", "id": "home-1"})).await?;
+    client.send(&json!({"type": "status", "id": "status-1"})).await?;
+
+    let error = client
+        .wait_for(MOVE_TIMEOUT, |message| message["id"] == 7)
+        .await?;
+    assert_eq!(error["type"], "error", "{}", error);
+    let homed = client
+        .wait_for(MOVE_TIMEOUT, |message| message["id"] == "home-1")
+        .await?;
+    assert_eq!(homed["type"], "response", "{}", homed);
+    assert_eq!(homed["command"], "home");
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            assert!(message["type"] != "status" || message.get("id").is_none() || message["id"] == "status-1");
+            message["id"] == "status-1"
+        })
+        .await?;
+    assert_eq!(status["type"], "status", "{}", status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unhomed_door_reports_degraded_health() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "pending");
+
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["type"], "health");
+    assert_eq!(health["status"], "degraded");
+    let homing = health["components"]
+        .as_array()
+        .and_then(|components| components.iter().find(|c| c["name"] == "homing"))
+        .expect("homing component");
+    assert_eq!(homing["status"], "degraded");
+    Ok(())
+}
+
+#[tokio::test]
+async fn home_open_and_close() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    home(&mut client).await?;
+    assert!(grbl.commands().iter().any(|c| c == "$HX"));
+
+    client.request(json!({"type": "open"})).await?;
+    let status = wait_for_state(&mut client, "open").await?;
+    assert_eq!(status["door"]["position_mm"], 100.0);
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    assert!(grbl.position('X').abs() < 0.01);
+
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "ok");
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_leaves_door_part_open() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    // Slow the door down so there's time to stop it mid-travel
+    let reply = client
+        .request(json!({"type": "set_config", "open_speed": 600.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    client.request(json!({"type": "stop"})).await?;
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    let position = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!(
+        position > 0.0 && position < 100.0,
+        "stopped at {}",
+        position
+    );
+
+    // The controller really stopped
+    let stopped_at = grbl.position('X');
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(grbl.position('X'), stopped_at);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unhomed_door_refuses_to_move() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    let report = client
+        .request(json!({"type": "run_scene", "scene": "open-up"}))
+        .await?;
+    assert_eq!(report["success"], false);
+    let error = report["results"][0]["error"].as_str().unwrap_or_default();
+    assert!(error.contains("must be homed"), "{}", report);
+
+    assert!(!grbl.commands().iter().any(|c| c.contains("G1")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn jog_and_zero_set_a_new_closed_position() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    // Jogging works before homing, so the door can be lined up by hand
+    let reply = client
+        .request(json!({"type": "jog", "distance": 20.0, "feed_rate": 6000.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    let deadline = tokio::time::Instant::now() + MOVE_TIMEOUT;
+    while (grbl.position('X') - 20.0).abs() > 0.01 {
+        assert!(tokio::time::Instant::now() < deadline, "jog never finished");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(grbl.commands().iter().any(|c| c.starts_with("$J=")));
+
+    let reply = client.request(json!({"type": "zero"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "closed");
+    assert_eq!(status["door"]["position_mm"], 0.0);
+
+    // Travel is measured from the zeroed position
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 120.0).abs() < 0.01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn manual_mode_disables_the_motor_until_rehomed() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let reply = client.request(json!({"type": "manual_mode"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    assert!(grbl.commands().iter().any(|c| c == "$SLP"));
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "manual");
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "degraded", "{}", health);
+
+    // The position is untrusted, so nothing motorised runs until the door is re-homed
+    let report = client
+        .request(json!({"type": "run_scene", "scene": "open-up"}))
+        .await?;
+    assert_eq!(report["success"], false);
+    let error = report["results"][0]["error"].as_str().unwrap_or_default();
+    assert!(error.contains("must be homed"), "{}", report);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "manual", "the monitor left manual mode");
+
+    home(&mut client).await?;
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn safety_door_stops_the_door_until_resumed() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+    client
+        .request(json!({"type": "set_config", "open_speed": 1200.0}))
+        .await?;
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    grbl.open_safety_door();
+    wait_for_state(&mut client, "safety_door").await?;
+    let stopped_at = grbl.position('X');
+    assert!(stopped_at > 0.0 && stopped_at < 100.0, "stopped at {}", stopped_at);
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "degraded", "{}", health);
+
+    // Nothing else moves the door, and it won't resume while the safety door is open
+    client.request(json!({"type": "close"})).await?;
+    let reply = client.request(json!({"type": "resume"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "safety_door", "{}", status);
+    assert_eq!(status["door"]["safety_door_ready"], false, "{}", status);
+    assert_eq!(grbl.position('X'), stopped_at);
+
+    grbl.close_safety_door();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "safety_door", "{}", status);
+    assert_eq!(status["door"]["safety_door_ready"], true, "{}", status);
+    assert_eq!(grbl.position('X'), stopped_at);
+
+    // Resume picks the open up where it stopped
+    let reply = client.request(json!({"type": "resume"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "opening").await?;
+    wait_for_state(&mut client, "open").await?;
+    assert_eq!(grbl.position('X'), 100.0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn opening_reports_progress_and_time_left() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    // 100mm at 1200 mm/min takes 5s
+    client
+        .request(json!({"type": "set_config", "open_speed": 1200.0}))
+        .await?;
+    client.request(json!({"type": "open"})).await?;
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status"
+                && message["door"]["state"] == "opening"
+                && message["door"]["progress_percent"].as_f64().is_some_and(|p| p > 10.0)
+        })
+        .await?;
+    let door = &status["door"];
+    assert_eq!(door["feed_rate"], 1200.0);
+    let progress = door["progress_percent"].as_f64().unwrap_or_default();
+    let eta = door["eta_secs"].as_f64().unwrap_or_default();
+    assert!(progress < 100.0, "{}", door);
+    assert!((eta - 5.0 * (100.0 - progress) / 100.0).abs() < 0.2, "{}", door);
+
+    let status = wait_for_state(&mut client, "open").await?;
+    assert!(status["door"].get("progress_percent").is_none());
+    assert!(status["door"].get("eta_secs").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn move_stops_at_an_intermediate_position() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    client.request(json!({"type": "move", "percent": 50.0})).await?;
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    assert_eq!(status["door"]["position_percent"], 50.0);
+    assert!((grbl.position('X') - 50.0).abs() < 0.01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_reverses_a_closing_door() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+
+    // Slow the close down so there's time to reverse it
+    client
+        .request(json!({"type": "set_config", "close_speed": 600.0}))
+        .await?;
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closing").await?;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn moving_door_is_busy_for_other_clients() -> Result<()> {
+    let (_grbl, dosa, mut first) = start().await?;
+    let mut second = WsClient::connect(dosa.port()).await?;
+    home(&mut first).await?;
+
+    first
+        .request(json!({"type": "set_config", "open_speed": 600.0}))
+        .await?;
+    first.request(json!({"type": "open"})).await?;
+    let accepted = second
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "command_accepted" && message["command"] == "open"
+        })
+        .await?;
+    assert_eq!(accepted["door"], "main");
+    wait_for_state(&mut first, "opening").await?;
+
+    // A close from someone else doesn't race the open
+    let reply = second.request(json!({"type": "close"})).await?;
+    assert_eq!(reply["type"], "busy", "{}", reply);
+    assert_eq!(reply["command"], "close");
+    assert_eq!(reply["held_by"]["command"], "open");
+    let status = first.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "opening");
+
+    // stop always wins, and then the door is the stopping client's
+    let reply = second.request(json!({"type": "stop"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut second, "intermediate").await?;
+    let reply = second.request(json!({"type": "close"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = first.request(json!({"type": "open"})).await?;
+    assert_eq!(reply["type"], "busy", "{}", reply);
+    wait_for_state(&mut second, "closed").await?;
+
+    // Once the door is at rest anyone can move it
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let reply = first.request(json!({"type": "open"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cnc_settings_are_read_and_written() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "get_cnc_settings"})).await?;
+    assert_eq!(reply["type"], "cnc_settings");
+    assert_eq!(reply["settings"]["$110"], "6000.000");
+
+    let reply = client
+        .request(json!({"type": "set_cnc_setting", "setting": "$120", "value": "500"}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    assert!(grbl.commands().iter().any(|c| c == "$120=500"));
+
+    let reply = client
+        .request(json!({"type": "get_cnc_setting", "setting": "$120"}))
+        .await?;
+    assert_eq!(reply["type"], "cnc_setting");
+    assert_eq!(reply["value"], "500");
+
+    // Names without the `$` never reach the controller
+    let reply = client
+        .request(json!({"type": "set_cnc_setting", "setting": "120", "value": "1"}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    Ok(())
+}
+
+#[tokio::test]
+async fn configured_cnc_settings_are_pushed_on_connect() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let settings = "  cnc_settings:\n    $110: 6000\n    $120: 500\n";
+    let dosa = Service::dosa(&grbl, settings).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    // Only the setting the controller doesn't match is written
+    let commands = grbl.commands();
+    assert!(commands.iter().any(|c| c == "$120=500"));
+    assert!(!commands.iter().any(|c| c.starts_with("$110=")));
+
+    let reply = client.request(json!({"type": "diff_cnc_settings"})).await?;
+    assert_eq!(reply["type"], "cnc_settings_diff");
+    assert_eq!(reply["differences"], json!([]));
+
+    client
+        .request(json!({"type": "set_cnc_setting", "setting": "$120", "value": "800"}))
+        .await?;
+    let reply = client.request(json!({"type": "diff_cnc_settings"})).await?;
+    assert_eq!(
+        reply["differences"],
+        json!([{"setting": "$120", "desired": "500", "current": "800"}])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cnc_settings_backup_restores_onto_a_changed_controller() -> Result<()> {
+    let (grbl, dosa, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "backup_cnc_settings"})).await?;
+    assert_eq!(reply["type"], "cnc_settings_backup", "{}", reply);
+    assert_eq!(reply["settings"]["$110"], "6000.000");
+    assert!(dosa.config_file("cnc-settings.json").exists());
+
+    // A replacement controller with a different value
+    client
+        .request(json!({"type": "set_cnc_setting", "setting": "$110", "value": "4000"}))
+        .await?;
+    let written = grbl.commands().len();
+
+    let reply = client
+        .request(json!({"type": "restore_cnc_settings", "dry_run": true}))
+        .await?;
+    assert_eq!(reply["type"], "cnc_settings_restore", "{}", reply);
+    assert_eq!(
+        reply["differences"],
+        json!([{"setting": "$110", "desired": "6000.000", "current": "4000"}])
+    );
+    assert!(!grbl.commands()[written..].iter().any(|c| c.starts_with("$110=")));
+
+    let reply = client.request(json!({"type": "restore_cnc_settings"})).await?;
+    assert_eq!(reply["dry_run"], false);
+    assert!(grbl.commands()[written..].iter().any(|c| c == "$110=6000.000"));
+    let reply = client
+        .request(json!({"type": "get_cnc_setting", "setting": "$110"}))
+        .await?;
+    assert_eq!(reply["value"], "6000.000");
+
+    // A blob from elsewhere is checked before anything is written
+    let reply = client
+        .request(json!({"type": "restore_cnc_settings", "settings": {"$120": "1\n$1=0"}}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    assert!(!grbl.commands().iter().any(|c| c == "$1=0"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn calibrate_measures_travel_to_the_limit_switch() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    let reply = client
+        .request(json!({"type": "calibrate", "feed_rate": 6000.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    wait_for_state(&mut client, "calibrating").await?;
+
+    // The far limit switch closes 250mm out
+    let deadline = tokio::time::Instant::now() + MOVE_TIMEOUT;
+    while grbl.position('X') < 250.0 {
+        assert!(tokio::time::Instant::now() < deadline, "calibration never got going");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    grbl.set_report_field("Pn", "X");
+
+    let status = wait_for_state(&mut client, "open").await?;
+    grbl.remove_report_field("Pn");
+    assert!(grbl.commands().iter().any(|c| c == "$HX"));
+
+    // Measured where the jog stopped, less the 3mm homing pull-off
+    let distance = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!(distance > 245.0 && distance < 400.0, "measured {}", distance);
+    assert!((grbl.position('X') - distance).abs() < 0.01);
+
+    let config = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(config["config"]["open_distance"], distance);
+
+    // The new travel is used from now on
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - distance).abs() < 0.01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn calibrate_ends_where_stop_is_sent() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+
+    client
+        .request(json!({"type": "calibrate", "feed_rate": 600.0}))
+        .await?;
+    wait_for_state(&mut client, "calibrating").await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    client.request(json!({"type": "stop"})).await?;
+    let status = wait_for_state(&mut client, "open").await?;
+    let distance = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!(distance > 1.0 && distance < 100.0, "measured {}", distance);
+    assert!((grbl.position('X') - distance).abs() < 0.01);
+
+    let config = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(config["config"]["open_distance"], distance);
+    Ok(())
+}
+
+/// Open and close the door, closing the limit switch once the check after the close jogs
+/// `switch_at` mm (machine position) toward it; returns the status after the check
+async fn close_against_switch(grbl: &MockGrbl, client: &mut WsClient, switch_at: f64) -> Result<Value> {
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(client, "open").await?;
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(client, "verifying").await?;
+
+    let deadline = tokio::time::Instant::now() + MOVE_TIMEOUT;
+    while grbl.position('X') > switch_at {
+        assert!(tokio::time::Instant::now() < deadline, "never probed for the switch");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    grbl.set_report_field("Pn", "X");
+    let status = wait_for_state(client, "closed").await;
+    grbl.remove_report_field("Pn");
+    status
+}
+
+#[tokio::test]
+async fn closing_checks_the_position_against_the_limit_switch() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let verify = "  close_verify:\n    enabled: true\n    feed_rate: 600\n    search_distance: 5\n    \
+                  tolerance: 1\n";
+    let dosa = Service::dosa(&grbl, verify).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    // The switch closes the 3mm homing pull-off past closed, as it should
+    let status = close_against_switch(&grbl, &mut client, -3.0).await?;
+    assert!(status["door"].get("position_drift").is_none(), "{}", status);
+    assert!(grbl.position('X').abs() < 0.01, "not back at closed");
+
+    // 2mm further out: the door lost steps and stops short of closed
+    let status = close_against_switch(&grbl, &mut client, -5.0).await?;
+    let drift = status["door"]["position_drift"]["drift_mm"].as_f64().unwrap_or(0.0);
+    assert!(drift > 1.5 && drift < 3.0, "{}", status);
+    assert_eq!(status["door"]["position_drift"]["tolerance_mm"], 1.0);
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "degraded", "{}", health);
+
+    // Homing again clears the warning
+    home(&mut client).await?;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert!(status["door"].get("position_drift").is_none(), "{}", status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn position_streams_while_moving() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "subscribe_position", "interval_ms": 10}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    let reply = client
+        .request(json!({"type": "subscribe_position", "interval_ms": 50}))
+        .await?;
+    assert_eq!(reply["success"], true);
+
+    // 100mm at 3000 mm/min takes 2s: about 40 positions at 50ms
+    client
+        .request(json!({"type": "set_config", "open_speed": 3000.0}))
+        .await?;
+    client.request(json!({"type": "open"})).await?;
+    let positions = RefCell::new(Vec::new());
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            if message["type"] == "position" {
+                assert_eq!(message["door"], "main");
+                assert_eq!(message["state"], "opening");
+                positions
+                    .borrow_mut()
+                    .push(message["position_mm"].as_f64().unwrap_or(-1.0));
+            }
+            message["type"] == "status" && message["door"]["state"] == "open"
+        })
+        .await?;
+    let positions = positions.into_inner();
+    assert!(positions.len() >= 20, "{} positions", positions.len());
+    assert!(positions.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", positions);
+    assert!(positions.iter().any(|&p| p > 0.0 && p < 100.0), "{:?}", positions);
+
+    // Nothing more once unsubscribed
+    client
+        .request(json!({"type": "unsubscribe_position"}))
+        .await?;
+    client.request(json!({"type": "close"})).await?;
+    let message = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "position"
+                || (message["type"] == "status" && message["door"]["state"] == "closed")
+        })
+        .await?;
+    assert_eq!(message["type"], "status");
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscriptions_filter_broadcasts_per_client() -> Result<()> {
+    let (_grbl, dosa, mut client) = start().await?;
+    let mut quiet = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    let reply = quiet
+        .request(json!({"type": "set_subscriptions", "position": false, "config": false, "events": false}))
+        .await?;
+    assert_eq!(reply["type"], "subscriptions", "{}", reply);
+    assert_eq!(reply["status"], true);
+    assert_eq!(reply["position"], false);
+    assert_eq!(reply["config"], false);
+    assert_eq!(reply["events"], false);
+
+    // 100mm at 3000 mm/min takes 2s, plenty of position ticks
+    client
+        .request(json!({"type": "set_config", "open_speed": 3000.0}))
+        .await?;
+    client.request(json!({"type": "open"})).await?;
+    let opening = RefCell::new(0);
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            if message["type"] == "status" && message["door"]["state"] == "opening" {
+                *opening.borrow_mut() += 1;
+            }
+            message["type"] == "status" && message["door"]["state"] == "open"
+        })
+        .await?;
+    assert!(opening.into_inner() > 1);
+
+    // Only the state changes reach the quiet client
+    let opening = RefCell::new(0);
+    let open = quiet
+        .wait_for(MOVE_TIMEOUT, |message| {
+            assert!(
+                !matches!(message["type"].as_str(), Some("config_changed" | "command_accepted")),
+                "{}",
+                message
+            );
+            if message["type"] == "status" && message["door"]["state"] == "opening" {
+                *opening.borrow_mut() += 1;
+            }
+            message["type"] == "status" && message["door"]["state"] == "open"
+        })
+        .await?;
+    assert_eq!(opening.into_inner(), 1);
+    assert_eq!(open["door"]["position_mm"], 100.0);
+
+    // Categories left out stay as they were
+    let reply = quiet
+        .request(json!({"type": "set_subscriptions", "events": true}))
+        .await?;
+    assert_eq!(reply["position"], false, "{}", reply);
+    assert_eq!(reply["events"], true, "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_resumes_from_the_saved_position() -> Result<()> {
+    let (grbl, mut dosa, mut client) = start().await?;
+    home(&mut client).await?;
+    client.request(json!({"type": "move", "percent": 40.0})).await?;
+    wait_for_state(&mut client, "intermediate").await?;
+
+    dosa.terminate().await?;
+    assert!(dosa.config_file("door-positions.json").exists());
+    dosa.start_again().await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    assert_eq!(status["door"]["position_mm"], 40.0);
+    // Used up by the start it was saved for
+    assert!(!dosa.config_file("door-positions.json").exists());
+
+    // No homing needed, and moves land where they did before
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+    assert_eq!(grbl.commands().iter().filter(|c| *c == "$HX").count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_halts_a_moving_door_first() -> Result<()> {
+    let (grbl, mut dosa, mut client) = start().await?;
+    home(&mut client).await?;
+    client
+        .request(json!({"type": "set_config", "open_speed": 600.0}))
+        .await?;
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    dosa.terminate().await?;
+    let stopped_at = grbl.position('X');
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(grbl.position('X'), stopped_at);
+
+    dosa.start_again().await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    let position = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!((position - stopped_at).abs() < 0.01, "{} vs {}", position, stopped_at);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reset_controller_position_is_only_restored_when_trusted() -> Result<()> {
+    let (grbl, mut dosa, mut client) = start().await?;
+
+    for trusted in [false, true] {
+        home(&mut client).await?;
+        client.request(json!({"type": "move", "percent": 25.0})).await?;
+        wait_for_state(&mut client, "intermediate").await?;
+
+        // The controller is reset while dosa is down and comes back in alarm
+        dosa.terminate().await?;
+        grbl.trigger_alarm(11);
+        if trusted {
+            let path = dosa.config_file("config.yaml");
+            let config = std::fs::read_to_string(&path)?;
+            std::fs::write(&path, config.replace("restore_position: verify", "restore_position: trust"))?;
+        }
+        dosa.start_again().await?;
+        client = WsClient::connect(dosa.port()).await?;
+
+        if trusted {
+            let status = wait_for_state(&mut client, "intermediate").await?;
+            assert_eq!(status["door"]["position_mm"], 25.0);
+            assert!(grbl.commands().iter().any(|c| c == "$X"));
+        } else {
+            // `verify` (the default) won't restore a position the controller lost
+            let status = wait_for_state(&mut client, "alarm").await?;
+            assert_eq!(status["door"]["position_mm"], 0.0);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn power_cut_offers_the_last_position_instead_of_homing() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let port = grbl.port();
+    let mut dosa = Service::dosa(&grbl, "").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    client.request(json!({"type": "move", "percent": 40.0})).await?;
+    wait_for_state(&mut client, "intermediate").await?;
+
+    // Power goes off for both: no shutdown save, and the controller starts over at MPos 0
+    drop(grbl);
+    dosa.terminate().await?;
+    let _ = std::fs::remove_file(dosa.config_file("door-positions.json"));
+    assert!(dosa.config_file("last-positions.json").exists());
+    let grbl = MockGrbl::start_on(port).await?;
+    dosa.start_again().await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["trust_position_mm"].is_number()
+        })
+        .await?;
+    assert_eq!(status["door"]["state"], "pending");
+    assert_eq!(status["door"]["trust_position_mm"], 40.0);
+
+    client.request(json!({"type": "trust_position"})).await?;
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    assert_eq!(status["door"]["position_mm"], 40.0);
+    assert!(status["door"].get("trust_position_mm").is_none(), "{}", status);
+
+    // Moves carry on from the trusted position without a homing cycle
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 60.0).abs() < 0.01);
+    assert!(!grbl.commands().iter().any(|c| c.starts_with("$H")));
+
+    let error = client.request(json!({"type": "trust_position"})).await?;
+    assert_eq!(error["type"], "error", "{}", error);
+    Ok(())
+}
+
+#[tokio::test]
+async fn alarm_is_reported_and_cleared() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    grbl.trigger_alarm(1);
+    let status = wait_for_state(&mut client, "alarm").await?;
+    assert_eq!(status["door"]["alarm_code"], "1");
+    assert_eq!(
+        status["door"]["alarm_description"],
+        "Hard limit triggered, position lost"
+    );
+
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "failed");
+
+    // Refused commands say what the alarm means
+    let report = client
+        .request(json!({"type": "run_scene", "scene": "open-up"}))
+        .await?;
+    assert_eq!(report["success"], false);
+    let error = report["results"][0]["error"].as_str().unwrap_or_default();
+    assert!(error.contains("Code 1: Hard limit triggered"), "{}", report);
+
+    let reply = client.request(json!({"type": "clear_alarm"})).await?;
+    assert_eq!(reply["success"], true);
+    assert!(grbl.commands().iter().any(|c| c == "$X"));
+
+    // Clearing an alarm loses the position reference, so the door needs homing again
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "pending");
+    Ok(())
+}
+
+#[tokio::test]
+async fn scenes_run_local_actions() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let report = client
+        .request(json!({"type": "run_scene", "scene": "open-up"}))
+        .await?;
+    assert_eq!(report["type"], "scene_result");
+    assert_eq!(report["success"], true);
+    assert_eq!(report["results"][0]["action"], "open");
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+
+    let reply = client
+        .request(json!({"type": "run_scene", "scene": "missing"}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    assert!(reply["message"]
+        .as_str()
+        .unwrap_or("")
+        .contains("Unknown scene"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn obstruction_stops_and_backs_off() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let obstruction = "  obstruction:\n    enabled: true\n    field: Ld\n    threshold: 80\n    \
+                       samples: 1\n    back_off: 5\n";
+    let dosa = Service::dosa(&grbl, obstruction).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "set_config", "open_speed": 600.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    grbl.set_report_field("Ld", "95,0,0");
+    let status = wait_for_state(&mut client, "obstructed").await?;
+    let stopped_at = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!(stopped_at > 5.0, "stopped at {}", stopped_at);
+
+    // Backed off toward closed and stays obstructed once idle
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!((grbl.position('X') - (stopped_at - 5.0)).abs() < 0.5);
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "obstructed");
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(health["status"], "degraded");
+
+    grbl.set_report_field("Ld", "10,0,0");
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn obstruction_backs_off_despite_interlock() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let obstruction = "  obstruction:\n    enabled: true\n    field: Ld\n    threshold: 80\n    \
+                       samples: 1\n    back_off: 5\n";
+    let dosa = Service::dosa(&grbl, obstruction).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+
+    // The interlock keeps clients from opening the door, not the door from backing off
+    let reply = client
+        .request(json!({"type": "set_interlock", "reason": "High wind"}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client
+        .request(json!({"type": "set_config", "close_speed": 600.0}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closing").await?;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    grbl.set_report_field("Ld", "95,0,0");
+    let status = wait_for_state(&mut client, "obstructed").await?;
+    let stopped_at = status["door"]["position_mm"].as_f64().unwrap_or(0.0);
+    assert!(stopped_at < 95.0, "stopped at {}", stopped_at);
+
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!((grbl.position('X') - (stopped_at + 5.0)).abs() < 0.5);
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["interlock"], "High wind");
+    Ok(())
+}
+
+#[tokio::test]
+async fn safety_sensor_reopens_closing_door() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let sensor =
+        "  safety_sensor:\n    enabled: true\n    input:\n      source: grbl\n      pin: P\n";
+    let dosa = Service::dosa(&grbl, sensor).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "set_config", "close_speed": 600.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closing").await?;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // Beam broken: the door stops and opens fully again
+    grbl.set_report_field("Pn", "P");
+    wait_for_state(&mut client, "opening").await?;
+    let status = wait_for_state(&mut client, "open").await?;
+    assert_eq!(status["door"]["safety_sensor"]["triggered"], true);
+
+    let reply = client.request(json!({"type": "get_safety_sensor"})).await?;
+    assert_eq!(reply["type"], "safety_sensor", "{}", reply);
+    assert_eq!(reply["config"]["input"]["source"], "grbl");
+    assert_eq!(reply["state"]["triggered"], true);
+
+    // Won't close while the beam is broken
+    client.request(json!({"type": "close"})).await?;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "open");
+
+    grbl.remove_report_field("Pn");
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["safety_sensor"]["triggered"] == false
+        })
+        .await?;
+    let reply = client
+        .request(json!({"type": "set_config", "close_speed": 60000.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn estop_latches_until_reset() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "  estop:\n    enabled: true\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "set_config", "open_speed": 600.0}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // grblHAL's E-stop pin shows up as `E` in `Pn:`
+    grbl.set_report_field("Pn", "E");
+    wait_for_state(&mut client, "estop").await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let stopped_at = grbl.position('X');
+    assert!(stopped_at > 0.0 && stopped_at < 100.0, "stopped at {}", stopped_at);
+
+    let reply = client.request(json!({"type": "clear_alarm"})).await?;
+    assert_eq!(reply["type"], "error");
+    let reply = client.request(json!({"type": "reset_estop"})).await?;
+    assert_eq!(reply["type"], "error", "reset while still pressed: {}", reply);
+
+    // Released, it stays latched and refuses to move until reset
+    grbl.remove_report_field("Pn");
+    client.request(json!({"type": "close"})).await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "estop");
+    assert_eq!(grbl.position('X'), stopped_at);
+
+    let reply = client.request(json!({"type": "reset_estop"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "intermediate").await?;
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tokens_grant_read_or_control() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let auth =
+        "auth:\n  enabled: true\n  tokens:\n    - name: dashboard\n      token: watch-only\n      \
+                role: read\n    - name: hub\n      token: \"let me in\"\n      role: control\n";
+    let dosa = Service::dosa(&grbl, auth).await?;
+
+    assert!(
+        WsClient::connect(dosa.port()).await.is_err(),
+        "connected without a token"
+    );
+    assert!(WsClient::connect_with_token(dosa.port(), "wrong")
+        .await
+        .is_err());
+
+    // Read role: queries work, commands are refused
+    let mut dashboard = WsClient::connect_with_token(dosa.port(), "watch-only").await?;
+    let status = dashboard.request(json!({"type": "status"})).await?;
+    assert_eq!(status["type"], "status");
+    let reply = dashboard.request(json!({"type": "home"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(reply["message"].as_str().unwrap_or("").contains("control"));
+    let reply = dashboard
+        .request(json!({"type": "set_cnc_setting", "setting": "$110", "value": "1000"}))
+        .await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+
+    // Control role, token in the query string
+    let url = format!("ws://127.0.0.1:{}/?token=let%20me%20in", dosa.port());
+    let mut hub = WsClient::connect_url(&url).await?;
+    home(&mut hub).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn repeated_motion_commands_are_debounced_and_limited() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let limit = "command_limit:\n  rate: 0.1\n  burst: 3\n  debounce_ms: 2000\n";
+    let dosa = Service::dosa(&grbl, limit).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    // A stuck button: the repeat is acknowledged but only one move reaches the controller
+    for _ in 0..2 {
+        let reply = client.request(json!({"type": "open"})).await?;
+        assert_eq!(reply["success"], true, "{}", reply);
+    }
+    wait_for_state(&mut client, "open").await?;
+    let moves = grbl.commands().iter().filter(|c| c.contains("G1")).count();
+    assert_eq!(moves, 1);
+
+    // Home, open and close used up the burst; stop always gets through
+    client.request(json!({"type": "close"})).await?;
+    let reply = client.request(json!({"type": "move", "percent": 50})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(reply["retry_after_ms"].as_u64().unwrap_or(0) > 0, "{}", reply);
+    let reply = client.request(json!({"type": "stop"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn raw_console_needs_the_advanced_role() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let auth = "auth:\n  enabled: true\n  tokens:\n    - name: hub\n      token: hub\n      role: control\n    \
+                - name: bench\n      token: bench\n      role: advanced\n";
+    let dosa = Service::dosa(&grbl, auth).await?;
+
+    let mut hub = WsClient::connect_with_token(dosa.port(), "hub").await?;
+    let reply = hub.request(json!({"type": "raw_command", "command": "$$"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(reply["message"].as_str().unwrap_or("").contains("advanced"), "{}", reply);
+    let reply = hub.request(json!({"type": "subscribe_console"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+
+    // Advanced role: commands go straight through, and the console shows both directions
+    let mut bench = WsClient::connect_with_token(dosa.port(), "bench").await?;
+    let reply = bench.request(json!({"type": "subscribe_console"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = bench.request(json!({"type": "raw_command", "command": "$$"})).await?;
+    assert_eq!(reply["type"], "raw_response", "{}", reply);
+    assert_eq!(reply["door"], "main");
+    let lines: Vec<&str> = reply["lines"].as_array().unwrap().iter().filter_map(|l| l.as_str()).collect();
+    assert_eq!(lines.last(), Some(&"ok"), "{:?}", lines);
+    assert!(lines.iter().any(|line| line.starts_with("$110=")), "{:?}", lines);
+
+    bench
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "console" && message["direction"] == "sent" && message["line"] == "$$"
+        })
+        .await?;
+    bench
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "console" && message["direction"] == "received" && message["line"] == "ok"
+        })
+        .await?;
+
+    // Homing has to go through `home`, which tracks the cycle
+    let reply = bench.request(json!({"type": "raw_command", "command": "$HX"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(!grbl.commands().iter().any(|c| c.starts_with("$H")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhooks_get_door_events_and_retry() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    // The first delivery fails and is retried
+    let moves = WebhookServer::start(1).await?;
+    let alarms = WebhookServer::start(0).await?;
+    let webhooks = format!(
+        "webhooks:\n  - url: \"{}\"\n    events: [opened, closed]\n    headers:\n      Authorization: \"Bearer hook\"\n    \
+         retries: 2\n  - url: \"{}\"\n    events: [alarm]\n",
+        moves.url(),
+        alarms.url()
+    );
+    let dosa = Service::dosa(&grbl, &webhooks).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    home(&mut client).await?;
+    let closed = moves.wait_for(MOVE_TIMEOUT, |body| body["event"] == "closed").await?;
+    assert_eq!(closed.body["door"], "main");
+    assert_eq!(closed.body["state"], "closed");
+    assert_eq!(closed.headers.get("authorization").map(String::as_str), Some("Bearer hook"));
+    assert_eq!(moves.requests()[0].status, 500);
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    let opened = moves.wait_for(MOVE_TIMEOUT, |body| body["event"] == "opened").await?;
+    assert_eq!(opened.body["position_mm"], 100.0);
+    assert!(opened.body["timestamp_ms"].as_u64().unwrap_or(0) > 0, "{}", opened.body);
+
+    grbl.trigger_alarm(1);
+    let alarm = alarms.wait_for(MOVE_TIMEOUT, |body| body["event"] == "alarm").await?;
+    assert_eq!(alarm.body["alarm_code"], "1");
+    // Each webhook only gets the events it asked for
+    assert!(alarms.requests().iter().all(|request| request.body["event"] == "alarm"));
+    assert!(!moves.requests().iter().any(|request| request.body["event"] == "alarm"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_api_drives_the_door() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let port = free_port()?;
+    let config = format!(
+        "http:\n  host: \"127.0.0.1\"\n  port: {}\nauth:\n  enabled: true\n  tokens:\n    - name: dashboard\n      \
+         token: watch-only\n      role: read\n    - name: script\n      token: curl\n      role: control\n",
+        port
+    );
+    let dosa = Service::dosa(&grbl, &config).await?;
+    let mut client = WsClient::connect_with_token(dosa.port(), "curl").await?;
+    home(&mut client).await?;
+    // Let the WebSocket client's claim from homing lapse
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    let (code, reply) = http_request(port, "GET", "/status", None, None).await?;
+    assert_eq!(code, 401, "{}", reply);
+    let (code, reply) = http_request(port, "GET", "/status", Some("watch-only"), None).await?;
+    assert_eq!(code, 200, "{}", reply);
+    assert_eq!(reply["door"]["state"], "closed");
+    let (code, reply) = http_request(port, "POST", "/open", Some("watch-only"), None).await?;
+    assert_eq!(code, 403, "{}", reply);
+
+    let (code, reply) = http_request(port, "POST", "/open", Some("curl"), None).await?;
+    assert_eq!(code, 202, "{}", reply);
+    assert_eq!(reply["command"], "open");
+    let accepted = client
+        .wait_for(MOVE_TIMEOUT, |message| message["type"] == "command_accepted")
+        .await?;
+    assert!(accepted["client"].as_str().unwrap_or("").starts_with("script@"), "{}", accepted);
+    wait_for_state(&mut client, "open").await?;
+
+    let (code, reply) = http_request(port, "POST", "/move", Some("curl"), Some(&json!({"percent": 50.0}))).await?;
+    assert_eq!(code, 202, "{}", reply);
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    assert_eq!(status["door"]["position_mm"], 50.0);
+
+    let (code, reply) = http_request(port, "POST", "/move", Some("curl"), None).await?;
+    assert_eq!(code, 400, "{}", reply);
+    let (code, reply) = http_request(port, "POST", "/close?door=nope", Some("curl"), None).await?;
+    assert_eq!(code, 404, "{}", reply);
+
+    http_request(port, "POST", "/close", Some("curl"), None).await?;
+    wait_for_state(&mut client, "closed").await?;
+    let (code, _) = http_request(port, "POST", "/stop", Some("curl"), None).await?;
+    assert_eq!(code, 202);
+    Ok(())
+}
+
+#[tokio::test]
+async fn interlock_keeps_the_door_from_opening() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let port = free_port()?;
+    let config = format!("  open_speed: 600.0\nhttp:\n  host: \"127.0.0.1\"\n  port: {}\n", port);
+    let dosa = Service::dosa(&grbl, &config).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    // A weather station's webhook
+    let body = json!({"reason": "High wind"});
+    let (code, reply) = http_request(port, "POST", "/interlock", None, Some(&body)).await?;
+    assert_eq!(code, 200, "{}", reply);
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| message["type"] == "status" && message["door"]["interlock"] == "High wind")
+        .await?;
+    assert_eq!(status["door"]["state"], "closed");
+
+    let reply = client.request(json!({"type": "open"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(reply["message"].as_str().unwrap_or("").contains("High wind"), "{}", reply);
+    let reply = client.request(json!({"type": "move", "percent": 50.0})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    let (code, reply) = http_request(port, "POST", "/open", None, None).await?;
+    assert_eq!(code, 409, "{}", reply);
+    let (code, reply) = http_request(port, "POST", "/interlock", None, Some(&json!({"reason": " "}))).await?;
+    assert_eq!(code, 400, "{}", reply);
+
+    let (code, reply) = http_request(port, "DELETE", "/interlock", None, None).await?;
+    assert_eq!(code, 200, "{}", reply);
+    let status = client.request(json!({"type": "status"})).await?;
+    assert!(status["door"].get("interlock").is_none(), "{}", status);
+
+    // Setting it mid-opening stops the door, and closing still works
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "opening").await?;
+    let reply = client
+        .request(json!({"type": "set_interlock", "reason": "Storm warning"}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    assert_eq!(status["door"]["interlock"], "Storm warning");
+
+    let reply = client.request(json!({"type": "close"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn schedules_are_added_listed_and_removed() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let job = json!({"id": "open-now", "cron": "* * * * * *", "action": "open"});
+    let reply = client
+        .request(json!({"type": "add_schedule", "job": job}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    // Runs without a restart
+    wait_for_state(&mut client, "open").await?;
+
+    let reply = client
+        .request(json!({"type": "add_schedule", "job": job}))
+        .await?;
+    assert_eq!(reply["type"], "error", "duplicate ID accepted: {}", reply);
+
+    let reply = client.request(json!({"type": "list_schedules"})).await?;
+    assert_eq!(reply["type"], "schedules");
+    let jobs = reply["jobs"].as_array().expect("jobs");
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0]["id"], "open-now");
+    assert_eq!(jobs[0]["action"], "open");
+    assert!(jobs[0]["next_run_ms"].as_i64().unwrap_or(0) > 0);
+
+    let remove = json!({"type": "remove_schedule", "job_id": "open-now"});
+    let reply = client.request(remove.clone()).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client.request(remove).await?;
+    assert_eq!(reply["type"], "error");
+
+    let reply = client.request(json!({"type": "list_schedules"})).await?;
+    assert_eq!(reply["jobs"], json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn presets_are_saved_and_opened() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "set_preset", "name": "pet", "percent": 25.0}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client
+        .request(json!({"type": "set_preset", "name": "vent", "percent": 140.0}))
+        .await?;
+    assert_eq!(
+        reply["type"], "error",
+        "out of range preset accepted: {}",
+        reply
+    );
+
+    let reply = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(
+        reply["config"]["presets"],
+        json!({"pet": 25.0}),
+        "{}",
+        reply
+    );
+
+    let reply = client
+        .request(json!({"type": "open_preset", "name": "pet"}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let status = wait_for_state(&mut client, "intermediate").await?;
+    let percent = status["door"]["position_percent"].as_f64().unwrap_or(0.0);
+    assert!((percent - 25.0).abs() < 1.0, "stopped at {}%", percent);
+
+    let reply = client
+        .request(json!({"type": "open_preset", "name": "vent"}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+
+    let remove = json!({"type": "remove_preset", "name": "pet"});
+    let reply = client.request(remove.clone()).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client.request(remove).await?;
+    assert_eq!(reply["type"], "error");
+    Ok(())
+}
+
+#[tokio::test]
+async fn motion_profile_ramps_and_creeps() -> Result<()> {
+    let (grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let motion = json!({"ramp_distance": 20.0, "creep_distance": 30.0, "creep_speed": 1200.0});
+    let reply = client
+        .request(json!({"type": "set_config", "motion": motion}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    let before = grbl.commands().len();
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+
+    let moves: Vec<String> = grbl.commands()[before..]
+        .iter()
+        .filter(|command| command.contains("G1"))
+        .cloned()
+        .collect();
+    assert_eq!(
+        moves,
+        [
+            "G90 G1 X5F1200",
+            "G90 G1 X10F15900",
+            "G90 G1 X15F30600",
+            "G90 G1 X20F45300",
+            "G90 G1 X70F60000",
+            "G90 G1 X100F1200",
+        ]
+    );
+
+    // Short moves that don't reach the end of travel skip the creep
+    let before = grbl.commands().len();
+    client
+        .request(json!({"type": "move", "percent": 90.0}))
+        .await?;
+    wait_for_state(&mut client, "intermediate").await?;
+    let moves: Vec<String> = grbl.commands()[before..]
+        .iter()
+        .filter(|command| command.contains("G1"))
+        .cloned()
+        .collect();
+    assert_eq!(moves.len(), 4, "{:?}", moves);
+    assert!(moves[3].starts_with("G90 G1 X90F"), "{:?}", moves);
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_door_auto_closes_unless_held() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+
+    let reply = client
+        .request(json!({"type": "set_config", "auto_close_after_secs": 2}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client.request(json!({"type": "hold_open"})).await?;
+    assert_eq!(reply["type"], "error", "held a closed door: {}", reply);
+
+    client.request(json!({"type": "open"})).await?;
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["auto_close_in_secs"].is_u64()
+        })
+        .await?;
+    assert_eq!(status["door"]["state"], "open");
+    assert!(status["door"]["auto_close_in_secs"].as_u64().unwrap_or(0) <= 2);
+    wait_for_state(&mut client, "closed").await?;
+
+    // Held open: the countdown goes away and the door stays put
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    let reply = client.request(json!({"type": "hold_open"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "open");
+    assert!(
+        status["door"].get("auto_close_in_secs").is_none(),
+        "{}",
+        status
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn hold_open_keeps_the_door_open_until_released() -> Result<()> {
+    let (_grbl, _dosa, mut client) = start().await?;
+    home(&mut client).await?;
+    let reply = client
+        .request(json!({"type": "set_config", "auto_close_after_secs": 1}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    let reply = client
+        .request(json!({"type": "hold_open", "duration_secs": 5, "indefinite": true}))
+        .await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    let reply = client.request(json!({"type": "release_hold"})).await?;
+    assert_eq!(reply["type"], "error", "released without a hold: {}", reply);
+
+    // An indefinite hold opens the closed door and outlasts the auto-close delay
+    let reply = client
+        .request(json!({"type": "hold_open", "indefinite": true}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "open").await?;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "open");
+    assert_eq!(status["door"]["hold"], "indefinite", "{}", status);
+    assert!(status["door"].get("auto_close_in_secs").is_none(), "{}", status);
+
+    // Releasing it lets auto-close take over
+    let reply = client.request(json!({"type": "release_hold"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "closed").await?;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert!(status["door"].get("hold").is_none(), "{}", status);
+
+    // A timed hold counts down and then expires on its own
+    client
+        .request(json!({"type": "hold_open", "duration_secs": 2}))
+        .await?;
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["hold"] == "timed"
+        })
+        .await?;
+    assert!(status["door"]["hold_remaining_secs"].as_u64().unwrap_or(0) <= 2, "{}", status);
+    wait_for_state(&mut client, "open").await?;
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn doors_are_addressed_by_id() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let doors = format!(
+        "doors:\n  side:\n    open_distance: 50.0\n    open_speed: 60000.0\n    \
+         close_speed: 60000.0\n    cnc_axis: Y\n    open_direction: left\n    \
+         cnc_connection:\n      type: tcp\n      host: \"127.0.0.1\"\n      port: {}\n",
+        grbl.port()
+    );
+    let dosa = Service::dosa(&grbl, &doors).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    let reply = client
+        .request(json!({"type": "home", "door": "side"}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_door_state(&mut client, "side", "closed").await?;
+
+    client
+        .request(json!({"type": "open", "door": "side"}))
+        .await?;
+    wait_for_door_state(&mut client, "side", "open").await?;
+    assert!((grbl.position('Y') + 50.0).abs() < 0.01);
+    assert_eq!(grbl.position('X'), 0.0);
+
+    // The primary door is untouched and still the default target
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["id"], "main");
+    assert_eq!(status["door"]["state"], "pending");
+    let status = client
+        .request(json!({"type": "status", "door": "side"}))
+        .await?;
+    assert_eq!(status["door"]["state"], "open");
+
+    let reply = client
+        .request(json!({"type": "open", "door": "back"}))
+        .await?;
+    assert_eq!(reply["type"], "error", "unknown door accepted: {}", reply);
+
+    let health = client.request(json!({"type": "get_health"})).await?;
+    let names: Vec<&str> = health["components"]
+        .as_array()
+        .expect("components")
+        .iter()
+        .filter_map(|component| component["name"].as_str())
+        .collect();
+    assert!(
+        names.contains(&"cnc") && names.contains(&"side.cnc"),
+        "{:?}",
+        names
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_axes_move_in_lockstep() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let sync = "  sync_axes:\n    - axis: Z\n      open_direction: left\n";
+    let dosa = Service::dosa(&grbl, sync).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    let commands = grbl.commands();
+    assert!(
+        commands.iter().any(|command| command == "$HZ"),
+        "{:?}",
+        commands
+    );
+    assert!(
+        commands.iter().any(|command| command == "G92 X0 Z0"),
+        "{:?}",
+        commands
+    );
+
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!((grbl.position('X') - 100.0).abs() < 0.01);
+    assert!((grbl.position('Z') + 100.0).abs() < 0.01);
+    let commands = grbl.commands();
+    assert!(
+        commands
+            .iter()
+            .any(|command| command.starts_with("G90 G1 X100 Z-100F")),
+        "{:?}",
+        commands
+    );
+
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    assert!(grbl.position('Z').abs() < 0.01);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_serves_wss_only() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let tls = format!(
+        "websocket:\n  tls:\n    cert: {:?}\n    key: {:?}\n",
+        fixture("tls/server.pem"),
+        fixture("tls/server.key")
+    );
+    let dosa = Service::dosa(&grbl, &tls).await?;
+
+    let mut client = WsClient::connect_tls(dosa.port(), &fixture("tls/ca.pem")).await?;
+    let reply = client.request(json!({"type": "status"})).await?;
+    assert_eq!(reply["type"], "status", "{}", reply);
+
+    assert!(WsClient::connect(dosa.port()).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn scene_drives_tls_peer() -> Result<()> {
+    let peer_grbl = MockGrbl::start().await?;
+    let tls = format!(
+        "websocket:\n  tls:\n    cert: {:?}\n    key: {:?}\n",
+        fixture("tls/server.pem"),
+        fixture("tls/server.key")
+    );
+    let peer = Service::dosa(&peer_grbl, &tls).await?;
+
+    // The hub trusts the test CA in place of the system's root certificates
+    let grbl = MockGrbl::start().await?;
+    let hub = Service::dosa_with_env(
+        &grbl,
+        &format!(
+            "scene:\n  peers:\n    door:\n      url: \"wss://localhost:{}\"\n  scenes:\n    \
+             - id: reset-door\n      actions:\n        \
+             - remote: {{ peer: door, message: {{ type: home }} }}\n",
+            peer.port()
+        ),
+        &[("SSL_CERT_FILE", fixture("tls/ca.pem"))],
+    )
+    .await?;
+    let mut client = WsClient::connect(hub.port()).await?;
+
+    let report = client
+        .request(json!({"type": "run_scene", "scene": "reset-door"}))
+        .await?;
+    assert_eq!(report["success"], true, "{}", report);
+    assert_eq!(report["results"][0]["target"], "door");
+
+    // dosa acknowledges home straight away and homes in the background
+    for _ in 0..200 {
+        if peer_grbl.commands().iter().any(|c| c == "$HX") {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    anyhow::bail!("peer never homed: {:?}", peer_grbl.commands())
+}
+
+#[tokio::test]
+async fn history_records_commands_and_state_changes() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "audit:\n  query:\n    enabled: true\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+
+    let reply = client.request(json!({"type": "get_history"})).await?;
+    assert_eq!(reply["type"], "history", "{}", reply);
+    let records = reply["records"].as_array().expect("records");
+    assert!(records
+        .iter()
+        .all(|r| r["kind"] == "command" || r["kind"] == "state"));
+
+    let open = records
+        .iter()
+        .position(|r| r["action"] == "open")
+        .expect("open record");
+    assert!(records[open]["client"]
+        .as_str()
+        .is_some_and(|c| c.starts_with("127.0.0.1:")));
+    // Moves are quick enough that `opening` may fall between two polls
+    let opened = records[open..]
+        .iter()
+        .find(|r| r["action"] == "state_changed" && r["detail"]["to"] == "open")
+        .expect("state change to open");
+    assert_eq!(opened["detail"]["door"], "main");
+    assert_eq!(opened["detail"]["position_percent"], 100.0);
+
+    // Only events from `since` on
+    let since = records[open]["timestamp_ms"].as_u64().unwrap_or_default() + 60_000;
+    let reply = client
+        .request(json!({"type": "get_history", "since": since}))
+        .await?;
+    assert_eq!(reply["records"], json!([]), "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn stats_time_each_open_and_close() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    for (command, state) in [("open", "open"), ("close", "closed"), ("open", "open")] {
+        client.request(json!({"type": command})).await?;
+        wait_for_state(&mut client, state).await?;
+    }
+
+    let reply = client.request(json!({"type": "get_stats"})).await?;
+    assert_eq!(reply["type"], "stats", "{}", reply);
+    assert_eq!(reply["door"], "main");
+    let cycles = reply["cycles"].as_array().expect("cycles");
+    let directions: Vec<_> = cycles.iter().map(|c| c["direction"].clone()).collect();
+    assert_eq!(directions, [json!("open"), json!("close"), json!("open")], "{}", reply);
+    for cycle in cycles {
+        let duration = cycle["duration_secs"].as_f64().expect("duration");
+        assert!(duration > 0.0 && duration < 5.0, "{}", cycle);
+        assert_eq!(cycle["speed"], 60000.0);
+        assert_eq!(cycle["distance_mm"], 100.0);
+        assert_eq!(cycle["slow"], false);
+    }
+    // Three cycles in one direction are needed for a baseline
+    assert_eq!(reply["open_baseline_secs"], json!(null));
+    Ok(())
+}
+
+#[tokio::test]
+async fn lost_controller_reconnects_in_background() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let port = grbl.port();
+    let dosa = Service::dosa(&grbl, "  reconnect_interval_secs: 1\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    // The controller goes away; the next command fails and leaves the door in fault
+    drop(grbl);
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "fault").await?;
+
+    // Once it's back the door recovers by itself and needs homing again
+    let _grbl = MockGrbl::start_on(port).await?;
+    wait_for_state(&mut client, "pending").await?;
+    home(&mut client).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn lost_controller_fails_over_to_the_backup() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let port = grbl.port();
+    let backup = MockGrbl::start().await?;
+    let fallbacks = format!(
+        "  cnc_fallbacks:\n    - type: tcp\n      host: \"127.0.0.1\"\n      port: {}\n",
+        backup.port()
+    );
+    let dosa = Service::dosa(&grbl, &fallbacks).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["cnc_endpoint"], format!("127.0.0.1:{}", port));
+    home(&mut client).await?;
+
+    // The next command finds the controller gone and switches to the backup
+    drop(grbl);
+    client.request(json!({"type": "open"})).await?;
+    let backup_endpoint = format!("127.0.0.1:{}", backup.port());
+    let status = client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["cnc_endpoint"] == backup_endpoint.as_str()
+        })
+        .await?;
+    assert_ne!(status["door"]["state"], "fault", "{}", status);
+    home(&mut client).await?;
+    assert!(backup.commands().iter().any(|c| c.starts_with("$H")));
+
+    // Failing over wraps around to the first controller
+    drop(backup);
+    let _grbl = MockGrbl::start_on(port).await?;
+    client.request(json!({"type": "open"})).await?;
+    client
+        .wait_for(MOVE_TIMEOUT, |message| {
+            message["type"] == "status" && message["door"]["cnc_endpoint"] == format!("127.0.0.1:{}", port)
+        })
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn polling_slows_down_at_rest() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let polling = "  poll_interval_ms: 100\n  idle_poll_interval_ms: 1500\n";
+    let dosa = Service::dosa(&grbl, polling).await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let queries = grbl.status_queries();
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let idle = grbl.status_queries() - queries;
+    assert!(idle <= 3, "{} polls in 3s at rest", idle);
+
+    // Starting a move cuts the idle wait short
+    let started = tokio::time::Instant::now();
+    client.request(json!({"type": "open"})).await?;
+    wait_for_state(&mut client, "open").await?;
+    assert!(
+        started.elapsed() < Duration::from_millis(1000),
+        "took {:?} to see the door open",
+        started.elapsed()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn pushed_status_reports_replace_polling() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "  status_report_interval_ms: 100\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+    assert!(grbl.commands().contains(&"$481=100".to_string()), "{:?}", grbl.commands());
+
+    // The door follows the pushed reports without asking for them
+    let queries = grbl.status_queries();
+    let reply = client.request(json!({"type": "open"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "open").await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(grbl.status_queries(), queries);
+
+    client.request(json!({"type": "close"})).await?;
+    wait_for_state(&mut client, "closed").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn alarm_line_is_reported_before_the_next_status_report() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "  status_report_interval_ms: 1000\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+    home(&mut client).await?;
+
+    // The ALARM: line gets there well before the next pushed report would
+    grbl.trigger_alarm(1);
+    let status = client
+        .wait_for(Duration::from_millis(300), |message| {
+            message["type"] == "status" && message["door"]["state"] == "alarm"
+        })
+        .await?;
+    assert_eq!(status["door"]["alarm_code"], "1");
+
+    let reply = client.request(json!({"type": "clear_alarm"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let status = client.request(json!({"type": "status"})).await?;
+    assert_eq!(status["door"]["state"], "pending");
+    Ok(())
+}
+
+#[tokio::test]
+async fn homing_times_out_after_the_configured_time() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    grbl.set_homing_time(Duration::from_millis(2500));
+    let dosa = Service::dosa(&grbl, "  timeouts:\n    homing_secs: 1\n").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    // Gives up after a second, aborting the cycle with a soft reset (alarm 6)
+    let started = tokio::time::Instant::now();
+    let reply = client.request(json!({"type": "home"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    wait_for_state(&mut client, "homing").await?;
+    wait_for_state(&mut client, "pending").await?;
+    let status = wait_for_state(&mut client, "alarm").await?;
+    assert_eq!(status["door"]["alarm_code"], "6");
+    assert!(
+        started.elapsed() < Duration::from_millis(2500),
+        "took {:?} to give up",
+        started.elapsed()
+    );
+
+    // Nothing is left running, so a cycle within the timeout homes the door straight away
+    grbl.set_homing_time(Duration::from_millis(300));
+    home(&mut client).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_aborts_a_hung_homing_cycle() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    grbl.set_homing_time(Duration::from_secs(30));
+    let dosa = Service::dosa(&grbl, "").await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    client.request(json!({"type": "home"})).await?;
+    wait_for_state(&mut client, "homing").await?;
+    let started = tokio::time::Instant::now();
+    let reply = client.request(json!({"type": "stop"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    wait_for_state(&mut client, "pending").await?;
+    let status = wait_for_state(&mut client, "alarm").await?;
+    assert_eq!(status["door"]["alarm_code"], "6");
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+
+    // The timeout is a door setting that set_config can raise for the next cycle
+    let reply = client
+        .request(json!({"type": "set_config", "timeouts": {"homing_secs": 120}}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let config = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(config["config"]["timeouts"]["homing_secs"], 120);
+
+    grbl.set_homing_time(Duration::from_millis(300));
+    home(&mut client).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn clients_that_stop_answering_pings_are_dropped() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(
+        &grbl,
+        "websocket:\n  keep_alive:\n    ping_interval_secs: 1\n    timeout_secs: 1\n",
+    )
+    .await?;
+
+    // A wall panel that crashed: never reads, so never answers a ping
+    let mut silent = WsClient::connect(dosa.port()).await?;
+    let mut active = WsClient::connect(dosa.port()).await?;
+    for _ in 0..8 {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let reply = active.request(json!({"type": "status"})).await?;
+        assert_eq!(reply["type"], "status", "{}", reply);
+    }
+
+    let err = silent
+        .wait_for(Duration::from_secs(2), |_| false)
+        .await
+        .expect_err("silent client is still connected");
+    assert!(!err.to_string().contains("Timed out"), "{:#}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn command_log_lines_carry_a_correlation_id() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(
+        &grbl,
+        "logging:\n  file:\n    path: \"config/dosa/dosa.log\"\n    format: json\n",
+    )
+    .await?;
+    let mut client = WsClient::connect(dosa.port()).await?;
+
+    // Unhomed, both fail in the background; the errors are logged by the spawned tasks
+    client.request(json!({"type": "open"})).await?;
+    client.request(json!({"type": "close"})).await?;
+
+    let failed = |line: &Value, command: &str| {
+        let message = line["fields"]["message"].as_str().unwrap_or_default();
+        line["span"]["command"] == command && message.contains("failed")
+    };
+    let mut lines = Vec::new();
+    for _ in 0..50 {
+        let log = std::fs::read_to_string(dosa.config_file("dosa.log"))?;
+        lines = log.lines().map(serde_json::from_str).collect::<Result<Vec<Value>, _>>()?;
+        if ["open", "close"].iter().all(|command| lines.iter().any(|line| failed(line, command))) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let id = |command: &str| {
+        let line = lines
+            .iter()
+            .find(|line| failed(line, command))
+            .unwrap_or_else(|| panic!("no failed {} in {:?}", command, lines));
+        line["span"]["correlation_id"].as_str().unwrap_or_default().to_string()
+    };
+    let (open, close) = (id("open"), id("close"));
+    assert_eq!(open.len(), 16, "{}", open);
+    assert_ne!(open, close);
+    Ok(())
+}
+
+#[tokio::test]
+async fn config_file_edits_apply_without_a_restart() -> Result<()> {
+    let (_grbl, dosa, mut client) = start().await?;
+    let path = dosa.config_file("config.yaml");
+    let config = std::fs::read_to_string(&path)?;
+
+    std::fs::write(&path, config.replace("open_speed: 60000.0", "open_speed: 30000.0"))?;
+    let changed = client
+        .wait_for(Duration::from_secs(10), |message| message["type"] == "config_changed")
+        .await?;
+    assert_eq!(changed["sections"], json!(["door"]), "{}", changed);
+    let reply = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(reply["config"]["open_speed"], 30000.0, "{}", reply);
+
+    // An edit that doesn't validate is ignored
+    std::fs::write(&path, config.replace("open_speed: 60000.0", "open_speed: -1.0"))?;
+    let ignored = client
+        .wait_for(Duration::from_secs(5), |message| message["type"] == "config_changed")
+        .await;
+    assert!(ignored.is_err(), "{:?}", ignored);
+    let reply = client.request(json!({"type": "get_config"})).await?;
+    assert_eq!(reply["config"]["open_speed"], 30000.0, "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ctl_drives_the_door_from_the_command_line() -> Result<()> {
+    let (_grbl, dosa, mut client) = start().await?;
+
+    // Unhomed, the open is accepted but never starts, which --wait reports as a failure
+    let output = dosa.ctl(&["open", "--wait"]).await?;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+
+    home(&mut client).await?;
+    // Let the WebSocket client's claim from homing lapse
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let output = dosa.ctl(&["move", "50", "--wait"]).await?;
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("main: intermediate, 50.0% (50.0 mm)"), "{}", stdout);
+
+    let output = dosa.ctl(&["status", "--json"]).await?;
+    let status: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(status["door"]["position_percent"], 50.0, "{}", status);
+
+    let output = dosa.ctl(&["settings"]).await?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("open_distance: 100.0"), "{}", stdout);
+
+    // Bad usage exits with 2 without touching the door
+    let output = dosa.ctl(&["move", "150"]).await?;
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    Ok(())
+}
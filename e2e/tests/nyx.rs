@@ -0,0 +1,1284 @@
+//! nyx over WebSocket, against a fake backlight and no touch device.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use shq_e2e::chrome::SCREENSHOT;
+use shq_e2e::{fixture, free_port, http_request, MockChrome, Service, WsClient};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+
+async fn start() -> Result<(Service, WsClient)> {
+    let nyx = Service::nyx(json!({
+        "auto_dim": {
+            "dim_level": 10,
+            "bright_level": 200,
+            "auto_dim_time": 0,
+            "auto_off_time": 0
+        }
+    }))
+    .await?;
+    let client = WsClient::connect(nyx.port()).await?;
+    Ok((nyx, client))
+}
+
+fn component<'a>(health: &'a Value, name: &str) -> &'a Value {
+    health["components"]
+        .as_array()
+        .and_then(|components| components.iter().find(|c| c["name"] == name))
+        .unwrap_or(&Value::Null)
+}
+
+#[tokio::test]
+async fn hello_reply_carries_envelope() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "get_info"})).await?;
+    assert_eq!(reply["type"], "hello");
+    assert_eq!(reply["service"], "nyx");
+    assert_eq!(reply["source"], "nyx");
+    assert_eq!(reply["id"], "e2e-1");
+    Ok(())
+}
+
+#[tokio::test]
+async fn hello_negotiates_features() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client
+        .request(json!({"type": "hello", "features": ["auto_dim", "teleport", "auto_dim"]}))
+        .await?;
+    assert_eq!(reply["accepted"], json!(["auto_dim"]));
+    assert_eq!(reply["unsupported"], json!(["teleport"]));
+
+    // Plain handshakes are unchanged
+    let reply = client.request(json!({"type": "hello"})).await?;
+    assert!(reply.get("accepted").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn starts_at_bright_level() -> Result<()> {
+    let (nyx, mut client) = start().await?;
+
+    // 200 on the 0-255 scale, with max_brightness 100
+    assert_eq!(nyx.backlight()?, 78);
+
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["type"], "metrics");
+    assert_eq!(metrics["display"]["display_on"], true);
+    assert_eq!(metrics["auto_dim"]["bright_level"], 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn backlight_changes_outside_nyx_are_picked_up() -> Result<()> {
+    let (nyx, mut client) = start().await?;
+
+    // Reads come from a cache that's reconciled with sysfs every few seconds
+    nyx.set_backlight(50)?;
+    client
+        .wait_for(Duration::from_secs(10), |m| m["type"] == "metrics" && m["display"]["brightness"] == 127)
+        .await?;
+
+    // The cache knows the backlight moved, so setting the old level writes it again
+    client.request(json!({"type": "set_brightness", "brightness": 200})).await?;
+    assert_eq!(nyx.backlight()?, 78);
+    Ok(())
+}
+
+#[tokio::test]
+async fn brightness_reaches_backlight() -> Result<()> {
+    let (nyx, mut client) = start().await?;
+
+    let reply = client
+        .request(json!({"type": "set_brightness", "brightness": 255}))
+        .await?;
+    assert_eq!(reply["success"], true);
+    assert_eq!(nyx.backlight()?, 100);
+
+    client.request(json!({"type": "sleep"})).await?;
+    assert_eq!(nyx.backlight()?, 0);
+
+    client.request(json!({"type": "wake"})).await?;
+    assert!(nyx.backlight()? > 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn displays_are_addressed_by_id() -> Result<()> {
+    let nyx = Service::nyx_with_displays(
+        json!({
+            "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 0, "auto_off_time": 0},
+            "displays": {
+                "panel-b": {
+                    "auto_dim": {"dim_level": 5, "bright_level": 100, "auto_dim_time": 0, "auto_off_time": 0}
+                }
+            }
+        }),
+        &["panel-a", "panel-b"],
+    )
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    // Each display starts at its own bright level
+    assert_eq!(nyx.backlight_of("panel-a")?, 78);
+    assert_eq!(nyx.backlight_of("panel-b")?, 39);
+
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["display"]["id"], "panel-a");
+    let displays = metrics["displays"].as_array().cloned().unwrap_or_default();
+    assert_eq!(displays.len(), 2, "{}", metrics);
+    assert_eq!(displays[1]["id"], "panel-b");
+    assert_eq!(displays[1]["auto_dim"]["bright_level"], 100);
+
+    client
+        .request(json!({"type": "set_brightness", "display": "panel-b", "brightness": 255}))
+        .await?;
+    assert_eq!(nyx.backlight_of("panel-b")?, 100);
+    assert_eq!(nyx.backlight_of("panel-a")?, 78);
+
+    client.request(json!({"type": "sleep", "display": "panel-a"})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 0);
+    assert_eq!(nyx.backlight_of("panel-b")?, 100);
+
+    // Without a display, commands act on all of them
+    client.request(json!({"type": "sleep"})).await?;
+    assert_eq!(nyx.backlight_of("panel-b")?, 0);
+    client.request(json!({"type": "wake"})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 78);
+    assert_eq!(nyx.backlight_of("panel-b")?, 39);
+
+    let reply = client
+        .request(json!({"type": "wake", "display": "panel-c"}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["message"], "Unknown display 'panel-c'");
+
+    client
+        .request(json!({
+            "type": "set_auto_dim_config",
+            "display": "panel-a",
+            "dim_level": 20,
+            "bright_level": 150,
+            "auto_dim_time": 30,
+            "auto_off_time": 0
+        }))
+        .await?;
+    let reply = client
+        .request(json!({"type": "get_auto_dim_config", "display": "panel-a"}))
+        .await?;
+    assert_eq!(reply["config"]["bright_level"], 150);
+    let reply = client.request(json!({"type": "get_auto_dim_config"})).await?;
+    assert_eq!(reply["config"]["bright_level"], 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ctl_drives_the_display_from_the_command_line() -> Result<()> {
+    let (nyx, _client) = start().await?;
+
+    let output = nyx.ctl(&["brightness", "255"]).await?;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout)?.trim(), "set_brightness: accepted");
+    assert_eq!(nyx.backlight()?, 100);
+
+    let output = nyx.ctl(&["metrics"]).await?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("fake: on, brightness 255 (100%)"), "{}", stdout);
+
+    let output = nyx.ctl(&["sleep"]).await?;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(nyx.backlight()?, 0);
+    let output = nyx.ctl(&["metrics", "--json"]).await?;
+    let metrics: Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(metrics["display"]["display_on"], false, "{}", metrics);
+
+    // A refused command exits with 1, bad usage with 2
+    let output = nyx.ctl(&["wake", "--display", "nope"]).await?;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let output = nyx.ctl(&["brightness", "300"]).await?;
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(nyx.backlight()?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn brightness_curves_map_onto_the_backlight() -> Result<()> {
+    let nyx = Service::nyx_with_displays(
+        json!({
+            "brightness_curve": {"gamma": 2.0, "min_raw": 5},
+            "displays": {
+                "panel-b": {"brightness_curve": {"points": [[1, 10], [128, 20], [255, 100]]}}
+            }
+        }),
+        &["panel-a", "panel-b"],
+    )
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    // 5 + (128 / 255)² × 95 on the shared gamma curve; a point of the table
+    client.request(json!({"type": "set_brightness", "brightness": 128})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 29);
+    assert_eq!(nyx.backlight_of("panel-b")?, 20);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["displays"][0]["brightness"], 127, "{}", metrics);
+
+    client.request(json!({"type": "set_brightness", "brightness": 255})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 100);
+    assert_eq!(nyx.backlight_of("panel-b")?, 100);
+
+    // The low end stays lit, and 0 is still off
+    client.request(json!({"type": "set_brightness", "brightness": 1})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 5);
+    assert_eq!(nyx.backlight_of("panel-b")?, 10);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["displays"][1]["brightness"], 1, "{}", metrics);
+    assert_eq!(metrics["displays"][1]["display_on"], true);
+    client.request(json!({"type": "set_brightness", "brightness": 0})).await?;
+    assert_eq!(nyx.backlight_of("panel-a")?, 0);
+    assert_eq!(nyx.backlight_of("panel-b")?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn screensaver_takes_over_when_idle_and_restores_on_wake() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({"screensaver": {"idle_time": 2, "mode": "clock"}}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    chrome
+        .wait_for(Duration::from_secs(10), |command| {
+            command["method"] == "Page.navigate"
+                && command["params"]["url"].as_str().is_some_and(|url| url.starts_with("data:text/html"))
+        })
+        .await?;
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["screensaver"], true, "{}", metrics);
+
+    // A wake counts as activity, so the dashboard comes back
+    client.request(json!({"type": "wake"})).await?;
+    chrome
+        .wait_for(Duration::from_secs(5), |command| {
+            command["params"]["url"] == "http://dashboard.local/"
+        })
+        .await?;
+    assert_eq!(chrome.url(), "http://dashboard.local/");
+    Ok(())
+}
+
+#[tokio::test]
+async fn night_mode_filters_the_page() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let config = json!({
+        "scene": {"scenes": [{"id": "evening", "actions": [{"local": {"night_mode": {"enabled": true}}}]}]}
+    });
+    let nyx = Service::nyx_with_chrome(config, &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+    let filters = |chrome: &MockChrome| {
+        chrome
+            .commands()
+            .into_iter()
+            .filter(|c| c["params"]["expression"].as_str().is_some_and(|e| e.contains("feColorMatrix")))
+            .count()
+    };
+
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["night_mode"], json!({"enabled": false, "temperature": 3400}), "{}", metrics);
+    assert_eq!(filters(&chrome), 0);
+
+    let reply = client
+        .request(json!({"type": "set_night_mode", "enabled": true, "temperature": 2700}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let command = chrome
+        .wait_for(Duration::from_secs(5), |c| {
+            c["params"]["expression"].as_str().is_some_and(|e| e.contains("feColorMatrix"))
+        })
+        .await?;
+    assert!(command["params"]["expression"].as_str().unwrap_or_default().contains("'2700'"));
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["night_mode"], json!({"enabled": true, "temperature": 2700}), "{}", metrics);
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(nyx.config_file("config.json"))?)?;
+    assert_eq!(saved["night_mode"]["temperature"], 2700);
+
+    client.request(json!({"type": "set_night_mode", "enabled": false})).await?;
+    chrome
+        .wait_for(Duration::from_secs(5), |c| {
+            c["params"]["expression"].as_str().is_some_and(|e| e.ends_with("?.remove()"))
+        })
+        .await?;
+
+    // Scenes and schedules switch it too, keeping the temperature
+    let report = client.request(json!({"type": "run_scene", "scene": "evening"})).await?;
+    assert_eq!(report["success"], true, "{}", report);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["night_mode"], json!({"enabled": true, "temperature": 2700}), "{}", metrics);
+
+    let reply = client
+        .request(json!({"type": "set_night_mode", "enabled": true, "temperature": 500}))
+        .await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cdp_session_persists_and_follows_tabs() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    for url in ["http://a.local/", "http://b.local/"] {
+        let reply = client.request(json!({"type": "navigate", "url": url})).await?;
+        assert_eq!(reply["success"], true, "{}", reply);
+    }
+    assert_eq!(chrome.connections(), 1);
+    assert_eq!(chrome.url(), "http://b.local/");
+
+    let reply = client.request(json!({"type": "new_tab", "url": "http://other.local/"})).await?;
+    assert_eq!(reply["type"], "tab", "{}", reply);
+    let new_tab = reply["tab"]["id"].as_str().unwrap_or_default().to_string();
+    assert_eq!(reply["tab"]["active"], true);
+
+    // Commands follow the active tab
+    client.request(json!({"type": "reload"})).await?;
+    assert_eq!(chrome.commands().last().map(|c| c["tab"].clone()), Some(json!(new_tab)));
+    let first_tab = chrome.tabs()[0].id.clone();
+    client.request(json!({"type": "activate_tab", "tab": first_tab})).await?;
+    assert_eq!(chrome.activated(), vec![first_tab.clone()]);
+    client.request(json!({"type": "reload"})).await?;
+    assert_eq!(chrome.commands().last().map(|c| c["tab"].clone()), Some(json!(first_tab)));
+
+    let reply = client.request(json!({"type": "close_tab", "tab": first_tab})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let reply = client.request(json!({"type": "list_tabs"})).await?;
+    let tabs = reply["tabs"].as_array().cloned().unwrap_or_default();
+    assert_eq!(tabs.len(), 1, "{}", reply);
+    assert_eq!(tabs[0]["id"], json!(new_tab));
+    assert_eq!(tabs[0]["active"], true);
+
+    let reply = client.request(json!({"type": "close_tab", "tab": "missing"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+
+    // A Chrome restart drops the session; the next command reconnects
+    let connections = chrome.connections();
+    chrome.restart();
+    let reply = client.request(json!({"type": "navigate", "url": "http://c.local/"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    assert_eq!(chrome.connections(), connections + 1);
+    assert_eq!(chrome.tabs()[0].url, "http://c.local/");
+    Ok(())
+}
+
+#[tokio::test]
+async fn browser_watchdog_restarts_unresponsive_chrome() -> Result<()> {
+    let scratch = tempfile::TempDir::new()?;
+    let marker = scratch.path().join("restarted");
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(
+        json!({
+            "browser_watchdog": {
+                "enabled": true,
+                "interval": 1,
+                "failures": 2,
+                "restart_command": ["touch", marker],
+                "restart_grace": 1
+            }
+        }),
+        &chrome,
+    )
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    client
+        .wait_for(Duration::from_secs(5), |m| m["type"] == "metrics" && m["browser"]["state"] == "ok")
+        .await?;
+    assert!(chrome.commands().iter().any(|c| c["method"] == "Runtime.evaluate"));
+    assert!(!marker.exists());
+
+    // The debug port goes away; two failed heartbeats later the browser is restarted
+    drop(chrome);
+    let metrics = client
+        .wait_for(Duration::from_secs(10), |m| {
+            m["type"] == "metrics" && m["browser"]["state"] == "restarting"
+        })
+        .await?;
+    assert_eq!(metrics["browser"]["restarts"], 1, "{}", metrics);
+    assert!(metrics["browser"]["last_restart_ms"].is_u64(), "{}", metrics);
+    assert!(marker.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn screenshots_are_returned_or_saved() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client.request(json!({"type": "screenshot"})).await?;
+    assert_eq!(reply["type"], "screenshot", "{}", reply);
+    assert_eq!(reply["format"], "png");
+    assert_eq!(reply["data"], SCREENSHOT);
+
+    let reply = client
+        .request(json!({"type": "screenshot", "format": "jpeg", "quality": 80, "file": "panel.jpeg"}))
+        .await?;
+    assert_eq!(reply["path"], json!(nyx.config_file("screenshots/panel.jpeg")), "{}", reply);
+    assert!(reply.get("data").is_none());
+    assert_eq!(std::fs::read(nyx.config_file("screenshots/panel.jpeg"))?, b"\x89PNG\r\n\x1a\n");
+    let command = chrome.commands().last().cloned().unwrap_or_default();
+    assert_eq!(command["params"], json!({"format": "jpeg", "quality": 80}));
+
+    let reply = client
+        .request(json!({"type": "screenshot", "file": "../config.json"}))
+        .await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn profiles_switch_the_whole_kiosk() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let config = json!({
+        "profiles": {
+            "day": {"url": "http://dashboard.local/", "brightness": 255},
+            "night": {
+                "url": "http://clock.local/",
+                "brightness": 100,
+                "auto_dim": {"dim_level": 5, "bright_level": 100, "auto_dim_time": 600, "auto_off_time": 0},
+                "night_mode": {"enabled": true, "temperature": 2700}
+            }
+        },
+        "scene": {"scenes": [{"id": "morning", "actions": [{"local": {"profile": {"name": "day"}}}]}]}
+    });
+    let nyx = Service::nyx_with_chrome(config, &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client.request(json!({"type": "set_profile", "name": "night"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    assert_eq!(chrome.url(), "http://clock.local/");
+    // 100 on the 0-255 scale, with max_brightness 100
+    assert_eq!(nyx.backlight()?, 39);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["profile"], "night", "{}", metrics);
+    assert_eq!(metrics["night_mode"], json!({"enabled": true, "temperature": 2700}));
+    assert_eq!(metrics["auto_dim"]["dim_level"], 5);
+
+    // A scene (or schedule) switches back; what "day" leaves out stays
+    let report = client.request(json!({"type": "run_scene", "scene": "morning"})).await?;
+    assert_eq!(report["success"], true, "{}", report);
+    assert_eq!(chrome.url(), "http://dashboard.local/");
+    assert_eq!(nyx.backlight()?, 100);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["profile"], "day", "{}", metrics);
+    assert_eq!(metrics["night_mode"]["enabled"], true);
+
+    let reply = client.request(json!({"type": "set_profile", "name": "party"})).await?;
+    assert_eq!(reply["message"], "Unknown profile 'party'", "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn page_errors_are_kept_and_streamed() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+    client.request(json!({"type": "subscribe_page_errors"})).await?;
+
+    // nyx opens a session on its own to listen for them
+    chrome
+        .wait_for(Duration::from_secs(5), |c| c["method"] == "Log.enable")
+        .await?;
+    chrome.emit(
+        "Runtime.consoleAPICalled",
+        json!({"type": "log", "args": [{"type": "string", "value": "all good"}], "timestamp": 1.7e12}),
+    );
+    chrome.emit(
+        "Runtime.consoleAPICalled",
+        json!({
+            "type": "error",
+            "args": [{"type": "string", "value": "Failed to load"}, {"type": "number", "value": 404}],
+            "timestamp": 1701619234500.0,
+            "stackTrace": {"callFrames": [{"url": "http://dashboard.local/app.js", "lineNumber": 12}]}
+        }),
+    );
+    chrome.emit(
+        "Log.entryAdded",
+        json!({"entry": {"level": "error", "text": "net::ERR_NAME_NOT_RESOLVED", "timestamp": 1701619235000.0}}),
+    );
+
+    let streamed = client
+        .wait_for(Duration::from_secs(5), |m| m["type"] == "page_error")
+        .await?;
+    assert_eq!(streamed["source"], "console", "{}", streamed);
+    assert_eq!(streamed["message"], "Failed to load 404");
+    assert_eq!(streamed["url"], "http://dashboard.local/app.js");
+    assert_eq!(streamed["line"], 12);
+    assert_eq!(streamed["timestamp_ms"], 1701619234500u64);
+
+    client
+        .wait_for(Duration::from_secs(5), |m| m["type"] == "page_error" && m["source"] == "log")
+        .await?;
+    let reply = client.request(json!({"type": "get_page_errors"})).await?;
+    assert_eq!(reply["type"], "page_errors", "{}", reply);
+    assert_eq!(reply["errors"].as_array().map(Vec::len), Some(2), "{}", reply);
+    let reply = client.request(json!({"type": "get_page_errors", "limit": 1})).await?;
+    assert_eq!(reply["errors"][0]["message"], "net::ERR_NAME_NOT_RESOLVED", "{}", reply);
+    assert!(reply["errors"][0].get("url").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn fallback_page_stands_in_for_an_unreachable_dashboard() -> Result<()> {
+    let dashboard_port = free_port()?;
+    let dashboard = format!("http://127.0.0.1:{}/", dashboard_port);
+    let chrome = MockChrome::start(&dashboard).await?;
+    let port = free_port()?;
+    let fallback = format!("http://127.0.0.1:{}/", port);
+    let nyx = Service::nyx_with_chrome(json!({"fallback": {"port": port, "interval": 1}}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    chrome
+        .wait_for(Duration::from_secs(5), |c| c["method"] == "Network.enable")
+        .await?;
+    let tab = chrome.tabs()[0].id.clone();
+    let load = |id: &str, frame: &str, url: &str| {
+        chrome.emit(
+            "Network.requestWillBeSent",
+            json!({"requestId": id, "loaderId": id, "frameId": frame, "type": "Document", "request": {"url": url}}),
+        );
+    };
+    // An iframe that won't load is the page's business
+    load("2.1", "camera-frame", "http://camera.local/");
+    chrome.emit(
+        "Network.loadingFailed",
+        json!({"requestId": "2.1", "type": "Document", "errorText": "net::ERR_NAME_NOT_RESOLVED", "canceled": false}),
+    );
+    load("2.2", &tab, &dashboard);
+    chrome.emit(
+        "Network.loadingFailed",
+        json!({"requestId": "2.2", "type": "Document", "errorText": "net::ERR_CONNECTION_REFUSED", "canceled": false}),
+    );
+
+    chrome
+        .wait_for(Duration::from_secs(5), |c| c["method"] == "Page.navigate" && c["params"]["url"] == fallback)
+        .await?;
+    let navigations = chrome.commands().into_iter().filter(|c| c["method"] == "Page.navigate").count();
+    assert_eq!(navigations, 1);
+    let (code, status) = http_request(port, "GET", "/status", None, None).await?;
+    assert_eq!(code, 200, "{}", status);
+    assert_eq!(status["url"], dashboard.as_str(), "{}", status);
+    assert_eq!(status["reason"], "net::ERR_CONNECTION_REFUSED");
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["fallback"]["url"], dashboard.as_str(), "{}", metrics);
+
+    // Checks fail while nothing answers on the dashboard's port
+    let mut status = Value::Null;
+    for _ in 0..50 {
+        status = http_request(port, "GET", "/status", None, None).await?.1;
+        if status["check_error"].is_string() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(status["checks"].as_u64() >= Some(1), "{}", status);
+    assert!(status["check_error"].is_string(), "{}", status);
+
+    // Once it does, the dashboard is put back
+    let listener = TcpListener::bind(("127.0.0.1", dashboard_port)).await?;
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.read(&mut [0; 1024]).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await;
+        }
+    });
+    chrome
+        .wait_for(Duration::from_secs(5), |c| c["method"] == "Page.navigate" && c["params"]["url"] == dashboard)
+        .await?;
+    let (_, status) = http_request(port, "GET", "/status", None, None).await?;
+    assert!(status.is_null(), "{}", status);
+    Ok(())
+}
+
+#[tokio::test]
+async fn automation_scripts_and_drives_the_page() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({"automation": {"enabled": true, "token": "secret"}}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client
+        .request(json!({"type": "evaluate_js", "expression": "document.title"}))
+        .await?;
+    assert_eq!(reply["message"], "Automation refused: Invalid automation token", "{}", reply);
+
+    // The mock Chrome answers with the expression itself
+    let reply = client
+        .request(json!({"type": "evaluate_js", "token": "secret", "expression": "document.title"}))
+        .await?;
+    assert_eq!(reply["type"], "js_result", "{}", reply);
+    assert_eq!(reply["value"], "document.title");
+    let reply = client
+        .request(json!({"type": "evaluate_js", "token": "secret", "expression": "throw new Error('x')"}))
+        .await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+
+    let reply = client
+        .request(json!({"type": "dispatch_touch", "token": "secret", "x": 100, "y": 50}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    client
+        .request(json!({"type": "dispatch_key", "token": "secret", "key": "Enter"}))
+        .await?;
+
+    let input: Vec<Value> = chrome
+        .commands()
+        .into_iter()
+        .filter(|c| c["method"].as_str().is_some_and(|m| m.starts_with("Input.")))
+        .map(|c| c["params"].clone())
+        .collect();
+    assert_eq!(
+        input,
+        [
+            json!({"type": "touchStart", "touchPoints": [{"x": 100.0, "y": 50.0}]}),
+            json!({"type": "touchEnd", "touchPoints": []}),
+            json!({"type": "keyDown", "key": "Enter", "text": "\r", "windowsVirtualKeyCode": 13}),
+            json!({"type": "keyUp", "key": "Enter", "windowsVirtualKeyCode": 13}),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn automation_is_off_by_default() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let nyx = Service::nyx_with_chrome(json!({}), &chrome).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client.request(json!({"type": "dispatch_key", "key": "a"})).await?;
+    assert_eq!(reply["message"], "Automation refused: Automation is disabled", "{}", reply);
+    // Only the page error listener talks to Chrome
+    assert!(chrome.commands().iter().all(|c| c["method"].as_str().is_some_and(|m| m.ends_with(".enable"))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn auto_dim_changes_are_pushed() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 2, "auto_off_time": 0}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    // No command is sent; the dim shows up on its own, announced as a transition
+    let transition = client
+        .wait_for(Duration::from_secs(10), |m| m["type"] == "auto_dim_transition")
+        .await?;
+    assert_eq!(transition["display"], "fake");
+    assert_eq!(transition["from"], "active");
+    assert_eq!(transition["to"], "dimmed");
+    assert_eq!(transition["event"], "dim_timeout");
+
+    // 10 reads back as 7 through the 0-100 backlight
+    let metrics = client
+        .wait_for(Duration::from_secs(10), |m| m["type"] == "metrics" && m["display"]["brightness"] == 7)
+        .await?;
+    assert!(metrics.get("id").is_none(), "{}", metrics);
+    assert_eq!(metrics["auto_dim"]["state"], "dimmed");
+    assert_eq!(nyx.backlight()?, 3);
+
+    // Waking leaves the dimmed state
+    client.request(json!({"type": "wake"})).await?;
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["auto_dim"]["state"], "active");
+    assert_eq!(nyx.backlight()?, 78);
+    Ok(())
+}
+
+/// Write an executable shell script standing in for a power tool; it logs its arguments to
+/// `args` next to it
+fn power_tool(dir: &std::path::Path, body: &str) -> Result<std::path::PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join("tool");
+    std::fs::write(&path, format!("#!/bin/sh\necho \"$@\" >> {}\n{}", dir.join("args").display(), body))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+#[tokio::test]
+async fn display_power_follows_commands_and_brightness() -> Result<()> {
+    let scratch = tempfile::TempDir::new()?;
+    let state = scratch.path().join("state");
+    let tool = power_tool(
+        scratch.path(),
+        &format!(
+            "case \"$2\" in 0|1) echo $2 > {0};; *) echo \"display_power=$(cat {0} 2>/dev/null || echo 1)\";; esac\n",
+            state.display()
+        ),
+    )?;
+    let nyx = Service::nyx(json!({
+        "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 0, "auto_off_time": 0},
+        "power": {"backend": "vcgencmd", "program": tool}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+    let args = || std::fs::read_to_string(scratch.path().join("args")).unwrap_or_default();
+
+    // Power off leaves the backlight alone but the display reports off
+    let reply = client.request(json!({"type": "set_power", "state": "off"})).await?;
+    assert_eq!(reply["command"], "set_power", "{}", reply);
+    assert!(args().contains("display_power 0"), "{}", args());
+    assert_eq!(nyx.backlight()?, 78);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["display"]["display_on"], false, "{}", metrics);
+
+    // Wake powers it back on
+    client.request(json!({"type": "wake"})).await?;
+    assert!(args().contains("display_power 1"), "{}", args());
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["display"]["display_on"], true, "{}", metrics);
+
+    // Sleep turns the backlight down and, following it, the power off
+    client.request(json!({"type": "sleep"})).await?;
+    assert_eq!(std::fs::read_to_string(&state)?.trim(), "0");
+    Ok(())
+}
+
+#[tokio::test]
+async fn monitor_without_backlight_is_driven_by_power() -> Result<()> {
+    let scratch = tempfile::TempDir::new()?;
+    let state = scratch.path().join("enabled");
+    let tool = power_tool(
+        scratch.path(),
+        &format!(
+            "case \"$3\" in --on) echo true > {0};; --off) echo false > {0};; *) echo \"[{{\\\"name\\\": \\\"HDMI-A-1\\\", \\\"enabled\\\": $(cat {0} 2>/dev/null || echo true)}}]\";; esac\n",
+            state.display()
+        ),
+    )?;
+    let nyx = Service::nyx_with_displays(
+        json!({"power": {"backend": "wlr_randr", "output": "HDMI-A-1", "program": tool}}),
+        &[],
+    )
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["display"]["id"], "HDMI-A-1", "{}", metrics);
+    assert_eq!(metrics["display"]["display_on"], true, "{}", metrics);
+
+    client.request(json!({"type": "set_brightness", "brightness": 0})).await?;
+    assert_eq!(std::fs::read_to_string(&state)?.trim(), "false");
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["display"]["display_on"], false, "{}", metrics);
+    assert_eq!(metrics["display"]["brightness"], 0, "{}", metrics);
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_power_needs_a_backend() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "set_power", "state": "off"})).await?;
+    assert_eq!(reply["type"], "error");
+    assert!(reply["message"].as_str().unwrap_or_default().contains("power.backend"), "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn presence_sensor_wakes_the_display() -> Result<()> {
+    let scratch = tempfile::TempDir::new()?;
+    let gpio = scratch.path().join("value");
+    std::fs::write(&gpio, "0\n")?;
+    let nyx = Service::nyx(json!({
+        "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 0, "auto_off_time": 0},
+        "presence": {"input": {"source": "gpio", "path": gpio}, "hold_time": 0}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    client.request(json!({"type": "sleep"})).await?;
+    assert_eq!(nyx.backlight()?, 0);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["presence"], json!({"present": false}), "{}", metrics);
+
+    // Someone walks up
+    std::fs::write(&gpio, "1\n")?;
+    client
+        .wait_for(Duration::from_secs(5), |m| m["type"] == "metrics" && m["presence"]["present"] == true)
+        .await?;
+    for _ in 0..50 {
+        if nyx.backlight()? == 78 {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(nyx.backlight()?, 78);
+
+    // And leaves; an unreadable sensor shows in health
+    std::fs::write(&gpio, "2\n")?;
+    client
+        .wait_for(Duration::from_secs(5), |m| m["type"] == "metrics" && m["presence"]["present"] == false)
+        .await?;
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(component(&health, "presence")["status"], "degraded", "{}", health);
+    Ok(())
+}
+
+#[tokio::test]
+async fn gestures_are_opt_in() -> Result<()> {
+    // No touch device here, so only the subscription itself can be exercised
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "hello", "features": ["gestures"]})).await?;
+    assert_eq!(reply["accepted"], json!(["gestures"]));
+
+    let reply = client.request(json!({"type": "subscribe_gestures"})).await?;
+    assert_eq!(reply["success"], true);
+    assert_eq!(reply["command"], "subscribe_gestures");
+
+    let reply = client.request(json!({"type": "unsubscribe_gestures"})).await?;
+    assert_eq!(reply["command"], "unsubscribe_gestures");
+    Ok(())
+}
+
+#[tokio::test]
+async fn touch_activity_is_opt_in() -> Result<()> {
+    // As with gestures, nothing here touches the panel
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "hello", "features": ["touch_activity"]})).await?;
+    assert_eq!(reply["accepted"], json!(["touch_activity"]));
+
+    let reply = client.request(json!({"type": "subscribe_touch"})).await?;
+    assert_eq!(reply["command"], "subscribe_touch", "{}", reply);
+
+    // A wake resets the idle timer but isn't a touch, so nothing is streamed
+    client.request(json!({"type": "wake"})).await?;
+    let streamed = client
+        .wait_for(Duration::from_secs(2), |m| m["type"] == "touch_activity")
+        .await;
+    assert!(streamed.is_err(), "{:?}", streamed);
+
+    let reply = client.request(json!({"type": "unsubscribe_touch"})).await?;
+    assert_eq!(reply["command"], "unsubscribe_touch");
+    Ok(())
+}
+
+#[tokio::test]
+async fn touch_lock_needs_the_pin() -> Result<()> {
+    let (nyx, mut client) = start().await?;
+
+    let reply = client.request(json!({"type": "lock_touch", "pin": ""})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+
+    let reply = client.request(json!({"type": "lock_touch", "pin": "2468"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["touch_locked"], true, "{}", metrics);
+    // The display stays as it was
+    assert_eq!(metrics["display"]["display_on"], true);
+    assert_eq!(nyx.backlight()?, 78);
+
+    let reply = client.request(json!({"type": "lock_touch", "pin": "1357"})).await?;
+    assert_eq!(reply["message"], "Touch input is already locked", "{}", reply);
+    let reply = client.request(json!({"type": "unlock_touch", "pin": "1357"})).await?;
+    assert_eq!(reply["message"], "Wrong PIN", "{}", reply);
+
+    let reply = client.request(json!({"type": "unlock_touch", "pin": "2468"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["touch_locked"], false, "{}", metrics);
+    Ok(())
+}
+
+#[tokio::test]
+async fn touch_unlock_attempts_are_limited() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+    let reply = client.request(json!({"type": "lock_touch", "pin": "2468"})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    for _ in 0..5 {
+        let reply = client.request(json!({"type": "unlock_touch", "pin": "0000"})).await?;
+        assert_eq!(reply["message"], "Wrong PIN", "{}", reply);
+    }
+
+    // Out of attempts, the right PIN isn't even checked
+    let reply = client.request(json!({"type": "unlock_touch", "pin": "2468"})).await?;
+    let message = reply["message"].as_str().unwrap_or("");
+    assert!(message.starts_with("Too many unlock attempts"), "{}", reply);
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["touch_locked"], true, "{}", metrics);
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_touch_device_is_degraded() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+
+    // The touch monitor reports its first open failure shortly after startup
+    let mut health = Value::Null;
+    for _ in 0..50 {
+        health = client.request(json!({"type": "get_health"})).await?;
+        if component(&health, "touch")["status"] == "degraded" {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(component(&health, "touch")["status"], "degraded");
+    let reason = component(&health, "touch")["reason"].as_str().unwrap_or_default().to_string();
+    assert!(reason.contains("no-touch-device"), "{}", health);
+    assert_eq!(component(&health, "display")["status"], "ok");
+
+    // Nothing is being read, and the device is still looked for
+    let metrics = client.request(json!({"type": "get_metrics"})).await?;
+    assert_eq!(metrics["touch_devices"], json!([]), "{}", metrics);
+    sleep(Duration::from_secs(3)).await;
+    let health = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(component(&health, "touch")["status"], "degraded");
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_auto_dim_config_is_rejected() -> Result<()> {
+    let (_nyx, mut client) = start().await?;
+
+    let reply = client
+        .request(json!({
+            "type": "set_auto_dim_config",
+            "dim_level": 10,
+            "bright_level": 0,
+            "auto_dim_time": 30,
+            "auto_off_time": 60
+        }))
+        .await?;
+    assert_eq!(reply["type"], "error");
+
+    let reply = client
+        .request(json!({"type": "get_auto_dim_config"}))
+        .await?;
+    assert_eq!(reply["config"]["bright_level"], 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_follow_configured_locale() -> Result<()> {
+    let nyx = Service::nyx(json!({"i18n": {"locale": "de"}})).await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client
+        .request(json!({
+            "type": "set_auto_dim_config",
+            "dim_level": 10,
+            "bright_level": 0,
+            "auto_dim_time": 30,
+            "auto_off_time": 60
+        }))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    let message = reply["message"].as_str().unwrap_or_default();
+    assert!(message.starts_with("bright_level muss"), "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn old_config_is_migrated_and_backed_up() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "auto_dim": {
+            "enabled": true,
+            "dim_level": 10,
+            "bright_level": 200,
+            "timeout_seconds": 45
+        }
+    }))
+    .await?;
+
+    let backup: Value = serde_json::from_str(&std::fs::read_to_string(
+        nyx.config_file("config.json.v0.bak"),
+    )?)?;
+    assert_eq!(backup["auto_dim"]["timeout_seconds"], 45);
+
+    let config: Value =
+        serde_json::from_str(&std::fs::read_to_string(nyx.config_file("config.json"))?)?;
+    assert_eq!(config["config_version"], 1);
+    assert_eq!(
+        config["auto_dim"],
+        json!({"dim_level": 10, "bright_level": 200, "auto_dim_time": 45, "auto_off_time": 0})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_over_the_burst_are_rate_limited() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "rate_limit": {"enabled": true, "rate": 0.5, "burst": 2}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    for _ in 0..2 {
+        let reply = client.request(json!({"type": "get_info"})).await?;
+        assert_eq!(reply["type"], "hello");
+    }
+
+    let reply = client.request(json!({"type": "get_info"})).await?;
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["id"], "e2e-3");
+    let retry_after_ms = reply["retry_after_ms"].as_u64().unwrap_or_default();
+    assert!((1..=2000).contains(&retry_after_ms), "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn config_export_and_import() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 0, "auto_off_time": 0},
+        "backup": {"enabled": true, "token": "secret"}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client.request(json!({"type": "export_config"})).await?;
+    assert_eq!(reply["type"], "error");
+
+    let reply = client
+        .request(json!({"type": "export_config", "token": "secret"}))
+        .await?;
+    assert_eq!(reply["type"], "config_archive");
+    let mut archive = reply["archive"].clone();
+    assert_eq!(archive["entries"]["nyx"]["service"], "nyx");
+    assert_eq!(
+        archive["entries"]["nyx"]["config"]["auto_dim"]["bright_level"],
+        200
+    );
+
+    archive["entries"]["nyx"]["config"]["auto_dim"]["bright_level"] = json!(150);
+    let reply = client
+        .request(json!({"type": "import_config", "token": "secret", "archive": archive}))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    let reply = client
+        .request(json!({"type": "get_auto_dim_config"}))
+        .await?;
+    assert_eq!(reply["config"]["bright_level"], 150);
+    assert!(nyx.config_file("config.json.bak").exists());
+
+    // A dosa entry can't be restored on nyx
+    archive["entries"]["nyx"]["service"] = json!("dosa");
+    let reply = client
+        .request(json!({"type": "import_config", "token": "secret", "archive": archive}))
+        .await?;
+    assert_eq!(reply["type"], "error");
+    Ok(())
+}
+
+#[tokio::test]
+async fn audit_log_is_hash_chained() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "auto_dim": {"dim_level": 10, "bright_level": 200, "auto_dim_time": 0, "auto_off_time": 0},
+        "audit": {"query": {"enabled": true, "token": "secret"}}
+    }))
+    .await?;
+    let mut client = WsClient::connect(nyx.port()).await?;
+
+    let reply = client.request(json!({"type": "get_audit_log"})).await?;
+    assert_eq!(reply["type"], "error");
+
+    let reply = client
+        .request(json!({
+            "type": "set_auto_dim_config",
+            "dim_level": 10,
+            "bright_level": 150,
+            "auto_dim_time": 0,
+            "auto_off_time": 0
+        }))
+        .await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+
+    // Config changes are recorded by a background task
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        let reply = client
+            .request(json!({"type": "get_audit_log", "token": "secret"}))
+            .await?;
+        assert_eq!(reply["type"], "audit_log", "{}", reply);
+        assert_eq!(reply["chain"]["valid"], true, "{}", reply);
+        records = reply["records"].as_array().cloned().unwrap_or_default();
+        if records.iter().any(|r| r["action"] == "config_changed") {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    let actions: Vec<&str> = records
+        .iter()
+        .filter_map(|r| r["action"].as_str())
+        .collect();
+    assert_eq!(actions[..3], ["started", "connect", "get_audit_log"]);
+    assert_eq!(records[2]["success"], false);
+    let set = records
+        .iter()
+        .find(|r| r["action"] == "set_auto_dim_config")
+        .expect("set_auto_dim_config record");
+    assert_eq!(set["detail"]["bright_level"], 150);
+    assert!(set["client"]
+        .as_str()
+        .is_some_and(|c| c.starts_with("127.0.0.1:")));
+    let changed = records
+        .iter()
+        .find(|r| r["action"] == "config_changed")
+        .expect("config_changed record");
+    assert_eq!(changed["detail"]["sections"], json!(["auto_dim"]));
+    for pair in records.windows(2) {
+        assert_eq!(pair[1]["prev_hash"], pair[0]["hash"]);
+    }
+
+    // Editing a record breaks the chain
+    let path = nyx.config_file("audit.jsonl");
+    let log = std::fs::read_to_string(&path)?;
+    std::fs::write(
+        &path,
+        log.replacen("\"bright_level\":150", "\"bright_level\":100", 1),
+    )?;
+    let reply = client
+        .request(json!({"type": "get_audit_log", "token": "secret"}))
+        .await?;
+    assert_eq!(reply["chain"]["valid"], false, "{}", reply);
+    let error = reply["chain"]["error"].as_str().unwrap_or_default();
+    let modified = format!("record {} has been modified", set["seq"]);
+    assert!(error.contains(&modified), "{}", reply);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_serves_wss_only() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "websocket": {
+            "tls": {
+                "cert": fixture("tls/server.pem"),
+                "key": fixture("tls/server.key")
+            }
+        }
+    }))
+    .await?;
+
+    let mut client = WsClient::connect_tls(nyx.port(), &fixture("tls/ca.pem")).await?;
+    let reply = client.request(json!({"type": "get_health"})).await?;
+    assert_eq!(reply["type"], "health", "{}", reply);
+
+    assert!(WsClient::connect(nyx.port()).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn tokens_grant_read_or_control() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "auth": {
+            "enabled": true,
+            "tokens": [
+                {"name": "dashboard", "token": "watch-only", "role": "read"},
+                {"name": "hub", "token": "let me in", "role": "control"}
+            ]
+        }
+    }))
+    .await?;
+
+    assert!(WsClient::connect(nyx.port()).await.is_err(), "connected without a token");
+    assert!(WsClient::connect_with_token(nyx.port(), "wrong").await.is_err());
+
+    // Read role: queries work, commands are refused
+    let mut dashboard = WsClient::connect_with_token(nyx.port(), "watch-only").await?;
+    let reply = dashboard.request(json!({"type": "get_health"})).await?;
+    assert_eq!(reply["type"], "health", "{}", reply);
+    let reply = dashboard.request(json!({"type": "navigate", "url": "https://example.com"})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert!(reply["message"].as_str().unwrap_or("").contains("control"), "{}", reply);
+    let before = nyx.backlight()?;
+    let reply = dashboard.request(json!({"type": "set_brightness", "brightness": 100})).await?;
+    assert_eq!(reply["type"], "error", "{}", reply);
+    assert_eq!(nyx.backlight()?, before);
+
+    // Control role, token in the query string
+    let url = format!("ws://127.0.0.1:{}/?token=let%20me%20in", nyx.port());
+    let mut hub = WsClient::connect_url(&url).await?;
+    let reply = hub.request(json!({"type": "set_brightness", "brightness": 100})).await?;
+    assert_eq!(reply["success"], true, "{}", reply);
+    assert_eq!(nyx.backlight()?, 39);
+    Ok(())
+}
+
+#[tokio::test]
+async fn http_api_drives_the_display() -> Result<()> {
+    let chrome = MockChrome::start("http://dashboard.local/").await?;
+    let port = free_port()?;
+    let config = json!({
+        "http": {"host": "127.0.0.1", "port": port},
+        "auth": {
+            "enabled": true,
+            "tokens": [
+                {"name": "dashboard", "token": "watch-only", "role": "read"},
+                {"name": "script", "token": "curl", "role": "control"}
+            ]
+        }
+    });
+    let nyx = Service::nyx_with_chrome(config, &chrome).await?;
+
+    let (code, reply) = http_request(port, "GET", "/metrics", None, None).await?;
+    assert_eq!(code, 401, "{}", reply);
+    let (code, reply) = http_request(port, "GET", "/metrics", Some("watch-only"), None).await?;
+    assert_eq!(code, 200, "{}", reply);
+    assert_eq!(reply["type"], "metrics");
+    let (code, reply) = http_request(port, "POST", "/sleep", Some("watch-only"), None).await?;
+    assert_eq!(code, 403, "{}", reply);
+
+    let body = json!({"brightness": 255});
+    let (code, reply) = http_request(port, "POST", "/brightness", Some("curl"), Some(&body)).await?;
+    assert_eq!(code, 200, "{}", reply);
+    assert_eq!(reply["command"], "set_brightness");
+    assert_eq!(nyx.backlight()?, 100);
+    let (code, reply) = http_request(port, "POST", "/brightness", Some("curl"), None).await?;
+    assert_eq!(code, 400, "{}", reply);
+    let (code, reply) = http_request(port, "POST", "/sleep?display=nope", Some("curl"), None).await?;
+    assert_eq!(code, 404, "{}", reply);
+
+    let (code, _) = http_request(port, "POST", "/sleep", Some("curl"), None).await?;
+    assert_eq!(code, 200);
+    assert_eq!(nyx.backlight()?, 0);
+    let (code, _) = http_request(port, "POST", "/wake", Some("curl"), None).await?;
+    assert_eq!(code, 200);
+    assert!(nyx.backlight()? > 0);
+
+    let body = json!({"url": "http://weather.local/"});
+    let (code, reply) = http_request(port, "POST", "/navigate", Some("curl"), Some(&body)).await?;
+    assert_eq!(code, 200, "{}", reply);
+    assert_eq!(reply["url"], "http://weather.local/");
+    assert_eq!(chrome.url(), "http://weather.local/");
+    Ok(())
+}
+
+#[tokio::test]
+async fn clients_that_stop_answering_pings_are_dropped() -> Result<()> {
+    let nyx = Service::nyx(json!({
+        "websocket": { "keep_alive": { "ping_interval_secs": 1, "timeout_secs": 1 } }
+    }))
+    .await?;
+
+    // A wall panel that crashed: never reads, so never answers a ping
+    let mut silent = WsClient::connect(nyx.port()).await?;
+    let mut active = WsClient::connect(nyx.port()).await?;
+    for _ in 0..8 {
+        sleep(Duration::from_millis(500)).await;
+        let reply = active.request(json!({"type": "get_health"})).await?;
+        assert_eq!(reply["type"], "health", "{}", reply);
+    }
+
+    let err = silent
+        .wait_for(Duration::from_secs(2), |_| false)
+        .await
+        .expect_err("silent client is still connected");
+    assert!(!err.to_string().contains("Timed out"), "{:#}", err);
+    Ok(())
+}
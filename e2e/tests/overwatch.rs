@@ -0,0 +1,552 @@
+//! overwatch over gRPC with the stub TTS backend (`--features overwatch`).
+
+#![cfg(feature = "overwatch")]
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use shq_e2e::voice::voice_service_client::VoiceServiceClient;
+use shq_e2e::voice::schedule::Action as ScheduleAction;
+use shq_e2e::voice::{
+    CancelScheduleRequest, CreateScheduleRequest, ExportConfigRequest, GetHealthRequest, GetInfoRequest, GetQueueRequest,
+    ImportConfigRequest, ListActiveRequest, ListSchedulesRequest, PurgeCacheRequest, RunSceneRequest, Schedule,
+    SetAlarmRequest, SnoozeAlarmRequest, StopAllRequest, StreamLogsRequest, VerbaliseRequest,
+};
+use shq_e2e::{MockGrbl, Service, WsClient};
+use tokio::time::{sleep, timeout, Duration};
+use tonic::transport::Channel;
+use tonic::Code;
+
+async fn connect(overwatch: &Service) -> Result<VoiceServiceClient<Channel>> {
+    let url = format!("http://127.0.0.1:{}", overwatch.port());
+    Ok(VoiceServiceClient::connect(url).await?)
+}
+
+#[tokio::test]
+async fn info_and_health() -> Result<()> {
+    let overwatch = Service::overwatch("").await?;
+    let mut client = connect(&overwatch).await?;
+
+    let info = client
+        .get_info(GetInfoRequest {
+            client: Some("e2e".to_string()),
+            features: vec!["scenes".to_string(), "teleport".to_string()],
+        })
+        .await?
+        .into_inner();
+    assert_eq!(info.service, "overwatch");
+    assert!(info.capabilities.iter().any(|c| c == "scenes"));
+    assert_eq!(info.accepted, ["scenes"]);
+    assert_eq!(info.unsupported, ["teleport"]);
+
+    let health = client.get_health(GetHealthRequest {}).await?.into_inner();
+    let tts = health
+        .components
+        .iter()
+        .find(|c| c.name == "tts")
+        .expect("tts component");
+    assert_eq!(tts.status, "ok");
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_alarm_scene_and_zone_are_not_found() -> Result<()> {
+    let overwatch = Service::overwatch("").await?;
+    let mut client = connect(&overwatch).await?;
+
+    let err = client
+        .set_alarm(SetAlarmRequest {
+            alarm_id: "missing".to_string(),
+            enabled: true,
+            volume: None,
+            zone: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    let err = client
+        .run_scene(RunSceneRequest {
+            scene: "missing".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    // No outputs configured, so the only zone is all of them
+    let err = client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            zone: Some("kitchen".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    assert!(err.message().contains("kitchen"), "{}", err.message());
+    Ok(())
+}
+
+#[tokio::test]
+async fn verbalise_uses_stub_backend() -> Result<()> {
+    let overwatch = Service::overwatch("").await?;
+    let mut client = connect(&overwatch).await?;
+
+    // Voices are still validated
+    let err = client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            notification_tone_id: None,
+            voice_id: Some("nobody".to_string()),
+            volume: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        err.message().contains("Unsupported voice"),
+        "{}",
+        err.message()
+    );
+
+    // Synthesis succeeds; playback only fails if the machine has no audio output
+    let result = client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            notification_tone_id: None,
+            voice_id: None,
+            volume: Some(0.0),
+            ..Default::default()
+        })
+        .await;
+    if let Err(err) = result {
+        assert!(
+            err.message().contains("Audio playback failed"),
+            "{}",
+            err.message()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn verbalise_priorities_share_one_queue() -> Result<()> {
+    let overwatch = Service::overwatch("playback:\n  preempt: duck\n").await?;
+    let mut client = connect(&overwatch).await?;
+
+    let err = client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            priority: Some("urgent".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert!(err.message().contains("urgent"), "{}", err.message());
+
+    // Without an audio device nothing plays and the queue can't be read
+    for priority in ["ambient", "alarm"] {
+        let result = client
+            .verbalise(VerbaliseRequest {
+                text: format!("{} message", priority),
+                priority: Some(priority.to_string()),
+                volume: Some(0.0),
+                ..Default::default()
+            })
+            .await;
+        if let Err(err) = result {
+            assert!(err.message().contains("Audio playback failed"), "{}", err.message());
+            return Ok(());
+        }
+    }
+
+    // The stub's 100ms of silence may have played out already; if not, the alarm is heard first
+    let queue = client.get_queue(GetQueueRequest {}).await?.into_inner();
+    if let Some(first) = queue.playing.first() {
+        assert_eq!(first.priority, "alarm");
+        assert!(!first.ducked);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn long_texts_are_synthesised_in_chunks() -> Result<()> {
+    let overwatch = Service::overwatch("synthesis:\n  max_chunk_chars: 20\n  concurrency: 2\n").await?;
+    let mut client = connect(&overwatch).await?;
+
+    let text = "This sentence is long. So is this one. And a third to finish.";
+    let result = client
+        .verbalise(VerbaliseRequest {
+            text: text.to_string(),
+            volume: Some(0.0),
+            ..Default::default()
+        })
+        .await;
+    if let Err(err) = result {
+        assert!(err.message().contains("Audio playback failed"), "{}", err.message());
+        return Ok(());
+    }
+
+    // The chunks play as one message
+    let queue = client.get_queue(GetQueueRequest {}).await?.into_inner();
+    assert!(queue.queued.is_empty());
+    if let Some(playing) = queue.playing.first() {
+        assert_eq!(playing.text, text);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn tts_cache_is_kept_under_its_limit_and_purged() -> Result<()> {
+    // The stub backend never writes to the cache, so fill it by hand
+    let cache = tempfile::TempDir::new()?;
+    let old = cache.path().join("old.mp3");
+    std::fs::write(&old, vec![0u8; 700 * 1024])?;
+    std::fs::File::options()
+        .write(true)
+        .open(&old)?
+        .set_modified(std::time::SystemTime::now() - Duration::from_secs(3600))?;
+    std::fs::write(cache.path().join("new.mp3"), vec![0u8; 700 * 1024])?;
+    std::fs::write(cache.path().join("notes.txt"), "not audio")?;
+
+    let overwatch = Service::overwatch(&format!(
+        "tts_cache:\n  dir: {:?}\n  max_size_mb: 1\n  prewarm:\n    - text: \"Good morning\"\n",
+        cache.path()
+    ))
+    .await?;
+    let mut client = connect(&overwatch).await?;
+
+    // Over the limit at startup, so the least recently used file went
+    assert!(!old.exists());
+    assert!(cache.path().join("new.mp3").exists());
+
+    let purged = client
+        .purge_cache(PurgeCacheRequest {
+            include_prewarmed: false,
+        })
+        .await?
+        .into_inner();
+    assert_eq!(purged.files, 1);
+    assert_eq!(purged.bytes, 700 * 1024);
+    assert!(!cache.path().join("new.mp3").exists());
+    assert!(cache.path().join("notes.txt").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_all_silences_speech() -> Result<()> {
+    let overwatch = Service::overwatch("").await?;
+    let mut client = connect(&overwatch).await?;
+
+    // Without an audio device there's nothing to list or stop
+    match client.list_active(ListActiveRequest {}).await {
+        Ok(active) => assert!(active.into_inner().alarms.is_empty()),
+        Err(err) => {
+            assert!(err.message().contains("Audio playback failed"), "{}", err.message());
+            return Ok(());
+        }
+    }
+
+    client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            volume: Some(0.0),
+            ..Default::default()
+        })
+        .await?;
+    let stopped = client.stop_all(StopAllRequest {}).await?.into_inner();
+    assert!(stopped.alarms.is_empty());
+    assert!(stopped.messages <= 1, "{}", stopped.messages);
+
+    let queue = client.get_queue(GetQueueRequest {}).await?.into_inner();
+    assert!(queue.playing.is_empty());
+    assert!(queue.queued.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn schedules_are_created_listed_and_cancelled() -> Result<()> {
+    let overwatch = Service::overwatch("alarms:\n  wake: /nonexistent/wake.wav\n").await?;
+    let mut client = connect(&overwatch).await?;
+    let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_millis() as u64;
+
+    let wake = Schedule {
+        id: "weekday-wake".to_string(),
+        cron: Some("30 6 * * Mon-Fri".to_string()),
+        action: Some(ScheduleAction::Alarm(SetAlarmRequest {
+            alarm_id: "wake".to_string(),
+            enabled: true,
+            volume: Some(0.0),
+            zone: None,
+        })),
+        ..Default::default()
+    };
+    let created = client
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(wake.clone()),
+        })
+        .await?
+        .into_inner();
+    assert!(created.next_run_ms.unwrap_or(0) > now_ms);
+
+    // IDs are unique and alarms must exist
+    let err = client
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(wake.clone()),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    let err = client
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(Schedule {
+                id: "other".to_string(),
+                action: Some(ScheduleAction::Alarm(SetAlarmRequest {
+                    alarm_id: "missing".to_string(),
+                    enabled: true,
+                    volume: None,
+                    zone: None,
+                })),
+                ..wake.clone()
+            }),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    // A one-shot runs without a restart and is then dropped from the config
+    let reminder = Schedule {
+        id: "reminder".to_string(),
+        at_ms: Some(now_ms + 2000),
+        action: Some(ScheduleAction::Announce(VerbaliseRequest {
+            text: "Take the bins out".to_string(),
+            volume: Some(0.0),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    client
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(reminder.clone()),
+        })
+        .await?;
+    let err = client
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(Schedule {
+                id: "too-late".to_string(),
+                at_ms: Some(now_ms - 1000),
+                ..reminder
+            }),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let listed = client.list_schedules(ListSchedulesRequest {}).await?.into_inner();
+    let ids: Vec<_> = listed.schedules.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["weekday-wake", "reminder"]);
+    assert_eq!(listed.schedules[1].next_run_ms, Some(now_ms + 2000));
+    assert!(listed.schedules[0].enabled);
+
+    timeout(Duration::from_secs(10), async {
+        loop {
+            let listed = client.list_schedules(ListSchedulesRequest {}).await?.into_inner();
+            if listed.schedules.len() == 1 {
+                return Ok::<_, anyhow::Error>(());
+            }
+            sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await??;
+    let config = std::fs::read_to_string(overwatch.config_file("config.yaml"))?;
+    assert!(!config.contains("reminder"), "{}", config);
+
+    // Only sounding alarms can be snoozed
+    let err = client
+        .snooze_alarm(SnoozeAlarmRequest {
+            alarm_id: "wake".to_string(),
+            minutes: Some(5),
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err.code(), Code::FailedPrecondition | Code::Internal),
+        "{}",
+        err.message()
+    );
+
+    let cancel = CancelScheduleRequest {
+        id: "weekday-wake".to_string(),
+    };
+    client.cancel_schedule(cancel.clone()).await?;
+    let err = client.cancel_schedule(cancel).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let listed = client.list_schedules(ListSchedulesRequest {}).await?.into_inner();
+    assert!(listed.schedules.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn announcement_templates_follow_locale() -> Result<()> {
+    let overwatch = Service::overwatch("i18n:\n  locale: de\n").await?;
+    let mut client = connect(&overwatch).await?;
+
+    let err = client
+        .verbalise(VerbaliseRequest {
+            template: Some("missing".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    assert_eq!(err.message(), "Ansagevorlage 'missing' nicht gefunden");
+
+    let err = client
+        .verbalise(VerbaliseRequest {
+            text: "Hello".to_string(),
+            template: Some("good_night".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    let result = client
+        .verbalise(VerbaliseRequest {
+            template: Some("reminder".to_string()),
+            args: [("what".to_string(), "Müll".to_string())].into(),
+            volume: Some(0.0),
+            ..Default::default()
+        })
+        .await;
+    if let Err(err) = result {
+        assert!(
+            err.message().contains("Audiowiedergabe fehlgeschlagen"),
+            "{}",
+            err.message()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn scene_drives_dosa_peer() -> Result<()> {
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(&grbl, "").await?;
+    let overwatch = Service::overwatch(&format!(
+        "scene:\n  peers:\n    door:\n      url: \"ws://127.0.0.1:{}\"\n  scenes:\n    \
+         - id: reset-door\n      actions:\n        \
+         - remote: {{ peer: door, message: {{ type: home }} }}\n",
+        dosa.port()
+    ))
+    .await?;
+    let mut client = connect(&overwatch).await?;
+
+    let report = client
+        .run_scene(RunSceneRequest {
+            scene: "reset-door".to_string(),
+        })
+        .await?
+        .into_inner();
+    assert!(report.success, "{:?}", report.results);
+    assert_eq!(report.results[0].target, "door");
+    assert_eq!(report.results[0].action, "home");
+
+    // dosa acknowledges home straight away and homes in the background
+    for _ in 0..200 {
+        if grbl.commands().iter().any(|c| c == "$HX") {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    anyhow::bail!("dosa never homed: {:?}", grbl.commands())
+}
+
+#[tokio::test]
+async fn dosa_announces_door_events() -> Result<()> {
+    let overwatch = Service::overwatch("logging:\n  stream:\n    enabled: true\n").await?;
+    let grbl = MockGrbl::start().await?;
+    let dosa = Service::dosa(
+        &grbl,
+        &format!(
+            "overwatch:\n  url: \"http://127.0.0.1:{}\"\n  volume: 0.0\n  announcements:\n    \
+             opening: {{ text: \"{{door}} door opening\" }}\n    \
+             opened: {{ template: door_opened, args: {{ door: garage door }} }}\n",
+            overwatch.port()
+        ),
+    )
+    .await?;
+    let mut door = WsClient::connect(dosa.port()).await?;
+
+    door.request(json!({"type": "home"})).await?;
+    door.wait_for(Duration::from_secs(15), |m| {
+        m["type"] == "status" && m["door"]["state"] == "closed"
+    })
+    .await?;
+    door.request(json!({"type": "open"})).await?;
+    door.wait_for(Duration::from_secs(15), |m| {
+        m["type"] == "status" && m["door"]["state"] == "open"
+    })
+    .await?;
+
+    // overwatch logs each announcement before speaking it
+    let mut client = connect(&overwatch).await?;
+    let mut logs = client
+        .stream_logs(StreamLogsRequest {
+            lines: Some(1000),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    let mut wanted = vec!["'main door opening'", "'The garage door is open.'"];
+    while !wanted.is_empty() {
+        let entry = timeout(Duration::from_secs(15), logs.message())
+            .await??
+            .ok_or_else(|| anyhow::anyhow!("log stream ended, still waiting for {:?}", wanted))?;
+        wanted.retain(|text| !entry.message.contains(text));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn config_archive_covers_peers() -> Result<()> {
+    let nyx = Service::nyx(json!({"backup": {"enabled": true, "token": "nyx-secret"}})).await?;
+    let overwatch = Service::overwatch(&format!(
+        "backup:\n  enabled: true\nscene:\n  peers:\n    hall:\n      url: \"ws://127.0.0.1:{}\"\n      backup_token: nyx-secret\n    gone:\n      url: \"ws://127.0.0.1:1\"\n",
+        nyx.port()
+    ))
+    .await?;
+    let mut client = connect(&overwatch).await?;
+
+    let export = client
+        .export_config(ExportConfigRequest {
+            token: None,
+            include_peers: true,
+        })
+        .await?
+        .into_inner();
+    let mut archive: Value = serde_json::from_str(&export.archive)?;
+    assert_eq!(archive["entries"]["overwatch"]["service"], "overwatch");
+    assert_eq!(archive["entries"]["hall"]["service"], "nyx");
+    assert!(archive["entries"].get("gone").is_none());
+    let failed: Vec<_> = export.peers.iter().filter(|p| !p.success).collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].target, "gone");
+
+    archive["entries"]["hall"]["config"]["auto_dim"]["dim_level"] = json!(42);
+    let import = client
+        .import_config(ImportConfigRequest {
+            token: None,
+            archive: archive.to_string(),
+            include_peers: true,
+        })
+        .await?
+        .into_inner();
+    assert!(import.success, "{:?}", import.results);
+    assert_eq!(import.results[0].target, "local");
+
+    let mut peer = WsClient::connect(nyx.port()).await?;
+    let reply = peer.request(json!({"type": "get_auto_dim_config"})).await?;
+    assert_eq!(reply["config"]["dim_level"], 42);
+    Ok(())
+}
@@ -21,6 +21,15 @@ SERVICE_JOG_SCHEMA = vol.Schema({
     vol.Optional("feed_rate"): vol.Coerce(float),
 })
 
+SERVICE_SET_INTERLOCK_SCHEMA = vol.Schema({
+    vol.Required("device_id"): cv.string,
+    vol.Required("reason"): cv.string,
+})
+
+SERVICE_CLEAR_INTERLOCK_SCHEMA = vol.Schema({
+    vol.Required("device_id"): cv.string,
+})
+
 
 async def async_setup(hass: HomeAssistant, config: dict):
     """Set up the DOSA component from configuration.yaml."""
@@ -36,12 +45,13 @@ async def async_setup(hass: HomeAssistant, config: dict):
         host = device_config.get("host")
         port = device_config.get("port", 8766)
         name = device_config.get("name", f"DOSA {device_id}")
+        token = device_config.get("token")  # dosa auth token (control role)
 
         if not host:
             _LOGGER.error(f"No host specified for device {device_id}")
             continue
 
-        coordinator = DosaCoordinator(hass, device_id, name, host, port)
+        coordinator = DosaCoordinator(hass, device_id, name, host, port, token)
         await coordinator.async_start()
         coordinators[device_id] = coordinator
         _LOGGER.info(f"Coordinator created for {name}")
@@ -78,6 +88,38 @@ async def async_setup(hass: HomeAssistant, config: dict):
         DOMAIN, "jog", handle_jog, schema=SERVICE_JOG_SCHEMA
     )
 
+    async def handle_set_interlock(call: ServiceCall) -> None:
+        """Handle set_interlock service call."""
+        device_id = call.data.get("device_id")
+
+        if device_id not in coordinators:
+            _LOGGER.error(f"Device {device_id} not found")
+            return
+
+        coordinator = coordinators[device_id]
+        await coordinator.async_send_command(
+            coordinator.client.set_interlock,
+            call.data.get("reason")
+        )
+
+    async def handle_clear_interlock(call: ServiceCall) -> None:
+        """Handle clear_interlock service call."""
+        device_id = call.data.get("device_id")
+
+        if device_id not in coordinators:
+            _LOGGER.error(f"Device {device_id} not found")
+            return
+
+        coordinator = coordinators[device_id]
+        await coordinator.async_send_command(coordinator.client.clear_interlock)
+
+    hass.services.async_register(
+        DOMAIN, "set_interlock", handle_set_interlock, schema=SERVICE_SET_INTERLOCK_SCHEMA
+    )
+    hass.services.async_register(
+        DOMAIN, "clear_interlock", handle_clear_interlock, schema=SERVICE_CLEAR_INTERLOCK_SCHEMA
+    )
+
     # Forward setup to platforms
     await discovery.async_load_platform(
         hass, Platform.COVER, DOMAIN, {}, config
@@ -27,6 +27,10 @@ async def async_setup_platform(hass, config, async_add_entities, discovery_info=
             DosaHomeButton(coordinator, device_id),
             DosaZeroButton(coordinator, device_id),
             DosaClearAlarmButton(coordinator, device_id),
+            DosaResetEstopButton(coordinator, device_id),
+            DosaManualModeButton(coordinator, device_id),
+            DosaResumeButton(coordinator, device_id),
+            DosaTrustPositionButton(coordinator, device_id),
         ])
 
     async_add_entities(entities, True)
@@ -113,3 +117,67 @@ class DosaClearAlarmButton(DosaButtonBase):
         await self.coordinator.async_send_command(
             self.coordinator.client.clear_alarm
         )
+
+
+class DosaResetEstopButton(DosaButtonBase):
+    """Button to reset a tripped E-stop on the DOSA door."""
+
+    _attr_icon = "mdi:car-brake-alert"
+
+    def __init__(self, coordinator: DosaCoordinator, device_id: str):
+        """Initialize the reset E-stop button."""
+        super().__init__(coordinator, device_id, "reset_estop", "Reset E-stop")
+
+    async def async_press(self) -> None:
+        """Handle the button press."""
+        await self.coordinator.async_send_command(
+            self.coordinator.client.reset_estop
+        )
+
+
+class DosaManualModeButton(DosaButtonBase):
+    """Button to disable the DOSA motor for hand operation."""
+
+    _attr_icon = "mdi:hand-back-right"
+
+    def __init__(self, coordinator: DosaCoordinator, device_id: str):
+        """Initialize the manual mode button."""
+        super().__init__(coordinator, device_id, "manual_mode", "Manual Mode")
+
+    async def async_press(self) -> None:
+        """Handle the button press."""
+        await self.coordinator.async_send_command(
+            self.coordinator.client.manual_mode
+        )
+
+
+class DosaResumeButton(DosaButtonBase):
+    """Button to resume a move the safety door interrupted."""
+
+    _attr_icon = "mdi:play"
+
+    def __init__(self, coordinator: DosaCoordinator, device_id: str):
+        """Initialize the resume button."""
+        super().__init__(coordinator, device_id, "resume", "Resume")
+
+    async def async_press(self) -> None:
+        """Handle the button press."""
+        await self.coordinator.async_send_command(
+            self.coordinator.client.resume
+        )
+
+
+class DosaTrustPositionButton(DosaButtonBase):
+    """Button to take the last resting position instead of homing after a power cut."""
+
+    _attr_icon = "mdi:map-marker-check"
+
+    def __init__(self, coordinator: DosaCoordinator, device_id: str):
+        """Initialize the trust position button."""
+        super().__init__(coordinator, device_id, "trust_position", "Trust Position")
+
+    async def async_press(self) -> None:
+        """Handle the button press."""
+        await self.coordinator.async_send_command(
+            self.coordinator.client.trust_position
+        )
@@ -3,6 +3,7 @@ import asyncio
 import json
 import logging
 from typing import Optional, Dict, Any
+from urllib.parse import quote
 import websockets
 
 _LOGGER = logging.getLogger(__name__)
@@ -11,15 +12,19 @@ _LOGGER = logging.getLogger(__name__)
 class DosaClient:
     """Client for communicating with DOSA server."""
 
-    def __init__(self, host: str, port: int = 8766):
+    def __init__(self, host: str, port: int = 8766, token: Optional[str] = None):
         """Initialize the client."""
         self.host = host
         self.port = port
         self.uri = f"ws://{host}:{port}"
+        # Sent as a query parameter; logs use self.uri so the token stays out of them
+        self._connect_uri = f"{self.uri}/?token={quote(token, safe='')}" if token else self.uri
         self._websocket = None
         self._connected = False
         self._keepalive_task = None
-        self._response_queue: asyncio.Queue = asyncio.Queue()
+        # Replies are matched to commands by the request id dosa echoes back
+        self._next_id = 0
+        self._pending: Dict[str, asyncio.Future] = {}
         self._listening = False
 
     async def connect(self) -> bool:
@@ -33,7 +38,7 @@ class DosaClient:
             self._websocket = None
 
         try:
-            self._websocket = await websockets.connect(self.uri)
+            self._websocket = await websockets.connect(self._connect_uri)
             self._connected = True
             _LOGGER.info(f"Connected to {self.uri}")
             return True
@@ -57,36 +62,44 @@ class DosaClient:
             self._connected = False
 
     async def _send_command(self, command: Dict[str, Any]) -> Optional[Dict[str, Any]]:
-        """Send a command and wait for response."""
+        """Send a command and wait for its reply."""
         if not self._connected:
             if not await self.connect():
                 return None
 
-        try:
-            # Send command
-            await self._websocket.send(json.dumps(command))
-            _LOGGER.info(f"Sent command: {command} (listening mode: {self._listening})")
+        self._next_id += 1
+        request_id = f"ha-{self._next_id}"
+        command = {**command, 'id': request_id}
 
-            # If we're in listening mode, wait for response from queue
+        try:
+            # If we're in listening mode, the receive loop hands the reply over
             if self._listening:
+                reply = asyncio.get_running_loop().create_future()
+                self._pending[request_id] = reply
                 try:
-                    # Wait for a response message (status, response, or error)
-                    response = await asyncio.wait_for(
-                        self._response_queue.get(),
-                        timeout=10.0
-                    )
+                    await self._websocket.send(json.dumps(command))
+                    _LOGGER.info(f"Sent command: {command} (listening mode: {self._listening})")
+                    response = await asyncio.wait_for(reply, timeout=10.0)
                     _LOGGER.debug(f"Received response: {response}")
                     return response
                 except asyncio.TimeoutError:
-                    _LOGGER.error("Timeout waiting for response")
+                    _LOGGER.error(f"Timeout waiting for response to {request_id}")
                     return None
+                finally:
+                    self._pending.pop(request_id, None)
             else:
-                # Not in listening mode, read directly
-                response = await self._websocket.recv()
-                data = json.loads(response)
-                _LOGGER.info(f"Received message: {data}")
-                return data
+                # Not in listening mode, read directly, skipping broadcasts
+                await self._websocket.send(json.dumps(command))
+                _LOGGER.info(f"Sent command: {command} (listening mode: {self._listening})")
+                while True:
+                    data = json.loads(await asyncio.wait_for(self._websocket.recv(), timeout=10.0))
+                    if data.get('id') == request_id:
+                        _LOGGER.info(f"Received message: {data}")
+                        return data
 
+        except asyncio.TimeoutError:
+            _LOGGER.error(f"Timeout waiting for response to {request_id}")
+            return None
         except websockets.exceptions.ConnectionClosed:
             _LOGGER.error("Connection closed")
             self._connected = False
@@ -160,6 +173,48 @@ class DosaClient:
             return response.get('success', False)
         return False
 
+    async def reset_estop(self) -> bool:
+        """Reset a tripped E-stop."""
+        response = await self._send_command({'type': 'reset_estop'})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
+    async def manual_mode(self) -> bool:
+        """Disable the motor so the door can be moved by hand."""
+        response = await self._send_command({'type': 'manual_mode'})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
+    async def resume(self) -> bool:
+        """Carry on with the move the safety door interrupted."""
+        response = await self._send_command({'type': 'resume'})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
+    async def trust_position(self) -> bool:
+        """Take the door's last resting position as its position, without homing."""
+        response = await self._send_command({'type': 'trust_position'})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
+    async def set_interlock(self, reason: str) -> bool:
+        """Keep the door from opening until the interlock is cleared."""
+        response = await self._send_command({'type': 'set_interlock', 'reason': reason})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
+    async def clear_interlock(self) -> bool:
+        """Let the door open again."""
+        response = await self._send_command({'type': 'clear_interlock'})
+        if response and response.get('type') == 'response':
+            return response.get('success', False)
+        return False
+
     async def stop(self) -> bool:
         """Emergency stop."""
         response = await self._send_command({'type': 'stop'})
@@ -198,12 +253,10 @@ class DosaClient:
                     data = json.loads(message)
                     _LOGGER.debug(f"WebSocket received message: {data}")
 
-                    msg_type = data.get('type')
-
-                    # Response, error, and status messages go to the response queue for command handlers
-                    # Status broadcasts also trigger callbacks for real-time updates
-                    if msg_type in ('response', 'error', 'status'):
-                        await self._response_queue.put(data)
+                    # Replies go to the command waiting for them (broadcasts carry no id)
+                    reply = self._pending.get(data.get('id'))
+                    if reply and not reply.done():
+                        reply.set_result(data)
 
                     # All messages also go to the callback if set
                     callback(data)
@@ -217,6 +270,9 @@ class DosaClient:
             self._connected = False
         finally:
             self._listening = False
+            for reply in self._pending.values():
+                if not reply.done():
+                    reply.set_exception(ConnectionError("Connection closed"))
             # Close websocket when listening stops
             if self._websocket:
                 try:
@@ -26,6 +26,7 @@ class DosaCoordinator(DataUpdateCoordinator):
         name: str,
         host: str,
         port: int = 8766,
+        token: Optional[str] = None,
     ):
         """Initialize the coordinator."""
         super().__init__(
@@ -37,7 +38,7 @@ class DosaCoordinator(DataUpdateCoordinator):
         self.device_id = device_id
         self.host = host
         self.port = port
-        self.client = DosaClient(host, port)
+        self.client = DosaClient(host, port, token)
         self._listen_task: Optional[asyncio.Task] = None
         self._connected = False
         self._connecting = False
@@ -144,6 +145,9 @@ class DosaCoordinator(DataUpdateCoordinator):
         self._last_update_time = time.time()
 
         if data.get('type') == 'status':
+            # Only the primary door; other doors (dosa `doors` config) carry their own id
+            if data.get('door', {}).get('id', 'main') != 'main':
+                return
             _LOGGER.debug(f"Received status update: {data}")
             # Update coordinator data with new status
             self.async_set_updated_data(data)
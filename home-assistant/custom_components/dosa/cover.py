@@ -99,11 +99,12 @@ class DosaCover(CoordinatorEntity, CoverEntity):
         state = door.get("state")
 
         # Return True only if closed, False for all other states except fault/pending
-        if state == "closed":
+        if state in ("closed", "verifying"):
             return True
-        elif state in ("open", "intermediate", "opening", "closing", "halting", "homing"):
+        elif state in ("open", "intermediate", "opening", "closing", "halting", "homing", "calibrating"):
             return False
-        # Only return None for truly unknown states (fault, pending, alarm, or missing)
+        # Only return None for truly unknown states (fault, pending, alarm, estop, manual,
+        # safety_door, or missing)
         return None
 
     @property
@@ -114,8 +115,8 @@ class DosaCover(CoordinatorEntity, CoverEntity):
 
         door = self.coordinator.data.get("door", {})
         state = door.get("state")
-        # Treat homing as opening since it's a similar motion
-        return state in ("opening", "homing")
+        # Treat homing and calibrating as opening since they're similar motions
+        return state in ("opening", "homing", "calibrating")
 
     @property
     def is_closing(self) -> bool:
@@ -169,6 +170,38 @@ class DosaCover(CoordinatorEntity, CoverEntity):
         else:
             attrs["has_alarm"] = False
 
+        # Add move progress while the door is opening or closing
+        for key in ("progress_percent", "feed_rate", "eta_secs"):
+            if (value := door.get(key)) is not None:
+                attrs[key] = value
+
+        # Add the hold-open state while a client keeps the door open
+        for key in ("hold", "hold_remaining_secs"):
+            if (value := door.get(key)) is not None:
+                attrs[key] = value
+
+        # Which CNC connection is in use, e.g. after failing over to a backup controller
+        if endpoint := door.get("cnc_endpoint"):
+            attrs["cnc_endpoint"] = endpoint
+
+        # Why the door may not open (e.g. a high-wind interlock)
+        if interlock := door.get("interlock"):
+            attrs["interlock"] = interlock
+
+        # Whether the safety door is closed again, so the door can resume
+        if (ready := door.get("safety_door_ready")) is not None:
+            attrs["safety_door_ready"] = ready
+
+        # Where the door last came to rest, while it can be trusted instead of homing
+        if (trust_mm := door.get("trust_position_mm")) is not None:
+            attrs["trust_position_mm"] = trust_mm
+
+        # Flag a closed position check that found the limit switch out of place
+        drift = door.get("position_drift")
+        attrs["has_position_drift"] = drift is not None
+        if drift and (drift_mm := drift.get("drift_mm")) is not None:
+            attrs["position_drift_mm"] = drift_mm
+
         return attrs
 
     @property
@@ -28,12 +28,14 @@ async def async_setup(hass: HomeAssistant, config: dict):
         host = device_config.get("host")
         port = device_config.get("port", 8765)
         name = device_config.get("name", f"SHQ Display {device_id}")
+        panels = device_config.get("panels", [])
+        token = device_config.get("token")  # nyx auth token (control role)
 
         if not host:
             _LOGGER.error(f"No host specified for device {device_id}")
             continue
 
-        coordinator = SHQDisplayCoordinator(hass, device_id, name, host, port)
+        coordinator = SHQDisplayCoordinator(hass, device_id, name, host, port, panels, token)
         await coordinator.async_start()
         coordinators[device_id] = coordinator
         _LOGGER.info(f"Coordinator created for {name}")
@@ -60,6 +62,69 @@ async def async_setup(hass: HomeAssistant, config: dict):
         }),
     )
 
+    # Register night mode service
+    async def handle_set_night_mode(call):
+        device_id = call.data["device_id"]
+        coordinator = hass.data[DOMAIN].get(device_id)
+        if coordinator is None:
+            _LOGGER.error(f"Unknown device_id: {device_id}")
+            return
+        await coordinator.async_send_command(
+            coordinator.client.set_night_mode, call.data["enabled"], call.data.get("temperature")
+        )
+
+    hass.services.async_register(
+        DOMAIN,
+        "set_night_mode",
+        handle_set_night_mode,
+        schema=vol.Schema({
+            vol.Required("device_id"): cv.string,
+            vol.Required("enabled"): cv.boolean,
+            vol.Optional("temperature"): vol.All(vol.Coerce(int), vol.Range(min=1000, max=6500)),
+        }),
+    )
+
+    # Register profile service
+    async def handle_set_profile(call):
+        device_id = call.data["device_id"]
+        coordinator = hass.data[DOMAIN].get(device_id)
+        if coordinator is None:
+            _LOGGER.error(f"Unknown device_id: {device_id}")
+            return
+        await coordinator.async_send_command(coordinator.client.set_profile, call.data["name"])
+
+    hass.services.async_register(
+        DOMAIN,
+        "set_profile",
+        handle_set_profile,
+        schema=vol.Schema({
+            vol.Required("device_id"): cv.string,
+            vol.Required("name"): cv.string,
+        }),
+    )
+
+    # Register touch lock services
+    def touch_lock_handler(command):
+        async def handle(call):
+            device_id = call.data["device_id"]
+            coordinator = hass.data[DOMAIN].get(device_id)
+            if coordinator is None:
+                _LOGGER.error(f"Unknown device_id: {device_id}")
+                return
+            await coordinator.async_send_command(getattr(coordinator.client, command), call.data["pin"])
+        return handle
+
+    for command in ("lock_touch", "unlock_touch"):
+        hass.services.async_register(
+            DOMAIN,
+            command,
+            touch_lock_handler(command),
+            schema=vol.Schema({
+                vol.Required("device_id"): cv.string,
+                vol.Required("pin"): cv.string,
+            }),
+        )
+
     # Register shutdown handler
     async def async_shutdown(event):
         """Shutdown coordinators on Home Assistant stop."""
@@ -3,6 +3,7 @@ import asyncio
 import json
 import logging
 from typing import Optional, Dict, Any
+from urllib.parse import quote
 import websockets
 
 _LOGGER = logging.getLogger(__name__)
@@ -11,11 +12,13 @@ _LOGGER = logging.getLogger(__name__)
 class SHQDisplayClient:
     """Client for communicating with SHQ Display server."""
 
-    def __init__(self, host: str, port: int = 8765):
+    def __init__(self, host: str, port: int = 8765, token: Optional[str] = None):
         """Initialize the client."""
         self.host = host
         self.port = port
         self.uri = f"ws://{host}:{port}"
+        # Sent as a query parameter; logs use self.uri so the token stays out of them
+        self._connect_uri = f"{self.uri}/?token={quote(token, safe='')}" if token else self.uri
         self._websocket = None
         self._connected = False
         self._keepalive_task = None
@@ -25,7 +28,7 @@ class SHQDisplayClient:
     async def connect(self) -> bool:
         """Connect to the server."""
         try:
-            self._websocket = await websockets.connect(self.uri)
+            self._websocket = await websockets.connect(self._connect_uri)
             self._connected = True
             _LOGGER.info(f"Connected to {self.uri}")
             return True
@@ -111,12 +114,12 @@ class SHQDisplayClient:
         response = await self._send_command({'type': 'get_metrics'})
         return response if response and response.get('success') else None
 
-    async def set_brightness(self, brightness: int) -> bool:
-        """Set brightness (0-10)."""
-        response = await self._send_command({
-            'type': 'set_brightness',
-            'brightness': brightness
-        })
+    async def set_brightness(self, brightness: int, display: Optional[str] = None) -> bool:
+        """Set brightness (0-255) of one display, or all of them."""
+        command = {'type': 'set_brightness', 'brightness': brightness}
+        if display is not None:
+            command['display'] = display
+        response = await self._send_command(command)
         return response.get('success', False) if response else False
 
     async def set_display_state(self, state: bool) -> bool:
@@ -127,14 +130,20 @@ class SHQDisplayClient:
         })
         return response.get('success', False) if response else False
 
-    async def wake(self) -> bool:
-        """Wake display to bright level."""
-        response = await self._send_command({'type': 'wake'})
+    async def wake(self, display: Optional[str] = None) -> bool:
+        """Wake one display, or all of them, to bright level."""
+        command = {'type': 'wake'}
+        if display is not None:
+            command['display'] = display
+        response = await self._send_command(command)
         return response.get('success', False) if response else False
 
-    async def sleep(self) -> bool:
-        """Sleep display (turn off)."""
-        response = await self._send_command({'type': 'sleep'})
+    async def sleep(self, display: Optional[str] = None) -> bool:
+        """Sleep one display, or all of them (turn off)."""
+        command = {'type': 'sleep'}
+        if display is not None:
+            command['display'] = display
+        response = await self._send_command(command)
         return response.get('success', False) if response else False
 
     async def set_auto_dim_config(
@@ -163,6 +172,34 @@ class SHQDisplayClient:
         response = await self._send_command({'type': 'navigate', 'url': url})
         return response.get('success', False) if response else False
 
+    async def set_night_mode(self, enabled: bool, temperature: Optional[int] = None) -> bool:
+        """Turn night mode on or off, optionally with a colour temperature (kelvin)."""
+        command = {'type': 'set_night_mode', 'enabled': enabled}
+        if temperature is not None:
+            command['temperature'] = temperature
+        response = await self._send_command(command)
+        return response.get('success', False) if response else False
+
+    async def set_profile(self, name: str) -> bool:
+        """Switch to one of the kiosk's configured profiles."""
+        response = await self._send_command({'type': 'set_profile', 'name': name})
+        return response.get('success', False) if response else False
+
+    async def lock_touch(self, pin: str) -> bool:
+        """Ignore touch input until unlocked with the same PIN."""
+        response = await self._send_command({'type': 'lock_touch', 'pin': pin})
+        return response.get('success', False) if response else False
+
+    async def unlock_touch(self, pin: str) -> bool:
+        """Accept touch input again."""
+        response = await self._send_command({'type': 'unlock_touch', 'pin': pin})
+        return response.get('success', False) if response else False
+
+    async def subscribe_touch(self) -> bool:
+        """Stream touch_activity messages to this connection."""
+        response = await self._send_command({'type': 'subscribe_touch'})
+        return response.get('success', False) if response else False
+
     async def get_auto_dim_config(self) -> Optional[Dict[str, Any]]:
         """Get auto-dim configuration."""
         response = await self._send_command({'type': 'get_auto_dim_config'})
@@ -26,6 +26,8 @@ class SHQDisplayCoordinator(DataUpdateCoordinator):
         name: str,
         host: str,
         port: int = 8765,
+        panels: Optional[list] = None,
+        token: Optional[str] = None,
     ):
         """Initialize the coordinator."""
         super().__init__(
@@ -37,7 +39,9 @@ class SHQDisplayCoordinator(DataUpdateCoordinator):
         self.device_id = device_id
         self.host = host
         self.port = port
-        self.client = SHQDisplayClient(host, port)
+        # Backlight device names of extra panels, each getting its own light
+        self.panels = panels or []
+        self.client = SHQDisplayClient(host, port, token)
         self._listen_task: Optional[asyncio.Task] = None
         self._connected = False
         self._reconnect_task: Optional[asyncio.Task] = None
@@ -46,6 +50,8 @@ class SHQDisplayCoordinator(DataUpdateCoordinator):
         self._availability_task: Optional[asyncio.Task] = None
         self._last_availability_state: bool = False
         self._connecting = False
+        # Latest touch_activity message ("active"/"idle"), once the panel has been touched
+        self.touch_activity: Optional[Dict[str, Any]] = None
 
     async def async_start(self):
         """Start the coordinator and establish WebSocket connection."""
@@ -76,6 +82,8 @@ class SHQDisplayCoordinator(DataUpdateCoordinator):
                 self._listen_task = asyncio.create_task(
                     self._async_listen_for_updates()
                 )
+                # Panel touches are a presence signal; older nyx builds just reply with an error
+                asyncio.create_task(self.client.subscribe_touch())
                 _LOGGER.info(f"Successfully connected to SHQ Display at {self.host}:{self.port}")
             else:
                 _LOGGER.warning(f"Failed to connect to SHQ Display at {self.host}:{self.port}, will retry")
@@ -134,6 +142,9 @@ class SHQDisplayCoordinator(DataUpdateCoordinator):
         if data.get('type') == 'metrics':
             # Update coordinator data with new metrics
             self.async_set_updated_data(data)
+        elif data.get('type') == 'touch_activity':
+            self.touch_activity = data
+            self.async_update_listeners()
 
     def is_available(self) -> bool:
         """Check if device is available based on last update time."""
@@ -30,29 +30,49 @@ async def async_setup_platform(
 
     for device_id, coordinator in coordinators.items():
         entities.append(SHQDisplayLight(coordinator))
+        for panel in coordinator.panels:
+            entities.append(SHQDisplayLight(coordinator, panel))
 
     async_add_entities(entities)
 
 
 class SHQDisplayLight(CoordinatorEntity, LightEntity):
-    """Representation of an SHQ Display as a light."""
+    """Representation of an SHQ Display as a light.
 
-    def __init__(self, coordinator):
+    Without a panel the light drives every display on the kiosk and shows the primary one;
+    with one it drives and shows only that backlight device.
+    """
+
+    def __init__(self, coordinator, panel: Optional[str] = None):
         """Initialize the light."""
         super().__init__(coordinator)
-        self._attr_name = coordinator.name
-        self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_light"
+        self._panel = panel
+        if panel is None:
+            self._attr_name = coordinator.name
+            self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_light"
+        else:
+            self._attr_name = f"{coordinator.name} {panel}"
+            self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_{panel}_light"
         self._attr_color_mode = ColorMode.BRIGHTNESS
         self._attr_supported_color_modes = {ColorMode.BRIGHTNESS}
         # Note: device_info not supported for YAML-based integrations
 
+    def _display_data(self) -> dict:
+        """Metrics of this light's display."""
+        if self._panel is None:
+            return self.coordinator.data.get('display', {})
+        for display in self.coordinator.data.get('displays', []):
+            if display.get('id') == self._panel:
+                return display
+        return {}
+
     @property
     def is_on(self) -> bool:
         """Return true if light is on."""
         if not self.coordinator.data:
             return True
 
-        display_data = self.coordinator.data.get('display', {})
+        display_data = self._display_data()
         brightness = display_data.get('brightness', 255)
         display_on = display_data.get('display_on', True)
         return display_on and brightness > 0
@@ -63,7 +83,7 @@ class SHQDisplayLight(CoordinatorEntity, LightEntity):
         if not self.coordinator.data:
             return 255
 
-        display_data = self.coordinator.data.get('display', {})
+        display_data = self._display_data()
         brightness = display_data.get('brightness', 255)  # 0-255 scale
 
         return brightness
@@ -75,16 +95,16 @@ class SHQDisplayLight(CoordinatorEntity, LightEntity):
         if brightness is not None:
             # Brightness is already 0-255, no conversion needed
             await self.coordinator.async_send_command(
-                self.coordinator.client.set_brightness, brightness
+                self.coordinator.client.set_brightness, brightness, self._panel
             )
         else:
             # Use wake command to turn on to bright level
             await self.coordinator.async_send_command(
-                self.coordinator.client.wake
+                self.coordinator.client.wake, self._panel
             )
 
     async def async_turn_off(self, **kwargs: Any) -> None:
         """Turn the light off using sleep command."""
         await self.coordinator.async_send_command(
-            self.coordinator.client.sleep
+            self.coordinator.client.sleep, self._panel
         )
@@ -27,6 +27,9 @@ async def async_setup_platform(
     for device_id, coordinator in coordinators.items():
         entities.append(SHQDisplayVersionSensor(coordinator))
         entities.append(SHQDisplayUrlSensor(coordinator))
+        entities.append(SHQDisplayBrowserSensor(coordinator))
+        entities.append(SHQDisplayPresenceSensor(coordinator))
+        entities.append(SHQDisplayTouchActivitySensor(coordinator))
 
     async_add_entities(entities)
 
@@ -75,3 +78,94 @@ class SHQDisplayUrlSensor(CoordinatorEntity, SensorEntity):
     @property
     def available(self) -> bool:
         return self.coordinator.is_available()
+
+
+class SHQDisplayBrowserSensor(CoordinatorEntity, SensorEntity):
+    """Browser watchdog state for SHQ Display (unknown unless browser_watchdog is enabled)."""
+
+    def __init__(self, coordinator):
+        """Initialize the browser sensor."""
+        super().__init__(coordinator)
+        self._attr_name = f"{coordinator.name} Browser"
+        self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_browser"
+        self._attr_icon = "mdi:google-chrome"
+
+    @property
+    def native_value(self) -> Optional[str]:
+        """Return the watchdog state (ok, unresponsive, restarting)."""
+        if not self.coordinator.data:
+            return None
+        browser = self.coordinator.data.get('browser')
+        return browser.get('state') if browser else None
+
+    @property
+    def extra_state_attributes(self) -> dict:
+        """Return restart count and the last error."""
+        browser = (self.coordinator.data or {}).get('browser') or {}
+        return {
+            'restarts': browser.get('restarts'),
+            'last_restart_ms': browser.get('last_restart_ms'),
+            'error': browser.get('error'),
+        }
+
+    @property
+    def available(self) -> bool:
+        return self.coordinator.is_available()
+
+
+class SHQDisplayPresenceSensor(CoordinatorEntity, SensorEntity):
+    """Presence sensor reading for SHQ Display (unknown unless presence.input is set)."""
+
+    def __init__(self, coordinator):
+        """Initialize the presence sensor."""
+        super().__init__(coordinator)
+        self._attr_name = f"{coordinator.name} Presence"
+        self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_presence"
+        self._attr_icon = "mdi:motion-sensor"
+
+    @property
+    def native_value(self) -> Optional[str]:
+        """Return detected or clear."""
+        if not self.coordinator.data:
+            return None
+        presence = self.coordinator.data.get('presence')
+        if not presence:
+            return None
+        return "detected" if presence.get('present') else "clear"
+
+    @property
+    def extra_state_attributes(self) -> dict:
+        """Return why the sensor couldn't be read, if it couldn't."""
+        presence = (self.coordinator.data or {}).get('presence') or {}
+        return {'error': presence.get('error')}
+
+    @property
+    def available(self) -> bool:
+        return self.coordinator.is_available()
+
+
+class SHQDisplayTouchActivitySensor(CoordinatorEntity, SensorEntity):
+    """Whether the panel is being touched (unknown until it first is)."""
+
+    def __init__(self, coordinator):
+        """Initialize the touch activity sensor."""
+        super().__init__(coordinator)
+        self._attr_name = f"{coordinator.name} Touch Activity"
+        self._attr_unique_id = f"{DOMAIN}_{coordinator.device_id}_touch_activity"
+        self._attr_icon = "mdi:gesture-tap"
+
+    @property
+    def native_value(self) -> Optional[str]:
+        """Return active or idle."""
+        activity = self.coordinator.touch_activity
+        return activity.get('state') if activity else None
+
+    @property
+    def extra_state_attributes(self) -> dict:
+        """Return when the panel was last touched."""
+        activity = self.coordinator.touch_activity or {}
+        return {'last_touch_time': activity.get('last_touch_time')}
+
+    @property
+    def available(self) -> bool:
+        return self.coordinator.is_available()
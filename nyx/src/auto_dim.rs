@@ -1,84 +1,108 @@
 use anyhow::Result;
+use shq_systemd::Heartbeat;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::{watch, Mutex};
+use tokio::sync::{broadcast, watch, Mutex};
 use tokio::task;
 use tokio::time::{interval, Duration};
 
-use crate::display::DisplayController;
-use crate::messages::{AutoDimConfig, AutoDimStatus};
+use crate::display::{DisplayController, Displays};
+use crate::messages::{AutoDimConfig, AutoDimEvent, AutoDimState, AutoDimStatus, AutoDimTransition};
+use crate::presence::PresenceSensor;
 use crate::touch::TouchMonitor;
 
+/// Transitions buffered per subscriber before the oldest are dropped
+const TRANSITION_BUFFER: usize = 32;
+/// The shared auto-dim config and the displays that have their own
+#[derive(Debug, Clone)]
+struct AutoDimConfigs {
+    shared: AutoDimConfig,
+    displays: BTreeMap<String, AutoDimConfig>,
+}
+
+impl AutoDimConfigs {
+    fn get(&self, display: Option<&str>) -> &AutoDimConfig {
+        display
+            .and_then(|id| self.displays.get(id))
+            .unwrap_or(&self.shared)
+    }
+}
+
 /// Auto-dim manager for automatic brightness dimming and display power-off
+///
+/// Each display is in an explicit [`AutoDimState`] (active → dimmed → off) and only moves on
+/// an [`AutoDimEvent`]: idle timeouts and activity from the check loop, `wake`/`sleep`, and
+/// brightness set by hand. All displays share the touch device, so they share the idle time
+/// too; each dims and turns off after its own config's times. A presence sensor with
+/// `keep_awake` counts as activity alongside touches.
 #[derive(Clone)]
 pub struct AutoDimManager {
-    config: Arc<Mutex<AutoDimConfig>>,
-    is_dimmed: Arc<Mutex<bool>>,
-    display: DisplayController,
+    configs: Arc<Mutex<AutoDimConfigs>>,
+    states: Arc<Mutex<BTreeMap<String, AutoDimState>>>,
+    transitions: broadcast::Sender<AutoDimTransition>,
+    displays: Displays,
     touch_monitor: TouchMonitor,
+    presence: PresenceSensor,
     shutdown: watch::Sender<bool>,
 }
 
 impl AutoDimManager {
-    /// Create a new auto-dim manager
+    /// Create a new auto-dim manager; every display starts active
     pub fn new(
         config: AutoDimConfig,
-        display: DisplayController,
+        display_configs: BTreeMap<String, AutoDimConfig>,
+        displays: Displays,
         touch_monitor: TouchMonitor,
+        presence: PresenceSensor,
     ) -> Self {
         let (shutdown_tx, _) = watch::channel(false);
+        let (transitions, _) = broadcast::channel(TRANSITION_BUFFER);
+        let states = displays
+            .iter()
+            .map(|display| (display.id().to_string(), AutoDimState::Active))
+            .collect();
 
         Self {
-            config: Arc::new(Mutex::new(config)),
-            is_dimmed: Arc::new(Mutex::new(false)),
-            display,
+            configs: Arc::new(Mutex::new(AutoDimConfigs {
+                shared: config,
+                displays: display_configs,
+            })),
+            states: Arc::new(Mutex::new(states)),
+            transitions,
+            displays,
             touch_monitor,
+            presence,
             shutdown: shutdown_tx,
         }
     }
 
-    /// Start the auto-dim manager
-    pub async fn start(&self) -> Result<()> {
+    /// Start the auto-dim manager, beating `heartbeat` on every check
+    pub async fn start(&self, heartbeat: Heartbeat) -> Result<()> {
         tracing::info!("Auto-dim manager started");
 
         // Create wake channel for touch monitor callbacks
         let (wake_tx, mut wake_rx) = tokio::sync::mpsc::unbounded_channel();
         self.touch_monitor.set_wake_callback(wake_tx).await;
 
-        let config = self.config.clone();
-        let is_dimmed = self.is_dimmed.clone();
-        let display = self.display.clone();
-        let touch_monitor = self.touch_monitor.clone();
         let mut shutdown_rx = self.shutdown.subscribe();
 
-        // Spawn wake handler (handles touch events and explicit wake calls)
-        let wake_display = self.display.clone();
-        let wake_config = self.config.clone();
-        let wake_touch = self.touch_monitor.clone();
+        // Spawn wake handler (handles touch events and explicit wake calls); a touch wakes
+        // every display
+        let wake = self.clone();
         task::spawn(async move {
             while let Some(()) = wake_rx.recv().await {
                 tracing::info!("Wake request received");
-                let cfg = wake_config.lock().await.clone();
-
-                // Reset idle time
-                wake_touch.reset_touch_timer().await;
-
-                // Stop grabbing if grabbing
-                wake_touch.set_should_block(false).await;
-
-                // Restore brightness if below bright_level
-                if let Ok(current_brightness) = wake_display.get_brightness().await {
-                    if current_brightness < cfg.bright_level {
-                        if let Err(e) = wake_display.set_brightness(cfg.bright_level).await {
-                            tracing::error!("Failed to set brightness during wake: {}", e);
-                        }
-                    }
+                if let Err(e) = wake.wake(None).await {
+                    tracing::error!("Failed to set brightness during wake: {}", e);
                 }
             }
         });
 
+        let manager = self.clone();
         task::spawn(async move {
             // Check every 25ms for faster response to touch events
             let mut tick = interval(Duration::from_millis(25));
+            let mut last_idle_time = f64::MAX;
 
             loop {
                 tokio::select! {
@@ -89,16 +113,10 @@ impl AutoDimManager {
                         }
                     }
                     _ = tick.tick() => {
-                        if let Err(e) = Self::check_and_apply_dimming(
-                            &config,
-                            &is_dimmed,
-                            &display,
-                            &touch_monitor,
-                        )
-                        .await
-                        {
+                        if let Err(e) = manager.check_and_apply_dimming(&mut last_idle_time).await {
                             tracing::error!("Auto-dim error: {}", e);
                         }
+                        heartbeat.beat();
                     }
                 }
             }
@@ -112,72 +130,122 @@ impl AutoDimManager {
         let _ = self.shutdown.send(true);
     }
 
-    /// Check idle time and apply dimming/off logic
-    async fn check_and_apply_dimming(
-        config: &Arc<Mutex<AutoDimConfig>>,
-        _is_dimmed: &Arc<Mutex<bool>>,
-        display: &DisplayController,
-        touch_monitor: &TouchMonitor,
-    ) -> Result<()> {
-        let cfg = config.lock().await.clone();
-        let idle_time = touch_monitor.get_idle_time().await;
+    /// Every auto-dim state change, for clients
+    pub fn subscribe_transitions(&self) -> broadcast::Receiver<AutoDimTransition> {
+        self.transitions.subscribe()
+    }
 
-        // Check if auto-dim is enabled
-        if cfg.auto_dim_time == 0 && cfg.auto_off_time == 0 {
-            return Ok(());
+    /// Turn the idle time into events and move every display on by them; activity is the
+    /// idle time going down since `last_idle_time`
+    async fn check_and_apply_dimming(&self, last_idle_time: &mut f64) -> Result<()> {
+        let configs = self.configs.lock().await.clone();
+        let mut idle_time = self.touch_monitor.get_idle_time().await;
+        if let Some(presence_idle) = self.presence.idle_time().await {
+            idle_time = idle_time.min(presence_idle);
         }
-
-        // Auto-off: same as sleep
-        if cfg.auto_off_time > 0 && idle_time >= cfg.auto_off_time as f64 {
-            let current_brightness = display.get_brightness().await?;
-            if current_brightness > 0 {
-                tracing::info!("Auto-off triggered after {:.1} seconds idle", idle_time);
-                display.set_brightness(0).await?;
-                touch_monitor.set_should_block(true).await;
+        let activity = idle_time < *last_idle_time;
+        *last_idle_time = idle_time;
+
+        let mut turned_off = false;
+        for display in self.displays.iter() {
+            let cfg = configs.get(Some(display.id()));
+            let event = if activity {
+                AutoDimEvent::Activity
+            } else if cfg.auto_off_time > 0 && idle_time >= cfg.auto_off_time as f64 {
+                AutoDimEvent::OffTimeout
+            } else if cfg.auto_dim_time > 0 && idle_time >= cfg.auto_dim_time as f64 {
+                AutoDimEvent::DimTimeout
+            } else {
+                continue;
+            };
+
+            let from = self.state(display.id()).await;
+            let to = next_state(from, event);
+            if to != from {
+                let id = display.id();
+                tracing::info!("Auto-dim of display {}: {:?} after {:.1} seconds idle", id, event, idle_time);
+                Self::enter(display, cfg, to).await?;
+                self.record(display.id(), event, to).await;
+                turned_off |= to == AutoDimState::Off;
             }
-            return Ok(());
         }
 
-        // Auto-dim: set brightness to dim_level if currently brighter
-        if cfg.auto_dim_time > 0 && idle_time >= cfg.auto_dim_time as f64 {
-            let current_brightness = display.get_brightness().await?;
-            if current_brightness > cfg.dim_level {
-                tracing::info!(
-                    "Auto-dim triggered after {:.1} seconds idle, setting brightness to {}",
-                    idle_time,
-                    cfg.dim_level
-                );
-                display.set_brightness(cfg.dim_level).await?;
-            }
+        // Touches only wake the displays once they're all off
+        if turned_off && self.displays.all_off().await {
+            self.touch_monitor.set_should_block(true).await;
         }
+        Ok(())
+    }
 
-        // On touch when dimmed (recent activity detected): restore brightness
-        // Touch events update idle_time, so very low idle_time indicates a recent touch
-        if idle_time < 0.1 {
-            let current_brightness = display.get_brightness().await?;
-            if current_brightness > 0 && current_brightness < cfg.bright_level {
-                tracing::info!("Touch detected while dimmed, restoring brightness to {}", cfg.bright_level);
-                display.set_brightness(cfg.bright_level).await?;
+    /// Set the brightness `state` stands for; active only brightens and dimmed only darkens,
+    /// so a level set by hand survives
+    async fn enter(display: &DisplayController, cfg: &AutoDimConfig, state: AutoDimState) -> Result<()> {
+        let current_brightness = display.get_brightness().await?;
+        match state {
+            AutoDimState::Active if current_brightness < cfg.bright_level => {
+                display.set_brightness(cfg.bright_level).await
             }
+            AutoDimState::Dimmed if current_brightness > cfg.dim_level => {
+                display.set_brightness(cfg.dim_level).await
+            }
+            AutoDimState::Off if current_brightness > 0 => display.set_brightness(0).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Current state of `display`
+    async fn state(&self, display: &str) -> AutoDimState {
+        self.states
+            .lock()
+            .await
+            .get(display)
+            .copied()
+            .unwrap_or(AutoDimState::Active)
+    }
+
+    /// Move display `id` to `to`, telling subscribers if that's a change
+    async fn record(&self, id: &str, event: AutoDimEvent, to: AutoDimState) {
+        let from = self.states.lock().await.insert(id.to_string(), to);
+        let from = from.unwrap_or(AutoDimState::Active);
+        if from != to {
+            tracing::debug!("Display {} auto-dim {:?} -> {:?} ({:?})", id, from, to, event);
+            let _ = self.transitions.send(AutoDimTransition {
+                display: id.to_string(),
+                from,
+                to,
+                event,
+            });
         }
+    }
 
-        Ok(())
+    /// Get the configuration of `display`, or the shared one
+    pub async fn get_config(&self, display: Option<&str>) -> AutoDimConfig {
+        self.configs.lock().await.get(display).clone()
     }
 
-    /// Get current configuration
-    pub async fn get_config(&self) -> AutoDimConfig {
-        self.config.lock().await.clone()
+    /// Set the configuration of `display`, or the shared one
+    pub async fn set_config(&self, display: Option<&str>, config: AutoDimConfig) {
+        let mut configs = self.configs.lock().await;
+        match display {
+            Some(id) => {
+                configs.displays.insert(id.to_string(), config);
+            }
+            None => configs.shared = config,
+        }
     }
 
-    /// Set configuration
-    pub async fn set_config(&self, config: AutoDimConfig) {
-        *self.config.lock().await = config;
+    /// Replace every configuration, e.g. after the config file changed
+    pub async fn set_configs(&self, config: AutoDimConfig, display_configs: BTreeMap<String, AutoDimConfig>) {
+        *self.configs.lock().await = AutoDimConfigs {
+            shared: config,
+            displays: display_configs,
+        };
     }
 
-    /// Get current status
-    pub async fn get_status(&self) -> AutoDimStatus {
-        let config = self.config.lock().await.clone();
-        let is_dimmed = *self.is_dimmed.lock().await;
+    /// Get the current status of `display`
+    pub async fn get_status(&self, display: &str) -> AutoDimStatus {
+        let config = self.get_config(Some(display)).await;
+        let state = self.state(display).await;
         let last_touch_time = self.touch_monitor.get_last_touch_time().await;
 
         AutoDimStatus {
@@ -185,20 +253,30 @@ impl AutoDimManager {
             bright_level: config.bright_level,
             auto_dim_time: config.auto_dim_time,
             auto_off_time: config.auto_off_time,
-            is_dimmed,
+            state,
+            is_dimmed: state == AutoDimState::Dimmed,
             last_touch_time,
         }
     }
 
-    /// Reset dimmed state and idle timer (call after manual brightness changes)
-    pub async fn reset_dimmed_state(&self) {
-        *self.is_dimmed.lock().await = false;
+    /// Take the brightness set by hand on `displays` as their state (off at 0, else active)
+    /// and restart the idle timer
+    pub async fn manual(&self, displays: &[DisplayController]) -> Result<()> {
+        for display in displays {
+            let state = if display.get_brightness().await? > 0 {
+                AutoDimState::Active
+            } else {
+                AutoDimState::Off
+            };
+            self.record(display.id(), AutoDimEvent::Manual, state).await;
+        }
         self.touch_monitor.reset_touch_timer().await;
+        Ok(())
     }
 
-    /// Wake the display (turn on and set to bright level)
-    pub async fn wake(&self) -> Result<()> {
-        let config = self.config.lock().await.clone();
+    /// Wake `display`, or every display (turn on and set to bright level)
+    pub async fn wake(&self, display: Option<&str>) -> Result<()> {
+        let configs = self.configs.lock().await.clone();
 
         // Reset idle time
         self.touch_monitor.reset_touch_timer().await;
@@ -207,24 +285,42 @@ impl AutoDimManager {
         self.touch_monitor.set_should_block(false).await;
 
         // Restore brightness if below bright_level
-        let current_brightness = self.display.get_brightness().await?;
-        if current_brightness < config.bright_level {
-            self.display.set_brightness(config.bright_level).await?;
+        for display in self.displays.select(display).unwrap_or_default() {
+            Self::enter(&display, configs.get(Some(display.id())), AutoDimState::Active).await?;
+            self.record(display.id(), AutoDimEvent::Wake, AutoDimState::Active).await;
         }
 
         tracing::info!("Display woken");
         Ok(())
     }
 
-    /// Sleep the display (turn off)
-    pub async fn sleep(&self) -> Result<()> {
-        // Start grabbing
-        self.touch_monitor.set_should_block(true).await;
-
+    /// Sleep `display`, or every display (turn off)
+    pub async fn sleep(&self, display: Option<&str>) -> Result<()> {
         // Set brightness to 0
-        self.display.set_brightness(0).await?;
+        for display in self.displays.select(display).unwrap_or_default() {
+            display.set_brightness(0).await?;
+            self.record(display.id(), AutoDimEvent::Sleep, AutoDimState::Off).await;
+        }
+
+        // Start grabbing once nothing is left on
+        if self.displays.all_off().await {
+            self.touch_monitor.set_should_block(true).await;
+        }
 
         tracing::info!("Display put to sleep");
         Ok(())
     }
 }
+
+/// Where `event` takes a display in `state`
+fn next_state(state: AutoDimState, event: AutoDimEvent) -> AutoDimState {
+    match (state, event) {
+        (AutoDimState::Active, AutoDimEvent::DimTimeout) => AutoDimState::Dimmed,
+        (AutoDimState::Active | AutoDimState::Dimmed, AutoDimEvent::OffTimeout) => AutoDimState::Off,
+        (AutoDimState::Dimmed, AutoDimEvent::Activity) => AutoDimState::Active,
+        (_, AutoDimEvent::Wake) => AutoDimState::Active,
+        (_, AutoDimEvent::Sleep) => AutoDimState::Off,
+        // Only a wake, or a brightness set by hand, turns an off display back on
+        (state, _) => state,
+    }
+}
@@ -0,0 +1,168 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+
+use crate::cdp::CdpClient;
+use crate::config::{BrowserWatchdogConfig, ConfigManager};
+use crate::messages::{BrowserState, BrowserStatus};
+
+/// Script run as the heartbeat; any answer means the renderer is alive
+const HEARTBEAT: &str = "document.readyState";
+
+/// Longest a heartbeat may take, including finding the tab and connecting
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Longest the restart command may run
+const RESTART_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Watches Chrome with a JS heartbeat over CDP and runs `browser_watchdog.restart_command`
+/// once enough heartbeats fail in a row, whether the debug port is gone or the page hangs
+///
+/// The section is read before every heartbeat, so edits apply straight away.
+#[derive(Clone)]
+pub struct BrowserWatchdog {
+    status: watch::Sender<Option<BrowserStatus>>,
+    config_manager: ConfigManager,
+    cdp: CdpClient,
+    shutdown: watch::Sender<bool>,
+}
+
+impl BrowserWatchdog {
+    pub fn new(config_manager: ConfigManager, cdp: CdpClient) -> Self {
+        let (status, _) = watch::channel(None);
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            status,
+            config_manager,
+            cdp,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Start the heartbeats
+    pub fn start(&self) {
+        let watchdog = self.clone();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                let config = watchdog.config_manager.get_browser_watchdog_config();
+                let wait = if config.enabled {
+                    watchdog.check(&config).await
+                } else {
+                    watchdog.publish(None);
+                    Duration::from_secs(config.interval.max(1))
+                };
+
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+        });
+    }
+
+    /// Stop the heartbeats
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// The current status; `None` while the watchdog is disabled
+    pub fn status(&self) -> Option<BrowserStatus> {
+        self.status.borrow().clone()
+    }
+
+    /// Notified whenever the status changes
+    pub fn subscribe(&self) -> watch::Receiver<Option<BrowserStatus>> {
+        self.status.subscribe()
+    }
+
+    /// Send one heartbeat, restarting the browser if it's failed often enough; returns how
+    /// long to wait before the next
+    async fn check(&self, config: &BrowserWatchdogConfig) -> Duration {
+        let mut status = self.status().unwrap_or(BrowserStatus {
+            state: BrowserState::Unknown,
+            failures: 0,
+            restarts: 0,
+            last_restart_ms: None,
+            error: None,
+        });
+
+        let error = match timeout(HEARTBEAT_TIMEOUT, self.cdp.evaluate(HEARTBEAT)).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(format!("{:#}", e)),
+            Err(_) => Some(format!("No heartbeat reply within {:?}", HEARTBEAT_TIMEOUT)),
+        };
+
+        let Some(error) = error else {
+            if status.state != BrowserState::Ok && status.state != BrowserState::Unknown {
+                tracing::info!("Browser is answering again");
+            }
+            status.state = BrowserState::Ok;
+            status.failures = 0;
+            status.error = None;
+            self.publish(Some(status));
+            return Duration::from_secs(config.interval);
+        };
+
+        status.failures += 1;
+        tracing::warn!(
+            "Browser heartbeat failed ({} of {}): {}",
+            status.failures,
+            config.failures,
+            error
+        );
+        status.error = Some(error);
+        if status.failures < config.failures {
+            status.state = BrowserState::Unresponsive;
+            self.publish(Some(status));
+            return Duration::from_secs(config.interval);
+        }
+
+        tracing::error!("Browser unresponsive, restarting it with {:?}", config.restart_command);
+        if let Err(e) = restart(&config.restart_command).await {
+            tracing::error!("Browser restart failed: {:#}", e);
+            status.error = Some(format!("Restart failed: {:#}", e));
+        }
+        status.state = BrowserState::Restarting;
+        status.failures = 0;
+        status.restarts += 1;
+        status.last_restart_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .ok();
+        self.publish(Some(status));
+        Duration::from_secs(config.restart_grace)
+    }
+
+    fn publish(&self, status: Option<BrowserStatus>) {
+        self.status.send_if_modified(|current| {
+            let changed = *current != status;
+            *current = status;
+            changed
+        });
+    }
+}
+
+/// Run `command` (program and arguments) and wait for it to exit successfully
+async fn restart(command: &[String]) -> anyhow::Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        anyhow::bail!("No restart command");
+    };
+    let status = timeout(
+        RESTART_TIMEOUT,
+        Command::new(program).args(args).kill_on_drop(true).status(),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("{} still running after {:?}", program, RESTART_TIMEOUT))??;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
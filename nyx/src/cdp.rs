@@ -1,126 +1,531 @@
 use anyhow::{bail, Context, Result};
-use futures_util::{SinkExt, StreamExt};
-use serde::Deserialize;
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
 const CDP_ADDR: &str = "127.0.0.1:9222";
 
+/// Longest a CDP command may take before the session is given up on
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events buffered per subscriber before the oldest are dropped (a page load brings a burst of
+/// network events)
+const EVENT_BUFFER: usize = 1024;
+
+/// Domains enabled on every new session, for their console and log events and page loads
+const EVENT_DOMAINS: &[&str] = &["Runtime.enable", "Log.enable", "Network.enable"];
+
 #[derive(Debug, Deserialize)]
 struct CdpTarget {
+    id: String,
     #[serde(rename = "type")]
     target_type: String,
     #[serde(rename = "webSocketDebuggerUrl")]
     web_socket_debugger_url: Option<String>,
     url: String,
+    #[serde(default)]
+    title: String,
 }
 
-/// Navigate Chrome to a new URL via the Chrome DevTools Protocol.
-pub async fn navigate(url: &str) -> Result<()> {
-    let target = discover_page_target().await?;
+/// Synthetic touch sent by `dispatch_touch`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchAction {
+    /// Start and end at the same point
+    #[default]
+    Tap,
+    Start,
+    Move,
+    End,
+    Cancel,
+}
 
-    let ws_url = target
-        .web_socket_debugger_url
-        .context("Page target has no WebSocket debugger URL")?;
+/// Synthetic key event sent by `dispatch_key`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    /// Down then up
+    #[default]
+    Press,
+    Down,
+    Up,
+}
 
-    tracing::debug!("Connecting to CDP WebSocket: {}", ws_url);
+/// A Chrome tab, as listed by `list_tabs`
+#[derive(Debug, Clone, Serialize)]
+pub struct Tab {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    /// Navigation, reloads and scripts go to this tab
+    pub active: bool,
+}
 
-    let (mut ws, _) = connect_async(&ws_url)
-        .await
-        .with_context(|| format!("Failed to connect to Chrome CDP WebSocket at {}", ws_url))?;
+/// Event Chrome sent on the session, e.g. `Runtime.consoleAPICalled`
+#[derive(Debug, Clone)]
+pub struct CdpEvent {
+    pub method: String,
+    pub params: Value,
+}
 
-    let cmd = serde_json::json!({
-        "id": 1,
-        "method": "Page.navigate",
-        "params": { "url": url }
-    });
+type CdpSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
-    ws.send(Message::Text(cmd.to_string()))
-        .await
-        .context("Failed to send navigate command")?;
+/// Open debugger WebSocket to one tab
+struct Session {
+    target_id: String,
+    ws: CdpSocket,
+}
 
-    // Wait for the response
-    if let Some(Ok(Message::Text(response))) = ws.next().await {
-        let resp: serde_json::Value = serde_json::from_str(&response)?;
-        if let Some(error) = resp.get("error") {
-            bail!("CDP navigate error: {}", error);
+struct State {
+    session: Option<Session>,
+    /// Tab picked by `new_tab`/`activate_tab`; the first page when unset or gone
+    tab: Option<String>,
+    next_id: u64,
+}
+
+/// Chrome DevTools Protocol client for the kiosk's browser
+///
+/// Keeps one WebSocket session to the active tab open between commands. When Chrome
+/// restarts or the tab goes away the session is dropped, and the next command finds the tab
+/// again and reconnects. Events the session receives go to `subscribe_events()`.
+#[derive(Clone)]
+pub struct CdpClient {
+    addr: String,
+    state: Arc<Mutex<State>>,
+    events: broadcast::Sender<CdpEvent>,
+}
+
+impl CdpClient {
+    /// Client for `127.0.0.1:9222`; `NYX_CDP_ADDR` replaces it (used by `e2e/`)
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            addr: std::env::var("NYX_CDP_ADDR").unwrap_or_else(|_| CDP_ADDR.to_string()),
+            state: Arc::new(Mutex::new(State {
+                session: None,
+                tab: None,
+                next_id: 0,
+            })),
+            events,
         }
     }
 
-    ws.close(None).await.ok();
-    Ok(())
-}
+    /// Events from the active tab (`Runtime` and `Log` domains)
+    pub fn subscribe_events(&self) -> broadcast::Receiver<CdpEvent> {
+        self.events.subscribe()
+    }
 
-/// Get the current URL of the Chrome page target.
-pub async fn get_current_url() -> Result<String> {
-    let target = discover_page_target().await?;
-    Ok(target.url)
-}
+    /// Hand on the events the session has received since the last command, opening it first
+    /// if there isn't one; events are otherwise only read while a command waits for its reply
+    pub async fn pump_events(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let mut session = match state.session.take() {
+            Some(session) => session,
+            None => self.open(&mut state).await?,
+        };
 
-/// Discover the first page-type target from Chrome's debug endpoint.
-fn discover_page_target() -> impl std::future::Future<Output = Result<CdpTarget>> {
-    async {
-        let body = http_get_targets().await?;
+        loop {
+            match session.ws.next().now_or_never() {
+                None => break,
+                Some(Some(Ok(Message::Text(text)))) => {
+                    if let Ok(message) = serde_json::from_str(&text) {
+                        forward_event(&self.events, message);
+                    }
+                }
+                Some(Some(Ok(Message::Close(_))) | None) => bail!("Chrome closed the CDP connection"),
+                Some(Some(Ok(_))) => {}
+                Some(Some(Err(e))) => return Err(e).context("CDP connection failed"),
+            }
+        }
+        state.session = Some(session);
+        Ok(())
+    }
 
-        let targets: Vec<CdpTarget> =
-            serde_json::from_str(&body).context("Failed to parse CDP targets JSON")?;
+    /// Navigate the active tab to a new URL
+    pub async fn navigate(&self, url: &str) -> Result<()> {
+        self.call("Page.navigate", json!({ "url": url })).await?;
+        Ok(())
+    }
 
-        targets
-            .into_iter()
-            .find(|t| t.target_type == "page")
+    /// Reload the active tab
+    pub async fn reload(&self) -> Result<()> {
+        self.call("Page.reload", json!({})).await?;
+        Ok(())
+    }
+
+    /// Run a JavaScript expression in the active tab and return its value
+    pub async fn evaluate(&self, expression: &str) -> Result<Value> {
+        let result = self
+            .call(
+                "Runtime.evaluate",
+                json!({ "expression": expression, "returnByValue": true, "awaitPromise": true }),
+            )
+            .await?;
+        if let Some(exception) = result.get("exceptionDetails") {
+            let description = exception["exception"]["description"]
+                .as_str()
+                .or_else(|| exception["text"].as_str())
+                .unwrap_or("exception");
+            bail!("Script failed: {}", description);
+        }
+        Ok(result["result"]["value"].clone())
+    }
+
+    /// Send a synthetic touch at (`x`, `y`) CSS pixels to the active tab
+    pub async fn dispatch_touch(&self, action: TouchAction, x: f64, y: f64) -> Result<()> {
+        let events: &[&str] = match action {
+            TouchAction::Tap => &["touchStart", "touchEnd"],
+            TouchAction::Start => &["touchStart"],
+            TouchAction::Move => &["touchMove"],
+            TouchAction::End => &["touchEnd"],
+            TouchAction::Cancel => &["touchCancel"],
+        };
+        for event in events {
+            // Ending touches carry no points
+            let points = match *event {
+                "touchStart" | "touchMove" => json!([{ "x": x, "y": y }]),
+                _ => json!([]),
+            };
+            self.call("Input.dispatchTouchEvent", json!({ "type": event, "touchPoints": points }))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send a synthetic key event to the active tab; `key` is a DOM key name ("a", "Enter",
+    /// "ArrowDown", ...)
+    pub async fn dispatch_key(&self, action: KeyAction, key: &str) -> Result<()> {
+        let code = key_code(key);
+        // Printable keys type their character; Enter types a newline
+        let text = match key {
+            "Enter" => Some("\r".to_string()),
+            _ if key.chars().count() == 1 => Some(key.to_string()),
+            _ => None,
+        };
+
+        if matches!(action, KeyAction::Press | KeyAction::Down) {
+            let mut params = json!({ "type": "keyDown", "key": key });
+            if let Some(text) = &text {
+                params["text"] = json!(text);
+            }
+            if let Some(code) = code {
+                params["windowsVirtualKeyCode"] = json!(code);
+            }
+            self.call("Input.dispatchKeyEvent", params).await?;
+        }
+        if matches!(action, KeyAction::Press | KeyAction::Up) {
+            let mut params = json!({ "type": "keyUp", "key": key });
+            if let Some(code) = code {
+                params["windowsVirtualKeyCode"] = json!(code);
+            }
+            self.call("Input.dispatchKeyEvent", params).await?;
+        }
+        Ok(())
+    }
+
+    /// Capture what the active tab shows as base64 image data (`png` or `jpeg`, with `quality`
+    /// 0-100 for jpeg)
+    pub async fn capture_screenshot(&self, format: &str, quality: Option<u8>) -> Result<String> {
+        let mut params = json!({ "format": format });
+        if let Some(quality) = quality {
+            params["quality"] = json!(quality);
+        }
+        let result = self.call("Page.captureScreenshot", params).await?;
+        result["data"]
+            .as_str()
+            .map(str::to_string)
+            .context("Screenshot reply has no image data")
+    }
+
+    /// Get the current URL of the active tab
+    pub async fn get_current_url(&self) -> Result<String> {
+        let tabs = self.list_tabs().await?;
+        tabs.into_iter()
+            .find(|tab| tab.active)
+            .map(|tab| tab.url)
             .context("No page target found — is Chrome running?")
     }
-}
 
-/// Raw HTTP GET to Chrome's /json endpoint. No extra dependencies needed
-/// since we're only ever talking to localhost.
-async fn http_get_targets() -> Result<String> {
-    let stream = TcpStream::connect(CDP_ADDR).await.context(
-        "Failed to connect to Chrome remote debugging port. \
-         Is Chrome running with --remote-debugging-port=9222?",
-    )?;
+    /// Every open tab
+    pub async fn list_tabs(&self) -> Result<Vec<Tab>> {
+        let pages = self.pages().await?;
+        let state = self.state.lock().await;
+        let active = active_page(&pages, state.tab.as_deref()).map(|page| page.id.clone());
+        Ok(pages
+            .into_iter()
+            .map(|page| Tab {
+                active: Some(&page.id) == active.as_ref(),
+                id: page.id,
+                url: page.url,
+                title: page.title,
+            })
+            .collect())
+    }
 
-    let (read_half, mut write_half) = tokio::io::split(stream);
+    /// Open a tab at `url` and make it the active one
+    pub async fn new_tab(&self, url: &str) -> Result<Tab> {
+        // Chrome 111+ only accepts PUT here
+        let body = self.http("PUT", &format!("/json/new?{}", url)).await?;
+        let page: CdpTarget = serde_json::from_str(&body).context("Failed to parse new tab JSON")?;
+        self.select(&page.id).await;
+        Ok(Tab {
+            id: page.id,
+            url: page.url,
+            title: page.title,
+            active: true,
+        })
+    }
 
-    write_half
-        .write_all(b"GET /json HTTP/1.1\r\nHost: 127.0.0.1:9222\r\nConnection: close\r\n\r\n")
-        .await?;
+    /// Close a tab; if it was the active one, the first remaining page takes over
+    pub async fn close_tab(&self, id: &str) -> Result<()> {
+        self.http("GET", &format!("/json/close/{}", id)).await?;
+        let mut state = self.state.lock().await;
+        if state.tab.as_deref() == Some(id) {
+            state.tab = None;
+        }
+        if state.session.as_ref().is_some_and(|session| session.target_id == id) {
+            state.session = None;
+        }
+        Ok(())
+    }
 
-    let mut reader = BufReader::new(read_half);
-    let mut content_length: Option<usize> = None;
+    /// Bring a tab to the front and make it the active one
+    pub async fn activate_tab(&self, id: &str) -> Result<()> {
+        self.http("GET", &format!("/json/activate/{}", id)).await?;
+        self.select(id).await;
+        Ok(())
+    }
 
-    // Read headers line-by-line until the blank line.
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).await?;
-        if line == "\r\n" || line.is_empty() {
-            break;
+    /// Send further commands to tab `id`
+    async fn select(&self, id: &str) {
+        let mut state = self.state.lock().await;
+        state.tab = Some(id.to_string());
+        if state.session.as_ref().is_some_and(|session| session.target_id != id) {
+            state.session = None;
+        }
+    }
+
+    /// Send one command to the active tab and return its result, reconnecting once if the
+    /// session has gone
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let mut state = self.state.lock().await;
+        let mut reconnected = false;
+
+        loop {
+            // Taken out while in use, so a failed session is simply not put back
+            let mut session = match state.session.take() {
+                Some(session) => session,
+                None => {
+                    reconnected = true;
+                    self.open(&mut state).await?
+                }
+            };
+
+            state.next_id += 1;
+            let exchanged = exchange(&mut session.ws, state.next_id, method, &params, &self.events);
+            let reply = match timeout(CALL_TIMEOUT, exchanged).await {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(e)) if !reconnected => {
+                    tracing::info!("CDP session lost ({:#}), reconnecting", e);
+                    continue;
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => bail!("CDP {} timed out", method),
+            };
+            state.session = Some(session);
+
+            if let Some(error) = reply.get("error") {
+                bail!("CDP {} error: {}", method, error);
+            }
+            return Ok(reply.get("result").cloned().unwrap_or(Value::Null));
         }
-        if line.to_ascii_lowercase().starts_with("content-length:") {
-            content_length = line.split(':').nth(1).and_then(|v| v.trim().parse().ok());
+    }
+
+    /// Open a session to the active tab with its event domains enabled
+    async fn open(&self, state: &mut State) -> Result<Session> {
+        let mut session = self.connect(&mut state.tab).await?;
+        let params = json!({});
+        for method in EVENT_DOMAINS {
+            state.next_id += 1;
+            let exchanged = exchange(&mut session.ws, state.next_id, method, &params, &self.events);
+            match timeout(CALL_TIMEOUT, exchanged).await {
+                Ok(reply) => reply?,
+                Err(_) => bail!("CDP {} timed out", method),
+            };
         }
+        Ok(session)
     }
 
-    // Read exactly Content-Length bytes so we don't block waiting for EOF.
-    let body = match content_length {
-        Some(len) => {
-            let mut buf = vec![0u8; len];
-            reader.read_exact(&mut buf).await?;
-            buf
+    /// Open a session to `tab`, or to the first page if it's unset or gone (clearing it)
+    async fn connect(&self, tab: &mut Option<String>) -> Result<Session> {
+        let pages = self.pages().await?;
+        let page = active_page(&pages, tab.as_deref()).context("No page target found — is Chrome running?")?;
+        if tab.as_deref() != Some(page.id.as_str()) {
+            *tab = None;
         }
-        None => {
-            // Fallback: read to end with a timeout.
-            let mut buf = Vec::new();
-            tokio::time::timeout(
-                std::time::Duration::from_secs(1),
-                reader.read_to_end(&mut buf),
-            )
+
+        let ws_url = page
+            .web_socket_debugger_url
+            .as_deref()
+            .context("Page target has no WebSocket debugger URL")?;
+
+        tracing::debug!("Connecting to CDP WebSocket: {}", ws_url);
+
+        let (ws, _) = connect_async(ws_url)
             .await
-            .context("Timeout reading from Chrome debug port")??;
-            buf
+            .with_context(|| format!("Failed to connect to Chrome CDP WebSocket at {}", ws_url))?;
+        Ok(Session {
+            target_id: page.id.clone(),
+            ws,
+        })
+    }
+
+    /// Page-type targets from Chrome's debug endpoint
+    async fn pages(&self) -> Result<Vec<CdpTarget>> {
+        let body = self.http("GET", "/json/list").await?;
+
+        let targets: Vec<CdpTarget> = serde_json::from_str(&body).context("Failed to parse CDP targets JSON")?;
+        Ok(targets.into_iter().filter(|t| t.target_type == "page").collect())
+    }
+
+    /// Raw HTTP request to Chrome's debug endpoint. No extra dependencies needed since we're
+    /// only ever talking to localhost.
+    async fn http(&self, method: &str, path: &str) -> Result<String> {
+        let stream = TcpStream::connect(&self.addr).await.context(
+            "Failed to connect to Chrome remote debugging port. \
+             Is Chrome running with --remote-debugging-port=9222?",
+        )?;
+
+        let (read_half, mut write_half) = tokio::io::split(stream);
+
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            method, path, self.addr
+        );
+        write_half.write_all(request.as_bytes()).await?;
+
+        let mut reader = BufReader::new(read_half);
+        let mut content_length: Option<usize> = None;
+
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).await?;
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .with_context(|| format!("Malformed reply from Chrome debug port: {:?}", status_line))?;
+
+        // Read headers line-by-line until the blank line.
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await?;
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            if line.to_ascii_lowercase().starts_with("content-length:") {
+                content_length = line.split(':').nth(1).and_then(|v| v.trim().parse().ok());
+            }
+        }
+
+        // Read exactly Content-Length bytes so we don't block waiting for EOF.
+        let body = match content_length {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                reader.read_exact(&mut buf).await?;
+                buf
+            }
+            None => {
+                // Fallback: read to end with a timeout.
+                let mut buf = Vec::new();
+                timeout(Duration::from_secs(1), reader.read_to_end(&mut buf))
+                    .await
+                    .context("Timeout reading from Chrome debug port")??;
+                buf
+            }
+        };
+
+        let body = String::from_utf8(body).context("Non-UTF8 response from Chrome debug port")?;
+        if !(200..300).contains(&status) {
+            bail!("Chrome debug port answered {} {}: {}", method, path, body.trim());
         }
+        Ok(body)
+    }
+}
+
+/// Windows virtual key code Chrome needs to act on a non-printable key
+fn key_code(key: &str) -> Option<u32> {
+    let code = match key {
+        "Backspace" => 8,
+        "Tab" => 9,
+        "Enter" => 13,
+        "Escape" => 27,
+        " " => 32,
+        "PageUp" => 33,
+        "PageDown" => 34,
+        "End" => 35,
+        "Home" => 36,
+        "ArrowLeft" => 37,
+        "ArrowUp" => 38,
+        "ArrowRight" => 39,
+        "ArrowDown" => 40,
+        "Delete" => 46,
+        _ => return None,
     };
+    Some(code)
+}
+
+/// The page `tab` names, or the first one
+fn active_page<'a>(pages: &'a [CdpTarget], tab: Option<&str>) -> Option<&'a CdpTarget> {
+    tab.and_then(|id| pages.iter().find(|page| page.id == id))
+        .or_else(|| pages.first())
+}
 
-    String::from_utf8(body).context("Non-UTF8 response from Chrome debug port")
+/// Send command `id` and wait for its reply, handing any events in between to `events`
+async fn exchange(
+    ws: &mut CdpSocket,
+    id: u64,
+    method: &str,
+    params: &Value,
+    events: &broadcast::Sender<CdpEvent>,
+) -> Result<Value> {
+    let cmd = json!({
+        "id": id,
+        "method": method,
+        "params": params
+    });
+
+    ws.send(Message::Text(cmd.to_string()))
+        .await
+        .with_context(|| format!("Failed to send {} command", method))?;
+
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(response))) => {
+                let resp: Value = serde_json::from_str(&response)?;
+                if resp.get("id").and_then(Value::as_u64) == Some(id) {
+                    return Ok(resp);
+                }
+                forward_event(events, resp);
+            }
+            Some(Ok(Message::Close(_))) | None => bail!("Chrome closed the CDP connection"),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e).context("CDP connection failed"),
+        }
+    }
+}
+
+/// Pass `message` on to `events` if it's an event rather than a reply
+fn forward_event(events: &broadcast::Sender<CdpEvent>, mut message: Value) {
+    if let Some(method) = message.get("method").and_then(Value::as_str) {
+        let _ = events.send(CdpEvent {
+            method: method.to_string(),
+            params: message["params"].take(),
+        });
+    }
 }
@@ -1,8 +1,21 @@
-use anyhow::{Context, Result};
-use directories::ProjectDirs;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shq_audit::AuditConfig;
+use shq_auth::{token_matches, AuthConfig};
+use shq_config::{migrate, BackupConfig, ConfigEntry, Configuration, Migration};
+use shq_i18n::I18nConfig;
+use shq_logging::LoggingConfig;
+use shq_mdns::MdnsConfig;
+use shq_mqtt::MqttConfig;
+use shq_ratelimit::RateLimitConfig;
+use shq_scene::SceneConfig;
+use shq_scheduler::SchedulerConfig;
+use shq_ws::{KeepAliveConfig, TlsConfig};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use tokio::fs;
+use tokio::sync::watch;
+use tokio::time::Duration;
 
 use crate::messages::AutoDimConfig;
 
@@ -14,6 +27,11 @@ pub struct WebSocketConfig {
     pub host: String,
     /// Port to listen on
     pub port: u16,
+    /// Serve `wss://` with this certificate and key (plain `ws://` if absent); read at startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Server pings, and how long a client gets to answer before it's dropped; read at startup
+    pub keep_alive: KeepAliveConfig,
 }
 
 impl Default for WebSocketConfig {
@@ -21,116 +39,840 @@ impl Default for WebSocketConfig {
         Self {
             host: "0.0.0.0".to_string(),
             port: 8765,
+            tls: None,
+            keep_alive: KeepAliveConfig::default(),
         }
     }
 }
 
-/// Application configuration stored in ~/.config/shqd/config.json
+/// Plain HTTP API server configuration (`http`)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
-pub struct Config {
-    pub auto_dim: AutoDimConfig,
-    pub websocket: WebSocketConfig,
+pub struct HttpConfig {
+    /// Host address to bind to
+    pub host: String,
+    /// Port to listen on (separate from the WebSocket port)
+    pub port: u16,
 }
 
-impl Default for Config {
+impl Default for HttpConfig {
     fn default() -> Self {
         Self {
-            auto_dim: AutoDimConfig::default(),
-            websocket: WebSocketConfig::default(),
+            host: "0.0.0.0".to_string(),
+            port: 8768,
         }
     }
 }
 
-/// Configuration manager for persistent storage
-pub struct ConfigManager {
-    config_path: PathBuf,
-    config: Config,
+/// Local diagnostics page shown while the kiosk URL can't be loaded (`fallback`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    /// Port the page is served on, on localhost only
+    pub port: u16,
+    /// Seconds between checks of whether the original URL is reachable again
+    pub interval: u64,
+    /// Treat HTTP error responses (4xx and 5xx) like network failures
+    pub http_errors: bool,
 }
 
-impl ConfigManager {
-    /// Create a new configuration manager and load config from disk
-    pub async fn new() -> Result<Self> {
-        let config_path = Self::get_config_path()?;
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            port: 8769,
+            interval: 10,
+            http_errors: true,
+        }
+    }
+}
+
+/// Display command run by a schedule job or scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayAction {
+    Wake,
+    Sleep,
+    /// Point Chrome at a URL
+    Navigate { url: String },
+    /// Turn night mode on or off, optionally changing its colour temperature
+    NightMode {
+        enabled: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<u32>,
+    },
+    /// Switch to one of `profiles`
+    Profile { name: String },
+}
+
+/// Named kiosk setup switched as one by `set_profile` or a schedule (`profiles`), e.g. a
+/// daytime dashboard and a night clock; anything left out stays as it is
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Page to show
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Brightness (0-255) every display is set to; 0 puts them to sleep
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// Replaces the shared `auto_dim`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_dim: Option<AutoDimConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub night_mode: Option<ProfileNightMode>,
+}
+
+/// Night mode as a profile sets it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProfileNightMode {
+    pub enabled: bool,
+    /// Kelvin; the configured one if left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<u32>,
+}
+
+impl ProfileConfig {
+    fn validate(&self, name: &str) -> Result<()> {
+        if self.url.as_deref().is_some_and(str::is_empty) {
+            anyhow::bail!("profiles.{}.url must not be empty", name);
+        }
+        if self.auto_dim.as_ref().is_some_and(|auto_dim| auto_dim.bright_level == 0) {
+            anyhow::bail!("profiles.{}.auto_dim.bright_level must be greater than 0", name);
+        }
+        let temperature = self.night_mode.and_then(|night_mode| night_mode.temperature);
+        if temperature.is_some_and(|t| !NIGHT_MODE_TEMPERATURES.contains(&t)) {
+            anyhow::bail!(
+                "profiles.{}.night_mode.temperature must be between {} and {}",
+                name,
+                NIGHT_MODE_TEMPERATURES.start(),
+                NIGHT_MODE_TEMPERATURES.end()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How 0-255 brightness maps onto a backlight's raw values (`brightness_curve`)
+///
+/// Brightness `b` above 0 becomes `min_raw + (b / 255)^gamma * (max_brightness - min_raw)`, or
+/// is interpolated between `points` when there are any; 0 is always off. The default is the
+/// plain linear scale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrightnessCurve {
+    /// Exponent applied to brightness: 1 is linear, around 2.2 looks even to the eye
+    pub gamma: f64,
+    /// Lowest raw value written for a brightness above 0, for panels that go dark below it
+    pub min_raw: u32,
+    /// `[brightness, raw]` pairs to interpolate between instead of `gamma`, brightness ascending
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<(u8, u32)>,
+}
+
+impl Default for BrightnessCurve {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            min_raw: 0,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl BrightnessCurve {
+    fn validate(&self, section: &str) -> Result<()> {
+        if !self.gamma.is_finite() || self.gamma <= 0.0 {
+            anyhow::bail!("{}.gamma must be greater than 0", section);
+        }
+        for pair in self.points.windows(2) {
+            let ((b1, raw1), (b2, raw2)) = (pair[0], pair[1]);
+            if b2 <= b1 || raw2 < raw1 {
+                anyhow::bail!(
+                    "{}.points must rise with brightness ([{}, {}] is followed by [{}, {}])",
+                    section,
+                    b1,
+                    raw1,
+                    b2,
+                    raw2
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings for one display, by backlight device name (`displays`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Replaces the top-level `auto_dim` for this display
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_dim: Option<AutoDimConfig>,
+    /// Replaces the top-level `brightness_curve` for this display
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness_curve: Option<BrightnessCurve>,
+}
+
+/// What the screensaver does to the page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreensaverMode {
+    /// Show `url`
+    Url,
+    /// Show a drifting clock on black
+    #[default]
+    Clock,
+    /// Keep the page but move it a few pixels every `shift_interval`
+    PixelShift,
+}
+
+/// Burn-in protection for panels left on one page (`screensaver`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreensaverConfig {
+    /// Seconds without a touch before the screensaver starts; 0 turns it off
+    pub idle_time: u64,
+    pub mode: ScreensaverMode,
+    /// Page shown in `url` mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// How far (px) and how often (seconds) `pixel_shift` moves the page
+    pub shift_pixels: u32,
+    pub shift_interval: u64,
+}
+
+impl Default for ScreensaverConfig {
+    fn default() -> Self {
+        Self {
+            idle_time: 0,
+            mode: ScreensaverMode::default(),
+            url: None,
+            shift_pixels: 4,
+            shift_interval: 60,
+        }
+    }
+}
+
+impl ScreensaverConfig {
+    fn validate(&self) -> Result<()> {
+        if self.mode == ScreensaverMode::Url && self.url.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!("screensaver.url is required in url mode");
+        }
+        if self.mode == ScreensaverMode::PixelShift && (self.shift_pixels == 0 || self.shift_interval == 0) {
+            anyhow::bail!("screensaver.shift_pixels and shift_interval must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// Restarting the kiosk browser when it stops answering (`browser_watchdog`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowserWatchdogConfig {
+    pub enabled: bool,
+    /// Seconds between heartbeats
+    pub interval: u64,
+    /// Heartbeats failed in a row before the browser is restarted
+    pub failures: u32,
+    /// Program and arguments that restart the browser, e.g. `["systemctl", "--user", "restart", "kiosk"]`
+    pub restart_command: Vec<String>,
+    /// Seconds the browser gets to come back before heartbeats resume
+    pub restart_grace: u64,
+}
+
+impl Default for BrowserWatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 30,
+            failures: 3,
+            restart_command: Vec::new(),
+            restart_grace: 60,
+        }
+    }
+}
 
-        // Ensure config directory exists
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)
-                .await
-                .context("Failed to create config directory")?;
+impl BrowserWatchdogConfig {
+    fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
         }
+        if self.restart_command.is_empty() {
+            anyhow::bail!("browser_watchdog.restart_command is required when the watchdog is enabled");
+        }
+        if self.interval == 0 || self.failures == 0 {
+            anyhow::bail!("browser_watchdog.interval and failures must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// How display power is switched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerBackend {
+    /// Backlight only
+    #[default]
+    None,
+    /// `wlr-randr --output <output> --on|--off` (Wayland compositors such as labwc)
+    WlrRandr,
+    /// `vcgencmd display_power 0|1 [output]` (Raspberry Pi firmware)
+    Vcgencmd,
+}
+
+/// Display power control for monitors the backlight can't turn off (`power`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub backend: PowerBackend,
+    /// wlr-randr output name (required), or vcgencmd display number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Path to the backend's program, if it isn't on `PATH`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// Power the output off when brightness reaches 0 and back on above it
+    pub follow_brightness: bool,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            backend: PowerBackend::None,
+            output: None,
+            program: None,
+            follow_brightness: true,
+        }
+    }
+}
 
-        // Load or create default config
-        let config = Self::load_config(&config_path).await?;
+impl PowerConfig {
+    /// The program the backend runs
+    pub fn program(&self) -> &str {
+        match (&self.program, self.backend) {
+            (Some(program), _) => program,
+            (None, PowerBackend::WlrRandr) => "wlr-randr",
+            (None, _) => "vcgencmd",
+        }
+    }
 
-        Ok(Self {
-            config_path,
-            config,
-        })
+    fn validate(&self) -> Result<()> {
+        if self.backend == PowerBackend::WlrRandr && self.output.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!("power.output is required for the wlr_randr backend");
+        }
+        Ok(())
     }
+}
+
+/// Where the kiosk is, for turning night mode on at sunset and off at sunrise
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SunSchedule {
+    pub latitude: f64,
+    pub longitude: f64,
+}
 
-    /// Get the XDG-compliant config path: ~/.config/shqd/config.json
-    fn get_config_path() -> Result<PathBuf> {
-        let proj_dirs = ProjectDirs::from("", "", "shqd")
-            .context("Failed to determine config directory")?;
+/// Blue-light reduction on the kiosk page (`night_mode`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightModeConfig {
+    pub enabled: bool,
+    /// Colour temperature in kelvin; 6500 is neutral, lower is warmer
+    pub temperature: u32,
+    /// Switch `enabled` at sunset and sunrise here; `set_night_mode` still overrides it until
+    /// the next one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sun: Option<SunSchedule>,
+}
 
-        Ok(proj_dirs.config_dir().join("config.json"))
+impl Default for NightModeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            temperature: 3400,
+            sun: None,
+        }
     }
+}
 
-    /// Load config from disk, or create default if it doesn't exist
-    async fn load_config(path: &PathBuf) -> Result<Config> {
-        if path.exists() {
-            let contents = fs::read_to_string(path)
-                .await
-                .context("Failed to read config file")?;
+/// Colour temperatures night mode accepts, in kelvin
+pub const NIGHT_MODE_TEMPERATURES: std::ops::RangeInclusive<u32> = 1000..=6500;
 
-            let config: Config = serde_json::from_str(&contents)
-                .context("Failed to parse config file")?;
+impl NightModeConfig {
+    fn validate(&self) -> Result<()> {
+        if !NIGHT_MODE_TEMPERATURES.contains(&self.temperature) {
+            anyhow::bail!(
+                "night_mode.temperature must be between {} and {}",
+                NIGHT_MODE_TEMPERATURES.start(),
+                NIGHT_MODE_TEMPERATURES.end()
+            );
+        }
+        if let Some(sun) = &self.sun {
+            if !(-90.0..=90.0).contains(&sun.latitude) || !(-180.0..=180.0).contains(&sun.longitude) {
+                anyhow::bail!("night_mode.sun needs a latitude of -90 to 90 and a longitude of -180 to 180");
+            }
+        }
+        Ok(())
+    }
+}
 
-            tracing::info!("Loaded configuration from {:?}", path);
-            Ok(config)
-        } else {
-            tracing::info!("Config file not found, creating default at {:?}", path);
-            let config = Config::default();
+/// Input devices watched for touches (`touch`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TouchConfig {
+    /// Devices to watch, e.g. under `/dev/input/by-id/`; empty watches every touchscreen found
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<PathBuf>,
+    /// Least seconds between `touch_activity` events while the panel keeps being touched
+    pub activity_debounce: u64,
+    /// Seconds without a touch before `touch_activity` reports idle
+    pub idle_after: u64,
+}
 
-            // Save default config
-            let json = serde_json::to_string_pretty(&config)
-                .context("Failed to serialize default config")?;
-            fs::write(path, json)
-                .await
-                .context("Failed to write default config")?;
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            devices: Vec::new(),
+            activity_debounce: 5,
+            idle_after: 60,
+        }
+    }
+}
 
-            Ok(config)
+impl TouchConfig {
+    fn validate(&self) -> Result<()> {
+        if self.activity_debounce == 0 || self.idle_after == 0 {
+            anyhow::bail!("touch.activity_debounce and idle_after must be greater than 0");
         }
+        Ok(())
     }
+}
 
-    /// Save config to disk
-    async fn save(&self) -> Result<()> {
-        let json = serde_json::to_string_pretty(&self.config)
-            .context("Failed to serialize config")?;
+/// Input a presence sensor is read from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum PresenceInput {
+    /// File holding "0" or "1", e.g. a PIR on a sysfs GPIO like `/sys/class/gpio/gpio17/value`;
+    /// someone is there while it reads 1
+    Gpio { path: PathBuf },
+    /// Input device, e.g. a proximity sensor; any event means someone is there
+    Evdev { device: PathBuf },
+}
 
-        fs::write(&self.config_path, json)
-            .await
-            .context("Failed to write config file")?;
+/// Presence sensor that wakes the display as someone approaches (`presence`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    /// No input means no presence sensor (the default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<PresenceInput>,
+    /// Flip a `gpio` reading, for sensors that pull the line low on detection
+    pub invert: bool,
+    /// Seconds someone still counts as there after the sensor last saw them
+    pub hold_time: u64,
+    /// Wake the displays when someone arrives
+    pub wake: bool,
+    /// Count presence as activity for auto-dim, like a touch (a dimmed display brightens too)
+    pub keep_awake: bool,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            input: None,
+            invert: false,
+            hold_time: 30,
+            wake: true,
+            keep_awake: true,
+        }
+    }
+}
+
+impl PresenceConfig {
+    fn validate(&self) -> Result<()> {
+        let path = match &self.input {
+            None => return Ok(()),
+            Some(PresenceInput::Gpio { path }) => path,
+            Some(PresenceInput::Evdev { device }) => device,
+        };
+        if path.as_os_str().is_empty() {
+            anyhow::bail!("presence.input path must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Remote scripting and synthetic input on the kiosk page (`automation`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationConfig {
+    /// Allow `evaluate_js`, `dispatch_touch` and `dispatch_key` (off by default)
+    pub enabled: bool,
+    /// Token clients must present (none = any client may automate the page)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl AutomationConfig {
+    /// Check whether a client presenting `token` may script the page
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        if !self.enabled {
+            anyhow::bail!("Automation is disabled");
+        }
+
+        match &self.token {
+            Some(expected) if !token_matches(expected, token) => anyhow::bail!("Invalid automation token"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Application configuration stored in ~/.config/shqd/config.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Schema version, maintained by `shq-config` migrations
+    pub config_version: u32,
+    /// Auto-dim for every display without its own in `displays`
+    pub auto_dim: AutoDimConfig,
+    /// Per-display settings for kiosks with more than one panel
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub displays: BTreeMap<String, DisplayConfig>,
+    /// Brightness to backlight mapping for every display without its own in `displays`
+    pub brightness_curve: BrightnessCurve,
+    /// Burn-in protection once the panel has been idle a while
+    pub screensaver: ScreensaverConfig,
+    /// Heartbeats to Chrome, restarting it when it stops answering
+    pub browser_watchdog: BrowserWatchdogConfig,
+    /// Remote scripting and synthetic input
+    pub automation: AutomationConfig,
+    /// Which input devices count as touches
+    pub touch: TouchConfig,
+    /// PIR or proximity sensor that wakes the display and holds off auto-dim
+    pub presence: PresenceConfig,
+    /// DPMS power control through wlr-randr or vcgencmd
+    pub power: PowerConfig,
+    /// Warmer colours in the evening
+    pub night_mode: NightModeConfig,
+    /// Kiosk setups switched by `set_profile` or a schedule, by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    pub websocket: WebSocketConfig,
+    /// Client tokens and their roles for the WebSocket API (off by default)
+    pub auth: AuthConfig,
+    pub logging: LoggingConfig,
+    /// Scheduled display on/off and navigation
+    pub schedule: SchedulerConfig<DisplayAction>,
+    /// Named bundles of display and peer commands
+    pub scene: SceneConfig<DisplayAction>,
+    /// Per-client request limits for the WebSocket API
+    pub rate_limit: RateLimitConfig,
+    /// Remote config export/import
+    pub backup: BackupConfig,
+    /// Language of messages sent to clients
+    pub i18n: I18nConfig,
+    /// Audit log of connections, privileged commands and config changes
+    pub audit: AuditConfig,
+    /// `_nyx._tcp` advertisement for panels and peer services (on by default)
+    pub mdns: MdnsConfig,
+    /// Home Assistant MQTT discovery (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    /// REST endpoints for scripts and curl (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpConfig>,
+    /// Diagnostics page in place of an unreachable kiosk URL (disabled if absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<FallbackConfig>,
+}
+
+impl Configuration for Config {
+    const VERSION: u32 = 1;
+
+    fn migrations() -> Vec<Migration> {
+        vec![migrate_auto_dim_timeout]
+    }
 
-        tracing::debug!("Saved configuration to {:?}", self.config_path);
+    fn validate(&self) -> Result<()> {
+        if self.auto_dim.bright_level == 0 {
+            anyhow::bail!("auto_dim.bright_level must be greater than 0 (use dim_level for dimmed brightness)");
+        }
+        for (id, auto_dim) in self.display_auto_dim() {
+            if auto_dim.bright_level == 0 {
+                anyhow::bail!("displays.{}.auto_dim.bright_level must be greater than 0", id);
+            }
+        }
+        self.brightness_curve.validate("brightness_curve")?;
+        for (id, display) in &self.displays {
+            if let Some(curve) = &display.brightness_curve {
+                curve.validate(&format!("displays.{}.brightness_curve", id))?;
+            }
+        }
+        self.screensaver.validate()?;
+        self.browser_watchdog.validate()?;
+        self.touch.validate()?;
+        self.presence.validate()?;
+        self.power.validate()?;
+        self.night_mode.validate()?;
+        for (name, profile) in &self.profiles {
+            profile.validate(name)?;
+        }
+        self.schedule.validate()?;
+        self.scene.validate()?;
+        self.rate_limit.validate()?;
+        self.i18n.validate()?;
+        self.mdns.validate()?;
+        self.websocket.keep_alive.validate()?;
+        self.auth.validate()?;
+        if let Some(http) = &self.http {
+            if http.port == self.websocket.port {
+                anyhow::bail!("http.port must differ from websocket.port ({})", http.port);
+            }
+        }
+        if let Some(fallback) = &self.fallback {
+            if fallback.interval == 0 {
+                anyhow::bail!("fallback.interval must be greater than 0");
+            }
+            let taken = [Some(self.websocket.port), self.http.as_ref().map(|http| http.port)];
+            if taken.contains(&Some(fallback.port)) {
+                anyhow::bail!("fallback.port must differ from websocket.port and http.port ({})", fallback.port);
+            }
+        }
         Ok(())
     }
+}
+
+impl Config {
+    /// Auto-dim configs of the displays that have their own, by display id
+    pub fn display_auto_dim(&self) -> BTreeMap<String, AutoDimConfig> {
+        self.displays
+            .iter()
+            .filter_map(|(id, display)| Some((id.clone(), display.auto_dim.clone()?)))
+            .collect()
+    }
+
+    /// Brightness curve of display `id`: its own, or the shared one
+    pub fn brightness_curve(&self, id: &str) -> BrightnessCurve {
+        self.displays
+            .get(id)
+            .and_then(|display| display.brightness_curve.clone())
+            .unwrap_or_else(|| self.brightness_curve.clone())
+    }
+}
+
+/// 0 → 1: `auto_dim.timeout_seconds` became `auto_dim_time`, `enabled: false` is now a time
+/// of 0, and `auto_off_time` was added (off by default)
+fn migrate_auto_dim_timeout(doc: &mut Value) -> Result<()> {
+    let enabled = migrate::remove(doc, "auto_dim.enabled").and_then(|v| v.as_bool());
+    migrate::rename(doc, "auto_dim.timeout_seconds", "auto_dim.auto_dim_time");
+
+    if let Some(auto_dim) = doc.get_mut("auto_dim").and_then(Value::as_object_mut) {
+        if enabled == Some(false) {
+            auto_dim.insert("auto_dim_time".to_string(), Value::from(0));
+        }
+        auto_dim
+            .entry("auto_off_time")
+            .or_insert_with(|| Value::from(0));
+    }
+    Ok(())
+}
+
+/// Configuration manager for persistent storage
+#[derive(Clone)]
+pub struct ConfigManager {
+    inner: shq_config::ConfigManager<Config>,
+}
+
+impl ConfigManager {
+    /// Create a new configuration manager and load config from disk
+    pub async fn new() -> Result<Self> {
+        let inner = shq_config::ConfigManager::load_xdg("shqd", "config.json").await?;
+        Ok(Self { inner })
+    }
 
     /// Get the current auto-dim configuration
     pub fn get_auto_dim_config(&self) -> AutoDimConfig {
-        self.config.auto_dim.clone()
+        self.inner.get().auto_dim
     }
 
-    /// Set and persist auto-dim configuration
-    pub async fn set_auto_dim_config(&mut self, config: AutoDimConfig) -> Result<()> {
-        self.config.auto_dim = config;
-        self.save().await?;
-        Ok(())
+    /// Get the brightness curve of display `id`
+    pub fn get_brightness_curve(&self, id: &str) -> BrightnessCurve {
+        self.inner.get().brightness_curve(id)
+    }
+
+    /// Get the auto-dim configs of displays that have their own
+    pub fn get_display_auto_dim_configs(&self) -> BTreeMap<String, AutoDimConfig> {
+        self.inner.get().display_auto_dim()
+    }
+
+    /// Set and persist the auto-dim configuration of `display`, or the shared one
+    pub async fn set_auto_dim_config(&self, display: Option<&str>, config: AutoDimConfig) -> Result<()> {
+        self.inner
+            .update(|c| match display {
+                Some(id) => c.displays.entry(id.to_string()).or_default().auto_dim = Some(config),
+                None => c.auto_dim = config,
+            })
+            .await
+    }
+
+    /// Get the screensaver settings
+    pub fn get_screensaver_config(&self) -> ScreensaverConfig {
+        self.inner.get().screensaver
+    }
+
+    /// Get the browser watchdog settings
+    pub fn get_browser_watchdog_config(&self) -> BrowserWatchdogConfig {
+        self.inner.get().browser_watchdog
+    }
+
+    /// Get the page automation settings
+    pub fn get_automation_config(&self) -> AutomationConfig {
+        self.inner.get().automation
+    }
+
+    /// Get the touch device settings
+    pub fn get_touch_config(&self) -> TouchConfig {
+        self.inner.get().touch
+    }
+
+    /// Get the presence sensor settings
+    pub fn get_presence_config(&self) -> PresenceConfig {
+        self.inner.get().presence
+    }
+
+    /// Get the display power settings
+    pub fn get_power_config(&self) -> PowerConfig {
+        self.inner.get().power
+    }
+
+    /// Get the night mode settings
+    pub fn get_night_mode_config(&self) -> NightModeConfig {
+        self.inner.get().night_mode
+    }
+
+    /// Turn night mode on or off and persist it, optionally with a new colour temperature
+    pub async fn set_night_mode(&self, enabled: bool, temperature: Option<u32>) -> Result<()> {
+        self.inner
+            .update(|c| {
+                c.night_mode.enabled = enabled;
+                if let Some(temperature) = temperature {
+                    c.night_mode.temperature = temperature;
+                }
+            })
+            .await
+    }
+
+    /// Get profile `name`
+    pub fn get_profile(&self, name: &str) -> Option<ProfileConfig> {
+        self.inner.get().profiles.remove(name)
     }
 
     /// Get the WebSocket configuration
     pub fn get_websocket_config(&self) -> WebSocketConfig {
-        self.config.websocket.clone()
+        self.inner.get().websocket
+    }
+
+    /// Get the logging configuration
+    pub fn get_logging_config(&self) -> LoggingConfig {
+        self.inner.get().logging
+    }
+
+    /// Get the WebSocket rate limits
+    pub fn get_rate_limit_config(&self) -> RateLimitConfig {
+        self.inner.get().rate_limit
+    }
+
+    /// Get the display schedule
+    pub fn get_schedule_config(&self) -> SchedulerConfig<DisplayAction> {
+        self.inner.get().schedule
+    }
+
+    /// Get the configured scenes
+    pub fn get_scene_config(&self) -> SceneConfig<DisplayAction> {
+        self.inner.get().scene
+    }
+
+    /// Get the mDNS advertisement settings
+    pub fn get_mdns_config(&self) -> MdnsConfig {
+        self.inner.get().mdns
+    }
+
+    /// Get the locale settings
+    pub fn get_i18n_config(&self) -> I18nConfig {
+        self.inner.get().i18n
+    }
+
+    /// Get the WebSocket client tokens and roles
+    pub fn get_auth_config(&self) -> AuthConfig {
+        self.inner.get().auth
+    }
+
+    /// Get the MQTT settings (`None` if disabled)
+    pub fn get_mqtt_config(&self) -> Option<MqttConfig> {
+        self.inner.get().mqtt
+    }
+
+    /// Get the HTTP API settings (`None` if disabled)
+    pub fn get_http_config(&self) -> Option<HttpConfig> {
+        self.inner.get().http
+    }
+
+    /// Get the fallback page settings (`None` if disabled)
+    pub fn get_fallback_config(&self) -> Option<FallbackConfig> {
+        self.inner.get().fallback
+    }
+
+    /// Get the audit log settings
+    pub fn get_audit_config(&self) -> AuditConfig {
+        self.inner.get().audit
+    }
+
+    /// Get the config export/import settings
+    pub fn get_backup_config(&self) -> BackupConfig {
+        self.inner.get().backup
+    }
+
+    /// The full config as an archive entry
+    pub fn export(&self) -> Result<ConfigEntry> {
+        self.inner.export("nyx", env!("CARGO_PKG_VERSION"))
+    }
+
+    /// Replace the config with an archived one; applied through the usual reload path
+    pub async fn import(&self, entry: &ConfigEntry) -> Result<()> {
+        self.inner.import(entry).await
+    }
+
+    /// Fingerprint of the current config, recorded in crash reports
+    pub fn hash(&self) -> String {
+        self.inner.hash()
+    }
+
+    /// Where the panic hook writes crash reports (next to the config file)
+    pub fn crash_report_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("crash-report.json")
+    }
+
+    /// Where schedule last-run times are kept (next to the config file)
+    pub fn schedule_state_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("schedule-state.json")
+    }
+
+    /// Where `screenshot` saves files (next to the config file)
+    pub fn screenshot_dir(&self) -> PathBuf {
+        self.inner.path().with_file_name("screenshots")
+    }
+
+    /// Default audit log location (next to the config file)
+    pub fn audit_log_path(&self) -> PathBuf {
+        self.inner.path().with_file_name("audit.jsonl")
+    }
+
+    /// Subscribe to configuration changes (API updates and hot reloads from disk)
+    pub fn subscribe(&self) -> watch::Receiver<Config> {
+        self.inner.subscribe()
+    }
+
+    /// Start polling the config file for hand edits
+    pub fn watch(&self) {
+        self.inner.watch(Duration::from_secs(2));
     }
 }
@@ -0,0 +1,279 @@
+//! `nyx ctl` (or `nyx-ctl`, a link to nyx): command-line client for a running nyx, for shell
+//! scripts and SSH sessions.
+//!
+//! Speaks the WebSocket API like any other client. Output is human-readable unless `--json` is
+//! given; the exit status is 0 on success, 1 if the command failed and 2 on bad usage.
+
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Where nyx listens unless `--url`, `--host`/`--port` or the environment say otherwise
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 8765;
+
+/// Longest wait for nyx to accept the connection or reply to a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "\
+Usage: nyx ctl [options] <command>
+       nyx-ctl [options] <command>
+
+Commands:
+  metrics               Show the displays' state, brightness and page
+  wake                  Wake the displays
+  sleep                 Put the displays to sleep
+  brightness <0-255>    Set the brightness (0 turns the display off)
+  navigate <url>        Point the kiosk browser at a URL
+
+Options:
+  --host <host>         nyx's address (default: $NYX_HOST, or 127.0.0.1)
+  --port <port>         nyx's WebSocket port (default: $NYX_PORT, or 8765)
+  --url <url>           nyx's WebSocket URL, instead of --host and --port (default: $NYX_URL)
+  --token <token>       Auth token (default: $NYX_TOKEN)
+  --display <id>        Display to address (default: every display)
+  --json                Print nyx's replies as JSON
+";
+
+/// A display command from the command line
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Metrics,
+    Wake,
+    Sleep,
+    Brightness(u8),
+    Navigate(String),
+}
+
+impl Command {
+    fn message(&self) -> Value {
+        match self {
+            Self::Metrics => json!({"type": "get_metrics"}),
+            Self::Wake => json!({"type": "wake"}),
+            Self::Sleep => json!({"type": "sleep"}),
+            Self::Brightness(brightness) => json!({"type": "set_brightness", "brightness": brightness}),
+            Self::Navigate(url) => json!({"type": "navigate", "url": url}),
+        }
+    }
+}
+
+struct Options {
+    url: String,
+    token: Option<String>,
+    display: Option<String>,
+    json: bool,
+    command: Command,
+}
+
+/// Run `nyx ctl` with the arguments after `ctl` (or after `nyx-ctl`), returning the exit status
+pub async fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", USAGE);
+        return 0;
+    }
+
+    let options = match parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("nyx ctl: {:#}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    match execute(&options).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("nyx ctl: {:#}", e);
+            1
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Options> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let mut url = env("NYX_URL");
+    let mut host = env("NYX_HOST").unwrap_or_else(|| DEFAULT_HOST.to_string());
+    let mut port = match env("NYX_PORT") {
+        Some(port) => port.parse().context("NYX_PORT must be a port number")?,
+        None => DEFAULT_PORT,
+    };
+    let mut token = env("NYX_TOKEN");
+    let mut display = None;
+    let mut json = false;
+    let mut words = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().with_context(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--url" => url = Some(value()?),
+            "--host" => {
+                host = value()?;
+                url = None;
+            }
+            "--port" => {
+                port = value()?.parse().context("--port needs a port number")?;
+                url = None;
+            }
+            "--token" => token = Some(value()?),
+            "--display" => display = Some(value()?),
+            "--json" => json = true,
+            option if option.starts_with("--") => bail!("Unknown option {}", option),
+            word => words.push(word),
+        }
+    }
+
+    let command = match words.as_slice() {
+        ["metrics"] => Command::Metrics,
+        ["wake"] => Command::Wake,
+        ["sleep"] => Command::Sleep,
+        ["brightness", brightness] => brightness
+            .parse()
+            .map(Command::Brightness)
+            .with_context(|| format!("Invalid brightness '{}' (0 to 255)", brightness))?,
+        ["navigate", url] => Command::Navigate(url.to_string()),
+        [] => bail!("No command given"),
+        words => bail!("Unknown command '{}'", words.join(" ")),
+    };
+
+    Ok(Options {
+        url: url.unwrap_or_else(|| format!("ws://{}:{}", host, port)),
+        token,
+        display,
+        json,
+        command,
+    })
+}
+
+async fn execute(options: &Options) -> Result<()> {
+    let mut connection = Connection::open(&options.url, options.token.as_deref()).await?;
+    let mut message = options.command.message();
+    // Metrics cover every display and the browser is shared, so only display commands take one
+    if let Some(display) = &options.display {
+        if matches!(options.command, Command::Wake | Command::Sleep | Command::Brightness(_)) {
+            message["display"] = json!(display);
+        }
+    }
+    let reply = connection.request(message).await?;
+
+    match options.command {
+        _ if options.json => println!("{}", reply),
+        Command::Metrics => println!("{}", describe_metrics(&reply, options.display.as_deref())?),
+        _ => println!("{}: accepted", reply["command"].as_str().unwrap_or_default()),
+    }
+    Ok(())
+}
+
+/// A line per display (just `display`'s, if given), then the page and anything out of the ordinary
+fn describe_metrics(metrics: &Value, display: Option<&str>) -> Result<String> {
+    let displays = metrics["displays"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let mut lines = Vec::new();
+    for status in displays.iter().filter(|status| display.is_none_or(|id| status["id"] == id)) {
+        let brightness = status["brightness"].as_u64().unwrap_or_default();
+        let mut line = format!(
+            "{}: {}, brightness {} ({}%)",
+            status["id"].as_str().unwrap_or("display"),
+            if status["display_on"] == true { "on" } else { "off" },
+            brightness,
+            brightness * 100 / 255
+        );
+        if let Some(state) = status["auto_dim"]["state"].as_str() {
+            line.push_str(&format!(", {}", state));
+        }
+        lines.push(line);
+    }
+    if let Some(display) = display.filter(|_| lines.is_empty()) {
+        bail!("Unknown display '{}'", display);
+    }
+
+    if let Some(url) = metrics["url"].as_str() {
+        lines.push(format!("url: {}", url));
+    }
+    if let Some(profile) = metrics["profile"].as_str() {
+        lines.push(format!("profile: {}", profile));
+    }
+    if metrics["screensaver"] == true {
+        lines.push("screensaver: on".to_string());
+    }
+    if metrics["night_mode"]["enabled"] == true {
+        let temperature = metrics["night_mode"]["temperature"].as_u64().unwrap_or_default();
+        lines.push(format!("night mode: on ({} K)", temperature));
+    }
+    if metrics["touch_locked"] == true {
+        lines.push("touch: locked".to_string());
+    }
+    if let Some(fallback) = metrics["fallback"].as_object() {
+        lines.push(format!(
+            "fallback page: showing for {} ({})",
+            fallback["url"].as_str().unwrap_or_default(),
+            fallback["reason"].as_str().unwrap_or_default()
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// WebSocket connection to nyx
+struct Connection {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Connection {
+    async fn open(url: &str, token: Option<&str>) -> Result<Self> {
+        let mut request = url
+            .into_client_request()
+            .with_context(|| format!("Invalid URL {}", url))?;
+        if let Some(token) = token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token)).context("Invalid token")?;
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+
+        let (ws, _) = match timeout(REPLY_TIMEOUT, tokio_tungstenite::connect_async(request)).await {
+            Ok(connected) => connected.with_context(|| format!("Failed to connect to {}", url))?,
+            Err(_) => bail!("Timed out connecting to {}", url),
+        };
+        Ok(Self { ws, next_id: 1 })
+    }
+
+    /// Send `message` and return the reply to it, or its `message` as an error if nyx
+    /// refused it (`error`, a failed `response`)
+    ///
+    /// Broadcasts that arrive in the meantime are skipped.
+    async fn request(&mut self, mut message: Value) -> Result<Value> {
+        let id = format!("ctl-{}", self.next_id);
+        self.next_id += 1;
+        message["id"] = json!(id);
+        self.ws.send(Message::Text(message.to_string())).await?;
+
+        let reply = timeout(REPLY_TIMEOUT, self.reply_to(&id))
+            .await
+            .context("Timed out waiting for a reply")??;
+        let accepted = match reply["type"].as_str() {
+            Some("metrics") => true,
+            Some("response") => reply["success"] == true,
+            _ => false,
+        };
+        if !accepted {
+            bail!("{}", reply["message"].as_str().unwrap_or("nyx refused the command"));
+        }
+        Ok(reply)
+    }
+
+    async fn reply_to(&mut self, id: &str) -> Result<Value> {
+        loop {
+            let frame = self.ws.next().await.context("nyx closed the connection")??;
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            let message: Value = serde_json::from_str(&text)?;
+            if message["id"] == id {
+                return Ok(message);
+            }
+        }
+    }
+}
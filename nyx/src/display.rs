@@ -3,25 +3,143 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
 
+use crate::config::{BrightnessCurve, Config, ConfigManager};
 use crate::messages::DisplayMetrics;
+use crate::power::PowerControl;
 
-/// Display controller for hardware backlight control via sysfs
+/// Brightness assumed for a display turned on without an earlier brightness (~70%)
+const DEFAULT_BRIGHTNESS: u8 = 178;
+
+/// How long the cached backlight value is trusted before it's read back from sysfs, so
+/// changes made outside nyx show up
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Display controller for one backlight device via sysfs, or for a monitor whose only control
+/// is display power
+///
+/// The backlight value is cached: reads come from the cache, which is reconciled against sysfs
+/// every few seconds, and writes of the value already there are skipped. Brightness maps onto
+/// the backlight's scale through the display's `brightness_curve`.
 #[derive(Clone)]
 pub struct DisplayController {
+    /// Device name under `/sys/class/backlight`, e.g. `10-0045`, or the power output
+    id: String,
     inner: Arc<Mutex<DisplayControllerInner>>,
+    /// Display power, for the primary display
+    power: Option<PowerControl>,
 }
 
 struct DisplayControllerInner {
-    backlight_path: PathBuf,
-    max_brightness: u32,
+    backlight: Option<Backlight>,
+    /// Last brightness above 0, restored when the display is turned back on
     cached_brightness: u8,
 }
 
+struct Backlight {
+    path: PathBuf,
+    max_brightness: u32,
+    curve: BrightnessCurve,
+    /// Raw value last written or read; `None` until read, or after a failed write
+    raw: Option<u32>,
+    /// When `raw` was last read from sysfs
+    reconciled: Instant,
+}
+
+impl Backlight {
+    /// Device scale for a 0-255 brightness, through the curve
+    fn to_raw(&self, brightness: u8) -> u32 {
+        if brightness == 0 {
+            return 0;
+        }
+        let curve = &self.curve;
+        let min = curve.min_raw.min(self.max_brightness);
+        let span = self.max_brightness - min;
+        let raw = if !curve.points.is_empty() {
+            interpolate(&curve.points, brightness)
+        } else if curve.gamma == 1.0 {
+            min + (brightness as u32 * span) / 255
+        } else {
+            min + ((brightness as f64 / 255.0).powf(curve.gamma) * span as f64).round() as u32
+        };
+        raw.clamp(min, self.max_brightness)
+    }
+
+    /// 0-255 brightness for a device value: the lowest brightness the curve takes to it or
+    /// beyond, so a brightness never reads back higher than it was set (where several share a
+    /// raw value, it reads back as the lowest of them)
+    fn to_brightness(&self, raw: u32) -> u8 {
+        if self.curve == BrightnessCurve::default() {
+            return ((raw.min(self.max_brightness) * 255) / self.max_brightness) as u8;
+        }
+        if raw == 0 {
+            return 0;
+        }
+        (1..=255).find(|&brightness| self.to_raw(brightness) >= raw).unwrap_or(255)
+    }
+
+    /// Read the device's value, replacing the cached one
+    async fn read(&mut self) -> Result<u32> {
+        let brightness_str = fs::read_to_string(self.path.join("brightness"))
+            .await
+            .context("Failed to read brightness")?;
+        let raw: u32 = brightness_str
+            .trim()
+            .parse()
+            .context("Failed to parse brightness")?;
+
+        if self.raw.is_some_and(|cached| cached != raw) {
+            tracing::debug!("Backlight {:?} changed outside nyx to {}", self.path, raw);
+        }
+        self.raw = Some(raw);
+        self.reconciled = Instant::now();
+        Ok(raw)
+    }
+
+    /// The cached value, read from the device first if it's stale
+    async fn current(&mut self) -> Result<u32> {
+        match self.raw {
+            Some(raw) if self.reconciled.elapsed() < RECONCILE_INTERVAL => Ok(raw),
+            _ => self.read().await,
+        }
+    }
+
+    /// Write `raw` to the device, unless it's already there
+    async fn write(&mut self, raw: u32) -> Result<()> {
+        if self.current().await.ok() == Some(raw) {
+            return Ok(());
+        }
+        if let Err(e) = fs::write(self.path.join("brightness"), raw.to_string()).await {
+            self.raw = None;
+            return Err(e).context("Failed to write brightness");
+        }
+        self.raw = Some(raw);
+        Ok(())
+    }
+}
+
+/// Raw value at `brightness` on the line through `points`, flat beyond either end
+fn interpolate(points: &[(u8, u32)], brightness: u8) -> u32 {
+    let upper = points.iter().position(|&(b, _)| b >= brightness);
+    match upper {
+        Some(0) => points[0].1,
+        None => points[points.len() - 1].1,
+        Some(i) => {
+            let ((b1, raw1), (b2, raw2)) = (points[i - 1], points[i]);
+            let fraction = (brightness - b1) as f64 / (b2 - b1) as f64;
+            (raw1 as f64 + fraction * (raw2 - raw1) as f64).round() as u32
+        }
+    }
+}
+
 impl DisplayController {
-    /// Create a new display controller by detecting the backlight device
-    pub async fn new() -> Result<Self> {
-        let backlight_path = Self::detect_backlight_device().await?;
+    /// Open the backlight device at `backlight_path`, switching `power` along with it
+    async fn open(backlight_path: PathBuf, curve: BrightnessCurve, power: Option<PowerControl>) -> Result<Self> {
+        let id = backlight_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow!("Invalid backlight path {:?}", backlight_path))?;
 
         // Read max_brightness from device
         let max_brightness_path = backlight_path.join("max_brightness");
@@ -32,6 +150,9 @@ impl DisplayController {
             .trim()
             .parse()
             .context("Failed to parse max_brightness")?;
+        if max_brightness == 0 {
+            return Err(anyhow!("max_brightness is 0"));
+        }
 
         tracing::info!(
             "Display controller initialized: {:?}, max_brightness={}",
@@ -41,71 +162,94 @@ impl DisplayController {
 
         // Read initial brightness
         let controller = Self {
+            id,
             inner: Arc::new(Mutex::new(DisplayControllerInner {
-                backlight_path,
-                max_brightness,
+                backlight: Some(Backlight {
+                    path: backlight_path,
+                    max_brightness,
+                    curve,
+                    raw: None,
+                    reconciled: Instant::now(),
+                }),
                 cached_brightness: 0,
             })),
+            power,
         };
+        controller.query_power().await;
 
         // Update cached brightness
-        let brightness = controller.get_brightness().await?;
+        let brightness = controller.reconcile().await?;
         controller.inner.lock().await.cached_brightness = brightness;
 
         Ok(controller)
     }
 
-    /// Detect the backlight device, preferring Touch Display 2
-    async fn detect_backlight_device() -> Result<PathBuf> {
-        let base_path = PathBuf::from("/sys/class/backlight");
+    /// A monitor without a backlight device, on and off through `power`; brightness is only
+    /// remembered, and anything above 0 means on
+    async fn power_only(id: String, power: PowerControl) -> Self {
+        tracing::info!("Display controller initialized without a backlight: {}", id);
+        let controller = Self {
+            id,
+            inner: Arc::new(Mutex::new(DisplayControllerInner {
+                backlight: None,
+                cached_brightness: DEFAULT_BRIGHTNESS,
+            })),
+            power: Some(power),
+        };
+        controller.query_power().await;
+        controller
+    }
 
-        // Prefer Touch Display 2
-        let touch_display_2 = base_path.join("10-0045");
-        if touch_display_2.exists() {
-            tracing::info!("Detected Touch Display 2 at {:?}", touch_display_2);
-            return Ok(touch_display_2);
-        }
+    /// Device name, used as the display's id in messages and config
+    pub fn id(&self) -> &str {
+        &self.id
+    }
 
-        // Fall back to original display
-        let original_display = base_path.join("rpi_backlight");
-        if original_display.exists() {
-            tracing::info!("Detected original display at {:?}", original_display);
-            return Ok(original_display);
-        }
+    /// Get display state (on/off): powered, and above brightness 0
+    pub async fn get_display_state(&self) -> Result<bool> {
+        let brightness = self.get_brightness().await?;
+        Ok(brightness > 0)
+    }
 
-        // Try to find any backlight device
-        if base_path.exists() {
-            let mut entries = fs::read_dir(&base_path)
-                .await
-                .context("Failed to read backlight directory")?;
+    /// Whether this display has power control
+    pub fn has_power(&self) -> bool {
+        self.power.as_ref().is_some_and(PowerControl::is_enabled)
+    }
 
-            while let Some(entry) = entries.next_entry().await? {
-                let path = entry.path();
-                if path.is_dir() {
-                    tracing::info!("Detected backlight device at {:?}", path);
-                    return Ok(path);
-                }
-            }
+    /// Turn display power on or off, leaving the backlight as it is
+    pub async fn set_power(&self, on: bool) -> Result<()> {
+        match &self.power {
+            Some(power) => power.set(on).await,
+            None => Err(anyhow!("Display {} has no power control", self.id)),
         }
+    }
 
-        Err(anyhow!("No backlight device found"))
+    /// Refresh the cached power state from the backend (failures are logged)
+    async fn query_power(&self) {
+        if let Some(power) = self.power.as_ref().filter(|power| power.is_enabled()) {
+            if let Err(e) = power.query().await {
+                tracing::warn!("Failed to read the power state of display {}: {:#}", self.id, e);
+            }
+        }
     }
 
-    /// Get display state (on/off)
-    pub async fn get_display_state(&self) -> Result<bool> {
-        let brightness = self.get_brightness().await?;
-        Ok(brightness > 0)
+    /// Last known power state; `None` without power control
+    async fn powered(&self) -> Option<bool> {
+        match &self.power {
+            Some(power) => power.cached().await,
+            None => None,
+        }
     }
 
     /// Set display state (on/off)
     pub async fn set_display_state(&self, state: bool) -> Result<()> {
         let inner = self.inner.lock().await;
         let brightness = if state {
-            // Use cached brightness or default to 178 (~70% brightness)
+            // Use cached brightness or default to ~70% brightness
             if inner.cached_brightness > 0 {
                 inner.cached_brightness
             } else {
-                178
+                DEFAULT_BRIGHTNESS
             }
         } else {
             0
@@ -116,54 +260,188 @@ impl DisplayController {
         self.set_brightness(brightness).await
     }
 
-    /// Get brightness (0-255 scale)
+    /// Get brightness (0-255 scale) from the cache; 0 while display power is off
     pub async fn get_brightness(&self) -> Result<u8> {
-        let inner = self.inner.lock().await;
-        let brightness_path = inner.backlight_path.join("brightness");
+        if self.powered().await == Some(false) {
+            return Ok(0);
+        }
 
-        let brightness_str = fs::read_to_string(&brightness_path)
-            .await
-            .context("Failed to read brightness")?;
+        let mut inner = self.inner.lock().await;
+        let Some(backlight) = inner.backlight.as_mut() else {
+            return Ok(inner.cached_brightness);
+        };
+        let raw = backlight.current().await?;
+        Ok(backlight.to_brightness(raw))
+    }
 
-        let raw_brightness: u32 = brightness_str
-            .trim()
-            .parse()
-            .context("Failed to parse brightness")?;
+    /// Read the backlight from sysfs now rather than trusting the cache, e.g. to check it's
+    /// still readable; brightness (0-255 scale) regardless of display power
+    pub async fn reconcile(&self) -> Result<u8> {
+        let mut inner = self.inner.lock().await;
+        let Some(backlight) = inner.backlight.as_mut() else {
+            return Ok(inner.cached_brightness);
+        };
+        let raw = backlight.read().await?;
+        Ok(backlight.to_brightness(raw))
+    }
 
-        // Convert from device scale to 0-255 scale
-        let brightness = ((raw_brightness * 255) / inner.max_brightness) as u8;
-        Ok(brightness)
+    /// Map brightness through `curve` from now on; the backlight keeps its value until the
+    /// next write
+    pub async fn set_curve(&self, curve: BrightnessCurve) {
+        if let Some(backlight) = self.inner.lock().await.backlight.as_mut() {
+            backlight.curve = curve;
+        }
     }
 
     /// Set brightness (0-255 scale)
+    ///
+    /// With power control, anything above 0 powers the display on, and 0 powers it off when
+    /// `power.follow_brightness` is set (always, without a backlight).
     pub async fn set_brightness(&self, brightness: u8) -> Result<()> {
         let mut inner = self.inner.lock().await;
 
-        // Convert from 0-255 scale to device scale
-        let raw_brightness = (brightness as u32 * inner.max_brightness) / 255;
-
-        let brightness_path = inner.backlight_path.join("brightness");
-        fs::write(&brightness_path, raw_brightness.to_string())
-            .await
-            .context("Failed to write brightness")?;
+        if let Some(backlight) = inner.backlight.as_mut() {
+            let raw_brightness = backlight.to_raw(brightness);
+            backlight.write(raw_brightness).await?;
+            tracing::debug!("Set brightness to {} (raw: {})", brightness, raw_brightness);
+        }
 
         // Cache brightness if > 0
         if brightness > 0 {
             inner.cached_brightness = brightness;
         }
+        let has_backlight = inner.backlight.is_some();
+        drop(inner);
 
-        tracing::debug!("Set brightness to {} (raw: {})", brightness, raw_brightness);
+        if let Some(power) = self.power.as_ref().filter(|power| power.is_enabled()) {
+            let on = brightness > 0;
+            let switch = on || !has_backlight || power.follows_brightness();
+            if switch && power.cached().await != Some(on) {
+                power.set(on).await?;
+            }
+        }
         Ok(())
     }
 
-    /// Get display metrics
+    /// Get display metrics, asking the power backend for its current state first
     pub async fn get_metrics(&self) -> Result<DisplayMetrics> {
+        self.query_power().await;
         let display_on = self.get_display_state().await?;
         let brightness = self.get_brightness().await?;
 
         Ok(DisplayMetrics {
+            id: self.id.clone(),
             display_on,
             brightness,
         })
     }
 }
+
+/// Every backlight device on the kiosk, primary first
+#[derive(Clone)]
+pub struct Displays {
+    displays: Arc<Vec<DisplayController>>,
+}
+
+impl Displays {
+    /// Open every backlight device; ones that can't be read are logged and left out
+    ///
+    /// `power` switches the primary display; with no backlight at all, a configured power
+    /// backend becomes the only display, named after `power.output` (or `hdmi`).
+    pub async fn detect(config_manager: &ConfigManager, power: PowerControl) -> Result<Self> {
+        let mut displays = Vec::new();
+        for path in Self::detect_backlight_devices().await? {
+            let primary_power = displays.is_empty().then(|| power.clone());
+            let id = path.file_name().unwrap_or_default().to_string_lossy();
+            let curve = config_manager.get_brightness_curve(&id);
+            match DisplayController::open(path.clone(), curve, primary_power).await {
+                Ok(display) => displays.push(display),
+                Err(e) => tracing::warn!("Skipping backlight device {:?}: {:#}", path, e),
+            }
+        }
+        if displays.is_empty() && power.is_enabled() {
+            let id = power.output().unwrap_or_else(|| "hdmi".to_string());
+            displays.push(DisplayController::power_only(id, power).await);
+        }
+        if displays.is_empty() {
+            return Err(anyhow!("No usable backlight device found"));
+        }
+
+        Ok(Self {
+            displays: Arc::new(displays),
+        })
+    }
+
+    /// List the backlight devices, Touch Display 2 first, then the original display, then
+    /// the rest by name
+    ///
+    /// `NYX_BACKLIGHT_DIR` replaces `/sys/class/backlight`, e.g. to point tests at a fake device.
+    async fn detect_backlight_devices() -> Result<Vec<PathBuf>> {
+        let base_path = std::env::var_os("NYX_BACKLIGHT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/sys/class/backlight"));
+
+        let mut paths = Vec::new();
+        if base_path.exists() {
+            let mut entries = fs::read_dir(&base_path)
+                .await
+                .context("Failed to read backlight directory")?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.is_dir() {
+                    paths.push(path);
+                }
+            }
+        }
+
+        let rank = |path: &PathBuf| match path.file_name().and_then(|name| name.to_str()) {
+            Some("10-0045") => 0,
+            Some("rpi_backlight") => 1,
+            _ => 2,
+        };
+        paths.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.cmp(b)));
+
+        if paths.is_empty() {
+            tracing::warn!("No backlight device found");
+        }
+        for path in &paths {
+            tracing::info!("Detected backlight device at {:?}", path);
+        }
+        Ok(paths)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&DisplayController> {
+        self.displays.iter().find(|display| display.id == id)
+    }
+
+    /// Every display, primary (reported as `display` in metrics) first
+    pub fn iter(&self) -> impl Iterator<Item = &DisplayController> {
+        self.displays.iter()
+    }
+
+    /// Display `id`, or every display without one; `None` if there's no such display
+    pub fn select(&self, id: Option<&str>) -> Option<Vec<DisplayController>> {
+        match id {
+            Some(id) => self.get(id).map(|display| vec![display.clone()]),
+            None => Some(self.displays.to_vec()),
+        }
+    }
+
+    /// Apply each display's `brightness_curve` from `config`, after it's been edited
+    pub async fn set_curves(&self, config: &Config) {
+        for display in self.iter() {
+            display.set_curve(config.brightness_curve(display.id())).await;
+        }
+    }
+
+    /// Whether every display is off
+    pub async fn all_off(&self) -> bool {
+        for display in self.iter() {
+            if !matches!(display.get_brightness().await, Ok(0)) {
+                return false;
+            }
+        }
+        true
+    }
+}
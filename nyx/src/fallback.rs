@@ -0,0 +1,315 @@
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::Value;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+use tokio::time::{interval, Duration, Instant};
+
+use crate::cdp::{CdpClient, CdpEvent};
+use crate::config::FallbackConfig;
+use crate::messages::FallbackStatus;
+
+/// The diagnostics page; it polls `/status` for what to show
+const PAGE: &str = include_str!("fallback.html");
+
+/// Longest a reachability check of the failed URL may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to see whether a reachability check is due
+const TICK: Duration = Duration::from_secs(1);
+
+/// Puts a local diagnostics page up in place of a kiosk page that fails to load, and the page
+/// back once its URL answers again
+///
+/// Main-frame loads are followed through Chrome's network events: a network error, or an HTTP
+/// error status with `fallback.http_errors`, navigates to the page served on
+/// `http://127.0.0.1:<fallback.port>/`. While it's showing, the failed URL is fetched every
+/// `fallback.interval` seconds and restored as soon as it answers. The section is read at
+/// startup.
+#[derive(Clone)]
+pub struct Fallback {
+    status: watch::Sender<Option<FallbackStatus>>,
+    cdp: CdpClient,
+    shutdown: watch::Sender<bool>,
+}
+
+/// Main-frame load in progress
+struct Load {
+    request_id: String,
+    url: String,
+}
+
+/// Follows page loads and checks the failed URL, for one [`Fallback`]
+struct Watcher {
+    fallback: Fallback,
+    config: FallbackConfig,
+    /// URL of the fallback page itself, whose loads are left alone
+    page: String,
+    client: reqwest::Client,
+    loading: Option<Load>,
+    next_check: Instant,
+}
+
+impl Fallback {
+    pub fn new(cdp: CdpClient) -> Self {
+        let (status, _) = watch::channel(None);
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            status,
+            cdp,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Serve the page and start following page loads
+    pub async fn start(&self, config: FallbackConfig) -> Result<()> {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind fallback page to {}", addr))?;
+        tracing::info!("Fallback page on http://{}/", addr);
+
+        let router = Router::new()
+            .route("/", get(page))
+            .route("/status", get(status))
+            .with_state(self.clone());
+        let mut server_shutdown = self.shutdown.subscribe();
+        tokio::spawn(async move {
+            let shutdown = async move {
+                while server_shutdown.changed().await.is_ok() && !*server_shutdown.borrow() {}
+            };
+            if let Err(e) = axum::serve(listener, router).with_graceful_shutdown(shutdown).await {
+                tracing::error!("Fallback page server error: {}", e);
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .timeout(CHECK_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client")?;
+        let mut watcher = Watcher {
+            fallback: self.clone(),
+            config,
+            page: format!("http://{}/", addr),
+            client,
+            loading: None,
+            next_check: Instant::now(),
+        };
+        let mut events = self.cdp.subscribe_events();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        // Events arrive as PageErrors drains the CDP session
+        tokio::spawn(async move {
+            let mut tick = interval(TICK);
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => watcher.check().await,
+                    event = events.recv() => match event {
+                        Ok(event) => watcher.follow(&event).await,
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::debug!("Fallback missed {} page events", missed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop serving the page and following page loads
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Why the fallback page is showing; `None` while it isn't
+    pub fn status(&self) -> Option<FallbackStatus> {
+        self.status.borrow().clone()
+    }
+
+    /// Notified whenever the status changes
+    pub fn subscribe(&self) -> watch::Receiver<Option<FallbackStatus>> {
+        self.status.subscribe()
+    }
+
+    fn publish(&self, status: Option<FallbackStatus>) {
+        self.status.send_if_modified(|current| {
+            let changed = *current != status;
+            *current = status;
+            changed
+        });
+    }
+}
+
+impl Watcher {
+    /// Track main-frame loads through their network events
+    async fn follow(&mut self, event: &CdpEvent) {
+        let params = &event.params;
+        let request_id = params["requestId"].as_str().unwrap_or_default();
+        match event.method.as_str() {
+            "Network.requestWillBeSent" if is_navigation(params) => {
+                let url = params["request"]["url"].as_str().unwrap_or_default();
+                if url.starts_with(&self.page) {
+                    self.loading = None;
+                } else if self.is_main_frame(params).await {
+                    self.loading = Some(Load {
+                        request_id: request_id.to_string(),
+                        url: url.to_string(),
+                    });
+                }
+            }
+            "Network.responseReceived" => {
+                let Some(load) = self.take_load(request_id) else {
+                    return;
+                };
+                let status = params["response"]["status"].as_u64().unwrap_or_default();
+                if status >= 400 && self.config.http_errors {
+                    let text = params["response"]["statusText"].as_str().unwrap_or_default();
+                    self.fail(load.url, format!("HTTP {} {}", status, text).trim_end().to_string())
+                        .await;
+                } else {
+                    self.loaded(&load.url);
+                }
+            }
+            "Network.loadingFailed" => {
+                let Some(load) = self.take_load(request_id) else {
+                    return;
+                };
+                // Cancelled by another navigation, which is followed in turn
+                if params["canceled"].as_bool() != Some(true) {
+                    let error = params["errorText"].as_str().unwrap_or("net::ERR_FAILED");
+                    self.fail(load.url, error.to_string()).await;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The load in progress, if `request_id` is it
+    fn take_load(&mut self, request_id: &str) -> Option<Load> {
+        if self.loading.as_ref()?.request_id == request_id {
+            self.loading.take()
+        } else {
+            None
+        }
+    }
+
+    /// Whether a load is in the active tab's top frame (whose id is the tab's), not an iframe
+    async fn is_main_frame(&self, params: &Value) -> bool {
+        let frame = params["frameId"].as_str().unwrap_or_default();
+        match self.fallback.cdp.list_tabs().await {
+            Ok(tabs) => tabs.iter().any(|tab| tab.active && tab.id == frame),
+            Err(e) => {
+                tracing::debug!("Can't find the active tab: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// The kiosk page failed to load `url`: put the fallback page up
+    async fn fail(&mut self, url: String, reason: String) {
+        tracing::warn!("Failed to load {} ({}), showing the fallback page", url, reason);
+        let status = match self.fallback.status() {
+            Some(status) if status.url == url => FallbackStatus { reason, ..status },
+            _ => FallbackStatus {
+                url,
+                reason,
+                since_ms: now_ms(),
+                checks: 0,
+                check_error: None,
+            },
+        };
+        self.fallback.publish(Some(status));
+        self.next_check = Instant::now() + Duration::from_secs(self.config.interval);
+        if let Err(e) = self.fallback.cdp.navigate(&self.page).await {
+            tracing::warn!("Failed to show the fallback page: {:#}", e);
+        }
+    }
+
+    /// A page loaded, so whatever the fallback page was standing in for is over
+    fn loaded(&self, url: &str) {
+        if self.fallback.status().is_some() {
+            tracing::info!("Loaded {}, fallback page no longer needed", url);
+            self.fallback.publish(None);
+        }
+    }
+
+    /// Check the failed URL when it's due, restoring it if it answers
+    async fn check(&mut self) {
+        let Some(mut status) = self.fallback.status() else {
+            return;
+        };
+        if Instant::now() < self.next_check {
+            return;
+        }
+        self.next_check = Instant::now() + Duration::from_secs(self.config.interval);
+
+        status.checks += 1;
+        status.check_error = match self.client.get(&status.url).send().await {
+            Ok(response) if response.status().as_u16() >= 400 && self.config.http_errors => {
+                Some(format!("HTTP {}", response.status()))
+            }
+            Ok(_) => None,
+            Err(e) => Some(describe(&e)),
+        };
+        if status.check_error.is_some() {
+            self.fallback.publish(Some(status));
+            return;
+        }
+
+        tracing::info!("{} is reachable again, restoring it", status.url);
+        match self.fallback.cdp.navigate(&status.url).await {
+            // A failure to load it again puts the fallback page straight back
+            Ok(()) => self.fallback.publish(None),
+            Err(e) => {
+                tracing::warn!("Failed to restore {}: {:#}", status.url, e);
+                status.check_error = Some(format!("{:#}", e));
+                self.fallback.publish(Some(status));
+            }
+        }
+    }
+}
+
+/// `GET /`: the diagnostics page
+async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+/// `GET /status`: why the page is showing, `null` once it's no longer needed
+async fn status(State(fallback): State<Fallback>) -> Json<Option<FallbackStatus>> {
+    Json(fallback.status())
+}
+
+/// Whether a request is a document load (in any frame) rather than a resource or redirect hop
+fn is_navigation(params: &Value) -> bool {
+    params["type"] == "Document" && params["requestId"] == params["loaderId"] && params["redirectResponse"].is_null()
+}
+
+/// A failed check, with its cause (reqwest's own message rarely says what went wrong)
+fn describe(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message = format!("{}: {}", message, cause);
+        source = cause.source();
+    }
+    message
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
@@ -0,0 +1,305 @@
+use evdev::{AbsoluteAxisType, InputEvent, InputEventKind, Key, Synchronization};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tokio::time::{Duration, Instant};
+
+/// Shortest movement, as a fraction of the screen, that counts as a swipe
+const SWIPE_DISTANCE: f64 = 0.15;
+
+/// Movement, as a fraction of the screen, a tap or long-press may wander
+const MOVE_TOLERANCE: f64 = 0.03;
+
+/// Longest a touch may last and still be a tap
+const TAP_TIME: Duration = Duration::from_millis(300);
+
+/// How long a touch must be held still to be a long-press
+const LONG_PRESS_TIME: Duration = Duration::from_millis(800);
+
+/// Distance from a screen edge, as a fraction of the screen, a gesture may start at and still
+/// count as starting from that edge
+const EDGE_MARGIN: f64 = 0.05;
+
+/// A recognised gesture
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureKind {
+    SwipeLeft,
+    SwipeRight,
+    SwipeUp,
+    SwipeDown,
+    LongPress,
+    Tap,
+}
+
+/// Screen edge a gesture started at
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// A gesture read off the touchscreen; positions are fractions of the screen (0-1) from the
+/// top left, following the first finger down
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gesture {
+    pub gesture: GestureKind,
+    /// Most fingers down at once
+    pub fingers: usize,
+    pub start_x: f64,
+    pub start_y: f64,
+    pub end_x: f64,
+    pub end_y: f64,
+    pub duration_ms: u64,
+    /// Edge the gesture started at, if it started right by one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edge: Option<Edge>,
+}
+
+/// Axis range, for turning device units into fractions of the screen
+#[derive(Debug, Clone, Copy)]
+pub struct AxisRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl AxisRange {
+    fn normalize(self, value: i32) -> f64 {
+        let span = (self.max - self.min).max(1) as f64;
+        ((value - self.min) as f64 / span).clamp(0.0, 1.0)
+    }
+}
+
+/// One finger, by multi-touch slot
+#[derive(Debug, Default)]
+struct Contact {
+    down: bool,
+    x: Option<i32>,
+    y: Option<i32>,
+    /// Where the finger came down, once both coordinates are known
+    start: Option<(f64, f64)>,
+}
+
+/// A touch from the first finger down to the last finger up
+#[derive(Debug)]
+struct Sequence {
+    started: Instant,
+    /// Slot of the first finger down, which positions follow
+    primary: usize,
+    fingers: usize,
+    moved: bool,
+    long_pressed: bool,
+}
+
+/// Turns raw evdev events into gestures
+///
+/// Understands multi-touch protocol B (`ABS_MT_SLOT`/`ABS_MT_TRACKING_ID`) and falls back to
+/// `ABS_X`/`ABS_Y` with `BTN_TOUCH` for single-touch screens. Swipes and taps are reported
+/// when the last finger lifts, long-presses as soon as they've been held long enough (see
+/// [`GestureDecoder::poll`]).
+#[derive(Debug)]
+pub struct GestureDecoder {
+    x_range: AxisRange,
+    y_range: AxisRange,
+    multitouch: bool,
+    slot: usize,
+    contacts: BTreeMap<usize, Contact>,
+    sequence: Option<Sequence>,
+}
+
+impl GestureDecoder {
+    pub fn new(x_range: AxisRange, y_range: AxisRange) -> Self {
+        Self {
+            x_range,
+            y_range,
+            multitouch: false,
+            slot: 0,
+            contacts: BTreeMap::new(),
+            sequence: None,
+        }
+    }
+
+    /// Forget any touch in progress, e.g. after events were grabbed or dropped
+    pub fn reset(&mut self) {
+        self.contacts.values_mut().for_each(|contact| {
+            contact.down = false;
+            contact.start = None;
+        });
+        self.sequence = None;
+    }
+
+    /// Feed one event; returns a gesture when a `SYN_REPORT` completes one
+    pub fn handle(&mut self, event: &InputEvent, now: Instant) -> Option<Gesture> {
+        match event.kind() {
+            InputEventKind::AbsAxis(axis) => self.handle_axis(axis, event.value()),
+            InputEventKind::Key(Key::BTN_TOUCH) if !self.multitouch => {
+                let contact = self.contacts.entry(0).or_default();
+                contact.down = event.value() != 0;
+                if contact.down {
+                    contact.start = None;
+                }
+            }
+            InputEventKind::Synchronization(Synchronization::SYN_REPORT) => return self.sync(now),
+            InputEventKind::Synchronization(Synchronization::SYN_DROPPED) => self.reset(),
+            _ => {}
+        }
+        None
+    }
+
+    /// Report a long-press once a still touch has been held long enough; call regularly
+    /// while no events arrive
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        let sequence = self.sequence.as_mut()?;
+        if sequence.moved || sequence.long_pressed || now - sequence.started < LONG_PRESS_TIME {
+            return None;
+        }
+        sequence.long_pressed = true;
+        self.gesture(GestureKind::LongPress, now)
+    }
+
+    fn handle_axis(&mut self, axis: AbsoluteAxisType, value: i32) {
+        match axis {
+            AbsoluteAxisType::ABS_MT_SLOT => {
+                self.multitouch = true;
+                self.slot = value.max(0) as usize;
+            }
+            AbsoluteAxisType::ABS_MT_TRACKING_ID => {
+                self.multitouch = true;
+                let contact = self.contacts.entry(self.slot).or_default();
+                contact.down = value >= 0;
+                if contact.down {
+                    contact.start = None;
+                }
+            }
+            AbsoluteAxisType::ABS_MT_POSITION_X => {
+                self.multitouch = true;
+                self.contacts.entry(self.slot).or_default().x = Some(value);
+            }
+            AbsoluteAxisType::ABS_MT_POSITION_Y => {
+                self.multitouch = true;
+                self.contacts.entry(self.slot).or_default().y = Some(value);
+            }
+            AbsoluteAxisType::ABS_X if !self.multitouch => {
+                self.contacts.entry(0).or_default().x = Some(value);
+            }
+            AbsoluteAxisType::ABS_Y if !self.multitouch => {
+                self.contacts.entry(0).or_default().y = Some(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn sync(&mut self, now: Instant) -> Option<Gesture> {
+        let (x_range, y_range) = (self.x_range, self.y_range);
+        for contact in self.contacts.values_mut().filter(|contact| contact.down) {
+            if let (None, Some(x), Some(y)) = (contact.start, contact.x, contact.y) {
+                contact.start = Some((x_range.normalize(x), y_range.normalize(y)));
+            }
+        }
+
+        let down: Vec<usize> = self
+            .contacts
+            .iter()
+            .filter(|(_, contact)| contact.down && contact.start.is_some())
+            .map(|(slot, _)| *slot)
+            .collect();
+
+        if self.sequence.is_none() {
+            let primary = *down.first()?;
+            self.sequence = Some(Sequence {
+                started: now,
+                primary,
+                fingers: 0,
+                moved: false,
+                long_pressed: false,
+            });
+        }
+
+        let (distance, _, _) = self.movement();
+        let sequence = self.sequence.as_mut()?;
+        sequence.fingers = sequence.fingers.max(down.len());
+        sequence.moved |= distance > MOVE_TOLERANCE;
+        if !down.is_empty() {
+            return None;
+        }
+
+        let gesture = self.finish(now);
+        self.sequence = None;
+        gesture
+    }
+
+    /// Classify a touch whose fingers have all lifted
+    fn finish(&self, now: Instant) -> Option<Gesture> {
+        let sequence = self.sequence.as_ref()?;
+        if sequence.long_pressed {
+            return None;
+        }
+
+        let (distance, dx, dy) = self.movement();
+        let kind = if distance >= SWIPE_DISTANCE {
+            match (dx.abs() >= dy.abs(), dx >= 0.0, dy >= 0.0) {
+                (true, true, _) => GestureKind::SwipeRight,
+                (true, false, _) => GestureKind::SwipeLeft,
+                (false, _, true) => GestureKind::SwipeDown,
+                (false, _, false) => GestureKind::SwipeUp,
+            }
+        } else if !sequence.moved && now - sequence.started <= TAP_TIME {
+            GestureKind::Tap
+        } else {
+            return None;
+        };
+        self.gesture(kind, now)
+    }
+
+    /// How far the primary finger has come: (distance, dx, dy)
+    fn movement(&self) -> (f64, f64, f64) {
+        let Some(((start_x, start_y), (x, y))) = self.positions() else {
+            return (0.0, 0.0, 0.0);
+        };
+        let (dx, dy) = (x - start_x, y - start_y);
+        (dx.abs().max(dy.abs()), dx, dy)
+    }
+
+    /// Start and latest position of the primary finger
+    fn positions(&self) -> Option<((f64, f64), (f64, f64))> {
+        let contact = self.contacts.get(&self.sequence.as_ref()?.primary)?;
+        let start = contact.start?;
+        let end = (
+            self.x_range.normalize(contact.x?),
+            self.y_range.normalize(contact.y?),
+        );
+        Some((start, end))
+    }
+
+    fn gesture(&self, gesture: GestureKind, now: Instant) -> Option<Gesture> {
+        let sequence = self.sequence.as_ref()?;
+        let ((start_x, start_y), (end_x, end_y)) = self.positions()?;
+        Some(Gesture {
+            gesture,
+            fingers: sequence.fingers.max(1),
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            duration_ms: (now - sequence.started).as_millis() as u64,
+            edge: edge(start_x, start_y),
+        })
+    }
+}
+
+/// The edge a gesture starting at (`x`, `y`) starts from, if any
+fn edge(x: f64, y: f64) -> Option<Edge> {
+    if x <= EDGE_MARGIN {
+        Some(Edge::Left)
+    } else if x >= 1.0 - EDGE_MARGIN {
+        Some(Edge::Right)
+    } else if y <= EDGE_MARGIN {
+        Some(Edge::Top)
+    } else if y >= 1.0 - EDGE_MARGIN {
+        Some(Edge::Bottom)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use shq_audit::AuditLog;
+use shq_auth::Identity;
+use shq_i18n::Translator;
+use shq_ratelimit::RateLimiter;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::Instrument;
+
+use crate::config::ConfigManager;
+use crate::display::Displays;
+use crate::messages::{ClientMessage, ServerMessage};
+use crate::websocket::WebSocketHandler;
+
+/// Everything the HTTP endpoints share with the WebSocket API
+pub struct HttpApi {
+    /// Runs the commands, exactly as for a WebSocket client
+    pub handler: WebSocketHandler,
+    pub displays: Displays,
+    pub config_manager: ConfigManager,
+    pub tr: Translator,
+    pub audit: AuditLog,
+    pub rate_limiter: RateLimiter,
+}
+
+/// Query parameters every endpoint accepts
+#[derive(Debug, Default, Deserialize)]
+struct Params {
+    /// Display to address (every display if absent)
+    display: Option<String>,
+    /// Auth token, for clients that can't set an `Authorization` header
+    token: Option<String>,
+}
+
+/// Body of `POST /brightness`
+#[derive(Debug, Deserialize)]
+struct BrightnessBody {
+    brightness: u8,
+}
+
+/// Body of `POST /navigate`
+#[derive(Debug, Deserialize)]
+struct NavigateBody {
+    url: String,
+}
+
+/// REST endpoints mapping onto the display commands, on their own port
+pub struct HttpServer {
+    listener: TcpListener,
+    router: Router,
+}
+
+impl HttpServer {
+    /// Bind the listener; requests are served once [`HttpServer::run`] is called
+    pub async fn bind(addr: SocketAddr, api: HttpApi) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind HTTP API to {}", addr))?;
+        tracing::info!("HTTP API listening on http://{}", addr);
+
+        let router = Router::new()
+            .route("/metrics", get(metrics))
+            .route("/wake", post(wake))
+            .route("/sleep", post(sleep))
+            .route("/brightness", post(brightness))
+            .route("/navigate", post(navigate))
+            .with_state(Arc::new(api));
+        Ok(Self { listener, router })
+    }
+
+    /// Serve requests until `shutdown` completes
+    pub async fn run(self, shutdown: impl Future<Output = ()> + Send + 'static) -> Result<()> {
+        axum::serve(
+            self.listener,
+            self.router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+        Ok(())
+    }
+}
+
+async fn metrics(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, |_| ClientMessage::GetMetrics).await
+}
+
+async fn wake(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, |display| ClientMessage::Wake { display }).await
+}
+
+async fn sleep(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+) -> Response {
+    api.handle(addr, &headers, params, |display| ClientMessage::Sleep { display }).await
+}
+
+/// `POST /brightness` with `{"brightness": 128}` (0-255, 0 sleeps); the body is read as JSON
+/// whatever its content type, so `curl -d` works as is
+async fn brightness(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match serde_json::from_slice::<BrightnessBody>(&body) {
+        Ok(BrightnessBody { brightness }) => {
+            api.handle(addr, &headers, params, |display| ClientMessage::SetBrightness { display, brightness })
+                .await
+        }
+        Err(e) => api
+            .error(StatusCode::BAD_REQUEST, "error.invalid_message", &[("error", &e)])
+            .into_response(),
+    }
+}
+
+/// `POST /navigate` with `{"url": "http://..."}`, read as JSON like `POST /brightness`
+async fn navigate(
+    State(api): State<Arc<HttpApi>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Params>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match serde_json::from_slice::<NavigateBody>(&body) {
+        Ok(NavigateBody { url }) => api.handle(addr, &headers, params, |_| ClientMessage::Navigate { url }).await,
+        Err(e) => api
+            .error(StatusCode::BAD_REQUEST, "error.invalid_message", &[("error", &e)])
+            .into_response(),
+    }
+}
+
+impl HttpApi {
+    /// Handle one request in its own command span, so its log lines share a correlation ID;
+    /// `message` builds the command for the `?display=` the request names
+    async fn handle(
+        &self,
+        addr: SocketAddr,
+        headers: &HeaderMap,
+        params: Params,
+        message: impl FnOnce(Option<String>) -> ClientMessage,
+    ) -> Response {
+        let display = params.display.clone();
+        let message = message(display.clone());
+        let span = shq_logging::command_span(command_name(&message));
+        span.record("client", tracing::field::display(addr));
+        self.check_and_run(addr, headers, params.token, display, message)
+            .instrument(span)
+            .await
+    }
+
+    /// Run one request through the same checks as a WebSocket command: rate limit, token, role
+    /// and display, with privileged commands recorded in the audit log
+    async fn check_and_run(
+        &self,
+        addr: SocketAddr,
+        headers: &HeaderMap,
+        token: Option<String>,
+        display: Option<String>,
+        message: ClientMessage,
+    ) -> Response {
+        if let Err(limited) = self.rate_limiter.check(addr.ip()) {
+            let mut response = self
+                .error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "error.rate_limited",
+                    &[("retry_after_ms", &limited.retry_after_ms())],
+                )
+                .into_response();
+            let retry_after = limited.retry_after_ms().div_ceil(1000).to_string();
+            if let Ok(value) = retry_after.parse() {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+            return response;
+        }
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string())
+            .or(token);
+        let identity = match self.config_manager.get_auth_config().authenticate(token.as_deref()) {
+            Ok(identity) => identity,
+            Err(e) => {
+                tracing::warn!("Refused HTTP request from {}: {}", addr, e);
+                let message = ServerMessage::Error { message: e.to_string() };
+                return (StatusCode::UNAUTHORIZED, Json(message)).into_response();
+            }
+        };
+
+        let Some(event) = message.audit_event() else {
+            return self.run(&identity, addr, display, message).await.into_response();
+        };
+        let event = event.client(identity.label(addr.ip()));
+        let (status, Json(reply)) = self.run(&identity, addr, display, message).await;
+        self.audit.record(match &reply {
+            ServerMessage::Error { message } => event.failed(message),
+            _ => event,
+        });
+        (status, Json(reply)).into_response()
+    }
+
+    /// Check the role and the display, then run the command through the WebSocket handler
+    ///
+    /// Every request is a new connection, so clients are labelled by token and IP address only.
+    async fn run(
+        &self,
+        identity: &Identity,
+        addr: SocketAddr,
+        display: Option<String>,
+        message: ClientMessage,
+    ) -> (StatusCode, Json<ServerMessage>) {
+        let role = message.required_role();
+        if identity.require(role).is_err() {
+            tracing::warn!(
+                "Refused HTTP command from {}: requires the {} role",
+                identity.label(addr.ip()),
+                role
+            );
+            return self.error(StatusCode::FORBIDDEN, "error.forbidden", &[("role", &role)]);
+        }
+        if self.displays.select(display.as_deref()).is_none() {
+            return self.error(
+                StatusCode::NOT_FOUND,
+                "error.unknown_display",
+                &[("display", &display.unwrap_or_default())],
+            );
+        }
+
+        // Only a browser command can fail on the way to something else (Chrome)
+        let failed = match message {
+            ClientMessage::Navigate { .. } => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        match self.handler.execute(message).await {
+            Ok(reply @ ServerMessage::Error { .. }) => (failed, Json(reply)),
+            Ok(reply) => (StatusCode::OK, Json(reply)),
+            Err(e) => {
+                tracing::error!("HTTP command failed: {:#}", e);
+                self.error(failed, "error.command_failed", &[("error", &format!("{:#}", e))])
+            }
+        }
+    }
+
+    /// An `error` reply with a translated message
+    fn error(&self, status: StatusCode, key: &str, args: &[(&str, &dyn Display)]) -> (StatusCode, Json<ServerMessage>) {
+        let message = ServerMessage::Error {
+            message: self.tr.t(key, args),
+        };
+        (status, Json(message))
+    }
+}
+
+/// Name of a command with an HTTP route, as in WebSocket `response` messages
+fn command_name(message: &ClientMessage) -> &'static str {
+    match message {
+        ClientMessage::GetMetrics => "get_metrics",
+        ClientMessage::Wake { .. } => "wake",
+        ClientMessage::Sleep { .. } => "sleep",
+        ClientMessage::SetBrightness { .. } => "set_brightness",
+        ClientMessage::Navigate { .. } => "navigate",
+        _ => unreachable!("no HTTP route for this command"),
+    }
+}
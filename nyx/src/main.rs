@@ -1,40 +1,93 @@
 mod auto_dim;
+mod browser;
 mod cdp;
 mod config;
+mod ctl;
 mod display;
+mod fallback;
+mod gesture;
+mod http;
 mod messages;
+mod mqtt;
+mod night_mode;
+mod page_errors;
+mod power;
+mod presence;
+mod schedule;
+mod screensaver;
 mod touch;
 mod websocket;
 
 use anyhow::Result;
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_i18n::Translator;
+use shq_logging::LogFormat;
+use shq_mdns::Advertisement;
+use shq_ratelimit::RateLimiter;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::signal;
+use tokio::sync::oneshot;
 
 use auto_dim::AutoDimManager;
+use browser::BrowserWatchdog;
+use cdp::CdpClient;
 use config::ConfigManager;
-use display::DisplayController;
+use display::Displays;
+use fallback::Fallback;
+use http::{HttpApi, HttpServer};
+use messages::{LOCALES, PROTOCOL_VERSION};
+use night_mode::NightMode;
+use page_errors::PageErrors;
+use power::PowerControl;
+use presence::PresenceSensor;
+use screensaver::Screensaver;
 use touch::TouchMonitor;
-use websocket::WebSocketServer;
+use websocket::WebSocketHandler;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "nyx=info".into()),
-        )
-        .init();
+    // `nyx ctl ...` (or `nyx-ctl ...` through a link) is a client for a running nyx rather than
+    // the service itself
+    let args: Vec<String> = std::env::args().collect();
+    let program = args.first().map(std::path::Path::new).and_then(|path| path.file_name());
+    if program.is_some_and(|name| name == "nyx-ctl") {
+        std::process::exit(ctl::run(&args[1..]).await);
+    }
+    if args.get(1).is_some_and(|arg| arg == "ctl") {
+        std::process::exit(ctl::run(&args[2..]).await);
+    }
+
+    // Initialize tracing (file logging is enabled once config is loaded)
+    let log_handle = shq_logging::init("nyx=info", LogFormat::from_args()?);
 
     tracing::info!("Starting Nyx Display Server v{}", env!("CARGO_PKG_VERSION"));
 
     // Initialize configuration manager
     let config_manager = ConfigManager::new().await?;
     let ws_config = config_manager.get_websocket_config();
+    let mdns_config = config_manager.get_mdns_config();
+
+    let mut logging_config = config_manager.get_logging_config();
+    if let Err(e) = log_handle.apply(&logging_config) {
+        tracing::warn!("File logging disabled: {:#}", e);
+    }
+
+    let crash_config = config_manager.clone();
+    log_handle.install_panic_hook(
+        "nyx",
+        env!("CARGO_PKG_VERSION"),
+        config_manager.crash_report_path(),
+        move || crash_config.hash(),
+    );
+
+    // Security audit trail: connections, privileged commands and config changes
+    let audit = AuditLog::open("nyx", &config_manager.get_audit_config(), config_manager.audit_log_path());
+    audit.record(
+        AuditEvent::new(AuditKind::Service, "started").detail(json!({ "version": env!("CARGO_PKG_VERSION") })),
+    );
+    audit.watch_config(config_manager.subscribe());
 
     // Parse command-line arguments (can override config values)
-    let args: Vec<String> = std::env::args().collect();
     let host = args
         .iter()
         .position(|arg| arg == "--host")
@@ -52,54 +105,275 @@ async fn main() -> Result<()> {
     let addr: SocketAddr = format!("{}:{}", host, port).parse()?;
     let auto_dim_config = config_manager.get_auto_dim_config();
 
-    // Initialize display controller
-    let display = DisplayController::new().await?;
+    // Initialize display controllers, one per backlight device (or just the power output)
+    let displays = Displays::detect(&config_manager, PowerControl::new(config_manager.clone())).await?;
+
+    // Every core loop beats a heartbeat; systemd restarts nyx if one of them stalls
+    let watchdog = shq_systemd::Watchdog::new();
 
-    // Initialize touch monitor
-    let touch_monitor = TouchMonitor::new();
-    touch_monitor.start().await?;
+    // Initialize touch monitor; it keeps looking for touch devices as they come and go
+    let touch_monitor = TouchMonitor::new(config_manager.clone());
+    touch_monitor.start(watchdog.heartbeat("touch"));
+
+    // PIR or proximity sensor, if one is configured
+    let presence = PresenceSensor::new(config_manager.clone());
+    presence.start();
 
     // Initialize auto-dim manager
-    let auto_dim = AutoDimManager::new(auto_dim_config, display.clone(), touch_monitor.clone());
-    auto_dim.start().await?;
+    let auto_dim = AutoDimManager::new(
+        auto_dim_config,
+        config_manager.get_display_auto_dim_configs(),
+        displays.clone(),
+        touch_monitor.clone(),
+        presence.clone(),
+    );
+    auto_dim.start(watchdog.heartbeat("auto-dim")).await?;
+
+    // Burn-in protection once the panel has been idle a while
+    let cdp = CdpClient::new();
+    let screensaver = Screensaver::new(config_manager.clone(), touch_monitor.clone(), cdp.clone());
+    screensaver.start();
+
+    // Warmer colours in the evening, by command, schedule or sunset
+    let night_mode = NightMode::new(config_manager.clone(), cdp.clone());
+    night_mode.start();
 
-    // Set display to bright level on startup
-    let config = auto_dim.get_config().await;
-    if let Err(e) = display.set_brightness(config.bright_level).await {
-        tracing::warn!("Failed to set initial brightness: {}", e);
+    // Console errors and exceptions from the page, for get_page_errors
+    let page_errors = PageErrors::new(cdp.clone());
+    page_errors.start();
+
+    // Restart Chrome when it stops answering heartbeats
+    let browser_watchdog = BrowserWatchdog::new(config_manager.clone(), cdp.clone());
+    browser_watchdog.start();
+
+    // Diagnostics page in place of a kiosk URL that won't load (optional)
+    let fallback = Fallback::new(cdp.clone());
+    if let Some(fallback_config) = config_manager.get_fallback_config() {
+        fallback.start(fallback_config).await?;
+    }
+
+    // Hand edits to the config file are applied below, once everything is running
+    config_manager.watch();
+    let mut config_rx = config_manager.subscribe();
+
+    // Set each display to its bright level on startup
+    for display in displays.iter() {
+        let id = display.id();
+        let config = auto_dim.get_config(Some(id)).await;
+        if let Err(e) = display.set_brightness(config.bright_level).await {
+            tracing::warn!("Failed to set initial brightness of display {}: {}", id, e);
+        }
     }
 
     // Create and start WebSocket server
-    let server = Arc::new(WebSocketServer::new(
-        addr,
-        display.clone(),
+    let broadcaster = shq_ws::Broadcaster::new("nyx");
+    websocket::start_log_forwarder(log_handle.clone(), broadcaster.clone());
+    websocket::start_gesture_forwarder(touch_monitor.subscribe_gestures(), broadcaster.clone());
+    websocket::start_page_error_forwarder(page_errors.subscribe(), broadcaster.clone());
+    websocket::start_auto_dim_forwarder(auto_dim.subscribe_transitions(), broadcaster.clone());
+    websocket::start_touch_activity_forwarder(touch_monitor.clone(), config_manager.clone(), broadcaster.clone());
+
+    let mut schedule_config = config_manager.get_schedule_config();
+    let schedule_state_path = config_manager.schedule_state_path();
+    let rate_limiter = RateLimiter::new(config_manager.get_rate_limit_config());
+    let mut i18n_config = config_manager.get_i18n_config();
+    let translator = Translator::new(LOCALES, &i18n_config);
+    let http_translator = translator.clone();
+    let handler = WebSocketHandler::new(
+        displays.clone(),
         auto_dim.clone(),
-        config_manager,
-    ));
+        touch_monitor.clone(),
+        screensaver.clone(),
+        night_mode.clone(),
+        page_errors.clone(),
+        cdp,
+        browser_watchdog.clone(),
+        fallback.clone(),
+        presence.clone(),
+        config_manager.clone(),
+        log_handle.clone(),
+        broadcaster.clone(),
+        translator.clone(),
+        audit.clone(),
+    );
 
-    // Spawn server task
-    let server_clone = server.clone();
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = server_clone.start().await {
-            tracing::error!("WebSocket server error: {}", e);
+    // Push metrics whenever they change, e.g. after auto-dim or a touch wake
+    websocket::start_metrics_broadcaster(handler.clone(), watchdog.heartbeat("metrics"));
+
+    // Tell clients when the browser watchdog's view of Chrome changes
+    let mut browser_rx = browser_watchdog.subscribe();
+    let browser_handler = handler.clone();
+    tokio::spawn(async move {
+        while browser_rx.changed().await.is_ok() {
+            browser_handler.broadcast_metrics().await;
         }
     });
 
-    // Wait for shutdown signal
-    match signal::ctrl_c().await {
-        Ok(()) => {
-            tracing::info!("Received shutdown signal");
+    // Tell clients when the fallback page goes up or comes down
+    let mut fallback_rx = fallback.subscribe();
+    let fallback_handler = handler.clone();
+    tokio::spawn(async move {
+        while fallback_rx.changed().await.is_ok() {
+            fallback_handler.broadcast_metrics().await;
         }
-        Err(err) => {
-            tracing::error!("Unable to listen for shutdown signal: {}", err);
+    });
+
+    // Wake the displays when someone walks up, and tell clients whenever presence changes
+    let mut presence_rx = presence.subscribe();
+    let presence_handler = handler.clone();
+    let presence_auto_dim = auto_dim.clone();
+    let presence_config = config_manager.clone();
+    tokio::spawn(async move {
+        let mut present = false;
+        while presence_rx.changed().await.is_ok() {
+            let now_present = presence_rx.borrow_and_update().as_ref().is_some_and(|status| status.present);
+            if now_present && !present && presence_config.get_presence_config().wake {
+                if let Err(e) = presence_auto_dim.wake(None).await {
+                    tracing::error!("Failed to wake on presence: {}", e);
+                }
+            }
+            present = now_present;
+            presence_handler.broadcast_metrics().await;
         }
+    });
+
+    // Scheduled wake/sleep/navigation
+    let scheduler = schedule::start(
+        schedule_config.clone(),
+        schedule_state_path,
+        handler.clone(),
+        audit.clone(),
+    );
+
+    // Apply auto-dim, brightness curve, logging, schedule, rate limit and locale changes made by
+    // hand-editing the config file
+    let reload_auto_dim = auto_dim.clone();
+    let reload_displays = displays.clone();
+    let reload_log_handle = log_handle.clone();
+    let reload_rate_limiter = rate_limiter.clone();
+    tokio::spawn(async move {
+        while config_rx.changed().await.is_ok() {
+            let config = config_rx.borrow_and_update().clone();
+            reload_auto_dim
+                .set_configs(config.auto_dim.clone(), config.display_auto_dim())
+                .await;
+            reload_displays.set_curves(&config).await;
+            reload_rate_limiter.update(config.rate_limit);
+
+            if config.schedule != schedule_config {
+                scheduler.update(config.schedule.clone());
+                schedule_config = config.schedule;
+            }
+
+            if config.i18n != i18n_config {
+                if let Err(e) = translator.update(&config.i18n) {
+                    tracing::warn!("Failed to apply i18n config: {:#}", e);
+                }
+                i18n_config = config.i18n;
+            }
+
+            if config.logging != logging_config {
+                if let Err(e) = reload_log_handle.apply(&config.logging) {
+                    tracing::warn!("Failed to apply logging config: {:#}", e);
+                }
+                logging_config = config.logging;
+            }
+        }
+    });
+
+    // Home Assistant light and screen switch (optional)
+    if let Some(mqtt_config) = config_manager.get_mqtt_config() {
+        mqtt::start(&mqtt_config, handler.clone(), displays.clone(), audit.clone());
+    }
+
+    // Plain HTTP API (optional), sharing auth, limits and commands with the WebSocket API
+    let http_config = config_manager.get_http_config();
+    let http_server = match &http_config {
+        Some(http_config) => {
+            let http_addr: SocketAddr = format!("{}:{}", http_config.host, http_config.port).parse()?;
+            let api = HttpApi {
+                handler: handler.clone(),
+                displays: displays.clone(),
+                config_manager: config_manager.clone(),
+                tr: http_translator,
+                audit: audit.clone(),
+                rate_limiter: rate_limiter.clone(),
+            };
+            Some(HttpServer::bind(http_addr, api).await?)
+        }
+        None => None,
+    };
+
+    let mut server = shq_ws::Server::bind(addr, handler, broadcaster)
+        .await?
+        .rate_limit(rate_limiter)
+        .keep_alive(&ws_config.keep_alive);
+    if let Some(tls) = &ws_config.tls {
+        server = server.tls(tls)?;
+    }
+    shq_systemd::notify_ready();
+    shq_systemd::notify_status(&format!("Serving on {}", addr));
+
+    // Announce _nyx._tcp so panels and peers can find this display without its address
+    let protocol = PROTOCOL_VERSION.to_string();
+    let http_port = http_config.map(|http| http.port.to_string());
+    let mut txt = vec![
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("protocol", protocol.as_str()),
+        ("tls", if ws_config.tls.is_some() { "true" } else { "false" }),
+    ];
+    if let Some(http_port) = &http_port {
+        txt.push(("http_port", http_port));
     }
+    let mdns = Advertisement::start(&mdns_config, "nyx", port, &txt);
+
+    // Spawn server task; it stops accepting and closes connections once shutdown starts
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server_handle = tokio::spawn(async move {
+        let shutdown = async {
+            let _ = shutdown_rx.await;
+        };
+        if let Err(e) = server.run(shutdown).await {
+            tracing::error!("WebSocket server error: {}", e);
+        }
+    });
+    let (http_shutdown_tx, http_shutdown_rx) = oneshot::channel::<()>();
+    let http_handle = http_server.map(|http_server| {
+        tokio::spawn(async move {
+            let shutdown = async {
+                let _ = http_shutdown_rx.await;
+            };
+            if let Err(e) = http_server.run(shutdown).await {
+                tracing::error!("HTTP API error: {}", e);
+            }
+        })
+    });
+    watchdog.start();
 
-    // Cleanup
+    // Wait for SIGTERM/SIGINT
+    shq_systemd::shutdown_signal().await;
+
+    // Cleanup: disconnect clients first, then stop the background loops
     tracing::info!("Shutting down...");
+    shq_systemd::notify_stopping();
+    if let Some(mdns) = mdns {
+        mdns.stop();
+    }
+    let _ = shutdown_tx.send(());
+    let _ = http_shutdown_tx.send(());
+    let _ = server_handle.await;
+    if let Some(http_handle) = http_handle {
+        let _ = http_handle.await;
+    }
     auto_dim.stop();
+    screensaver.stop();
+    night_mode.stop();
+    page_errors.stop();
+    browser_watchdog.stop();
+    fallback.stop();
+    presence.stop();
     touch_monitor.stop();
-    server_handle.abort();
+    audit.record(AuditEvent::new(AuditKind::Service, "stopped"));
 
     tracing::info!("Shutdown complete");
     Ok(())
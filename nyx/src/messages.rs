@@ -1,34 +1,279 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shq_audit::{AuditEvent, AuditKind, AuditPage};
+use shq_auth::Role;
+use shq_config::ConfigArchive;
+use shq_i18n::BuiltinLocales;
+use shq_logging::LogLine;
+use shq_protocol::{Health, SceneReport, ServiceInfo};
+
+use crate::cdp::{KeyAction, Tab, TouchAction};
+use crate::gesture::Gesture;
+
+/// WebSocket protocol revision, reported in the `hello` handshake
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features advertised in the `hello` handshake
+pub const CAPABILITIES: &[&str] = &[
+    "auto_dim", "navigate", "get_url", "logs", "health", "scenes", "envelope", "rate_limit",
+    "config_backup", "i18n", "audit", "multi_display", "screensaver",
+    "tabs", "browser_watchdog", "screenshot", "automation", "gestures",
+    "presence", "power", "night_mode", "touch_hotplug", "touch_lock", "touch_activity", "auto_dim_state",
+    "page_errors", "profiles", "auth", "fallback",
+];
+
+/// Built-in catalogs for client-facing messages (`i18n.locale`)
+pub const LOCALES: BuiltinLocales = &[
+    ("en", include_str!("../locales/en.yaml")),
+    ("de", include_str!("../locales/de.yaml")),
+];
 
 /// Client-to-server command messages
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
-    SetDisplay { state: bool },
-    SetBrightness { brightness: u8 },
+    /// Version and capability handshake (`client` is an optional name for logging, `features`
+    /// the optional features the client wants to use)
+    #[serde(alias = "get_info")]
+    Hello {
+        client: Option<String>,
+        features: Option<Vec<String>>,
+    },
+    /// `display` picks one backlight by id; without it the command acts on every display
+    SetDisplay { display: Option<String>, state: bool },
+    SetBrightness { display: Option<String>, brightness: u8 },
+    /// Switch display power (DPMS) without touching the backlight (requires `power.backend`)
+    SetPower { display: Option<String>, state: PowerState },
+    /// Warm the page's colours for the evening; `temperature` in kelvin (1000-6500), the
+    /// configured one if left out
+    SetNightMode { enabled: bool, temperature: Option<u32> },
+    /// Switch to one of the configured `profiles` (page, brightness, auto-dim, night mode)
+    SetProfile { name: String },
     GetMetrics,
+    /// Without `display`, sets the auto-dim config of every display that has none of its own
     SetAutoDimConfig {
+        display: Option<String>,
         dim_level: u8,
         bright_level: u8,
         auto_dim_time: u32,
         auto_off_time: u32,
     },
-    GetAutoDimConfig,
-    Wake,
-    Sleep,
+    GetAutoDimConfig { display: Option<String> },
+    Wake { display: Option<String> },
+    Sleep { display: Option<String> },
     Navigate { url: String },
     GetUrl,
+    /// Chrome's open tabs; navigation and reloads go to the `active` one
+    ListTabs,
+    /// Open a tab at `url` and make it the active one
+    NewTab { url: String },
+    CloseTab { tab: String },
+    /// Bring a tab to the front and make it the active one
+    ActivateTab { tab: String },
+    Reload,
+    /// Run JavaScript in the active tab and return its value (requires `automation.enabled`)
+    EvaluateJs { token: Option<String>, expression: String },
+    /// Synthetic touch at CSS pixel (`x`, `y`) in the active tab (requires `automation.enabled`)
+    DispatchTouch {
+        token: Option<String>,
+        #[serde(default)]
+        action: TouchAction,
+        x: f64,
+        y: f64,
+    },
+    /// Synthetic key event in the active tab (requires `automation.enabled`)
+    DispatchKey {
+        token: Option<String>,
+        #[serde(default)]
+        action: KeyAction,
+        key: String,
+    },
+    /// Capture what the active tab shows; with `file`, saved under `screenshots/` next to the
+    /// config instead of returned
+    Screenshot {
+        #[serde(default)]
+        format: ScreenshotFormat,
+        /// 0-100, jpeg only
+        quality: Option<u8>,
+        file: Option<String>,
+    },
+    /// The newest page errors Chrome reported (console errors, uncaught exceptions, log
+    /// errors), oldest first; `limit` defaults to all that are kept
+    GetPageErrors { limit: Option<usize> },
+    /// Stream page errors to this client as they happen
+    SubscribePageErrors,
+    UnsubscribePageErrors,
+    /// Component health (backlight, touch device, Chrome)
+    GetHealth,
+    /// Run a configured scene and report each action's result
+    RunScene { scene: String },
+    /// Stream live log output to this client (requires `logging.stream.enabled`)
+    SubscribeLogs {
+        token: Option<String>,
+        /// Number of recent lines to send first (default 100)
+        lines: Option<usize>,
+    },
+    UnsubscribeLogs,
+    /// Ignore all touch input, with the display left as it is, until `unlock_touch` with the
+    /// same PIN
+    LockTouch { pin: String },
+    UnlockTouch { pin: String },
+    /// Stream debounced touch activity and idle transitions to this client
+    SubscribeTouch,
+    UnsubscribeTouch,
+    /// Stream touchscreen gestures (swipes, long-presses, taps) to this client
+    SubscribeGestures,
+    UnsubscribeGestures,
+    /// Dump the full config as an archive (requires `backup.enabled`)
+    ExportConfig { token: Option<String> },
+    /// Replace the config from an archive (requires `backup.enabled`); `entry` picks one when
+    /// the archive holds several nyx configs
+    ImportConfig {
+        token: Option<String>,
+        archive: ConfigArchive,
+        entry: Option<String>,
+    },
+    /// Read the audit log (requires `audit.query.enabled`); without `after_seq` the newest
+    /// `limit` records (default 100) are returned
+    GetAuditLog {
+        token: Option<String>,
+        after_seq: Option<u64>,
+        limit: Option<usize>,
+        kind: Option<AuditKind>,
+    },
     Noop,
 }
 
+impl ClientMessage {
+    /// Role a client needs to send this message (`auth`); anything not listed as a query
+    /// needs `control`, so new commands are restricted until they're deliberately opened up
+    pub fn required_role(&self) -> Role {
+        match self {
+            ClientMessage::Hello { .. }
+            | ClientMessage::GetMetrics
+            | ClientMessage::GetAutoDimConfig { .. }
+            | ClientMessage::GetUrl
+            | ClientMessage::ListTabs
+            | ClientMessage::GetPageErrors { .. }
+            | ClientMessage::SubscribePageErrors
+            | ClientMessage::UnsubscribePageErrors
+            | ClientMessage::GetHealth
+            | ClientMessage::SubscribeLogs { .. }
+            | ClientMessage::UnsubscribeLogs
+            | ClientMessage::SubscribeTouch
+            | ClientMessage::UnsubscribeTouch
+            | ClientMessage::SubscribeGestures
+            | ClientMessage::UnsubscribeGestures
+            | ClientMessage::GetAuditLog { .. }
+            | ClientMessage::Noop => Role::Read,
+            _ => Role::Control,
+        }
+    }
+
+    /// Audit event for privileged commands (`None` for read-only ones); tokens are left out
+    pub fn audit_event(&self) -> Option<AuditEvent> {
+        let (action, detail) = match self {
+            ClientMessage::SetDisplay { display, state } => {
+                ("set_display", Some(with_display(json!({ "state": state }), display)))
+            }
+            ClientMessage::SetPower { display, state } => {
+                ("set_power", Some(with_display(json!({ "state": state }), display)))
+            }
+            ClientMessage::SetNightMode { enabled, temperature } => {
+                ("set_night_mode", Some(json!({ "enabled": enabled, "temperature": temperature })))
+            }
+            ClientMessage::SetProfile { name } => ("set_profile", Some(json!({ "name": name }))),
+            ClientMessage::SetAutoDimConfig {
+                display,
+                dim_level,
+                bright_level,
+                auto_dim_time,
+                auto_off_time,
+            } => {
+                let detail = json!({
+                    "dim_level": dim_level,
+                    "bright_level": bright_level,
+                    "auto_dim_time": auto_dim_time,
+                    "auto_off_time": auto_off_time,
+                });
+                ("set_auto_dim_config", Some(with_display(detail, display)))
+            }
+            ClientMessage::Wake { display } => ("wake", display.as_ref().map(|d| json!({ "display": d }))),
+            ClientMessage::Sleep { display } => ("sleep", display.as_ref().map(|d| json!({ "display": d }))),
+            ClientMessage::Navigate { url } => ("navigate", Some(json!({ "url": url }))),
+            ClientMessage::NewTab { url } => ("new_tab", Some(json!({ "url": url }))),
+            ClientMessage::CloseTab { tab } => ("close_tab", Some(json!({ "tab": tab }))),
+            ClientMessage::ActivateTab { tab } => ("activate_tab", Some(json!({ "tab": tab }))),
+            ClientMessage::Reload => ("reload", None),
+            ClientMessage::EvaluateJs { expression, .. } => ("evaluate_js", Some(json!({ "expression": expression }))),
+            ClientMessage::DispatchTouch { action, x, y, .. } => {
+                ("dispatch_touch", Some(json!({ "action": action, "x": x, "y": y })))
+            }
+            ClientMessage::DispatchKey { action, key, .. } => {
+                ("dispatch_key", Some(json!({ "action": action, "key": key })))
+            }
+            ClientMessage::Screenshot { file, .. } => ("screenshot", file.as_ref().map(|f| json!({ "file": f }))),
+            ClientMessage::RunScene { scene } => ("run_scene", Some(json!({ "scene": scene }))),
+            ClientMessage::LockTouch { .. } => ("lock_touch", None),
+            ClientMessage::UnlockTouch { .. } => ("unlock_touch", None),
+            ClientMessage::SubscribeLogs { .. } => ("subscribe_logs", None),
+            ClientMessage::ExportConfig { .. } => ("export_config", None),
+            ClientMessage::ImportConfig { entry, .. } => ("import_config", Some(json!({ "entry": entry }))),
+            ClientMessage::GetAuditLog { .. } => ("get_audit_log", None),
+            _ => return None,
+        };
+
+        let event = AuditEvent::new(AuditKind::Command, action);
+        Some(match detail {
+            Some(detail) => event.detail(detail),
+            None => event,
+        })
+    }
+}
+
+/// Add the `display` a command was sent to, if it named one, to its audit detail
+fn with_display(mut detail: Value, display: &Option<String>) -> Value {
+    if let (Some(fields), Some(display)) = (detail.as_object_mut(), display) {
+        fields.insert("display".to_string(), json!(display));
+    }
+    detail
+}
+
 /// Server-to-client response messages
+// Built to be serialized straight away, so `Metrics` being much the largest costs nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerMessage {
+    /// Handshake reply: service, version, protocol revision and capabilities
+    Hello(ServiceInfo),
+    /// Component health report
+    Health(Health),
+    /// `display` and `auto_dim` are the primary display's; `displays` lists every display
     Metrics {
         version: String,
         display: DisplayMetrics,
         auto_dim: AutoDimStatus,
+        displays: Vec<DisplayStatus>,
+        /// The screensaver has taken over the page
+        screensaver: bool,
+        night_mode: NightModeStatus,
+        /// Profile last switched to since startup, while it exists
+        #[serde(skip_serializing_if = "Option::is_none")]
+        profile: Option<String>,
+        /// Input devices being read for touches
+        touch_devices: Vec<TouchDevice>,
+        /// `lock_touch` is ignoring touch input
+        touch_locked: bool,
+        /// Presence sensor reading, when `presence.input` is set
+        #[serde(skip_serializing_if = "Option::is_none")]
+        presence: Option<PresenceStatus>,
+        /// Browser watchdog state, when `browser_watchdog` is enabled
+        #[serde(skip_serializing_if = "Option::is_none")]
+        browser: Option<BrowserStatus>,
+        /// Why the fallback page is showing in place of the kiosk URL, while it is
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fallback: Option<FallbackStatus>,
         #[serde(skip_serializing_if = "Option::is_none")]
         url: Option<String>,
     },
@@ -40,6 +285,58 @@ pub enum ServerMessage {
         #[serde(skip_serializing_if = "Option::is_none")]
         url: Option<String>,
     },
+    /// Chrome's open tabs, sent in reply to `list_tabs`
+    Tabs {
+        tabs: Vec<Tab>,
+    },
+    /// The tab `new_tab` opened
+    Tab {
+        tab: Tab,
+    },
+    /// Value of an `evaluate_js` expression (promises are awaited; `null` if not serializable)
+    JsResult {
+        value: Value,
+    },
+    /// Reply to `screenshot`: base64 image `data`, or the `path` it was saved to
+    Screenshot {
+        format: ScreenshotFormat,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+    },
+    /// Recent page errors, sent in reply to `get_page_errors`
+    PageErrors {
+        errors: Vec<PageError>,
+    },
+    /// Page error as it happens, for clients that sent `subscribe_page_errors`
+    PageError(PageError),
+    /// Per-action results of a `run_scene`
+    SceneResult(SceneReport),
+    /// Recent log lines, sent in reply to `subscribe_logs`
+    Logs {
+        lines: Vec<LogLine>,
+    },
+    /// Live log line for subscribed clients
+    Log(LogLine),
+    /// Someone is using the panel, or has stopped, for clients that sent `subscribe_touch`
+    TouchActivity {
+        state: TouchActivityState,
+        /// When the panel was last touched (Unix seconds)
+        last_touch_time: f64,
+    },
+    /// Touchscreen gesture, for clients that sent `subscribe_gestures`
+    Gesture(Gesture),
+    /// A display moved between auto-dim states, sent to every client
+    AutoDimTransition(AutoDimTransition),
+    /// Full config, sent in reply to `export_config`
+    ConfigArchive {
+        archive: ConfigArchive,
+    },
+    /// Audit records and hash chain state, sent in reply to `get_audit_log`
+    AuditLog(AuditPage),
+    /// Sent to every client right before the server closes connections on shutdown
+    ServerShuttingDown,
     Error {
         message: String,
     },
@@ -48,12 +345,22 @@ pub enum ServerMessage {
 /// Display state and brightness
 #[derive(Debug, Clone, Serialize)]
 pub struct DisplayMetrics {
+    /// Backlight device name
+    pub id: String,
     pub display_on: bool,
     pub brightness: u8,
 }
 
+/// One display's entry in `metrics.displays`
+#[derive(Debug, Clone, Serialize)]
+pub struct DisplayStatus {
+    #[serde(flatten)]
+    pub display: DisplayMetrics,
+    pub auto_dim: AutoDimStatus,
+}
+
 /// Auto-dim configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AutoDimConfig {
     pub dim_level: u8,
     pub bright_level: u8,
@@ -72,6 +379,78 @@ impl Default for AutoDimConfig {
     }
 }
 
+/// Display power for `set_power`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerState {
+    On,
+    Off,
+}
+
+/// Image format of a `screenshot`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenshotFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl ScreenshotFormat {
+    /// CDP format name, also the file extension
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Jpeg => "jpeg",
+        }
+    }
+}
+
+/// How the browser watchdog last found Chrome
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrowserState {
+    /// Not checked yet
+    Unknown,
+    Ok,
+    /// Heartbeats are failing, but not enough of them yet for a restart
+    Unresponsive,
+    /// The restart command ran; waiting out `restart_grace`
+    Restarting,
+}
+
+/// Browser watchdog runtime status
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrowserStatus {
+    pub state: BrowserState,
+    /// Heartbeats failed in a row
+    pub failures: u32,
+    /// Restarts since nyx started
+    pub restarts: u32,
+    /// When the browser was last restarted, in ms since the epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_restart_ms: Option<u64>,
+    /// Why the last heartbeat or restart failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The kiosk URL the fallback page is standing in for, and why
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FallbackStatus {
+    /// URL that failed to load, restored once it's reachable again
+    pub url: String,
+    /// Chrome's network error (e.g. `net::ERR_NAME_NOT_RESOLVED`) or the HTTP status
+    pub reason: String,
+    /// When the URL first failed, in ms since the epoch
+    pub since_ms: u64,
+    /// Reachability checks made since
+    pub checks: u32,
+    /// Why the last check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_error: Option<String>,
+}
+
 /// Auto-dim runtime status
 #[derive(Debug, Clone, Serialize)]
 pub struct AutoDimStatus {
@@ -79,6 +458,110 @@ pub struct AutoDimStatus {
     pub bright_level: u8,
     pub auto_dim_time: u32,
     pub auto_off_time: u32,
+    pub state: AutoDimState,
+    /// `state` is `dimmed`, kept for older clients
     pub is_dimmed: bool,
     pub last_touch_time: f64,
 }
+
+/// Where a display is in the auto-dim cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoDimState {
+    /// At `bright_level`, or wherever it was set by hand
+    Active,
+    /// Down to `dim_level` after `auto_dim_time` idle
+    Dimmed,
+    /// Brightness 0, by `auto_off_time`, `sleep` or by hand
+    Off,
+}
+
+/// What moved a display between auto-dim states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoDimEvent {
+    /// The idle timer was reset: a touch, presence or `unlock_touch`
+    Activity,
+    /// Idle for `auto_dim_time`
+    DimTimeout,
+    /// Idle for `auto_off_time`
+    OffTimeout,
+    /// `wake`, a touch on the sleeping panel, presence or a schedule
+    Wake,
+    /// `sleep`, brightness 0 or a schedule
+    Sleep,
+    /// `set_brightness`, `set_display` or `set_power`
+    Manual,
+}
+
+/// One display's auto-dim state change
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoDimTransition {
+    pub display: String,
+    pub from: AutoDimState,
+    pub to: AutoDimState,
+    pub event: AutoDimEvent,
+}
+
+/// Error the kiosk page reported through Chrome
+#[derive(Debug, Clone, Serialize)]
+pub struct PageError {
+    /// When Chrome saw it (Unix ms)
+    pub timestamp_ms: u64,
+    pub source: PageErrorSource,
+    pub message: String,
+    /// Script or resource it came from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 0-based line in `url`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+}
+
+/// Where a page error came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageErrorSource {
+    /// `console.error` or a failed `console.assert`
+    Console,
+    /// Uncaught exception or unhandled rejection
+    Exception,
+    /// Browser log entry, e.g. a failed network request or a CSP violation
+    Log,
+}
+
+/// Input device the touch monitor is reading
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TouchDevice {
+    pub path: String,
+    /// Name the device reports, e.g. "FT5406 memory based driver"
+    pub name: String,
+}
+
+/// Whether the panel is being used, in `touch_activity`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchActivityState {
+    /// Touched; repeated at most every `touch.activity_debounce` seconds while it goes on
+    Active,
+    /// Untouched for `touch.idle_after` seconds
+    Idle,
+}
+
+/// Night mode state in `metrics`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NightModeStatus {
+    pub enabled: bool,
+    /// Colour temperature in kelvin
+    pub temperature: u32,
+}
+
+/// Presence sensor reading
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PresenceStatus {
+    /// Someone is there, or was within `presence.hold_time`
+    pub present: bool,
+    /// Why the sensor couldn't be read last time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,128 @@
+use shq_audit::AuditLog;
+use shq_mqtt::{DeviceInfo, Entity, MqttClient, MqttCommand, MqttConfig};
+use tokio::time::{interval, Duration};
+
+use crate::display::Displays;
+use crate::messages::{ClientMessage, ServerMessage};
+use crate::websocket::WebSocketHandler;
+
+const LIGHT: &str = "display";
+const SWITCH: &str = "screen";
+
+/// How often the primary display's state is checked for changes to publish
+const STATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Expose the displays to Home Assistant as a light (on/off and brightness) and a screen switch
+///
+/// Light `ON`/`OFF` runs `wake`/`sleep` and brightness runs `set_brightness`; the switch runs
+/// `set_display`. They go through the same handler as WebSocket commands, acting on every
+/// display, and are recorded in the audit log with client "mqtt". State follows the primary
+/// display.
+pub fn start(config: &MqttConfig, handler: WebSocketHandler, displays: Displays, audit: AuditLog) {
+    let entities = vec![
+        Entity::light(LIGHT, "Display"),
+        Entity::switch(SWITCH, "Screen").with("icon", "mdi:monitor"),
+    ];
+
+    let device = DeviceInfo {
+        service: "nyx".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let (client, mut commands) = MqttClient::connect(config, device, entities);
+
+    // Publish display state changes, whatever made them (auto-dim, touch, commands)
+    tokio::spawn(async move {
+        let mut ticker = interval(STATE_INTERVAL);
+        let mut last = None;
+        loop {
+            ticker.tick().await;
+            let Some(primary) = displays.iter().next() else {
+                continue;
+            };
+            match (primary.get_display_state().await, primary.get_brightness().await) {
+                (Ok(on), Ok(brightness)) => publish_state(&client, on, brightness, &mut last),
+                (Err(e), _) | (_, Err(e)) => tracing::debug!("MQTT state unavailable: {}", e),
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(command) = commands.recv().await {
+            let Some(message) = parse_command(&command) else {
+                continue;
+            };
+            run_command(&handler, &audit, message).await;
+        }
+    });
+}
+
+/// Publish light and switch state if the display changed since the last publish
+fn publish_state(client: &MqttClient, on: bool, brightness: u8, last: &mut Option<(bool, u8)>) {
+    if *last == Some((on, brightness)) {
+        return;
+    }
+    let state = if on { "ON" } else { "OFF" };
+    client.publish(LIGHT, "state", state);
+    // HA keeps the last brightness of a light that's off, so only report it while on
+    if on {
+        client.publish(LIGHT, "brightness", brightness.to_string());
+    }
+    client.publish(SWITCH, "state", state);
+    *last = Some((on, brightness));
+}
+
+/// The display command a message from Home Assistant asks for
+fn parse_command(command: &MqttCommand) -> Option<ClientMessage> {
+    let payload = command.payload.trim();
+    let on = || match payload.to_ascii_uppercase().as_str() {
+        "ON" => Some(true),
+        "OFF" => Some(false),
+        other => {
+            tracing::warn!("Ignoring unknown MQTT {} command '{}'", command.object_id, other);
+            None
+        }
+    };
+
+    match (command.object_id.as_str(), command.topic.as_str()) {
+        (LIGHT, "set") => Some(match on()? {
+            true => ClientMessage::Wake { display: None },
+            false => ClientMessage::Sleep { display: None },
+        }),
+        (LIGHT, "brightness/set") => match payload.parse::<u8>() {
+            Ok(brightness) => Some(ClientMessage::SetBrightness { display: None, brightness }),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid MQTT brightness '{}'", payload);
+                None
+            }
+        },
+        (SWITCH, "set") => Some(ClientMessage::SetDisplay {
+            display: None,
+            state: on()?,
+        }),
+        (object_id, topic) => {
+            tracing::debug!("Ignoring MQTT topic '{}/{}'", object_id, topic);
+            None
+        }
+    }
+}
+
+/// Run a display command from Home Assistant
+async fn run_command(handler: &WebSocketHandler, audit: &AuditLog, message: ClientMessage) {
+    let event = message.audit_event().map(|event| event.client("mqtt"));
+    let error = match handler.execute(message).await {
+        Ok(ServerMessage::Error { message }) => Some(message),
+        Ok(_) => None,
+        Err(e) => Some(format!("{:#}", e)),
+    };
+
+    if let Some(error) = &error {
+        tracing::error!("MQTT command failed: {}", error);
+    }
+    if let Some(event) = event {
+        audit.record(match error {
+            Some(error) => event.failed(error),
+            None => event,
+        });
+    }
+}
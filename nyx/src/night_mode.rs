@@ -0,0 +1,190 @@
+use anyhow::Result;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Mutex};
+use tokio::time::{interval, Duration};
+
+use crate::cdp::CdpClient;
+use crate::config::{ConfigManager, SunSchedule};
+
+/// How often the filter is reapplied (pages lose it when they navigate) and the sun checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Id of the element holding the filter, so it can be found and replaced
+const ELEMENT_ID: &str = "nyx-night-mode";
+
+/// Sun altitude, in degrees, at sunrise and sunset (refraction and the sun's radius)
+const SUNSET_ALTITUDE: f64 = -0.833;
+
+/// Blue-light reduction: keeps an SVG colour matrix filter for `night_mode.temperature` on the
+/// kiosk page while `night_mode.enabled` is set, and flips `enabled` at sunset and sunrise
+/// when `night_mode.sun` is set
+///
+/// The filter goes on the root element, so fixed-position content keeps working. The section
+/// is read on every check, so edits apply straight away.
+#[derive(Clone)]
+pub struct NightMode {
+    /// Temperature the page was last filtered to; `None` while unfiltered
+    applied: Arc<Mutex<Option<u32>>>,
+    /// Whether the sun was down at the last check; `None` without a `sun` schedule
+    sun_down: Arc<Mutex<Option<bool>>>,
+    config_manager: ConfigManager,
+    cdp: CdpClient,
+    shutdown: watch::Sender<bool>,
+}
+
+impl NightMode {
+    pub fn new(config_manager: ConfigManager, cdp: CdpClient) -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            applied: Arc::new(Mutex::new(None)),
+            sun_down: Arc::new(Mutex::new(None)),
+            config_manager,
+            cdp,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Start keeping the page filtered
+    pub fn start(&self) {
+        let night_mode = self.clone();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            let mut tick = interval(CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => night_mode.check().await,
+                }
+            }
+        });
+    }
+
+    /// Stop keeping the page filtered (a filter already on the page stays until it navigates)
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Turn night mode on or off, persisting it, and filter the page straight away; if Chrome
+    /// can't be reached the next check catches up
+    pub async fn set(&self, enabled: bool, temperature: Option<u32>) -> Result<()> {
+        self.config_manager.set_night_mode(enabled, temperature).await?;
+        tracing::info!("Night mode {}", if enabled { "on" } else { "off" });
+        if let Err(e) = self.apply().await {
+            tracing::warn!("Night mode can't reach the page: {:#}", e);
+        }
+        Ok(())
+    }
+
+    async fn check(&self) {
+        let config = self.config_manager.get_night_mode_config();
+        let sun_down = config.sun.map(|sun| is_sun_down(sun, SystemTime::now()));
+
+        let mut last = self.sun_down.lock().await;
+        let changed = sun_down.is_some() && *last != sun_down;
+        *last = sun_down;
+        drop(last);
+
+        if let Some(down) = sun_down.filter(|down| changed && *down != config.enabled) {
+            let (state, event) = if down { ("on", "sunset") } else { ("off", "sunrise") };
+            tracing::info!("Night mode {} at {}", state, event);
+            if let Err(e) = self.config_manager.set_night_mode(down, None).await {
+                tracing::warn!("Failed to switch night mode: {:#}", e);
+            }
+        }
+
+        if let Err(e) = self.apply().await {
+            // Chrome restarting is common; the next check tries again
+            tracing::debug!("Night mode can't reach the page: {:#}", e);
+        }
+    }
+
+    /// Put the configured filter on the page, or take it off
+    async fn apply(&self) -> Result<()> {
+        let config = self.config_manager.get_night_mode_config();
+        let mut applied = self.applied.lock().await;
+        if config.enabled {
+            self.cdp.evaluate(&filter_script(config.temperature)).await?;
+            *applied = Some(config.temperature);
+        } else if applied.is_some() {
+            self.cdp
+                .evaluate(&format!("document.getElementById('{}')?.remove()", ELEMENT_ID))
+                .await?;
+            *applied = None;
+        }
+        Ok(())
+    }
+}
+
+/// Script adding the filter for `temperature` to the page; does nothing if it's already there
+fn filter_script(temperature: u32) -> String {
+    let (r, g, b) = kelvin_to_rgb(temperature);
+    format!(
+        r#"(() => {{
+  const current = document.getElementById('{id}');
+  if (current && current.dataset.temperature === '{temperature}') return;
+  current?.remove();
+  const host = document.createElement('div');
+  host.id = '{id}';
+  host.dataset.temperature = '{temperature}';
+  host.style.cssText = 'position:absolute;width:0;height:0;overflow:hidden';
+  host.innerHTML = '<svg xmlns="http://www.w3.org/2000/svg"><filter id="{id}-filter" color-interpolation-filters="sRGB"><feColorMatrix type="matrix" values="{r:.3} 0 0 0 0 0 {g:.3} 0 0 0 0 0 {b:.3} 0 0 0 0 0 1 0"/></filter></svg><style>html{{filter:url(#{id}-filter) !important}}</style>';
+  (document.body || document.documentElement).appendChild(host);
+}})()"#,
+        id = ELEMENT_ID,
+    )
+}
+
+/// Channel multipliers (0-1) for a colour temperature, after Tanner Helland's fit of the
+/// blackbody curve
+fn kelvin_to_rgb(temperature: u32) -> (f64, f64, f64) {
+    let t = temperature as f64 / 100.0;
+    let (r, g) = if t <= 66.0 {
+        (255.0, 99.470_802_586_1 * t.ln() - 161.119_568_166_1)
+    } else {
+        (
+            329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2),
+            288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2),
+        )
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+    };
+    let scale = |channel: f64| channel.clamp(0.0, 255.0) / 255.0;
+    (scale(r), scale(g), scale(b))
+}
+
+/// Whether the sun is below the horizon at `sun`'s location at `now` (low-precision solar
+/// position, good to a few minutes)
+fn is_sun_down(sun: SunSchedule, now: SystemTime) -> bool {
+    let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    // Days since J2000
+    let days = seconds / 86_400.0 - 10_957.5;
+    let deg = PI / 180.0;
+
+    let mean_longitude = (280.460 + 0.985_647_4 * days).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * days).rem_euclid(360.0) * deg;
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin()) * deg;
+    let obliquity = (23.439 - 0.000_000_4 * days) * deg;
+
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let sidereal_time = (280.460_618_37 + 360.985_647_366_29 * days + sun.longitude).rem_euclid(360.0) * deg;
+    let hour_angle = sidereal_time - right_ascension;
+
+    let latitude = sun.latitude * deg;
+    let altitude = (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos()).asin();
+    altitude < SUNSET_ALTITUDE * deg
+}
@@ -0,0 +1,171 @@
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::time::{interval, Duration};
+
+use crate::cdp::{CdpClient, CdpEvent};
+use crate::messages::{PageError, PageErrorSource};
+
+/// How often Chrome's events are read while no command is waiting on the session
+const PUMP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Errors kept for `get_page_errors`
+const HISTORY: usize = 100;
+
+/// Errors buffered per subscriber before the oldest are dropped
+const ERROR_BUFFER: usize = 32;
+
+/// Keeps the last errors the kiosk page reported (console errors, uncaught exceptions, browser
+/// log errors), so a dashboard that went blank can be diagnosed remotely
+///
+/// Events arrive on the shared `CdpClient` session, which is drained every second so they
+/// turn up without waiting for the next command.
+#[derive(Clone)]
+pub struct PageErrors {
+    history: Arc<Mutex<VecDeque<PageError>>>,
+    errors: broadcast::Sender<PageError>,
+    cdp: CdpClient,
+    shutdown: watch::Sender<bool>,
+}
+
+impl PageErrors {
+    pub fn new(cdp: CdpClient) -> Self {
+        let (errors, _) = broadcast::channel(ERROR_BUFFER);
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(HISTORY))),
+            errors,
+            cdp,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Start collecting errors
+    pub fn start(&self) {
+        let page_errors = self.clone();
+        let mut events = self.cdp.subscribe_events();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            let mut tick = interval(PUMP_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => {
+                        if let Err(e) = page_errors.cdp.pump_events().await {
+                            // Chrome restarting is common; the next tick tries again
+                            tracing::debug!("Can't read page events: {:#}", e);
+                        }
+                    }
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            if let Some(error) = page_error(&event) {
+                                page_errors.record(error).await;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!("Missed {} page events", missed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+
+    /// Stop collecting errors
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Errors as they're reported
+    pub fn subscribe(&self) -> broadcast::Receiver<PageError> {
+        self.errors.subscribe()
+    }
+
+    /// The newest `limit` errors (all that are kept without one), oldest first
+    pub async fn recent(&self, limit: Option<usize>) -> Vec<PageError> {
+        let history = self.history.lock().await;
+        let skip = history.len().saturating_sub(limit.unwrap_or(HISTORY));
+        history.iter().skip(skip).cloned().collect()
+    }
+
+    async fn record(&self, error: PageError) {
+        tracing::debug!("Page error ({:?}): {}", error.source, error.message);
+        let mut history = self.history.lock().await;
+        if history.len() == HISTORY {
+            history.pop_front();
+        }
+        history.push_back(error.clone());
+        drop(history);
+        let _ = self.errors.send(error);
+    }
+}
+
+/// The error in `event`, if it reports one
+fn page_error(event: &CdpEvent) -> Option<PageError> {
+    let params = &event.params;
+    let (source, message, location) = match event.method.as_str() {
+        "Runtime.consoleAPICalled" if matches!(params["type"].as_str(), Some("error" | "assert")) => {
+            let args = params["args"].as_array().map(Vec::as_slice).unwrap_or_default();
+            let message = args.iter().map(describe).collect::<Vec<_>>().join(" ");
+            (PageErrorSource::Console, message, &params["stackTrace"]["callFrames"][0])
+        }
+        "Runtime.exceptionThrown" => {
+            let details = &params["exceptionDetails"];
+            let message = details["exception"]["description"]
+                .as_str()
+                .or_else(|| details["text"].as_str())
+                .unwrap_or("Uncaught exception")
+                .to_string();
+            (PageErrorSource::Exception, message, details)
+        }
+        "Log.entryAdded" if params["entry"]["level"] == "error" => {
+            let entry = &params["entry"];
+            let message = entry["text"].as_str().unwrap_or_default().to_string();
+            (PageErrorSource::Log, message, entry)
+        }
+        _ => return None,
+    };
+
+    // CDP timestamps are Unix ms
+    let timestamp_ms = params["timestamp"]
+        .as_f64()
+        .or_else(|| params["entry"]["timestamp"].as_f64())
+        .map(|ms| ms as u64)
+        .unwrap_or_else(now_ms);
+    Some(PageError {
+        timestamp_ms,
+        source,
+        message,
+        url: location["url"].as_str().filter(|url| !url.is_empty()).map(str::to_string),
+        line: location["lineNumber"].as_u64(),
+    })
+}
+
+/// Text for a `console.error` argument (a `RemoteObject`)
+fn describe(arg: &Value) -> String {
+    match &arg["value"] {
+        Value::String(text) => text.clone(),
+        Value::Null => arg["description"]
+            .as_str()
+            .or_else(|| arg["type"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
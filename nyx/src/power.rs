@@ -0,0 +1,134 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+
+use crate::config::{ConfigManager, PowerBackend, PowerConfig};
+
+/// Longest a power command may run
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One output in `wlr-randr --json`
+#[derive(Deserialize)]
+struct WlrOutput {
+    name: String,
+    enabled: bool,
+}
+
+/// Display power (DPMS) through `wlr-randr` or `vcgencmd`, so a monitor really turns off
+/// instead of showing black at backlight 0
+///
+/// The `power` section is read on every call, so edits apply straight away. The last known
+/// state is cached for the auto-dim loop, which can't afford a process per check.
+#[derive(Clone)]
+pub struct PowerControl {
+    config_manager: ConfigManager,
+    state: Arc<Mutex<Option<bool>>>,
+}
+
+impl PowerControl {
+    pub fn new(config_manager: ConfigManager) -> Self {
+        Self {
+            config_manager,
+            state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether a backend is configured
+    pub fn is_enabled(&self) -> bool {
+        self.config_manager.get_power_config().backend != PowerBackend::None
+    }
+
+    /// The configured output, if any
+    pub fn output(&self) -> Option<String> {
+        self.config_manager.get_power_config().output
+    }
+
+    /// Whether brightness 0 should power the output off
+    pub fn follows_brightness(&self) -> bool {
+        self.config_manager.get_power_config().follow_brightness
+    }
+
+    /// Turn the output on or off
+    pub async fn set(&self, on: bool) -> Result<()> {
+        let config = self.config_manager.get_power_config();
+        let state = if on { "1" } else { "0" };
+        match config.backend {
+            PowerBackend::None => bail!("No display power backend configured"),
+            PowerBackend::WlrRandr => {
+                let output = config.output.as_deref().unwrap_or_default();
+                let flag = if on { "--on" } else { "--off" };
+                run(&config, &["--output", output, flag]).await?
+            }
+            PowerBackend::Vcgencmd => match &config.output {
+                Some(output) => run(&config, &["display_power", state, output]).await?,
+                None => run(&config, &["display_power", state]).await?,
+            },
+        };
+
+        tracing::info!("Display power {}", if on { "on" } else { "off" });
+        *self.state.lock().await = Some(on);
+        Ok(())
+    }
+
+    /// Ask the backend whether the output is on; `None` without a backend
+    pub async fn query(&self) -> Result<Option<bool>> {
+        let config = self.config_manager.get_power_config();
+        let on = match config.backend {
+            PowerBackend::None => None,
+            PowerBackend::WlrRandr => {
+                let json = run(&config, &["--json"]).await?;
+                let outputs: Vec<WlrOutput> =
+                    serde_json::from_str(&json).context("Unexpected wlr-randr --json output")?;
+                let output = config.output.as_deref().unwrap_or_default();
+                match outputs.into_iter().find(|candidate| candidate.name == output) {
+                    Some(found) => Some(found.enabled),
+                    None => bail!("wlr-randr has no output {:?}", output),
+                }
+            }
+            PowerBackend::Vcgencmd => {
+                let reply = match &config.output {
+                    Some(output) => run(&config, &["display_power", "-1", output]).await?,
+                    None => run(&config, &["display_power"]).await?,
+                };
+                match reply.trim().strip_prefix("display_power=") {
+                    Some("1") => Some(true),
+                    Some("0") => Some(false),
+                    _ => bail!("Unexpected vcgencmd reply {:?}", reply.trim()),
+                }
+            }
+        };
+
+        *self.state.lock().await = on;
+        Ok(on)
+    }
+
+    /// Last state set or queried; `None` until then, or without a backend
+    pub async fn cached(&self) -> Option<bool> {
+        if !self.is_enabled() {
+            return None;
+        }
+        *self.state.lock().await
+    }
+}
+
+/// Run the backend's program with `args` and return what it printed
+async fn run(config: &PowerConfig, args: &[&str]) -> Result<String> {
+    let program = config.program();
+    let output = timeout(COMMAND_TIMEOUT, Command::new(program).args(args).kill_on_drop(true).output())
+        .await
+        .map_err(|_| anyhow::anyhow!("{} still running after {:?}", program, COMMAND_TIMEOUT))?
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} exited with {}: {}",
+            program,
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, bail, Context, Result};
+use evdev::{Device, InputEventKind};
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tokio::time::{interval, Duration, Instant};
+
+use crate::config::{ConfigManager, PresenceConfig, PresenceInput};
+use crate::messages::PresenceStatus;
+
+/// How often the sensor is read
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads a PIR or proximity sensor, so someone walking up wakes the display and keeps
+/// auto-dim off while they're there
+///
+/// The section is read on every poll, so edits apply straight away.
+#[derive(Clone)]
+pub struct PresenceSensor {
+    status: watch::Sender<Option<PresenceStatus>>,
+    /// When the sensor last saw someone
+    last_seen: Arc<Mutex<Option<Instant>>>,
+    config_manager: ConfigManager,
+    shutdown: watch::Sender<bool>,
+}
+
+impl PresenceSensor {
+    pub fn new(config_manager: ConfigManager) -> Self {
+        let (status, _) = watch::channel(None);
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            status,
+            last_seen: Arc::new(Mutex::new(None)),
+            config_manager,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Start reading the sensor
+    pub fn start(&self) {
+        let sensor = self.clone();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            let mut tick = interval(POLL_INTERVAL);
+            let mut device = None;
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => sensor.check(&mut device).await,
+                }
+            }
+        });
+    }
+
+    /// Stop reading the sensor
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// The current status; `None` without a `presence.input`
+    pub fn status(&self) -> Option<PresenceStatus> {
+        self.status.borrow().clone()
+    }
+
+    /// Notified whenever the status changes
+    pub fn subscribe(&self) -> watch::Receiver<Option<PresenceStatus>> {
+        self.status.subscribe()
+    }
+
+    /// Seconds since someone was last there, for auto-dim; 0 while they are, and `None`
+    /// without a sensor or with `keep_awake` off
+    pub async fn idle_time(&self) -> Option<f64> {
+        let config = self.config_manager.get_presence_config();
+        if config.input.is_none() || !config.keep_awake {
+            return None;
+        }
+        if self.status().is_some_and(|status| status.present) {
+            return Some(0.0);
+        }
+        let last_seen = (*self.last_seen.lock().await)?;
+        let idle = last_seen.elapsed().saturating_sub(Duration::from_secs(config.hold_time));
+        Some(idle.as_secs_f64())
+    }
+
+    async fn check(&self, device: &mut Option<(PathBuf, Device)>) {
+        let config = self.config_manager.get_presence_config();
+        let Some(input) = &config.input else {
+            *device = None;
+            *self.last_seen.lock().await = None;
+            self.publish(None);
+            return;
+        };
+
+        let reading = match input {
+            PresenceInput::Gpio { path } => read_gpio(path).await.map(|active| active != config.invert),
+            PresenceInput::Evdev { device: path } => {
+                // The config may point somewhere else now
+                if device.as_ref().is_some_and(|(open, _)| open != path) {
+                    *device = None;
+                }
+                read_device(device, path)
+            }
+        };
+
+        let mut last_seen = self.last_seen.lock().await;
+        if let Ok(true) = reading {
+            *last_seen = Some(Instant::now());
+        }
+        let present = matches!(reading, Ok(true)) || held(&config, *last_seen);
+        let error = reading.err().map(|e| format!("{:#}", e));
+        if let Some(error) = &error {
+            tracing::debug!("Presence sensor unreadable: {}", error);
+        }
+        drop(last_seen);
+
+        if present != self.status().is_some_and(|status| status.present) {
+            tracing::info!("Presence {}", if present { "detected" } else { "ended" });
+        }
+        self.publish(Some(PresenceStatus { present, error }));
+    }
+
+    fn publish(&self, status: Option<PresenceStatus>) {
+        self.status.send_if_modified(|current| {
+            let changed = *current != status;
+            *current = status;
+            changed
+        });
+    }
+}
+
+/// Whether someone seen at `last_seen` still counts as there
+fn held(config: &PresenceConfig, last_seen: Option<Instant>) -> bool {
+    last_seen.is_some_and(|seen| seen.elapsed() < Duration::from_secs(config.hold_time))
+}
+
+/// Read a GPIO value file ("0" or "1")
+async fn read_gpio(path: &Path) -> Result<bool> {
+    let value = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    match value.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        other => bail!("Unexpected value {:?} in {:?}", other, path),
+    }
+}
+
+/// Drain the events `path` has queued, opening it first if needed; true if there were any
+fn read_device(device: &mut Option<(PathBuf, Device)>, path: &Path) -> Result<bool> {
+    if device.is_none() {
+        let opened = Device::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        fcntl(opened.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+            .map_err(|e| anyhow!("Failed to set {:?} to non-blocking: {}", path, e))?;
+        *device = Some((path.to_path_buf(), opened));
+    }
+    let Some((_, open)) = device.as_mut() else {
+        return Ok(false);
+    };
+
+    let read = match open.fetch_events() {
+        Ok(events) => Ok(events
+            .into_iter()
+            .any(|event| !matches!(event.kind(), InputEventKind::Synchronization(_)))),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(anyhow!("Failed to read {:?}: {}", path, e)),
+    };
+    if read.is_err() {
+        // Reopen on the next poll, e.g. after the device was unplugged
+        *device = None;
+    }
+    read
+}
@@ -0,0 +1,35 @@
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_scheduler::{Scheduler, SchedulerConfig};
+use std::path::PathBuf;
+
+use crate::config::DisplayAction;
+use crate::websocket::WebSocketHandler;
+
+/// Run scheduled display commands through the WebSocket handler, so clients see the
+/// resulting metrics; each run is recorded in the audit log
+pub fn start(
+    config: SchedulerConfig<DisplayAction>,
+    state_path: PathBuf,
+    handler: WebSocketHandler,
+    audit: AuditLog,
+) -> Scheduler<DisplayAction> {
+    let (scheduler, mut triggers) = Scheduler::start(config, Some(state_path));
+
+    tokio::spawn(async move {
+        while let Some(trigger) = triggers.recv().await {
+            let event = AuditEvent::new(AuditKind::Command, "schedule")
+                .client("schedule")
+                .detail(json!({ "job": trigger.job_id, "action": trigger.action }));
+            match handler.run_action(&trigger.action).await {
+                Ok(()) => audit.record(event),
+                Err(e) => {
+                    tracing::error!("Schedule job '{}' failed: {:#}", trigger.job_id, e);
+                    audit.record(event.failed(format!("{:#}", e)));
+                }
+            }
+        }
+    });
+
+    scheduler
+}
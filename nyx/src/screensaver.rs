@@ -0,0 +1,204 @@
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+use tokio::time::{interval, Duration, Instant};
+
+use crate::cdp::CdpClient;
+use crate::config::{ConfigManager, ScreensaverConfig, ScreensaverMode};
+use crate::touch::TouchMonitor;
+
+/// How often the idle time is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Clock page for `clock` mode; the clock moves every minute so it doesn't burn in either
+const CLOCK_PAGE: &str = r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>Clock</title><style>
+html,body{margin:0;height:100%;background:#000;overflow:hidden;cursor:none}
+#clock{position:absolute;color:#888;font:12vw sans-serif;transform:translate(-50%,-50%)}
+</style></head><body><div id="clock"></div><script>
+const clock = document.getElementById('clock');
+function move() {
+  clock.style.left = (25 + Math.random() * 50) + '%';
+  clock.style.top = (25 + Math.random() * 50) + '%';
+}
+function tick() {
+  clock.textContent = new Date().toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' });
+}
+move(); tick();
+setInterval(tick, 1000);
+setInterval(move, 60000);
+</script></body></html>"#;
+
+/// Directions `pixel_shift` steps through, one per `shift_interval`, scaled by `shift_pixels`
+const SHIFT_PATTERN: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+/// A screensaver that's showing
+struct Running {
+    mode: ScreensaverMode,
+    /// Page to go back to; `None` if it couldn't be read, or something navigated since
+    previous_url: Option<String>,
+    shift_step: usize,
+    shifted: Instant,
+}
+
+/// Burn-in protection: after `screensaver.idle_time` without a touch, points Chrome at the
+/// idle URL or the clock page, or keeps shifting the page a few pixels, and puts things back
+/// on the next touch or wake
+///
+/// The section is read on every check, so edits apply straight away.
+#[derive(Clone)]
+pub struct Screensaver {
+    running: Arc<Mutex<Option<Running>>>,
+    config_manager: ConfigManager,
+    touch_monitor: TouchMonitor,
+    cdp: CdpClient,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Screensaver {
+    pub fn new(config_manager: ConfigManager, touch_monitor: TouchMonitor, cdp: CdpClient) -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+
+        Self {
+            running: Arc::new(Mutex::new(None)),
+            config_manager,
+            touch_monitor,
+            cdp,
+            shutdown: shutdown_tx,
+        }
+    }
+
+    /// Start watching the idle time
+    pub fn start(&self) {
+        let screensaver = self.clone();
+        let mut shutdown_rx = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            let mut tick = interval(CHECK_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => screensaver.check().await,
+                }
+            }
+        });
+    }
+
+    /// Stop watching the idle time (a showing screensaver is left as it is)
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Whether the screensaver is showing
+    pub async fn is_active(&self) -> bool {
+        self.running.lock().await.is_some()
+    }
+
+    /// Don't go back to the earlier page when the screensaver ends; call after navigating
+    pub async fn forget_previous_url(&self) {
+        if let Some(running) = self.running.lock().await.as_mut() {
+            running.previous_url = None;
+        }
+    }
+
+    async fn check(&self) {
+        let config = self.config_manager.get_screensaver_config();
+        let idle_time = self.touch_monitor.get_idle_time().await;
+        let idle = config.idle_time > 0 && idle_time >= config.idle_time as f64;
+
+        let mut running = self.running.lock().await;
+        match running.as_mut() {
+            None if idle => {
+                tracing::info!("Screensaver started after {:.0} seconds idle", idle_time);
+                *running = Some(activate(&self.cdp, &config).await);
+            }
+            Some(_) if !idle => {
+                if let Some(previous) = running.take() {
+                    tracing::info!("Screensaver ended");
+                    deactivate(&self.cdp, previous).await;
+                }
+            }
+            Some(current)
+                if current.mode == ScreensaverMode::PixelShift
+                    && current.shifted.elapsed() >= Duration::from_secs(config.shift_interval) =>
+            {
+                current.shift_step = (current.shift_step + 1) % SHIFT_PATTERN.len();
+                current.shifted = Instant::now();
+                shift(&self.cdp, current.shift_step, config.shift_pixels).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn activate(cdp: &CdpClient, config: &ScreensaverConfig) -> Running {
+    let mut running = Running {
+        mode: config.mode,
+        previous_url: None,
+        shift_step: 0,
+        shifted: Instant::now(),
+    };
+
+    let page = match config.mode {
+        ScreensaverMode::PixelShift => {
+            shift(cdp, 0, config.shift_pixels).await;
+            return running;
+        }
+        ScreensaverMode::Url => config.url.clone().unwrap_or_default(),
+        ScreensaverMode::Clock => clock_url(),
+    };
+
+    match cdp.get_current_url().await {
+        Ok(url) => running.previous_url = Some(url),
+        Err(e) => tracing::warn!("Screensaver can't read the current page: {:#}", e),
+    }
+    if let Err(e) = cdp.navigate(&page).await {
+        tracing::warn!("Screensaver failed to navigate: {:#}", e);
+        running.previous_url = None;
+    }
+    running
+}
+
+async fn deactivate(cdp: &CdpClient, running: Running) {
+    if running.mode == ScreensaverMode::PixelShift {
+        if let Err(e) = cdp.evaluate("document.documentElement.style.transform = ''").await {
+            tracing::warn!("Screensaver failed to restore the page position: {:#}", e);
+        }
+        return;
+    }
+
+    if let Some(url) = running.previous_url {
+        if let Err(e) = cdp.navigate(&url).await {
+            tracing::warn!("Screensaver failed to restore {}: {:#}", url, e);
+        }
+    }
+}
+
+/// Move the page to the `step`th offset of `SHIFT_PATTERN`
+async fn shift(cdp: &CdpClient, step: usize, pixels: u32) {
+    let (x, y) = SHIFT_PATTERN[step];
+    let pixels = pixels as i32;
+    let expression = format!(
+        "document.documentElement.style.transform = 'translate({}px, {}px)'",
+        x * pixels,
+        y * pixels
+    );
+    if let Err(e) = cdp.evaluate(&expression).await {
+        tracing::warn!("Screensaver failed to shift the page: {:#}", e);
+    }
+}
+
+/// `CLOCK_PAGE` as a `data:` URL
+fn clock_url() -> String {
+    let mut url = String::from("data:text/html;charset=utf-8,");
+    for byte in CLOCK_PAGE.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
@@ -1,40 +1,120 @@
 use anyhow::{anyhow, Result};
-use evdev::{Device, EventType, InputEventKind};
+use evdev::{AbsoluteAxisType, Device, EventType, InputEventKind};
 use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use shq_auth::constant_time_eq;
+use shq_protocol::ComponentHealth;
+use shq_ratelimit::{RateLimitConfig, RateLimited, RateLimiter};
+use shq_systemd::Heartbeat;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
 use std::os::unix::io::AsRawFd;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as SyncMutex};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::{watch, Mutex};
-use tokio::task;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::{self, JoinHandle};
+use tokio::time::{interval, Duration, Instant};
 
-/// Touch monitor for detecting touch events
+use crate::config::{ConfigManager, PresenceInput};
+use crate::gesture::{AxisRange, Gesture, GestureDecoder};
+use crate::messages::TouchDevice;
+
+/// Gestures buffered per subscriber before the oldest are dropped
+const GESTURE_BUFFER: usize = 32;
+
+/// How often input devices are rescanned for ones plugged in, unplugged or configured
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a reader may go without polling its device before it counts as stuck
+const READER_STALL: Duration = Duration::from_secs(10);
+
+/// Unlock attempts allowed in a row before they're limited to one per [`PIN_RETRY_SECS`]
+const PIN_ATTEMPTS: u32 = 5;
+
+/// Seconds between unlock attempts once [`PIN_ATTEMPTS`] are used up
+const PIN_RETRY_SECS: f64 = 30.0;
+
+/// Why `unlock` refused
+#[derive(Debug)]
+pub enum UnlockRefused {
+    WrongPin,
+    /// Too many attempts; PINs aren't checked until the retry time is up
+    TooManyAttempts(RateLimited),
+}
+
+/// A device being read by its own task
+struct Monitored {
+    name: String,
+    task: JoinHandle<()>,
+    /// When the reader last polled the device
+    polled: Arc<SyncMutex<Instant>>,
+}
+
+/// Touch monitor for detecting touch events and decoding gestures from them
+///
+/// Every touchscreen found (or each of `touch.devices`) is read by its own task; devices are
+/// rescanned every couple of seconds, so ones plugged in later are picked up and unplugged
+/// ones dropped without a restart.
 #[derive(Clone)]
 pub struct TouchMonitor {
     last_touch: Arc<Mutex<f64>>,
     shutdown: watch::Sender<bool>,
     should_block: Arc<Mutex<bool>>,
+    /// PIN `lock_touch` set; while there is one every device is grabbed and its input ignored
+    lock_pin: Arc<Mutex<Option<String>>>,
+    /// One bucket for every client's unlock attempts, so guessing the PIN takes hours
+    pin_attempts: RateLimiter,
     wake_tx: Arc<Mutex<Option<tokio::sync::mpsc::UnboundedSender<()>>>>,
+    device_error: Arc<Mutex<Option<String>>>, // Last open/read error, cleared once a device opens
+    devices: Arc<Mutex<BTreeMap<PathBuf, Monitored>>>,
+    /// Time of the last real touch, unlike `last_touch` which wakes and unlocks also reset
+    touched: watch::Sender<Option<f64>>,
+    gestures: broadcast::Sender<Gesture>,
+    config_manager: ConfigManager,
 }
 
 impl TouchMonitor {
     /// Create a new touch monitor
-    pub fn new() -> Self {
+    pub fn new(config_manager: ConfigManager) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs_f64();
 
         let (shutdown_tx, _) = watch::channel(false);
+        let (gestures, _) = broadcast::channel(GESTURE_BUFFER);
+        let (touched, _) = watch::channel(None);
 
         Self {
             last_touch: Arc::new(Mutex::new(now)),
             shutdown: shutdown_tx,
             should_block: Arc::new(Mutex::new(false)),
+            lock_pin: Arc::new(Mutex::new(None)),
+            pin_attempts: RateLimiter::new(RateLimitConfig {
+                enabled: true,
+                rate: 1.0 / PIN_RETRY_SECS,
+                burst: PIN_ATTEMPTS,
+                exempt: Vec::new(),
+            }),
             wake_tx: Arc::new(Mutex::new(None)),
+            device_error: Arc::new(Mutex::new(None)),
+            devices: Arc::new(Mutex::new(BTreeMap::new())),
+            touched,
+            gestures,
+            config_manager,
         }
     }
 
+    /// Gestures recognised while touch isn't blocked
+    pub fn subscribe_gestures(&self) -> broadcast::Receiver<Gesture> {
+        self.gestures.subscribe()
+    }
+
+    /// Notified on every touch (while not locked), with its time
+    pub fn subscribe_touches(&self) -> watch::Receiver<Option<f64>> {
+        self.touched.subscribe()
+    }
+
     /// Set a wake callback that gets called when touch is detected while blocking
     pub async fn set_wake_callback(&self, tx: tokio::sync::mpsc::UnboundedSender<()>) {
         *self.wake_tx.lock().await = Some(tx);
@@ -46,62 +126,95 @@ impl TouchMonitor {
         *self.should_block.lock().await = block;
     }
 
-    /// Start monitoring for touch events
-    pub async fn start(&self) -> Result<()> {
-        let device_path = Self::find_touch_device().await?;
-        tracing::info!("Touch monitor started on device: {:?}", device_path);
+    /// Ignore all touch input until `unlock` with the same `pin`, e.g. while cleaning the
+    /// screen; false if it's already locked
+    pub async fn lock(&self, pin: &str) -> bool {
+        let mut lock_pin = self.lock_pin.lock().await;
+        if lock_pin.is_some() {
+            return false;
+        }
+        tracing::info!("Touch input locked");
+        *lock_pin = Some(pin.to_string());
+        true
+    }
+
+    /// Lift a `lock` (not being locked counts as unlocked)
+    ///
+    /// Every attempt while locked is rate limited, right or wrong, so the PIN can't be
+    /// brute-forced.
+    pub async fn unlock(&self, pin: &str) -> Result<(), UnlockRefused> {
+        let mut lock_pin = self.lock_pin.lock().await;
+        let Some(expected) = lock_pin.as_deref() else {
+            return Ok(());
+        };
+        self.pin_attempts
+            .check(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .map_err(UnlockRefused::TooManyAttempts)?;
+        if !constant_time_eq(expected.as_bytes(), pin.as_bytes()) {
+            tracing::warn!("Wrong PIN for touch unlock");
+            return Err(UnlockRefused::WrongPin);
+        }
+
+        tracing::info!("Touch input unlocked");
+        *lock_pin = None;
+        Ok(())
+    }
+
+    /// Whether touch input is locked
+    pub async fn is_locked(&self) -> bool {
+        self.lock_pin.lock().await.is_some()
+    }
 
-        let last_touch = self.last_touch.clone();
-        let should_block = self.should_block.clone();
-        let wake_tx = self.wake_tx.clone();
+    /// Start monitoring for touch events, beating `heartbeat` after every rescan while no
+    /// reader is stuck
+    pub fn start(&self, heartbeat: Heartbeat) {
+        let monitor = self.clone();
         let mut shutdown_rx = self.shutdown.subscribe();
 
         task::spawn(async move {
+            let mut tick = interval(RESCAN_INTERVAL);
             loop {
-                // Check for shutdown signal with timeout to avoid blocking
-                if tokio::time::timeout(
-                    tokio::time::Duration::from_millis(100),
-                    shutdown_rx.changed()
-                ).await.is_ok() && *shutdown_rx.borrow() {
-                    tracing::info!("Touch monitor shutting down");
-                    break;
-                }
-
-                // Open the device (may need to reopen if disconnected)
-                let device = match Device::open(&device_path) {
-                    Ok(dev) => dev,
-                    Err(e) => {
-                        tracing::warn!("Failed to open touch device: {}, retrying in 5s", e);
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                        continue;
+                tokio::select! {
+                    _ = shutdown_rx.changed() => {
+                        if *shutdown_rx.borrow() {
+                            tracing::info!("Touch monitor shutting down");
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => {
+                        monitor.rescan().await;
+                        if monitor.readers_alive().await {
+                            heartbeat.beat();
+                        }
                     }
-                };
-
-                // Set device to non-blocking mode
-                let fd = device.as_raw_fd();
-                if let Err(e) = fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
-                    tracing::error!("Failed to set device to non-blocking: {}", e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                    continue;
-                }
-
-                // Monitor events
-                if let Err(e) = Self::monitor_events(
-                    device,
-                    last_touch.clone(),
-                    should_block.clone(),
-                    wake_tx.clone(),
-                    &mut shutdown_rx,
-                )
-                .await
-                {
-                    tracing::error!("Touch monitor error: {}, restarting in 5s", e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
                 }
             }
         });
+    }
 
-        Ok(())
+    /// Touch device health; no device means touch-to-wake doesn't work
+    pub async fn health(&self) -> ComponentHealth {
+        if !self.devices().await.is_empty() {
+            return ComponentHealth::ok("touch");
+        }
+        match self.device_error.lock().await.as_ref() {
+            Some(error) => ComponentHealth::degraded("touch", error.clone()),
+            None => ComponentHealth::degraded("touch", "No touch device found"),
+        }
+    }
+
+    /// Devices being read, for metrics
+    pub async fn devices(&self) -> Vec<TouchDevice> {
+        self.devices
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, monitored)| !monitored.task.is_finished())
+            .map(|(path, monitored)| TouchDevice {
+                path: path.display().to_string(),
+                name: monitored.name.clone(),
+            })
+            .collect()
     }
 
     /// Stop the touch monitor
@@ -109,19 +222,95 @@ impl TouchMonitor {
         let _ = self.shutdown.send(true);
     }
 
-    /// Monitor events from the device
-    async fn monitor_events(
-        mut device: Device,
-        last_touch: Arc<Mutex<f64>>,
-        should_block: Arc<Mutex<bool>>,
-        wake_tx: Arc<Mutex<Option<tokio::sync::mpsc::UnboundedSender<()>>>>,
-        shutdown_rx: &mut watch::Receiver<bool>,
-    ) -> Result<()> {
+    /// Start reading devices that appeared and drop ones that went away or were unconfigured
+    async fn rescan(&self) {
+        let wanted = self.find_touch_devices();
+        let mut devices = self.devices.lock().await;
+
+        devices.retain(|path, monitored| {
+            if monitored.task.is_finished() {
+                return false;
+            }
+            let keep = wanted.contains(path);
+            if !keep {
+                tracing::info!("Touch device {:?} no longer wanted", path);
+                monitored.task.abort();
+            }
+            keep
+        });
+
+        for path in wanted {
+            if devices.contains_key(&path) {
+                continue;
+            }
+            match Self::open_device(&path) {
+                Ok(device) => {
+                    let name = device.name().unwrap_or("").to_string();
+                    tracing::info!("Touch monitor started on device: {} at {:?}", name, path);
+                    *self.device_error.lock().await = None;
+                    let polled = Arc::new(SyncMutex::new(Instant::now()));
+                    let task = self.spawn_reader(path.clone(), device, polled.clone());
+                    devices.insert(path, Monitored { name, task, polled });
+                }
+                Err(e) => self.record_error(format!("Failed to open touch device {:?}: {}", path, e)).await,
+            }
+        }
+    }
+
+    /// Whether every reader polled its device recently; a stuck one is logged so the watchdog
+    /// restart that follows has a reason
+    async fn readers_alive(&self) -> bool {
+        let devices = self.devices.lock().await;
+        let stuck: Vec<&PathBuf> = devices
+            .iter()
+            .filter(|(_, monitored)| monitored.polled.lock().unwrap().elapsed() > READER_STALL)
+            .map(|(path, _)| path)
+            .collect();
+        if !stuck.is_empty() {
+            tracing::error!("Touch reader stuck on {:?}", stuck);
+        }
+        stuck.is_empty()
+    }
+
+    /// Open `path` for non-blocking reads
+    fn open_device(path: &Path) -> Result<Device> {
+        let device = Device::open(path)?;
+        fcntl(device.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))
+            .map_err(|e| anyhow!("Failed to set device to non-blocking: {}", e))?;
+        Ok(device)
+    }
+
+    /// Read `device` until it fails (e.g. it was unplugged) or the monitor shuts down; the next
+    /// rescan reopens it if it's still there
+    fn spawn_reader(&self, path: PathBuf, device: Device, polled: Arc<SyncMutex<Instant>>) -> JoinHandle<()> {
+        let monitor = self.clone();
+        task::spawn(async move {
+            if let Err(e) = monitor.monitor_events(device, &polled).await {
+                monitor.record_error(format!("Touch device {:?} failed: {}", path, e)).await;
+            }
+        })
+    }
+
+    /// Remember an open/read error for health, warning only when it's a new one so a missing
+    /// device doesn't log on every rescan
+    async fn record_error(&self, error: String) {
+        let mut device_error = self.device_error.lock().await;
+        if device_error.as_deref() != Some(error.as_str()) {
+            tracing::warn!("{}", error);
+        }
+        *device_error = Some(error);
+    }
+
+    /// Monitor events from the device, stamping `polled` on every pass
+    async fn monitor_events(&self, mut device: Device, polled: &SyncMutex<Instant>) -> Result<()> {
+        let mut shutdown_rx = self.shutdown.subscribe();
         let mut is_grabbed = false;
+        let (x_range, y_range) = Self::axis_ranges(&device);
+        let mut decoder = GestureDecoder::new(x_range, y_range);
 
         loop {
             // Check for shutdown
-            if *shutdown_rx.borrow() {
+            if *shutdown_rx.borrow_and_update() {
                 // Ungrab before exiting
                 if is_grabbed {
                     let _ = device.ungrab();
@@ -129,12 +318,14 @@ impl TouchMonitor {
                 break;
             }
 
-            // Check if we should grab/ungrab the device
-            let should_be_grabbed = *should_block.lock().await;
+            // Check if we should grab/ungrab the device; a lock grabs it without waking on touch
+            let locked = self.is_locked().await;
+            let should_be_grabbed = locked || *self.should_block.lock().await;
             if should_be_grabbed && !is_grabbed {
                 tracing::info!("Grabbing touch device to block events");
                 device.grab().map_err(|e| anyhow!("Failed to grab device: {}", e))?;
                 is_grabbed = true;
+                decoder.reset();
             } else if !should_be_grabbed && is_grabbed {
                 tracing::info!("Ungrabbing touch device to allow events");
                 device.ungrab().map_err(|e| anyhow!("Failed to ungrab device: {}", e))?;
@@ -144,8 +335,16 @@ impl TouchMonitor {
             // Fetch events (non-blocking)
             match device.fetch_events() {
                 Ok(events) => {
-                    // Process events
-                    for event in events {
+                    // Process events; a locked device's input counts for nothing
+                    for event in events.filter(|_| !locked) {
+                        // A touch that wakes the screen isn't a gesture
+                        if !is_grabbed {
+                            if let Some(gesture) = decoder.handle(&event, Instant::now()) {
+                                tracing::debug!("Gesture: {:?}", gesture);
+                                let _ = self.gestures.send(gesture);
+                            }
+                        }
+
                         match event.kind() {
                             InputEventKind::AbsAxis(_) | InputEventKind::Key(_) => {
                                 // Touch event detected
@@ -154,12 +353,13 @@ impl TouchMonitor {
                                     .unwrap()
                                     .as_secs_f64();
 
-                                *last_touch.lock().await = now;
+                                *self.last_touch.lock().await = now;
+                                self.touched.send_replace(Some(now));
                                 tracing::debug!("Touch event detected (blocking={})", is_grabbed);
 
                                 // If we're blocking events, trigger wake callback
                                 if is_grabbed {
-                                    if let Some(tx) = wake_tx.lock().await.as_ref() {
+                                    if let Some(tx) = self.wake_tx.lock().await.as_ref() {
                                         tracing::info!("Touch detected while screen off, triggering wake");
                                         let _ = tx.send(());
                                     }
@@ -177,6 +377,12 @@ impl TouchMonitor {
                 }
             }
 
+            if let Some(gesture) = decoder.poll(Instant::now()) {
+                tracing::debug!("Gesture: {:?}", gesture);
+                let _ = self.gestures.send(gesture);
+            }
+            *polled.lock().unwrap() = Instant::now();
+
             // Sleep briefly to avoid busy-waiting
             tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
         }
@@ -184,27 +390,61 @@ impl TouchMonitor {
         Ok(())
     }
 
-    /// Find the touch input device
-    async fn find_touch_device() -> Result<PathBuf> {
-        let devices = evdev::enumerate();
-
-        // Look for touchscreen device
-        for (path, device) in devices {
-            let name = device.name().unwrap_or("");
+    /// Ranges of the X and Y axes, preferring the multi-touch ones
+    fn axis_ranges(device: &Device) -> (AxisRange, AxisRange) {
+        let supported = device.supported_absolute_axes();
+        let state = device.get_abs_state().ok();
+        let range = |mt: AbsoluteAxisType, single: AbsoluteAxisType| {
+            let axis = match supported {
+                Some(axes) if axes.contains(mt) => mt,
+                _ => single,
+            };
+            state
+                .map(|state| state[axis.0 as usize])
+                .filter(|info| info.maximum > info.minimum)
+                .map(|info| AxisRange { min: info.minimum, max: info.maximum })
+                .unwrap_or(AxisRange { min: 0, max: i32::MAX })
+        };
+        (
+            range(AbsoluteAxisType::ABS_MT_POSITION_X, AbsoluteAxisType::ABS_X),
+            range(AbsoluteAxisType::ABS_MT_POSITION_Y, AbsoluteAxisType::ABS_Y),
+        )
+    }
 
-            // Check if this is a touchscreen
-            if name.to_lowercase().contains("touch")
-                || name.to_lowercase().contains("ft5406")
-                || device
-                    .supported_events()
-                    .contains(EventType::ABSOLUTE)
-            {
-                tracing::info!("Found touch device: {} at {:?}", name, path);
-                return Ok(path);
-            }
+    /// Find the touch input devices to read
+    ///
+    /// `NYX_TOUCH_DEVICE` (one path, or several separated by `:`) skips detection, as does
+    /// `touch.devices`; paths that can't be opened leave touch degraded. An evdev presence
+    /// sensor is never read as a touchscreen, since grabbing it would hide it from the sensor.
+    fn find_touch_devices(&self) -> Vec<PathBuf> {
+        if let Some(paths) = std::env::var_os("NYX_TOUCH_DEVICE") {
+            return std::env::split_paths(&paths).collect();
+        }
+        let configured = self.config_manager.get_touch_config().devices;
+        if !configured.is_empty() {
+            return configured;
         }
 
-        Err(anyhow!("No touch device found"))
+        let presence = match self.config_manager.get_presence_config().input {
+            Some(PresenceInput::Evdev { device }) => Some(device),
+            _ => None,
+        };
+
+        // Look for touchscreen devices
+        let mut found: Vec<PathBuf> = evdev::enumerate()
+            .filter(|(path, _)| Some(path) != presence.as_ref())
+            .filter(|(_, device)| {
+                let name = device.name().unwrap_or("").to_lowercase();
+
+                // Check if this is a touchscreen
+                name.contains("touch")
+                    || name.contains("ft5406")
+                    || device.supported_events().contains(EventType::ABSOLUTE)
+            })
+            .map(|(path, _)| path)
+            .collect();
+        found.sort();
+        found
     }
 
     /// Get the timestamp of the last touch event
@@ -1,175 +1,282 @@
-use anyhow::Result;
-use futures_util::{SinkExt, StreamExt};
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use anyhow::{Context, Result};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use shq_audit::{AuditEvent, AuditKind, AuditLog, AuditQuery};
+use shq_auth::Identity;
+use shq_config::ConfigArchive;
+use shq_i18n::Translator;
+use shq_logging::LogHandle;
+use shq_protocol::{ComponentHealth, Health, ServiceInfo};
+use shq_ratelimit::RateLimited;
+use shq_systemd::Heartbeat;
+use shq_ws::{request_token, Broadcaster, Client, Handler, Request};
+use serde_json::Value;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::{broadcast, Mutex};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio::time::{interval, Duration};
 
 use crate::auto_dim::AutoDimManager;
-use crate::cdp;
-use crate::config::ConfigManager;
-use crate::display::DisplayController;
-use crate::messages::{AutoDimConfig, ClientMessage, ServerMessage};
+use crate::browser::BrowserWatchdog;
+use crate::fallback::Fallback;
+use crate::cdp::CdpClient;
+use crate::config::{ConfigManager, DisplayAction, ProfileConfig, NIGHT_MODE_TEMPERATURES};
+use crate::display::Displays;
+use crate::gesture::Gesture;
+use crate::messages::{
+    AutoDimConfig, AutoDimTransition, ClientMessage, DisplayStatus, NightModeStatus, PageError, PowerState, ScreenshotFormat, ServerMessage,
+    TouchActivityState, CAPABILITIES, PROTOCOL_VERSION,
+};
+use crate::night_mode::NightMode;
+use crate::page_errors::PageErrors;
+use crate::presence::PresenceSensor;
+use crate::screensaver::Screensaver;
+use crate::touch::{TouchMonitor, UnlockRefused};
 
-type ClientId = usize;
+/// Broadcast topic for display metrics (all clients)
+const METRICS_TOPIC: &str = "metrics";
 
-/// WebSocket server for display control
-pub struct WebSocketServer {
-    addr: SocketAddr,
-    display: DisplayController,
+/// Broadcast topic for live log lines (clients that sent `subscribe_logs`)
+const LOGS_TOPIC: &str = "logs";
+
+/// Broadcast topic for touchscreen gestures (clients that sent `subscribe_gestures`)
+const GESTURES_TOPIC: &str = "gestures";
+
+/// Broadcast topic for page errors (clients that sent `subscribe_page_errors`)
+const PAGE_ERRORS_TOPIC: &str = "page_errors";
+
+/// Broadcast topic for touch activity (clients that sent `subscribe_touch`)
+const TOUCH_TOPIC: &str = "touch";
+
+/// How often touch activity is checked for having gone idle
+const TOUCH_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Recent log lines sent to a new log subscriber unless it asks for a different amount
+const DEFAULT_LOG_BACKLOG: usize = 100;
+
+/// How often metrics are checked for changes nobody announced (auto-dim, touch wakes, page
+/// navigation inside Chrome)
+const METRICS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// WebSocket protocol handler for display control
+#[derive(Clone)]
+pub struct WebSocketHandler {
+    displays: Displays,
     auto_dim: AutoDimManager,
-    config_manager: Arc<Mutex<ConfigManager>>,
-    clients: Arc<Mutex<HashMap<ClientId, broadcast::Sender<String>>>>,
-    next_client_id: Arc<Mutex<ClientId>>,
+    touch_monitor: TouchMonitor,
+    screensaver: Screensaver,
+    night_mode: NightMode,
+    page_errors: PageErrors,
+    cdp: CdpClient,
+    browser_watchdog: BrowserWatchdog,
+    fallback: Fallback,
+    presence: PresenceSensor,
+    config_manager: ConfigManager,
+    log_handle: LogHandle,
+    broadcaster: Broadcaster,
+    /// Client-facing message catalog
+    tr: Translator,
+    audit: AuditLog,
+    /// Last metrics broadcast, without touch times, so the poll only sends real changes
+    last_metrics: Arc<Mutex<Option<Value>>>,
+    /// Profile last switched to, for metrics
+    profile: Arc<Mutex<Option<String>>>,
 }
 
-impl WebSocketServer {
-    /// Create a new WebSocket server
-    pub fn new(
-        addr: SocketAddr,
-        display: DisplayController,
-        auto_dim: AutoDimManager,
-        config_manager: ConfigManager,
-    ) -> Self {
-        Self {
-            addr,
-            display,
-            auto_dim,
-            config_manager: Arc::new(Mutex::new(config_manager)),
-            clients: Arc::new(Mutex::new(HashMap::new())),
-            next_client_id: Arc::new(Mutex::new(0)),
-        }
-    }
+impl Handler for WebSocketHandler {
+    type Message = ServerMessage;
+    type State = Identity;
 
-    /// Start the WebSocket server
-    pub async fn start(self: Arc<Self>) -> Result<()> {
-        let listener = TcpListener::bind(self.addr).await?;
-        tracing::info!("WebSocket server listening on {}", self.addr);
-
-        loop {
-            match listener.accept().await {
-                Ok((stream, peer_addr)) => {
-                    let server = self.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = server.handle_connection(stream, peer_addr).await {
-                            tracing::error!("Connection error from {}: {}", peer_addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    tracing::error!("Accept error: {}", e);
-                }
-            }
-        }
+    fn default_topics(&self) -> &'static [&'static str] {
+        &[METRICS_TOPIC]
     }
 
-    /// Handle a new client connection
-    async fn handle_connection(&self, stream: TcpStream, peer_addr: SocketAddr) -> Result<()> {
-        tracing::info!("New connection from {}", peer_addr);
-
-        let ws_stream = accept_async(stream).await?;
-        let (mut write, mut read) = ws_stream.split();
+    fn authorize(&self, request: &Request) -> Result<Identity, String> {
+        self.config_manager
+            .get_auth_config()
+            .authenticate(request_token(request).as_deref())
+            .map_err(|e| e.to_string())
+    }
 
-        // Register client
-        let client_id = self.register_client().await;
-        tracing::info!("Client {} registered from {}", client_id, peer_addr);
+    async fn on_connect(&self, client: &mut Client<Identity>) -> Vec<ServerMessage> {
+        self.audit
+            .record(AuditEvent::new(AuditKind::Connection, "connect").client(client.state.label(client.addr)));
 
         // Send initial metrics
-        if let Ok(metrics) = self.collect_metrics().await {
-            let msg = serde_json::to_string(&metrics)?;
-            let _ = write.send(Message::Text(msg)).await;
-        }
+        self.collect_metrics().await.into_iter().collect()
+    }
 
-        // Get broadcast receiver for this client
-        let mut rx = {
-            let clients = self.clients.lock().await;
-            clients.get(&client_id).unwrap().subscribe()
-        };
+    fn shutdown_message(&self) -> Option<ServerMessage> {
+        Some(ServerMessage::ServerShuttingDown)
+    }
 
-        loop {
-            tokio::select! {
-                // Handle incoming messages from client
-                msg = read.next() => {
-                    match msg {
-                        Some(Ok(Message::Text(text))) => {
-                            let response = match self.handle_message(&text).await {
-                                Ok(resp) => resp,
-                                Err(e) => ServerMessage::Error {
-                                    message: format!("Invalid message: {}", e),
-                                },
-                            };
-                            let response_json = serde_json::to_string(&response)?;
-                            write.send(Message::Text(response_json)).await?;
-                        }
-                        Some(Ok(Message::Close(_))) | None => {
-                            tracing::info!("Client {} disconnected", client_id);
-                            break;
-                        }
-                        Some(Err(e)) => {
-                            tracing::error!("WebSocket error from client {}: {}", client_id, e);
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
-                // Handle broadcast messages to this client
-                Ok(broadcast_msg) = rx.recv() => {
-                    if let Err(e) = write.send(Message::Text(broadcast_msg)).await {
-                        tracing::error!("Failed to send broadcast to client {}: {}", client_id, e);
-                        break;
-                    }
-                }
-            }
-        }
+    fn rate_limited_message(&self, limited: &RateLimited) -> String {
+        self.tr.t("error.rate_limited", &[("retry_after_ms", &limited.retry_after_ms())])
+    }
 
-        // Unregister client
-        self.unregister_client(client_id).await;
-        tracing::info!("Client {} unregistered", client_id);
+    async fn on_message(&self, client: &mut Client<Identity>, text: &str) -> ServerMessage {
+        match self.handle_message(client, text).await {
+            Ok(resp) => resp,
+            Err(e) => ServerMessage::Error {
+                message: self.tr.t("error.invalid_message", &[("error", &e)]),
+            },
+        }
+    }
 
-        Ok(())
+    async fn on_disconnect(&self, client: &mut Client<Identity>) {
+        self.audit
+            .record(AuditEvent::new(AuditKind::Connection, "disconnect").client(client.state.label(client.addr)));
     }
+}
 
-    /// Register a new client
-    async fn register_client(&self) -> ClientId {
-        let mut next_id = self.next_client_id.lock().await;
-        let client_id = *next_id;
-        *next_id += 1;
+impl WebSocketHandler {
+    /// Create a new WebSocket handler
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        displays: Displays,
+        auto_dim: AutoDimManager,
+        touch_monitor: TouchMonitor,
+        screensaver: Screensaver,
+        night_mode: NightMode,
+        page_errors: PageErrors,
+        cdp: CdpClient,
+        browser_watchdog: BrowserWatchdog,
+        fallback: Fallback,
+        presence: PresenceSensor,
+        config_manager: ConfigManager,
+        log_handle: LogHandle,
+        broadcaster: Broadcaster,
+        tr: Translator,
+        audit: AuditLog,
+    ) -> Self {
+        Self {
+            displays,
+            auto_dim,
+            touch_monitor,
+            screensaver,
+            night_mode,
+            page_errors,
+            cdp,
+            browser_watchdog,
+            fallback,
+            presence,
+            config_manager,
+            log_handle,
+            broadcaster,
+            tr,
+            audit,
+            last_metrics: Arc::new(Mutex::new(None)),
+            profile: Arc::new(Mutex::new(None)),
+        }
+    }
 
-        let (tx, _) = broadcast::channel(100);
-        self.clients.lock().await.insert(client_id, tx);
+    /// Handle a client message, recording privileged commands and their outcome in the audit log
+    async fn handle_message(&self, client: &Client<Identity>, text: &str) -> Result<ServerMessage> {
+        let message: ClientMessage = serde_json::from_str(text)?;
+        let Some(event) = message.audit_event() else {
+            return self.authorize_and_run(client, message).await;
+        };
 
-        client_id
+        let event = event.client(client.state.label(client.addr));
+        let result = self.authorize_and_run(client, message).await;
+        self.audit.record(match &result {
+            Ok(ServerMessage::Error { message }) => event.failed(message),
+            Ok(_) => event,
+            Err(e) => event.failed(e),
+        });
+        result
     }
 
-    /// Unregister a client
-    async fn unregister_client(&self, client_id: ClientId) {
-        self.clients.lock().await.remove(&client_id);
+    /// Refuse commands the client's role doesn't allow, then run the command
+    async fn authorize_and_run(&self, client: &Client<Identity>, message: ClientMessage) -> Result<ServerMessage> {
+        let role = message.required_role();
+        if client.state.require(role).is_err() {
+            tracing::warn!("Refused command from {}: requires the {} role", client.state.label(client.addr), role);
+            return Ok(ServerMessage::Error {
+                message: self.tr.t("error.forbidden", &[("role", &role)]),
+            });
+        }
+        self.run_command(client, message).await
     }
 
-    /// Broadcast a message to all connected clients
-    pub async fn broadcast(&self, message: &ServerMessage) -> Result<()> {
-        let json = serde_json::to_string(message)?;
-        let clients = self.clients.lock().await;
+    /// Run a parsed client command
+    async fn run_command(&self, client: &Client<Identity>, message: ClientMessage) -> Result<ServerMessage> {
+        match message {
+            ClientMessage::Hello { client: name, features } => {
+                tracing::info!("Hello from client {:?}", name.as_deref().unwrap_or("unknown"));
+                let info = ServiceInfo::new("nyx", env!("CARGO_PKG_VERSION"), PROTOCOL_VERSION, CAPABILITIES);
+                let info = match features {
+                    Some(features) => info.negotiate(&features),
+                    None => info,
+                };
+                if let Some(unsupported) = info.unsupported.as_ref().filter(|u| !u.is_empty()) {
+                    tracing::debug!("Client {} requested unsupported features: {:?}", client.id, unsupported);
+                }
+                Ok(ServerMessage::Hello(info))
+            }
+            ClientMessage::SubscribeLogs { token, lines } => {
+                let stream_config = self.config_manager.get_logging_config().stream;
+                if let Err(e) = stream_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.logs_refused", &[("error", &e)]),
+                    });
+                }
 
-        for (client_id, tx) in clients.iter() {
-            if let Err(e) = tx.send(json.clone()) {
-                tracing::warn!("Failed to broadcast to client {}: {}", client_id, e);
+                let (backlog, _) = self
+                    .log_handle
+                    .subscribe(lines.unwrap_or(DEFAULT_LOG_BACKLOG));
+                client.subscribe(LOGS_TOPIC).await;
+                Ok(ServerMessage::Logs { lines: backlog })
+            }
+            ClientMessage::UnsubscribeLogs => {
+                client.unsubscribe(LOGS_TOPIC).await;
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "unsubscribe_logs".to_string(),
+                    config: None,
+                    url: None,
+                })
+            }
+            ClientMessage::SubscribeTouch => {
+                client.subscribe(TOUCH_TOPIC).await;
+                Ok(self.ack("subscribe_touch"))
+            }
+            ClientMessage::UnsubscribeTouch => {
+                client.unsubscribe(TOUCH_TOPIC).await;
+                Ok(self.ack("unsubscribe_touch"))
+            }
+            ClientMessage::SubscribePageErrors => {
+                client.subscribe(PAGE_ERRORS_TOPIC).await;
+                Ok(self.ack("subscribe_page_errors"))
             }
+            ClientMessage::UnsubscribePageErrors => {
+                client.unsubscribe(PAGE_ERRORS_TOPIC).await;
+                Ok(self.ack("unsubscribe_page_errors"))
+            }
+            ClientMessage::SubscribeGestures => {
+                client.subscribe(GESTURES_TOPIC).await;
+                Ok(self.ack("subscribe_gestures"))
+            }
+            ClientMessage::UnsubscribeGestures => {
+                client.unsubscribe(GESTURES_TOPIC).await;
+                Ok(self.ack("unsubscribe_gestures"))
+            }
+            message => self.execute(message).await,
         }
-
-        Ok(())
     }
 
-    /// Handle a client message
-    async fn handle_message(&self, text: &str) -> Result<ServerMessage> {
-        let message: ClientMessage = serde_json::from_str(text)?;
-
+    /// Run a command that doesn't depend on the connection it came from (everything but the
+    /// handshake and subscriptions); the HTTP API goes straight here
+    pub async fn execute(&self, message: ClientMessage) -> Result<ServerMessage> {
         match message {
-            ClientMessage::SetDisplay { state } => {
-                self.display.set_display_state(state).await?;
-                self.auto_dim.reset_dimmed_state().await;
+            ClientMessage::SetDisplay { display, state } => {
+                let Some(displays) = self.displays.select(display.as_deref()) else {
+                    return Ok(self.unknown_display(display));
+                };
+                for display in &displays {
+                    display.set_display_state(state).await?;
+                }
+                self.auto_dim.manual(&displays).await?;
                 self.broadcast_metrics().await;
                 Ok(ServerMessage::Response {
                     success: true,
@@ -178,13 +285,78 @@ impl WebSocketServer {
                     url: None,
                 })
             }
-            ClientMessage::SetBrightness { brightness } => {
+            ClientMessage::SetPower { display, state } => {
+                let Some(displays) = self.displays.select(display.as_deref()) else {
+                    return Ok(self.unknown_display(display));
+                };
+                let displays: Vec<_> = displays.into_iter().filter(|display| display.has_power()).collect();
+                if displays.is_empty() {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.power_unsupported", &[]),
+                    });
+                }
+
+                let on = state == PowerState::On;
+                for display in &displays {
+                    if let Err(e) = display.set_power(on).await {
+                        tracing::error!("Display power failed: {:#}", e);
+                        return Ok(ServerMessage::Error {
+                            message: self.tr.t("error.power_failed", &[("error", &format!("{:#}", e))]),
+                        });
+                    }
+                }
+                // Like sleep/wake: touches wake a powered-off kiosk, and powering on is activity
+                self.auto_dim.manual(&displays).await?;
+                if on {
+                    self.touch_monitor.set_should_block(false).await;
+                } else if self.displays.all_off().await {
+                    self.touch_monitor.set_should_block(true).await;
+                }
+                self.broadcast_metrics().await;
+                Ok(self.ack("set_power"))
+            }
+            ClientMessage::SetNightMode { enabled, temperature } => {
+                if temperature.is_some_and(|t| !NIGHT_MODE_TEMPERATURES.contains(&t)) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t(
+                            "error.night_mode_temperature",
+                            &[
+                                ("min", NIGHT_MODE_TEMPERATURES.start()),
+                                ("max", NIGHT_MODE_TEMPERATURES.end()),
+                            ],
+                        ),
+                    });
+                }
+                self.night_mode.set(enabled, temperature).await?;
+                self.broadcast_metrics().await;
+                Ok(self.ack("set_night_mode"))
+            }
+            ClientMessage::SetProfile { name } => {
+                let Some(profile) = self.config_manager.get_profile(&name) else {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.unknown_profile", &[("profile", &name)]),
+                    });
+                };
+                if let Err(e) = self.apply_profile(&name, profile).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.profile_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+                self.broadcast_metrics().await;
+                Ok(self.ack("set_profile"))
+            }
+            ClientMessage::SetBrightness { display, brightness } => {
+                let Some(displays) = self.displays.select(display.as_deref()) else {
+                    return Ok(self.unknown_display(display));
+                };
                 if brightness == 0 {
                     // Setting brightness to 0 is same as sleep
-                    self.auto_dim.sleep().await?;
+                    self.auto_dim.sleep(display.as_deref()).await?;
                 } else {
-                    self.display.set_brightness(brightness).await?;
-                    self.auto_dim.reset_dimmed_state().await;
+                    for display in &displays {
+                        display.set_brightness(brightness).await?;
+                    }
+                    self.auto_dim.manual(&displays).await?;
                 }
                 self.broadcast_metrics().await;
                 Ok(ServerMessage::Response {
@@ -195,15 +367,20 @@ impl WebSocketServer {
                 })
             }
             ClientMessage::GetMetrics => self.collect_metrics().await,
+            ClientMessage::GetHealth => Ok(ServerMessage::Health(self.collect_health().await)),
             ClientMessage::SetAutoDimConfig {
+                display,
                 dim_level,
                 bright_level,
                 auto_dim_time,
                 auto_off_time,
             } => {
+                if self.displays.select(display.as_deref()).is_none() {
+                    return Ok(self.unknown_display(display));
+                }
                 if bright_level == 0 {
                     return Ok(ServerMessage::Error {
-                        message: "bright_level must be greater than 0 (use dim_level for dimmed brightness)".to_string(),
+                        message: self.tr.t("error.bright_level_zero", &[]),
                     });
                 }
 
@@ -214,12 +391,10 @@ impl WebSocketServer {
                     auto_off_time,
                 };
 
-                self.auto_dim.set_config(config.clone()).await;
                 self.config_manager
-                    .lock()
-                    .await
-                    .set_auto_dim_config(config)
+                    .set_auto_dim_config(display.as_deref(), config.clone())
                     .await?;
+                self.auto_dim.set_config(display.as_deref(), config).await;
 
                 self.broadcast_metrics().await;
 
@@ -230,8 +405,11 @@ impl WebSocketServer {
                     url: None,
                 })
             }
-            ClientMessage::GetAutoDimConfig => {
-                let config = self.auto_dim.get_config().await;
+            ClientMessage::GetAutoDimConfig { display } => {
+                if self.displays.select(display.as_deref()).is_none() {
+                    return Ok(self.unknown_display(display));
+                }
+                let config = self.auto_dim.get_config(display.as_deref()).await;
                 Ok(ServerMessage::Response {
                     success: true,
                     command: "get_auto_dim_config".to_string(),
@@ -239,8 +417,11 @@ impl WebSocketServer {
                     url: None,
                 })
             }
-            ClientMessage::Wake => {
-                self.auto_dim.wake().await?;
+            ClientMessage::Wake { display } => {
+                if self.displays.select(display.as_deref()).is_none() {
+                    return Ok(self.unknown_display(display));
+                }
+                self.auto_dim.wake(display.as_deref()).await?;
                 self.broadcast_metrics().await;
                 Ok(ServerMessage::Response {
                     success: true,
@@ -249,8 +430,11 @@ impl WebSocketServer {
                     url: None,
                 })
             }
-            ClientMessage::Sleep => {
-                self.auto_dim.sleep().await?;
+            ClientMessage::Sleep { display } => {
+                if self.displays.select(display.as_deref()).is_none() {
+                    return Ok(self.unknown_display(display));
+                }
+                self.auto_dim.sleep(display.as_deref()).await?;
                 self.broadcast_metrics().await;
                 Ok(ServerMessage::Response {
                     success: true,
@@ -260,9 +444,10 @@ impl WebSocketServer {
                 })
             }
             ClientMessage::Navigate { url } => {
-                match cdp::navigate(&url).await {
+                match self.cdp.navigate(&url).await {
                     Ok(()) => {
                         tracing::info!("Navigated Chrome to {}", url);
+                        self.screensaver.forget_previous_url().await;
                         self.broadcast_metrics().await;
                         Ok(ServerMessage::Response {
                             success: true,
@@ -274,13 +459,78 @@ impl WebSocketServer {
                     Err(e) => {
                         tracing::error!("Failed to navigate: {:#}", e);
                         Ok(ServerMessage::Error {
-                            message: format!("Navigate failed: {:#}", e),
+                            message: self.tr.t("error.navigate_failed", &[("error", &format!("{:#}", e))]),
                         })
                     }
                 }
             }
+            ClientMessage::ListTabs => match self.cdp.list_tabs().await {
+                Ok(tabs) => Ok(ServerMessage::Tabs { tabs }),
+                Err(e) => Ok(self.tabs_failed(e)),
+            },
+            ClientMessage::NewTab { url } => match self.cdp.new_tab(&url).await {
+                Ok(tab) => {
+                    tracing::info!("Opened tab {} at {}", tab.id, url);
+                    self.screensaver.forget_previous_url().await;
+                    self.broadcast_metrics().await;
+                    Ok(ServerMessage::Tab { tab })
+                }
+                Err(e) => Ok(self.tabs_failed(e)),
+            },
+            ClientMessage::CloseTab { tab } => match self.cdp.close_tab(&tab).await {
+                Ok(()) => {
+                    tracing::info!("Closed tab {}", tab);
+                    self.broadcast_metrics().await;
+                    Ok(self.ack("close_tab"))
+                }
+                Err(e) => Ok(self.tabs_failed(e)),
+            },
+            ClientMessage::ActivateTab { tab } => match self.cdp.activate_tab(&tab).await {
+                Ok(()) => {
+                    tracing::info!("Activated tab {}", tab);
+                    self.screensaver.forget_previous_url().await;
+                    self.broadcast_metrics().await;
+                    Ok(self.ack("activate_tab"))
+                }
+                Err(e) => Ok(self.tabs_failed(e)),
+            },
+            ClientMessage::Reload => match self.cdp.reload().await {
+                Ok(()) => Ok(self.ack("reload")),
+                Err(e) => {
+                    tracing::error!("Failed to reload: {:#}", e);
+                    Ok(ServerMessage::Error {
+                        message: self.tr.t("error.reload_failed", &[("error", &format!("{:#}", e))]),
+                    })
+                }
+            },
+            ClientMessage::EvaluateJs { token, expression } => {
+                if let Err(e) = self.config_manager.get_automation_config().authorize(token.as_deref()) {
+                    return Ok(self.automation_refused(e));
+                }
+                match self.cdp.evaluate(&expression).await {
+                    Ok(value) => Ok(ServerMessage::JsResult { value }),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.evaluate_failed", &[("error", &format!("{:#}", e))]),
+                    }),
+                }
+            }
+            ClientMessage::DispatchTouch { token, action, x, y } => {
+                if let Err(e) = self.config_manager.get_automation_config().authorize(token.as_deref()) {
+                    return Ok(self.automation_refused(e));
+                }
+                let result = self.cdp.dispatch_touch(action, x, y).await;
+                Ok(self.input_response("dispatch_touch", result))
+            }
+            ClientMessage::DispatchKey { token, action, key } => {
+                if let Err(e) = self.config_manager.get_automation_config().authorize(token.as_deref()) {
+                    return Ok(self.automation_refused(e));
+                }
+                let result = self.cdp.dispatch_key(action, &key).await;
+                Ok(self.input_response("dispatch_key", result))
+            }
+            ClientMessage::Screenshot { format, quality, file } => Ok(self.screenshot(format, quality, file).await),
             ClientMessage::GetUrl => {
-                match cdp::get_current_url().await {
+                match self.cdp.get_current_url().await {
                     Ok(url) => Ok(ServerMessage::Response {
                         success: true,
                         command: "get_url".to_string(),
@@ -290,38 +540,548 @@ impl WebSocketServer {
                     Err(e) => {
                         tracing::error!("Failed to get URL: {:#}", e);
                         Ok(ServerMessage::Error {
-                            message: format!("Get URL failed: {:#}", e),
+                            message: self.tr.t("error.get_url_failed", &[("error", &format!("{:#}", e))]),
                         })
                     }
                 }
             }
+            ClientMessage::RunScene { scene } => {
+                let config = self.config_manager.get_scene_config();
+                match config.run(&scene, self).await {
+                    Ok(report) => Ok(ServerMessage::SceneResult(report)),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.scene_failed", &[("error", &e)]),
+                    }),
+                }
+            }
+            ClientMessage::LockTouch { pin } => {
+                if pin.is_empty() {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.pin_required", &[]),
+                    });
+                }
+                if !self.touch_monitor.lock(&pin).await {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.touch_already_locked", &[]),
+                    });
+                }
+                self.broadcast_metrics().await;
+                Ok(self.ack("lock_touch"))
+            }
+            ClientMessage::UnlockTouch { pin } => {
+                if let Err(refused) = self.touch_monitor.unlock(&pin).await {
+                    let message = match refused {
+                        UnlockRefused::WrongPin => self.tr.t("error.wrong_pin", &[]),
+                        UnlockRefused::TooManyAttempts(limited) => self.tr.t(
+                            "error.too_many_pin_attempts",
+                            &[("retry_after_secs", &limited.retry_after_ms().div_ceil(1000))],
+                        ),
+                    };
+                    return Ok(ServerMessage::Error { message });
+                }
+                // Whoever unlocked it is standing there, which brightens a dimmed panel
+                self.touch_monitor.reset_touch_timer().await;
+                self.broadcast_metrics().await;
+                Ok(self.ack("unlock_touch"))
+            }
+            ClientMessage::GetPageErrors { limit } => Ok(ServerMessage::PageErrors {
+                errors: self.page_errors.recent(limit).await,
+            }),
+            ClientMessage::ExportConfig { token } => {
+                let backup_config = self.config_manager.get_backup_config();
+                if let Err(e) = backup_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.export_refused", &[("error", &e)]),
+                    });
+                }
+
+                let entry = self.config_manager.export()?;
+                Ok(ServerMessage::ConfigArchive {
+                    archive: ConfigArchive::single(entry),
+                })
+            }
+            ClientMessage::ImportConfig { token, archive, entry } => {
+                let backup_config = self.config_manager.get_backup_config();
+                if let Err(e) = backup_config.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.import_refused", &[("error", &e)]),
+                    });
+                }
+
+                let result = match archive.entry_for("nyx", entry.as_deref()) {
+                    Ok(entry) => self.config_manager.import(entry).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.import_failed", &[("error", &format!("{:#}", e))]),
+                    });
+                }
+
+                // Hot-reloadable sections are applied by the config reload task
+                Ok(ServerMessage::Response {
+                    success: true,
+                    command: "import_config".to_string(),
+                    config: None,
+                    url: None,
+                })
+            }
+            ClientMessage::GetAuditLog { token, after_seq, limit, kind } => {
+                let audit_config = self.config_manager.get_audit_config();
+                if let Err(e) = audit_config.query.authorize(token.as_deref()) {
+                    return Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_refused", &[("error", &e)]),
+                    });
+                }
+
+                match self.audit.query(&AuditQuery {
+                    after_seq,
+                    limit,
+                    kinds: kind.into_iter().collect(),
+                    ..Default::default()
+                }) {
+                    Ok(page) => Ok(ServerMessage::AuditLog(page)),
+                    Err(e) => Ok(ServerMessage::Error {
+                        message: self.tr.t("error.audit_failed", &[("error", &format!("{:#}", e))]),
+                    }),
+                }
+            }
             ClientMessage::Noop => Ok(ServerMessage::Response {
                 success: true,
                 command: "noop".to_string(),
                 config: None,
                 url: None,
             }),
+            ClientMessage::Hello { .. }
+            | ClientMessage::SubscribeLogs { .. }
+            | ClientMessage::UnsubscribeLogs
+            | ClientMessage::SubscribeTouch
+            | ClientMessage::UnsubscribeTouch
+            | ClientMessage::SubscribePageErrors
+            | ClientMessage::UnsubscribePageErrors
+            | ClientMessage::SubscribeGestures
+            | ClientMessage::UnsubscribeGestures => {
+                unreachable!("connection commands are handled by run_command")
+            }
+        }
+    }
+
+    /// Reply to a command naming a display that doesn't exist
+    fn unknown_display(&self, display: Option<String>) -> ServerMessage {
+        ServerMessage::Error {
+            message: self.tr.t("error.unknown_display", &[("display", &display.unwrap_or_default())]),
+        }
+    }
+
+    /// Acknowledge a browser command
+    fn ack(&self, command: &str) -> ServerMessage {
+        ServerMessage::Response {
+            success: true,
+            command: command.to_string(),
+            config: None,
+            url: None,
         }
     }
 
+    fn tabs_failed(&self, e: anyhow::Error) -> ServerMessage {
+        tracing::error!("Tab command failed: {:#}", e);
+        ServerMessage::Error {
+            message: self.tr.t("error.tabs_failed", &[("error", &format!("{:#}", e))]),
+        }
+    }
+
+    fn automation_refused(&self, e: anyhow::Error) -> ServerMessage {
+        ServerMessage::Error {
+            message: self.tr.t("error.automation_refused", &[("error", &e)]),
+        }
+    }
+
+    /// Acknowledge synthetic input, or report why Chrome refused it
+    fn input_response(&self, command: &str, result: Result<()>) -> ServerMessage {
+        match result {
+            Ok(()) => self.ack(command),
+            Err(e) => {
+                tracing::error!("Failed to {}: {:#}", command, e);
+                ServerMessage::Error {
+                    message: self.tr.t("error.input_failed", &[("error", &format!("{:#}", e))]),
+                }
+            }
+        }
+    }
+
+    /// Capture the active tab, returning the image or saving it as `file` under the
+    /// screenshot directory
+    async fn screenshot(&self, format: ScreenshotFormat, quality: Option<u8>, file: Option<String>) -> ServerMessage {
+        if let Some(name) = file.as_deref().filter(|name| !is_plain_file_name(name)) {
+            return ServerMessage::Error {
+                message: self.tr.t("error.invalid_file_name", &[("file", &name)]),
+            };
+        }
+
+        let result = match self.cdp.capture_screenshot(format.as_str(), quality).await {
+            Ok(data) => match file {
+                Some(name) => self.save_screenshot(&name, &data).await.map(|path| ServerMessage::Screenshot {
+                    format,
+                    data: None,
+                    path: Some(path.display().to_string()),
+                }),
+                None => Ok(ServerMessage::Screenshot {
+                    format,
+                    data: Some(data),
+                    path: None,
+                }),
+            },
+            Err(e) => Err(e),
+        };
+        result.unwrap_or_else(|e| {
+            tracing::error!("Screenshot failed: {:#}", e);
+            ServerMessage::Error {
+                message: self.tr.t("error.screenshot_failed", &[("error", &format!("{:#}", e))]),
+            }
+        })
+    }
+
+    async fn save_screenshot(&self, name: &str, data: &str) -> Result<PathBuf> {
+        let image = BASE64_STANDARD
+            .decode(data)
+            .context("Chrome sent invalid image data")?;
+        let dir = self.config_manager.screenshot_dir();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(name);
+        tokio::fs::write(&path, image)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        tracing::info!("Saved screenshot to {:?}", path);
+        Ok(path)
+    }
+
     /// Collect and return current metrics
     async fn collect_metrics(&self) -> Result<ServerMessage> {
-        let display = self.display.get_metrics().await?;
-        let auto_dim = self.auto_dim.get_status().await;
-        let url = cdp::get_current_url().await.ok();
+        let mut displays = Vec::new();
+        for display in self.displays.iter() {
+            displays.push(DisplayStatus {
+                display: display.get_metrics().await?,
+                auto_dim: self.auto_dim.get_status(display.id()).await,
+            });
+        }
+        let url = self.cdp.get_current_url().await.ok();
 
         Ok(ServerMessage::Metrics {
             version: env!("CARGO_PKG_VERSION").to_string(),
-            display,
-            auto_dim,
+            display: displays[0].display.clone(),
+            auto_dim: displays[0].auto_dim.clone(),
+            displays,
+            screensaver: self.screensaver.is_active().await,
+            night_mode: self.night_mode_status(),
+            profile: self.profile.lock().await.clone().filter(|name| self.config_manager.get_profile(name).is_some()),
+            touch_devices: self.touch_monitor.devices().await,
+            touch_locked: self.touch_monitor.is_locked().await,
+            presence: self.presence.status(),
+            browser: self.browser_watchdog.status(),
+            fallback: self.fallback.status(),
             url,
         })
     }
 
+    fn night_mode_status(&self) -> NightModeStatus {
+        let config = self.config_manager.get_night_mode_config();
+        NightModeStatus {
+            enabled: config.enabled,
+            temperature: config.temperature,
+        }
+    }
+
+    /// Check the backlights, touch device and Chrome
+    async fn collect_health(&self) -> Health {
+        let mut unreadable = Vec::new();
+        for display in self.displays.iter() {
+            if let Err(e) = display.reconcile().await {
+                unreadable.push(format!("{}: {}", display.id(), e));
+            }
+        }
+        let display = if unreadable.is_empty() {
+            ComponentHealth::ok("display")
+        } else {
+            ComponentHealth::failed("display", format!("Backlight unreadable: {}", unreadable.join("; ")))
+        };
+
+        // Chrome restarting is common and recoverable; the display still works without it
+        let browser = match self.cdp.get_current_url().await {
+            Ok(_) => ComponentHealth::ok("browser"),
+            Err(e) => ComponentHealth::degraded("browser", format!("{:#}", e)),
+        };
+
+        let mut components = vec![display, self.touch_monitor.health().await, browser];
+        if let Some(presence) = self.presence.status() {
+            components.push(match presence.error {
+                None => ComponentHealth::ok("presence"),
+                Some(error) => ComponentHealth::degraded("presence", error),
+            });
+        }
+        components.extend(self.audit.health());
+        Health::new("nyx", components).with_last_crash(self.log_handle.last_crash())
+    }
+
     /// Broadcast current metrics to all clients
-    async fn broadcast_metrics(&self) {
+    pub async fn broadcast_metrics(&self) {
         if let Ok(metrics) = self.collect_metrics().await {
-            let _ = self.broadcast(&metrics).await;
+            *self.last_metrics.lock().await = Some(metrics_fingerprint(&metrics));
+            let _ = self.broadcaster.broadcast(METRICS_TOPIC, &metrics).await;
+        }
+    }
+
+    /// Broadcast current metrics if anything but the touch times changed since the last
+    /// broadcast
+    async fn broadcast_changed_metrics(&self) {
+        if !self.broadcaster.has_subscribers(METRICS_TOPIC).await {
+            return;
+        }
+        let Ok(metrics) = self.collect_metrics().await else {
+            return;
+        };
+
+        let fingerprint = metrics_fingerprint(&metrics);
+        let mut last = self.last_metrics.lock().await;
+        if last.as_ref() == Some(&fingerprint) {
+            return;
+        }
+        *last = Some(fingerprint);
+        drop(last);
+
+        tracing::debug!("Metrics changed, broadcasting");
+        let _ = self.broadcaster.broadcast(METRICS_TOPIC, &metrics).await;
+    }
+
+    /// Switch to `profile`: auto-dim and night mode settings first (both persisted), then the
+    /// brightness, then the page
+    async fn apply_profile(&self, name: &str, profile: ProfileConfig) -> Result<()> {
+        tracing::info!("Switching to profile {}", name);
+        *self.profile.lock().await = Some(name.to_string());
+
+        if let Some(auto_dim) = profile.auto_dim {
+            self.config_manager.set_auto_dim_config(None, auto_dim.clone()).await?;
+            self.auto_dim.set_config(None, auto_dim).await;
         }
+        if let Some(night_mode) = profile.night_mode {
+            self.night_mode.set(night_mode.enabled, night_mode.temperature).await?;
+        }
+        match profile.brightness {
+            Some(0) => self.auto_dim.sleep(None).await?,
+            Some(brightness) => {
+                let displays: Vec<_> = self.displays.iter().cloned().collect();
+                for display in &displays {
+                    display.set_brightness(brightness).await?;
+                }
+                self.auto_dim.manual(&displays).await?;
+                self.touch_monitor.set_should_block(false).await;
+            }
+            None => {}
+        }
+        if let Some(url) = profile.url {
+            self.cdp.navigate(&url).await?;
+            self.screensaver.forget_previous_url().await;
+        }
+        Ok(())
     }
+
+    /// Run a scheduled or scene display command, broadcasting metrics afterwards like the
+    /// matching WebSocket commands do
+    pub async fn run_action(&self, action: &DisplayAction) -> Result<()> {
+        match action {
+            DisplayAction::Wake => self.auto_dim.wake(None).await?,
+            DisplayAction::Sleep => self.auto_dim.sleep(None).await?,
+            DisplayAction::Navigate { url } => {
+                self.cdp.navigate(url).await?;
+                self.screensaver.forget_previous_url().await;
+            }
+            DisplayAction::NightMode { enabled, temperature } => {
+                self.night_mode.set(*enabled, *temperature).await?;
+            }
+            DisplayAction::Profile { name } => {
+                let profile = self
+                    .config_manager
+                    .get_profile(name)
+                    .with_context(|| format!("Unknown profile '{}'", name))?;
+                self.apply_profile(name, profile).await?;
+            }
+        }
+        self.broadcast_metrics().await;
+        Ok(())
+    }
+}
+
+impl shq_scene::LocalActions<DisplayAction> for WebSocketHandler {
+    async fn run(&self, action: &DisplayAction) -> Result<()> {
+        self.run_action(action).await
+    }
+}
+
+/// Start background task to broadcast metrics whenever brightness, display state, the page or
+/// anything else in them changes, whatever caused it; beats `heartbeat` on every poll, as it
+/// goes through the same client list and state as the handler
+pub fn start_metrics_broadcaster(handler: WebSocketHandler, heartbeat: Heartbeat) {
+    tokio::spawn(async move {
+        let mut ticker = interval(METRICS_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            handler.broadcast_changed_metrics().await;
+            heartbeat.beat();
+        }
+    });
+}
+
+/// `metrics` without `last_touch_time`, which moves with every touch
+fn metrics_fingerprint(metrics: &ServerMessage) -> Value {
+    let mut fingerprint = serde_json::to_value(metrics).unwrap_or_default();
+    if let Some(auto_dim) = fingerprint.get_mut("auto_dim").and_then(Value::as_object_mut) {
+        auto_dim.remove("last_touch_time");
+    }
+    if let Some(displays) = fingerprint.get_mut("displays").and_then(Value::as_array_mut) {
+        for auto_dim in displays.iter_mut().filter_map(|display| display.get_mut("auto_dim")) {
+            if let Some(auto_dim) = auto_dim.as_object_mut() {
+                auto_dim.remove("last_touch_time");
+            }
+        }
+    }
+    fingerprint
+}
+
+/// Start background task to forward log output to clients subscribed to logs
+pub fn start_log_forwarder(log_handle: LogHandle, broadcaster: Broadcaster) {
+    let (_, mut log_rx) = log_handle.subscribe(0);
+
+    tokio::spawn(async move {
+        loop {
+            match log_rx.recv().await {
+                Ok(line) => {
+                    if broadcaster.has_subscribers(LOGS_TOPIC).await {
+                        let _ = broadcaster.broadcast(LOGS_TOPIC, &ServerMessage::Log(line)).await;
+                    }
+                }
+                // Dropped lines are acceptable for a debugging stream
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Start background task to forward touchscreen gestures to clients subscribed to them
+pub fn start_gesture_forwarder(mut gesture_rx: broadcast::Receiver<Gesture>, broadcaster: Broadcaster) {
+    tokio::spawn(async move {
+        loop {
+            match gesture_rx.recv().await {
+                Ok(gesture) => {
+                    if broadcaster.has_subscribers(GESTURES_TOPIC).await {
+                        let _ = broadcaster.broadcast(GESTURES_TOPIC, &ServerMessage::Gesture(gesture)).await;
+                    }
+                }
+                // A gesture that's this stale is no use to anyone
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Start background task to forward page errors to clients subscribed to them
+pub fn start_page_error_forwarder(mut error_rx: broadcast::Receiver<PageError>, broadcaster: Broadcaster) {
+    tokio::spawn(async move {
+        loop {
+            match error_rx.recv().await {
+                Ok(error) => {
+                    if broadcaster.has_subscribers(PAGE_ERRORS_TOPIC).await {
+                        let _ = broadcaster.broadcast(PAGE_ERRORS_TOPIC, &ServerMessage::PageError(error)).await;
+                    }
+                }
+                // get_page_errors still has them
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Start background task to tell every client when a display changes auto-dim state
+pub fn start_auto_dim_forwarder(mut transition_rx: broadcast::Receiver<AutoDimTransition>, broadcaster: Broadcaster) {
+    tokio::spawn(async move {
+        loop {
+            match transition_rx.recv().await {
+                Ok(transition) => {
+                    let _ = broadcaster
+                        .broadcast(METRICS_TOPIC, &ServerMessage::AutoDimTransition(transition))
+                        .await;
+                }
+                // Metrics carry the current state, so missed transitions aren't lost for good
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Start background task to tell clients subscribed to touch activity when the panel is
+/// touched (at most every `touch.activity_debounce` seconds) and when it's been left alone for
+/// `touch.idle_after`
+pub fn start_touch_activity_forwarder(
+    touch_monitor: TouchMonitor,
+    config_manager: ConfigManager,
+    broadcaster: Broadcaster,
+) {
+    let mut touches = touch_monitor.subscribe_touches();
+
+    tokio::spawn(async move {
+        let mut ticker = interval(TOUCH_IDLE_CHECK_INTERVAL);
+        // Last touch seen, and last touch an `active` event went out for while active
+        let mut last_touch = None;
+        let mut reported: Option<f64> = None;
+        loop {
+            let config = config_manager.get_touch_config();
+            let state = tokio::select! {
+                changed = touches.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    last_touch = *touches.borrow_and_update();
+                    match (last_touch, reported) {
+                        (Some(touch), Some(sent)) if touch - sent < config.activity_debounce as f64 => continue,
+                        (Some(touch), _) => {
+                            reported = Some(touch);
+                            TouchActivityState::Active
+                        }
+                        (None, _) => continue,
+                    }
+                }
+                _ = ticker.tick() => {
+                    let idle = last_touch.is_some_and(|touch| unix_time() - touch >= config.idle_after as f64);
+                    if reported.is_none() || !idle {
+                        continue;
+                    }
+                    reported = None;
+                    TouchActivityState::Idle
+                }
+            };
+
+            tracing::debug!("Touch activity: {:?}", state);
+            if broadcaster.has_subscribers(TOUCH_TOPIC).await {
+                let message = ServerMessage::TouchActivity {
+                    state,
+                    last_touch_time: last_touch.unwrap_or_default(),
+                };
+                let _ = broadcaster.broadcast(TOUCH_TOPIC, &message).await;
+            }
+        }
+    });
+}
+
+/// Seconds since the Unix epoch
+fn unix_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+/// A bare file name: no directories, and not hidden
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
 }
@@ -1,59 +1,280 @@
+use crate::config::{PlaybackConfig, Preempt, Priority, ALL_ZONES};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
-use std::collections::HashMap;
+use shq_protocol::ComponentHealth;
+use shq_systemd::Heartbeat;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::{mpsc, oneshot};
 
 #[derive(Debug)]
 pub enum AudioCommand {
-    PlayFile {
-        path: PathBuf,
-        volume: f32,
-        response: oneshot::Sender<anyhow::Result<()>>,
+    /// Queue a message, replying with its place in the queue
+    Speak {
+        utterance: Utterance,
+        playback: PlaybackConfig,
+        response: oneshot::Sender<anyhow::Result<QueuePosition>>,
     },
-    PlayBytes {
-        data: Vec<u8>,
-        volume: f32,
-        response: oneshot::Sender<anyhow::Result<()>>,
+    QueueStatus {
+        response: oneshot::Sender<QueueStatus>,
     },
     StartAlarm {
         alarm_id: String,
         path: PathBuf,
         volume: f32,
+        duck_volume: f32,
+        zone: Option<String>,
         response: oneshot::Sender<anyhow::Result<()>>,
     },
     StopAlarm {
         alarm_id: String,
         response: oneshot::Sender<bool>,
     },
+    /// Silence everything: every alarm, the speech playing and the speech queue
+    StopAll {
+        response: oneshot::Sender<Silenced>,
+    },
+    ListActive {
+        response: oneshot::Sender<Vec<ActiveAlarm>>,
+    },
+    /// Stop alarms, let queued speech finish, then end the audio thread
+    Shutdown {
+        response: oneshot::Sender<()>,
+    },
+}
+
+/// Longest time shutdown waits for speech that is still playing or queued
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name of the only output when no `outputs` are configured
+const DEFAULT_OUTPUT: &str = "default";
+
+/// A spoken message: an optional notification tone, then the synthesised speech
+#[derive(Debug)]
+pub struct Utterance {
+    /// What is said, for the queue status
+    pub text: String,
+    pub priority: Priority,
+    pub tone: Option<PathBuf>,
+    /// Encoded audio, a chunk per synthesis request; kept so an interrupted message can be
+    /// played again
+    pub speech: Vec<Arc<[u8]>>,
+    /// Chunks still being synthesised, in order; `None` once they've all arrived
+    pub more: Option<mpsc::UnboundedReceiver<Arc<[u8]>>>,
+    pub volume: f32,
+    /// Output to play on; every output if `None` or `all`
+    pub zone: Option<String>,
+}
+
+impl Utterance {
+    /// Collect chunks synthesised since the last call
+    fn receive(&mut self) {
+        let Some(more) = &mut self.more else {
+            return;
+        };
+        loop {
+            match more.try_recv() {
+                Ok(chunk) => self.speech.push(chunk),
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.more = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Where a newly queued message ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueuePosition {
+    /// Started straight away (possibly preempting a less urgent message)
+    Playing,
+    /// Waiting, with this many messages ahead of it in the queue (not counting what's playing)
+    Queued(usize),
+}
+
+/// Messages playing and waiting, for GetQueue
+#[derive(Debug, Clone, Default)]
+pub struct QueueStatus {
+    /// The message being heard first, then any ducked under it
+    pub playing: Vec<QueueEntry>,
+    /// In the order they'll play
+    pub queued: Vec<QueueEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueEntry {
+    pub id: u64,
+    pub priority: Priority,
+    pub text: String,
+    pub zone: Option<String>,
+    /// Playing quietly under a more urgent message
+    pub ducked: bool,
+    /// Time since the message was queued
+    pub waited: Duration,
+}
+
+/// What `StopAll` stopped
+#[derive(Debug, Clone, Default)]
+pub struct Silenced {
+    /// IDs of the alarms that were sounding
+    pub alarms: Vec<String>,
+    /// Messages that were playing or waiting
+    pub messages: usize,
+}
+
+/// An alarm that is sounding, for ListActive
+#[derive(Debug, Clone)]
+pub struct ActiveAlarm {
+    pub alarm_id: String,
+    pub path: PathBuf,
+    pub volume: f32,
+    pub zone: Option<String>,
+    /// Turned down for speech
+    pub ducked: bool,
+    pub started: SystemTime,
 }
 
 pub struct AudioManager {
     command_tx: mpsc::UnboundedSender<AudioCommand>,
+    /// Configured outputs whose device couldn't be opened
+    unavailable: Arc<Mutex<Vec<String>>>,
 }
 
 struct AudioManagerInner {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    /// In config order
+    outputs: Vec<Output>,
     active_alarms: HashMap<String, AlarmState>,
+    /// Outputs (indices into `outputs`) whose alarm loops are turned down for speech
+    ducked_outputs: HashSet<usize>,
+    speech: SpeechQueue,
+}
+
+/// An open audio device
+struct Output {
+    name: String,
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
 }
 
+/// A sink on one output, by index into `AudioManagerInner::outputs`
+type OutputSink = (usize, Sink);
+
 struct AlarmState {
-    sink: Sink,
+    sinks: Vec<OutputSink>,
     path: PathBuf,
+    zone: Option<String>,
     volume: f32,
+    /// Fraction of `volume` to play at while speech is playing
+    duck_volume: f32,
     started_at: Instant,
+    /// Wall-clock `started_at`, for ListActive
+    started: SystemTime,
+}
+
+impl AlarmState {
+    fn volume(&self, ducked: bool) -> f32 {
+        if ducked {
+            self.volume * self.duck_volume
+        } else {
+            self.volume
+        }
+    }
+
+    /// Turn the loop down on the outputs speech is playing on, and up on the rest
+    fn duck(&self, ducked_outputs: &HashSet<usize>) {
+        for (output, sink) in &self.sinks {
+            sink.set_volume(self.volume(ducked_outputs.contains(output)));
+        }
+    }
+
+    fn stop(&self) {
+        for (_, sink) in &self.sinks {
+            sink.stop();
+        }
+    }
+}
+
+/// Plays one message at a time, most urgent first
+///
+/// A message more urgent than the one playing preempts it as `playback.preempt` says: it is
+/// either stopped and queued again (from the start, ahead of others of its priority), or ducked
+/// and left playing under the new one until that finishes.
+#[derive(Default)]
+struct SpeechQueue {
+    playback: PlaybackConfig,
+    next_id: u64,
+    /// Most urgent first, oldest first within a priority
+    queued: Vec<Queued>,
+    /// The message being heard last; any before it are ducked under it
+    speaking: Vec<Speaking>,
+}
+
+struct Queued {
+    id: u64,
+    utterance: Utterance,
+    queued_at: Instant,
+}
+
+struct Speaking {
+    item: Queued,
+    /// One per output in the message's zone
+    sinks: Vec<OutputSink>,
+    /// Chunks of the speech appended to the sinks so far
+    appended: usize,
+}
+
+impl Speaking {
+    /// Append chunks that arrived since the sinks were last fed
+    fn feed(&mut self) {
+        self.item.utterance.receive();
+        for chunk in &self.item.utterance.speech[self.appended..] {
+            for (_, sink) in &self.sinks {
+                match Decoder::new(Cursor::new(Arc::clone(chunk))) {
+                    Ok(source) => sink.append(source),
+                    Err(e) => tracing::warn!("Skipping speech chunk that can't be decoded: {}", e),
+                }
+            }
+        }
+        self.appended = self.item.utterance.speech.len();
+    }
+
+    /// Played out, with no more chunks to come
+    fn finished(&self) -> bool {
+        self.sinks.iter().all(|(_, sink)| sink.empty()) && self.item.utterance.more.is_none()
+    }
+
+    fn set_volume(&self, volume: f32) {
+        for (_, sink) in &self.sinks {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn stop(&self) {
+        for (_, sink) in &self.sinks {
+            sink.stop();
+        }
+    }
 }
 
 impl AudioManager {
-    pub fn new() -> anyhow::Result<Self> {
+    /// Start the audio thread, which beats `heartbeat` on every loop iteration
+    ///
+    /// `outputs` maps zone names to device names; without any, everything plays on the default
+    /// device.
+    pub fn new(heartbeat: Heartbeat, outputs: BTreeMap<String, String>) -> anyhow::Result<Self> {
         let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let unavailable = Arc::new(Mutex::new(Vec::new()));
 
         // Spawn dedicated audio thread
+        let thread_unavailable = Arc::clone(&unavailable);
         std::thread::spawn(move || {
-            let mut inner = match AudioManagerInner::new() {
+            let mut inner = match AudioManagerInner::new(&outputs, &thread_unavailable) {
                 Ok(inner) => inner,
                 Err(e) => {
                     tracing::error!("Failed to initialize audio: {}", e);
@@ -61,43 +282,56 @@ impl AudioManager {
                 }
             };
 
-            inner.run(command_rx);
+            inner.run(command_rx, heartbeat);
         });
 
-        Ok(Self { command_tx })
+        Ok(Self {
+            command_tx,
+            unavailable,
+        })
     }
 
-    pub async fn play_file(&self, path: PathBuf, volume: f32) -> anyhow::Result<()> {
+    /// Queue a message; it plays once nothing as urgent is ahead of it
+    pub async fn speak(&self, utterance: Utterance, playback: PlaybackConfig) -> anyhow::Result<QueuePosition> {
         let (response_tx, response_rx) = oneshot::channel();
         self.command_tx
-            .send(AudioCommand::PlayFile {
-                path,
-                volume,
+            .send(AudioCommand::Speak {
+                utterance,
+                playback,
                 response: response_tx,
             })
             .map_err(|_| anyhow::anyhow!("Audio thread died"))?;
         response_rx.await?
     }
 
-    pub async fn play_bytes(&self, data: Vec<u8>, volume: f32) -> anyhow::Result<()> {
+    pub async fn queue_status(&self) -> anyhow::Result<QueueStatus> {
         let (response_tx, response_rx) = oneshot::channel();
         self.command_tx
-            .send(AudioCommand::PlayBytes {
-                data,
-                volume,
+            .send(AudioCommand::QueueStatus {
                 response: response_tx,
             })
             .map_err(|_| anyhow::anyhow!("Audio thread died"))?;
-        response_rx.await?
+        Ok(response_rx.await?)
     }
 
-    pub async fn start_alarm(&self, alarm_id: String, path: PathBuf, volume: f32) -> anyhow::Result<()> {
+    /// Start an alarm loop in `zone` (every output if `None`); it drops to `duck_volume` of
+    /// `volume` on an output whenever speech plays there
+    pub async fn start_alarm(
+        &self,
+        alarm_id: String,
+        path: PathBuf,
+        volume: f32,
+        duck_volume: f32,
+        zone: Option<String>,
+    ) -> anyhow::Result<()> {
         let (response_tx, response_rx) = oneshot::channel();
         self.command_tx
             .send(AudioCommand::StartAlarm {
                 alarm_id,
                 path,
                 volume,
+                duck_volume,
+                zone,
                 response: response_tx,
             })
             .map_err(|_| anyhow::anyhow!("Audio thread died"))?;
@@ -114,41 +348,150 @@ impl AudioManager {
             .ok();
         response_rx.await.unwrap_or(false)
     }
+
+    /// Stop every alarm and all speech, returning what was stopped
+    pub async fn stop_all(&self) -> anyhow::Result<Silenced> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.command_tx
+            .send(AudioCommand::StopAll {
+                response: response_tx,
+            })
+            .map_err(|_| anyhow::anyhow!("Audio thread died"))?;
+        Ok(response_rx.await?)
+    }
+
+    /// Alarms that are sounding, oldest first
+    pub async fn list_active(&self) -> anyhow::Result<Vec<ActiveAlarm>> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.command_tx
+            .send(AudioCommand::ListActive {
+                response: response_tx,
+            })
+            .map_err(|_| anyhow::anyhow!("Audio thread died"))?;
+        Ok(response_rx.await?)
+    }
+
+    /// Audio thread health; it exits if no output device can be opened, and is degraded while
+    /// some configured outputs are missing
+    pub fn health(&self) -> ComponentHealth {
+        if self.command_tx.is_closed() {
+            return ComponentHealth::failed("audio", "Audio thread not running (output device unavailable?)");
+        }
+        let unavailable = self.unavailable.lock().unwrap();
+        if unavailable.is_empty() {
+            ComponentHealth::ok("audio")
+        } else {
+            ComponentHealth::degraded("audio", format!("Outputs unavailable: {}", unavailable.join(", ")))
+        }
+    }
+
+    /// Stop all alarms and wait for queued speech to finish
+    pub async fn shutdown(&self) {
+        let (response_tx, response_rx) = oneshot::channel();
+        if self
+            .command_tx
+            .send(AudioCommand::Shutdown {
+                response: response_tx,
+            })
+            .is_ok()
+        {
+            let _ = response_rx.await;
+        }
+    }
 }
 
 impl AudioManagerInner {
-    fn new() -> anyhow::Result<Self> {
-        let (stream, stream_handle) = OutputStream::try_default()?;
+    /// Open every configured output, noting the ones that fail in `unavailable`; an error only
+    /// if none could be opened
+    fn new(devices: &BTreeMap<String, String>, unavailable: &Mutex<Vec<String>>) -> anyhow::Result<Self> {
+        let mut outputs = Vec::new();
+        if devices.is_empty() {
+            let (stream, handle) = OutputStream::try_default()?;
+            outputs.push(Output {
+                name: DEFAULT_OUTPUT.to_string(),
+                _stream: stream,
+                handle,
+            });
+        }
+        for (name, device) in devices {
+            match open_device(device) {
+                Ok((stream, handle)) => {
+                    tracing::info!("Audio output '{}' on device '{}'", name, device);
+                    outputs.push(Output {
+                        name: name.clone(),
+                        _stream: stream,
+                        handle,
+                    });
+                }
+                Err(e) => {
+                    tracing::error!("Failed to open audio output '{}': {:#}", name, e);
+                    unavailable.lock().unwrap().push(name.clone());
+                }
+            }
+        }
+        if outputs.is_empty() {
+            anyhow::bail!("None of the configured audio outputs could be opened");
+        }
+
         Ok(Self {
-            _stream: stream,
-            stream_handle,
+            outputs,
             active_alarms: HashMap::new(),
+            ducked_outputs: HashSet::new(),
+            speech: SpeechQueue::default(),
         })
     }
 
-    fn run(&mut self, mut command_rx: mpsc::UnboundedReceiver<AudioCommand>) {
+    /// The outputs (indices) `zone` plays on: all of them for `None` or `all`
+    fn zone_outputs(&self, zone: Option<&str>) -> anyhow::Result<Vec<usize>> {
+        match zone.filter(|zone| *zone != ALL_ZONES) {
+            None => Ok((0..self.outputs.len()).collect()),
+            Some(zone) => self
+                .outputs
+                .iter()
+                .position(|output| output.name == zone)
+                .map(|output| vec![output])
+                .ok_or_else(|| anyhow::anyhow!("Audio output '{}' isn't available", zone)),
+        }
+    }
+
+    /// A new sink on each output in `zone`
+    fn zone_sinks(&self, zone: Option<&str>) -> anyhow::Result<Vec<OutputSink>> {
+        self.zone_outputs(zone)?
+            .into_iter()
+            .map(|output| Ok((output, Sink::try_new(&self.outputs[output].handle)?)))
+            .collect()
+    }
+
+    fn run(&mut self, mut command_rx: mpsc::UnboundedReceiver<AudioCommand>, heartbeat: Heartbeat) {
         let mut last_cleanup = Instant::now();
 
         loop {
+            heartbeat.beat();
+
             // Try to receive a command with a non-blocking check
             match command_rx.try_recv() {
                 Ok(command) => {
                     match command {
-                        AudioCommand::PlayFile { path, volume, response } => {
-                            let result = self.play_file_inner(&path, volume);
+                        AudioCommand::Speak {
+                            utterance,
+                            playback,
+                            response,
+                        } => {
+                            let result = self.speak_inner(utterance, playback);
                             let _ = response.send(result);
                         }
-                        AudioCommand::PlayBytes { data, volume, response } => {
-                            let result = self.play_bytes_inner(data, volume);
-                            let _ = response.send(result);
+                        AudioCommand::QueueStatus { response } => {
+                            let _ = response.send(self.speech.status());
                         }
                         AudioCommand::StartAlarm {
                             alarm_id,
                             path,
                             volume,
+                            duck_volume,
+                            zone,
                             response,
                         } => {
-                            let result = self.start_alarm_inner(alarm_id, &path, volume);
+                            let result = self.start_alarm_inner(alarm_id, &path, volume, duck_volume, zone);
                             let _ = response.send(result);
                         }
                         AudioCommand::StopAlarm {
@@ -158,9 +501,23 @@ impl AudioManagerInner {
                             let result = self.stop_alarm_inner(&alarm_id);
                             let _ = response.send(result);
                         }
+                        AudioCommand::StopAll { response } => {
+                            let _ = response.send(self.stop_all_inner());
+                        }
+                        AudioCommand::ListActive { response } => {
+                            let _ = response.send(self.list_active_inner());
+                        }
+                        AudioCommand::Shutdown { response } => {
+                            self.drain();
+                            let _ = response.send(());
+                            break;
+                        }
                     }
                 }
                 Err(mpsc::error::TryRecvError::Empty) => {
+                    // Move on to the next message once the current one finishes
+                    self.advance_speech();
+
                     // No command available, check if we need to do cleanup
                     if last_cleanup.elapsed() >= Duration::from_secs(10) {
                         self.cleanup_dead_alarms();
@@ -177,45 +534,165 @@ impl AudioManagerInner {
         }
     }
 
-    fn play_file_inner(&self, path: &PathBuf, volume: f32) -> anyhow::Result<()> {
-        let file = File::open(path)?;
-        let source = Decoder::new(BufReader::new(file))?;
-        let sink = Sink::try_new(&self.stream_handle)?;
-        sink.set_volume(volume);
-        sink.append(source);
-        sink.detach();
-        Ok(())
+    fn speak_inner(&mut self, utterance: Utterance, playback: PlaybackConfig) -> anyhow::Result<QueuePosition> {
+        // Refuse audio that can't be decoded, or has nowhere to play, now rather than when its
+        // turn comes
+        if let Some(first) = utterance.speech.first() {
+            Decoder::new(Cursor::new(Arc::clone(first)))?;
+        }
+        self.zone_outputs(utterance.zone.as_deref())?;
+
+        self.speech.playback = playback;
+        let id = self.speech.push(utterance);
+        if let Some(e) = self.advance_speech().into_iter().find_map(|(failed, e)| (failed == id).then_some(e)) {
+            return Err(e);
+        }
+
+        Ok(match self.speech.queued.iter().position(|item| item.id == id) {
+            Some(ahead) => QueuePosition::Queued(ahead),
+            None => QueuePosition::Playing,
+        })
+    }
+
+    /// Pass on newly synthesised chunks, drop finished messages and start the next, returning
+    /// the ones that failed to start
+    fn advance_speech(&mut self) -> Vec<(u64, anyhow::Error)> {
+        let mut failed = Vec::new();
+        for item in &mut self.speech.queued {
+            item.utterance.receive();
+        }
+        for speaking in &mut self.speech.speaking {
+            speaking.feed();
+        }
+        self.speech.drop_finished();
+
+        while let Some(next) = self.speech.queued.first() {
+            let preempts = match self.speech.speaking.last() {
+                None => false,
+                Some(current) if next.utterance.priority > current.item.utterance.priority => true,
+                Some(_) => break,
+            };
+
+            let item = self.speech.queued.remove(0);
+            if preempts {
+                self.speech.preempt();
+            }
+            let id = item.id;
+            if let Err(e) = self.start_speech(item) {
+                tracing::error!("Failed to play speech: {}", e);
+                failed.push((id, e));
+            }
+        }
+
+        self.duck_alarms();
+        failed
+    }
+
+    /// Turn alarm loops down on the outputs speech plays on so it can be heard, and back up after
+    fn duck_alarms(&mut self) {
+        let ducked: HashSet<usize> = self
+            .speech
+            .speaking
+            .iter()
+            .flat_map(|speaking| speaking.sinks.iter().map(|(output, _)| *output))
+            .collect();
+        if ducked == self.ducked_outputs {
+            return;
+        }
+        self.ducked_outputs = ducked;
+
+        if !self.active_alarms.is_empty() {
+            tracing::debug!(
+                "Ducking alarms on {} of {} output(s) for speech",
+                self.ducked_outputs.len(),
+                self.outputs.len()
+            );
+        }
+        for state in self.active_alarms.values() {
+            state.duck(&self.ducked_outputs);
+        }
     }
 
-    fn play_bytes_inner(&self, data: Vec<u8>, volume: f32) -> anyhow::Result<()> {
-        let cursor = std::io::Cursor::new(data);
-        let source = Decoder::new(cursor)?;
-        let sink = Sink::try_new(&self.stream_handle)?;
-        sink.set_volume(volume);
-        sink.append(source);
-        sink.detach();
+    fn start_speech(&mut self, item: Queued) -> anyhow::Result<()> {
+        let sinks = self.zone_sinks(item.utterance.zone.as_deref())?;
+        for (_, sink) in &sinks {
+            sink.set_volume(item.utterance.volume);
+            if let Some(path) = &item.utterance.tone {
+                match decode_file(path) {
+                    Ok(tone) => sink.append(tone),
+                    Err(e) => tracing::warn!("Failed to play notification tone: {}", e),
+                }
+            }
+        }
+
+        tracing::debug!(
+            "Speaking message {} ({}) after {:?} in the queue",
+            item.id,
+            item.utterance.priority.as_str(),
+            item.queued_at.elapsed()
+        );
+        let mut speaking = Speaking {
+            item,
+            sinks,
+            appended: 0,
+        };
+        speaking.feed();
+        self.speech.speaking.push(speaking);
         Ok(())
     }
 
-    fn start_alarm_inner(&mut self, alarm_id: String, path: &PathBuf, volume: f32) -> anyhow::Result<()> {
-        let file = File::open(path)?;
-        let source = Decoder::new(BufReader::new(file))?.repeat_infinite();
+    /// Stop every alarm and block until queued speech has played (or the drain timeout)
+    fn drain(&mut self) {
+        for (alarm_id, state) in self.active_alarms.drain() {
+            tracing::info!("Stopping alarm '{}' for shutdown", alarm_id);
+            state.stop();
+        }
 
-        let sink = Sink::try_new(&self.stream_handle)?;
-        sink.set_volume(volume);
-        sink.append(source);
+        let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+        loop {
+            self.advance_speech();
+            if self.speech.speaking.is_empty() && self.speech.queued.is_empty() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                tracing::warn!("Audio still playing after {:?}, stopping", SHUTDOWN_DRAIN_TIMEOUT);
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
 
-        // Stop existing alarm with same ID if present
-        if let Some(old_state) = self.active_alarms.remove(&alarm_id) {
-            old_state.sink.stop();
+        self.speech.queued.clear();
+        for speaking in self.speech.speaking.drain(..) {
+            speaking.stop();
         }
+    }
 
+    fn start_alarm_inner(
+        &mut self,
+        alarm_id: String,
+        path: &PathBuf,
+        volume: f32,
+        duck_volume: f32,
+        zone: Option<String>,
+    ) -> anyhow::Result<()> {
         let alarm_state = AlarmState {
-            sink,
+            sinks: self.zone_sinks(zone.as_deref())?,
             path: path.clone(),
+            zone,
             volume,
+            duck_volume,
             started_at: Instant::now(),
+            started: SystemTime::now(),
         };
+        alarm_state.duck(&self.ducked_outputs);
+        for (_, sink) in &alarm_state.sinks {
+            sink.append(decode_file(path)?.repeat_infinite());
+        }
+
+        // Stop existing alarm with same ID if present
+        if let Some(old_state) = self.active_alarms.remove(&alarm_id) {
+            old_state.stop();
+        }
 
         self.active_alarms.insert(alarm_id, alarm_state);
         Ok(())
@@ -223,19 +700,59 @@ impl AudioManagerInner {
 
     fn stop_alarm_inner(&mut self, alarm_id: &str) -> bool {
         if let Some(state) = self.active_alarms.remove(alarm_id) {
-            state.sink.stop();
+            state.stop();
             true
         } else {
             false
         }
     }
 
+    fn stop_all_inner(&mut self) -> Silenced {
+        let mut alarms: Vec<String> = self
+            .active_alarms
+            .drain()
+            .map(|(alarm_id, state)| {
+                state.stop();
+                alarm_id
+            })
+            .collect();
+        alarms.sort();
+
+        let messages = self.speech.speaking.len() + self.speech.queued.len();
+        for speaking in self.speech.speaking.drain(..) {
+            speaking.stop();
+        }
+        self.speech.queued.clear();
+        self.ducked_outputs.clear();
+
+        tracing::info!("Stopped {} alarm(s) and {} message(s)", alarms.len(), messages);
+        Silenced { alarms, messages }
+    }
+
+    fn list_active_inner(&self) -> Vec<ActiveAlarm> {
+        let mut active: Vec<ActiveAlarm> = self
+            .active_alarms
+            .iter()
+            .map(|(alarm_id, state)| ActiveAlarm {
+                alarm_id: alarm_id.clone(),
+                path: state.path.clone(),
+                volume: state.volume,
+                zone: state.zone.clone(),
+                ducked: state.duck_volume < 1.0
+                    && state.sinks.iter().any(|(output, _)| self.ducked_outputs.contains(output)),
+                started: state.started,
+            })
+            .collect();
+        active.sort_by_key(|alarm| alarm.started);
+        active
+    }
+
     fn cleanup_dead_alarms(&mut self) {
         let mut dead_alarms = Vec::new();
 
         for (alarm_id, state) in &self.active_alarms {
-            // Check if the sink is empty (which it shouldn't be for infinite playback)
-            if state.sink.empty() {
+            // Check if a sink is empty (which it shouldn't be for infinite playback)
+            if state.sinks.iter().any(|(_, sink)| sink.empty()) {
                 tracing::warn!(
                     "Alarm '{}' sink became empty after {:?} - this indicates an audio stream error",
                     alarm_id,
@@ -254,8 +771,13 @@ impl AudioManagerInner {
                 );
 
                 // Try to restart the alarm
-                match self.start_alarm_inner(alarm_id.clone(), &state.path, state.volume) {
+                state.stop();
+                match self.start_alarm_inner(alarm_id.clone(), &state.path, state.volume, state.duck_volume, state.zone.clone()) {
                     Ok(_) => {
+                        // It's the same alarm as far as ListActive is concerned
+                        if let Some(restarted) = self.active_alarms.get_mut(&alarm_id) {
+                            restarted.started = state.started;
+                        }
                         tracing::info!("Successfully restarted alarm '{}'", alarm_id);
                     }
                     Err(e) => {
@@ -270,3 +792,101 @@ impl AudioManagerInner {
         }
     }
 }
+
+impl SpeechQueue {
+    /// Queue a message behind everything at least as urgent, returning its ID
+    fn push(&mut self, utterance: Utterance) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.insert(Queued {
+            id,
+            utterance,
+            queued_at: Instant::now(),
+        });
+        id
+    }
+
+    /// Most urgent first, then by ID, so an interrupted message goes back ahead of later ones
+    fn insert(&mut self, item: Queued) {
+        let key = |item: &Queued| (std::cmp::Reverse(item.utterance.priority), item.id);
+        let at = self
+            .queued
+            .iter()
+            .position(|queued| key(queued) > key(&item))
+            .unwrap_or(self.queued.len());
+        self.queued.insert(at, item);
+    }
+
+    /// Forget messages that have played out, restoring the volume of one no longer ducked
+    fn drop_finished(&mut self) {
+        let before = self.speaking.len();
+        self.speaking.retain(|speaking| !speaking.finished());
+        if self.speaking.len() < before {
+            if let Some(current) = self.speaking.last() {
+                current.set_volume(current.item.utterance.volume);
+            }
+        }
+    }
+
+    /// Make way for a more urgent message
+    fn preempt(&mut self) {
+        match self.playback.preempt {
+            Preempt::Interrupt => {
+                if let Some(current) = self.speaking.pop() {
+                    tracing::info!("Interrupting message {} for a more urgent one", current.item.id);
+                    current.stop();
+                    self.insert(current.item);
+                }
+            }
+            Preempt::Duck => {
+                if let Some(current) = self.speaking.last() {
+                    tracing::info!("Ducking message {} under a more urgent one", current.item.id);
+                    current.set_volume(current.item.utterance.volume * self.playback.duck_volume);
+                }
+            }
+        }
+    }
+
+    fn status(&self) -> QueueStatus {
+        let entry = |item: &Queued, ducked| QueueEntry {
+            id: item.id,
+            priority: item.utterance.priority,
+            text: item.utterance.text.clone(),
+            zone: item.utterance.zone.clone(),
+            ducked,
+            waited: item.queued_at.elapsed(),
+        };
+
+        QueueStatus {
+            playing: self
+                .speaking
+                .iter()
+                .rev()
+                .enumerate()
+                .map(|(i, speaking)| entry(&speaking.item, i > 0))
+                .collect(),
+            queued: self.queued.iter().map(|item| entry(item, false)).collect(),
+        }
+    }
+}
+
+/// Open the output device called `name`, listing the ones there are if it doesn't exist
+fn open_device(name: &str) -> anyhow::Result<(OutputStream, OutputStreamHandle)> {
+    let host = rodio::cpal::default_host();
+    let mut names = Vec::new();
+    for device in host.output_devices()? {
+        let Ok(device_name) = device.name() else {
+            continue;
+        };
+        if device_name == name {
+            return Ok(OutputStream::try_from_device(&device)?);
+        }
+        names.push(device_name);
+    }
+    anyhow::bail!("No output device '{}' (devices: {})", name, names.join(", "))
+}
+
+fn decode_file(path: &Path) -> anyhow::Result<Decoder<BufReader<File>>> {
+    let file = File::open(path)?;
+    Ok(Decoder::new(BufReader::new(file))?)
+}
@@ -0,0 +1,135 @@
+use crate::config::Config;
+use anyhow::Context;
+use serde_json::{json, Value};
+use shq_config::{ConfigArchive, ConfigEntry, ConfigManager};
+use shq_scene::Peer;
+use tokio::time::{timeout, Duration};
+
+/// Longest a peer gets to answer an export or import
+const PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Archive entry name for overwatch's own config
+const LOCAL_ENTRY: &str = "overwatch";
+
+/// Outcome for one target of an aggregate export or import ("local" or a peer name)
+pub struct TargetResult {
+    pub target: String,
+    pub result: anyhow::Result<()>,
+}
+
+/// Archive overwatch's config and, with `include_peers`, each scene peer's (keyed by peer
+/// name). Unreachable peers are left out and reported.
+pub async fn export(
+    config: &ConfigManager<Config>,
+    include_peers: bool,
+) -> anyhow::Result<(ConfigArchive, Vec<TargetResult>)> {
+    let mut archive = ConfigArchive::default();
+    archive.entries.insert(
+        LOCAL_ENTRY.to_string(),
+        config.export("overwatch", env!("CARGO_PKG_VERSION"))?,
+    );
+
+    let mut results = Vec::new();
+    if include_peers {
+        for (name, peer) in config.get().scene.peers {
+            let result = match export_peer(&peer).await {
+                Ok(entry) => {
+                    archive.entries.insert(name.clone(), entry);
+                    Ok(())
+                }
+                Err(e) => {
+                    tracing::warn!("Config export from peer '{}' failed: {:#}", name, e);
+                    Err(e)
+                }
+            };
+            results.push(TargetResult {
+                target: name,
+                result,
+            });
+        }
+    }
+
+    Ok((archive, results))
+}
+
+/// Restore overwatch's entry, if the archive has one, then with `include_peers` send every
+/// other entry to the scene peer of the same name (as configured after the local restore)
+pub async fn import(
+    config: &ConfigManager<Config>,
+    archive: &ConfigArchive,
+    include_peers: bool,
+) -> Vec<TargetResult> {
+    let mut results = Vec::new();
+
+    if archive.entries.values().any(|e| e.service == "overwatch") {
+        let result = match archive.entry_for("overwatch", None) {
+            Ok(entry) => config.import(entry).await,
+            Err(e) => Err(e),
+        };
+        results.push(TargetResult {
+            target: "local".to_string(),
+            result,
+        });
+    }
+
+    if include_peers {
+        let peers = config.get().scene.peers;
+        for (name, entry) in &archive.entries {
+            if entry.service == "overwatch" {
+                continue;
+            }
+
+            let result = match peers.get(name) {
+                Some(peer) => import_peer(peer, name, entry).await,
+                None => Err(anyhow::anyhow!("No scene peer named '{}'", name)),
+            };
+            if let Err(e) = &result {
+                tracing::warn!("Config import on peer '{}' failed: {:#}", name, e);
+            }
+            results.push(TargetResult {
+                target: name.clone(),
+                result,
+            });
+        }
+    }
+
+    results
+}
+
+async fn export_peer(peer: &Peer) -> anyhow::Result<ConfigEntry> {
+    let message = json!({"type": "export_config", "token": peer.backup_token});
+    let reply = request(peer, &message).await?;
+
+    let archive: ConfigArchive = serde_json::from_value(reply["archive"].clone())
+        .context("Peer sent an invalid config archive")?;
+    archive
+        .entries
+        .into_values()
+        .next()
+        .context("Peer sent an empty config archive")
+}
+
+async fn import_peer(peer: &Peer, name: &str, entry: &ConfigEntry) -> anyhow::Result<()> {
+    let mut archive = ConfigArchive::default();
+    archive.entries.insert(name.to_string(), entry.clone());
+
+    let message = json!({
+        "type": "import_config",
+        "token": peer.backup_token,
+        "archive": archive,
+        "entry": name,
+    });
+    request(peer, &message).await?;
+    Ok(())
+}
+
+async fn request(peer: &Peer, message: &Value) -> anyhow::Result<Value> {
+    timeout(PEER_TIMEOUT, peer.request(message))
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow::anyhow!(
+                "Timed out after {}s",
+                PEER_TIMEOUT.as_secs()
+            ))
+        })
+}
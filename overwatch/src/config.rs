@@ -1,10 +1,27 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use shq_audit::AuditConfig;
+use shq_config::{BackupConfig, Configuration};
+use shq_i18n::I18nConfig;
+use shq_logging::LoggingConfig;
+use shq_mqtt::MqttConfig;
+use shq_ratelimit::RateLimitConfig;
+use shq_scene::SceneConfig;
+use shq_scheduler::SchedulerConfig;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Zone that plays on every output
+pub const ALL_ZONES: &str = "all";
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
-    pub alarms: HashMap<String, PathBuf>,
+    /// Schema version, maintained by `shq-config` migrations
+    #[serde(default)]
+    pub config_version: u32,
+    #[serde(default)]
+    pub alarms: HashMap<String, AlarmSound>,
+    #[serde(default)]
     pub notification_tones: HashMap<String, PathBuf>,
     #[serde(default = "default_server_address")]
     pub server_address: String,
@@ -14,7 +31,225 @@ pub struct Config {
     pub default_engine: String,
     #[serde(default = "default_volume")]
     pub default_volume: f32,
+    /// Named audio outputs (zones), each an ALSA/PulseAudio device name; speech and alarms play
+    /// on all of them unless a request names a zone. The default device if empty. Read at startup.
+    #[serde(default)]
+    pub outputs: BTreeMap<String, String>,
     pub aws: Option<AwsConfig>,
+    /// Speech synthesis backend; read at startup
+    #[serde(default)]
+    pub tts_backend: TtsBackend,
+    /// Where synthesised speech is cached, how big the cache may grow and what to put in it at
+    /// startup; read at startup
+    #[serde(default)]
+    pub tts_cache: TtsCacheConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Home Assistant MQTT integration (disabled if absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    /// Scheduled alarms and announcements
+    #[serde(default)]
+    pub schedule: SchedulerConfig<VoiceAction>,
+    /// How long SnoozeAlarm silences an alarm unless the request says otherwise
+    #[serde(default = "default_snooze_minutes")]
+    pub snooze_minutes: u32,
+    /// Named bundles of voice actions and peer commands
+    #[serde(default)]
+    pub scene: SceneConfig<VoiceAction>,
+    /// Per-client request limits for the gRPC API
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Config export/import, for this service and its scene peers
+    #[serde(default)]
+    pub backup: BackupConfig,
+    /// Language of client errors and announcement templates
+    #[serde(default)]
+    pub i18n: I18nConfig,
+    /// Audit log of privileged RPCs and config changes
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// How the speech queue handles a more urgent message arriving mid-announcement
+    #[serde(default)]
+    pub playback: PlaybackConfig,
+    /// How long texts are split up for synthesis
+    #[serde(default)]
+    pub synthesis: SynthesisConfig,
+}
+
+/// Action run by a schedule job or scene
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoiceAction {
+    /// Start or stop an alarm loop
+    Alarm {
+        alarm_id: String,
+        #[serde(default = "default_alarm_enabled")]
+        enabled: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        volume: Option<f32>,
+        /// Output to play on; every output if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        zone: Option<String>,
+    },
+    /// Speak a message: fixed `text`, or an `announce.<template>` from the locale files
+    Announce {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+        /// Values for the template's `{name}` placeholders
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        args: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        notification_tone_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        voice_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        volume: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        priority: Option<Priority>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        zone: Option<String>,
+    },
+}
+
+impl VoiceAction {
+    fn zone(&self) -> Option<&str> {
+        match self {
+            VoiceAction::Alarm { zone, .. } | VoiceAction::Announce { zone, .. } => zone.as_deref(),
+        }
+    }
+}
+
+fn default_alarm_enabled() -> bool {
+    true
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_version: Self::VERSION,
+            alarms: HashMap::new(),
+            notification_tones: HashMap::new(),
+            server_address: default_server_address(),
+            default_voice: default_voice(),
+            default_engine: default_engine(),
+            default_volume: default_volume(),
+            outputs: BTreeMap::new(),
+            aws: None,
+            tts_backend: TtsBackend::default(),
+            tts_cache: TtsCacheConfig::default(),
+            logging: LoggingConfig::default(),
+            mqtt: None,
+            schedule: SchedulerConfig::default(),
+            snooze_minutes: default_snooze_minutes(),
+            scene: SceneConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            backup: BackupConfig::default(),
+            i18n: I18nConfig::default(),
+            audit: AuditConfig::default(),
+            playback: PlaybackConfig::default(),
+            synthesis: SynthesisConfig::default(),
+        }
+    }
+}
+
+impl Configuration for Config {
+    fn validate(&self) -> anyhow::Result<()> {
+        if !(0.0..=2.0).contains(&self.default_volume) {
+            anyhow::bail!(
+                "default_volume must be between 0.0 and 2.0, got {}",
+                self.default_volume
+            );
+        }
+
+        for (name, device) in &self.outputs {
+            if name == ALL_ZONES {
+                anyhow::bail!("outputs: '{}' is reserved for every output", ALL_ZONES);
+            }
+            if device.is_empty() {
+                anyhow::bail!("outputs: '{}' needs a device name", name);
+            }
+        }
+
+        self.schedule.validate()?;
+        for job in &self.schedule.jobs {
+            if let Some(zone) = job.action.zone().filter(|zone| !self.has_zone(zone)) {
+                anyhow::bail!("schedule job '{}': unknown zone '{}'", job.id, zone);
+            }
+            match &job.action {
+                VoiceAction::Alarm { alarm_id, .. } if !self.alarms.contains_key(alarm_id) => {
+                    anyhow::bail!("schedule job '{}': unknown alarm '{}'", job.id, alarm_id);
+                }
+                VoiceAction::Announce { text, template, .. } if text.is_some() == template.is_some() => {
+                    anyhow::bail!("schedule job '{}': announce needs either text or template", job.id);
+                }
+                _ => {}
+            }
+        }
+
+        if self.snooze_minutes == 0 {
+            anyhow::bail!("snooze_minutes must be at least 1");
+        }
+
+        self.rate_limit.validate()?;
+        self.i18n.validate()?;
+
+        if !(0.0..=1.0).contains(&self.playback.duck_volume) {
+            anyhow::bail!(
+                "playback.duck_volume must be between 0.0 and 1.0, got {}",
+                self.playback.duck_volume
+            );
+        }
+        if !(0.0..=1.0).contains(&self.playback.alarm_duck_volume) {
+            anyhow::bail!(
+                "playback.alarm_duck_volume must be between 0.0 and 1.0, got {}",
+                self.playback.alarm_duck_volume
+            );
+        }
+        if !(1..=MAX_CHUNK_CHARS).contains(&self.synthesis.max_chunk_chars) {
+            anyhow::bail!(
+                "synthesis.max_chunk_chars must be between 1 and {}, got {}",
+                MAX_CHUNK_CHARS,
+                self.synthesis.max_chunk_chars
+            );
+        }
+        for phrase in &self.tts_cache.prewarm {
+            if phrase.text.is_some() == phrase.template.is_some() {
+                anyhow::bail!("tts_cache.prewarm: each phrase needs either text or template");
+            }
+        }
+        if self.synthesis.concurrency == 0 {
+            anyhow::bail!("synthesis.concurrency must be at least 1");
+        }
+        for (alarm_id, alarm) in &self.alarms {
+            if let Some(duck_volume) = alarm.duck_volume().filter(|v| !(0.0..=1.0).contains(v)) {
+                anyhow::bail!(
+                    "alarm '{}': duck_volume must be between 0.0 and 1.0, got {}",
+                    alarm_id,
+                    duck_volume
+                );
+            }
+        }
+
+        self.scene.validate()?;
+        for (scene, action) in self.scene.local_actions() {
+            if let Some(zone) = action.zone().filter(|zone| !self.has_zone(zone)) {
+                anyhow::bail!("scene '{}': unknown zone '{}'", scene, zone);
+            }
+            match action {
+                VoiceAction::Alarm { alarm_id, .. } if !self.alarms.contains_key(alarm_id) => {
+                    anyhow::bail!("scene '{}': unknown alarm '{}'", scene, alarm_id);
+                }
+                VoiceAction::Announce { text, template, .. } if text.is_some() == template.is_some() => {
+                    anyhow::bail!("scene '{}': announce needs either text or template", scene);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 }
 
 fn default_voice() -> String {
@@ -29,6 +264,176 @@ fn default_volume() -> f32 {
     1.0
 }
 
+fn default_snooze_minutes() -> u32 {
+    9
+}
+
+/// Where speech comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TtsBackend {
+    /// AWS Polly, with a local cache
+    #[default]
+    Polly,
+    /// Silent audio without calling AWS, for tests and offline development
+    Stub,
+}
+
+/// How urgent a spoken message is; a more urgent one preempts a less urgent one that's playing,
+/// and the queue plays the most urgent first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Background chatter such as reminders
+    Ambient,
+    #[default]
+    Announcement,
+    /// Warnings that mustn't wait behind anything else
+    Alarm,
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ambient => "ambient",
+            Self::Announcement => "announcement",
+            Self::Alarm => "alarm",
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "ambient" => Ok(Self::Ambient),
+            "announcement" => Ok(Self::Announcement),
+            "alarm" => Ok(Self::Alarm),
+            other => anyhow::bail!("unknown priority '{}'", other),
+        }
+    }
+}
+
+/// What happens to a message that's playing when a more urgent one arrives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preempt {
+    /// Stop it, and play it again from the start once the urgent one is done
+    #[default]
+    Interrupt,
+    /// Keep it playing at `duck_volume` under the urgent one
+    Duck,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PlaybackConfig {
+    pub preempt: Preempt,
+    /// Volume of a ducked message, as a fraction of its own (0.0-1.0)
+    pub duck_volume: f32,
+    /// Volume of alarm loops while speech plays, as a fraction of their own (0.0-1.0); an
+    /// alarm's own `duck_volume` overrides it
+    pub alarm_duck_volume: f32,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        Self {
+            preempt: Preempt::default(),
+            duck_volume: 0.25,
+            alarm_duck_volume: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TtsCacheConfig {
+    pub dir: PathBuf,
+    /// Size the cache is kept under by deleting the least recently used audio; 0 for no limit
+    pub max_size_mb: u64,
+    /// Synthesised in the background at startup and never evicted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub prewarm: Vec<PrewarmPhrase>,
+}
+
+impl Default for TtsCacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("./cache/tts"),
+            max_size_mb: 500,
+            prewarm: Vec::new(),
+        }
+    }
+}
+
+/// A phrase to have cached before it's first needed: fixed `text`, or an `announce.<template>`
+/// as Verbalise would speak it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PrewarmPhrase {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    /// `default_voice` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_id: Option<String>,
+}
+
+/// Polly's limit on the text of one request
+const MAX_CHUNK_CHARS: usize = 3000;
+
+/// Long texts are split at sentence ends into chunks that are synthesised side by side, and
+/// the first starts playing while the rest are still being synthesised
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SynthesisConfig {
+    /// Longest chunk, in characters (at most 3000)
+    pub max_chunk_chars: usize,
+    /// Chunks synthesised at once
+    pub concurrency: usize,
+}
+
+impl Default for SynthesisConfig {
+    fn default() -> Self {
+        Self {
+            max_chunk_chars: 300,
+            concurrency: 3,
+        }
+    }
+}
+
+/// An alarm's sound file: just the path, or `{ path, duck_volume }`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AlarmSound {
+    Path(PathBuf),
+    Options {
+        path: PathBuf,
+        /// Volume while speech plays, as a fraction of the alarm's own (0.0-1.0; 1.0 to not duck)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duck_volume: Option<f32>,
+    },
+}
+
+impl AlarmSound {
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            Self::Path(path) | Self::Options { path, .. } => path,
+        }
+    }
+
+    pub fn duck_volume(&self) -> Option<f32> {
+        match self {
+            Self::Path(_) => None,
+            Self::Options { duck_volume, .. } => *duck_volume,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AwsConfig {
     pub region: Option<String>,
@@ -41,17 +446,21 @@ fn default_server_address() -> String {
 }
 
 impl Config {
-    pub fn from_file(path: &str) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = serde_yaml::from_str(&content)?;
-        Ok(config)
+    pub fn get_alarm(&self, alarm_id: &str) -> Option<&AlarmSound> {
+        self.alarms.get(alarm_id)
     }
 
-    pub fn get_alarm(&self, alarm_id: &str) -> Option<&PathBuf> {
-        self.alarms.get(alarm_id)
+    /// How far an alarm loop is turned down while speech plays
+    pub fn alarm_duck_volume(&self, alarm: &AlarmSound) -> f32 {
+        alarm.duck_volume().unwrap_or(self.playback.alarm_duck_volume)
     }
 
     pub fn get_notification_tone(&self, tone_id: &str) -> Option<&PathBuf> {
         self.notification_tones.get(tone_id)
     }
+
+    /// Whether `zone` is `all` or a configured output
+    pub fn has_zone(&self, zone: &str) -> bool {
+        zone == ALL_ZONES || self.outputs.contains_key(zone)
+    }
 }
@@ -1,46 +1,156 @@
 mod audio;
+mod backup;
 mod config;
+mod mqtt;
+mod schedule;
 mod service;
 mod tts;
 
 use config::Config;
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog};
+use shq_config::ConfigManager;
 use service::voice::voice_service_server::VoiceServiceServer;
-use service::VoiceServiceImpl;
+use service::{RateLimitInterceptor, VoiceServiceImpl, LOCALES};
+use shq_i18n::Translator;
+use shq_logging::LogFormat;
+use shq_ratelimit::RateLimiter;
+use tokio::time::Duration;
 use tonic::transport::Server;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "overwatch=info,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Initialize tracing (file logging is enabled once config is loaded)
+    let log_handle = shq_logging::init("overwatch=info,tower_http=debug", LogFormat::from_args()?);
 
     // Load configuration
     let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.yaml".to_string());
     tracing::info!("Loading configuration from: {}", config_path);
-    let config = Config::from_file(&config_path)?;
+    let config_manager = ConfigManager::<Config>::load(config_path).await?;
 
-    let server_address = config.server_address.clone();
+    let mut logging_config = config_manager.get().logging;
+    if let Err(e) = log_handle.apply(&logging_config) {
+        tracing::warn!("File logging disabled: {:#}", e);
+    }
+
+    let crash_config = config_manager.clone();
+    log_handle.install_panic_hook(
+        "overwatch",
+        env!("CARGO_PKG_VERSION"),
+        config_manager.path().with_file_name("crash-report.json"),
+        move || crash_config.hash(),
+    );
+
+    // Security audit trail: privileged RPCs and config changes
+    let audit = AuditLog::open(
+        "overwatch",
+        &config_manager.get().audit,
+        config_manager.path().with_file_name("audit.jsonl"),
+    );
+    audit.record(
+        AuditEvent::new(AuditKind::Service, "started").detail(json!({ "version": env!("CARGO_PKG_VERSION") })),
+    );
+    audit.watch_config(config_manager.subscribe());
+
+    // Alarms, tones, defaults, logging, schedule, rate limits and locale are hot-reloaded; server
+    // address, AWS and MQTT need a restart
+    config_manager.watch(Duration::from_secs(2));
+    let mut config_rx = config_manager.subscribe();
+
+    let config = config_manager.get();
+    let schedule_state_path = config_manager.path().with_file_name("schedule-state.json");
+
+    let mut i18n_config = config.i18n.clone();
+    let translator = Translator::new(LOCALES, &i18n_config);
 
     // Create service
     tracing::info!("Initializing voice service...");
-    let voice_service = VoiceServiceImpl::new(config).await?;
+    let watchdog = shq_systemd::Watchdog::new();
+    let voice_service = VoiceServiceImpl::new(
+        config_manager,
+        log_handle.clone(),
+        watchdog.heartbeat("audio"),
+        translator.clone(),
+        audit.clone(),
+    )
+    .await?;
+    voice_service.prewarm();
+
+    // Home Assistant integration (needs a restart to pick up config changes)
+    if let Some(mqtt_config) = &config.mqtt {
+        let alarm_ids = config.alarms.keys().cloned().collect();
+        mqtt::start(mqtt_config, voice_service.clone(), alarm_ids);
+    }
+
+    // Scheduled alarms and announcements
+    let mut schedule_config = config.schedule.clone();
+    let scheduler = schedule::start(
+        schedule_config.clone(),
+        schedule_state_path,
+        voice_service.clone(),
+    );
+
+    let rate_limiter = RateLimiter::new(config.rate_limit.clone());
+
+    let reload_log_handle = log_handle.clone();
+    let reload_rate_limiter = rate_limiter.clone();
+    let reload_translator = translator.clone();
+    tokio::spawn(async move {
+        while config_rx.changed().await.is_ok() {
+            let config = config_rx.borrow_and_update().clone();
+            reload_rate_limiter.update(config.rate_limit.clone());
+
+            if config.schedule != schedule_config {
+                scheduler.update(config.schedule.clone());
+                schedule_config = config.schedule;
+            }
+
+            if config.i18n != i18n_config {
+                if let Err(e) = reload_translator.update(&config.i18n) {
+                    tracing::warn!("Failed to apply i18n config: {:#}", e);
+                }
+                i18n_config = config.i18n;
+            }
+
+            if config.logging != logging_config {
+                if let Err(e) = reload_log_handle.apply(&config.logging) {
+                    tracing::warn!("Failed to apply logging config: {:#}", e);
+                }
+                logging_config = config.logging;
+            }
+        }
+    });
 
     // Parse server address
-    let addr = server_address.parse()?;
+    let addr = config.server_address.parse()?;
 
     tracing::info!("Starting gRPC server on {}", addr);
+    watchdog.start();
+    shq_systemd::notify_ready();
+
+    // Serve until SIGTERM/SIGINT, then stop accepting and let in-flight RPCs finish
+    let shutdown_service = voice_service.clone();
+    let shutdown = async move {
+        shq_systemd::shutdown_signal().await;
+        tracing::info!("Shutting down...");
+        shq_systemd::notify_stopping();
+        shutdown_service.begin_shutdown();
+    };
 
-    // Start server
     Server::builder()
-        .add_service(VoiceServiceServer::new(voice_service))
-        .serve(addr)
+        // Each RPC gets its own correlation ID, like a WebSocket command in dosa and nyx
+        .trace_fn(|request| shq_logging::command_span(request.uri().path()))
+        .add_service(VoiceServiceServer::with_interceptor(
+            voice_service.clone(),
+            RateLimitInterceptor::new(rate_limiter, translator),
+        ))
+        .serve_with_shutdown(addr, shutdown)
         .await?;
 
+    // Stop alarms and let any announcement that is still playing finish
+    voice_service.finish_shutdown().await;
+    audit.record(AuditEvent::new(AuditKind::Service, "stopped"));
+
+    tracing::info!("Shutdown complete");
     Ok(())
 }
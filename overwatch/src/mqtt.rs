@@ -0,0 +1,140 @@
+use crate::service::voice::voice_service_server::VoiceService;
+use crate::service::voice::{SetAlarmRequest, VerbaliseRequest};
+use crate::service::VoiceServiceImpl;
+use serde::Deserialize;
+use shq_mqtt::{DeviceInfo, Entity, MqttClient, MqttCommand, MqttConfig};
+use std::collections::HashSet;
+use tonic::Request;
+
+const ANNOUNCE: &str = "announce";
+const ALARM: &str = "alarm";
+
+/// Siren command as published by Home Assistant (JSON unless a command template is set)
+#[derive(Debug, Deserialize)]
+struct SirenCommand {
+    state: String,
+    tone: Option<String>,
+    volume_level: Option<f32>,
+}
+
+/// Expose overwatch to Home Assistant as a notify entity (announcements) and a siren (alarms)
+///
+/// Siren tones are the alarm IDs from config at startup; adding alarms needs a restart to
+/// show up in HA.
+pub fn start(config: &MqttConfig, service: VoiceServiceImpl, mut alarm_ids: Vec<String>) {
+    alarm_ids.sort();
+
+    let entities = vec![
+        Entity::notify(ANNOUNCE, "Announcement"),
+        Entity::siren(ALARM, "Alarm")
+            .with("available_tones", alarm_ids.clone())
+            .with("support_duration", false)
+            .with("support_volume_set", true),
+    ];
+
+    let device = DeviceInfo {
+        service: "overwatch".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let (client, mut commands) = MqttClient::connect(config, device, entities);
+    client.publish(ALARM, "state", "OFF");
+
+    tokio::spawn(async move {
+        let mut active_alarms = HashSet::new();
+
+        while let Some(command) = commands.recv().await {
+            match command.object_id.as_str() {
+                ANNOUNCE => announce(&service, command),
+                ALARM => {
+                    set_siren(&service, &alarm_ids, &mut active_alarms, command).await;
+                    let state = if active_alarms.is_empty() {
+                        "OFF"
+                    } else {
+                        "ON"
+                    };
+                    client.publish(ALARM, "state", state);
+                }
+                other => tracing::debug!("Ignoring MQTT command for unknown entity '{}'", other),
+            }
+        }
+    });
+}
+
+/// Speak a notify message in the background so alarms aren't blocked behind synthesis
+fn announce(service: &VoiceServiceImpl, command: MqttCommand) {
+    let service = service.clone();
+
+    tokio::spawn(async move {
+        let request = Request::new(VerbaliseRequest {
+            text: command.payload,
+            notification_tone_id: None,
+            voice_id: None,
+            volume: None,
+            template: None,
+            args: Default::default(),
+            priority: None,
+            zone: None,
+        });
+
+        if let Err(e) = service.verbalise(request).await {
+            tracing::error!("MQTT announcement failed: {}", e.message());
+        }
+    });
+}
+
+/// Start the requested alarm tone, or stop every alarm
+async fn set_siren(
+    service: &VoiceServiceImpl,
+    alarm_ids: &[String],
+    active_alarms: &mut HashSet<String>,
+    command: MqttCommand,
+) {
+    // Accept plain ON/OFF as well as HA's JSON payload
+    let siren = serde_json::from_str::<SirenCommand>(&command.payload).unwrap_or(SirenCommand {
+        state: command.payload.trim().to_string(),
+        tone: None,
+        volume_level: None,
+    });
+
+    if siren.state.eq_ignore_ascii_case("ON") {
+        let Some(alarm_id) = siren.tone.or_else(|| alarm_ids.first().cloned()) else {
+            tracing::warn!("MQTT siren turned on but no alarms are configured");
+            return;
+        };
+
+        let request = Request::new(SetAlarmRequest {
+            alarm_id: alarm_id.clone(),
+            enabled: true,
+            volume: siren.volume_level,
+            zone: None,
+        });
+
+        match service.set_alarm(request).await {
+            Ok(response) if response.get_ref().success => {
+                active_alarms.insert(alarm_id);
+            }
+            Ok(response) => tracing::error!("MQTT siren failed: {}", response.get_ref().message),
+            Err(e) => tracing::error!("MQTT siren failed: {}", e.message()),
+        }
+    } else {
+        // Also stop alarms started over gRPC, which this bridge doesn't know about
+        let stop_ids: HashSet<String> = active_alarms
+            .drain()
+            .chain(alarm_ids.iter().cloned())
+            .collect();
+
+        for alarm_id in stop_ids {
+            let request = Request::new(SetAlarmRequest {
+                alarm_id,
+                enabled: false,
+                volume: None,
+                zone: None,
+            });
+
+            if let Err(e) = service.set_alarm(request).await {
+                tracing::debug!("Failed to stop alarm from MQTT: {}", e.message());
+            }
+        }
+    }
+}
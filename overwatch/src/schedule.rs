@@ -0,0 +1,25 @@
+use crate::config::VoiceAction;
+use crate::service::VoiceServiceImpl;
+use shq_scheduler::{Scheduler, SchedulerConfig};
+use std::path::PathBuf;
+
+/// Run scheduled alarms and announcements through the gRPC service methods; one-shot jobs are
+/// removed from the config once they've run
+pub fn start(
+    config: SchedulerConfig<VoiceAction>,
+    state_path: PathBuf,
+    service: VoiceServiceImpl,
+) -> Scheduler<VoiceAction> {
+    let (scheduler, mut triggers) = Scheduler::start(config, Some(state_path));
+
+    tokio::spawn(async move {
+        while let Some(trigger) = triggers.recv().await {
+            if let Err(e) = service.run_action(trigger.action).await {
+                tracing::error!("Schedule job '{}' failed: {}", trigger.job_id, e);
+            }
+            service.finish_one_shot(&trigger.job_id, trigger.scheduled).await;
+        }
+    });
+
+    scheduler
+}
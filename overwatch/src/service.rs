@@ -1,98 +1,719 @@
-use crate::audio::AudioManager;
-use crate::config::Config;
+use crate::audio::{ActiveAlarm, AudioManager, QueueEntry, QueuePosition, QueueStatus, Silenced, Utterance};
+use crate::backup::{self, TargetResult};
+use crate::config::{Config, Priority, VoiceAction};
 use crate::tts::TtsService;
+use chrono::{DateTime, Local, Utc};
+use serde_json::json;
+use shq_audit::{AuditEvent, AuditKind, AuditLog, AuditPage, AuditQuery};
+use shq_config::{ConfigArchive, ConfigManager};
+use shq_i18n::{BuiltinLocales, Translator};
+use shq_logging::{LogHandle, LogLine};
+use shq_protocol::{Health, SceneReport, ServiceInfo};
+use shq_ratelimit::RateLimiter;
+use shq_scheduler::{Job, MissedRunPolicy};
+use shq_systemd::Heartbeat;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataValue;
+use tonic::service::Interceptor;
 use tonic::{Request, Response, Status};
 
 pub mod voice {
     tonic::include_proto!("voice");
 }
 
+use voice::schedule::Action as ScheduleAction;
 use voice::voice_service_server::VoiceService;
 use voice::{
-    SetAlarmRequest, SetAlarmResponse, VerbaliseRequest, VerbaliseResponse,
+    ActiveAlarm as ActiveAlarmInfo, AuditChain, AuditRecord, CancelScheduleRequest, CancelScheduleResponse, ComponentHealth, ConfigTargetResult, CrashInfo, CreateScheduleRequest, CreateScheduleResponse, Envelope, ExportConfigRequest, ExportConfigResponse,
+    GetAuditLogRequest, GetAuditLogResponse, GetHealthRequest, GetHealthResponse, GetInfoRequest, GetInfoResponse, GetQueueRequest, GetQueueResponse, ImportConfigRequest, ImportConfigResponse, ListActiveRequest, ListActiveResponse, ListSchedulesRequest, ListSchedulesResponse, LogEntry, PlaybackItem, PurgeCacheRequest, PurgeCacheResponse, RunSceneRequest, RunSceneResponse, SceneActionResult,
+    Schedule, SetAlarmRequest, SetAlarmResponse, SnoozeAlarmRequest, SnoozeAlarmResponse, StopAllRequest, StopAllResponse, StreamLogsRequest, VerbaliseRequest, VerbaliseResponse,
 };
 
+/// gRPC API revision, reported by GetInfo
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features advertised by GetInfo
+const CAPABILITIES: &[&str] = &["alarms", "verbalise", "notification_tones", "logs", "health", "scenes", "envelope", "rate_limit", "config_backup", "i18n", "audit", "queue", "stop_all", "list_active", "purge_cache", "schedules", "snooze", "zones"];
+
+/// Built-in message catalogs and announcement templates
+pub const LOCALES: BuiltinLocales = &[
+    ("en", include_str!("../locales/en.yaml")),
+    ("de", include_str!("../locales/de.yaml")),
+];
+
+/// Recent log lines sent to a new log subscriber unless it asks for a different amount
+const DEFAULT_LOG_BACKLOG: usize = 100;
+
+/// Schedule job that restarts a snoozed alarm; snoozing again moves it
+fn snooze_job_id(alarm_id: &str) -> String {
+    format!("snooze-{}", alarm_id)
+}
+
+impl From<ServiceInfo> for GetInfoResponse {
+    fn from(info: ServiceInfo) -> Self {
+        Self {
+            service: info.service,
+            version: info.version,
+            protocol: info.protocol,
+            capabilities: info.capabilities,
+            accepted: info.accepted.unwrap_or_default(),
+            unsupported: info.unsupported.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<Health> for GetHealthResponse {
+    fn from(health: Health) -> Self {
+        Self {
+            service: health.service,
+            status: health.status.as_str().to_string(),
+            components: health
+                .components
+                .into_iter()
+                .map(|c| ComponentHealth {
+                    name: c.name,
+                    status: c.status.as_str().to_string(),
+                    reason: c.reason,
+                })
+                .collect(),
+            last_crash: health.last_crash.map(|crash| CrashInfo {
+                timestamp_ms: crash.timestamp_ms,
+                version: crash.version,
+                message: crash.message,
+                location: crash.location,
+                config_hash: crash.config_hash,
+            }),
+        }
+    }
+}
+
+impl From<SceneReport> for RunSceneResponse {
+    fn from(report: SceneReport) -> Self {
+        Self {
+            scene: report.scene,
+            success: report.success,
+            results: report
+                .results
+                .into_iter()
+                .map(|r| SceneActionResult {
+                    target: r.target,
+                    action: r.action,
+                    success: r.success,
+                    error: r.error,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<TargetResult> for ConfigTargetResult {
+    fn from(target: TargetResult) -> Self {
+        Self {
+            target: target.target,
+            success: target.result.is_ok(),
+            error: target.result.err().map(|e| format!("{:#}", e)),
+        }
+    }
+}
+
+impl From<AuditPage> for GetAuditLogResponse {
+    fn from(page: AuditPage) -> Self {
+        Self {
+            records: page
+                .records
+                .into_iter()
+                .map(|r| AuditRecord {
+                    seq: r.seq,
+                    timestamp_ms: r.timestamp_ms,
+                    service: r.service,
+                    kind: r.kind.as_str().to_string(),
+                    action: r.action,
+                    client: r.client,
+                    detail: r.detail.map(|d| d.to_string()),
+                    success: r.success,
+                    error: r.error,
+                    prev_hash: r.prev_hash,
+                    hash: r.hash,
+                })
+                .collect(),
+            chain: Some(AuditChain {
+                valid: page.chain.valid,
+                records: page.chain.records,
+                head: page.chain.head,
+                error: page.chain.error,
+            }),
+        }
+    }
+}
+
+impl From<QueueEntry> for PlaybackItem {
+    fn from(entry: QueueEntry) -> Self {
+        Self {
+            id: entry.id,
+            priority: entry.priority.as_str().to_string(),
+            text: entry.text,
+            ducked: entry.ducked,
+            waited_ms: entry.waited.as_millis() as u64,
+            zone: entry.zone,
+        }
+    }
+}
+
+impl From<QueueStatus> for GetQueueResponse {
+    fn from(status: QueueStatus) -> Self {
+        Self {
+            playing: status.playing.into_iter().map(Into::into).collect(),
+            queued: status.queued.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<Silenced> for StopAllResponse {
+    fn from(silenced: Silenced) -> Self {
+        Self {
+            alarms: silenced.alarms,
+            messages: silenced.messages as u32,
+        }
+    }
+}
+
+impl From<ActiveAlarm> for ActiveAlarmInfo {
+    fn from(alarm: ActiveAlarm) -> Self {
+        Self {
+            alarm_id: alarm.alarm_id,
+            started_ms: alarm
+                .started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            volume: alarm.volume,
+            path: alarm.path.display().to_string(),
+            ducked: alarm.ducked,
+            zone: alarm.zone,
+        }
+    }
+}
+
+impl From<VoiceAction> for ScheduleAction {
+    fn from(action: VoiceAction) -> Self {
+        match action {
+            VoiceAction::Alarm {
+                alarm_id,
+                enabled,
+                volume,
+                zone,
+            } => ScheduleAction::Alarm(SetAlarmRequest {
+                alarm_id,
+                enabled,
+                volume,
+                zone,
+            }),
+            VoiceAction::Announce {
+                text,
+                template,
+                args,
+                notification_tone_id,
+                voice_id,
+                volume,
+                priority,
+                zone,
+            } => ScheduleAction::Announce(VerbaliseRequest {
+                text: text.unwrap_or_default(),
+                notification_tone_id,
+                voice_id,
+                volume,
+                template,
+                args: args.into_iter().collect(),
+                priority: priority.map(|p| p.as_str().to_string()),
+                zone,
+            }),
+        }
+    }
+}
+
+impl From<shq_protocol::Envelope<()>> for Envelope {
+    fn from(envelope: shq_protocol::Envelope<()>) -> Self {
+        Self {
+            id: envelope.id.map(|id| match id {
+                serde_json::Value::String(id) => id,
+                id => id.to_string(),
+            }),
+            timestamp: envelope.timestamp,
+            source: envelope.source,
+            seq: envelope.seq,
+        }
+    }
+}
+
+impl From<LogLine> for LogEntry {
+    fn from(line: LogLine) -> Self {
+        Self {
+            timestamp_ms: line.timestamp_ms,
+            level: line.level,
+            target: line.target,
+            message: line.message,
+            envelope: None,
+        }
+    }
+}
+
+/// Stamps streamed events with an envelope; `seq` is shared by every stream
+#[derive(Clone, Default)]
+struct EventStamper {
+    next_seq: Arc<AtomicU64>,
+}
+
+impl EventStamper {
+    fn log_entry(&self, id: &Option<String>, line: LogLine) -> LogEntry {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        LogEntry {
+            envelope: Some(shq_protocol::Envelope::new(id.clone().map(Into::into), "overwatch", seq, ()).into()),
+            ..line.into()
+        }
+    }
+}
+
+/// Refuses requests from clients over their rate limit with RESOURCE_EXHAUSTED
+///
+/// The wait before retrying is in the message and in `retry-after-ms` metadata.
+#[derive(Clone)]
+pub struct RateLimitInterceptor {
+    limiter: RateLimiter,
+    tr: Translator,
+}
+
+impl RateLimitInterceptor {
+    pub fn new(limiter: RateLimiter, tr: Translator) -> Self {
+        Self { limiter, tr }
+    }
+}
+
+impl Interceptor for RateLimitInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(addr) = request.remote_addr() else {
+            return Ok(request);
+        };
+
+        match self.limiter.check(addr.ip()) {
+            Ok(()) => Ok(request),
+            Err(limited) => {
+                tracing::debug!("Rate limited {}: {}", addr, limited);
+                let message = self
+                    .tr
+                    .t("error.rate_limited", &[("retry_after_ms", &limited.retry_after_ms())]);
+                let mut status = Status::resource_exhausted(message);
+                status
+                    .metadata_mut()
+                    .insert("retry-after-ms", MetadataValue::from(limited.retry_after_ms()));
+                Err(status)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct VoiceServiceImpl {
-    config: Arc<Config>,
+    config: ConfigManager<Config>,
+    log_handle: LogHandle,
     audio_manager: Arc<AudioManager>,
     tts_service: Arc<TtsService>,
+    /// Flipped to `true` when the server starts shutting down; ends open log streams
+    shutdown: Arc<watch::Sender<bool>>,
+    events: EventStamper,
+    /// Client-facing messages and announcement templates
+    tr: Translator,
+    audit: AuditLog,
 }
 
 impl VoiceServiceImpl {
-    pub async fn new(config: Config) -> anyhow::Result<Self> {
-        let audio_manager = AudioManager::new()?;
-        let tts_service = TtsService::new(config.aws.as_ref()).await;
+    pub async fn new(
+        config: ConfigManager<Config>,
+        log_handle: LogHandle,
+        audio_heartbeat: Heartbeat,
+        tr: Translator,
+        audit: AuditLog,
+    ) -> anyhow::Result<Self> {
+        let tts_config = config.get();
+        let audio_manager = AudioManager::new(audio_heartbeat, tts_config.outputs.clone())?;
+        let tts_service = TtsService::new(tts_config.tts_backend, tts_config.aws.as_ref(), &tts_config.tts_cache).await;
 
         Ok(Self {
-            config: Arc::new(config),
+            config,
+            log_handle,
             audio_manager: Arc::new(audio_manager),
             tts_service: Arc::new(tts_service),
+            shutdown: Arc::new(watch::channel(false).0),
+            events: EventStamper::default(),
+            tr,
+            audit,
+        })
+    }
+
+    /// Check audio output, Polly and the configured sound files
+    fn collect_health(&self) -> Health {
+        let config = self.config.get();
+
+        let mut missing: Vec<String> = config
+            .alarms
+            .iter()
+            .map(|(id, alarm)| (id, alarm.path()))
+            .chain(config.notification_tones.iter())
+            .filter(|(_, path)| !path.is_file())
+            .map(|(id, _)| id.clone())
+            .collect();
+        missing.sort();
+
+        let sounds = if missing.is_empty() {
+            shq_protocol::ComponentHealth::ok("sounds")
+        } else {
+            shq_protocol::ComponentHealth::degraded(
+                "sounds",
+                format!("Missing sound files: {}", missing.join(", ")),
+            )
+        };
+
+        let mut components = vec![self.audio_manager.health(), self.tts_service.health(), sounds];
+        components.extend(self.audit.health());
+        Health::new("overwatch", components).with_last_crash(self.log_handle.last_crash())
+    }
+
+    /// Synthesise the `tts_cache.prewarm` phrases in the background, so they play without a wait
+    pub fn prewarm(&self) {
+        let config = self.config.get();
+        if config.tts_cache.prewarm.is_empty() {
+            return;
+        }
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut warmed = 0;
+            for phrase in &config.tts_cache.prewarm {
+                let text = match (&phrase.text, &phrase.template) {
+                    (Some(text), _) => text.clone(),
+                    (None, Some(template)) => {
+                        match service.tr.template(&format!("announce.{}", template), &phrase.args) {
+                            Some(text) => text,
+                            None => {
+                                tracing::warn!("Can't pre-warm unknown template '{}'", template);
+                                continue;
+                            }
+                        }
+                    }
+                    (None, None) => continue,
+                };
+                let voice_name = phrase.voice_id.as_ref().unwrap_or(&config.default_voice);
+
+                match service
+                    .tts_service
+                    .prewarm(&text, voice_name, &config.default_engine, &config.synthesis)
+                    .await
+                {
+                    Ok(()) => warmed += 1,
+                    Err(e) => tracing::warn!("Failed to pre-warm '{}': {:#}", text, e),
+                }
+            }
+            tracing::info!("Pre-warmed {} of {} TTS phrases", warmed, config.tts_cache.prewarm.len());
+        });
+    }
+
+    /// Run a scheduled or scene action through the SetAlarm/Verbalise handlers
+    pub async fn run_action(&self, action: VoiceAction) -> anyhow::Result<()> {
+        let (success, message) = match action.into() {
+            ScheduleAction::Alarm(request) => {
+                let response = self
+                    .set_alarm(Request::new(request))
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{}", e.message()))?
+                    .into_inner();
+                (response.success, response.message)
+            }
+            ScheduleAction::Announce(request) => {
+                let response = self
+                    .verbalise(Request::new(request))
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{}", e.message()))?
+                    .into_inner();
+                (response.success, response.message)
+            }
+        };
+
+        if !success {
+            anyhow::bail!("{}", message);
+        }
+        Ok(())
+    }
+
+    /// `NOT_FOUND` unless `zone` is unset, `all` or one of the configured `outputs`
+    fn check_zone(&self, config: &Config, zone: Option<&str>) -> Result<(), Status> {
+        match zone {
+            Some(zone) if !config.has_zone(zone) => {
+                Err(Status::not_found(self.tr.t("error.zone_not_found", &[("zone", &zone)])))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop a one-shot schedule job from the config once it has run
+    ///
+    /// Only the run that fired (`scheduled`) is removed, so a job recreated meanwhile under the
+    /// same ID (snoozing again) survives.
+    pub async fn finish_one_shot(&self, job_id: &str, scheduled: DateTime<Utc>) {
+        let ran = |job: &Job<VoiceAction>| job.id == job_id && job.at.is_some_and(|at| at == scheduled);
+        if !self.config.get().schedule.jobs.iter().any(ran) {
+            return;
+        }
+        if let Err(e) = self.config.update(|c| c.schedule.jobs.retain(|job| !ran(job))).await {
+            tracing::warn!("Failed to remove finished schedule job '{}': {:#}", job_id, e);
+        }
+    }
+
+    /// A schedule job from a CreateSchedule request
+    fn schedule_job(&self, schedule: Schedule) -> Result<Job<VoiceAction>, Status> {
+        let at = match schedule.at_ms {
+            Some(at_ms) => Some(
+                i64::try_from(at_ms)
+                    .ok()
+                    .and_then(DateTime::from_timestamp_millis)
+                    .filter(|at| *at > Utc::now())
+                    .ok_or_else(|| Status::invalid_argument(self.tr.t("error.schedule_in_past", &[])))?
+                    .with_timezone(&Local)
+                    .fixed_offset(),
+            ),
+            None => None,
+        };
+
+        let action = match schedule.action {
+            Some(ScheduleAction::Alarm(request)) => VoiceAction::Alarm {
+                alarm_id: request.alarm_id,
+                enabled: request.enabled,
+                volume: request.volume,
+                zone: request.zone,
+            },
+            Some(ScheduleAction::Announce(request)) => VoiceAction::Announce {
+                text: Some(request.text).filter(|text| !text.is_empty()),
+                template: request.template,
+                args: request.args.into_iter().collect(),
+                notification_tone_id: request.notification_tone_id,
+                voice_id: request.voice_id,
+                volume: request.volume,
+                priority: request
+                    .priority
+                    .map(|priority| {
+                        priority.parse::<Priority>().map_err(|_| {
+                            Status::invalid_argument(self.tr.t("error.invalid_priority", &[("priority", &priority)]))
+                        })
+                    })
+                    .transpose()?,
+                zone: request.zone,
+            },
+            None => return Err(Status::invalid_argument(self.tr.t("error.schedule_action", &[]))),
+        };
+
+        Ok(Job {
+            id: schedule.id,
+            cron: schedule.cron.unwrap_or_default(),
+            at,
+            timezone: schedule.timezone,
+            enabled: true,
+            missed: if schedule.run_missed {
+                MissedRunPolicy::RunOnce
+            } else {
+                MissedRunPolicy::Skip
+            },
+            action,
         })
     }
+
+    /// Run a privileged RPC and record it in the audit log; errors are recorded as failures
+    async fn audited<T>(
+        &self,
+        event: AuditEvent,
+        client: Option<SocketAddr>,
+        call: impl Future<Output = Result<Response<T>, Status>>,
+    ) -> Result<Response<T>, Status> {
+        let result = call.await;
+        let failure = result.as_ref().err().map(|status| status.message().to_string());
+        self.record(event, client, failure);
+        result
+    }
+
+    /// Record an audit event; `client` is unset for scheduled, scene and MQTT actions
+    fn record(&self, event: AuditEvent, client: Option<SocketAddr>, failure: Option<String>) {
+        let event = match client {
+            Some(addr) => event.client(addr),
+            None => event,
+        };
+        self.audit.record(match failure {
+            Some(error) => event.failed(error),
+            None => event,
+        });
+    }
+
+    /// End open log streams so the gRPC server can drain
+    ///
+    /// Call when shutdown starts; streaming clients get `UNAVAILABLE: server_shutting_down`.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Stop alarms and let speech that is still playing finish
+    ///
+    /// Call once the gRPC server has stopped, so no new audio can be queued.
+    pub async fn finish_shutdown(&self) {
+        self.audio_manager.shutdown().await;
+    }
+}
+
+impl shq_scene::LocalActions<VoiceAction> for VoiceServiceImpl {
+    async fn run(&self, action: &VoiceAction) -> anyhow::Result<()> {
+        self.run_action(action.clone()).await
+    }
 }
 
 #[tonic::async_trait]
 impl VoiceService for VoiceServiceImpl {
-    async fn set_alarm(
+    async fn get_info(
         &self,
-        request: Request<SetAlarmRequest>,
-    ) -> Result<Response<SetAlarmResponse>, Status> {
+        request: Request<GetInfoRequest>,
+    ) -> Result<Response<GetInfoResponse>, Status> {
         let req = request.into_inner();
-        let alarm_id = req.alarm_id;
-        let enabled = req.enabled;
+        tracing::info!("GetInfo from client {:?}", req.client.as_deref().unwrap_or("unknown"));
 
-        tracing::info!("Setting alarm '{}' enabled={}", alarm_id, enabled);
+        let info = ServiceInfo::new(
+            "overwatch",
+            env!("CARGO_PKG_VERSION"),
+            PROTOCOL_VERSION,
+            CAPABILITIES,
+        )
+        .negotiate(&req.features);
+        if let Some(unsupported) = info.unsupported.as_ref().filter(|u| !u.is_empty()) {
+            tracing::debug!("GetInfo requested unsupported features: {:?}", unsupported);
+        }
 
-        let alarm_config = self
-            .config
-            .get_alarm(&alarm_id)
-            .ok_or_else(|| Status::not_found(format!("Alarm '{}' not found", alarm_id)))?;
+        Ok(Response::new(info.into()))
+    }
 
-        // Determine volume to use (either specified or default)
-        let volume = req.volume.unwrap_or(self.config.default_volume);
+    async fn get_health(
+        &self,
+        _request: Request<GetHealthRequest>,
+    ) -> Result<Response<GetHealthResponse>, Status> {
+        Ok(Response::new(self.collect_health().into()))
+    }
 
-        // Validate volume range (0.0 to 2.0)
-        if volume < 0.0 || volume > 2.0 {
-            return Err(Status::invalid_argument(
-                format!("Volume must be between 0.0 and 2.0, got {}", volume)
-            ));
-        }
+    async fn run_scene(
+        &self,
+        request: Request<RunSceneRequest>,
+    ) -> Result<Response<RunSceneResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "run_scene")
+            .detail(json!({ "scene": request.get_ref().scene }));
+        self.audited(event, client, async move {
+            let scene = request.into_inner().scene;
+            let config = self.config.get();
 
-        if volume > 1.0 {
-            tracing::warn!("Volume {} exceeds 1.0, may cause audio clipping", volume);
-        }
+            if !config.scene.scenes.iter().any(|s| s.id == scene) {
+                return Err(Status::not_found(self.tr.t("error.scene_not_found", &[("scene", &scene)])));
+            }
 
-        let result = if enabled {
-            // Start the alarm
-            match self
-                .audio_manager
-                .start_alarm(alarm_id.clone(), alarm_config.clone(), volume)
+            let report = config
+                .scene
+                .run(&scene, self)
                 .await
-            {
-                Ok(_) => SetAlarmResponse {
-                    success: true,
-                    message: format!("Alarm '{}' started", alarm_id),
-                },
-                Err(e) => SetAlarmResponse {
-                    success: false,
-                    message: format!("Failed to start alarm: {}", e),
-                },
+                .map_err(|e| Status::internal(self.tr.t("error.scene_failed", &[("error", &e)])))?;
+            Ok(Response::new(report.into()))
+        })
+        .await
+    }
+
+    async fn set_alarm(
+        &self,
+        request: Request<SetAlarmRequest>,
+    ) -> Result<Response<SetAlarmResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "set_alarm").detail(json!({
+            "alarm_id": request.get_ref().alarm_id,
+            "enabled": request.get_ref().enabled,
+            "volume": request.get_ref().volume,
+            "zone": request.get_ref().zone,
+        }));
+        let result = async move {
+            let req = request.into_inner();
+            let config = self.config.get();
+            let alarm_id = req.alarm_id;
+            let enabled = req.enabled;
+
+            tracing::info!("Setting alarm '{}' enabled={}", alarm_id, enabled);
+
+            let alarm = config
+                .get_alarm(&alarm_id)
+                .ok_or_else(|| Status::not_found(self.tr.t("error.alarm_not_found", &[("alarm", &alarm_id)])))?;
+            self.check_zone(&config, req.zone.as_deref())?;
+
+            // Determine volume to use (either specified or default)
+            let volume = req.volume.unwrap_or(config.default_volume);
+
+            // Validate volume range (0.0 to 2.0)
+            if !(0.0..=2.0).contains(&volume) {
+                return Err(Status::invalid_argument(self.tr.t("error.invalid_volume", &[("volume", &volume)])));
             }
-        } else {
-            // Stop the alarm
-            let stopped = self.audio_manager.stop_alarm(alarm_id.clone()).await;
-            SetAlarmResponse {
-                success: true,
-                message: if stopped {
-                    format!("Alarm '{}' stopped", alarm_id)
-                } else {
-                    format!("Alarm '{}' was not playing", alarm_id)
-                },
+
+            if volume > 1.0 {
+                tracing::warn!("Volume {} exceeds 1.0, may cause audio clipping", volume);
             }
-        };
 
-        Ok(Response::new(result))
+            let result = if enabled {
+                // Start the alarm
+                match self
+                    .audio_manager
+                    .start_alarm(
+                        alarm_id.clone(),
+                        alarm.path().clone(),
+                        volume,
+                        config.alarm_duck_volume(alarm),
+                        req.zone,
+                    )
+                    .await
+                {
+                    Ok(_) => SetAlarmResponse {
+                        success: true,
+                        message: format!("Alarm '{}' started", alarm_id),
+                    },
+                    Err(e) => SetAlarmResponse {
+                        success: false,
+                        message: self.tr.t("error.alarm_start_failed", &[("error", &e)]),
+                    },
+                }
+            } else {
+                // Stop the alarm
+                let stopped = self.audio_manager.stop_alarm(alarm_id.clone()).await;
+                SetAlarmResponse {
+                    success: true,
+                    message: if stopped {
+                        format!("Alarm '{}' stopped", alarm_id)
+                    } else {
+                        format!("Alarm '{}' was not playing", alarm_id)
+                    },
+                }
+            };
+
+            Ok(Response::new(result))
+        }
+        .await;
+
+        // Alarms that fail to start are reported in the reply, not as an error
+        let failure = match &result {
+            Ok(response) if !response.get_ref().success => Some(response.get_ref().message.clone()),
+            Ok(_) => None,
+            Err(status) => Some(status.message().to_string()),
+        };
+        self.record(event, client, failure);
+        result
     }
 
     async fn verbalise(
@@ -100,98 +721,482 @@ impl VoiceService for VoiceServiceImpl {
         request: Request<VerbaliseRequest>,
     ) -> Result<Response<VerbaliseResponse>, Status> {
         let req = request.into_inner();
-        let text = req.text;
+        let config = self.config.get();
+        let text = match req.template {
+            Some(_) if !req.text.is_empty() => {
+                return Err(Status::invalid_argument(self.tr.t("error.announcement_source", &[])));
+            }
+            Some(template) => {
+                let args = req.args.into_iter().collect();
+                self.tr
+                    .template(&format!("announce.{}", template), &args)
+                    .ok_or_else(|| {
+                        Status::not_found(self.tr.t("error.template_not_found", &[("template", &template)]))
+                    })?
+            }
+            None => req.text,
+        };
+        self.check_zone(&config, req.zone.as_deref())?;
         let notification_tone_id = req.notification_tone_id;
         let voice_id = req.voice_id;
+        let priority = match req.priority {
+            Some(priority) => priority.parse::<Priority>().map_err(|_| {
+                Status::invalid_argument(self.tr.t("error.invalid_priority", &[("priority", &priority)]))
+            })?,
+            None => Priority::default(),
+        };
 
         tracing::info!(
-            "Verbalising text: '{}' with tone={:?}, voice={:?}",
+            "Verbalising text: '{}' with tone={:?}, voice={:?}, priority={}, zone={:?}",
             text,
             notification_tone_id,
-            voice_id
+            voice_id,
+            priority.as_str(),
+            req.zone
         );
 
         // Determine voice to use (either specified or default)
-        let voice_name = voice_id.unwrap_or_else(|| self.config.default_voice.clone());
+        let voice_name = voice_id.unwrap_or_else(|| config.default_voice.clone());
 
         // Determine volume to use (either specified or default)
-        let volume = req.volume.unwrap_or(self.config.default_volume);
+        let volume = req.volume.unwrap_or(config.default_volume);
 
         // Validate volume range (0.0 to 2.0)
-        if volume < 0.0 || volume > 2.0 {
-            return Err(Status::invalid_argument(
-                format!("Volume must be between 0.0 and 2.0, got {}", volume)
-            ));
+        if !(0.0..=2.0).contains(&volume) {
+            return Err(Status::invalid_argument(self.tr.t("error.invalid_volume", &[("volume", &volume)])));
         }
 
         if volume > 1.0 {
             tracing::warn!("Volume {} exceeds 1.0, may cause audio clipping", volume);
         }
 
-        // Start TTS synthesis immediately (in parallel with notification tone)
         tracing::info!(
             "Starting TTS synthesis: voice='{}', engine='{}', text_length={}",
             voice_name,
-            self.config.default_engine,
+            config.default_engine,
             text.len()
         );
 
-        let tts_service = Arc::clone(&self.tts_service);
-        let text_clone = text.clone();
-        let voice_name_clone = voice_name.clone();
-        let engine_clone = self.config.default_engine.clone();
-
-        let synthesis_task = tokio::spawn(async move {
-            tts_service
-                .synthesize(&text_clone, &voice_name_clone, &engine_clone)
-                .await
-        });
-
-        // Play notification tone while synthesis is happening
-        if let Some(tone_id) = notification_tone_id {
-            if let Some(tone_path) = self.config.get_notification_tone(&tone_id) {
-                if let Err(e) = self
-                    .audio_manager
-                    .play_file(tone_path.clone(), volume)
-                    .await
-                {
-                    tracing::warn!("Failed to play notification tone: {}", e);
-                }
-                // Small delay to let the tone finish playing
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-            } else {
-                tracing::warn!("Notification tone '{}' not found", tone_id);
-            }
-        }
-
-        // Wait for TTS synthesis to complete
-        let audio_data = synthesis_task
+        // Long texts play from the first chunk while the rest are still being synthesised
+        let (first_chunk, more) = self
+            .tts_service
+            .synthesize_chunks(&text, &voice_name, &config.default_engine, &config.synthesis)
             .await
-            .map_err(|e| Status::internal(format!("TTS synthesis task failed: {}", e)))?
             .map_err(|e| {
                 tracing::error!(
                     "TTS synthesis failed: voice='{}', engine='{}', error={}",
                     voice_name,
-                    self.config.default_engine,
+                    config.default_engine,
                     e
                 );
-                Status::internal(format!(
-                    "TTS synthesis failed for voice '{}' with engine '{}': {}",
-                    voice_name, self.config.default_engine, e
+                Status::internal(self.tr.t(
+                    "error.tts_failed",
+                    &[("voice", &voice_name), ("engine", &config.default_engine), ("error", &e)],
                 ))
             })?;
 
-        // Play synthesized audio
-        self.audio_manager
-            .play_bytes(audio_data, volume)
+        // The tone plays right before the speech, so nothing queued can come between them
+        let tone = notification_tone_id.and_then(|tone_id| {
+            let path = config.get_notification_tone(&tone_id).cloned();
+            if path.is_none() {
+                tracing::warn!("Notification tone '{}' not found", tone_id);
+            }
+            path
+        });
+
+        let utterance = Utterance {
+            text,
+            priority,
+            tone,
+            speech: vec![first_chunk.into()],
+            more,
+            volume,
+            zone: req.zone,
+        };
+        let position = self
+            .audio_manager
+            .speak(utterance, config.playback)
             .await
-            .map_err(|e| Status::internal(format!("Audio playback failed: {}", e)))?;
+            .map_err(|e| Status::internal(self.tr.t("error.playback_failed", &[("error", &e)])))?;
 
-        let response = VerbaliseResponse {
-            success: true,
-            message: "Speech synthesised and played successfully".to_string(),
+        let message = match position {
+            QueuePosition::Playing => "Speech synthesised and playing".to_string(),
+            QueuePosition::Queued(ahead) => format!("Speech synthesised and queued behind {} message(s)", ahead),
         };
+        Ok(Response::new(VerbaliseResponse { success: true, message }))
+    }
 
-        Ok(Response::new(response))
+    async fn stop_all(
+        &self,
+        request: Request<StopAllRequest>,
+    ) -> Result<Response<StopAllResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "stop_all");
+        self.audited(event, client, async move {
+            let silenced = self
+                .audio_manager
+                .stop_all()
+                .await
+                .map_err(|e| Status::internal(self.tr.t("error.playback_failed", &[("error", &e)])))?;
+            Ok(Response::new(silenced.into()))
+        })
+        .await
+    }
+
+    async fn purge_cache(
+        &self,
+        request: Request<PurgeCacheRequest>,
+    ) -> Result<Response<PurgeCacheResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "purge_cache")
+            .detail(json!({ "include_prewarmed": request.get_ref().include_prewarmed }));
+        self.audited(event, client, async move {
+            let (files, bytes) = self
+                .tts_service
+                .purge(request.into_inner().include_prewarmed)
+                .map_err(|e| Status::internal(self.tr.t("error.purge_failed", &[("error", &e)])))?;
+            Ok(Response::new(PurgeCacheResponse { files, bytes }))
+        })
+        .await
+    }
+
+    async fn list_active(
+        &self,
+        _request: Request<ListActiveRequest>,
+    ) -> Result<Response<ListActiveResponse>, Status> {
+        let alarms = self
+            .audio_manager
+            .list_active()
+            .await
+            .map_err(|e| Status::internal(self.tr.t("error.playback_failed", &[("error", &e)])))?;
+        Ok(Response::new(ListActiveResponse {
+            alarms: alarms.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn snooze_alarm(
+        &self,
+        request: Request<SnoozeAlarmRequest>,
+    ) -> Result<Response<SnoozeAlarmResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "snooze_alarm").detail(json!({
+            "alarm_id": request.get_ref().alarm_id,
+            "minutes": request.get_ref().minutes,
+        }));
+        self.audited(event, client, async move {
+            let req = request.into_inner();
+            let config = self.config.get();
+            let alarm_id = req.alarm_id;
+
+            if config.get_alarm(&alarm_id).is_none() {
+                return Err(Status::not_found(self.tr.t("error.alarm_not_found", &[("alarm", &alarm_id)])));
+            }
+            let minutes = req.minutes.unwrap_or(config.snooze_minutes);
+            if minutes == 0 {
+                return Err(Status::invalid_argument(self.tr.t("error.invalid_snooze", &[])));
+            }
+
+            let active = self
+                .audio_manager
+                .list_active()
+                .await
+                .map_err(|e| Status::internal(self.tr.t("error.playback_failed", &[("error", &e)])))?
+                .into_iter()
+                .find(|alarm| alarm.alarm_id == alarm_id)
+                .ok_or_else(|| {
+                    Status::failed_precondition(self.tr.t("error.alarm_not_sounding", &[("alarm", &alarm_id)]))
+                })?;
+
+            // Saved before the alarm stops, so a failure leaves it sounding rather than lost.
+            // The restart runs even if overwatch was down when it fell due.
+            let until = Utc::now() + chrono::Duration::minutes(minutes.into());
+            let job = Job {
+                id: snooze_job_id(&alarm_id),
+                cron: String::new(),
+                at: Some(until.with_timezone(&Local).fixed_offset()),
+                timezone: None,
+                enabled: true,
+                missed: MissedRunPolicy::RunOnce,
+                action: VoiceAction::Alarm {
+                    alarm_id: alarm_id.clone(),
+                    enabled: true,
+                    volume: Some(active.volume),
+                    zone: active.zone,
+                },
+            };
+            let job_id = job.id.clone();
+            self.config
+                .update(|c| {
+                    c.schedule.jobs.retain(|existing| existing.id != job.id);
+                    c.schedule.jobs.push(job);
+                })
+                .await
+                .map_err(|e| Status::internal(self.tr.t("error.schedule_failed", &[("error", &format!("{:#}", e))])))?;
+
+            self.audio_manager.stop_alarm(alarm_id.clone()).await;
+            tracing::info!("Snoozed alarm '{}' until {}", alarm_id, until.with_timezone(&Local));
+
+            Ok(Response::new(SnoozeAlarmResponse {
+                job_id,
+                until_ms: until.timestamp_millis() as u64,
+            }))
+        })
+        .await
+    }
+
+    async fn create_schedule(
+        &self,
+        request: Request<CreateScheduleRequest>,
+    ) -> Result<Response<CreateScheduleResponse>, Status> {
+        let client = request.remote_addr();
+        let schedule = request.get_ref().schedule.as_ref();
+        let event = AuditEvent::new(AuditKind::Command, "create_schedule").detail(json!({
+            "id": schedule.map(|s| &s.id),
+            "cron": schedule.and_then(|s| s.cron.as_ref()),
+            "at_ms": schedule.and_then(|s| s.at_ms),
+        }));
+        self.audited(event, client, async move {
+            let schedule = request
+                .into_inner()
+                .schedule
+                .ok_or_else(|| Status::invalid_argument(self.tr.t("error.schedule_action", &[])))?;
+            let job = self.schedule_job(schedule)?;
+
+            // Validated with the rest of the config: unique ID, cron or at, known alarm
+            let saved = job.clone();
+            self.config
+                .update(|c| c.schedule.jobs.push(saved))
+                .await
+                .map_err(|e| {
+                    Status::invalid_argument(self.tr.t("error.schedule_failed", &[("error", &format!("{:#}", e))]))
+                })?;
+
+            let next_run = self.config.get().schedule.next_run(&job);
+            tracing::info!("Created schedule job '{}', next run {:?}", job.id, next_run);
+            Ok(Response::new(CreateScheduleResponse {
+                next_run_ms: next_run.map(|t| t.timestamp_millis() as u64),
+            }))
+        })
+        .await
+    }
+
+    async fn list_schedules(
+        &self,
+        _request: Request<ListSchedulesRequest>,
+    ) -> Result<Response<ListSchedulesResponse>, Status> {
+        let schedule = self.config.get().schedule;
+        let schedules = schedule
+            .jobs
+            .iter()
+            .map(|job| Schedule {
+                id: job.id.clone(),
+                cron: Some(job.cron.clone()).filter(|cron| !cron.is_empty()),
+                at_ms: job.at.map(|at| at.timestamp_millis() as u64),
+                timezone: job.timezone.clone(),
+                enabled: job.enabled,
+                run_missed: job.missed == MissedRunPolicy::RunOnce,
+                action: Some(job.action.clone().into()),
+                next_run_ms: schedule.next_run(job).map(|t| t.timestamp_millis() as u64),
+            })
+            .collect();
+
+        Ok(Response::new(ListSchedulesResponse {
+            timezone: schedule.timezone,
+            schedules,
+        }))
+    }
+
+    async fn cancel_schedule(
+        &self,
+        request: Request<CancelScheduleRequest>,
+    ) -> Result<Response<CancelScheduleResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "cancel_schedule")
+            .detail(json!({ "id": request.get_ref().id }));
+        self.audited(event, client, async move {
+            let id = request.into_inner().id;
+            if !self.config.get().schedule.jobs.iter().any(|job| job.id == id) {
+                return Err(Status::not_found(self.tr.t("error.schedule_not_found", &[("id", &id)])));
+            }
+
+            self.config
+                .update(|c| c.schedule.jobs.retain(|job| job.id != id))
+                .await
+                .map_err(|e| Status::internal(self.tr.t("error.schedule_failed", &[("error", &format!("{:#}", e))])))?;
+            tracing::info!("Cancelled schedule job '{}'", id);
+            Ok(Response::new(CancelScheduleResponse {}))
+        })
+        .await
+    }
+
+    async fn get_queue(
+        &self,
+        _request: Request<GetQueueRequest>,
+    ) -> Result<Response<GetQueueResponse>, Status> {
+        let status = self
+            .audio_manager
+            .queue_status()
+            .await
+            .map_err(|e| Status::internal(self.tr.t("error.playback_failed", &[("error", &e)])))?;
+        Ok(Response::new(status.into()))
+    }
+
+    async fn export_config(
+        &self,
+        request: Request<ExportConfigRequest>,
+    ) -> Result<Response<ExportConfigResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "export_config")
+            .detail(json!({ "include_peers": request.get_ref().include_peers }));
+        self.audited(event, client, async move {
+            let req = request.into_inner();
+
+            self.config
+                .get()
+                .backup
+                .authorize(req.token.as_deref())
+                .map_err(|e| Status::permission_denied(self.tr.t("error.export_refused", &[("error", &e)])))?;
+
+            let (archive, peers) = backup::export(&self.config, req.include_peers)
+                .await
+                .map_err(|e| Status::internal(self.tr.t("error.export_failed", &[("error", &format!("{:#}", e))])))?;
+            let archive = serde_json::to_string_pretty(&archive).map_err(|e| Status::internal(e.to_string()))?;
+
+            tracing::info!("Exported config archive ({} peers)", peers.len());
+            Ok(Response::new(ExportConfigResponse {
+                archive,
+                peers: peers.into_iter().map(Into::into).collect(),
+            }))
+        })
+        .await
+    }
+
+    async fn import_config(
+        &self,
+        request: Request<ImportConfigRequest>,
+    ) -> Result<Response<ImportConfigResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "import_config")
+            .detail(json!({ "include_peers": request.get_ref().include_peers }));
+        self.audited(event, client, async move {
+            let req = request.into_inner();
+
+            self.config
+                .get()
+                .backup
+                .authorize(req.token.as_deref())
+                .map_err(|e| Status::permission_denied(self.tr.t("error.import_refused", &[("error", &e)])))?;
+
+            let archive: ConfigArchive = serde_json::from_str(&req.archive)
+                .map_err(|e| Status::invalid_argument(self.tr.t("error.invalid_archive", &[("error", &e)])))?;
+
+            // Hot-reloadable settings apply through the config reload task; the rest on restart
+            let results = backup::import(&self.config, &archive, req.include_peers).await;
+            if results.is_empty() {
+                return Err(Status::invalid_argument(self.tr.t("error.nothing_to_restore", &[])));
+            }
+
+            Ok(Response::new(ImportConfigResponse {
+                success: results.iter().all(|r| r.result.is_ok()),
+                results: results.into_iter().map(Into::into).collect(),
+            }))
+        })
+        .await
+    }
+
+    async fn get_audit_log(
+        &self,
+        request: Request<GetAuditLogRequest>,
+    ) -> Result<Response<GetAuditLogResponse>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "get_audit_log");
+        self.audited(event, client, async move {
+            let req = request.into_inner();
+
+            self.config
+                .get()
+                .audit
+                .query
+                .authorize(req.token.as_deref())
+                .map_err(|e| Status::permission_denied(self.tr.t("error.audit_refused", &[("error", &e)])))?;
+
+            let kind = req
+                .kind
+                .map(|kind| kind.parse())
+                .transpose()
+                .map_err(|e: anyhow::Error| Status::invalid_argument(e.to_string()))?;
+            let query = AuditQuery {
+                after_seq: req.after_seq,
+                limit: req.limit.map(|limit| limit as usize),
+                kinds: kind.into_iter().collect(),
+                ..Default::default()
+            };
+            let page = self
+                .audit
+                .query(&query)
+                .map_err(|e| Status::internal(self.tr.t("error.audit_failed", &[("error", &format!("{:#}", e))])))?;
+            Ok(Response::new(page.into()))
+        })
+        .await
+    }
+
+        type StreamLogsStream = ReceiverStream<Result<LogEntry, Status>>;
+
+    async fn stream_logs(
+        &self,
+        request: Request<StreamLogsRequest>,
+    ) -> Result<Response<Self::StreamLogsStream>, Status> {
+        let client = request.remote_addr();
+        let event = AuditEvent::new(AuditKind::Command, "stream_logs");
+        self.audited(event, client, async move {
+            let req = request.into_inner();
+
+            self.config
+                .get()
+                .logging
+                .stream
+                .authorize(req.token.as_deref())
+                .map_err(|e| Status::permission_denied(self.tr.t("error.logs_refused", &[("error", &e)])))?;
+
+            let lines = req.lines.map_or(DEFAULT_LOG_BACKLOG, |n| n as usize);
+            let (backlog, mut log_rx) = self.log_handle.subscribe(lines);
+            let (tx, rx) = mpsc::channel(64);
+            let mut shutdown = self.shutdown.subscribe();
+            let events = self.events.clone();
+            let id = req.id;
+
+            tracing::info!("Log stream client subscribed");
+
+            // Forward log lines until the client goes away
+            tokio::spawn(async move {
+                for line in backlog {
+                    if tx.send(Ok(events.log_entry(&id, line))).await.is_err() {
+                        return;
+                    }
+                }
+
+                loop {
+                    tokio::select! {
+                        received = log_rx.recv() => match received {
+                            Ok(line) => {
+                                if tx.send(Ok(events.log_entry(&id, line))).await.is_err() {
+                                    break;
+                                }
+                            }
+                            // Dropped lines are acceptable for a debugging stream
+                            Err(broadcast::error::RecvError::Lagged(_)) => {}
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        },
+                        _ = async { shutdown.wait_for(|shutting_down| *shutting_down).await.is_ok() } => {
+                            let _ = tx.send(Err(Status::unavailable("server_shutting_down"))).await;
+                            break;
+                        }
+                    }
+                }
+            });
+
+            Ok(Response::new(ReceiverStream::new(rx)))
+        })
+        .await
     }
 }
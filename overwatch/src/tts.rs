@@ -1,17 +1,64 @@
 use aws_sdk_polly::types::{Engine, OutputFormat, VoiceId};
 use aws_sdk_polly::Client as PollyClient;
 use aws_config::BehaviorVersion;
-use crate::config::AwsConfig;
+use crate::config::{AwsConfig, SynthesisConfig, TtsBackend, TtsCacheConfig};
 use sha2::{Sha256, Digest};
+use shq_protocol::ComponentHealth;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
 
 pub struct TtsService {
-    client: PollyClient,
+    client: Option<PollyClient>, // None with the stub backend
     cache_dir: PathBuf,
+    max_cache_bytes: Option<u64>, // None for no limit
+    pinned: Mutex<HashSet<String>>, // Cache keys of pre-warmed phrases, which eviction spares
+    last_error: Mutex<Option<String>>, // Most recent Polly failure, cleared by the next success
+}
+
+/// An audio file in the cache
+struct CacheEntry {
+    key: String,
+    path: PathBuf,
+    size: u64,
+    /// Modification time, which a cache hit updates
+    used: SystemTime,
 }
 
 impl TtsService {
-    pub async fn new(aws_config: Option<&AwsConfig>) -> Self {
+    pub async fn new(backend: TtsBackend, aws_config: Option<&AwsConfig>, cache: &TtsCacheConfig) -> Self {
+        let client = match backend {
+            TtsBackend::Polly => Some(Self::polly_client(aws_config).await),
+            TtsBackend::Stub => {
+                tracing::warn!("Using stub TTS backend - speech will be silent");
+                None
+            }
+        };
+
+        // Set up cache directory
+        let cache_dir = cache.dir.clone();
+        if let Err(e) = std::fs::create_dir_all(&cache_dir) {
+            tracing::warn!("Failed to create TTS cache directory: {}", e);
+        }
+
+        let service = Self {
+            client,
+            cache_dir,
+            max_cache_bytes: (cache.max_size_mb > 0).then(|| cache.max_size_mb * 1024 * 1024),
+            pinned: Mutex::new(HashSet::new()),
+            last_error: Mutex::new(None),
+        };
+        // The limit may have been lowered since the last run
+        service.evict();
+        service
+    }
+
+    async fn polly_client(aws_config: Option<&AwsConfig>) -> PollyClient {
         let config = if let Some(aws_cfg) = aws_config {
             let mut loader = aws_config::defaults(BehaviorVersion::latest());
 
@@ -38,15 +85,7 @@ impl TtsService {
             aws_config::load_from_env().await
         };
 
-        let client = PollyClient::new(&config);
-
-        // Set up cache directory
-        let cache_dir = PathBuf::from("./cache/tts");
-        if let Err(e) = std::fs::create_dir_all(&cache_dir) {
-            tracing::warn!("Failed to create TTS cache directory: {}", e);
-        }
-
-        Self { client, cache_dir }
+        PollyClient::new(&config)
     }
 
     pub async fn synthesize(
@@ -55,6 +94,13 @@ impl TtsService {
         voice_name: &str,
         engine_name: &str,
     ) -> anyhow::Result<Vec<u8>> {
+        let Some(client) = &self.client else {
+            // Stub backend: validate like Polly would, then return silence
+            self.parse_voice_id(voice_name)?;
+            self.parse_engine(engine_name)?;
+            return Ok(silent_wav());
+        };
+
         // Generate cache key from voice, engine, and text
         let cache_key = self.generate_cache_key(text, voice_name, engine_name);
 
@@ -74,8 +120,7 @@ impl TtsService {
             text.len()
         );
 
-        let response = match self
-            .client
+        let response = match client
             .synthesize_speech()
             .engine(engine.clone())
             .output_format(OutputFormat::Mp3)
@@ -92,6 +137,7 @@ impl TtsService {
                     engine,
                     e
                 );
+                *self.last_error.lock().unwrap() = Some(e.to_string());
                 return Err(anyhow::anyhow!(
                     "AWS Polly error for voice '{}' with engine '{:?}': {}",
                     voice_name,
@@ -105,6 +151,7 @@ impl TtsService {
             Ok(audio_stream) => {
                 let bytes = audio_stream.into_bytes().to_vec();
                 tracing::info!("Successfully synthesized {} bytes of audio", bytes.len());
+                *self.last_error.lock().unwrap() = None;
 
                 // Save to cache (ignore errors - caching is non-critical)
                 if let Err(e) = self.save_to_cache(&cache_key, &bytes) {
@@ -115,11 +162,133 @@ impl TtsService {
             }
             Err(e) => {
                 tracing::error!("Failed to collect audio stream: {:?}", e);
+                *self.last_error.lock().unwrap() = Some(e.to_string());
                 Err(anyhow::anyhow!("Failed to collect audio stream: {}", e))
             }
         }
     }
 
+    /// Synthesise `text` in chunks (see [`SynthesisConfig`]), several at a time
+    ///
+    /// Returns once the first chunk is ready, with its audio and, for longer texts, a channel
+    /// that delivers the others in order as they're synthesised. A failure of the first chunk is
+    /// returned; a later one is logged and ends the channel early.
+    pub async fn synthesize_chunks(
+        self: &Arc<Self>,
+        text: &str,
+        voice_name: &str,
+        engine_name: &str,
+        config: &SynthesisConfig,
+    ) -> anyhow::Result<(Vec<u8>, Option<mpsc::UnboundedReceiver<Arc<[u8]>>>)> {
+        let mut chunks = split_text(text, config.max_chunk_chars);
+        if chunks.is_empty() {
+            chunks.push(text.to_string());
+        }
+        if chunks.len() > 1 {
+            tracing::info!("Synthesizing {} characters in {} chunks", text.chars().count(), chunks.len());
+        }
+
+        let permits = Arc::new(Semaphore::new(config.concurrency));
+        let mut tasks: VecDeque<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                let tts = Arc::clone(self);
+                let permits = Arc::clone(&permits);
+                let voice_name = voice_name.to_string();
+                let engine_name = engine_name.to_string();
+                tokio::spawn(
+                    async move {
+                        let _permit = permits.acquire_owned().await?;
+                        tts.synthesize(&chunk, &voice_name, &engine_name).await
+                    }
+                    .in_current_span(),
+                )
+            })
+            .collect();
+
+        let first = tasks.pop_front().expect("there is always at least one chunk");
+        let first = match join(first).await {
+            Ok(audio) => audio,
+            Err(e) => {
+                tasks.iter().for_each(|task| task.abort());
+                return Err(e);
+            }
+        };
+        if tasks.is_empty() {
+            return Ok((first, None));
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(
+            async move {
+                while let Some(task) = tasks.pop_front() {
+                    let audio = match join(task).await {
+                        Ok(audio) => audio,
+                        Err(e) => {
+                            tracing::error!("TTS synthesis of a later chunk failed, cutting the message short: {}", e);
+                            break;
+                        }
+                    };
+                    // The message was stopped or the audio thread is gone
+                    if tx.send(audio.into()).is_err() {
+                        break;
+                    }
+                }
+                tasks.iter().for_each(|task| task.abort());
+            }
+            .in_current_span(),
+        );
+        Ok((first, Some(rx)))
+    }
+
+    /// Cache `text` the way Verbalise would synthesise it, and keep it from being evicted
+    pub async fn prewarm(
+        &self,
+        text: &str,
+        voice_name: &str,
+        engine_name: &str,
+        config: &SynthesisConfig,
+    ) -> anyhow::Result<()> {
+        for chunk in split_text(text, config.max_chunk_chars) {
+            let cache_key = self.generate_cache_key(&chunk, voice_name, engine_name);
+            self.pinned.lock().unwrap().insert(cache_key);
+            self.synthesize(&chunk, voice_name, engine_name).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete cached audio, sparing pre-warmed phrases unless `include_prewarmed`; returns the
+    /// number of files and bytes deleted
+    pub fn purge(&self, include_prewarmed: bool) -> anyhow::Result<(u32, u64)> {
+        let pinned = self.pinned.lock().unwrap().clone();
+        let (mut files, mut bytes) = (0, 0);
+        for entry in self.cache_entries()? {
+            if !include_prewarmed && pinned.contains(&entry.key) {
+                continue;
+            }
+            match std::fs::remove_file(&entry.path) {
+                Ok(()) => {
+                    files += 1;
+                    bytes += entry.size;
+                }
+                Err(e) => tracing::warn!("Failed to delete {}: {}", entry.path.display(), e),
+            }
+        }
+
+        tracing::info!("Purged {} files ({} bytes) from the TTS cache", files, bytes);
+        Ok((files, bytes))
+    }
+
+    /// Polly health based on the most recent request; cached phrases play regardless
+    pub fn health(&self) -> ComponentHealth {
+        match self.last_error.lock().unwrap().as_ref() {
+            None => ComponentHealth::ok("tts"),
+            Some(error) => {
+                ComponentHealth::degraded("tts", format!("Last AWS Polly request failed: {}", error))
+            }
+        }
+    }
+
     fn parse_engine(&self, engine_name: &str) -> anyhow::Result<Engine> {
         match engine_name.to_lowercase().as_str() {
             "neural" => Ok(Engine::Neural),
@@ -204,6 +373,14 @@ impl TtsService {
         match std::fs::read(&cache_path) {
             Ok(data) => {
                 tracing::info!("TTS cache hit: {} ({} bytes)", cache_key, data.len());
+                // Eviction goes by modification time, so a hit counts as a use
+                let touched = File::options()
+                    .write(true)
+                    .open(&cache_path)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                if let Err(e) = touched {
+                    tracing::debug!("Failed to mark {} as used: {}", cache_key, e);
+                }
                 Some(data)
             }
             Err(_) => {
@@ -217,6 +394,161 @@ impl TtsService {
         let cache_path = self.get_cache_path(cache_key);
         std::fs::write(&cache_path, data)?;
         tracing::info!("Saved to TTS cache: {} ({} bytes)", cache_key, data.len());
+        self.evict();
         Ok(())
     }
+
+    /// Delete the least recently used audio until the cache is under its size limit, sparing
+    /// pre-warmed phrases
+    fn evict(&self) {
+        let Some(max_bytes) = self.max_cache_bytes else {
+            return;
+        };
+        let mut entries = match self.cache_entries() {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("Failed to read TTS cache directory: {}", e);
+                return;
+            }
+        };
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        if total <= max_bytes {
+            return;
+        }
+
+        entries.sort_by_key(|entry| entry.used);
+        let pinned = self.pinned.lock().unwrap();
+        let mut evicted = 0;
+        for entry in entries.iter().filter(|entry| !pinned.contains(&entry.key)) {
+            if total <= max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&entry.path).is_ok() {
+                total -= entry.size;
+                evicted += 1;
+            }
+        }
+        tracing::info!("Evicted {} files from the TTS cache, {} bytes left", evicted, total);
+    }
+
+    fn cache_entries(&self) -> std::io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.cache_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "mp3") {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let Ok(metadata) = path.metadata() else {
+                continue;
+            };
+            entries.push(CacheEntry {
+                key,
+                size: metadata.len(),
+                used: metadata.modified()?,
+                path,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// A chunk's synthesis result, or why its task died
+async fn join(task: JoinHandle<anyhow::Result<Vec<u8>>>) -> anyhow::Result<Vec<u8>> {
+    task.await?
+}
+
+/// Split text into chunks of at most `max_chars` characters, packing whole sentences together
+///
+/// A sentence longer than a chunk is split between words, and a word longer than a chunk
+/// wherever it has to be.
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for sentence in sentences(text) {
+        if sentence.chars().count() <= max_chars {
+            append(&mut chunks, &mut current, sentence, max_chars);
+            continue;
+        }
+
+        for mut word in sentence.split_whitespace() {
+            while word.chars().count() > max_chars {
+                let at = word.char_indices().nth(max_chars).map_or(word.len(), |(i, _)| i);
+                let (head, tail) = word.split_at(at);
+                if !current.is_empty() {
+                    chunks.push(std::mem::take(&mut current));
+                }
+                chunks.push(head.to_string());
+                word = tail;
+            }
+            append(&mut chunks, &mut current, word, max_chars);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Add `piece` to the chunk being built, starting a new one if it doesn't fit
+fn append(chunks: &mut Vec<String>, current: &mut String, piece: &str, max_chars: usize) {
+    if piece.is_empty() {
+        return;
+    }
+    if !current.is_empty() {
+        if current.chars().count() + 1 + piece.chars().count() > max_chars {
+            chunks.push(std::mem::take(current));
+        } else {
+            current.push(' ');
+        }
+    }
+    current.push_str(piece);
+}
+
+/// Sentences of `text`, ending at `.`, `!`, `?` or `…` before whitespace, or at a line break
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '.' | '!' | '?' | '…' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            '\n' => true,
+            _ => false,
+        };
+        if ends {
+            let end = i + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// 100ms of 16-bit mono silence as a WAV file, returned by the stub backend
+fn silent_wav() -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 16_000;
+    let data_len = SAMPLE_RATE / 10 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(44 + data_len as usize, 0);
+    wav
 }